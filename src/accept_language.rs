@@ -0,0 +1,133 @@
+//! `Accept-Language` parsing (RFC 9110 §12.5.4) and picking the best of a
+//! set of supported locales for it by RFC 4647 lookup.
+
+use serde::Serialize;
+
+use crate::format::parse_qvalue;
+
+/// One entry of an `Accept-Language` header.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LanguageRange {
+    /// As sent, e.g. `en-US` or `*`.
+    pub tag: String,
+    /// 0 to 1; 0 means "not acceptable".
+    pub q: f64,
+}
+
+/// The entries of `raw`, highest q first; equal ones keep the client's
+/// order. Malformed entries are skipped.
+pub fn parse(raw: &str) -> Vec<LanguageRange> {
+    let mut ranges: Vec<(u16, &str)> = raw
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let tag = parts.next()?.trim();
+            let valid = tag == "*"
+                || (!tag.is_empty()
+                    && tag.split('-').all(|sub| {
+                        (1..=8).contains(&sub.len())
+                            && sub.bytes().all(|b| b.is_ascii_alphanumeric())
+                    }));
+            if !valid {
+                return None;
+            }
+            let mut q = 1000;
+            for param in parts {
+                if let Some((name, value)) = param.split_once('=')
+                    && name.trim().eq_ignore_ascii_case("q")
+                {
+                    q = parse_qvalue(value.trim())?;
+                }
+            }
+            Some((q, tag))
+        })
+        .collect();
+    ranges.sort_by_key(|&(q, _)| std::cmp::Reverse(q));
+    ranges
+        .into_iter()
+        .map(|(q, tag)| LanguageRange {
+            tag: tag.to_string(),
+            q: f64::from(q) / 1000.0,
+        })
+        .collect()
+}
+
+/// The entry of `supported` the client prefers. Each range is tried in
+/// order: as is, then with subtags dropped from the end (`de-CH` tries
+/// `de`), then as a prefix of a supported locale (`en` finds `en-GB`).
+/// `*` takes the first supported locale not ruled out with `q=0`.
+pub fn best_match<'a>(ranges: &[LanguageRange], supported: &'a [String]) -> Option<&'a str> {
+    let refused = |locale: &str| {
+        ranges
+            .iter()
+            .any(|r| r.q == 0.0 && r.tag.eq_ignore_ascii_case(locale))
+    };
+    let candidates = || supported.iter().filter(|s| !refused(s));
+    for range in ranges.iter().filter(|r| r.q > 0.0) {
+        if range.tag == "*" {
+            if let Some(locale) = candidates().next() {
+                return Some(locale);
+            }
+            continue;
+        }
+        let mut tag = range.tag.as_str();
+        loop {
+            if let Some(locale) = candidates().find(|s| s.eq_ignore_ascii_case(tag)) {
+                return Some(locale);
+            }
+            match tag.rsplit_once('-') {
+                Some((shorter, _)) => tag = shorter,
+                None => break,
+            }
+        }
+        let prefix = format!("{}-", range.tag.to_ascii_lowercase());
+        if let Some(locale) = candidates().find(|s| s.to_ascii_lowercase().starts_with(&prefix)) {
+            return Some(locale);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(raw: &str) -> Vec<(String, f64)> {
+        parse(raw).into_iter().map(|r| (r.tag, r.q)).collect()
+    }
+
+    fn supported(locales: &[&str]) -> Vec<String> {
+        locales.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn orders_by_q_then_position() {
+        assert_eq!(
+            tags("fr;q=0.5, en-US, de;q=0.8, en;q=0.8, *;q=0.1"),
+            [
+                ("en-US".to_string(), 1.0),
+                ("de".to_string(), 0.8),
+                ("en".to_string(), 0.8),
+                ("fr".to_string(), 0.5),
+                ("*".to_string(), 0.1),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_malformed_entries() {
+        assert_eq!(tags("en;q=2, , x_y, de ;Q=0.3"), [("de".to_string(), 0.3)]);
+    }
+
+    #[test]
+    fn lookup() {
+        let ours = supported(&["en", "de-DE", "pt-BR"]);
+        let best = |raw: &str| best_match(&parse(raw), &ours);
+        assert_eq!(best("de-CH, en;q=0.5"), Some("en"));
+        assert_eq!(best("de, en;q=0.5"), Some("de-DE"));
+        assert_eq!(best("en-GB"), Some("en"));
+        assert_eq!(best("ja, *;q=0.1"), Some("en"));
+        assert_eq!(best("en;q=0, *"), Some("de-DE"));
+        assert_eq!(best("ja"), None);
+    }
+}
@@ -0,0 +1,298 @@
+//! Apache combined-format access log (`ACCESS_LOG`).
+//!
+//! One line per request, in the format GoAccess, AWStats and friends parse
+//! by default:
+//!
+//! ```text
+//! 203.0.113.9 - - [15/Oct/2026:05:04:06 +0000] "GET /ip HTTP/1.1" 200 10 "-" "curl/8.5.0"
+//! ```
+//!
+//! The host is the proxy-resolved client IP, not the peer. The line is
+//! written once the response body has been sent (or the client went away),
+//! so the byte count is what was actually transferred. Lines go through a
+//! bounded channel to a writer thread; if the disk can't keep up, lines are
+//! dropped and counted in `access_log_dropped_total` rather than stalling
+//! requests.
+
+use std::fs::OpenOptions;
+use std::io::{self, BufWriter, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+
+use axum::body::{Body, Bytes};
+use axum::extract::{ConnectInfo, State};
+use axum::http::{HeaderMap, Request, Response, header};
+use axum::middleware::Next;
+use http_body::{Body as HttpBody, Frame, SizeHint};
+
+use crate::client_ip::resolve_client_ip;
+use crate::datetime::UtcDateTime;
+use crate::state::AppState;
+
+/// Lines buffered between requests and the writer thread.
+const QUEUE_CAPACITY: usize = 8192;
+
+/// Handle to the access log writer thread.
+pub struct AccessLog {
+    tx: SyncSender<String>,
+}
+
+impl AccessLog {
+    /// Append to the file at `path`, creating it if needed. `-` writes to
+    /// stdout instead.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        if path == Path::new("-") {
+            return Ok(Self::from_writer(io::stdout()));
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::from_writer(file))
+    }
+
+    /// Write lines to `writer` on a background thread.
+    pub fn from_writer(writer: impl Write + Send + 'static) -> Self {
+        let (tx, rx) = mpsc::sync_channel(QUEUE_CAPACITY);
+        std::thread::Builder::new()
+            .name("access-log".into())
+            .spawn(move || write_lines(rx, writer))
+            .expect("failed to spawn access log thread");
+        Self { tx }
+    }
+
+    fn write(&self, line: String) {
+        match self.tx.try_send(line) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                metrics::counter!("access_log_dropped_total").increment(1);
+            }
+            // The writer thread died on an I/O error; it already logged why.
+            Err(TrySendError::Disconnected(_)) => {}
+        }
+    }
+}
+
+/// Drain `rx` into `writer`, flushing whenever the queue is empty so lines
+/// show up promptly without a syscall per line under load.
+fn write_lines(rx: Receiver<String>, writer: impl Write) {
+    let mut writer = BufWriter::new(writer);
+    while let Ok(line) = rx.recv() {
+        let mut result = writer.write_all(line.as_bytes());
+        while result.is_ok()
+            && let Ok(line) = rx.try_recv()
+        {
+            result = writer.write_all(line.as_bytes());
+        }
+        if let Err(e) = result.and_then(|()| writer.flush()) {
+            tracing::error!(error = %e, "access log write failed; access logging stopped");
+            return;
+        }
+    }
+}
+
+/// Log every request that passes through. Only installed when `ACCESS_LOG`
+/// is set.
+pub async fn access_log_middleware(
+    State(state): State<Arc<AppState>>,
+    request: Request<Body>,
+    next: Next,
+) -> Response<Body> {
+    let Some(log) = state.access_log.clone() else {
+        return next.run(request).await;
+    };
+
+    let client_ip =
+        request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| {
+                resolve_client_ip(addr.ip(), request.headers(), &state.config.load())
+            });
+    let entry = Entry {
+        client_ip,
+        time: SystemTime::now(),
+        request_line: format!(
+            "{} {} {:?}",
+            request.method(),
+            request.uri().path_and_query().map_or("/", |p| p.as_str()),
+            request.version()
+        ),
+        status: 0,
+        referer: header_value(request.headers(), header::REFERER),
+        user_agent: header_value(request.headers(), header::USER_AGENT),
+    };
+
+    let response = next.run(request).await;
+    let entry = Entry {
+        status: response.status().as_u16(),
+        ..entry
+    };
+    response.map(|body| {
+        Body::new(LoggedBody {
+            inner: body,
+            bytes: 0,
+            pending: Some((entry, log)),
+        })
+    })
+}
+
+fn header_value(headers: &HeaderMap, name: header::HeaderName) -> Option<String> {
+    headers
+        .get(name)
+        .map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned())
+}
+
+/// What's known about a request before its body is sent.
+struct Entry {
+    client_ip: Option<IpAddr>,
+    time: SystemTime,
+    request_line: String,
+    status: u16,
+    referer: Option<String>,
+    user_agent: Option<String>,
+}
+
+impl Entry {
+    /// `%h %l %u %t "%r" %>s %b "%{Referer}i" "%{User-Agent}i"`.
+    fn format(&self, bytes: u64) -> String {
+        let host = self
+            .client_ip
+            .map_or_else(|| "-".to_string(), |ip| ip.to_string());
+        let bytes = match bytes {
+            0 => "-".to_string(),
+            n => n.to_string(),
+        };
+        format!(
+            "{host} - - [{}] \"{}\" {} {bytes} \"{}\" \"{}\"\n",
+            clf_time(self.time),
+            escape(&self.request_line),
+            self.status,
+            self.referer.as_deref().map_or_else(|| "-".into(), escape),
+            self.user_agent
+                .as_deref()
+                .map_or_else(|| "-".into(), escape),
+        )
+    }
+}
+
+/// Escape quotes, backslashes and control characters the way Apache does,
+/// so a crafted header can't break the line apart.
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => {
+                for b in c.to_string().bytes() {
+                    out.push_str(&format!("\\x{b:02x}"));
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// `15/Oct/2026:05:04:06 +0000`. Always UTC.
+fn clf_time(time: SystemTime) -> String {
+    let t = UtcDateTime::from_system_time(time);
+    format!(
+        "{:02}/{}/{}:{:02}:{:02}:{:02} +0000",
+        t.day,
+        t.month_abbr(),
+        t.year,
+        t.hour,
+        t.minute,
+        t.second
+    )
+}
+
+/// Counts body bytes and writes the log line when dropped, i.e. after the
+/// last frame was sent or the connection closed.
+struct LoggedBody {
+    inner: Body,
+    bytes: u64,
+    pending: Option<(Entry, Arc<AccessLog>)>,
+}
+
+impl HttpBody for LoggedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &poll
+            && let Some(data) = frame.data_ref()
+        {
+            self.bytes += data.len() as u64;
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Drop for LoggedBody {
+    fn drop(&mut self) {
+        if let Some((entry, log)) = self.pending.take() {
+            log.write(entry.format(self.bytes));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+
+    #[test]
+    fn formats_combined_line() {
+        let entry = Entry {
+            client_ip: Some("203.0.113.9".parse().unwrap()),
+            time: UNIX_EPOCH + Duration::from_secs(1_792_040_646),
+            request_line: "GET /ip?format=json HTTP/1.1".into(),
+            status: 200,
+            referer: None,
+            user_agent: Some("curl/8.5.0".into()),
+        };
+        assert_eq!(
+            entry.format(10),
+            "203.0.113.9 - - [15/Oct/2026:05:04:06 +0000] \"GET /ip?format=json HTTP/1.1\" 200 10 \"-\" \"curl/8.5.0\"\n"
+        );
+    }
+
+    #[test]
+    fn empty_body_and_unknown_client_are_dashes() {
+        let entry = Entry {
+            client_ip: None,
+            time: UNIX_EPOCH,
+            request_line: "HEAD / HTTP/1.1".into(),
+            status: 204,
+            referer: None,
+            user_agent: None,
+        };
+        assert_eq!(
+            entry.format(0),
+            "- - - [01/Jan/1970:00:00:00 +0000] \"HEAD / HTTP/1.1\" 204 - \"-\" \"-\"\n"
+        );
+    }
+
+    #[test]
+    fn quotes_and_control_characters_are_escaped() {
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\x0ad");
+    }
+}
@@ -0,0 +1,73 @@
+//! Automatic certificate provisioning over ACME (Let's Encrypt).
+//!
+//! Uses the TLS-ALPN-01 challenge, so validation happens on the HTTPS
+//! listener itself and no port 80 is needed. Certificates and the account
+//! key are cached in `ACME_CACHE_DIR`; a background task orders the initial
+//! certificate, renews it ahead of expiry, and retries with back-off on
+//! failure. Until the first certificate is available, handshakes fail.
+
+use std::io;
+
+use futures::StreamExt;
+use rustls_acme::AcmeConfig;
+use rustls_acme::caches::DirCache;
+
+use crate::config::Config;
+use crate::listener::tls::{self, TlsConfig};
+
+/// Start the ACME state machine for `config.acme_domains` and return the
+/// TLS config the listener should use. Must be called inside a Tokio
+/// runtime.
+pub fn start(config: &Config) -> io::Result<TlsConfig> {
+    let mut state = AcmeConfig::new_with_provider(&config.acme_domains, tls::provider())
+        .contact(config.acme_contact.iter().map(|c| contact_uri(c)))
+        .cache(DirCache::new(config.acme_cache_dir.clone()))
+        .directory_lets_encrypt(!config.acme_staging)
+        .state();
+
+    let tls = TlsConfig::from_acme(
+        state.resolver(),
+        state.challenge_rustls_config_with_provider(tls::provider()),
+        config.tls_client_auth,
+    )?;
+
+    tokio::spawn(async move {
+        while let Some(event) = state.next().await {
+            match event {
+                Ok(ok) => {
+                    tracing::info!(event = ?ok, "ACME");
+                    metrics::counter!("acme_events_total", "result" => "ok").increment(1);
+                }
+                Err(e) => {
+                    tracing::warn!(error = %e, "ACME");
+                    metrics::counter!("acme_events_total", "result" => "error").increment(1);
+                }
+            }
+        }
+    });
+
+    Ok(tls)
+}
+
+/// ACME contacts are URIs; accept bare email addresses for convenience.
+fn contact_uri(contact: &str) -> String {
+    if contact.contains(':') {
+        contact.to_string()
+    } else {
+        format!("mailto:{contact}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bare_emails_become_mailto_uris() {
+        assert_eq!(contact_uri("ops@example.com"), "mailto:ops@example.com");
+        assert_eq!(
+            contact_uri("mailto:ops@example.com"),
+            "mailto:ops@example.com"
+        );
+    }
+}
@@ -0,0 +1,592 @@
+//! Address classification and notations for the `/ip/*` endpoints.
+//!
+//! [`AddressClass`] tells a routable public address apart from the ranges a client address
+//! commonly lands in when something in the path rewrites it: RFC 1918 and
+//! IPv6 unique-local space, carrier-grade NAT (RFC 6598), link-local and
+//! loopback. Anything else that should never appear as a source address on
+//! the internet (documentation, benchmarking, multicast, reserved, IPv6
+//! outside `2000::/3`) is a bogon. [`Ipv6Info`] takes an IPv6 address
+//! apart for `/ipv6/info`, and [`CidrInfo`] is the `/cidr` calculator.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use ipnet::IpNet;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AddressClass {
+    Public,
+    /// RFC 1918, or an IPv6 unique-local address (`fc00::/7`).
+    Private,
+    /// Shared address space `100.64.0.0/10`, used by carrier-grade NAT.
+    Cgnat,
+    LinkLocal,
+    Loopback,
+    Bogon,
+}
+
+impl AddressClass {
+    pub fn of(ip: IpAddr) -> Self {
+        match ip.to_canonical() {
+            IpAddr::V4(v4) => Self::of_v4(v4),
+            IpAddr::V6(v6) => Self::of_v6(v6),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Public => "public",
+            Self::Private => "private",
+            Self::Cgnat => "cgnat",
+            Self::LinkLocal => "link-local",
+            Self::Loopback => "loopback",
+            Self::Bogon => "bogon",
+        }
+    }
+
+    fn of_v4(ip: Ipv4Addr) -> Self {
+        let [a, b, ..] = ip.octets();
+        if ip.is_loopback() {
+            Self::Loopback
+        } else if ip.is_private() {
+            Self::Private
+        } else if a == 100 && (64..128).contains(&b) {
+            Self::Cgnat
+        } else if ip.is_link_local() {
+            Self::LinkLocal
+        } else if is_bogon_v4(ip) {
+            Self::Bogon
+        } else {
+            Self::Public
+        }
+    }
+
+    fn of_v6(ip: Ipv6Addr) -> Self {
+        let segments = ip.segments();
+        if ip.is_loopback() {
+            Self::Loopback
+        } else if ip.is_unique_local() {
+            Self::Private
+        } else if ip.is_unicast_link_local() {
+            Self::LinkLocal
+        } else if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+            // NAT64 well-known prefix: classify the embedded IPv4 address.
+            let [.., hi, lo] = segments;
+            Self::of_v4(Ipv4Addr::from((u32::from(hi) << 16) | u32::from(lo)))
+        } else if is_bogon_v6(ip) {
+            Self::Bogon
+        } else {
+            Self::Public
+        }
+    }
+}
+
+/// The address as an unsigned integer, e.g. `3405803777` for
+/// `203.0.113.1`. A string, since IPv6 values don't fit a JSON number.
+pub fn decimal(ip: IpAddr) -> String {
+    match ip.to_canonical() {
+        IpAddr::V4(v4) => u32::from(v4).to_string(),
+        IpAddr::V6(v6) => u128::from(v6).to_string(),
+    }
+}
+
+/// The address as one `0x`-prefixed hex number: 8 digits for IPv4, 32 for
+/// IPv6.
+pub fn hex(ip: IpAddr) -> String {
+    match ip.to_canonical() {
+        IpAddr::V4(v4) => format!("{:#010x}", u32::from(v4)),
+        IpAddr::V6(v6) => format!("{:#034x}", u128::from(v6)),
+    }
+}
+
+/// Reverse-DNS name: `1.113.0.203.in-addr.arpa` or the nibble-reversed
+/// `...ip6.arpa` form.
+pub fn ptr_name(ip: IpAddr) -> String {
+    match ip.to_canonical() {
+        IpAddr::V4(v4) => {
+            let [a, b, c, d] = v4.octets();
+            format!("{d}.{c}.{b}.{a}.in-addr.arpa")
+        }
+        IpAddr::V6(v6) => {
+            let mut name = String::with_capacity(72);
+            for byte in v6.octets().iter().rev() {
+                name.push_str(&format!("{:x}.{:x}.", byte & 0xf, byte >> 4));
+            }
+            name.push_str("ip6.arpa");
+            name
+        }
+    }
+}
+
+/// All eight groups with leading zeros and no `::`, as some firewall and
+/// zone-file tools require. `None` for IPv4.
+pub fn expanded(ip: IpAddr) -> Option<String> {
+    match ip.to_canonical() {
+        IpAddr::V4(_) => None,
+        IpAddr::V6(v6) => Some(
+            v6.segments()
+                .iter()
+                .map(|s| format!("{s:04x}"))
+                .collect::<Vec<_>>()
+                .join(":"),
+        ),
+    }
+}
+
+/// What an IPv6 address says about itself, for `/ipv6/info`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Ipv6Info {
+    pub address: Ipv6Addr,
+    pub expanded: String,
+    pub scope: Ipv6Scope,
+    /// How the low 64 bits were likely chosen.
+    pub interface_id: InterfaceId,
+    /// The hardware address an EUI-64 interface ID was derived from.
+    pub mac: Option<String>,
+    /// An IPv4 address carried by a transition mechanism.
+    pub embedded_ipv4: Option<EmbeddedIpv4>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Ipv6Scope {
+    /// `2000::/3`.
+    Global,
+    /// `fc00::/7`.
+    UniqueLocal,
+    /// `fe80::/10`.
+    LinkLocal,
+    Loopback,
+    Multicast,
+    /// Unspecified or in a reserved block.
+    Reserved,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InterfaceId {
+    /// SLAAC from a MAC address: `ff:fe` in the middle of the ID.
+    #[serde(rename = "eui-64")]
+    Eui64,
+    /// Few low bits set, like `::1` or `::53`: assigned by hand or by DHCPv6
+    /// from a small pool.
+    Manual,
+    /// Random-looking: a temporary address (RFC 8981) or a stable privacy
+    /// one (RFC 7217); the two can't be told apart from outside.
+    Privacy,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EmbeddedIpv4 {
+    /// `6to4`, `teredo` or `nat64`.
+    pub mechanism: &'static str,
+    /// For Teredo, the client's public address (de-obfuscated).
+    pub address: Ipv4Addr,
+}
+
+impl Ipv6Info {
+    pub fn new(address: Ipv6Addr) -> Self {
+        let octets = address.octets();
+        let interface_id = interface_id(&octets);
+        Self {
+            address,
+            expanded: expanded(IpAddr::V6(address)).unwrap_or_default(),
+            scope: scope(address),
+            interface_id,
+            mac: (interface_id == InterfaceId::Eui64).then(|| eui64_mac(&octets)),
+            embedded_ipv4: embedded_ipv4(&octets),
+        }
+    }
+}
+
+fn scope(ip: Ipv6Addr) -> Ipv6Scope {
+    if ip.is_loopback() {
+        Ipv6Scope::Loopback
+    } else if ip.is_multicast() {
+        Ipv6Scope::Multicast
+    } else if ip.is_unique_local() {
+        Ipv6Scope::UniqueLocal
+    } else if ip.is_unicast_link_local() {
+        Ipv6Scope::LinkLocal
+    } else if ip.segments()[0] & 0xe000 == 0x2000 {
+        Ipv6Scope::Global
+    } else {
+        Ipv6Scope::Reserved
+    }
+}
+
+fn interface_id(octets: &[u8; 16]) -> InterfaceId {
+    let id = u128::from_be_bytes(*octets) as u64;
+    if octets[11] == 0xff && octets[12] == 0xfe {
+        InterfaceId::Eui64
+    } else if id < 1 << 16 {
+        InterfaceId::Manual
+    } else {
+        InterfaceId::Privacy
+    }
+}
+
+/// The MAC address behind an EUI-64 interface ID: drop the `ff:fe` and flip
+/// the universal/local bit back.
+fn eui64_mac(octets: &[u8; 16]) -> String {
+    let mac = [
+        octets[8] ^ 0x02,
+        octets[9],
+        octets[10],
+        octets[13],
+        octets[14],
+        octets[15],
+    ];
+    mac.map(|b| format!("{b:02x}")).join(":")
+}
+
+fn embedded_ipv4(octets: &[u8; 16]) -> Option<EmbeddedIpv4> {
+    let v4 = |bytes: &[u8]| Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]);
+    let (mechanism, address) = match octets {
+        // 2002:AABB:CCDD::/48.
+        [0x20, 0x02, ..] => ("6to4", v4(&octets[2..6])),
+        // 2001:0000::/32; the client address is the last 32 bits, inverted.
+        [0x20, 0x01, 0x00, 0x00, ..] => {
+            let address = v4(&octets[12..]);
+            ("teredo", Ipv4Addr::from(!u32::from(address)))
+        }
+        // 64:ff9b::/96.
+        [0x00, 0x64, 0xff, 0x9b, 0, 0, 0, 0, 0, 0, 0, 0, ..] => ("nat64", v4(&octets[12..])),
+        _ => return None,
+    };
+    Some(EmbeddedIpv4 { mechanism, address })
+}
+
+/// A network and its boundaries, for `/cidr/{prefix}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CidrInfo {
+    /// The prefix with host bits cleared, e.g. `203.0.113.0/28`.
+    pub cidr: String,
+    /// The address as given, host bits and all.
+    pub address: IpAddr,
+    pub prefix_len: u8,
+    pub netmask: IpAddr,
+    /// Inverse of the netmask, as used in ACLs.
+    pub wildcard: IpAddr,
+    pub network: IpAddr,
+    /// `null` for IPv6, which has no broadcast address.
+    pub broadcast: Option<IpAddr>,
+    pub first_host: IpAddr,
+    pub last_host: IpAddr,
+    /// Decimal strings, since a large IPv6 count doesn't fit a JSON number.
+    pub addresses: String,
+    pub usable_hosts: String,
+}
+
+impl CidrInfo {
+    /// `203.0.113.5/28` or `2001:db8::/48`; a bare address is a single-host
+    /// prefix. `None` if it doesn't parse.
+    pub fn parse(input: &str) -> Option<Self> {
+        let net: IpNet = match input.parse() {
+            Ok(net) => net,
+            Err(_) => IpNet::from(input.parse::<IpAddr>().ok()?),
+        };
+        let host_bits = u32::from(net.max_prefix_len() - net.prefix_len());
+        let addresses = 1u128.checked_shl(host_bits);
+        let (broadcast, first_host, last_host, usable_hosts) = match net {
+            // RFC 3021: a /31 is two hosts with no network or broadcast
+            // address; a /32 is one host.
+            IpNet::V4(v4) if v4.prefix_len() >= 31 => (
+                None,
+                IpAddr::V4(v4.network()),
+                IpAddr::V4(v4.broadcast()),
+                addresses,
+            ),
+            IpNet::V4(v4) => (
+                Some(IpAddr::V4(v4.broadcast())),
+                IpAddr::V4(Ipv4Addr::from(u32::from(v4.network()) + 1)),
+                IpAddr::V4(Ipv4Addr::from(u32::from(v4.broadcast()) - 1)),
+                addresses.map(|n| n - 2),
+            ),
+            IpNet::V6(v6) => (
+                None,
+                IpAddr::V6(v6.network()),
+                IpAddr::V6(v6.broadcast()),
+                addresses,
+            ),
+        };
+        // Only `::/0` overflows: 2^128 addresses.
+        let count = |n: Option<u128>| {
+            n.map_or_else(
+                || "340282366920938463463374607431768211456".to_string(),
+                |n| n.to_string(),
+            )
+        };
+        Some(Self {
+            cidr: net.trunc().to_string(),
+            address: net.addr(),
+            prefix_len: net.prefix_len(),
+            netmask: net.netmask(),
+            wildcard: net.hostmask(),
+            network: net.network(),
+            broadcast,
+            first_host,
+            last_host,
+            addresses: count(addresses),
+            usable_hosts: count(usable_hosts),
+        })
+    }
+}
+
+fn is_bogon_v4(ip: Ipv4Addr) -> bool {
+    match ip.octets() {
+        // "This network".
+        [0, ..] => true,
+        // IETF protocol assignments.
+        [192, 0, 0, _] => true,
+        // Benchmarking, 198.18.0.0/15.
+        [198, 18 | 19, ..] => true,
+        // Multicast, reserved and broadcast.
+        [224..=255, ..] => true,
+        _ => ip.is_documentation(),
+    }
+}
+
+fn is_bogon_v6(ip: Ipv6Addr) -> bool {
+    match ip.segments() {
+        // Documentation, 2001:db8::/32 and 3fff::/20.
+        [0x2001, 0x0db8, ..] => true,
+        [0x3fff, second, ..] if second < 0x1000 => true,
+        // Anything outside global unicast, 2000::/3.
+        [first, ..] => first & 0xe000 != 0x2000,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn class(ip: &str) -> AddressClass {
+        AddressClass::of(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn ipv4_classes() {
+        for (ip, expected) in [
+            ("8.8.8.8", AddressClass::Public),
+            ("100.128.0.1", AddressClass::Public),
+            ("10.1.2.3", AddressClass::Private),
+            ("172.16.0.1", AddressClass::Private),
+            ("192.168.1.1", AddressClass::Private),
+            ("100.64.0.1", AddressClass::Cgnat),
+            ("100.127.255.254", AddressClass::Cgnat),
+            ("169.254.10.20", AddressClass::LinkLocal),
+            ("127.0.0.1", AddressClass::Loopback),
+            ("0.0.0.0", AddressClass::Bogon),
+            ("192.0.2.1", AddressClass::Bogon),
+            ("198.19.0.1", AddressClass::Bogon),
+            ("203.0.113.5", AddressClass::Bogon),
+            ("224.0.0.1", AddressClass::Bogon),
+            ("255.255.255.255", AddressClass::Bogon),
+        ] {
+            assert_eq!(class(ip), expected, "{ip}");
+        }
+    }
+
+    #[test]
+    fn ipv6_classes() {
+        for (ip, expected) in [
+            ("2001:4860:4860::8888", AddressClass::Public),
+            ("2002:c000:0204::1", AddressClass::Public),
+            ("fd12:3456::1", AddressClass::Private),
+            ("fe80::1", AddressClass::LinkLocal),
+            ("::1", AddressClass::Loopback),
+            ("::", AddressClass::Bogon),
+            ("2001:db8::1", AddressClass::Bogon),
+            ("3fff::1", AddressClass::Bogon),
+            ("3fff:1000::1", AddressClass::Public),
+            ("ff02::1", AddressClass::Bogon),
+            ("64:ff9b::808:808", AddressClass::Public),
+            ("64:ff9b::a00:1", AddressClass::Private),
+        ] {
+            assert_eq!(class(ip), expected, "{ip}");
+        }
+    }
+
+    #[test]
+    fn ipv4_mapped_addresses_use_the_ipv4_class() {
+        assert_eq!(class("::ffff:100.64.1.1"), AddressClass::Cgnat);
+    }
+
+    #[test]
+    fn ipv4_notations() {
+        let ip = "203.0.113.1".parse().unwrap();
+        assert_eq!(decimal(ip), "3405803777");
+        assert_eq!(hex(ip), "0xcb007101");
+        assert_eq!(ptr_name(ip), "1.113.0.203.in-addr.arpa");
+        assert_eq!(expanded(ip), None);
+        assert_eq!(hex("0.0.0.1".parse().unwrap()), "0x00000001");
+    }
+
+    #[test]
+    fn ipv6_notations() {
+        let ip = "2001:db8::567:89ab".parse().unwrap();
+        assert_eq!(decimal(ip), "42540766411282592856903984951744498091");
+        assert_eq!(hex(ip), "0x20010db80000000000000000056789ab");
+        assert_eq!(
+            ptr_name(ip),
+            "b.a.9.8.7.6.5.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa"
+        );
+        assert_eq!(
+            expanded(ip).as_deref(),
+            Some("2001:0db8:0000:0000:0000:0000:0567:89ab")
+        );
+    }
+
+    #[test]
+    fn ipv4_mapped_notations_use_ipv4() {
+        let ip = "::ffff:203.0.113.1".parse().unwrap();
+        assert_eq!(decimal(ip), "3405803777");
+        assert_eq!(ptr_name(ip), "1.113.0.203.in-addr.arpa");
+    }
+
+    fn info(ip: &str) -> Ipv6Info {
+        Ipv6Info::new(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn ipv6_scopes() {
+        for (ip, expected) in [
+            ("2001:4860:4860::8888", Ipv6Scope::Global),
+            ("fd12:3456::1", Ipv6Scope::UniqueLocal),
+            ("fe80::1", Ipv6Scope::LinkLocal),
+            ("::1", Ipv6Scope::Loopback),
+            ("ff02::1", Ipv6Scope::Multicast),
+            ("::", Ipv6Scope::Reserved),
+            ("100::1", Ipv6Scope::Reserved),
+        ] {
+            assert_eq!(info(ip).scope, expected, "{ip}");
+        }
+    }
+
+    #[test]
+    fn eui64_interface_id() {
+        let info = info("2001:db8::0211:22ff:fe33:4455");
+        assert_eq!(info.interface_id, InterfaceId::Eui64);
+        assert_eq!(info.mac.as_deref(), Some("00:11:22:33:44:55"));
+    }
+
+    #[test]
+    fn manual_and_privacy_interface_ids() {
+        assert_eq!(info("2001:db8::53").interface_id, InterfaceId::Manual);
+        assert_eq!(info("2001:db8::53").mac, None);
+        let info = info("2001:db8::a4c1:93e7:5d02:8b6f");
+        assert_eq!(info.interface_id, InterfaceId::Privacy);
+        assert_eq!(info.mac, None);
+    }
+
+    #[test]
+    fn embedded_ipv4_addresses() {
+        assert_eq!(
+            info("2002:cb00:7101::1").embedded_ipv4,
+            Some(EmbeddedIpv4 {
+                mechanism: "6to4",
+                address: Ipv4Addr::new(203, 0, 113, 1),
+            })
+        );
+        // RFC 4380 example: server 65.54.227.120, client 192.0.2.45:40000.
+        assert_eq!(
+            info("2001:0:4136:e378:8000:63bf:3fff:fdd2").embedded_ipv4,
+            Some(EmbeddedIpv4 {
+                mechanism: "teredo",
+                address: Ipv4Addr::new(192, 0, 2, 45),
+            })
+        );
+        assert_eq!(
+            info("64:ff9b::cb00:7101").embedded_ipv4,
+            Some(EmbeddedIpv4 {
+                mechanism: "nat64",
+                address: Ipv4Addr::new(203, 0, 113, 1),
+            })
+        );
+        assert_eq!(info("2001:4860:4860::8888").embedded_ipv4, None);
+    }
+
+    #[test]
+    fn ipv4_cidr() {
+        let info = CidrInfo::parse("203.0.113.5/28").unwrap();
+        assert_eq!(info.cidr, "203.0.113.0/28");
+        assert_eq!(info.address.to_string(), "203.0.113.5");
+        assert_eq!(info.prefix_len, 28);
+        assert_eq!(info.netmask.to_string(), "255.255.255.240");
+        assert_eq!(info.wildcard.to_string(), "0.0.0.15");
+        assert_eq!(info.network.to_string(), "203.0.113.0");
+        assert_eq!(info.broadcast.unwrap().to_string(), "203.0.113.15");
+        assert_eq!(info.first_host.to_string(), "203.0.113.1");
+        assert_eq!(info.last_host.to_string(), "203.0.113.14");
+        assert_eq!(info.addresses, "16");
+        assert_eq!(info.usable_hosts, "14");
+    }
+
+    #[test]
+    fn point_to_point_and_host_prefixes() {
+        let info = CidrInfo::parse("192.0.2.7/31").unwrap();
+        assert_eq!(info.broadcast, None);
+        assert_eq!(info.first_host.to_string(), "192.0.2.6");
+        assert_eq!(info.last_host.to_string(), "192.0.2.7");
+        assert_eq!(info.usable_hosts, "2");
+
+        let info = CidrInfo::parse("192.0.2.7").unwrap();
+        assert_eq!(info.cidr, "192.0.2.7/32");
+        assert_eq!(info.first_host, info.last_host);
+        assert_eq!(info.usable_hosts, "1");
+
+        let info = CidrInfo::parse("0.0.0.0/0").unwrap();
+        assert_eq!(info.addresses, "4294967296");
+        assert_eq!(info.usable_hosts, "4294967294");
+    }
+
+    #[test]
+    fn ipv6_cidr() {
+        let info = CidrInfo::parse("2001:db8:abcd:12::1/64").unwrap();
+        assert_eq!(info.cidr, "2001:db8:abcd:12::/64");
+        assert_eq!(info.netmask.to_string(), "ffff:ffff:ffff:ffff::");
+        assert_eq!(info.broadcast, None);
+        assert_eq!(info.first_host.to_string(), "2001:db8:abcd:12::");
+        assert_eq!(
+            info.last_host.to_string(),
+            "2001:db8:abcd:12:ffff:ffff:ffff:ffff"
+        );
+        assert_eq!(info.addresses, "18446744073709551616");
+        assert_eq!(info.usable_hosts, info.addresses);
+
+        let info = CidrInfo::parse("::/0").unwrap();
+        assert_eq!(info.addresses, "340282366920938463463374607431768211456");
+    }
+
+    #[test]
+    fn invalid_cidrs() {
+        for input in [
+            "",
+            "203.0.113.5/33",
+            "2001:db8::/129",
+            "example.com/24",
+            "1.2.3/24",
+        ] {
+            assert_eq!(CidrInfo::parse(input), None, "{input}");
+        }
+    }
+
+    #[test]
+    fn serializes_as_str() {
+        for class in [
+            AddressClass::Public,
+            AddressClass::Private,
+            AddressClass::Cgnat,
+            AddressClass::LinkLocal,
+            AddressClass::Loopback,
+            AddressClass::Bogon,
+        ] {
+            assert_eq!(
+                serde_json::to_value(class).unwrap(),
+                serde_json::Value::from(class.as_str())
+            );
+        }
+    }
+}
@@ -0,0 +1,254 @@
+//! Command-line flags. Anything set here overrides the environment and the
+//! config file.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use crate::config::{BindAddr, Config, FileConfig, LogFormat};
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Echo client connection metadata over HTTP")]
+pub struct Cli {
+    /// Path to a TOML config file [default: echo.toml, if present]
+    #[arg(long, short, env = "ECHO_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Address to bind the listener to (e.g. 127.0.0.1, ::, 0.0.0.0), or a
+    /// Unix socket as unix:/run/echo.sock
+    #[arg(long)]
+    pub bind: Option<BindAddr>,
+
+    /// Port to listen on (overrides PORT)
+    #[arg(long, value_parser = clap::value_parser!(u16).range(1..))]
+    pub port: Option<u16>,
+
+    /// Maximum length of the pending-connection queue
+    #[arg(long)]
+    pub backlog: Option<u32>,
+
+    /// Only accept IPv4 connections
+    #[arg(long, conflicts_with = "ipv6_only")]
+    pub ipv4_only: bool,
+
+    /// Only accept IPv6 connections (sets IPV6_V6ONLY)
+    #[arg(long)]
+    pub ipv6_only: bool,
+
+    /// PEM certificate chain; serves HTTPS instead of HTTP
+    #[arg(long, requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// PEM private key for --tls-cert
+    #[arg(long, requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+
+    /// Log filter, e.g. info or ipecho=debug,tower_http=warn (RUST_LOG wins)
+    #[arg(long)]
+    pub log_level: Option<String>,
+
+    /// Log output format: text or json
+    #[arg(long)]
+    pub log_format: Option<LogFormat>,
+
+    /// Switch to this user (name or ID) after binding; requires starting as
+    /// root
+    #[arg(long)]
+    pub user: Option<String>,
+
+    /// Switch to this group (name or ID) after binding [default: the
+    /// user's primary group]
+    #[arg(long)]
+    pub group: Option<String>,
+}
+
+impl Cli {
+    /// Read the config file and environment and apply these flags on top:
+    /// the configuration as of now, at startup or on reload.
+    pub fn load_config(&self) -> Result<Config, String> {
+        let file = FileConfig::load(self.config.as_deref())?;
+        let mut config = Config::load(file)?;
+        self.apply(&mut config)?;
+        Ok(config)
+    }
+
+    /// Apply CLI overrides to a file/env-derived config. Fails if the address
+    /// family flags contradict the bind address, or if the config file
+    /// declares `[[listeners]]`, which the listener flags can't override.
+    pub fn apply(&self, config: &mut Config) -> Result<(), String> {
+        let listener_flags = self.bind.is_some()
+            || self.port.is_some()
+            || self.backlog.is_some()
+            || self.ipv4_only
+            || self.ipv6_only;
+        if listener_flags && !config.listeners.is_empty() {
+            return Err(
+                "listener flags can't be combined with [[listeners]] in the config file".into(),
+            );
+        }
+        if let Some(port) = self.port {
+            config.port = port;
+        }
+        if let Some(backlog) = self.backlog {
+            config.listen_backlog = backlog;
+        }
+        let bind_ip = match &self.bind {
+            Some(BindAddr::Ip(ip)) => Some(*ip),
+            _ => None,
+        };
+        match &self.bind {
+            Some(BindAddr::Ip(ip)) => {
+                config.bind_addr = *ip;
+                config.unix_socket = None;
+            }
+            Some(BindAddr::Unix(path)) => config.unix_socket = Some(path.clone()),
+            None => {}
+        }
+        if let (Some(cert), Some(key)) = (&self.tls_cert, &self.tls_key) {
+            config.tls_cert = Some(cert.clone());
+            config.tls_key = Some(key.clone());
+            if config.unix_socket.is_none() && config.http_port == config.port {
+                return Err("--port must differ from HTTP_PORT with --tls-cert".into());
+            }
+        }
+
+        if let Some(level) = &self.log_level {
+            config.log_level = level.clone();
+        }
+        if let Some(format) = self.log_format {
+            config.log_format = format;
+        }
+
+        if self.ipv4_only {
+            match (bind_ip, config.bind_addr) {
+                (Some(IpAddr::V6(_)), _) => {
+                    return Err("--ipv4-only conflicts with an IPv6 --bind address".into());
+                }
+                (None, IpAddr::V6(_)) => config.bind_addr = Ipv4Addr::UNSPECIFIED.into(),
+                _ => {}
+            }
+        }
+
+        if self.ipv6_only {
+            match (bind_ip, config.bind_addr) {
+                (Some(IpAddr::V4(_)), _) => {
+                    return Err("--ipv6-only conflicts with an IPv4 --bind address".into());
+                }
+                (None, IpAddr::V4(_)) => config.bind_addr = Ipv6Addr::UNSPECIFIED.into(),
+                _ => {}
+            }
+            config.ipv6_only = true;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(args: &[&str]) -> Result<Config, String> {
+        let cli = Cli::try_parse_from(std::iter::once("ipecho").chain(args.iter().copied()))
+            .map_err(|e| e.to_string())?;
+        let mut config = Config::default();
+        cli.apply(&mut config)?;
+        Ok(config)
+    }
+
+    #[test]
+    fn no_flags_keeps_config() {
+        let c = apply(&[]).unwrap();
+        assert_eq!(c.port, Config::default().port);
+        assert_eq!(c.bind_addr, Config::default().bind_addr);
+        assert!(!c.ipv6_only);
+    }
+
+    #[test]
+    fn bind_port_and_backlog_override() {
+        let c = apply(&["--bind", "127.0.0.1", "--port", "8080", "--backlog", "64"]).unwrap();
+        assert_eq!(c.bind_addr, "127.0.0.1".parse::<IpAddr>().unwrap());
+        assert_eq!(c.port, 8080);
+        assert_eq!(c.listen_backlog, 64);
+    }
+
+    #[test]
+    fn port_zero_rejected() {
+        assert!(apply(&["--port", "0"]).is_err());
+    }
+
+    #[test]
+    fn ipv6_only_defaults_to_unspecified_v6() {
+        let c = apply(&["--ipv6-only"]).unwrap();
+        assert_eq!(c.bind_addr, IpAddr::from(Ipv6Addr::UNSPECIFIED));
+        assert!(c.ipv6_only);
+    }
+
+    #[test]
+    fn ipv4_only_with_dual_stack_bind_default() {
+        let mut config = Config {
+            bind_addr: Ipv6Addr::UNSPECIFIED.into(),
+            ..Config::default()
+        };
+        Cli::try_parse_from(["ipecho", "--ipv4-only"])
+            .unwrap()
+            .apply(&mut config)
+            .unwrap();
+        assert_eq!(config.bind_addr, IpAddr::from(Ipv4Addr::UNSPECIFIED));
+    }
+
+    #[test]
+    fn tls_flags_must_be_given_together() {
+        assert!(apply(&["--tls-cert", "cert.pem"]).is_err());
+        let c = apply(&["--tls-cert", "cert.pem", "--tls-key", "key.pem"]).unwrap();
+        assert_eq!(c.tls_cert, Some(PathBuf::from("cert.pem")));
+        assert_eq!(c.tls_key, Some(PathBuf::from("key.pem")));
+        assert!(
+            apply(&[
+                "--tls-cert",
+                "cert.pem",
+                "--tls-key",
+                "key.pem",
+                "--port",
+                "80"
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn log_flags_override() {
+        let c = apply(&["--log-level", "debug", "--log-format", "json"]).unwrap();
+        assert_eq!(c.log_level, "debug");
+        assert_eq!(c.log_format, LogFormat::Json);
+        assert!(apply(&["--log-format", "xml"]).is_err());
+    }
+
+    #[test]
+    fn bind_accepts_a_unix_socket() {
+        let c = apply(&["--bind", "unix:/run/echo.sock"]).unwrap();
+        assert_eq!(c.unix_socket, Some(PathBuf::from("/run/echo.sock")));
+        assert!(apply(&["--bind", "unix:"]).is_err());
+        assert!(apply(&["--bind", "localhost"]).is_err());
+    }
+
+    #[test]
+    fn family_flags_conflict_with_bind() {
+        assert!(apply(&["--ipv4-only", "--bind", "::1"]).is_err());
+        assert!(apply(&["--ipv6-only", "--bind", "127.0.0.1"]).is_err());
+        assert!(apply(&["--ipv4-only", "--ipv6-only"]).is_err());
+    }
+
+    #[test]
+    fn listener_flags_conflict_with_listener_list() {
+        let mut config = Config {
+            listeners: Config::default().all_listeners(),
+            ..Config::default()
+        };
+        let cli = Cli::try_parse_from(["ipecho", "--port", "8080"]).unwrap();
+        assert!(cli.apply(&mut config).is_err());
+        let cli = Cli::try_parse_from(["ipecho", "--log-level", "debug"]).unwrap();
+        assert!(cli.apply(&mut config).is_ok());
+    }
+}
@@ -0,0 +1,324 @@
+//! Client IP resolution behind trusted reverse proxies.
+//!
+//! `X-Forwarded-For` is walked from the right: each proxy appends the
+//! address it received the request from, so entries to the right were
+//! written by hops closer to us. We skip over entries that belong to
+//! trusted proxies and stop at the first one that doesn't — that is the
+//! rightmost address nobody we trust could have forged. Taking the leftmost
+//! entry instead would let any client spoof its IP by sending its own XFF.
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+use axum::extract::rejection::ExtensionRejection;
+use axum::extract::{ConnectInfo, FromRef, FromRequestParts};
+use axum::http::HeaderMap;
+use axum::http::request::Parts;
+
+use crate::config::Config;
+use crate::state::AppState;
+
+/// Extractor for the originating client IP, resolved with
+/// [`resolve_client_ip`] against the `TRUSTED_PROXIES` in the router's
+/// [`ClientIpConfig`]. Needs `ConnectInfo<SocketAddr>`, so serve the router
+/// with `into_make_service_with_connect_info::<SocketAddr>()`.
+///
+/// ```no_run
+/// use std::net::SocketAddr;
+///
+/// use axum::Router;
+/// use axum::routing::get;
+/// use ipecho::config::Config;
+/// use ipecho::{ClientIp, ClientIpConfig};
+///
+/// # async fn example() -> std::io::Result<()> {
+/// let trust = ClientIpConfig::new(Config {
+///     trusted_proxies: vec!["10.0.0.0/8".parse().unwrap()],
+///     ..Config::default()
+/// });
+/// let app = Router::new()
+///     .route("/", get(|ClientIp(ip): ClientIp| async move { ip.to_string() }))
+///     .with_state(trust);
+/// let listener = tokio::net::TcpListener::bind("0.0.0.0:8080").await?;
+/// axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+/// The settings [`ClientIp`] resolves against, taken from the router state
+/// through [`FromRef`]. Only the proxy settings of the [`Config`] matter.
+#[derive(Clone)]
+pub struct ClientIpConfig(Arc<ArcSwap<Config>>);
+
+impl ClientIpConfig {
+    pub fn new(config: Config) -> Self {
+        Self(Arc::new(ArcSwap::from_pointee(config)))
+    }
+}
+
+/// Follows the live config, so a reload's `TRUSTED_PROXIES` apply at once.
+impl From<Arc<ArcSwap<Config>>> for ClientIpConfig {
+    fn from(config: Arc<ArcSwap<Config>>) -> Self {
+        Self(config)
+    }
+}
+
+impl FromRef<Arc<AppState>> for ClientIpConfig {
+    fn from_ref(state: &Arc<AppState>) -> Self {
+        state.config.clone().into()
+    }
+}
+
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+    ClientIpConfig: FromRef<S>,
+{
+    type Rejection = ExtensionRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let ConnectInfo(peer) = ConnectInfo::<SocketAddr>::from_request_parts(parts, state).await?;
+        let ClientIpConfig(config) = ClientIpConfig::from_ref(state);
+        Ok(Self(resolve_client_ip(
+            peer.ip(),
+            &parts.headers,
+            &config.load(),
+        )))
+    }
+}
+
+/// Resolve the originating client IP for a request that arrived on a socket
+/// from `peer`. Forwarding headers are only honored when `peer` itself is a
+/// trusted proxy, or the request came in on the Unix socket.
+pub fn resolve_client_ip(peer: IpAddr, headers: &HeaderMap, config: &Config) -> IpAddr {
+    resolve(peer, None, headers, config).0
+}
+
+/// The originating client's source port, by the same rules as
+/// [`resolve_client_ip`]. Behind a proxy that is only known if the hop
+/// we settle on carries one (`203.0.113.1:4711`); most proxies don't send
+/// it, so this is usually `None` there.
+pub fn resolve_client_port(peer: SocketAddr, headers: &HeaderMap, config: &Config) -> Option<u16> {
+    resolve(peer.ip(), Some(peer.port()), headers, config).1
+}
+
+fn resolve(
+    peer: IpAddr,
+    peer_port: Option<u16>,
+    headers: &HeaderMap,
+    config: &Config,
+) -> (IpAddr, Option<u16>) {
+    let peer = peer.to_canonical();
+    if !config.is_trusted_peer(&peer) {
+        return (peer, peer_port);
+    }
+
+    let hops = forwarded_for_hops(headers);
+    if !hops.is_empty() {
+        let mut candidate = (peer, peer_port);
+        for hop in hops.iter().rev() {
+            match parse_hop(hop) {
+                Some(hop) => {
+                    candidate = hop;
+                    if !config.is_trusted_proxy(&hop.0) {
+                        break;
+                    }
+                }
+                // A garbled entry means everything to its left is untrustworthy;
+                // the last hop we could verify is the best answer we have.
+                None => break,
+            }
+        }
+        return candidate;
+    }
+
+    if let Some(hop) = headers
+        .get("x-real-ip")
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_hop)
+    {
+        return hop;
+    }
+
+    (peer, peer_port)
+}
+
+/// All `X-Forwarded-For` entries in order, across repeated header lines.
+fn forwarded_for_hops(headers: &HeaderMap) -> Vec<&str> {
+    headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Parse a single hop. Some proxies include the source port
+/// (`203.0.113.1:4711`, `[2001:db8::1]:4711`), so accept that form too.
+fn parse_hop(raw: &str) -> Option<(IpAddr, Option<u16>)> {
+    let raw = raw.trim();
+    raw.parse::<IpAddr>()
+        .map(|ip| (ip, None))
+        .or_else(|_| raw.parse::<SocketAddr>().map(|s| (s.ip(), Some(s.port()))))
+        .ok()
+        .map(|(ip, port)| (ip.to_canonical(), port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config() -> Config {
+        Config {
+            trusted_proxies: vec!["10.0.0.0/8".parse().unwrap()],
+            ..Config::default()
+        }
+    }
+
+    fn resolve(peer: &str, headers: &[(&'static str, &str)]) -> String {
+        let mut map = HeaderMap::new();
+        for (k, v) in headers {
+            map.append(*k, v.parse().unwrap());
+        }
+        resolve_client_ip(peer.parse().unwrap(), &map, &test_config()).to_string()
+    }
+
+    #[test]
+    fn test_extract_ip_direct_connection() {
+        assert_eq!(resolve("203.0.113.1", &[]), "203.0.113.1");
+    }
+
+    #[test]
+    fn test_extract_ip_xff_from_trusted_proxy() {
+        let ip = resolve("10.0.0.1", &[("x-forwarded-for", "203.0.113.50, 10.0.0.1")]);
+        assert_eq!(ip, "203.0.113.50");
+    }
+
+    #[test]
+    fn test_extract_ip_xff_from_untrusted_ignored() {
+        assert_eq!(
+            resolve("203.0.113.1", &[("x-forwarded-for", "1.2.3.4")]),
+            "203.0.113.1"
+        );
+    }
+
+    #[test]
+    fn test_extract_ip_x_real_ip_from_trusted() {
+        assert_eq!(
+            resolve("10.0.0.1", &[("x-real-ip", "203.0.113.99")]),
+            "203.0.113.99"
+        );
+    }
+
+    #[test]
+    fn test_xff_takes_priority_over_x_real_ip() {
+        let ip = resolve(
+            "10.0.0.1",
+            &[("x-forwarded-for", "1.1.1.1"), ("x-real-ip", "2.2.2.2")],
+        );
+        assert_eq!(ip, "1.1.1.1");
+    }
+
+    #[test]
+    fn spoofed_leftmost_entry_is_ignored() {
+        // The client sent "6.6.6.6" itself; the proxy appended the real address.
+        let ip = resolve("10.0.0.1", &[("x-forwarded-for", "6.6.6.6, 203.0.113.7")]);
+        assert_eq!(ip, "203.0.113.7");
+    }
+
+    #[test]
+    fn walks_past_multiple_trusted_hops() {
+        let ip = resolve(
+            "10.0.0.1",
+            &[("x-forwarded-for", "203.0.113.7, 10.1.1.1, 10.2.2.2")],
+        );
+        assert_eq!(ip, "203.0.113.7");
+    }
+
+    #[test]
+    fn all_trusted_hops_returns_leftmost() {
+        let ip = resolve("10.0.0.1", &[("x-forwarded-for", "10.9.9.9, 10.1.1.1")]);
+        assert_eq!(ip, "10.9.9.9");
+    }
+
+    #[test]
+    fn garbage_hop_stops_the_walk() {
+        let ip = resolve(
+            "10.0.0.1",
+            &[("x-forwarded-for", "203.0.113.7, bogus, 10.1.1.1")],
+        );
+        assert_eq!(ip, "10.1.1.1");
+    }
+
+    #[test]
+    fn repeated_xff_headers_are_concatenated() {
+        let ip = resolve(
+            "10.0.0.1",
+            &[
+                ("x-forwarded-for", "6.6.6.6"),
+                ("x-forwarded-for", "203.0.113.7, 10.1.1.1"),
+            ],
+        );
+        assert_eq!(ip, "203.0.113.7");
+    }
+
+    #[test]
+    fn hops_with_ports_are_accepted() {
+        assert_eq!(
+            resolve("10.0.0.1", &[("x-forwarded-for", "203.0.113.7:4711")]),
+            "203.0.113.7"
+        );
+        assert_eq!(
+            resolve("10.0.0.1", &[("x-forwarded-for", "[2001:db8::1]:4711")]),
+            "2001:db8::1"
+        );
+    }
+
+    fn resolve_port(peer: &str, headers: &[(&'static str, &str)]) -> Option<u16> {
+        let mut map = HeaderMap::new();
+        for (k, v) in headers {
+            map.append(*k, v.parse().unwrap());
+        }
+        resolve_client_port(peer.parse().unwrap(), &map, &test_config())
+    }
+
+    #[test]
+    fn client_port() {
+        assert_eq!(resolve_port("203.0.113.1:51234", &[]), Some(51234));
+        assert_eq!(
+            resolve_port("203.0.113.1:51234", &[("x-forwarded-for", "1.2.3.4:80")]),
+            Some(51234)
+        );
+        assert_eq!(
+            resolve_port("10.0.0.1:443", &[("x-forwarded-for", "203.0.113.7:4711")]),
+            Some(4711)
+        );
+        assert_eq!(
+            resolve_port("10.0.0.1:443", &[("x-forwarded-for", "203.0.113.7")]),
+            None
+        );
+        assert_eq!(resolve_port("10.0.0.1:443", &[]), Some(443));
+    }
+
+    #[test]
+    fn ipv4_mapped_peer_is_canonicalized() {
+        assert_eq!(
+            resolve("::ffff:10.0.0.1", &[("x-forwarded-for", "203.0.113.7")]),
+            "203.0.113.7"
+        );
+    }
+
+    #[test]
+    fn unix_socket_peer_is_always_trusted() {
+        let ip = resolve("0.0.0.0", &[("x-forwarded-for", "203.0.113.7, 192.0.2.1")]);
+        assert_eq!(ip, "192.0.2.1");
+        assert_eq!(
+            resolve("0.0.0.0", &[("x-real-ip", "203.0.113.8")]),
+            "203.0.113.8"
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! Response compression (`COMPRESSION`).
+//!
+//! Off by default, since a proxy or CDN in front usually compresses
+//! already. When on, text responses — JSON, HTML, plain text, XML, YAML
+//! and CSV — of at least `COMPRESSION_MIN_BYTES` are compressed with
+//! whichever of zstd, brotli or gzip the client prefers in
+//! `Accept-Encoding`. Binary formats, event streams and responses that
+//! already have a `Content-Encoding` (like `/gzip`) are left alone.
+
+use axum::http::{Response, header};
+use tower_http::compression::CompressionLayer;
+use tower_http::compression::predicate::{And, Predicate, SizeAbove};
+
+use crate::config::Config;
+
+/// Content types worth compressing, without parameters.
+const COMPRESSIBLE_TYPES: &[&str] = &[
+    "application/json",
+    "text/html",
+    "text/plain",
+    "application/xml",
+    "application/yaml",
+    "text/csv",
+];
+
+/// Whether a response is one of [`COMPRESSIBLE_TYPES`].
+#[derive(Debug, Clone, Copy)]
+pub struct TextContent;
+
+impl Predicate for TextContent {
+    fn should_compress<B>(&self, response: &Response<B>) -> bool
+    where
+        B: http_body::Body,
+    {
+        response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(';').next())
+            .is_some_and(|v| COMPRESSIBLE_TYPES.contains(&v.trim()))
+    }
+}
+
+/// The compression layer for `config`, or `None` when it's off.
+pub fn compression_layer(config: &Config) -> Option<CompressionLayer<And<SizeAbove, TextContent>>> {
+    if !config.compression {
+        return None;
+    }
+    let predicate = SizeAbove::new(config.compression_min_bytes).and(TextContent);
+    Some(CompressionLayer::new().compress_when(predicate))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::Router;
+    use axum::body::Body;
+    use axum::http::Request;
+    use axum::routing::get;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn app() -> Router {
+        let config = Config {
+            compression: true,
+            compression_min_bytes: 64,
+            ..Config::default()
+        };
+        let json = || async {
+            (
+                [(header::CONTENT_TYPE, "application/json")],
+                "x".repeat(1000),
+            )
+        };
+        let binary = || async {
+            (
+                [(header::CONTENT_TYPE, "application/octet-stream")],
+                "x".repeat(1000),
+            )
+        };
+        let small = || async { ([(header::CONTENT_TYPE, "application/json")], "{}") };
+        Router::new()
+            .route("/json", get(json))
+            .route("/binary", get(binary))
+            .route("/small", get(small))
+            .layer(compression_layer(&config).unwrap())
+    }
+
+    async fn encoding(uri: &str, accept: &str) -> Option<String> {
+        let request = Request::builder()
+            .uri(uri)
+            .header(header::ACCEPT_ENCODING, accept)
+            .body(Body::empty())
+            .unwrap();
+        let response = app().oneshot(request).await.unwrap();
+        response
+            .headers()
+            .get(header::CONTENT_ENCODING)
+            .map(|v| v.to_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn large_text_is_compressed_as_accepted() {
+        assert_eq!(encoding("/json", "gzip").await.as_deref(), Some("gzip"));
+        assert_eq!(encoding("/json", "br").await.as_deref(), Some("br"));
+        assert_eq!(encoding("/json", "zstd").await.as_deref(), Some("zstd"));
+        assert_eq!(encoding("/json", "identity").await, None);
+    }
+
+    #[tokio::test]
+    async fn small_and_binary_responses_are_not() {
+        assert_eq!(encoding("/small", "gzip").await, None);
+        assert_eq!(encoding("/binary", "gzip").await, None);
+    }
+
+    #[test]
+    fn off_by_default() {
+        assert!(compression_layer(&Config::default()).is_none());
+    }
+}
@@ -0,0 +1,414 @@
+//! `echo.toml` configuration file.
+//!
+//! Every field is optional; anything omitted falls back to the built-in
+//! default, and any env var that is set overrides the file. Unknown keys are
+//! rejected so typos fail loudly at startup instead of being ignored.
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use super::{
+    BindAddr, GeoIpBackend, LogFormat, LogRotation, SocketMode, SyslogFacility, SyslogTarget,
+};
+
+/// Path tried when neither `--config` nor `ECHO_CONFIG` is given. A missing
+/// file at this path is not an error.
+pub const DEFAULT_CONFIG_PATH: &str = "echo.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    pub log_level: Option<String>,
+    pub log_format: Option<LogFormat>,
+    pub access_log: Option<PathBuf>,
+    pub sync_interval_secs: Option<u64>,
+    pub trusted_proxies: Option<Vec<String>>,
+    pub excluded_headers: Option<Vec<String>>,
+    pub supported_languages: Option<Vec<String>>,
+    #[serde(default)]
+    pub listener: ListenerSection,
+    pub listeners: Option<Vec<ListenerEntry>>,
+    #[serde(default)]
+    pub rate_limit: RateLimitSection,
+    #[serde(default)]
+    pub limits: LimitsSection,
+    #[serde(default)]
+    pub timeouts: TimeoutsSection,
+    #[serde(default)]
+    pub rdns: RdnsSection,
+    #[serde(default)]
+    pub tls: TlsSection,
+    #[serde(default)]
+    pub acme: AcmeSection,
+    #[serde(default)]
+    pub geoip: GeoIpSection,
+    #[serde(default)]
+    pub metrics: MetricsSection,
+    #[serde(default)]
+    pub tcp_echo: TcpEchoSection,
+    #[serde(default)]
+    pub udp_echo: UdpEchoSection,
+    #[serde(default)]
+    pub dns: DnsSection,
+    #[serde(default)]
+    pub otlp: OtlpSection,
+    #[serde(default)]
+    pub statsd: StatsdSection,
+    #[serde(default)]
+    pub log_file: LogFileSection,
+    #[serde(default)]
+    pub syslog: SyslogSection,
+    #[serde(default)]
+    pub cors: CorsSection,
+    #[serde(default)]
+    pub security_headers: SecurityHeadersSection,
+    #[serde(default)]
+    pub compression: CompressionSection,
+    #[serde(default)]
+    pub outbound: OutboundSection,
+    #[serde(default)]
+    pub portcheck: PortcheckSection,
+    #[serde(default)]
+    pub ping: PingSection,
+    #[serde(default)]
+    pub trace: TraceSection,
+    #[serde(default)]
+    pub dual_stack: DualStackSection,
+}
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ListenerSection {
+    pub bind: Option<BindAddr>,
+    pub port: Option<u16>,
+    pub backlog: Option<u32>,
+    pub acceptors: Option<usize>,
+    pub ipv6_only: Option<bool>,
+    pub proxy_protocol: Option<bool>,
+    pub socket_mode: Option<SocketMode>,
+    pub socket_owner: Option<String>,
+}
+
+/// One `[[listeners]]` entry; the same keys as `[listener]`, plus `tls`
+/// and `redirect_https`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ListenerEntry {
+    pub bind: Option<BindAddr>,
+    pub port: Option<u16>,
+    pub backlog: Option<u32>,
+    pub acceptors: Option<usize>,
+    pub ipv6_only: Option<bool>,
+    pub proxy_protocol: Option<bool>,
+    pub tls: Option<bool>,
+    pub redirect_https: Option<bool>,
+    pub socket_mode: Option<SocketMode>,
+    pub socket_owner: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitSection {
+    pub per_second: Option<u64>,
+    pub burst: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LimitsSection {
+    pub max_concurrent_requests: Option<usize>,
+    pub max_header_bytes: Option<usize>,
+    pub max_body_bytes: Option<usize>,
+    pub max_half_open_per_ip: Option<usize>,
+    pub max_delay_secs: Option<u64>,
+    pub max_generated_bytes: Option<usize>,
+    pub max_speedtest_bytes: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimeoutsSection {
+    pub first_request_secs: Option<u64>,
+    pub header_read_secs: Option<u64>,
+    pub request_secs: Option<u64>,
+    pub idle_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RdnsSection {
+    pub enabled: Option<bool>,
+    pub timeout_ms: Option<u64>,
+    pub cache_ttl_secs: Option<u64>,
+    pub cache_capacity: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsSection {
+    pub cert: Option<PathBuf>,
+    pub key: Option<PathBuf>,
+    pub client_auth: Option<bool>,
+    pub http_port: Option<u16>,
+    pub redirect: Option<bool>,
+    pub redirect_exempt: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MetricsSection {
+    pub addr: Option<SocketAddr>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TcpEchoSection {
+    pub addr: Option<SocketAddr>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UdpEchoSection {
+    pub addr: Option<SocketAddr>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DnsSection {
+    pub addr: Option<SocketAddr>,
+    pub zone: Option<String>,
+    pub rate_limit: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OtlpSection {
+    pub endpoint: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LogFileSection {
+    pub path: Option<PathBuf>,
+    pub max_size_mb: Option<u64>,
+    pub rotation: Option<LogRotation>,
+    pub max_files: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SyslogSection {
+    pub addr: Option<SyslogTarget>,
+    pub facility: Option<SyslogFacility>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CorsSection {
+    pub allowed_origins: Option<Vec<String>>,
+    pub allowed_methods: Option<Vec<String>>,
+    pub allowed_headers: Option<Vec<String>>,
+    pub max_age_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SecurityHeadersSection {
+    pub enabled: Option<bool>,
+    pub hsts_max_age_secs: Option<u64>,
+    pub referrer_policy: Option<String>,
+    pub content_security_policy: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompressionSection {
+    pub enabled: Option<bool>,
+    pub min_bytes: Option<u16>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutboundSection {
+    pub allow_private: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PortcheckSection {
+    pub ports: Option<Vec<u16>>,
+    pub timeout_ms: Option<u64>,
+    pub per_minute: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PingSection {
+    pub enabled: Option<bool>,
+    pub count: Option<u8>,
+    pub timeout_ms: Option<u64>,
+    pub per_minute: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TraceSection {
+    pub enabled: Option<bool>,
+    pub max_hops: Option<u8>,
+    pub timeout_ms: Option<u64>,
+    pub per_minute: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DualStackSection {
+    pub origin: Option<String>,
+    pub ttl_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StatsdSection {
+    pub addr: Option<String>,
+    pub prefix: Option<String>,
+    pub tags: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GeoIpSection {
+    pub backend: Option<GeoIpBackend>,
+    pub city_db: Option<PathBuf>,
+    pub asn_db: Option<PathBuf>,
+    pub account_id: Option<String>,
+    pub license_key: Option<String>,
+    pub refresh_secs: Option<u64>,
+    pub ip2location_db: Option<PathBuf>,
+    pub api_token: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AcmeSection {
+    pub domains: Option<Vec<String>>,
+    pub contact: Option<Vec<String>>,
+    pub cache_dir: Option<PathBuf>,
+    pub staging: Option<bool>,
+}
+
+impl FileConfig {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        toml::from_str(raw).map_err(|e| e.to_string())
+    }
+
+    /// Load the config file. An explicitly requested path must exist; the
+    /// default `echo.toml` is optional.
+    pub fn load(explicit: Option<&Path>) -> Result<Self, String> {
+        let (path, required) = match explicit {
+            Some(p) => (p, true),
+            None => (Path::new(DEFAULT_CONFIG_PATH), false),
+        };
+        match std::fs::read_to_string(path) {
+            Ok(raw) => Self::parse(&raw).map_err(|e| format!("{}: {e}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !required => Ok(Self::default()),
+            Err(e) => Err(format!("failed to read {}: {e}", path.display())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_full_file() {
+        let file = FileConfig::parse(
+            r#"
+            log_level = "debug"
+            trusted_proxies = ["10.0.0.0/8"]
+
+            [listener]
+            bind = "::"
+            port = 8080
+            ipv6_only = true
+
+            [rate_limit]
+            burst = 5
+
+            [rdns]
+            enabled = false
+
+            [tls]
+            cert = "/etc/ipecho/cert.pem"
+            key = "/etc/ipecho/key.pem"
+
+            [geoip]
+            backend = "maxmind"
+            city_db = "/var/lib/GeoIP/GeoLite2-City.mmdb"
+            asn_db = "/var/lib/GeoIP/GeoLite2-ASN.mmdb"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(file.log_level.as_deref(), Some("debug"));
+        assert_eq!(file.listener.port, Some(8080));
+        assert_eq!(file.listener.bind, Some("::".parse().unwrap()));
+        assert_eq!(file.listener.ipv6_only, Some(true));
+        assert_eq!(file.rate_limit.burst, Some(5));
+        assert_eq!(file.rdns.enabled, Some(false));
+        assert_eq!(file.tls.cert, Some(PathBuf::from("/etc/ipecho/cert.pem")));
+        assert_eq!(
+            file.geoip.city_db,
+            Some(PathBuf::from("/var/lib/GeoIP/GeoLite2-City.mmdb"))
+        );
+        assert_eq!(file.geoip.backend, Some(GeoIpBackend::MaxMind));
+        assert_eq!(
+            file.geoip.asn_db,
+            Some(PathBuf::from("/var/lib/GeoIP/GeoLite2-ASN.mmdb"))
+        );
+    }
+
+    #[test]
+    fn parses_listener_list() {
+        let file = FileConfig::parse(
+            r#"
+            [[listeners]]
+            bind = "::"
+            port = 80
+
+            [[listeners]]
+            port = 443
+            tls = true
+
+            [[listeners]]
+            bind = "unix:/run/echo.sock"
+            socket_mode = "600"
+            "#,
+        )
+        .unwrap();
+        let listeners = file.listeners.unwrap();
+        assert_eq!(listeners.len(), 3);
+        assert_eq!(listeners[0].bind, Some("::".parse().unwrap()));
+        assert_eq!(listeners[1].tls, Some(true));
+        assert_eq!(listeners[2].socket_mode, Some(SocketMode(0o600)));
+    }
+
+    #[test]
+    fn empty_file_is_all_defaults() {
+        let file = FileConfig::parse("").unwrap();
+        assert!(file.listener.port.is_none());
+        assert!(file.trusted_proxies.is_none());
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(FileConfig::parse("prot = 80").is_err());
+        assert!(FileConfig::parse("[listener]\nprot = 80").is_err());
+    }
+
+    #[test]
+    fn missing_default_file_is_ok_but_explicit_is_not() {
+        assert!(FileConfig::load(Some(Path::new("/nonexistent/echo.toml"))).is_err());
+    }
+}
@@ -0,0 +1,2451 @@
+use std::env;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use ipnet::IpNet;
+use serde::Deserialize;
+
+mod file;
+
+pub use file::FileConfig;
+use file::{ListenerEntry, ListenerSection};
+
+const DEFAULT_PORT: u16 = 8083;
+const DEFAULT_BIND_ADDR: IpAddr = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+const DEFAULT_LISTEN_BACKLOG: u32 = 1024;
+const DEFAULT_ACCEPTORS: usize = 1;
+const DEFAULT_UNIX_SOCKET_MODE: SocketMode = SocketMode(0o660);
+const DEFAULT_SYNC_INTERVAL_SECS: u64 = 43200;
+const DEFAULT_LOG_LEVEL: &str = "info";
+const DEFAULT_TRUSTED_PROXIES: &str = "127.0.0.1/32,10.0.0.0/8,172.16.0.0/12,192.168.0.0/16";
+const DEFAULT_RATE_LIMIT_PER_SECOND: u64 = 10;
+const DEFAULT_RATE_LIMIT_BURST: u32 = 20;
+const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 1024;
+const DEFAULT_MAX_HEADER_BYTES: usize = 16 * 1024;
+const DEFAULT_MAX_BODY_BYTES: usize = 1024 * 1024;
+const DEFAULT_MAX_HALF_OPEN_PER_IP: usize = 32;
+const DEFAULT_MAX_DELAY_SECS: u64 = 10;
+const DEFAULT_MAX_GENERATED_BYTES: usize = 100 * 1024;
+const DEFAULT_MAX_SPEEDTEST_BYTES: usize = 100 * 1024 * 1024;
+const DEFAULT_HEADER_READ_TIMEOUT_SECS: u64 = 10;
+const DEFAULT_FIRST_REQUEST_TIMEOUT_SECS: u64 = 15;
+const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 60;
+const DEFAULT_SUPPORTED_LANGUAGES: &str = "en";
+const DEFAULT_RDNS_ENABLED: bool = true;
+const DEFAULT_RDNS_TIMEOUT_MS: u64 = 500;
+const DEFAULT_RDNS_CACHE_TTL_SECS: u64 = 3600;
+const DEFAULT_RDNS_CACHE_CAPACITY: usize = 10_000;
+/// A busy public resolver sends many users' queries from one address.
+const DEFAULT_DNS_RATE_LIMIT: u32 = 100;
+const DEFAULT_PROXY_PROTOCOL: bool = false;
+const DEFAULT_ACME_CACHE_DIR: &str = "acme-cache";
+const DEFAULT_HTTP_PORT: u16 = 80;
+const DEFAULT_HTTPS_REDIRECT_EXEMPT: &str = "/ip";
+const DEFAULT_GEOIP_REFRESH_SECS: u64 = 86400;
+const DEFAULT_STATSD_PREFIX: &str = "ipecho";
+const DEFAULT_LOG_FILE_MAX_SIZE_MB: u64 = 100;
+const DEFAULT_LOG_FILE_MAX_FILES: usize = 7;
+const DEFAULT_CORS_ALLOWED_METHODS: [&str; 2] = ["GET", "HEAD"];
+const DEFAULT_CORS_MAX_AGE_SECS: u64 = 600;
+const DEFAULT_SECURITY_HEADERS: bool = false;
+const DEFAULT_HSTS_MAX_AGE_SECS: u64 = 31_536_000;
+const DEFAULT_COMPRESSION: bool = false;
+const DEFAULT_COMPRESSION_MIN_BYTES: u16 = 1024;
+const DEFAULT_PORTCHECK_TIMEOUT_MS: u64 = 3000;
+const DEFAULT_PORTCHECK_PER_MINUTE: u32 = 6;
+const DEFAULT_PING_ENABLED: bool = false;
+const DEFAULT_PING_COUNT: u8 = 4;
+/// Most echo requests one `/ping-me` sends.
+const MAX_PING_COUNT: u8 = 10;
+const DEFAULT_PING_TIMEOUT_MS: u64 = 1000;
+const DEFAULT_PING_PER_MINUTE: u32 = 6;
+const DEFAULT_TRACE_ENABLED: bool = false;
+const DEFAULT_TRACE_MAX_HOPS: u8 = 30;
+/// Highest TTL `/trace-me` will probe with.
+const MAX_TRACE_HOPS: u8 = 64;
+const DEFAULT_TRACE_TIMEOUT_MS: u64 = 1000;
+const DEFAULT_TRACE_PER_MINUTE: u32 = 2;
+const DEFAULT_DUAL_STACK_TTL_SECS: u64 = 300;
+const DEFAULT_REFERRER_POLICY: &str = "no-referrer";
+/// Enough for the landing page's inline style and script, nothing else.
+const DEFAULT_CONTENT_SECURITY_POLICY: &str = "default-src 'none'; script-src 'unsafe-inline'; \
+     style-src 'unsafe-inline'; base-uri 'none'; form-action 'none'; frame-ancestors 'none'";
+
+/// Values `Referrer-Policy` accepts.
+const REFERRER_POLICIES: [&str; 8] = [
+    "no-referrer",
+    "no-referrer-when-downgrade",
+    "origin",
+    "origin-when-cross-origin",
+    "same-origin",
+    "strict-origin",
+    "strict-origin-when-cross-origin",
+    "unsafe-url",
+];
+
+/// Where geo and ASN data comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GeoIpBackend {
+    /// Local MaxMind `.mmdb` files (`GEOIP_CITY_DB`, `GEOIP_ASN_DB`).
+    MaxMind,
+    /// A local IP2Location CSV file (`GEOIP_IP2LOCATION_DB`).
+    Ip2Location,
+    /// The ipinfo.io API.
+    IpInfo,
+    /// The ipapi.co API.
+    IpApi,
+}
+
+impl FromStr for GeoIpBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "maxmind" => Ok(Self::MaxMind),
+            "ip2location" => Ok(Self::Ip2Location),
+            "ipinfo" => Ok(Self::IpInfo),
+            "ipapi" => Ok(Self::IpApi),
+            _ => Err("expected maxmind, ip2location, ipinfo or ipapi".into()),
+        }
+    }
+}
+
+/// How log lines are written to stdout.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines with ANSI colours.
+    #[default]
+    Text,
+    /// One JSON object per line, for log shippers.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err("expected text or json".into()),
+        }
+    }
+}
+
+/// When `LOG_FILE` is rotated regardless of size.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    /// Only when it reaches `LOG_FILE_MAX_SIZE_MB`.
+    Never,
+    /// At the start of every UTC hour.
+    Hourly,
+    /// At midnight UTC.
+    #[default]
+    Daily,
+}
+
+impl FromStr for LogRotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "never" => Ok(Self::Never),
+            "hourly" => Ok(Self::Hourly),
+            "daily" => Ok(Self::Daily),
+            _ => Err("expected never, hourly or daily".into()),
+        }
+    }
+}
+
+/// What `BIND_ADDR` (or `--bind`) names: an IP address to listen on with
+/// TCP, or `unix:/path` for a Unix domain socket.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum BindAddr {
+    Ip(IpAddr),
+    Unix(PathBuf),
+}
+
+impl FromStr for BindAddr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s.strip_prefix("unix:") {
+            Some("") => Err("missing socket path after unix:".into()),
+            Some(path) => Ok(Self::Unix(PathBuf::from(path))),
+            None => s
+                .parse()
+                .map(Self::Ip)
+                .map_err(|_| "expected an IP address or unix:/path".into()),
+        }
+    }
+}
+
+impl TryFrom<String> for BindAddr {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Permission bits for the Unix socket, written in octal (`660`, `0660`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct SocketMode(pub u32);
+
+impl FromStr for SocketMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match u32::from_str_radix(s.trim(), 8) {
+            Ok(mode) if mode <= 0o777 => Ok(Self(mode)),
+            _ => Err("expected octal permission bits such as 660".into()),
+        }
+    }
+}
+
+impl TryFrom<String> for SocketMode {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// One socket to serve on: a `[[listeners]]` entry in the config file, or
+/// the single listener the top-level settings describe.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListenerConfig {
+    pub bind: BindAddr,
+    /// Ignored for a Unix socket.
+    pub port: u16,
+    pub backlog: u32,
+    /// See [`Config::acceptors`]; always 1 for a Unix socket.
+    pub acceptors: usize,
+    pub ipv6_only: bool,
+    pub proxy_protocol: bool,
+    /// Serve HTTPS with the certificate from `TLS_CERT` or ACME.
+    pub tls: bool,
+    /// 301-redirect plain HTTP requests to HTTPS on this port, except for
+    /// `HTTPS_REDIRECT_EXEMPT` paths.
+    pub redirect_https: Option<u16>,
+    pub socket_mode: u32,
+    pub socket_owner: Option<String>,
+}
+
+impl ListenerConfig {
+    /// The TCP address, for a listener that isn't a Unix socket.
+    pub fn addr(&self) -> Option<SocketAddr> {
+        match self.bind {
+            BindAddr::Ip(ip) => Some(SocketAddr::new(ip, self.port)),
+            BindAddr::Unix(_) => None,
+        }
+    }
+}
+
+/// Where `SYSLOG_ADDR` sends log messages.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum SyslogTarget {
+    /// A local datagram socket such as `/dev/log`.
+    Unix(PathBuf),
+    /// `host:port`, one message per datagram.
+    Udp(String),
+    /// `host:port`, with RFC 6587 octet-counting framing.
+    Tcp(String),
+}
+
+impl FromStr for SyslogTarget {
+    type Err = String;
+
+    /// `unix:///dev/log` (or just `/dev/log`), `udp://host[:514]` or
+    /// `tcp://host[:601]`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let with_port = |rest: &str, default: u16| -> Result<String, String> {
+            let rest = rest.trim_end_matches('/');
+            let has_port = rest
+                .rsplit_once(':')
+                .is_some_and(|(host, port)| !host.ends_with(':') && port.parse::<u16>().is_ok());
+            match rest {
+                "" => Err("missing host".into()),
+                _ if has_port => Ok(rest.to_string()),
+                _ => Ok(format!("{rest}:{default}")),
+            }
+        };
+        if let Some(path) = s.strip_prefix("unix://") {
+            Ok(Self::Unix(PathBuf::from(path)))
+        } else if s.starts_with('/') {
+            Ok(Self::Unix(PathBuf::from(s)))
+        } else if let Some(rest) = s.strip_prefix("udp://") {
+            with_port(rest, 514).map(Self::Udp)
+        } else if let Some(rest) = s.strip_prefix("tcp://") {
+            with_port(rest, 601).map(Self::Tcp)
+        } else {
+            Err("expected unix:///path, udp://host:port or tcp://host:port".into())
+        }
+    }
+}
+
+impl fmt::Display for SyslogTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unix(path) => write!(f, "unix://{}", path.display()),
+            Self::Udp(addr) => write!(f, "udp://{addr}"),
+            Self::Tcp(addr) => write!(f, "tcp://{addr}"),
+        }
+    }
+}
+
+impl TryFrom<String> for SyslogTarget {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Syslog facility code (RFC 5424 section 6.2.1), parsed from its name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct SyslogFacility(pub u8);
+
+impl SyslogFacility {
+    pub const DAEMON: Self = Self(3);
+}
+
+impl FromStr for SyslogFacility {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const NAMES: [&str; 12] = [
+            "kern", "user", "mail", "daemon", "auth", "syslog", "lpr", "news", "uucp", "cron",
+            "authpriv", "ftp",
+        ];
+        let s = s.trim().to_ascii_lowercase();
+        if let Some(code) = NAMES.iter().position(|n| *n == s) {
+            return Ok(Self(code as u8));
+        }
+        match s.strip_prefix("local").and_then(|n| n.parse::<u8>().ok()) {
+            Some(n @ 0..=7) => Ok(Self(16 + n)),
+            _ => Err("expected a facility name such as daemon, user or local0-local7".into()),
+        }
+    }
+}
+
+impl TryFrom<String> for SyslogFacility {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub port: u16,
+    pub bind_addr: IpAddr,
+    /// Serve on this Unix domain socket instead of TCP (`BIND_ADDR=unix:/path`).
+    pub unix_socket: Option<PathBuf>,
+    /// Permission bits for `unix_socket`.
+    pub unix_socket_mode: u32,
+    /// `user[:group]` (names or numeric IDs) to own `unix_socket`.
+    pub unix_socket_owner: Option<String>,
+    pub listen_backlog: u32,
+    /// Listening sockets to open on the same address with `SO_REUSEPORT`,
+    /// each with its own accept loop, so the kernel spreads new
+    /// connections across them. 1 opens a single socket without it.
+    pub acceptors: usize,
+    /// Set `IPV6_V6ONLY` on an IPv6 listener. When false, binding `::`
+    /// accepts IPv4 too (as IPv4-mapped addresses).
+    pub ipv6_only: bool,
+    /// From `[[listeners]]` in the config file, replacing the single
+    /// listener above; see [`Config::all_listeners`].
+    pub listeners: Vec<ListenerConfig>,
+    pub sync_interval_secs: u64,
+    pub log_level: String,
+    pub log_format: LogFormat,
+    /// Also send logs to syslog, RFC 5424 formatted.
+    pub syslog_addr: Option<SyslogTarget>,
+    pub syslog_facility: SyslogFacility,
+    /// Combined Log Format access log file; `-` for stdout.
+    pub access_log: Option<PathBuf>,
+    /// Also write logs to this file as newline-delimited JSON.
+    pub log_file: Option<PathBuf>,
+    /// Rotate `log_file` once it exceeds this size; 0 disables the limit.
+    pub log_file_max_size_mb: u64,
+    pub log_file_rotation: LogRotation,
+    /// Rotated files to keep; 0 keeps all of them.
+    pub log_file_max_files: usize,
+    pub trusted_proxies: Vec<IpNet>,
+    pub rate_limit_per_second: u64,
+    pub rate_limit_burst: u32,
+    /// Public requests handled at once before new ones are shed with a 503;
+    /// 0 for no limit.
+    pub max_concurrent_requests: usize,
+    /// Largest total size of request header names and values; larger
+    /// requests get a 431.
+    pub max_header_bytes: usize,
+    /// Largest request body accepted; larger ones get a 413.
+    pub max_body_bytes: usize,
+    /// Connections per source IP that may be open without having sent a
+    /// request yet; further ones are closed on accept. 0 for no limit.
+    pub max_half_open_per_ip: usize,
+    /// Longest wait `/delay/{n}` and `/drip` will do; longer ones are cut
+    /// to it.
+    pub max_delay_secs: u64,
+    /// Most bytes `/bytes/{n}` and `/drip` will send; larger counts are cut
+    /// to it. `/pad/{n}` refuses them instead.
+    pub max_generated_bytes: usize,
+    /// Largest transfer the `/speedtest` endpoints accept; 0 disables them.
+    pub max_speedtest_bytes: usize,
+    /// Time from accept to the first complete request head, covering the
+    /// PROXY header and TLS handshake; 0 for none.
+    pub first_request_timeout_secs: u64,
+    /// Time an HTTP/1 client has to send a complete request head, including
+    /// the wait for the next request on a keep-alive connection; 0 for none.
+    pub header_read_timeout_secs: u64,
+    /// Time a request has to produce a response before it's answered with a
+    /// 408; 0 for none.
+    pub request_timeout_secs: u64,
+    /// Close a connection after this long without reading or writing a
+    /// byte; 0 for never.
+    pub idle_timeout_secs: u64,
+    pub excluded_headers: Vec<String>,
+    /// Locales `/lang.json` picks the client's best match from.
+    pub supported_languages: Vec<String>,
+    pub rdns_enabled: bool,
+    pub rdns_timeout_ms: u64,
+    pub rdns_cache_ttl_secs: u64,
+    pub rdns_cache_capacity: usize,
+    /// Require a PROXY protocol v1/v2 header on every accepted connection.
+    pub proxy_protocol: bool,
+    /// PEM certificate chain and private key. When both are set the
+    /// listener terminates TLS itself.
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    /// Ask TLS clients for a certificate, without requiring or checking
+    /// one, to report it at `/cert`.
+    pub tls_client_auth: bool,
+    /// Hostnames to obtain certificates for via ACME. Non-empty enables
+    /// HTTPS with automatically managed certificates.
+    pub acme_domains: Vec<String>,
+    pub acme_contact: Vec<String>,
+    pub acme_cache_dir: PathBuf,
+    /// Use the Let's Encrypt staging directory instead of production.
+    pub acme_staging: bool,
+    /// With HTTPS, also serve plain HTTP on this port; 0 for none.
+    pub http_port: u16,
+    /// Redirect requests on `http_port` to HTTPS.
+    pub https_redirect: bool,
+    /// Paths still answered over plain HTTP when redirecting, for clients
+    /// like `curl http://...` that don't follow redirects.
+    pub https_redirect_exempt: Vec<String>,
+    pub geoip_backend: GeoIpBackend,
+    /// MaxMind City database (`.mmdb`) for geolocation. Unset disables it.
+    pub geoip_city_db: Option<PathBuf>,
+    /// MaxMind ASN database (`.mmdb`) for AS number and organization.
+    pub geoip_asn_db: Option<PathBuf>,
+    /// MaxMind account credentials. With a license key set, the configured
+    /// databases are downloaded and refreshed in the background.
+    pub geoip_account_id: Option<String>,
+    pub geoip_license_key: Option<String>,
+    pub geoip_refresh_secs: u64,
+    /// IP2Location CSV database (DB1 through DB11 layouts).
+    pub geoip_ip2location_db: Option<PathBuf>,
+    /// Token for the ipinfo/ipapi backends; both have a keyless free tier.
+    pub geoip_api_token: Option<String>,
+    /// Serve `/metrics` on this address instead of the main listener, e.g.
+    /// a loopback or internal-only admin port.
+    pub metrics_addr: Option<SocketAddr>,
+    /// Raw TCP listener that writes each client its own `ip:port` and
+    /// closes, for `nc`/`telnet` users; see [`crate::tcp_echo`].
+    pub tcp_echo_addr: Option<SocketAddr>,
+    /// UDP socket that answers every datagram with the sender's
+    /// `ip:port`; see [`crate::udp_echo`].
+    pub udp_echo_addr: Option<SocketAddr>,
+    /// Authoritative DNS server, over UDP and TCP, answering queries for
+    /// `dns_zone` with the address of the resolver that asked; see
+    /// [`crate::dns_server`].
+    pub dns_addr: Option<SocketAddr>,
+    /// Lowercase, without the trailing dot. Required with `dns_addr`.
+    pub dns_zone: Option<String>,
+    /// Queries per second, and burst, per source address on `dns_addr`;
+    /// separate from the HTTP rate limit.
+    pub dns_rate_limit: u32,
+    /// OTLP/HTTP collector base URL, e.g. `http://otel-collector:4318`.
+    /// Set to export a trace span for every request.
+    pub otlp_endpoint: Option<String>,
+    /// StatsD agent as `host:port`. Set to send every metric there too.
+    pub statsd_addr: Option<String>,
+    /// Prepended to metric names as `prefix.`; empty for none.
+    pub statsd_prefix: String,
+    /// DogStatsD tags (`key:value` or bare) added to every metric.
+    pub statsd_tags: Vec<String>,
+    /// Origins (`https://host[:port]`) allowed to call the API from a
+    /// browser, or `*` for any. Empty disables CORS.
+    pub cors_allowed_origins: Vec<String>,
+    /// Methods allowed in cross-origin requests, uppercase.
+    pub cors_allowed_methods: Vec<String>,
+    /// Request headers allowed in cross-origin requests, lowercase.
+    pub cors_allowed_headers: Vec<String>,
+    /// How long browsers may cache a preflight response.
+    pub cors_max_age_secs: u64,
+    /// Add `X-Content-Type-Options`, `Referrer-Policy`, HSTS and (on HTML)
+    /// CSP headers to responses.
+    pub security_headers: bool,
+    /// `Strict-Transport-Security` max-age; 0 omits the header.
+    pub hsts_max_age_secs: u64,
+    pub referrer_policy: String,
+    /// Sent on HTML responses only; empty omits the header.
+    pub content_security_policy: String,
+    /// Compress text responses (JSON, HTML, ...) with gzip, brotli or zstd
+    /// when the client accepts one.
+    pub compression: bool,
+    /// Responses smaller than this are sent uncompressed.
+    pub compression_min_bytes: u16,
+    /// Let `/portcheck`, `/ping-me` and `/trace-me` target private, CGNAT,
+    /// link-local and loopback clients too, for a server that only faces a
+    /// LAN.
+    pub outbound_allow_private: bool,
+    /// Ports `/portcheck/{port}` may connect back to; empty disables it.
+    pub portcheck_ports: Vec<u16>,
+    /// How long a `/portcheck` connect may take before the port counts as
+    /// filtered.
+    pub portcheck_timeout_ms: u64,
+    /// `/portcheck` requests per client IP per minute.
+    pub portcheck_per_minute: u32,
+    /// Serve `/ping-me`, which sends ICMP echo requests to the client.
+    pub ping_enabled: bool,
+    /// Echo requests per `/ping-me`.
+    pub ping_count: u8,
+    /// How long to wait for each echo reply.
+    pub ping_timeout_ms: u64,
+    /// `/ping-me` requests per client IP per minute.
+    pub ping_per_minute: u32,
+    /// Serve `/trace-me`, a traceroute from the server to the client.
+    pub trace_enabled: bool,
+    /// Highest TTL `/trace-me` probes with.
+    pub trace_max_hops: u8,
+    /// How long to wait for each hop's answer.
+    pub trace_timeout_ms: u64,
+    /// `/trace-me` requests per client IP per minute.
+    pub trace_per_minute: u32,
+    /// Origin (`https://example.com`) whose `ipv4.` and `ipv6.` subdomains
+    /// serve `/ds/{token}`; unset disables the `/ds` flow.
+    pub dual_stack_origin: Option<String>,
+    /// How long a `/ds` token stays valid.
+    pub dual_stack_ttl_secs: u64,
+}
+
+impl Default for Config {
+    /// The configuration used when no env vars are set. Also the base that
+    /// tests build on via struct update syntax.
+    fn default() -> Self {
+        Self {
+            port: DEFAULT_PORT,
+            bind_addr: DEFAULT_BIND_ADDR,
+            unix_socket: None,
+            unix_socket_mode: DEFAULT_UNIX_SOCKET_MODE.0,
+            unix_socket_owner: None,
+            listen_backlog: DEFAULT_LISTEN_BACKLOG,
+            acceptors: DEFAULT_ACCEPTORS,
+            ipv6_only: false,
+            listeners: Vec::new(),
+            sync_interval_secs: DEFAULT_SYNC_INTERVAL_SECS,
+            log_level: DEFAULT_LOG_LEVEL.to_string(),
+            log_format: LogFormat::Text,
+            syslog_addr: None,
+            syslog_facility: SyslogFacility::DAEMON,
+            access_log: None,
+            log_file: None,
+            log_file_max_size_mb: DEFAULT_LOG_FILE_MAX_SIZE_MB,
+            log_file_rotation: LogRotation::Daily,
+            log_file_max_files: DEFAULT_LOG_FILE_MAX_FILES,
+            trusted_proxies: parse_trusted_proxies(DEFAULT_TRUSTED_PROXIES)
+                .expect("built-in default TRUSTED_PROXIES should always parse"),
+            rate_limit_per_second: DEFAULT_RATE_LIMIT_PER_SECOND,
+            rate_limit_burst: DEFAULT_RATE_LIMIT_BURST,
+            max_concurrent_requests: DEFAULT_MAX_CONCURRENT_REQUESTS,
+            max_header_bytes: DEFAULT_MAX_HEADER_BYTES,
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            max_half_open_per_ip: DEFAULT_MAX_HALF_OPEN_PER_IP,
+            max_delay_secs: DEFAULT_MAX_DELAY_SECS,
+            max_generated_bytes: DEFAULT_MAX_GENERATED_BYTES,
+            max_speedtest_bytes: DEFAULT_MAX_SPEEDTEST_BYTES,
+            first_request_timeout_secs: DEFAULT_FIRST_REQUEST_TIMEOUT_SECS,
+            header_read_timeout_secs: DEFAULT_HEADER_READ_TIMEOUT_SECS,
+            request_timeout_secs: DEFAULT_REQUEST_TIMEOUT_SECS,
+            idle_timeout_secs: DEFAULT_IDLE_TIMEOUT_SECS,
+            excluded_headers: Vec::new(),
+            supported_languages: vec![DEFAULT_SUPPORTED_LANGUAGES.to_string()],
+            rdns_enabled: DEFAULT_RDNS_ENABLED,
+            rdns_timeout_ms: DEFAULT_RDNS_TIMEOUT_MS,
+            rdns_cache_ttl_secs: DEFAULT_RDNS_CACHE_TTL_SECS,
+            rdns_cache_capacity: DEFAULT_RDNS_CACHE_CAPACITY,
+            proxy_protocol: DEFAULT_PROXY_PROTOCOL,
+            tls_cert: None,
+            tls_key: None,
+            tls_client_auth: false,
+            acme_domains: Vec::new(),
+            acme_contact: Vec::new(),
+            acme_cache_dir: PathBuf::from(DEFAULT_ACME_CACHE_DIR),
+            acme_staging: false,
+            http_port: DEFAULT_HTTP_PORT,
+            https_redirect: true,
+            https_redirect_exempt: vec![DEFAULT_HTTPS_REDIRECT_EXEMPT.to_string()],
+            geoip_backend: GeoIpBackend::MaxMind,
+            geoip_city_db: None,
+            geoip_asn_db: None,
+            geoip_account_id: None,
+            geoip_license_key: None,
+            geoip_refresh_secs: DEFAULT_GEOIP_REFRESH_SECS,
+            geoip_ip2location_db: None,
+            geoip_api_token: None,
+            metrics_addr: None,
+            tcp_echo_addr: None,
+            udp_echo_addr: None,
+            dns_addr: None,
+            dns_zone: None,
+            dns_rate_limit: DEFAULT_DNS_RATE_LIMIT,
+            otlp_endpoint: None,
+            statsd_addr: None,
+            statsd_prefix: DEFAULT_STATSD_PREFIX.to_string(),
+            statsd_tags: Vec::new(),
+            cors_allowed_origins: Vec::new(),
+            cors_allowed_methods: DEFAULT_CORS_ALLOWED_METHODS.map(String::from).to_vec(),
+            cors_allowed_headers: Vec::new(),
+            cors_max_age_secs: DEFAULT_CORS_MAX_AGE_SECS,
+            security_headers: DEFAULT_SECURITY_HEADERS,
+            hsts_max_age_secs: DEFAULT_HSTS_MAX_AGE_SECS,
+            referrer_policy: DEFAULT_REFERRER_POLICY.to_string(),
+            content_security_policy: DEFAULT_CONTENT_SECURITY_POLICY.to_string(),
+            compression: DEFAULT_COMPRESSION,
+            compression_min_bytes: DEFAULT_COMPRESSION_MIN_BYTES,
+            outbound_allow_private: false,
+            portcheck_ports: Vec::new(),
+            portcheck_timeout_ms: DEFAULT_PORTCHECK_TIMEOUT_MS,
+            portcheck_per_minute: DEFAULT_PORTCHECK_PER_MINUTE,
+            ping_enabled: DEFAULT_PING_ENABLED,
+            ping_count: DEFAULT_PING_COUNT,
+            ping_timeout_ms: DEFAULT_PING_TIMEOUT_MS,
+            ping_per_minute: DEFAULT_PING_PER_MINUTE,
+            trace_enabled: DEFAULT_TRACE_ENABLED,
+            trace_max_hops: DEFAULT_TRACE_MAX_HOPS,
+            trace_timeout_ms: DEFAULT_TRACE_TIMEOUT_MS,
+            trace_per_minute: DEFAULT_TRACE_PER_MINUTE,
+            dual_stack_origin: None,
+            dual_stack_ttl_secs: DEFAULT_DUAL_STACK_TTL_SECS,
+        }
+    }
+}
+
+/// Prefix for env var overrides. `ECHO_PORT` wins over the legacy bare
+/// `PORT`, which is still honored so existing deployments keep working.
+const ENV_PREFIX: &str = "ECHO_";
+
+/// Read an env var, preferring the `ECHO_`-prefixed name. Returns
+/// `Ok(None)` if unset, `Ok(Some((name, raw)))` if set (even to an empty
+/// string), and `Err(...)` if set to invalid Unicode.
+fn read_env(key: &str) -> Result<Option<(String, String)>, String> {
+    for name in [format!("{ENV_PREFIX}{key}"), key.to_string()] {
+        match env::var(&name) {
+            Ok(v) => return Ok(Some((name, v))),
+            Err(env::VarError::NotPresent) => {}
+            Err(env::VarError::NotUnicode(_)) => {
+                return Err(format!("{name} is set to invalid Unicode"));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Resolve a setting: an env var if set, else the config file value, else
+/// `default`. Explicitly configured values (env or file) must parse and
+/// pass `validate`, otherwise startup fails.
+fn parse_env<T, F>(key: &str, file: Option<T>, default: T, validate: F) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+    F: Fn(&T) -> Result<(), String>,
+{
+    match read_env(key)? {
+        None => match file {
+            Some(v) => {
+                validate(&v).map_err(|e| {
+                    format!("{} in config file is invalid: {e}", key.to_lowercase())
+                })?;
+                Ok(v)
+            }
+            None => Ok(default),
+        },
+        Some((name, raw)) => {
+            let parsed: T = raw
+                .parse()
+                .map_err(|e| format!("{name}=\"{raw}\" is not a valid value: {e}"))?;
+            validate(&parsed).map_err(|e| format!("{name}=\"{raw}\" is invalid: {e}"))?;
+            Ok(parsed)
+        }
+    }
+}
+
+fn nonzero<T: Default + PartialEq>(v: &T) -> Result<(), String> {
+    if *v == T::default() {
+        Err("must be greater than 0".into())
+    } else {
+        Ok(())
+    }
+}
+
+fn any<T>(_: &T) -> Result<(), String> {
+    Ok(())
+}
+
+/// A hostname: dot-separated labels of letters, digits, `-` and `_`.
+fn is_domain(s: &str) -> bool {
+    s.len() <= 253
+        && s.split('.').all(|label| {
+            (1..=63).contains(&label.len())
+                && label
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+        })
+}
+
+/// A browser `Origin` value: scheme, host and optional port, nothing else.
+fn is_origin(s: &str) -> bool {
+    url::Url::parse(s).is_ok_and(|url| url.origin().ascii_serialization() == s)
+}
+
+fn parse_trusted_proxies(raw: &str) -> Result<Vec<IpNet>, String> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse::<IpNet>()
+                .map_err(|e| format!("\"{s}\" is not a valid CIDR: {e}"))
+        })
+        .collect()
+}
+
+/// Comma-separated list from env, or a list from the config file.
+fn parse_list(key: &str, file: Option<Vec<String>>) -> Result<Option<(String, String)>, String> {
+    Ok(read_env(key)?.or_else(|| {
+        file.map(|v| {
+            (
+                format!("{} in config file", key.to_lowercase()),
+                v.join(","),
+            )
+        })
+    }))
+}
+
+/// A socket address from env, or the config file. Set but empty in env
+/// overrides the file with none.
+fn parse_socket_addr(key: &str, file: Option<SocketAddr>) -> Result<Option<SocketAddr>, String> {
+    match read_env(key)? {
+        Some((_, raw)) if raw.trim().is_empty() => Ok(None),
+        Some((name, raw)) => raw
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|e| format!("{name}=\"{raw}\" is not a valid value: {e}")),
+        None => Ok(file),
+    }
+}
+
+/// Fill in the defaults for a `[[listeners]]` entry. `tls` says whether
+/// a certificate source is configured, `https_port` is the port of the
+/// first HTTPS listener.
+fn listener_config(
+    entry: ListenerEntry,
+    tls: bool,
+    https_port: Option<u16>,
+) -> Result<ListenerConfig, String> {
+    if entry.port == Some(0) {
+        return Err("port in [[listeners]] must be non-zero".into());
+    }
+    match (entry.acceptors, &entry.bind) {
+        (Some(0), _) => return Err("acceptors in [[listeners]] must be non-zero".into()),
+        (Some(2..), Some(BindAddr::Unix(_))) => {
+            return Err("acceptors > 1 in [[listeners]] requires a TCP listener".into());
+        }
+        _ => {}
+    }
+    if entry.tls == Some(true) && !tls {
+        return Err("tls = true in [[listeners]] requires TLS_CERT/TLS_KEY or ACME_DOMAINS".into());
+    }
+    let redirect_https = match (entry.redirect_https, entry.tls, https_port) {
+        (Some(true), Some(true), _) => {
+            return Err("redirect_https in [[listeners]] is for plain HTTP listeners".into());
+        }
+        (Some(true), _, None) => {
+            return Err(
+                "redirect_https in [[listeners]] requires a listener with tls = true".into(),
+            );
+        }
+        (Some(true), _, port) => port,
+        _ => None,
+    };
+    Ok(ListenerConfig {
+        bind: entry.bind.unwrap_or(BindAddr::Ip(DEFAULT_BIND_ADDR)),
+        port: entry.port.unwrap_or(DEFAULT_PORT),
+        backlog: entry.backlog.unwrap_or(DEFAULT_LISTEN_BACKLOG),
+        acceptors: entry.acceptors.unwrap_or(DEFAULT_ACCEPTORS),
+        ipv6_only: entry.ipv6_only.unwrap_or(false),
+        proxy_protocol: entry.proxy_protocol.unwrap_or(DEFAULT_PROXY_PROTOCOL),
+        tls: entry.tls.unwrap_or(false),
+        redirect_https,
+        socket_mode: entry.socket_mode.unwrap_or(DEFAULT_UNIX_SOCKET_MODE).0,
+        socket_owner: entry.socket_owner,
+    })
+}
+
+impl Config {
+    /// Layer env vars over `file` over built-in defaults.
+    pub fn load(file: FileConfig) -> Result<Self, String> {
+        let FileConfig {
+            log_level: file_log_level,
+            log_format: file_log_format,
+            access_log: file_access_log,
+            sync_interval_secs: file_sync_interval_secs,
+            trusted_proxies: file_trusted_proxies,
+            excluded_headers: file_excluded_headers,
+            supported_languages: file_supported_languages,
+            listener,
+            listeners: file_listeners,
+            rate_limit,
+            limits,
+            timeouts,
+            rdns,
+            tls,
+            acme,
+            geoip,
+            metrics,
+            tcp_echo,
+            udp_echo,
+            dns,
+            otlp,
+            statsd,
+            log_file,
+            syslog,
+            cors,
+            security_headers,
+            compression,
+            outbound,
+            portcheck,
+            ping,
+            trace,
+            dual_stack,
+        } = file;
+
+        let file_listeners = file_listeners.unwrap_or_default();
+        if !file_listeners.is_empty() && listener != ListenerSection::default() {
+            return Err("[listener] and [[listeners]] can't be combined".into());
+        }
+
+        let port = parse_env::<u16, _>("PORT", listener.port, DEFAULT_PORT, |v| {
+            if *v == 0 {
+                Err("must be between 1 and 65535".into())
+            } else {
+                Ok(())
+            }
+        })?;
+
+        let (bind_addr, unix_socket) = match parse_env(
+            "BIND_ADDR",
+            listener.bind,
+            BindAddr::Ip(DEFAULT_BIND_ADDR),
+            any,
+        )? {
+            BindAddr::Ip(ip) => (ip, None),
+            BindAddr::Unix(path) => (DEFAULT_BIND_ADDR, Some(path)),
+        };
+        let unix_socket_mode = parse_env(
+            "UNIX_SOCKET_MODE",
+            listener.socket_mode,
+            DEFAULT_UNIX_SOCKET_MODE,
+            any,
+        )?
+        .0;
+        let unix_socket_owner = read_env("UNIX_SOCKET_OWNER")?
+            .map(|(_, v)| v)
+            .or(listener.socket_owner)
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+
+        let listen_backlog = parse_env(
+            "LISTEN_BACKLOG",
+            listener.backlog,
+            DEFAULT_LISTEN_BACKLOG,
+            nonzero,
+        )?;
+        let acceptors = parse_env("ACCEPTORS", listener.acceptors, DEFAULT_ACCEPTORS, nonzero)?;
+        if acceptors > 1 && unix_socket.is_some() {
+            return Err("ACCEPTORS > 1 requires a TCP listener".into());
+        }
+
+        let ipv6_only = parse_env("IPV6_ONLY", listener.ipv6_only, false, any)?;
+
+        let sync_interval_secs = parse_env(
+            "SYNC_INTERVAL_SECS",
+            file_sync_interval_secs,
+            DEFAULT_SYNC_INTERVAL_SECS,
+            nonzero,
+        )?;
+
+        let log_level = match read_env("LOG_LEVEL")?.map(|(_, v)| v).or(file_log_level) {
+            Some(s) if !s.trim().is_empty() => s,
+            _ => DEFAULT_LOG_LEVEL.to_string(),
+        };
+
+        let log_format = parse_env("LOG_FORMAT", file_log_format, LogFormat::Text, any)?;
+
+        let syslog_addr = match read_env("SYSLOG_ADDR")? {
+            Some((_, raw)) if raw.trim().is_empty() => None,
+            Some((name, raw)) => Some(
+                raw.parse()
+                    .map_err(|e| format!("{name}=\"{raw}\" is not a valid value: {e}"))?,
+            ),
+            None => syslog.addr,
+        };
+        let syslog_facility = parse_env(
+            "SYSLOG_FACILITY",
+            syslog.facility,
+            SyslogFacility::DAEMON,
+            any,
+        )?;
+
+        let access_log = match read_env("ACCESS_LOG")? {
+            Some((_, raw)) if raw.trim().is_empty() => None,
+            Some((_, raw)) => Some(PathBuf::from(raw)),
+            None => file_access_log,
+        };
+
+        let log_file_path = match read_env("LOG_FILE")? {
+            Some((_, raw)) if raw.trim().is_empty() => None,
+            Some((_, raw)) => Some(PathBuf::from(raw)),
+            None => log_file.path,
+        };
+        let log_file_max_size_mb = parse_env(
+            "LOG_FILE_MAX_SIZE_MB",
+            log_file.max_size_mb,
+            DEFAULT_LOG_FILE_MAX_SIZE_MB,
+            any,
+        )?;
+        let log_file_rotation = parse_env(
+            "LOG_FILE_ROTATION",
+            log_file.rotation,
+            LogRotation::Daily,
+            any,
+        )?;
+        let log_file_max_files = parse_env(
+            "LOG_FILE_MAX_FILES",
+            log_file.max_files,
+            DEFAULT_LOG_FILE_MAX_FILES,
+            any,
+        )?;
+
+        let trusted_proxies = match parse_list("TRUSTED_PROXIES", file_trusted_proxies)? {
+            None => parse_trusted_proxies(DEFAULT_TRUSTED_PROXIES)
+                .expect("built-in default TRUSTED_PROXIES should always parse"),
+            Some((name, raw)) => {
+                let parsed =
+                    parse_trusted_proxies(&raw).map_err(|e| format!("{name} is invalid: {e}"))?;
+                if parsed.is_empty() {
+                    return Err(format!("{name} is set but contains no entries: \"{raw}\""));
+                }
+                parsed
+            }
+        };
+
+        let rate_limit_per_second = parse_env(
+            "RATE_LIMIT_PER_SECOND",
+            rate_limit.per_second,
+            DEFAULT_RATE_LIMIT_PER_SECOND,
+            nonzero,
+        )?;
+
+        let rate_limit_burst = parse_env(
+            "RATE_LIMIT_BURST",
+            rate_limit.burst,
+            DEFAULT_RATE_LIMIT_BURST,
+            nonzero,
+        )?;
+
+        let max_concurrent_requests = parse_env(
+            "MAX_CONCURRENT_REQUESTS",
+            limits.max_concurrent_requests,
+            DEFAULT_MAX_CONCURRENT_REQUESTS,
+            any,
+        )?;
+
+        let max_header_bytes = parse_env(
+            "MAX_HEADER_BYTES",
+            limits.max_header_bytes,
+            DEFAULT_MAX_HEADER_BYTES,
+            nonzero,
+        )?;
+        let max_body_bytes = parse_env(
+            "MAX_BODY_BYTES",
+            limits.max_body_bytes,
+            DEFAULT_MAX_BODY_BYTES,
+            nonzero,
+        )?;
+
+        let max_half_open_per_ip = parse_env(
+            "MAX_HALF_OPEN_PER_IP",
+            limits.max_half_open_per_ip,
+            DEFAULT_MAX_HALF_OPEN_PER_IP,
+            any,
+        )?;
+        let max_delay_secs = parse_env(
+            "MAX_DELAY_SECS",
+            limits.max_delay_secs,
+            DEFAULT_MAX_DELAY_SECS,
+            any,
+        )?;
+        let max_generated_bytes = parse_env(
+            "MAX_GENERATED_BYTES",
+            limits.max_generated_bytes,
+            DEFAULT_MAX_GENERATED_BYTES,
+            any,
+        )?;
+        let max_speedtest_bytes = parse_env(
+            "MAX_SPEEDTEST_BYTES",
+            limits.max_speedtest_bytes,
+            DEFAULT_MAX_SPEEDTEST_BYTES,
+            any,
+        )?;
+
+        let first_request_timeout_secs = parse_env(
+            "FIRST_REQUEST_TIMEOUT_SECS",
+            timeouts.first_request_secs,
+            DEFAULT_FIRST_REQUEST_TIMEOUT_SECS,
+            any,
+        )?;
+        let header_read_timeout_secs = parse_env(
+            "HEADER_READ_TIMEOUT_SECS",
+            timeouts.header_read_secs,
+            DEFAULT_HEADER_READ_TIMEOUT_SECS,
+            any,
+        )?;
+        let request_timeout_secs = parse_env(
+            "REQUEST_TIMEOUT_SECS",
+            timeouts.request_secs,
+            DEFAULT_REQUEST_TIMEOUT_SECS,
+            any,
+        )?;
+        let idle_timeout_secs = parse_env(
+            "IDLE_TIMEOUT_SECS",
+            timeouts.idle_secs,
+            DEFAULT_IDLE_TIMEOUT_SECS,
+            any,
+        )?;
+
+        let excluded_headers = match parse_list("EXCLUDED_HEADERS", file_excluded_headers)? {
+            None => Vec::new(),
+            Some((_, raw)) => raw
+                .split(',')
+                .map(|s| s.trim().to_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect(),
+        };
+
+        let supported_languages =
+            match parse_list("SUPPORTED_LANGUAGES", file_supported_languages)? {
+                None => vec![DEFAULT_SUPPORTED_LANGUAGES.to_string()],
+                Some((_, raw)) => raw
+                    .split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect(),
+            };
+
+        let rdns_enabled = parse_env("RDNS_ENABLED", rdns.enabled, DEFAULT_RDNS_ENABLED, any)?;
+
+        let rdns_timeout_ms = parse_env(
+            "RDNS_TIMEOUT_MS",
+            rdns.timeout_ms,
+            DEFAULT_RDNS_TIMEOUT_MS,
+            nonzero,
+        )?;
+
+        let rdns_cache_ttl_secs = parse_env(
+            "RDNS_CACHE_TTL_SECS",
+            rdns.cache_ttl_secs,
+            DEFAULT_RDNS_CACHE_TTL_SECS,
+            any,
+        )?;
+
+        let rdns_cache_capacity = parse_env(
+            "RDNS_CACHE_CAPACITY",
+            rdns.cache_capacity,
+            DEFAULT_RDNS_CACHE_CAPACITY,
+            any,
+        )?;
+
+        let proxy_protocol = parse_env(
+            "PROXY_PROTOCOL",
+            listener.proxy_protocol,
+            DEFAULT_PROXY_PROTOCOL,
+            any,
+        )?;
+
+        let tls_cert = read_env("TLS_CERT")?
+            .map(|(_, v)| PathBuf::from(v))
+            .or(tls.cert);
+        let tls_key = read_env("TLS_KEY")?
+            .map(|(_, v)| PathBuf::from(v))
+            .or(tls.key);
+        if tls_cert.is_some() != tls_key.is_some() {
+            return Err("TLS_CERT and TLS_KEY must be set together".into());
+        }
+        let tls_client_auth = parse_env("TLS_CLIENT_AUTH", tls.client_auth, false, any)?;
+
+        let split = |raw: String| -> Vec<String> {
+            raw.split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect()
+        };
+        let acme_domains = parse_list("ACME_DOMAINS", acme.domains)?
+            .map(|(_, raw)| split(raw))
+            .unwrap_or_default();
+        let acme_contact = parse_list("ACME_CONTACT", acme.contact)?
+            .map(|(_, raw)| split(raw))
+            .unwrap_or_default();
+        let acme_cache_dir = read_env("ACME_CACHE_DIR")?
+            .map(|(_, v)| PathBuf::from(v))
+            .or(acme.cache_dir)
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_ACME_CACHE_DIR));
+        let acme_staging = parse_env("ACME_STAGING", acme.staging, false, any)?;
+        if !acme_domains.is_empty() && tls_cert.is_some() {
+            return Err("ACME_DOMAINS and TLS_CERT are mutually exclusive".into());
+        }
+
+        let tls_enabled = tls_cert.is_some() || !acme_domains.is_empty();
+
+        let http_port = parse_env("HTTP_PORT", tls.http_port, DEFAULT_HTTP_PORT, any)?;
+        let https_redirect = parse_env("HTTPS_REDIRECT", tls.redirect, true, any)?;
+        let https_redirect_exempt = parse_list("HTTPS_REDIRECT_EXEMPT", tls.redirect_exempt)?
+            .map(|(_, raw)| split(raw))
+            .unwrap_or_else(|| vec![DEFAULT_HTTPS_REDIRECT_EXEMPT.to_string()]);
+        if let Some(path) = https_redirect_exempt.iter().find(|p| !p.starts_with('/')) {
+            return Err(format!(
+                "HTTPS_REDIRECT_EXEMPT path {path:?} must start with /"
+            ));
+        }
+        if tls_enabled && unix_socket.is_none() && http_port == port {
+            return Err("HTTP_PORT must differ from PORT".into());
+        }
+
+        let https_port = file_listeners
+            .iter()
+            .find(|entry| entry.tls == Some(true))
+            .map(|entry| entry.port.unwrap_or(DEFAULT_PORT));
+        let listeners = file_listeners
+            .into_iter()
+            .map(|entry| listener_config(entry, tls_enabled, https_port))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let geoip_backend = parse_env("GEOIP_BACKEND", geoip.backend, GeoIpBackend::MaxMind, any)?;
+        let geoip_city_db = read_env("GEOIP_CITY_DB")?
+            .map(|(_, v)| PathBuf::from(v))
+            .or(geoip.city_db);
+        let geoip_asn_db = read_env("GEOIP_ASN_DB")?
+            .map(|(_, v)| PathBuf::from(v))
+            .or(geoip.asn_db);
+        let geoip_account_id = read_env("GEOIP_ACCOUNT_ID")?
+            .map(|(_, v)| v)
+            .or(geoip.account_id)
+            .filter(|v| !v.trim().is_empty());
+        let geoip_license_key = read_env("GEOIP_LICENSE_KEY")?
+            .map(|(_, v)| v)
+            .or(geoip.license_key)
+            .filter(|v| !v.trim().is_empty());
+        let geoip_refresh_secs = parse_env(
+            "GEOIP_REFRESH_SECS",
+            geoip.refresh_secs,
+            DEFAULT_GEOIP_REFRESH_SECS,
+            nonzero,
+        )?;
+        let geoip_ip2location_db = read_env("GEOIP_IP2LOCATION_DB")?
+            .map(|(_, v)| PathBuf::from(v))
+            .or(geoip.ip2location_db);
+        let geoip_api_token = read_env("GEOIP_API_TOKEN")?
+            .map(|(_, v)| v)
+            .or(geoip.api_token)
+            .filter(|v| !v.trim().is_empty());
+        if geoip_backend == GeoIpBackend::Ip2Location && geoip_ip2location_db.is_none() {
+            return Err("GEOIP_BACKEND=ip2location requires GEOIP_IP2LOCATION_DB".into());
+        }
+        if geoip_license_key.is_some() {
+            if geoip_backend != GeoIpBackend::MaxMind {
+                return Err("GEOIP_LICENSE_KEY only applies to GEOIP_BACKEND=maxmind".into());
+            }
+            if geoip_account_id.is_none() {
+                return Err("GEOIP_LICENSE_KEY requires GEOIP_ACCOUNT_ID".into());
+            }
+            if geoip_city_db.is_none() && geoip_asn_db.is_none() {
+                return Err(
+                    "GEOIP_LICENSE_KEY requires GEOIP_CITY_DB or GEOIP_ASN_DB to download into"
+                        .into(),
+                );
+            }
+        }
+
+        let metrics_addr = parse_socket_addr("METRICS_ADDR", metrics.addr)?;
+        // Whether one of the HTTP listeners binds `addr`.
+        let http_addr = |addr: SocketAddr| {
+            if listeners.is_empty() {
+                let http = (tls_enabled && http_port != 0).then_some(http_port);
+                unix_socket.is_none()
+                    && [Some(port), http]
+                        .into_iter()
+                        .flatten()
+                        .any(|port| addr == SocketAddr::new(bind_addr, port))
+            } else {
+                listeners.iter().any(|l| l.addr() == Some(addr))
+            }
+        };
+        if metrics_addr.is_some_and(http_addr) {
+            return Err("METRICS_ADDR must differ from the main listener address".into());
+        }
+
+        let tcp_echo_addr = parse_socket_addr("TCP_ECHO_ADDR", tcp_echo.addr)?;
+        if tcp_echo_addr.is_some_and(|addr| http_addr(addr) || metrics_addr == Some(addr)) {
+            return Err(
+                "TCP_ECHO_ADDR must differ from the HTTP and METRICS_ADDR listeners".into(),
+            );
+        }
+        // UDP, so it can share a port with any of the TCP listeners.
+        let udp_echo_addr = parse_socket_addr("UDP_ECHO_ADDR", udp_echo.addr)?;
+
+        let dns_addr = parse_socket_addr("DNS_ADDR", dns.addr)?;
+        let dns_zone = read_env("DNS_ZONE")?
+            .map(|(_, v)| v)
+            .or(dns.zone)
+            .map(|v| v.trim().trim_end_matches('.').to_ascii_lowercase())
+            .filter(|v| !v.is_empty());
+        if let Some(zone) = dns_zone.as_deref().filter(|zone| !is_domain(zone)) {
+            return Err(format!("DNS_ZONE {zone:?} is not a valid domain name"));
+        }
+        if let Some(addr) = dns_addr {
+            if dns_zone.is_none() {
+                return Err("DNS_ADDR requires DNS_ZONE".into());
+            }
+            if http_addr(addr) || [metrics_addr, tcp_echo_addr, udp_echo_addr].contains(&Some(addr))
+            {
+                return Err("DNS_ADDR must differ from the other listeners".into());
+            }
+        }
+        let dns_rate_limit = parse_env(
+            "DNS_RATE_LIMIT",
+            dns.rate_limit,
+            DEFAULT_DNS_RATE_LIMIT,
+            nonzero,
+        )?;
+
+        let otlp_endpoint = match read_env("OTLP_ENDPOINT")? {
+            Some((_, raw)) if raw.trim().is_empty() => None,
+            Some((name, raw)) => Some((format!("{name}=\"{raw}\""), raw.trim().to_string())),
+            None => otlp
+                .endpoint
+                .map(|v| ("otlp.endpoint in config file".to_string(), v)),
+        };
+        let otlp_endpoint = match otlp_endpoint {
+            Some((name, raw)) => {
+                let url = url::Url::parse(&raw)
+                    .map_err(|e| format!("{name} is not a valid URL: {e}"))?;
+                if !matches!(url.scheme(), "http" | "https") {
+                    return Err(format!("{name} must be an http or https URL"));
+                }
+                Some(raw.trim_end_matches('/').to_string())
+            }
+            None => None,
+        };
+
+        let statsd_addr = match read_env("STATSD_ADDR")? {
+            Some((_, raw)) if raw.trim().is_empty() => None,
+            Some((name, raw)) => Some((format!("{name}=\"{raw}\""), raw.trim().to_string())),
+            None => statsd
+                .addr
+                .map(|v| ("statsd.addr in config file".to_string(), v)),
+        };
+        let statsd_addr = match statsd_addr {
+            Some((name, raw)) => {
+                let valid = raw.rsplit_once(':').is_some_and(|(host, port)| {
+                    !host.is_empty() && port.parse::<u16>().is_ok_and(|p| p != 0)
+                });
+                if !valid {
+                    return Err(format!("{name} must be host:port"));
+                }
+                Some(raw)
+            }
+            None => None,
+        };
+        let statsd_prefix = read_env("STATSD_PREFIX")?
+            .map(|(_, v)| v)
+            .or(statsd.prefix)
+            .map(|v| v.trim().trim_end_matches('.').to_string())
+            .unwrap_or_else(|| DEFAULT_STATSD_PREFIX.to_string());
+        let statsd_tags = match parse_list("STATSD_TAGS", statsd.tags)? {
+            None => Vec::new(),
+            Some((name, raw)) => {
+                let tags = split(raw);
+                if let Some(bad) = tags.iter().find(|t| t.contains(['|', '#', ' '])) {
+                    return Err(format!("{name} has an invalid tag: \"{bad}\""));
+                }
+                tags
+            }
+        };
+
+        let cors_allowed_origins = match parse_list("CORS_ALLOWED_ORIGINS", cors.allowed_origins)? {
+            None => Vec::new(),
+            Some((name, raw)) => {
+                let origins = split(raw);
+                for origin in &origins {
+                    if origin != "*" && !is_origin(origin) {
+                        return Err(format!(
+                            "{name} has an invalid origin \"{origin}\"; expected * or scheme://host[:port]"
+                        ));
+                    }
+                }
+                origins
+            }
+        };
+        let cors_allowed_methods = match parse_list("CORS_ALLOWED_METHODS", cors.allowed_methods)? {
+            None => DEFAULT_CORS_ALLOWED_METHODS.map(String::from).to_vec(),
+            Some((name, raw)) => {
+                let methods: Vec<String> = split(raw).iter().map(|m| m.to_uppercase()).collect();
+                if let Some(bad) = methods
+                    .iter()
+                    .find(|m| axum::http::Method::from_bytes(m.as_bytes()).is_err())
+                {
+                    return Err(format!("{name} has an invalid method \"{bad}\""));
+                }
+                methods
+            }
+        };
+        let cors_allowed_headers = match parse_list("CORS_ALLOWED_HEADERS", cors.allowed_headers)? {
+            None => Vec::new(),
+            Some((name, raw)) => {
+                let headers: Vec<String> = split(raw).iter().map(|h| h.to_lowercase()).collect();
+                if let Some(bad) = headers
+                    .iter()
+                    .find(|h| axum::http::HeaderName::from_bytes(h.as_bytes()).is_err())
+                {
+                    return Err(format!("{name} has an invalid header name \"{bad}\""));
+                }
+                headers
+            }
+        };
+        let cors_max_age_secs = parse_env(
+            "CORS_MAX_AGE_SECS",
+            cors.max_age_secs,
+            DEFAULT_CORS_MAX_AGE_SECS,
+            any,
+        )?;
+
+        let hsts_max_age_secs = parse_env(
+            "HSTS_MAX_AGE_SECS",
+            security_headers.hsts_max_age_secs,
+            DEFAULT_HSTS_MAX_AGE_SECS,
+            any,
+        )?;
+        let referrer_policy = parse_env(
+            "REFERRER_POLICY",
+            security_headers.referrer_policy,
+            DEFAULT_REFERRER_POLICY.to_string(),
+            |v: &String| {
+                if REFERRER_POLICIES.contains(&v.as_str()) {
+                    Ok(())
+                } else {
+                    Err(format!("expected one of {}", REFERRER_POLICIES.join(", ")))
+                }
+            },
+        )?;
+        let content_security_policy = parse_env(
+            "CONTENT_SECURITY_POLICY",
+            security_headers.content_security_policy,
+            DEFAULT_CONTENT_SECURITY_POLICY.to_string(),
+            |v: &String| {
+                axum::http::HeaderValue::from_str(v)
+                    .map(|_| ())
+                    .map_err(|_| "not a valid header value".to_string())
+            },
+        )?;
+        let security_headers = parse_env(
+            "SECURITY_HEADERS",
+            security_headers.enabled,
+            DEFAULT_SECURITY_HEADERS,
+            any,
+        )?;
+
+        let compression_min_bytes = parse_env(
+            "COMPRESSION_MIN_BYTES",
+            compression.min_bytes,
+            DEFAULT_COMPRESSION_MIN_BYTES,
+            any,
+        )?;
+        let compression = parse_env(
+            "COMPRESSION",
+            compression.enabled,
+            DEFAULT_COMPRESSION,
+            any,
+        )?;
+
+        let outbound_allow_private =
+            parse_env("OUTBOUND_ALLOW_PRIVATE", outbound.allow_private, false, any)?;
+        let file_portcheck_ports = portcheck
+            .ports
+            .map(|ports| ports.iter().map(u16::to_string).collect());
+        let portcheck_ports = match parse_list("PORTCHECK_PORTS", file_portcheck_ports)? {
+            None => Vec::new(),
+            Some((name, raw)) => split(raw)
+                .iter()
+                .map(|port| match port.parse::<u16>() {
+                    Ok(port) if port > 0 => Ok(port),
+                    _ => Err(format!("{name} has an invalid port \"{port}\"")),
+                })
+                .collect::<Result<_, _>>()?,
+        };
+        let portcheck_timeout_ms = parse_env(
+            "PORTCHECK_TIMEOUT_MS",
+            portcheck.timeout_ms,
+            DEFAULT_PORTCHECK_TIMEOUT_MS,
+            nonzero,
+        )?;
+        let portcheck_per_minute = parse_env(
+            "PORTCHECK_PER_MINUTE",
+            portcheck.per_minute,
+            DEFAULT_PORTCHECK_PER_MINUTE,
+            nonzero,
+        )?;
+
+        let ping_enabled = parse_env("PING_ENABLED", ping.enabled, DEFAULT_PING_ENABLED, any)?;
+        let ping_count = parse_env("PING_COUNT", ping.count, DEFAULT_PING_COUNT, |v| {
+            if (1..=MAX_PING_COUNT).contains(v) {
+                Ok(())
+            } else {
+                Err(format!("must be between 1 and {MAX_PING_COUNT}"))
+            }
+        })?;
+        let ping_timeout_ms = parse_env(
+            "PING_TIMEOUT_MS",
+            ping.timeout_ms,
+            DEFAULT_PING_TIMEOUT_MS,
+            nonzero,
+        )?;
+        let ping_per_minute = parse_env(
+            "PING_PER_MINUTE",
+            ping.per_minute,
+            DEFAULT_PING_PER_MINUTE,
+            nonzero,
+        )?;
+
+        let trace_enabled = parse_env("TRACE_ENABLED", trace.enabled, DEFAULT_TRACE_ENABLED, any)?;
+        let trace_max_hops = parse_env(
+            "TRACE_MAX_HOPS",
+            trace.max_hops,
+            DEFAULT_TRACE_MAX_HOPS,
+            |v| {
+                if (1..=MAX_TRACE_HOPS).contains(v) {
+                    Ok(())
+                } else {
+                    Err(format!("must be between 1 and {MAX_TRACE_HOPS}"))
+                }
+            },
+        )?;
+        let trace_timeout_ms = parse_env(
+            "TRACE_TIMEOUT_MS",
+            trace.timeout_ms,
+            DEFAULT_TRACE_TIMEOUT_MS,
+            nonzero,
+        )?;
+        let trace_per_minute = parse_env(
+            "TRACE_PER_MINUTE",
+            trace.per_minute,
+            DEFAULT_TRACE_PER_MINUTE,
+            nonzero,
+        )?;
+
+        let dual_stack_origin = read_env("DUAL_STACK_ORIGIN")?
+            .map(|(_, v)| v)
+            .or(dual_stack.origin)
+            .map(|v| v.trim().trim_end_matches('/').to_ascii_lowercase())
+            .filter(|v| !v.is_empty());
+        if let Some(origin) = &dual_stack_origin {
+            let domain = url::Url::parse(origin)
+                .ok()
+                .filter(|url| matches!(url.scheme(), "http" | "https") && is_origin(origin))
+                .and_then(|url| url.domain().map(String::from));
+            if !domain.is_some_and(|domain| is_domain(&domain)) {
+                return Err(format!(
+                    "DUAL_STACK_ORIGIN {origin:?} must be an http(s) origin with a domain name"
+                ));
+            }
+        }
+        let dual_stack_ttl_secs = parse_env(
+            "DUAL_STACK_TTL_SECS",
+            dual_stack.ttl_secs,
+            DEFAULT_DUAL_STACK_TTL_SECS,
+            nonzero,
+        )?;
+
+        Ok(Self {
+            port,
+            bind_addr,
+            unix_socket,
+            unix_socket_mode,
+            unix_socket_owner,
+            listen_backlog,
+            acceptors,
+            ipv6_only,
+            listeners,
+            sync_interval_secs,
+            log_level,
+            log_format,
+            syslog_addr,
+            syslog_facility,
+            access_log,
+            log_file: log_file_path,
+            log_file_max_size_mb,
+            log_file_rotation,
+            log_file_max_files,
+            trusted_proxies,
+            rate_limit_per_second,
+            rate_limit_burst,
+            max_concurrent_requests,
+            max_header_bytes,
+            max_body_bytes,
+            max_half_open_per_ip,
+            max_delay_secs,
+            max_generated_bytes,
+            max_speedtest_bytes,
+            first_request_timeout_secs,
+            header_read_timeout_secs,
+            request_timeout_secs,
+            idle_timeout_secs,
+            excluded_headers,
+            supported_languages,
+            rdns_enabled,
+            rdns_timeout_ms,
+            rdns_cache_ttl_secs,
+            rdns_cache_capacity,
+            proxy_protocol,
+            tls_cert,
+            tls_key,
+            tls_client_auth,
+            acme_domains,
+            acme_contact,
+            acme_cache_dir,
+            acme_staging,
+            http_port,
+            https_redirect,
+            https_redirect_exempt,
+            geoip_backend,
+            geoip_city_db,
+            geoip_asn_db,
+            geoip_account_id,
+            geoip_license_key,
+            geoip_refresh_secs,
+            geoip_ip2location_db,
+            geoip_api_token,
+            metrics_addr,
+            tcp_echo_addr,
+            udp_echo_addr,
+            dns_addr,
+            dns_zone,
+            dns_rate_limit,
+            otlp_endpoint,
+            statsd_addr,
+            statsd_prefix,
+            statsd_tags,
+            cors_allowed_origins,
+            cors_allowed_methods,
+            cors_allowed_headers,
+            cors_max_age_secs,
+            security_headers,
+            hsts_max_age_secs,
+            referrer_policy,
+            content_security_policy,
+            compression,
+            compression_min_bytes,
+            outbound_allow_private,
+            portcheck_ports,
+            portcheck_timeout_ms,
+            portcheck_per_minute,
+            ping_enabled,
+            ping_count,
+            ping_timeout_ms,
+            ping_per_minute,
+            trace_enabled,
+            trace_max_hops,
+            trace_timeout_ms,
+            trace_per_minute,
+            dual_stack_origin,
+            dual_stack_ttl_secs,
+        })
+    }
+
+    pub fn is_trusted_proxy(&self, ip: &IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|net| net.contains(ip))
+    }
+
+    /// Whether forwarding headers from the socket peer `ip` are believed.
+    /// Connections on a Unix socket carry the unspecified address (see
+    /// `listener::unix`) and always are: only local processes the socket's
+    /// permissions admit can connect.
+    pub fn is_trusted_peer(&self, ip: &IpAddr) -> bool {
+        ip.is_unspecified() || self.is_trusted_proxy(ip)
+    }
+
+    /// The sockets to serve on: the `[[listeners]]` from the config file,
+    /// or else the main listener the other settings describe, plus one on
+    /// `HTTP_PORT` when it serves HTTPS.
+    pub fn all_listeners(&self) -> Vec<ListenerConfig> {
+        if !self.listeners.is_empty() {
+            return self.listeners.clone();
+        }
+        let tls = self.tls_cert.is_some() || !self.acme_domains.is_empty();
+        let main = ListenerConfig {
+            bind: match &self.unix_socket {
+                Some(path) => BindAddr::Unix(path.clone()),
+                None => BindAddr::Ip(self.bind_addr),
+            },
+            port: self.port,
+            backlog: self.listen_backlog,
+            acceptors: if self.unix_socket.is_some() {
+                1
+            } else {
+                self.acceptors
+            },
+            ipv6_only: self.ipv6_only,
+            proxy_protocol: self.proxy_protocol,
+            tls,
+            redirect_https: None,
+            socket_mode: self.unix_socket_mode,
+            socket_owner: self.unix_socket_owner.clone(),
+        };
+        if !tls || self.http_port == 0 || self.unix_socket.is_some() {
+            return vec![main];
+        }
+        let http = ListenerConfig {
+            port: self.http_port,
+            tls: false,
+            redirect_https: self.https_redirect.then_some(self.port),
+            ..main.clone()
+        };
+        vec![main, http]
+    }
+
+    pub fn is_header_excluded(&self, name: &str) -> bool {
+        self.excluded_headers.iter().any(|h| h == name)
+    }
+
+    /// This config with the settings that can change without a restart
+    /// taken from `new`: trusted proxies, rate limits, GeoIP database paths
+    /// and the TLS certificate files. See [`crate::reload`].
+    pub fn reloaded(&self, new: &Config) -> Config {
+        Config {
+            trusted_proxies: new.trusted_proxies.clone(),
+            rate_limit_per_second: new.rate_limit_per_second,
+            rate_limit_burst: new.rate_limit_burst,
+            geoip_city_db: new.geoip_city_db.clone(),
+            geoip_asn_db: new.geoip_asn_db.clone(),
+            geoip_ip2location_db: new.geoip_ip2location_db.clone(),
+            tls_cert: new.tls_cert.clone(),
+            tls_key: new.tls_key.clone(),
+            ..self.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // These tests mutate process-wide env vars, so they can't run in parallel
+    // with each other. We serialize by defining a single #[test] that runs
+    // each scenario sequentially; this keeps behavior simple without adding
+    // a `serial_test` dep.
+    fn clear_all() {
+        unsafe {
+            for k in [
+                "PORT",
+                "BIND_ADDR",
+                "UNIX_SOCKET_MODE",
+                "UNIX_SOCKET_OWNER",
+                "LISTEN_BACKLOG",
+                "ACCEPTORS",
+                "SYNC_INTERVAL_SECS",
+                "LOG_LEVEL",
+                "LOG_FORMAT",
+                "ACCESS_LOG",
+                "SYSLOG_ADDR",
+                "SYSLOG_FACILITY",
+                "LOG_FILE",
+                "LOG_FILE_MAX_SIZE_MB",
+                "LOG_FILE_ROTATION",
+                "LOG_FILE_MAX_FILES",
+                "TRUSTED_PROXIES",
+                "RATE_LIMIT_PER_SECOND",
+                "RATE_LIMIT_BURST",
+                "MAX_CONCURRENT_REQUESTS",
+                "MAX_HEADER_BYTES",
+                "MAX_BODY_BYTES",
+                "MAX_HALF_OPEN_PER_IP",
+                "MAX_DELAY_SECS",
+                "MAX_GENERATED_BYTES",
+                "MAX_SPEEDTEST_BYTES",
+                "FIRST_REQUEST_TIMEOUT_SECS",
+                "HEADER_READ_TIMEOUT_SECS",
+                "REQUEST_TIMEOUT_SECS",
+                "IDLE_TIMEOUT_SECS",
+                "EXCLUDED_HEADERS",
+                "SUPPORTED_LANGUAGES",
+                "RDNS_ENABLED",
+                "RDNS_TIMEOUT_MS",
+                "RDNS_CACHE_TTL_SECS",
+                "RDNS_CACHE_CAPACITY",
+                "PROXY_PROTOCOL",
+                "IPV6_ONLY",
+                "TLS_CERT",
+                "TLS_KEY",
+                "TLS_CLIENT_AUTH",
+                "ACME_DOMAINS",
+                "ACME_CONTACT",
+                "ACME_CACHE_DIR",
+                "ACME_STAGING",
+                "HTTP_PORT",
+                "HTTPS_REDIRECT",
+                "HTTPS_REDIRECT_EXEMPT",
+                "GEOIP_BACKEND",
+                "GEOIP_CITY_DB",
+                "GEOIP_ASN_DB",
+                "GEOIP_ACCOUNT_ID",
+                "GEOIP_LICENSE_KEY",
+                "GEOIP_REFRESH_SECS",
+                "GEOIP_IP2LOCATION_DB",
+                "GEOIP_API_TOKEN",
+                "METRICS_ADDR",
+                "TCP_ECHO_ADDR",
+                "UDP_ECHO_ADDR",
+                "DNS_ADDR",
+                "DNS_RATE_LIMIT",
+                "DNS_ZONE",
+                "OTLP_ENDPOINT",
+                "STATSD_ADDR",
+                "STATSD_PREFIX",
+                "STATSD_TAGS",
+                "CORS_ALLOWED_ORIGINS",
+                "CORS_ALLOWED_METHODS",
+                "CORS_ALLOWED_HEADERS",
+                "CORS_MAX_AGE_SECS",
+                "SECURITY_HEADERS",
+                "COMPRESSION",
+                "COMPRESSION_MIN_BYTES",
+                "OUTBOUND_ALLOW_PRIVATE",
+                "PORTCHECK_PORTS",
+                "PORTCHECK_TIMEOUT_MS",
+                "PORTCHECK_PER_MINUTE",
+                "PING_ENABLED",
+                "PING_COUNT",
+                "PING_TIMEOUT_MS",
+                "PING_PER_MINUTE",
+                "TRACE_ENABLED",
+                "TRACE_MAX_HOPS",
+                "TRACE_TIMEOUT_MS",
+                "TRACE_PER_MINUTE",
+                "DUAL_STACK_ORIGIN",
+                "DUAL_STACK_TTL_SECS",
+                "HSTS_MAX_AGE_SECS",
+                "REFERRER_POLICY",
+                "CONTENT_SECURITY_POLICY",
+            ] {
+                env::remove_var(k);
+                env::remove_var(format!("{ENV_PREFIX}{k}"));
+            }
+        }
+    }
+
+    fn from_env() -> Result<Config, String> {
+        Config::load(FileConfig::default())
+    }
+
+    #[test]
+    fn env_driven_config_scenarios() {
+        // Unset vars -> defaults.
+        clear_all();
+        let c = from_env().expect("defaults should be valid");
+        assert_eq!(c.port, DEFAULT_PORT);
+        assert_eq!(c.sync_interval_secs, DEFAULT_SYNC_INTERVAL_SECS);
+        assert_eq!(c.log_level, DEFAULT_LOG_LEVEL);
+        assert_eq!(c.trusted_proxies.len(), 4);
+        assert_eq!(c.rate_limit_per_second, DEFAULT_RATE_LIMIT_PER_SECOND);
+        assert_eq!(c.rate_limit_burst, DEFAULT_RATE_LIMIT_BURST);
+        assert!(c.excluded_headers.is_empty());
+        assert_eq!(c.supported_languages, [DEFAULT_SUPPORTED_LANGUAGES]);
+        assert!(c.rdns_enabled);
+        assert_eq!(c.rdns_timeout_ms, DEFAULT_RDNS_TIMEOUT_MS);
+        assert!(!c.proxy_protocol);
+
+        // SUPPORTED_LANGUAGES is a comma-separated list.
+        clear_all();
+        unsafe { env::set_var("SUPPORTED_LANGUAGES", "en-US, de ,pt-BR") };
+        assert_eq!(from_env().unwrap().supported_languages, ["en-US", "de", "pt-BR"]);
+
+        // RDNS_ENABLED=false disables reverse lookups.
+        clear_all();
+        unsafe { env::set_var("RDNS_ENABLED", "false") };
+        assert!(!from_env().unwrap().rdns_enabled);
+
+        // RDNS_ENABLED=maybe -> error.
+        clear_all();
+        unsafe { env::set_var("RDNS_ENABLED", "maybe") };
+        assert!(from_env().is_err());
+
+        // RDNS_TIMEOUT_MS=0 -> error.
+        clear_all();
+        unsafe { env::set_var("RDNS_TIMEOUT_MS", "0") };
+        assert!(from_env().is_err());
+
+        // Explicit PORT=0 -> error.
+        clear_all();
+        unsafe { env::set_var("PORT", "0") };
+        assert!(from_env().is_err());
+
+        // Explicit PORT=abc -> error (parse failure).
+        clear_all();
+        unsafe { env::set_var("PORT", "abc") };
+        assert!(from_env().is_err());
+
+        // Explicit valid PORT.
+        clear_all();
+        unsafe { env::set_var("PORT", "9000") };
+        assert_eq!(from_env().unwrap().port, 9000);
+
+        // BIND_ADDR must be an IP address or unix:/path.
+        clear_all();
+        unsafe { env::set_var("BIND_ADDR", "localhost") };
+        assert!(from_env().is_err());
+        unsafe { env::set_var("BIND_ADDR", "unix:") };
+        assert!(from_env().is_err());
+
+        // A Unix socket, with its mode and owner.
+        clear_all();
+        unsafe {
+            env::set_var("BIND_ADDR", "unix:/run/echo.sock");
+            env::set_var("UNIX_SOCKET_MODE", "600");
+            env::set_var("UNIX_SOCKET_OWNER", " www-data:www-data ");
+        }
+        let c = from_env().unwrap();
+        assert_eq!(c.unix_socket, Some(PathBuf::from("/run/echo.sock")));
+        assert_eq!(c.unix_socket_mode, 0o600);
+        assert_eq!(c.unix_socket_owner.as_deref(), Some("www-data:www-data"));
+        unsafe { env::set_var("UNIX_SOCKET_MODE", "999") };
+        assert!(from_env().is_err());
+        unsafe { env::set_var("UNIX_SOCKET_MODE", "1777") };
+        assert!(from_env().is_err());
+
+        // ACCEPTORS is non-zero, and only for TCP.
+        clear_all();
+        assert_eq!(from_env().unwrap().acceptors, 1);
+        unsafe { env::set_var("ACCEPTORS", "4") };
+        assert_eq!(from_env().unwrap().all_listeners()[0].acceptors, 4);
+        unsafe { env::set_var("BIND_ADDR", "unix:/run/echo.sock") };
+        assert!(from_env().is_err());
+        unsafe { env::set_var("ACCEPTORS", "0") };
+        assert!(from_env().is_err());
+
+        // SYNC_INTERVAL_SECS=0 -> error.
+        clear_all();
+        unsafe { env::set_var("SYNC_INTERVAL_SECS", "0") };
+        assert!(from_env().is_err());
+
+        // RATE_LIMIT_PER_SECOND=0 -> error.
+        clear_all();
+        unsafe { env::set_var("RATE_LIMIT_PER_SECOND", "0") };
+        assert!(from_env().is_err());
+
+        // RATE_LIMIT_BURST=0 -> error.
+        clear_all();
+        unsafe { env::set_var("RATE_LIMIT_BURST", "0") };
+        assert!(from_env().is_err());
+
+        // TRUSTED_PROXIES set but empty -> error.
+        clear_all();
+        unsafe { env::set_var("TRUSTED_PROXIES", "   ") };
+        assert!(from_env().is_err());
+
+        // TRUSTED_PROXIES with garbage entry -> error.
+        clear_all();
+        unsafe { env::set_var("TRUSTED_PROXIES", "not-a-cidr") };
+        assert!(from_env().is_err());
+
+        // TRUSTED_PROXIES with one valid entry -> ok.
+        clear_all();
+        unsafe { env::set_var("TRUSTED_PROXIES", "10.0.0.0/8") };
+        let c = from_env().unwrap();
+        assert_eq!(c.trusted_proxies.len(), 1);
+
+        // TLS_CERT without TLS_KEY -> error.
+        clear_all();
+        unsafe { env::set_var("TLS_CERT", "/tmp/cert.pem") };
+        assert!(from_env().is_err());
+        unsafe { env::set_var("TLS_KEY", "/tmp/key.pem") };
+        let c = from_env().unwrap();
+        assert_eq!(c.tls_key, Some(PathBuf::from("/tmp/key.pem")));
+        assert!(!c.tls_client_auth);
+        unsafe { env::set_var("TLS_CLIENT_AUTH", "true") };
+        assert!(from_env().unwrap().tls_client_auth);
+
+        // ACME_DOMAINS conflicts with the TLS_CERT/TLS_KEY still set above...
+        unsafe { env::set_var("ACME_DOMAINS", "echo.example.com, ip.example.com") };
+        assert!(from_env().is_err());
+        // ...and on its own is a comma-separated list.
+        clear_all();
+        unsafe { env::set_var("ACME_DOMAINS", "echo.example.com, ip.example.com") };
+        let c = from_env().unwrap();
+        assert_eq!(c.acme_domains, ["echo.example.com", "ip.example.com"]);
+        assert_eq!(c.acme_cache_dir, PathBuf::from(DEFAULT_ACME_CACHE_DIR));
+
+        // GEOIP_LICENSE_KEY needs an account and somewhere to download to.
+        clear_all();
+        unsafe { env::set_var("GEOIP_LICENSE_KEY", "secret") };
+        assert!(from_env().is_err());
+        unsafe { env::set_var("GEOIP_ACCOUNT_ID", "12345") };
+        assert!(from_env().is_err());
+        unsafe { env::set_var("GEOIP_ASN_DB", "/tmp/GeoLite2-ASN.mmdb") };
+        let c = from_env().unwrap();
+        assert_eq!(c.geoip_license_key.as_deref(), Some("secret"));
+        assert_eq!(c.geoip_refresh_secs, DEFAULT_GEOIP_REFRESH_SECS);
+        // ...and only makes sense for MaxMind.
+        unsafe { env::set_var("GEOIP_BACKEND", "ipinfo") };
+        assert!(from_env().is_err());
+
+        // GEOIP_BACKEND is case-insensitive; ip2location needs its file.
+        clear_all();
+        unsafe { env::set_var("GEOIP_BACKEND", "IP2Location") };
+        assert!(from_env().is_err());
+        unsafe { env::set_var("GEOIP_IP2LOCATION_DB", "/tmp/IP2LOCATION-LITE-DB11.CSV") };
+        assert_eq!(from_env().unwrap().geoip_backend, GeoIpBackend::Ip2Location);
+        unsafe { env::set_var("GEOIP_BACKEND", "nope") };
+        assert!(from_env().is_err());
+
+        // LOG_FORMAT is text or json, case-insensitive.
+        clear_all();
+        assert_eq!(from_env().unwrap().log_format, LogFormat::Text);
+        unsafe { env::set_var("LOG_FORMAT", "JSON") };
+        assert_eq!(from_env().unwrap().log_format, LogFormat::Json);
+        unsafe { env::set_var("LOG_FORMAT", "logfmt") };
+        assert!(from_env().is_err());
+
+        // ACCESS_LOG is a path (or "-"); empty disables it.
+        clear_all();
+        unsafe { env::set_var("ACCESS_LOG", "/var/log/ipecho/access.log") };
+        assert_eq!(
+            from_env().unwrap().access_log,
+            Some(PathBuf::from("/var/log/ipecho/access.log"))
+        );
+        unsafe { env::set_var("ACCESS_LOG", "") };
+        assert!(from_env().unwrap().access_log.is_none());
+
+        // LOG_FILE rotation settings.
+        clear_all();
+        let c = from_env().unwrap();
+        assert!(c.log_file.is_none());
+        assert_eq!(c.log_file_rotation, LogRotation::Daily);
+        unsafe {
+            env::set_var("LOG_FILE", "/var/log/ipecho/ipecho.log");
+            env::set_var("LOG_FILE_ROTATION", "hourly");
+            env::set_var("LOG_FILE_MAX_SIZE_MB", "0");
+            env::set_var("LOG_FILE_MAX_FILES", "24");
+        }
+        let c = from_env().unwrap();
+        assert_eq!(
+            c.log_file,
+            Some(PathBuf::from("/var/log/ipecho/ipecho.log"))
+        );
+        assert_eq!(c.log_file_rotation, LogRotation::Hourly);
+        assert_eq!(c.log_file_max_size_mb, 0);
+        assert_eq!(c.log_file_max_files, 24);
+        unsafe { env::set_var("LOG_FILE_ROTATION", "weekly") };
+        assert!(from_env().is_err());
+
+        // SYSLOG_ADDR picks the transport by scheme, with default ports.
+        clear_all();
+        unsafe {
+            env::set_var("SYSLOG_ADDR", "udp://logs.example.com");
+            env::set_var("SYSLOG_FACILITY", "local3");
+        }
+        let c = from_env().unwrap();
+        assert_eq!(
+            c.syslog_addr,
+            Some(SyslogTarget::Udp("logs.example.com:514".into()))
+        );
+        assert_eq!(c.syslog_facility, SyslogFacility(19));
+        unsafe { env::set_var("SYSLOG_ADDR", "tcp://[::1]:6514") };
+        assert_eq!(
+            from_env().unwrap().syslog_addr,
+            Some(SyslogTarget::Tcp("[::1]:6514".into()))
+        );
+        unsafe { env::set_var("SYSLOG_ADDR", "/dev/log") };
+        assert_eq!(
+            from_env().unwrap().syslog_addr,
+            Some(SyslogTarget::Unix("/dev/log".into()))
+        );
+        unsafe { env::set_var("SYSLOG_ADDR", "logs.example.com:514") };
+        assert!(from_env().is_err());
+        unsafe {
+            env::set_var("SYSLOG_ADDR", "/dev/log");
+            env::set_var("SYSLOG_FACILITY", "local8");
+        }
+        assert!(from_env().is_err());
+
+        // METRICS_ADDR is a socket address, distinct from the main listener.
+        clear_all();
+        unsafe { env::set_var("METRICS_ADDR", "127.0.0.1:9090") };
+        assert_eq!(
+            from_env().unwrap().metrics_addr,
+            Some("127.0.0.1:9090".parse().unwrap())
+        );
+        unsafe { env::set_var("METRICS_ADDR", "9090") };
+        assert!(from_env().is_err());
+        unsafe { env::set_var("METRICS_ADDR", "0.0.0.0:8083") };
+        assert!(from_env().is_err());
+
+        // TCP_ECHO_ADDR is a socket address, distinct from the other listeners.
+        clear_all();
+        unsafe { env::set_var("TCP_ECHO_ADDR", "0.0.0.0:2323") };
+        assert_eq!(
+            from_env().unwrap().tcp_echo_addr,
+            Some("0.0.0.0:2323".parse().unwrap())
+        );
+        unsafe { env::set_var("TCP_ECHO_ADDR", "2323") };
+        assert!(from_env().is_err());
+        unsafe { env::set_var("TCP_ECHO_ADDR", "0.0.0.0:8083") };
+        assert!(from_env().is_err());
+        unsafe {
+            env::set_var("TCP_ECHO_ADDR", "127.0.0.1:9090");
+            env::set_var("METRICS_ADDR", "127.0.0.1:9090");
+        }
+        assert!(from_env().is_err());
+
+        // UDP_ECHO_ADDR may share a port with the TCP listeners.
+        clear_all();
+        unsafe { env::set_var("UDP_ECHO_ADDR", "0.0.0.0:8083") };
+        assert_eq!(
+            from_env().unwrap().udp_echo_addr,
+            Some("0.0.0.0:8083".parse().unwrap())
+        );
+        unsafe { env::set_var("UDP_ECHO_ADDR", "") };
+        assert!(from_env().unwrap().udp_echo_addr.is_none());
+
+        // DNS_ADDR needs a zone to answer for, which is normalized.
+        clear_all();
+        unsafe { env::set_var("DNS_ADDR", "0.0.0.0:53") };
+        assert!(from_env().is_err());
+        unsafe { env::set_var("DNS_ZONE", "WhoAmI.Example.com.") };
+        let c = from_env().unwrap();
+        assert_eq!(c.dns_addr, Some("0.0.0.0:53".parse().unwrap()));
+        assert_eq!(c.dns_zone.as_deref(), Some("whoami.example.com"));
+        assert_eq!(c.dns_rate_limit, DEFAULT_DNS_RATE_LIMIT);
+        unsafe { env::set_var("DNS_RATE_LIMIT", "0") };
+        assert!(from_env().is_err());
+        unsafe { env::set_var("DNS_RATE_LIMIT", "500") };
+        assert_eq!(from_env().unwrap().dns_rate_limit, 500);
+        unsafe { env::set_var("DNS_ZONE", "who am i.example.com") };
+        assert!(from_env().is_err());
+        unsafe {
+            env::set_var("DNS_ZONE", "whoami.example.com");
+            env::set_var("UDP_ECHO_ADDR", "0.0.0.0:53");
+        }
+        assert!(from_env().is_err());
+
+        // OTLP_ENDPOINT must be an http(s) URL; a trailing slash is dropped.
+        clear_all();
+        unsafe { env::set_var("OTLP_ENDPOINT", "http://otel-collector:4318/") };
+        assert_eq!(
+            from_env().unwrap().otlp_endpoint.as_deref(),
+            Some("http://otel-collector:4318")
+        );
+        unsafe { env::set_var("OTLP_ENDPOINT", "otel-collector:4318") };
+        assert!(from_env().is_err());
+        unsafe { env::set_var("OTLP_ENDPOINT", "") };
+        assert!(from_env().unwrap().otlp_endpoint.is_none());
+
+        // STATSD_ADDR is host:port; tags are comma-separated.
+        clear_all();
+        unsafe {
+            env::set_var("STATSD_ADDR", "datadog-agent:8125");
+            env::set_var("STATSD_PREFIX", "edge.");
+            env::set_var("STATSD_TAGS", "env:prod, region:eu");
+        }
+        let c = from_env().unwrap();
+        assert_eq!(c.statsd_addr.as_deref(), Some("datadog-agent:8125"));
+        assert_eq!(c.statsd_prefix, "edge");
+        assert_eq!(c.statsd_tags, vec!["env:prod", "region:eu"]);
+        unsafe { env::set_var("STATSD_TAGS", "env|prod") };
+        assert!(from_env().is_err());
+        unsafe { env::remove_var("STATSD_TAGS") };
+        unsafe { env::set_var("STATSD_ADDR", "datadog-agent") };
+        assert!(from_env().is_err());
+        unsafe { env::set_var("STATSD_ADDR", "[::1]:8125") };
+        assert!(from_env().is_ok());
+
+        // CORS is off by default; origins must be exact, methods and
+        // headers are normalized.
+        clear_all();
+        let c = from_env().unwrap();
+        assert!(c.cors_allowed_origins.is_empty());
+        assert_eq!(c.cors_allowed_methods, ["GET", "HEAD"]);
+        unsafe {
+            env::set_var(
+                "CORS_ALLOWED_ORIGINS",
+                "https://dash.example.com, http://localhost:3000",
+            );
+            env::set_var("CORS_ALLOWED_METHODS", "get,post");
+            env::set_var("CORS_ALLOWED_HEADERS", "Authorization, X-Trace");
+            env::set_var("CORS_MAX_AGE_SECS", "60");
+        }
+        let c = from_env().unwrap();
+        assert_eq!(
+            c.cors_allowed_origins,
+            ["https://dash.example.com", "http://localhost:3000"]
+        );
+        assert_eq!(c.cors_allowed_methods, ["GET", "POST"]);
+        assert_eq!(c.cors_allowed_headers, ["authorization", "x-trace"]);
+        assert_eq!(c.cors_max_age_secs, 60);
+        unsafe { env::set_var("CORS_ALLOWED_ORIGINS", "https://dash.example.com/") };
+        assert!(from_env().is_err());
+        unsafe { env::set_var("CORS_ALLOWED_ORIGINS", "dash.example.com") };
+        assert!(from_env().is_err());
+        unsafe { env::set_var("CORS_ALLOWED_ORIGINS", "*") };
+        assert!(from_env().is_ok());
+        unsafe { env::set_var("CORS_ALLOWED_HEADERS", "bad header") };
+        assert!(from_env().is_err());
+
+        // Security headers are opt-in; the referrer policy must be one
+        // browsers understand.
+        clear_all();
+        let c = from_env().unwrap();
+        assert!(!c.security_headers);
+        assert_eq!(c.hsts_max_age_secs, 31_536_000);
+        assert_eq!(c.referrer_policy, "no-referrer");
+        unsafe {
+            env::set_var("SECURITY_HEADERS", "true");
+            env::set_var("HSTS_MAX_AGE_SECS", "0");
+            env::set_var("REFERRER_POLICY", "strict-origin");
+            env::set_var("CONTENT_SECURITY_POLICY", "");
+        }
+        let c = from_env().unwrap();
+        assert!(c.security_headers);
+        assert_eq!(c.hsts_max_age_secs, 0);
+        assert_eq!(c.referrer_policy, "strict-origin");
+        assert_eq!(c.content_security_policy, "");
+        unsafe { env::set_var("REFERRER_POLICY", "sometimes") };
+        assert!(from_env().is_err());
+        unsafe { env::set_var("REFERRER_POLICY", "same-origin") };
+        unsafe { env::set_var("CONTENT_SECURITY_POLICY", "default-src\n'none'") };
+        assert!(from_env().is_err());
+
+        // Compression is opt-in.
+        clear_all();
+        let c = from_env().unwrap();
+        assert!(!c.compression);
+        assert_eq!(c.compression_min_bytes, DEFAULT_COMPRESSION_MIN_BYTES);
+        unsafe { env::set_var("COMPRESSION", "true") };
+        let file = FileConfig::parse("[compression]\nenabled = false\nmin_bytes = 256").unwrap();
+        let c = Config::load(file).unwrap();
+        assert!(c.compression);
+        assert_eq!(c.compression_min_bytes, 256);
+
+        // The port check is off until ports are allowed.
+        clear_all();
+        let c = from_env().unwrap();
+        assert!(c.portcheck_ports.is_empty());
+        assert!(!c.outbound_allow_private);
+        assert_eq!(c.portcheck_timeout_ms, DEFAULT_PORTCHECK_TIMEOUT_MS);
+        let file = FileConfig::parse("[portcheck]\nports = [22, 443]\nper_minute = 2").unwrap();
+        let c = Config::load(file).unwrap();
+        assert_eq!(c.portcheck_ports, [22, 443]);
+        assert_eq!(c.portcheck_per_minute, 2);
+        unsafe { env::set_var("PORTCHECK_PORTS", "80, 8080") };
+        assert_eq!(from_env().unwrap().portcheck_ports, [80, 8080]);
+        unsafe { env::set_var("PORTCHECK_PORTS", "80,0") };
+        assert!(from_env().is_err());
+        unsafe { env::set_var("PORTCHECK_PORTS", "") };
+        unsafe { env::set_var("PORTCHECK_PER_MINUTE", "0") };
+        assert!(from_env().is_err());
+        unsafe { env::set_var("PORTCHECK_PER_MINUTE", "6") };
+        let file = FileConfig::parse("[outbound]\nallow_private = true").unwrap();
+        assert!(Config::load(file).unwrap().outbound_allow_private);
+
+        // So is /ping-me, and its count is bounded.
+        clear_all();
+        let c = from_env().unwrap();
+        assert!(!c.ping_enabled);
+        assert_eq!(c.ping_count, DEFAULT_PING_COUNT);
+        let file = FileConfig::parse("[ping]\nenabled = true\ncount = 10").unwrap();
+        let c = Config::load(file).unwrap();
+        assert!(c.ping_enabled);
+        assert_eq!(c.ping_count, 10);
+        unsafe { env::set_var("PING_COUNT", "11") };
+        assert!(from_env().is_err());
+        unsafe { env::set_var("PING_COUNT", "0") };
+        assert!(from_env().is_err());
+
+        // As is /trace-me, with its hop count bounded.
+        clear_all();
+        let c = from_env().unwrap();
+        assert!(!c.trace_enabled);
+        assert_eq!(c.trace_max_hops, DEFAULT_TRACE_MAX_HOPS);
+        assert_eq!(c.trace_per_minute, DEFAULT_TRACE_PER_MINUTE);
+        let file = FileConfig::parse("[trace]\nenabled = true\nmax_hops = 16").unwrap();
+        let c = Config::load(file).unwrap();
+        assert!(c.trace_enabled);
+        assert_eq!(c.trace_max_hops, 16);
+        unsafe { env::set_var("TRACE_MAX_HOPS", "65") };
+        assert!(from_env().is_err());
+
+        // /ds needs an origin with a domain name to build its hosts from.
+        clear_all();
+        let c = from_env().unwrap();
+        assert_eq!(c.dual_stack_origin, None);
+        assert_eq!(c.dual_stack_ttl_secs, DEFAULT_DUAL_STACK_TTL_SECS);
+        let file =
+            FileConfig::parse("[dual_stack]\norigin = \"https://Example.com/\"").unwrap();
+        let c = Config::load(file).unwrap();
+        assert_eq!(c.dual_stack_origin.as_deref(), Some("https://example.com"));
+        unsafe { env::set_var("DUAL_STACK_ORIGIN", "http://example.com:8083") };
+        assert!(from_env().is_ok());
+        for invalid in [
+            "example.com",
+            "https://203.0.113.1",
+            "https://example.com/ds",
+            "ftp://example.com",
+        ] {
+            unsafe { env::set_var("DUAL_STACK_ORIGIN", invalid) };
+            assert!(from_env().is_err(), "{invalid}");
+        }
+
+        // With HTTPS, a plain HTTP listener on HTTP_PORT redirects to it.
+        clear_all();
+        let c = from_env().unwrap();
+        assert_eq!(c.all_listeners().len(), 1);
+        unsafe {
+            env::set_var("PORT", "443");
+            env::set_var("TLS_CERT", "/etc/ipecho/cert.pem");
+            env::set_var("TLS_KEY", "/etc/ipecho/key.pem");
+        }
+        let listeners = from_env().unwrap().all_listeners();
+        assert_eq!(listeners.len(), 2);
+        assert_eq!(listeners[1].port, 80);
+        assert!(!listeners[1].tls);
+        assert_eq!(listeners[1].redirect_https, Some(443));
+        unsafe {
+            env::set_var("HTTPS_REDIRECT", "false");
+            env::set_var("HTTPS_REDIRECT_EXEMPT", "/ip, /all.json");
+        }
+        let c = from_env().unwrap();
+        assert_eq!(c.all_listeners()[1].redirect_https, None);
+        assert_eq!(c.https_redirect_exempt, ["/ip", "/all.json"]);
+        unsafe { env::set_var("HTTPS_REDIRECT_EXEMPT", "ip") };
+        assert!(from_env().is_err());
+        unsafe { env::set_var("HTTPS_REDIRECT_EXEMPT", "/ip") };
+        unsafe { env::set_var("HTTP_PORT", "0") };
+        assert_eq!(from_env().unwrap().all_listeners().len(), 1);
+        unsafe { env::set_var("HTTP_PORT", "443") };
+        assert!(from_env().is_err());
+
+        // MAX_CONCURRENT_REQUESTS=0 turns load shedding off.
+        clear_all();
+        assert_eq!(
+            from_env().unwrap().max_concurrent_requests,
+            DEFAULT_MAX_CONCURRENT_REQUESTS
+        );
+        unsafe { env::set_var("MAX_CONCURRENT_REQUESTS", "0") };
+        assert_eq!(from_env().unwrap().max_concurrent_requests, 0);
+        let file = FileConfig::parse("[limits]\nmax_concurrent_requests = 64").unwrap();
+        unsafe { env::remove_var("MAX_CONCURRENT_REQUESTS") };
+        assert_eq!(Config::load(file).unwrap().max_concurrent_requests, 64);
+
+        // Size limits must be positive.
+        clear_all();
+        let c = from_env().unwrap();
+        assert_eq!(c.max_header_bytes, DEFAULT_MAX_HEADER_BYTES);
+        assert_eq!(c.max_body_bytes, DEFAULT_MAX_BODY_BYTES);
+        unsafe { env::set_var("MAX_BODY_BYTES", "65536") };
+        let file = FileConfig::parse("[limits]\nmax_header_bytes = 8192").unwrap();
+        let c = Config::load(file).unwrap();
+        assert_eq!(c.max_header_bytes, 8192);
+        assert_eq!(c.max_body_bytes, 65536);
+        unsafe { env::set_var("MAX_HEADER_BYTES", "0") };
+        assert!(from_env().is_err());
+
+        // /delay cap; 0 answers at once.
+        clear_all();
+        assert_eq!(from_env().unwrap().max_delay_secs, DEFAULT_MAX_DELAY_SECS);
+        let file = FileConfig::parse("[limits]\nmax_delay_secs = 0").unwrap();
+        assert_eq!(Config::load(file).unwrap().max_delay_secs, 0);
+        assert_eq!(
+            from_env().unwrap().max_generated_bytes,
+            DEFAULT_MAX_GENERATED_BYTES
+        );
+        unsafe { env::set_var("MAX_GENERATED_BYTES", "4096") };
+        assert_eq!(from_env().unwrap().max_generated_bytes, 4096);
+        assert_eq!(
+            from_env().unwrap().max_speedtest_bytes,
+            DEFAULT_MAX_SPEEDTEST_BYTES
+        );
+        let file = FileConfig::parse("[limits]\nmax_speedtest_bytes = 0").unwrap();
+        assert_eq!(Config::load(file).unwrap().max_speedtest_bytes, 0);
+
+        // Slowloris limits; 0 disables either.
+        clear_all();
+        let c = from_env().unwrap();
+        assert_eq!(c.max_half_open_per_ip, DEFAULT_MAX_HALF_OPEN_PER_IP);
+        assert_eq!(
+            c.first_request_timeout_secs,
+            DEFAULT_FIRST_REQUEST_TIMEOUT_SECS
+        );
+        unsafe { env::set_var("MAX_HALF_OPEN_PER_IP", "0") };
+        let file = FileConfig::parse("[timeouts]\nfirst_request_secs = 3").unwrap();
+        let c = Config::load(file).unwrap();
+        assert_eq!(c.max_half_open_per_ip, 0);
+        assert_eq!(c.first_request_timeout_secs, 3);
+
+        // Timeouts come from env or the [timeouts] section; 0 disables one.
+        clear_all();
+        let c = from_env().unwrap();
+        assert_eq!(c.header_read_timeout_secs, DEFAULT_HEADER_READ_TIMEOUT_SECS);
+        assert_eq!(c.request_timeout_secs, DEFAULT_REQUEST_TIMEOUT_SECS);
+        assert_eq!(c.idle_timeout_secs, DEFAULT_IDLE_TIMEOUT_SECS);
+        unsafe { env::set_var("IDLE_TIMEOUT_SECS", "0") };
+        let file = FileConfig::parse(
+            "[timeouts]\nheader_read_secs = 5\nrequest_secs = 15\nidle_secs = 120",
+        )
+        .unwrap();
+        let c = Config::load(file).unwrap();
+        assert_eq!(c.header_read_timeout_secs, 5);
+        assert_eq!(c.request_timeout_secs, 15);
+        assert_eq!(c.idle_timeout_secs, 0);
+
+        // ECHO_-prefixed name wins over the legacy bare name.
+        clear_all();
+        unsafe {
+            env::set_var("PORT", "9000");
+            env::set_var("ECHO_PORT", "9001");
+        }
+        assert_eq!(from_env().unwrap().port, 9001);
+
+        // Config file values apply when env is unset...
+        clear_all();
+        let file = FileConfig::parse(
+            "log_level = \"debug\"\nlog_format = \"json\"\ntrusted_proxies = [\"10.0.0.0/8\"]\n[listener]\nport = 9100\n[rdns]\nenabled = false",
+        )
+        .unwrap();
+        let c = Config::load(file).unwrap();
+        assert_eq!(c.port, 9100);
+        assert_eq!(c.log_level, "debug");
+        assert_eq!(c.log_format, LogFormat::Json);
+        assert_eq!(c.trusted_proxies.len(), 1);
+        assert!(!c.rdns_enabled);
+
+        // ...and env overrides the file.
+        clear_all();
+        unsafe { env::set_var("ECHO_PORT", "9200") };
+        let file = FileConfig::parse("[listener]\nport = 9100").unwrap();
+        assert_eq!(Config::load(file).unwrap().port, 9200);
+
+        // File values are validated like env values.
+        clear_all();
+        let file = FileConfig::parse("[rate_limit]\nburst = 0").unwrap();
+        assert!(Config::load(file).is_err());
+        let file = FileConfig::parse("trusted_proxies = []").unwrap();
+        assert!(Config::load(file).is_err());
+
+        // [[listeners]] replace the single listener; entries default like it.
+        clear_all();
+        let c = Config::load(FileConfig::default()).unwrap();
+        assert_eq!(c.all_listeners().len(), 1);
+        assert_eq!(c.all_listeners()[0].port, DEFAULT_PORT);
+        let file = FileConfig::parse(
+            "[[listeners]]\nport = 80\n[[listeners]]\nbind = \"unix:/run/echo.sock\"\nproxy_protocol = true",
+        )
+        .unwrap();
+        let c = Config::load(file).unwrap();
+        let listeners = c.all_listeners();
+        assert_eq!(listeners.len(), 2);
+        assert_eq!(listeners[0].addr(), Some("0.0.0.0:80".parse().unwrap()));
+        assert!(!listeners[0].proxy_protocol);
+        assert_eq!(listeners[1].addr(), None);
+        assert_eq!(listeners[1].socket_mode, DEFAULT_UNIX_SOCKET_MODE.0);
+        assert!(listeners[1].proxy_protocol);
+        let file = FileConfig::parse("[[listeners]]\nport = 443\ntls = true").unwrap();
+        assert!(Config::load(file).is_err());
+        let file = FileConfig::parse("[[listeners]]\nport = 80\nredirect_https = true").unwrap();
+        assert!(Config::load(file).is_err());
+        let file = FileConfig::parse(
+            "[tls]\ncert = \"c.pem\"\nkey = \"k.pem\"\n[[listeners]]\nport = 80\nredirect_https = true\n[[listeners]]\nport = 8443\ntls = true",
+        )
+        .unwrap();
+        assert_eq!(
+            Config::load(file).unwrap().listeners[0].redirect_https,
+            Some(8443)
+        );
+        let file =
+            FileConfig::parse("[[listeners]]\nbind = \"unix:/run/echo.sock\"\nacceptors = 2")
+                .unwrap();
+        assert!(Config::load(file).is_err());
+        let file = FileConfig::parse("[listener]\nport = 80\n[[listeners]]\nport = 81").unwrap();
+        assert!(Config::load(file).is_err());
+        let file = FileConfig::parse("[metrics]\naddr = \"0.0.0.0:81\"\n[[listeners]]\nport = 81")
+            .unwrap();
+        assert!(Config::load(file).is_err());
+
+        clear_all();
+    }
+
+    #[test]
+    fn test_trusted_proxy_check() {
+        let config = Config {
+            trusted_proxies: vec!["10.0.0.0/8".parse().unwrap()],
+            ..Config::default()
+        };
+
+        let trusted: IpAddr = "10.0.0.1".parse().unwrap();
+        let untrusted: IpAddr = "203.0.113.1".parse().unwrap();
+
+        assert!(config.is_trusted_proxy(&trusted));
+        assert!(!config.is_trusted_proxy(&untrusted));
+    }
+
+    #[test]
+    fn test_reloaded_takes_only_live_settings() {
+        let running = Config::default();
+        let new = Config {
+            port: 9999,
+            trusted_proxies: vec!["192.0.2.0/24".parse().unwrap()],
+            rate_limit_burst: 5,
+            geoip_city_db: Some("/var/lib/ipecho/GeoLite2-City.mmdb".into()),
+            ..Config::default()
+        };
+        let reloaded = running.reloaded(&new);
+        assert_eq!(reloaded.port, running.port);
+        assert_eq!(reloaded.trusted_proxies, new.trusted_proxies);
+        assert_eq!(reloaded.rate_limit_burst, 5);
+        assert_eq!(reloaded.geoip_city_db, new.geoip_city_db);
+    }
+}
@@ -0,0 +1,139 @@
+//! Cross-origin access for browser-based tools (`CORS_ALLOWED_ORIGINS`).
+//!
+//! Off unless origins are configured. Preflight `OPTIONS` requests are
+//! answered here without reaching the rate limiter, and the CORS headers are
+//! added to every response, errors included, so a dashboard can read a 429
+//! instead of seeing an opaque network failure.
+
+use std::time::Duration;
+
+use axum::http::{HeaderName, HeaderValue, Method};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::config::Config;
+use crate::request_id::REQUEST_ID_HEADER;
+
+/// The CORS layer for `config`, or `None` when no origins are allowed.
+/// Values were validated when the config was loaded.
+pub fn cors_layer(config: &Config) -> Option<CorsLayer> {
+    if config.cors_allowed_origins.is_empty() {
+        return None;
+    }
+    let origins = if config.cors_allowed_origins.iter().any(|o| o == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(
+            config
+                .cors_allowed_origins
+                .iter()
+                .filter_map(|o| HeaderValue::from_str(o).ok()),
+        )
+    };
+    let methods: Vec<Method> = config
+        .cors_allowed_methods
+        .iter()
+        .filter_map(|m| Method::from_bytes(m.as_bytes()).ok())
+        .collect();
+    let headers: Vec<HeaderName> = config
+        .cors_allowed_headers
+        .iter()
+        .filter_map(|h| HeaderName::from_bytes(h.as_bytes()).ok())
+        .collect();
+    Some(
+        CorsLayer::new()
+            .allow_origin(origins)
+            .allow_methods(methods)
+            .allow_headers(headers)
+            .expose_headers([REQUEST_ID_HEADER])
+            .max_age(Duration::from_secs(config.cors_max_age_secs)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::Router;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode, header};
+    use axum::routing::get;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn app(origins: &[&str]) -> Router {
+        let config = Config {
+            cors_allowed_origins: origins.iter().map(|o| o.to_string()).collect(),
+            cors_allowed_headers: vec!["authorization".into()],
+            ..Config::default()
+        };
+        Router::new()
+            .route("/all.json", get(|| async { "{}" }))
+            .layer(cors_layer(&config).unwrap())
+    }
+
+    #[tokio::test]
+    async fn preflight_from_allowed_origin() {
+        let request = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/all.json")
+            .header(header::ORIGIN, "https://dash.example.com")
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+            .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "authorization")
+            .body(Body::empty())
+            .unwrap();
+        let response = app(&["https://dash.example.com"])
+            .oneshot(request)
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://dash.example.com"
+        );
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_METHODS], "GET,HEAD");
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_HEADERS],
+            "authorization"
+        );
+        assert_eq!(headers[header::ACCESS_CONTROL_MAX_AGE], "600");
+    }
+
+    #[tokio::test]
+    async fn other_origins_get_no_allow_header() {
+        let request = Request::builder()
+            .uri("/all.json")
+            .header(header::ORIGIN, "https://evil.example")
+            .body(Body::empty())
+            .unwrap();
+        let response = app(&["https://dash.example.com"])
+            .oneshot(request)
+            .await
+            .unwrap();
+        assert!(
+            !response
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        );
+    }
+
+    #[tokio::test]
+    async fn wildcard_allows_any_origin() {
+        let request = Request::builder()
+            .uri("/all.json")
+            .header(header::ORIGIN, "https://anywhere.example")
+            .body(Body::empty())
+            .unwrap();
+        let response = app(&["*"]).oneshot(request).await.unwrap();
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        assert_eq!(
+            response.headers()[header::ACCESS_CONTROL_EXPOSE_HEADERS],
+            "x-request-id"
+        );
+    }
+
+    #[test]
+    fn disabled_without_origins() {
+        assert!(cors_layer(&Config::default()).is_none());
+    }
+}
@@ -0,0 +1,121 @@
+//! Minimal UTC calendar conversion for log timestamps and file names,
+//! without pulling in a date/time crate.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A UTC wall-clock time, to the millisecond.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UtcDateTime {
+    pub year: i64,
+    /// 1–12.
+    pub month: u32,
+    /// 1–31.
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    /// 0–999; always 0 from [`UtcDateTime::from_unix`].
+    pub millisecond: u32,
+}
+
+impl UtcDateTime {
+    /// Times before the epoch are clamped to it.
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        Self {
+            millisecond: since_epoch.subsec_millis(),
+            ..Self::from_unix(since_epoch.as_secs())
+        }
+    }
+
+    pub fn from_unix(secs: u64) -> Self {
+        let (days, rem) = (secs / 86_400, secs % 86_400);
+        let (year, month, day) = civil_from_days(days as i64);
+        Self {
+            year,
+            month,
+            day,
+            hour: (rem / 3600) as u32,
+            minute: (rem % 3600 / 60) as u32,
+            second: (rem % 60) as u32,
+            millisecond: 0,
+        }
+    }
+
+    /// Three-letter English month name, as used by Common Log Format.
+    pub fn month_abbr(&self) -> &'static str {
+        const MONTHS: [&str; 12] = [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ];
+        MONTHS[self.month as usize - 1]
+    }
+
+    /// RFC 3339 with a `Z` offset, `2026-10-15T05:04:06Z`, or with `millis`
+    /// `2026-10-15T05:04:06.123Z`.
+    pub fn rfc3339(&self, millis: bool) -> String {
+        let date_time = format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        );
+        if millis {
+            format!("{date_time}.{:03}Z", self.millisecond)
+        } else {
+            format!("{date_time}Z")
+        }
+    }
+}
+
+/// Days since 1970-01-01 to (year, month, day), from Howard Hinnant's
+/// `civil_from_days`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn civil_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(20_741), (2026, 10, 15));
+    }
+
+    #[test]
+    fn splits_time_of_day() {
+        let t = UtcDateTime::from_unix(1_792_040_646);
+        assert_eq!(
+            t,
+            UtcDateTime {
+                year: 2026,
+                month: 10,
+                day: 15,
+                hour: 5,
+                minute: 4,
+                second: 6,
+                millisecond: 0,
+            }
+        );
+        assert_eq!(t.month_abbr(), "Oct");
+    }
+
+    #[test]
+    fn formats_rfc3339() {
+        let t =
+            UtcDateTime::from_system_time(UNIX_EPOCH + Duration::from_millis(1_792_040_646_007));
+        assert_eq!(t.rfc3339(false), "2026-10-15T05:04:06Z");
+        assert_eq!(t.rfc3339(true), "2026-10-15T05:04:06.007Z");
+    }
+}
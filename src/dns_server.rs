@@ -0,0 +1,486 @@
+//! DNS "whoami" server on `DNS_ADDR`, over UDP and TCP.
+//!
+//! A, AAAA and TXT queries for `DNS_ZONE` itself are answered with the
+//! address the query came from. Looked up through a recursive resolver,
+//! that is the resolver's own egress address, so `dig +short TXT
+//! whoami.example.com` shows which resolver a machine actually uses —
+//! like `whoami.akamai.net`. The zone has to be delegated here with an NS
+//! record.
+//!
+//! A and AAAA carry the address when it is of that family and are empty
+//! otherwise; TXT always has it. Answers have a TTL of 0 so resolvers
+//! don't hand one client's answer to another. Names directly below the
+//! zone that `/resolver-check` handed out are answered the same way, and
+//! each query for one is recorded (see [`crate::resolver_check`]). Other
+//! names below the zone get NXDOMAIN and names outside it REFUSED.
+//!
+//! Queries have their own rate limit per source address,
+//! `DNS_RATE_LIMIT`, apart from the HTTP one: a public resolver sends many
+//! users' queries from a few addresses. Over quota they're dropped, as a
+//! resolver will retry. At most [`MAX_TCP_CONNECTIONS`] TCP connections
+//! are served at once; more are closed straight away.
+
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use hickory_proto::op::{Edns, Message, MessageType, OpCode, ResponseCode};
+use hickory_proto::rr::rdata::opt::{EdnsCode, EdnsOption};
+use hickory_proto::rr::rdata::{A, AAAA, TXT};
+use hickory_proto::rr::{DNSClass, Name, RData, Record, RecordType};
+use ipnet::IpNet;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::Semaphore;
+
+use crate::listener::handle_accept_error;
+use crate::ratelimit::RateLimitState;
+use crate::resolver_check::{ResolverCheck, ResolverQuery};
+
+/// How long a TCP connection may sit between queries before it's closed.
+const TCP_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// EDNS payload size advertised to resolvers that use EDNS, the DNS Flag
+/// Day 2020 recommendation. Answers here are far smaller anyway.
+const EDNS_PAYLOAD: u16 = 1232;
+
+/// Largest UDP query read; a question for the zone fits with room to spare.
+const MAX_UDP_QUERY: usize = 512;
+
+/// Most TCP connections served at once. Resolvers fall back to TCP only
+/// for truncated answers, which these never are.
+pub const MAX_TCP_CONNECTIONS: usize = 256;
+
+/// How often idle sources are swept out of the rate limiter.
+const EVICTION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Answers queries for one zone. Cheap to clone, one per socket.
+#[derive(Clone)]
+pub struct DnsServer {
+    zone: Name,
+    rate_limit: RateLimitState,
+    resolver_check: Arc<ResolverCheck>,
+    tcp_connections: Arc<Semaphore>,
+}
+
+impl DnsServer {
+    /// Answer for `zone`, a validated `DNS_ZONE`, recording queries for
+    /// the names `resolver_check` hands out. `rate_limit` should be the
+    /// server's own (`DNS_RATE_LIMIT`); it's swept by [`Self::serve_udp`].
+    pub fn new(
+        zone: &str,
+        rate_limit: RateLimitState,
+        resolver_check: Arc<ResolverCheck>,
+    ) -> anyhow::Result<Self> {
+        let zone = Name::from_ascii(format!("{zone}."))
+            .map_err(|e| anyhow::anyhow!("invalid DNS_ZONE {zone}: {e}"))?;
+        Ok(Self {
+            zone,
+            rate_limit,
+            resolver_check,
+            tcp_connections: Arc::new(Semaphore::new(MAX_TCP_CONNECTIONS)),
+        })
+    }
+
+    /// Answer queries on `socket` until `shutdown` resolves.
+    pub async fn serve_udp<F>(self, socket: UdpSocket, shutdown: F)
+    where
+        F: Future<Output = ()>,
+    {
+        tokio::pin!(shutdown);
+        let mut buf = [0u8; MAX_UDP_QUERY];
+        let mut eviction = tokio::time::interval(EVICTION_INTERVAL);
+        loop {
+            let (len, peer) = tokio::select! {
+                received = socket.recv_from(&mut buf) => match received {
+                    Ok(received) => received,
+                    Err(e) => {
+                        tracing::debug!(error = %e, "DNS receive failed");
+                        continue;
+                    }
+                },
+                _ = eviction.tick() => {
+                    self.rate_limit.retain_recent();
+                    continue;
+                }
+                _ = &mut shutdown => break,
+            };
+            let Some(response) = self.answer(&buf[..len], peer.ip().to_canonical(), "udp") else {
+                continue;
+            };
+            if let Err(e) = socket.send_to(&response, peer).await {
+                tracing::debug!(%peer, error = %e, "DNS send failed");
+            }
+        }
+    }
+
+    /// Answer queries on connections to `listener` until `shutdown`
+    /// resolves.
+    pub async fn serve_tcp<F>(self, listener: TcpListener, shutdown: F)
+    where
+        F: Future<Output = ()>,
+    {
+        tokio::pin!(shutdown);
+        loop {
+            let (stream, peer) = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        handle_accept_error(e).await;
+                        continue;
+                    }
+                },
+                _ = &mut shutdown => break,
+            };
+            let Ok(permit) = self.tcp_connections.clone().try_acquire_owned() else {
+                tracing::debug!(%peer, "too many DNS TCP connections");
+                record("tcp", "too_many_connections");
+                continue;
+            };
+            let server = self.clone();
+            tokio::spawn(async move {
+                server.serve_connection(stream, peer).await;
+                drop(permit);
+            });
+        }
+    }
+
+    /// Answer length-prefixed queries until the client closes, goes idle
+    /// or sends something that isn't a query.
+    async fn serve_connection(self, mut stream: TcpStream, peer: SocketAddr) {
+        let client = peer.ip().to_canonical();
+        loop {
+            let read = async {
+                let len = stream.read_u16().await?;
+                let mut buf = vec![0; len.into()];
+                stream.read_exact(&mut buf).await?;
+                io::Result::Ok(buf)
+            };
+            let request = match tokio::time::timeout(TCP_IDLE_TIMEOUT, read).await {
+                Ok(Ok(request)) => request,
+                _ => return,
+            };
+            let Some(response) = self.answer(&request, client, "tcp") else {
+                return;
+            };
+            // Far below 64 KiB: one question and at most one answer.
+            let len = (response.len() as u16).to_be_bytes();
+            if let Err(e) = stream.write_all(&[&len[..], &response].concat()).await {
+                tracing::debug!(%peer, error = %e, "DNS send failed");
+                return;
+            }
+        }
+    }
+
+    /// The wire-format response to `request` from `client`, or `None` to
+    /// drop it.
+    fn answer(&self, request: &[u8], client: IpAddr, transport: &'static str) -> Option<Vec<u8>> {
+        if !self.rate_limit.check(client) {
+            record(transport, "rate_limited");
+            return None;
+        }
+        // Never answer a response, or two servers could bounce one forever.
+        let request = match Message::from_vec(request) {
+            Ok(request) if request.message_type() == MessageType::Query => request,
+            _ => {
+                record(transport, "malformed");
+                return None;
+            }
+        };
+        let response = self.respond(&request, client, transport);
+        record(transport, result(response.response_code()));
+        match response.to_vec() {
+            Ok(response) => Some(response),
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to encode DNS response");
+                None
+            }
+        }
+    }
+
+    fn respond(&self, request: &Message, client: IpAddr, transport: &'static str) -> Message {
+        let (code, answer) = self.lookup(request, client, transport);
+        let mut response = Message::new();
+        response
+            .set_id(request.id())
+            .set_message_type(MessageType::Response)
+            .set_op_code(request.op_code())
+            .set_authoritative(code != ResponseCode::Refused)
+            .set_recursion_desired(request.recursion_desired())
+            .set_response_code(code)
+            .add_queries(request.queries().to_vec())
+            .add_answers(answer);
+        if request.extensions().is_some() {
+            let mut edns = Edns::new();
+            edns.set_max_payload(EDNS_PAYLOAD);
+            response.set_edns(edns);
+        }
+        response
+    }
+
+    /// The response code and answer record for `request`.
+    fn lookup(
+        &self,
+        request: &Message,
+        client: IpAddr,
+        transport: &'static str,
+    ) -> (ResponseCode, Option<Record>) {
+        if request.op_code() != OpCode::Query {
+            return (ResponseCode::NotImp, None);
+        }
+        let [query] = request.queries() else {
+            return (ResponseCode::FormErr, None);
+        };
+        if query.query_class() != DNSClass::IN || !self.zone.zone_of(query.name()) {
+            return (ResponseCode::Refused, None);
+        }
+        if query.name().num_labels() != self.zone.num_labels() {
+            // A `/resolver-check` name is the token plus the zone.
+            let token = (query.name().num_labels() == self.zone.num_labels() + 1)
+                .then(|| query.name().iter().next())
+                .flatten()
+                .map(|label| String::from_utf8_lossy(label).to_ascii_lowercase());
+            let seen = token.is_some_and(|token| {
+                let asked = resolver_query(request, client, transport, query.query_type());
+                self.resolver_check.record(&token, asked)
+            });
+            if !seen {
+                return (ResponseCode::NXDomain, None);
+            }
+        }
+        let rdata = match (query.query_type(), client) {
+            (RecordType::A, IpAddr::V4(ip)) => RData::A(A(ip)),
+            (RecordType::AAAA, IpAddr::V6(ip)) => RData::AAAA(AAAA(ip)),
+            (RecordType::TXT, ip) => RData::TXT(TXT::new(vec![ip.to_string()])),
+            _ => return (ResponseCode::NoError, None),
+        };
+        // The name as asked, which may be in mixed case (DNS 0x20).
+        let record = Record::from_rdata(query.name().clone(), 0, rdata);
+        (ResponseCode::NoError, Some(record))
+    }
+}
+
+/// What a resolver's query for a `/resolver-check` name says about it.
+fn resolver_query(
+    request: &Message,
+    client: IpAddr,
+    transport: &'static str,
+    query_type: RecordType,
+) -> ResolverQuery {
+    let edns = request.extensions().as_ref();
+    let ecs = match edns.and_then(|edns| edns.option(EdnsCode::Subnet)) {
+        Some(EdnsOption::Subnet(subnet)) => IpNet::new(subnet.addr(), subnet.source_prefix())
+            .ok()
+            .map(|net| net.trunc().to_string()),
+        _ => None,
+    };
+    ResolverQuery {
+        resolver: client,
+        ecs,
+        dnssec_ok: edns.is_some_and(|edns| edns.flags().dnssec_ok),
+        transport,
+        query_type: query_type.to_string(),
+    }
+}
+
+fn result(code: ResponseCode) -> &'static str {
+    match code {
+        ResponseCode::NoError => "noerror",
+        ResponseCode::NXDomain => "nxdomain",
+        ResponseCode::Refused => "refused",
+        ResponseCode::NotImp => "notimp",
+        _ => "formerr",
+    }
+}
+
+fn record(transport: &'static str, result: &'static str) {
+    metrics::counter!("dns_queries_total", "transport" => transport, "result" => result)
+        .increment(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use hickory_proto::op::Query;
+
+    use super::*;
+    use crate::config::Config;
+
+    fn server_with(resolver_check: Arc<ResolverCheck>) -> DnsServer {
+        DnsServer::new(
+            "whoami.example.com",
+            RateLimitState::new(100, 100),
+            resolver_check,
+        )
+        .unwrap()
+    }
+
+    fn server() -> DnsServer {
+        server_with(Arc::new(ResolverCheck::new(&Config::default())))
+    }
+
+    fn query(name: &str, kind: RecordType) -> Message {
+        let mut request = Message::new();
+        request
+            .set_id(4711)
+            .set_recursion_desired(true)
+            .add_query(Query::query(Name::from_ascii(name).unwrap(), kind));
+        request
+    }
+
+    fn ask_server(server: &DnsServer, request: &Message, client: &str) -> Message {
+        let response = server
+            .answer(&request.to_vec().unwrap(), client.parse().unwrap(), "udp")
+            .unwrap();
+        Message::from_vec(&response).unwrap()
+    }
+
+    fn ask(name: &str, kind: RecordType, client: &str) -> Message {
+        ask_server(&server(), &query(name, kind), client)
+    }
+
+    #[test]
+    fn answers_with_the_resolver_address() {
+        let response = ask("WhoAmI.example.com.", RecordType::A, "198.51.100.53");
+        assert_eq!(response.id(), 4711);
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert!(response.authoritative());
+        assert_eq!(response.answers().len(), 1);
+        let answer = &response.answers()[0];
+        assert_eq!(answer.ttl(), 0);
+        assert_eq!(answer.name().to_ascii(), "WhoAmI.example.com.");
+        assert_eq!(
+            answer.data(),
+            &RData::A(A("198.51.100.53".parse().unwrap()))
+        );
+
+        let response = ask("whoami.example.com.", RecordType::TXT, "2001:db8::53");
+        assert_eq!(response.answers()[0].data().to_string(), "2001:db8::53");
+        let response = ask("whoami.example.com.", RecordType::AAAA, "2001:db8::53");
+        assert_eq!(
+            response.answers()[0].data(),
+            &RData::AAAA(AAAA("2001:db8::53".parse().unwrap()))
+        );
+    }
+
+    #[test]
+    fn other_families_and_types_are_empty() {
+        let response = ask("whoami.example.com.", RecordType::AAAA, "198.51.100.53");
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert!(response.answers().is_empty());
+        let response = ask("whoami.example.com.", RecordType::MX, "198.51.100.53");
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert!(response.answers().is_empty());
+    }
+
+    #[test]
+    fn other_names_are_nxdomain_or_refused() {
+        let response = ask("www.whoami.example.com.", RecordType::A, "198.51.100.53");
+        assert_eq!(response.response_code(), ResponseCode::NXDomain);
+        let response = ask("example.com.", RecordType::A, "198.51.100.53");
+        assert_eq!(response.response_code(), ResponseCode::Refused);
+        assert!(!response.authoritative());
+    }
+
+    #[test]
+    fn records_resolver_check_queries() {
+        let check = Arc::new(ResolverCheck::new(&Config {
+            dns_addr: Some("127.0.0.1:5353".parse().unwrap()),
+            dns_zone: Some("whoami.example.com".to_string()),
+            ..Config::default()
+        }));
+        let server = server_with(check.clone());
+        let token = check.start().unwrap();
+
+        // Mixed case, as a resolver using DNS 0x20 sends it.
+        let name = format!("{}.WhoAmI.example.com.", token.to_ascii_uppercase());
+        let mut request = query(&name, RecordType::AAAA);
+        let mut edns = Edns::new();
+        edns.set_dnssec_ok(true);
+        edns.options_mut()
+            .insert(EdnsOption::Subnet("203.0.113.77/24".parse().unwrap()));
+        request.set_edns(edns);
+        let response = ask_server(&server, &request, "2001:db8::53");
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(
+            response.answers()[0].data(),
+            &RData::AAAA(AAAA("2001:db8::53".parse().unwrap()))
+        );
+        let response = ask_server(&server, &query(&name, RecordType::A), "198.51.100.53");
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+
+        let queries = check.queries(&token).unwrap();
+        assert_eq!(queries.len(), 2);
+        assert_eq!(
+            queries[0].resolver,
+            "2001:db8::53".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(queries[0].ecs.as_deref(), Some("203.0.113.0/24"));
+        assert!(queries[0].dnssec_ok);
+        assert_eq!(queries[0].query_type, "AAAA");
+        assert_eq!(queries[1].ecs, None);
+        assert!(!queries[1].dnssec_ok);
+
+        // Names that weren't handed out are still NXDOMAIN.
+        let response = ask_server(
+            &server,
+            &query("0123abcd.whoami.example.com.", RecordType::A),
+            "198.51.100.53",
+        );
+        assert_eq!(response.response_code(), ResponseCode::NXDomain);
+    }
+
+    #[test]
+    fn drops_garbage_and_responses() {
+        let server = server();
+        let client = "198.51.100.53".parse().unwrap();
+        assert!(server.answer(b"\x12", client, "udp").is_none());
+        let mut response = Message::new();
+        response.set_message_type(MessageType::Response);
+        assert!(
+            server
+                .answer(&response.to_vec().unwrap(), client, "udp")
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn serves_length_prefixed_queries_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(server().serve_tcp(listener, std::future::pending()));
+
+        let mut request = Message::new();
+        request.add_query(Query::query(
+            Name::from_ascii("whoami.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        let request = request.to_vec().unwrap();
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        for _ in 0..2 {
+            stream.write_u16(request.len() as u16).await.unwrap();
+            stream.write_all(&request).await.unwrap();
+            let mut response = vec![0; stream.read_u16().await.unwrap().into()];
+            stream.read_exact(&mut response).await.unwrap();
+            let response = Message::from_vec(&response).unwrap();
+            assert_eq!(
+                response.answers()[0].data(),
+                &RData::A(A("127.0.0.1".parse().unwrap()))
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn closes_tcp_connections_over_the_cap() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut server = server();
+        server.tcp_connections = Arc::new(Semaphore::new(0));
+        tokio::spawn(server.serve_tcp(listener, std::future::pending()));
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let mut buf = [0u8; 1];
+        assert_eq!(stream.read(&mut buf).await.unwrap(), 0);
+    }
+}
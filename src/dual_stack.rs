@@ -0,0 +1,148 @@
+//! Dual-stack correlation (`/ds`): tie a client's IPv4 and IPv6 addresses
+//! together, to check both work and compare where each egresses.
+//!
+//! `/ds/start` hands out a token. The client then fetches `/ds/{token}`
+//! through `ipv4.<host>` and `ipv6.<host>`, names that only have an A and
+//! an AAAA record respectively, and each fetch records the address it came
+//! from under its family. `/ds/{token}/result` reports what arrived. The
+//! names are built from `DUAL_STACK_ORIGIN`, and the flow is off without
+//! it; tokens last `DUAL_STACK_TTL_SECS` (see [`crate::session`]).
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::errors::AppError;
+use crate::session::SessionStore;
+
+/// Most sessions in flight at once.
+const CAPACITY: usize = 10_000;
+
+/// The addresses seen for one token, one per family.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Addresses {
+    pub ipv4: Option<IpAddr>,
+    pub ipv6: Option<IpAddr>,
+}
+
+pub struct DualStack {
+    /// `scheme://` and `host[:port]` of `DUAL_STACK_ORIGIN`; `None` when
+    /// the flow is disabled.
+    origin: Option<(String, String)>,
+    sessions: SessionStore<Addresses>,
+}
+
+impl DualStack {
+    pub fn new(config: &Config) -> Self {
+        let origin = config.dual_stack_origin.as_deref().and_then(|origin| {
+            let (scheme, host) = origin.split_once("://")?;
+            Some((format!("{scheme}://"), host.to_string()))
+        });
+        Self {
+            origin,
+            sessions: SessionStore::new(Duration::from_secs(config.dual_stack_ttl_secs), CAPACITY),
+        }
+    }
+
+    fn origin(&self) -> Result<&(String, String), AppError> {
+        self.origin.as_ref().ok_or_else(|| {
+            AppError::NotFound("dual-stack tests are disabled (DUAL_STACK_ORIGIN)".to_string())
+        })
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.sessions.ttl()
+    }
+
+    /// Start a session, returning its token.
+    pub fn start(&self) -> Result<String, AppError> {
+        self.origin()?;
+        Ok(self.sessions.start())
+    }
+
+    /// `/ds/{token}` on the `family` (`ipv4` or `ipv6`) host.
+    pub fn url(&self, family: &str, token: &str) -> Result<String, AppError> {
+        let (scheme, host) = self.origin()?;
+        Ok(format!("{scheme}{family}.{host}/ds/{token}"))
+    }
+
+    /// `/ds/{token}/result` on the main host.
+    pub fn result_url(&self, token: &str) -> Result<String, AppError> {
+        let (scheme, host) = self.origin()?;
+        Ok(format!("{scheme}{host}/ds/{token}/result"))
+    }
+
+    /// Record `ip` under its family for `token`, replacing any earlier
+    /// address of that family.
+    pub fn record(&self, token: &str, ip: IpAddr) -> Result<IpAddr, AppError> {
+        self.origin()?;
+        let ip = ip.to_canonical();
+        self.sessions
+            .update(token, |addresses| match ip {
+                IpAddr::V4(_) => addresses.ipv4 = Some(ip),
+                IpAddr::V6(_) => addresses.ipv6 = Some(ip),
+            })
+            .ok_or_else(unknown_token)?;
+        Ok(ip)
+    }
+
+    /// What's been recorded for `token` so far.
+    pub fn addresses(&self, token: &str) -> Result<Addresses, AppError> {
+        self.origin()?;
+        self.sessions.get(token).ok_or_else(unknown_token)
+    }
+}
+
+fn unknown_token() -> AppError {
+    AppError::NotFound("unknown or expired dual-stack token".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dual_stack(origin: Option<&str>) -> DualStack {
+        DualStack::new(&Config {
+            dual_stack_origin: origin.map(String::from),
+            ..Config::default()
+        })
+    }
+
+    #[test]
+    fn disabled_without_an_origin() {
+        let ds = dual_stack(None);
+        assert!(matches!(ds.start(), Err(AppError::NotFound(_))));
+        assert!(matches!(ds.url("ipv4", "t"), Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn records_one_address_per_family() {
+        let ds = dual_stack(Some("https://example.com:8443"));
+        let token = ds.start().unwrap();
+        assert_eq!(
+            ds.url("ipv6", &token).unwrap(),
+            format!("https://ipv6.example.com:8443/ds/{token}")
+        );
+        assert_eq!(
+            ds.result_url(&token).unwrap(),
+            format!("https://example.com:8443/ds/{token}/result")
+        );
+
+        // A v4-mapped address counts as IPv4.
+        let v4 = ds
+            .record(&token, "::ffff:203.0.113.7".parse().unwrap())
+            .unwrap();
+        assert_eq!(v4, "203.0.113.7".parse::<IpAddr>().unwrap());
+        let addresses = ds.addresses(&token).unwrap();
+        assert_eq!((addresses.ipv4, addresses.ipv6), (Some(v4), None));
+
+        let v6: IpAddr = "2001:db8::1".parse().unwrap();
+        ds.record(&token, v6).unwrap();
+        assert_eq!(ds.addresses(&token).unwrap().ipv6, Some(v6));
+
+        assert!(matches!(
+            ds.record("unknown", v6),
+            Err(AppError::NotFound(_))
+        ));
+    }
+}
@@ -20,6 +20,18 @@ pub enum AppError {
     #[error("JSON serialization failed")]
     JsonError(#[from] serde_json::Error),
 
+    #[cfg(feature = "formats")]
+    #[error("YAML serialization failed")]
+    YamlError(#[from] serde_yaml::Error),
+
+    #[cfg(feature = "formats")]
+    #[error("CBOR serialization failed")]
+    CborError(#[from] ciborium::ser::Error<std::io::Error>),
+
+    #[cfg(feature = "formats")]
+    #[error("MessagePack serialization failed")]
+    MsgpackError(#[from] rmp_serde::encode::Error),
+
     #[error("HTTP builder failed")]
     HttpBuilderError,
 
@@ -28,15 +40,48 @@ pub enum AppError {
 
     #[error("Not found: {0}")]
     NotFound(String),
+
+    #[error("Bad request: {0}")]
+    BadRequest(String),
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    /// Over an endpoint's own quota, on top of the global rate limit.
+    #[error("Rate limit exceeded, retry in {retry_after_secs}s")]
+    RateLimited { retry_after_secs: u64 },
+
+    #[error("Server overloaded, try again shortly")]
+    Overloaded,
+
+    #[error("Request timed out")]
+    Timeout,
+
+    #[error("Request headers larger than {0} bytes")]
+    HeadersTooLarge(usize),
+
+    #[error("Request body larger than {0} bytes")]
+    PayloadTooLarge(usize),
 }
 
 impl AppError {
     pub fn status_code(&self) -> StatusCode {
         match self {
             Self::JsonError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            #[cfg(feature = "formats")]
+            Self::YamlError(_) | Self::CborError(_) | Self::MsgpackError(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
             Self::HttpBuilderError => StatusCode::INTERNAL_SERVER_ERROR,
             Self::HeaderError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            Self::Overloaded => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Timeout => StatusCode::REQUEST_TIMEOUT,
+            Self::HeadersTooLarge(_) => StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+            Self::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
         }
     }
 }
@@ -49,9 +94,13 @@ impl IntoResponse for AppError {
         });
         let body_str = serde_json::to_string(&body).unwrap_or_default();
         
-        Response::builder()
+        let mut builder = Response::builder()
             .status(status)
-            .header("content-type", "application/json")
+            .header("content-type", "application/json");
+        if let Self::RateLimited { retry_after_secs } = self {
+            builder = builder.header("retry-after", retry_after_secs);
+        }
+        builder
             .body(axum::body::Body::from(body_str))
             .unwrap_or_else(|_| Response::new(axum::body::Body::from("Internal Server Error")))
     }
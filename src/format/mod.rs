@@ -0,0 +1,416 @@
+//! Response format selection and the generic renderers behind it.
+//!
+//! Endpoints that support more than one representation pick one from the
+//! `Accept` header via [`ResponseFormat::negotiate`], passing their own
+//! default for clients that express no preference (`*/*` or no header) —
+//! so `curl /ip` keeps getting plain text while a browser gets HTML. `/`
+//! also picks its default by [`is_cli_client`], giving curl and friends a
+//! bare IP. A `?format=` query parameter overrides `Accept` for clients
+//! that can't easily set headers.
+//!
+//! Every format is rendered from the same `Serialize` value by
+//! [`render_with`] and the format's [`Renderer`](render::Renderer), so
+//! handlers build one response struct and never deal with individual
+//! formats.
+
+pub mod render;
+
+use axum::http::{HeaderMap, header};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::AppError;
+use render::{Document, Renderer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseFormat {
+    Json,
+    Text,
+    Html,
+    Xml,
+    #[cfg(feature = "formats")]
+    Yaml,
+    Csv,
+    #[cfg(feature = "formats")]
+    Cbor,
+    #[cfg(feature = "formats")]
+    Msgpack,
+}
+
+/// The `?format=` query parameter.
+#[derive(Debug, Default, Deserialize)]
+pub struct FormatQuery {
+    pub format: Option<String>,
+}
+
+/// `User-Agent` prefixes of command-line HTTP clients, lowercased.
+const CLI_USER_AGENTS: &[&str] = &[
+    "curl/",
+    "wget/",
+    "httpie/",
+    "xh/",
+    "fetch libfetch/",
+    "node-fetch",
+    "undici",
+    "aria2/",
+];
+
+/// Whether the request looks like it came from a command-line client
+/// (curl, wget, HTTPie, fetch, PowerShell...), judged by `User-Agent`.
+pub fn is_cli_client(headers: &HeaderMap) -> bool {
+    let Some(ua) = headers
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+    else {
+        return false;
+    };
+    let ua = ua.trim().to_ascii_lowercase();
+    CLI_USER_AGENTS.iter().any(|prefix| ua.starts_with(prefix)) || ua.contains("powershell/")
+}
+
+/// The `?callback=` query parameter for JSONP endpoints.
+#[derive(Debug, Default, Deserialize)]
+pub struct JsonpQuery {
+    pub callback: Option<String>,
+}
+
+/// Longest JSONP callback name we accept.
+const MAX_CALLBACK_LEN: usize = 64;
+
+/// Validate a JSONP callback name: a JavaScript identifier or a dotted path
+/// of them (`jQuery123.cb`). Anything else would let the caller inject
+/// script into our response, so it's rejected rather than escaped.
+pub fn jsonp_callback(raw: &str) -> Result<&str, AppError> {
+    let is_ident = |part: &str| {
+        let mut chars = part.chars();
+        matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '$')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+    };
+    if raw.len() <= MAX_CALLBACK_LEN && raw.split('.').all(is_ident) {
+        Ok(raw)
+    } else {
+        Err(AppError::BadRequest(format!("invalid callback \"{raw}\"")))
+    }
+}
+
+impl ResponseFormat {
+    const ALL: &[Self] = &[
+        Self::Json,
+        Self::Text,
+        Self::Html,
+        Self::Xml,
+        #[cfg(feature = "formats")]
+        Self::Yaml,
+        Self::Csv,
+        #[cfg(feature = "formats")]
+        Self::Cbor,
+        #[cfg(feature = "formats")]
+        Self::Msgpack,
+    ];
+
+    pub fn renderer(self) -> &'static dyn Renderer {
+        match self {
+            Self::Json => &render::Json,
+            Self::Text => &render::Text,
+            Self::Html => &render::Html,
+            Self::Xml => &render::Xml,
+            #[cfg(feature = "formats")]
+            Self::Yaml => &render::Yaml,
+            Self::Csv => &render::Csv,
+            #[cfg(feature = "formats")]
+            Self::Cbor => &render::Cbor,
+            #[cfg(feature = "formats")]
+            Self::Msgpack => &render::Msgpack,
+        }
+    }
+
+    fn media_type(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Json => ("application", "json"),
+            Self::Text => ("text", "plain"),
+            Self::Html => ("text", "html"),
+            Self::Xml => ("application", "xml"),
+            #[cfg(feature = "formats")]
+            Self::Yaml => ("application", "yaml"),
+            Self::Csv => ("text", "csv"),
+            #[cfg(feature = "formats")]
+            Self::Cbor => ("application", "cbor"),
+            #[cfg(feature = "formats")]
+            Self::Msgpack => ("application", "msgpack"),
+        }
+    }
+
+    /// Parse a `?format=` value. YAML, CBOR and MessagePack need the
+    /// `formats` feature; without it they're unknown names.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "text" | "txt" | "plain" => Some(Self::Text),
+            "html" => Some(Self::Html),
+            "xml" => Some(Self::Xml),
+            #[cfg(feature = "formats")]
+            "yaml" | "yml" => Some(Self::Yaml),
+            "csv" => Some(Self::Csv),
+            #[cfg(feature = "formats")]
+            "cbor" => Some(Self::Cbor),
+            #[cfg(feature = "formats")]
+            "msgpack" => Some(Self::Msgpack),
+            _ => None,
+        }
+    }
+
+    /// `?format=` if given, else `Accept` negotiation. An unknown format
+    /// name is a client error rather than a silent fallback.
+    pub fn select(
+        query: &FormatQuery,
+        headers: &HeaderMap,
+        default: Self,
+    ) -> Result<Self, AppError> {
+        match query.format.as_deref() {
+            Some(name) => Self::from_name(name)
+                .ok_or_else(|| AppError::BadRequest(format!("unsupported format \"{name}\""))),
+            None => Ok(Self::negotiate(headers, default)),
+        }
+    }
+
+    /// Pick the format the client ranks highest by `Accept` q-value. Ties
+    /// go to the more specific match (`text/html` beats `text/*` beats
+    /// `*/*`), then to `default`. Falls back to `default` if nothing we
+    /// offer is acceptable.
+    pub fn negotiate(headers: &HeaderMap, default: Self) -> Self {
+        let ranges: Vec<MediaRange> = headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .filter_map(MediaRange::parse)
+            .collect();
+        if ranges.is_empty() {
+            return default;
+        }
+
+        let mut best: Option<(Self, u16, u8)> = None;
+        for &format in Self::ALL {
+            let Some((q, specificity)) = match_ranges(&ranges, format.media_type()) else {
+                continue;
+            };
+            if q == 0 {
+                continue;
+            }
+            let better = match best {
+                None => true,
+                Some((current, bq, bs)) => {
+                    (q, specificity) > (bq, bs)
+                        || ((q, specificity) == (bq, bs) && format == default && current != default)
+                }
+            };
+            if better {
+                best = Some((format, q, specificity));
+            }
+        }
+        best.map_or(default, |(format, _, _)| format)
+    }
+}
+
+/// Serialize `value` with `renderer`. `text` replaces the generic
+/// `key: value` rendering for endpoints whose plain-text form is a bare
+/// value (e.g. `/ip`); `title` is only used for HTML.
+pub fn render_with<T: Serialize>(
+    renderer: &dyn Renderer,
+    title: &str,
+    value: &T,
+    text: Option<String>,
+) -> Result<Vec<u8>, AppError> {
+    let doc = Document {
+        title,
+        value: serde_json::to_value(value)?,
+        text,
+    };
+    renderer.render(&doc)
+}
+
+/// One entry of an `Accept` header, with `q` scaled to 0..=1000.
+struct MediaRange<'a> {
+    kind: &'a str,
+    subtype: &'a str,
+    q: u16,
+}
+
+impl<'a> MediaRange<'a> {
+    fn parse(raw: &'a str) -> Option<Self> {
+        let mut parts = raw.split(';');
+        let (kind, subtype) = parts.next()?.trim().split_once('/')?;
+        let mut q = 1000;
+        for param in parts {
+            if let Some((name, value)) = param.split_once('=')
+                && name.trim().eq_ignore_ascii_case("q")
+            {
+                q = parse_qvalue(value.trim())?;
+            }
+        }
+        Some(Self {
+            kind: kind.trim(),
+            subtype: subtype.trim(),
+            q,
+        })
+    }
+}
+
+/// An `Accept*` q-value (RFC 9110 §12.4.2) scaled to 0..=1000.
+pub(crate) fn parse_qvalue(raw: &str) -> Option<u16> {
+    let q: f32 = raw.parse().ok()?;
+    (0.0..=1.0)
+        .contains(&q)
+        .then(|| (q * 1000.0).round() as u16)
+}
+
+/// The q-value and specificity (2 exact, 1 `type/*`, 0 `*/*`) of the most
+/// specific range matching `(kind, subtype)`, per RFC 9110 §12.5.1.
+fn match_ranges(ranges: &[MediaRange], (kind, subtype): (&str, &str)) -> Option<(u16, u8)> {
+    ranges
+        .iter()
+        .filter_map(|r| {
+            let specificity = if r.kind == "*" && r.subtype == "*" {
+                0
+            } else if r.kind.eq_ignore_ascii_case(kind) && r.subtype == "*" {
+                1
+            } else if r.kind.eq_ignore_ascii_case(kind) && r.subtype.eq_ignore_ascii_case(subtype) {
+                2
+            } else {
+                return None;
+            };
+            Some((specificity, r.q))
+        })
+        .max_by_key(|(specificity, _)| *specificity)
+        .map(|(specificity, q)| (q, specificity))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn negotiate(accept: Option<&str>, default: ResponseFormat) -> ResponseFormat {
+        let mut headers = HeaderMap::new();
+        if let Some(accept) = accept {
+            headers.insert(header::ACCEPT, accept.parse().unwrap());
+        }
+        ResponseFormat::negotiate(&headers, default)
+    }
+
+    #[test]
+    fn no_preference_uses_default() {
+        assert_eq!(negotiate(None, ResponseFormat::Json), ResponseFormat::Json);
+        assert_eq!(
+            negotiate(Some("*/*"), ResponseFormat::Text),
+            ResponseFormat::Text
+        );
+        assert_eq!(
+            negotiate(Some("image/png"), ResponseFormat::Json),
+            ResponseFormat::Json
+        );
+    }
+
+    #[test]
+    fn browsers_get_html() {
+        let firefox = "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8";
+        assert_eq!(
+            negotiate(Some(firefox), ResponseFormat::Json),
+            ResponseFormat::Html
+        );
+        assert_eq!(
+            negotiate(Some(firefox), ResponseFormat::Text),
+            ResponseFormat::Html
+        );
+    }
+
+    #[test]
+    fn explicit_types_win_over_default() {
+        assert_eq!(
+            negotiate(Some("application/json"), ResponseFormat::Text),
+            ResponseFormat::Json
+        );
+        assert_eq!(
+            negotiate(Some("text/plain"), ResponseFormat::Json),
+            ResponseFormat::Text
+        );
+    }
+
+    #[test]
+    fn q_values_and_specificity() {
+        assert_eq!(
+            negotiate(
+                Some("application/json;q=0.5, text/plain"),
+                ResponseFormat::Json
+            ),
+            ResponseFormat::Text
+        );
+        // text/* matches both text formats equally; the default breaks the tie.
+        assert_eq!(
+            negotiate(Some("text/*"), ResponseFormat::Html),
+            ResponseFormat::Html
+        );
+        // q=0 means "not acceptable".
+        assert_eq!(
+            negotiate(Some("application/json;q=0, */*"), ResponseFormat::Json),
+            ResponseFormat::Text
+        );
+    }
+
+    #[test]
+    fn jsonp_callback_names() {
+        assert!(jsonp_callback("cb").is_ok());
+        assert!(jsonp_callback("jQuery_123.$cb").is_ok());
+        assert!(jsonp_callback("").is_err());
+        assert!(jsonp_callback("a..b").is_err());
+        assert!(jsonp_callback("1cb").is_err());
+        assert!(jsonp_callback("alert(1);cb").is_err());
+        assert!(jsonp_callback(&"a".repeat(65)).is_err());
+    }
+
+    #[test]
+    fn cli_clients() {
+        let ua = |ua: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::USER_AGENT, ua.parse().unwrap());
+            is_cli_client(&headers)
+        };
+        assert!(ua("curl/8.7.1"));
+        assert!(ua("Wget/1.21.4"));
+        assert!(ua("HTTPie/3.2.2"));
+        assert!(ua("fetch libfetch/2.0"));
+        assert!(ua(
+            "Mozilla/5.0 (Windows NT 10.0; Microsoft Windows 10.0.19045; en-US) PowerShell/7.4.1"
+        ));
+        assert!(!ua(
+            "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0"
+        ));
+        assert!(!ua("Mozilla/5.0 (compatible; curlbot)"));
+        assert!(!is_cli_client(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn format_names() {
+        assert_eq!(
+            ResponseFormat::from_name("JSON"),
+            Some(ResponseFormat::Json)
+        );
+        assert_eq!(ResponseFormat::from_name("txt"), Some(ResponseFormat::Text));
+        assert_eq!(ResponseFormat::from_name("bogus"), None);
+    }
+
+    #[test]
+    fn query_overrides_accept() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, "application/json".parse().unwrap());
+        let query = FormatQuery {
+            format: Some("csv".into()),
+        };
+        assert_eq!(
+            ResponseFormat::select(&query, &headers, ResponseFormat::Json).unwrap(),
+            ResponseFormat::Csv
+        );
+        let query = FormatQuery {
+            format: Some("bogus".into()),
+        };
+        assert!(ResponseFormat::select(&query, &headers, ResponseFormat::Json).is_err());
+    }
+}
@@ -0,0 +1,461 @@
+//! One [`Renderer`] per output format.
+//!
+//! Handlers rarely name these directly: they build a `Serialize` value and
+//! hand it to [`render_with`](super::render_with) with the renderer from
+//! [`ResponseFormat::renderer`](super::ResponseFormat::renderer), which
+//! converts it to a [`Document`]. Adding a format means adding a renderer here and a variant to
+//! `ResponseFormat` — no handler changes.
+
+use serde_json::Value;
+
+use crate::errors::AppError;
+
+/// Root element name for XML output.
+const XML_ROOT: &str = "ipecho";
+
+/// Template for the browser landing page, see [`Landing`].
+#[cfg(feature = "html")]
+const LANDING_TEMPLATE: &str = include_str!("landing.html");
+
+/// A response ready to be rendered in any format.
+pub struct Document<'a> {
+    /// Page title, used by HTML.
+    pub title: &'a str,
+    pub value: Value,
+    /// Replaces the generic `key: value` text rendering for endpoints whose
+    /// plain-text form is a bare value (e.g. `/ip`).
+    pub text: Option<String>,
+}
+
+pub trait Renderer: Sync {
+    fn content_type(&self) -> &'static str;
+
+    fn render(&self, doc: &Document) -> Result<Vec<u8>, AppError>;
+}
+
+pub struct Json;
+pub struct Text;
+pub struct Html;
+pub struct Xml;
+#[cfg(feature = "formats")]
+pub struct Yaml;
+pub struct Csv;
+#[cfg(feature = "formats")]
+pub struct Cbor;
+#[cfg(feature = "formats")]
+pub struct Msgpack;
+
+/// JSON wrapped in a call to `callback`, for `<script>`-tag embedding.
+/// `callback` must already be validated with
+/// [`jsonp_callback`](super::jsonp_callback).
+pub struct Jsonp<'a> {
+    pub callback: &'a str,
+}
+
+/// The browser landing page for `/`: the client IP with a copy button, a
+/// table of every field, and links to the API. Fills `{{base}}`, `{{ip}}`
+/// and `{{rows}}` in [`LANDING_TEMPLATE`]. Without the `html` feature, `/`
+/// falls back to the generic [`Html`] table.
+#[cfg(feature = "html")]
+pub struct Landing<'a> {
+    /// What the API links start with: the path the router is mounted at,
+    /// without a trailing slash (`""` at the root), or `.` for a page
+    /// beside the API routes.
+    pub base: &'a str,
+}
+
+impl Renderer for Json {
+    fn content_type(&self) -> &'static str {
+        "application/json"
+    }
+
+    fn render(&self, doc: &Document) -> Result<Vec<u8>, AppError> {
+        Ok(serde_json::to_vec_pretty(&doc.value)?)
+    }
+}
+
+impl Renderer for Text {
+    fn content_type(&self) -> &'static str {
+        "text/plain"
+    }
+
+    fn render(&self, doc: &Document) -> Result<Vec<u8>, AppError> {
+        Ok(match &doc.text {
+            Some(text) => text.clone().into_bytes(),
+            None => render_text(&doc.value).into_bytes(),
+        })
+    }
+}
+
+impl Renderer for Html {
+    fn content_type(&self) -> &'static str {
+        "text/html; charset=utf-8"
+    }
+
+    fn render(&self, doc: &Document) -> Result<Vec<u8>, AppError> {
+        Ok(render_html(doc.title, &doc.value).into_bytes())
+    }
+}
+
+impl Renderer for Xml {
+    fn content_type(&self) -> &'static str {
+        "application/xml"
+    }
+
+    fn render(&self, doc: &Document) -> Result<Vec<u8>, AppError> {
+        Ok(render_xml(&doc.value).into_bytes())
+    }
+}
+
+#[cfg(feature = "formats")]
+impl Renderer for Yaml {
+    fn content_type(&self) -> &'static str {
+        "application/yaml"
+    }
+
+    fn render(&self, doc: &Document) -> Result<Vec<u8>, AppError> {
+        Ok(serde_yaml::to_string(&doc.value)?.into_bytes())
+    }
+}
+
+impl Renderer for Csv {
+    fn content_type(&self) -> &'static str {
+        "text/csv"
+    }
+
+    fn render(&self, doc: &Document) -> Result<Vec<u8>, AppError> {
+        Ok(render_csv(&doc.value).into_bytes())
+    }
+}
+
+#[cfg(feature = "formats")]
+impl Renderer for Cbor {
+    fn content_type(&self) -> &'static str {
+        "application/cbor"
+    }
+
+    fn render(&self, doc: &Document) -> Result<Vec<u8>, AppError> {
+        let mut out = Vec::new();
+        ciborium::into_writer(&doc.value, &mut out)?;
+        Ok(out)
+    }
+}
+
+#[cfg(feature = "formats")]
+impl Renderer for Msgpack {
+    fn content_type(&self) -> &'static str {
+        "application/msgpack"
+    }
+
+    fn render(&self, doc: &Document) -> Result<Vec<u8>, AppError> {
+        Ok(rmp_serde::to_vec(&doc.value)?)
+    }
+}
+
+#[cfg(feature = "html")]
+impl Renderer for Landing<'_> {
+    fn content_type(&self) -> &'static str {
+        "text/html; charset=utf-8"
+    }
+
+    fn render(&self, doc: &Document) -> Result<Vec<u8>, AppError> {
+        let ip = doc.value.get("ip").and_then(Value::as_str).unwrap_or("");
+        let mut rows = String::new();
+        flatten(&doc.value, "", &mut |key, scalar| {
+            rows.push_str(&format!(
+                "<tr><th>{}</th><td>{}</td></tr>\n",
+                escape_html(key),
+                escape_html(&scalar)
+            ));
+        });
+        // `{{rows}}` last, so placeholders inside header values stay literal.
+        Ok(LANDING_TEMPLATE
+            .replace("{{base}}", &escape_html(self.base))
+            .replace("{{ip}}", &escape_html(ip))
+            .replace("{{rows}}", &rows)
+            .into_bytes())
+    }
+}
+
+impl Renderer for Jsonp<'_> {
+    fn content_type(&self) -> &'static str {
+        "application/javascript"
+    }
+
+    /// The leading empty comment stops the body from ever starting with
+    /// attacker-chosen bytes (the Rosetta Flash class of attacks).
+    fn render(&self, doc: &Document) -> Result<Vec<u8>, AppError> {
+        let mut out = format!("/**/{}(", self.callback).into_bytes();
+        serde_json::to_writer(&mut out, &doc.value)?;
+        out.extend_from_slice(b");");
+        Ok(out)
+    }
+}
+
+/// Flatten a JSON value into `key: value` lines. Nested objects use dotted
+/// keys (`headers.user-agent`), arrays use indices, and nulls are skipped.
+pub fn render_text(value: &Value) -> String {
+    let mut out = String::new();
+    flatten(value, "", &mut |key, scalar| {
+        out.push_str(key);
+        out.push_str(": ");
+        out.push_str(&scalar);
+        out.push('\n');
+    });
+    out
+}
+
+/// A minimal standalone HTML page showing `value` as a table.
+pub fn render_html(title: &str, value: &Value) -> String {
+    let mut rows = String::new();
+    flatten(value, "", &mut |key, scalar| {
+        rows.push_str(&format!(
+            "<tr><th>{}</th><td>{}</td></tr>\n",
+            escape_html(key),
+            escape_html(&scalar)
+        ));
+    });
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>body{{font-family:sans-serif;margin:2em}}th{{text-align:left;padding-right:1em;\
+         vertical-align:top}}td{{font-family:monospace}}</style>\n</head>\n<body>\n<h1>{title}</h1>\n\
+         <table>\n{rows}</table>\n</body>\n</html>\n",
+        title = escape_html(title),
+    )
+}
+
+/// XML document with `ipecho` as the root element. Object keys become child
+/// elements, falling back to `<entry key="...">` for keys that aren't valid
+/// XML names (arbitrary header names); array items become `<item>`.
+pub fn render_xml(value: &Value) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    write_xml_element(&mut out, XML_ROOT, value);
+    out.push('\n');
+    out
+}
+
+fn write_xml_element(out: &mut String, name: &str, value: &Value) {
+    let (open, close) = if is_xml_name(name) {
+        (format!("<{name}"), format!("</{name}>"))
+    } else {
+        (
+            format!("<entry key=\"{}\"", escape_html(name)),
+            "</entry>".to_string(),
+        )
+    };
+    out.push_str(&open);
+    match value {
+        Value::Null => {
+            out.push_str("/>");
+            return;
+        }
+        Value::Object(map) => {
+            out.push('>');
+            for (k, v) in map {
+                write_xml_element(out, k, v);
+            }
+        }
+        Value::Array(items) => {
+            out.push('>');
+            for v in items {
+                write_xml_element(out, "item", v);
+            }
+        }
+        Value::String(s) => {
+            out.push('>');
+            out.push_str(&escape_html(s));
+        }
+        other => {
+            out.push('>');
+            out.push_str(&other.to_string());
+        }
+    }
+    out.push_str(&close);
+}
+
+/// Conservative check for an XML element name: ASCII letter or underscore,
+/// then letters, digits, `-`, `_` or `.`; and not starting with "xml".
+fn is_xml_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        && !name.to_ascii_lowercase().starts_with("xml")
+}
+
+/// A header row of flattened keys and a single row of values.
+pub fn render_csv(value: &Value) -> String {
+    let mut keys = Vec::new();
+    let mut values = Vec::new();
+    flatten(value, "", &mut |key, scalar| {
+        keys.push(csv_field(key));
+        values.push(csv_field(&scalar));
+    });
+    format!("{}\r\n{}\r\n", keys.join(","), values.join(","))
+}
+
+/// Quote a CSV field if needed (RFC 4180).
+fn csv_field(raw: &str) -> String {
+    if raw.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", raw.replace('"', "\"\""))
+    } else {
+        raw.to_string()
+    }
+}
+
+fn flatten(value: &Value, prefix: &str, emit: &mut dyn FnMut(&str, String)) {
+    let key = |k: &str| {
+        if prefix.is_empty() {
+            k.to_string()
+        } else {
+            format!("{prefix}.{k}")
+        }
+    };
+    match value {
+        Value::Null => {}
+        Value::Object(map) => {
+            for (k, v) in map {
+                flatten(v, &key(k), emit);
+            }
+        }
+        Value::Array(items) => {
+            for (i, v) in items.iter().enumerate() {
+                flatten(v, &key(&i.to_string()), emit);
+            }
+        }
+        Value::String(s) => emit(prefix, s.clone()),
+        other => emit(prefix, other.to_string()),
+    }
+}
+
+fn escape_html(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    for c in raw.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(renderer: &dyn Renderer, value: Value, text: Option<&str>) -> String {
+        let doc = Document {
+            title: "t",
+            value,
+            text: text.map(String::from),
+        };
+        String::from_utf8(renderer.render(&doc).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn text_rendering_flattens_nested_values() {
+        let value = json!({"ip": "203.0.113.1", "region": null, "headers": {"accept": "*/*"}, "hops": [{"for": "a"}]});
+        assert_eq!(
+            render_text(&value),
+            "ip: 203.0.113.1\nheaders.accept: */*\nhops.0.for: a\n"
+        );
+    }
+
+    #[test]
+    fn xml_rendering() {
+        let value = json!({
+            "ip": "203.0.113.1",
+            "region": null,
+            "headers": {"user-agent": "<1>", "x&y": "v"},
+            "hops": ["a"],
+        });
+        assert_eq!(
+            render_xml(&value),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<ipecho><ip>203.0.113.1</ip><region/>\
+             <headers><user-agent>&lt;1&gt;</user-agent><entry key=\"x&amp;y\">v</entry></headers>\
+             <hops><item>a</item></hops></ipecho>\n"
+        );
+    }
+
+    #[test]
+    fn csv_rendering_quotes_fields() {
+        let value = json!({"ip": "203.0.113.1", "ua": "a, \"b\""});
+        assert_eq!(
+            render_csv(&value),
+            "ip,ua\r\n203.0.113.1,\"a, \"\"b\"\"\"\r\n"
+        );
+    }
+
+    #[cfg(feature = "formats")]
+    #[test]
+    fn yaml_rendering() {
+        let value = json!({"ip": "203.0.113.1", "headers": {"accept": "*/*"}, "region": null});
+        assert_eq!(
+            render(&Yaml, value, None),
+            "ip: 203.0.113.1\nheaders:\n  accept: '*/*'\nregion: null\n"
+        );
+    }
+
+    #[test]
+    fn text_override_only_applies_to_text() {
+        let value = json!({"ip": "203.0.113.1"});
+        assert_eq!(
+            render(&Text, value.clone(), Some("203.0.113.1")),
+            "203.0.113.1"
+        );
+        assert!(render(&Json, value, Some("203.0.113.1")).contains("\"ip\""));
+    }
+
+    #[cfg(feature = "formats")]
+    #[test]
+    fn binary_formats_round_trip() {
+        let value = json!({"ip": "203.0.113.1", "region": null, "hops": [{"for": "a"}]});
+        let doc = Document {
+            title: "t",
+            value: value.clone(),
+            text: Some("203.0.113.1".into()),
+        };
+
+        let cbor = Cbor.render(&doc).unwrap();
+        let decoded: Value = ciborium::from_reader(cbor.as_slice()).unwrap();
+        assert_eq!(decoded, value);
+
+        let msgpack = Msgpack.render(&doc).unwrap();
+        let decoded: Value = rmp_serde::from_slice(&msgpack).unwrap();
+        assert_eq!(decoded, value);
+    }
+
+    #[test]
+    fn jsonp_rendering() {
+        let jsonp = Jsonp { callback: "cb" };
+        assert_eq!(
+            render(&jsonp, json!({"ip": "203.0.113.1"}), None),
+            "/**/cb({\"ip\":\"203.0.113.1\"});"
+        );
+    }
+
+    #[cfg(feature = "html")]
+    #[test]
+    fn landing_page() {
+        let value = json!({"ip": "203.0.113.1", "headers": {"user-agent": "<b>{{ip}}</b>"}});
+        let html = render(&Landing { base: "/echo" }, value, None);
+        assert!(html.contains("<code id=\"ip\">203.0.113.1</code>"));
+        assert!(html.contains("<a href=\"/echo/ip\">"));
+        assert!(html.contains("<title>203.0.113.1 — ipecho</title>"));
+        assert!(
+            html.contains("<tr><th>headers.user-agent</th><td>&lt;b&gt;{{ip}}&lt;/b&gt;</td></tr>")
+        );
+        assert!(!html.contains("{{rows}}"));
+    }
+
+    #[test]
+    fn html_rendering_escapes() {
+        let html = render_html("t", &json!({"ua": "<script>"}));
+        assert!(html.contains("<td>&lt;script&gt;</td>"));
+        assert!(!html.contains("<script>"));
+    }
+}
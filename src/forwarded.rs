@@ -0,0 +1,160 @@
+//! RFC 7239 `Forwarded` header parsing.
+//!
+//! The header is a comma-separated list of elements, one per proxy hop, each
+//! a semicolon-separated list of `name=value` pairs. Values are tokens or
+//! quoted strings; quoted strings may contain commas, semicolons and
+//! backslash escapes, so a naive `split(',')` is not enough.
+
+use axum::http::HeaderMap;
+use serde::Serialize;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct ForwardedHop {
+    #[serde(rename = "for")]
+    pub for_: Option<String>,
+    pub by: Option<String>,
+    pub proto: Option<String>,
+    pub host: Option<String>,
+}
+
+/// Parse every `Forwarded` header line on the request into hops, in order
+/// from the original client to the closest proxy. Malformed pairs are
+/// skipped rather than failing the whole header.
+pub fn parse_headers(headers: &HeaderMap) -> Vec<ForwardedHop> {
+    headers
+        .get_all("forwarded")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(parse)
+        .collect()
+}
+
+/// Parse a single `Forwarded` header value.
+pub fn parse(raw: &str) -> Vec<ForwardedHop> {
+    split_unquoted(raw, ',')
+        .into_iter()
+        .filter_map(|element| {
+            let mut hop = ForwardedHop::default();
+            let mut any = false;
+            for pair in split_unquoted(element, ';') {
+                let Some((name, value)) = pair.split_once('=') else {
+                    continue;
+                };
+                let value = unquote(value.trim());
+                let slot = match name.trim().to_ascii_lowercase().as_str() {
+                    "for" => &mut hop.for_,
+                    "by" => &mut hop.by,
+                    "proto" => &mut hop.proto,
+                    "host" => &mut hop.host,
+                    _ => continue,
+                };
+                *slot = Some(value);
+                any = true;
+            }
+            any.then_some(hop)
+        })
+        .collect()
+}
+
+/// Split on `sep`, ignoring separators that appear inside quoted strings.
+pub(crate) fn split_unquoted(raw: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (i, c) in raw.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if in_quotes && c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            in_quotes = !in_quotes;
+        } else if c == sep && !in_quotes {
+            parts.push(raw[start..i].trim());
+            start = i + c.len_utf8();
+        }
+    }
+    parts.push(raw[start..].trim());
+    parts.retain(|p| !p.is_empty());
+    parts
+}
+
+/// Strip surrounding quotes and resolve backslash escapes. Tokens are
+/// returned unchanged.
+pub(crate) fn unquote(value: &str) -> String {
+    let Some(inner) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) else {
+        return value.to_string();
+    };
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(next) = chars.next() {
+                out.push(next);
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_single_hop() {
+        let hops = parse("for=192.0.2.60;proto=http;by=203.0.113.43");
+        assert_eq!(
+            hops,
+            vec![ForwardedHop {
+                for_: Some("192.0.2.60".into()),
+                by: Some("203.0.113.43".into()),
+                proto: Some("http".into()),
+                host: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn parses_multiple_hops_and_case_insensitive_names() {
+        let hops = parse("for=192.0.2.43, For=198.51.100.17;HOST=example.com");
+        assert_eq!(hops.len(), 2);
+        assert_eq!(hops[0].for_.as_deref(), Some("192.0.2.43"));
+        assert_eq!(hops[1].for_.as_deref(), Some("198.51.100.17"));
+        assert_eq!(hops[1].host.as_deref(), Some("example.com"));
+    }
+
+    #[test]
+    fn quoted_values_may_contain_separators() {
+        let hops = parse(r#"for="[2001:db8:cafe::17]:4711";host="a,b;c", for=unknown"#);
+        assert_eq!(hops.len(), 2);
+        assert_eq!(hops[0].for_.as_deref(), Some("[2001:db8:cafe::17]:4711"));
+        assert_eq!(hops[0].host.as_deref(), Some("a,b;c"));
+        assert_eq!(hops[1].for_.as_deref(), Some("unknown"));
+    }
+
+    #[test]
+    fn escapes_in_quoted_strings_are_resolved() {
+        let hops = parse(r#"for="_a\"b""#);
+        assert_eq!(hops[0].for_.as_deref(), Some("_a\"b"));
+    }
+
+    #[test]
+    fn malformed_and_unknown_pairs_are_skipped() {
+        let hops = parse("garbage, ext=1, for=192.0.2.1;oops");
+        assert_eq!(hops.len(), 1);
+        assert_eq!(hops[0].for_.as_deref(), Some("192.0.2.1"));
+    }
+
+    #[test]
+    fn multiple_header_lines_are_concatenated() {
+        let mut headers = HeaderMap::new();
+        headers.append("forwarded", "for=192.0.2.1".parse().unwrap());
+        headers.append("forwarded", "for=192.0.2.2".parse().unwrap());
+        let hops = parse_headers(&headers);
+        assert_eq!(hops.len(), 2);
+        assert_eq!(hops[1].for_.as_deref(), Some("192.0.2.2"));
+    }
+}
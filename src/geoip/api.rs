@@ -0,0 +1,341 @@
+//! Remote lookup APIs: ipinfo.io and ipapi.co.
+//!
+//! Each lookup is an HTTPS request bounded by a short timeout, so answers are
+//! cached in-process for an hour, like reverse DNS. Private and other
+//! non-routable addresses are never sent. Failures (timeouts, rate limiting,
+//! error responses) aren't cached, so the next request tries again.
+
+use std::future::Future;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use super::{AsnInfo, Enrichment, GeoInfo, IpEnricher, record};
+use crate::config::{Config, GeoIpBackend};
+use crate::ttl_map::TtlMap;
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+const CACHE_TTL: Duration = Duration::from_secs(3600);
+const CACHE_CAPACITY: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Api {
+    IpInfo,
+    IpApi,
+}
+
+impl Api {
+    fn name(self) -> &'static str {
+        match self {
+            Self::IpInfo => "ipinfo",
+            Self::IpApi => "ipapi",
+        }
+    }
+
+    fn base_url(self) -> &'static str {
+        match self {
+            Self::IpInfo => "https://ipinfo.io",
+            Self::IpApi => "https://ipapi.co",
+        }
+    }
+}
+
+pub struct HttpApi {
+    api: Api,
+    client: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+    cache: TtlMap<IpAddr, Enrichment>,
+}
+
+impl HttpApi {
+    pub fn from_config(config: &Config) -> Self {
+        let api = match config.geoip_backend {
+            GeoIpBackend::IpApi => Api::IpApi,
+            _ => Api::IpInfo,
+        };
+        Self::new(api, api.base_url(), config.geoip_api_token.clone())
+    }
+
+    pub fn new(api: Api, base_url: &str, token: Option<String>) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(TIMEOUT)
+            .build()
+            .unwrap_or_else(|_| reqwest::Client::new());
+        Self {
+            api,
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token,
+            cache: TtlMap::new(CACHE_TTL, CACHE_CAPACITY),
+        }
+    }
+
+    pub async fn lookup(&self, ip: IpAddr) -> Enrichment {
+        let name = self.api.name();
+        if !is_routable(ip) {
+            return Enrichment::default();
+        }
+        if let Some(enrichment) = self.cache.get(&ip) {
+            record(name, "cache_hit");
+            return enrichment;
+        }
+
+        match self.fetch(ip).await {
+            Ok(enrichment) => {
+                let found = enrichment != Enrichment::default();
+                record(name, if found { "hit" } else { "miss" });
+                self.cache.insert(ip, enrichment.clone());
+                enrichment
+            }
+            Err(e) => {
+                tracing::debug!(%ip, api = name, error = %e, "IP lookup API request failed");
+                record(name, "error");
+                Enrichment::default()
+            }
+        }
+    }
+
+    async fn fetch(&self, ip: IpAddr) -> anyhow::Result<Enrichment> {
+        match self.api {
+            Api::IpInfo => {
+                let mut request = self.client.get(format!("{}/{ip}/json", self.base_url));
+                if let Some(token) = &self.token {
+                    request = request.bearer_auth(token);
+                }
+                let body: IpInfoResponse = request.send().await?.error_for_status()?.json().await?;
+                Ok(body.into())
+            }
+            Api::IpApi => {
+                let mut request = self.client.get(format!("{}/{ip}/json/", self.base_url));
+                if let Some(token) = &self.token {
+                    request = request.query(&[("key", token)]);
+                }
+                let body: IpApiResponse = request.send().await?.error_for_status()?.json().await?;
+                if body.error {
+                    anyhow::bail!(body.reason.unwrap_or_else(|| "error response".into()));
+                }
+                Ok(body.into())
+            }
+        }
+    }
+}
+
+impl IpEnricher for HttpApi {
+    fn name(&self) -> &'static str {
+        self.api.name()
+    }
+
+    fn enrich<'a>(&'a self, ip: IpAddr) -> Pin<Box<dyn Future<Output = Enrichment> + Send + 'a>> {
+        Box::pin(self.lookup(ip))
+    }
+}
+
+/// Whether an API could know anything about `ip`: not private, loopback,
+/// link-local, CGNAT or unspecified.
+fn is_routable(ip: IpAddr) -> bool {
+    match ip.to_canonical() {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_private()
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(v6) => {
+            !(v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_unique_local()
+                || v6.is_unicast_link_local())
+        }
+    }
+}
+
+fn non_empty(s: Option<String>) -> Option<String> {
+    s.filter(|s| !s.trim().is_empty())
+}
+
+/// `AS15169 Google LLC` (ipinfo's `org`) or a bare `AS15169`.
+fn parse_as(raw: &str) -> Option<(u32, Option<String>)> {
+    let (number, organization) = raw.split_once(' ').unwrap_or((raw, ""));
+    let number = number.strip_prefix("AS")?.parse().ok()?;
+    Some((number, non_empty(Some(organization.trim().to_string()))))
+}
+
+/// <https://ipinfo.io/developers/responses>
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct IpInfoResponse {
+    city: Option<String>,
+    region: Option<String>,
+    country: Option<String>,
+    /// `"37.4056,-122.0775"`.
+    loc: Option<String>,
+    /// `"AS15169 Google LLC"`.
+    org: Option<String>,
+    timezone: Option<String>,
+}
+
+impl From<IpInfoResponse> for Enrichment {
+    fn from(r: IpInfoResponse) -> Self {
+        let (latitude, longitude) = r
+            .loc
+            .as_deref()
+            .and_then(|loc| loc.split_once(','))
+            .and_then(|(lat, lon)| Some((lat.trim().parse().ok()?, lon.trim().parse().ok()?)))
+            .unzip();
+        let geo = GeoInfo {
+            country_code: non_empty(r.country),
+            country: None,
+            region_code: None,
+            region: non_empty(r.region),
+            city: non_empty(r.city),
+            latitude,
+            longitude,
+            timezone: non_empty(r.timezone),
+        };
+        Self {
+            geo: (!geo.is_empty()).then_some(geo),
+            asn: r
+                .org
+                .as_deref()
+                .and_then(parse_as)
+                .map(|(number, organization)| AsnInfo {
+                    number,
+                    organization,
+                    prefix: None,
+                }),
+        }
+    }
+}
+
+/// <https://ipapi.co/api/#complete-location>
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct IpApiResponse {
+    city: Option<String>,
+    region: Option<String>,
+    region_code: Option<String>,
+    country_code: Option<String>,
+    country_name: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    timezone: Option<String>,
+    /// `"AS15169"`.
+    asn: Option<String>,
+    org: Option<String>,
+    error: bool,
+    reason: Option<String>,
+}
+
+impl From<IpApiResponse> for Enrichment {
+    fn from(r: IpApiResponse) -> Self {
+        let geo = GeoInfo {
+            country_code: non_empty(r.country_code),
+            country: non_empty(r.country_name),
+            region_code: non_empty(r.region_code),
+            region: non_empty(r.region),
+            city: non_empty(r.city),
+            latitude: r.latitude,
+            longitude: r.longitude,
+            timezone: non_empty(r.timezone),
+        };
+        Self {
+            geo: (!geo.is_empty()).then_some(geo),
+            asn: r
+                .asn
+                .as_deref()
+                .and_then(parse_as)
+                .map(|(number, _)| AsnInfo {
+                    number,
+                    organization: non_empty(r.org),
+                    prefix: None,
+                }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ipinfo_response() {
+        let body: IpInfoResponse = serde_json::from_str(
+            r#"{"ip":"8.8.8.8","hostname":"dns.google","city":"Mountain View","region":"California",
+                "country":"US","loc":"37.4056,-122.0775","org":"AS15169 Google LLC",
+                "postal":"94043","timezone":"America/Los_Angeles"}"#,
+        )
+        .unwrap();
+        let enrichment = Enrichment::from(body);
+        let geo = enrichment.geo.unwrap();
+        assert_eq!(geo.country_code.as_deref(), Some("US"));
+        assert_eq!(geo.city.as_deref(), Some("Mountain View"));
+        assert_eq!(geo.latitude, Some(37.4056));
+        assert_eq!(geo.longitude, Some(-122.0775));
+        assert_eq!(
+            enrichment.asn,
+            Some(AsnInfo {
+                number: 15169,
+                organization: Some("Google LLC".into()),
+                prefix: None,
+            })
+        );
+    }
+
+    #[test]
+    fn ipinfo_bogon_is_empty() {
+        let body: IpInfoResponse =
+            serde_json::from_str(r#"{"ip":"10.0.0.1","bogon":true}"#).unwrap();
+        assert_eq!(Enrichment::from(body), Enrichment::default());
+    }
+
+    #[test]
+    fn ipapi_response() {
+        let body: IpApiResponse = serde_json::from_str(
+            r#"{"ip":"8.8.8.8","city":"Mountain View","region":"California","region_code":"CA",
+                "country_code":"US","country_name":"United States","latitude":37.42301,
+                "longitude":-122.083352,"timezone":"America/Los_Angeles","asn":"AS15169","org":"GOOGLE"}"#,
+        )
+        .unwrap();
+        let enrichment = Enrichment::from(body);
+        let geo = enrichment.geo.unwrap();
+        assert_eq!(geo.region_code.as_deref(), Some("CA"));
+        assert_eq!(geo.country.as_deref(), Some("United States"));
+        let asn = enrichment.asn.unwrap();
+        assert_eq!(asn.number, 15169);
+        assert_eq!(asn.organization.as_deref(), Some("GOOGLE"));
+    }
+
+    #[test]
+    fn as_strings() {
+        assert_eq!(parse_as("AS64500"), Some((64500, None)));
+        assert_eq!(
+            parse_as("AS64500 Example Net"),
+            Some((64500, Some("Example Net".into())))
+        );
+        assert_eq!(parse_as("Example Net"), None);
+    }
+
+    #[test]
+    fn private_addresses_are_not_routable() {
+        for ip in [
+            "10.1.2.3",
+            "127.0.0.1",
+            "169.254.1.1",
+            "100.64.0.1",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+        ] {
+            assert!(!is_routable(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in ["8.8.8.8", "100.128.0.1", "2001:4860::8888"] {
+            assert!(is_routable(ip.parse().unwrap()), "{ip}");
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! IP2Location databases in CSV form: the free LITE downloads or the
+//! commercial DB1 through DB11, IPv4 or IPv6 editions.
+//!
+//! Rows are `"ip_from","ip_to","country_code","country_name",...` with
+//! addresses as decimal integers; larger layouts add region, city,
+//! latitude/longitude, zip code and a UTC-offset time zone, in that order.
+//! `-` marks an unknown value. The file is loaded into a sorted range table
+//! at startup, with locations shared by many ranges stored once. These
+//! layouts carry no AS data, so `asn` stays `null`.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::future::Future;
+use std::io::{BufRead, BufReader};
+use std::net::IpAddr;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use anyhow::Context;
+
+use super::{Enrichment, GeoInfo, IpEnricher, record};
+
+/// `::ffff:0.0.0.0`. IPv4 ranges are stored in IPv4-mapped form, as the
+/// IPv6 editions already do, so one table serves both families.
+const IPV4_MAPPED_BASE: u128 = 0xffff_0000_0000;
+
+struct Range {
+    from: u128,
+    to: u128,
+    geo: Arc<GeoInfo>,
+}
+
+pub struct Ip2Location {
+    /// Sorted by `from`, non-overlapping.
+    ranges: Vec<Range>,
+}
+
+impl Ip2Location {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("failed to read IP2Location database {}", path.display()))?;
+        let db = Self::from_reader(BufReader::new(file))
+            .with_context(|| format!("invalid IP2Location database {}", path.display()))?;
+        tracing::info!(path = %path.display(), ranges = db.ranges.len(), "IP2Location database loaded");
+        Ok(db)
+    }
+
+    pub fn from_reader(reader: impl BufRead) -> anyhow::Result<Self> {
+        let mut locations: HashMap<Vec<String>, Arc<GeoInfo>> = HashMap::new();
+        let mut ranges = Vec::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut fields = split_csv(&line);
+            let bound = |field: Option<&String>| field.and_then(|f| f.parse::<u128>().ok());
+            let (Some(mut from), Some(mut to)) = (bound(fields.first()), bound(fields.get(1)))
+            else {
+                anyhow::bail!("line {}: expected ip_from and ip_to", i + 1);
+            };
+            if fields.len() < 4 {
+                anyhow::bail!("line {}: expected at least a country", i + 1);
+            }
+            if to <= u128::from(u32::MAX) {
+                from += IPV4_MAPPED_BASE;
+                to += IPV4_MAPPED_BASE;
+            }
+            let geo = locations
+                .entry(fields.split_off(2))
+                .or_insert_with_key(|f| Arc::new(geo_info(f)))
+                .clone();
+            ranges.push(Range { from, to, geo });
+        }
+        ranges.sort_by_key(|r| r.from);
+        Ok(Self { ranges })
+    }
+
+    pub fn lookup(&self, ip: IpAddr) -> Option<GeoInfo> {
+        let n = match ip {
+            IpAddr::V4(v4) => u128::from(v4.to_ipv6_mapped()),
+            IpAddr::V6(v6) => u128::from(v6),
+        };
+        let idx = self.ranges.partition_point(|r| r.from <= n);
+        let geo = idx
+            .checked_sub(1)
+            .map(|i| &self.ranges[i])
+            .filter(|r| n <= r.to && !r.geo.is_empty())
+            .map(|r| GeoInfo::clone(&r.geo));
+        record("ip2location", if geo.is_some() { "hit" } else { "miss" });
+        geo
+    }
+}
+
+impl IpEnricher for Ip2Location {
+    fn name(&self) -> &'static str {
+        "ip2location"
+    }
+
+    fn enrich<'a>(&'a self, ip: IpAddr) -> Pin<Box<dyn Future<Output = Enrichment> + Send + 'a>> {
+        Box::pin(async move {
+            Enrichment {
+                geo: self.lookup(ip),
+                asn: None,
+            }
+        })
+    }
+}
+
+/// Location columns, starting at `country_code`. Rows without a country
+/// (reserved ranges) carry placeholder `0.0` coordinates; they're empty.
+fn geo_info(fields: &[String]) -> GeoInfo {
+    let text = |i: usize| {
+        fields
+            .get(i)
+            .map(|f| f.trim())
+            .filter(|f| !f.is_empty() && *f != "-")
+            .map(str::to_string)
+    };
+    let number = |i: usize| text(i).and_then(|f| f.parse::<f64>().ok());
+    if text(0).is_none() {
+        return GeoInfo::default();
+    }
+    GeoInfo {
+        country_code: text(0),
+        country: text(1),
+        region_code: None,
+        region: text(2),
+        city: text(3),
+        latitude: number(4),
+        longitude: number(5),
+        // Index 6 is the zip code.
+        timezone: text(7),
+    }
+}
+
+/// Split one CSV line, honouring quotes (`"Korea, Republic of"`) and
+/// doubled quotes inside them.
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DB11_V4: &str = r#""0","16777215","-","-","-","-","0.000000","0.000000","-","-"
+"3405774848","3405775103","AU","Australia","Queensland","Brisbane","-27.467940","153.028090","4000","+10:00"
+"2948595712","2948595967","KR","Korea (Republic of)","Seoul","Seoul","37.566000","126.978400","04524","+09:00"
+"#;
+
+    #[test]
+    fn ipv4_lookup() {
+        let db = Ip2Location::from_reader(DB11_V4.as_bytes()).unwrap();
+        let geo = db.lookup("203.0.113.7".parse().unwrap());
+        assert_eq!(geo, None);
+
+        // 3405774848 is 203.0.0.0.
+        let geo = db.lookup("203.0.0.200".parse().unwrap()).unwrap();
+        assert_eq!(
+            geo,
+            GeoInfo {
+                country_code: Some("AU".into()),
+                country: Some("Australia".into()),
+                region_code: None,
+                region: Some("Queensland".into()),
+                city: Some("Brisbane".into()),
+                latitude: Some(-27.46794),
+                longitude: Some(153.02809),
+                timezone: Some("+10:00".into()),
+            }
+        );
+        assert_eq!(
+            db.lookup("175.192.0.1".parse().unwrap())
+                .unwrap()
+                .city
+                .as_deref(),
+            Some("Seoul")
+        );
+    }
+
+    #[test]
+    fn unknown_rows_are_misses() {
+        let db = Ip2Location::from_reader(DB11_V4.as_bytes()).unwrap();
+        assert_eq!(db.lookup("0.0.0.1".parse().unwrap()), None);
+        assert_eq!(db.lookup("2001:db8::1".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn ipv6_edition_with_mapped_ipv4_and_short_layout() {
+        // DB1 layout: country only. IPv4 rows are already IPv4-mapped.
+        let csv = "\"281470698520576\",\"281470698520831\",\"AU\",\"Australia\"\n\
+                   \"42540766411282592856903984951653826560\",\"42540766490510755371168322545197776895\",\"NL\",\"Netherlands\"\n";
+        let db = Ip2Location::from_reader(csv.as_bytes()).unwrap();
+        let au = db.lookup("1.0.0.1".parse().unwrap()).unwrap();
+        assert_eq!(au.country_code.as_deref(), Some("AU"));
+        assert_eq!(au.city, None);
+        let nl = db.lookup("2001:db8::1".parse().unwrap()).unwrap();
+        assert_eq!(nl.country.as_deref(), Some("Netherlands"));
+    }
+
+    #[test]
+    fn rejects_malformed_rows() {
+        assert!(
+            Ip2Location::from_reader("\"a\",\"b\",\"US\",\"United States\"".as_bytes()).is_err()
+        );
+        assert!(Ip2Location::from_reader("\"1\",\"2\"".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn csv_quoting() {
+        assert_eq!(
+            split_csv(r#""1","Korea, Republic of","say ""hi""",-"#),
+            ["1", "Korea, Republic of", "say \"hi\"", "-"]
+        );
+    }
+}
@@ -0,0 +1,316 @@
+//! MaxMind databases (`.mmdb` format): a City database (GeoLite2-City or
+//! GeoIP2-City) for location and an ASN database (GeoLite2-ASN) for the
+//! announcing network.
+//!
+//! Each database is read fully into memory at startup from `GEOIP_CITY_DB`
+//! and `GEOIP_ASN_DB`. With a MaxMind license key, [`super::update`] keeps
+//! them current by swapping in new readers.
+
+use std::future::Future;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+
+use anyhow::Context;
+use maxminddb::{LookupResult, Reader, geoip2};
+use tokio::sync::RwLock;
+
+use super::{AsnInfo, Enrichment, GeoInfo, IpEnricher, record};
+
+pub struct MaxMind {
+    pub(super) city_path: Option<PathBuf>,
+    pub(super) asn_path: Option<PathBuf>,
+    pub(super) city: RwLock<Option<GeoIp>>,
+    pub(super) asn: RwLock<Option<AsnDb>>,
+}
+
+impl MaxMind {
+    /// In-memory databases, without files to refresh.
+    pub fn new(city: Option<GeoIp>, asn: Option<AsnDb>) -> Self {
+        Self {
+            city_path: None,
+            asn_path: None,
+            city: RwLock::new(city),
+            asn: RwLock::new(asn),
+        }
+    }
+
+    /// Load the databases at `city_path` and `asn_path`. With `downloads`,
+    /// missing files are left for the updater to fetch.
+    pub fn open(
+        city_path: Option<PathBuf>,
+        asn_path: Option<PathBuf>,
+        downloads: bool,
+    ) -> anyhow::Result<Self> {
+        let city = open_optional(city_path.as_deref(), downloads, GeoIp::open)?;
+        let asn = open_optional(asn_path.as_deref(), downloads, AsnDb::open)?;
+        Ok(Self {
+            city_path,
+            asn_path,
+            ..Self::new(city, asn)
+        })
+    }
+}
+
+impl IpEnricher for MaxMind {
+    fn name(&self) -> &'static str {
+        "maxmind"
+    }
+
+    fn enrich<'a>(&'a self, ip: IpAddr) -> Pin<Box<dyn Future<Output = Enrichment> + Send + 'a>> {
+        Box::pin(async move {
+            Enrichment {
+                geo: self.city.read().await.as_ref().and_then(|db| db.lookup(ip)),
+                asn: self.asn.read().await.as_ref().and_then(|db| db.lookup(ip)),
+            }
+        })
+    }
+
+    /// Every configured database is loaded. False while a missing file is
+    /// still waiting for its first download.
+    fn is_ready(&self) -> bool {
+        // A held write lock means an update is being swapped in, so a
+        // database is (still) there.
+        let city = self.city.try_read().map(|db| db.is_some()).unwrap_or(true);
+        let asn = self.asn.try_read().map(|db| db.is_some()).unwrap_or(true);
+        (self.city_path.is_none() || city) && (self.asn_path.is_none() || asn)
+    }
+}
+
+pub struct GeoIp {
+    reader: Reader<Vec<u8>>,
+}
+
+impl GeoIp {
+    /// Load the City database at `path` into memory.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        load(path, Self::from_bytes)
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> anyhow::Result<Self> {
+        let reader = Reader::from_source(bytes)?;
+        Ok(Self { reader })
+    }
+
+    /// Look up `ip`. `None` if the database has no (or only empty) data for
+    /// it; decode errors are logged and treated the same way.
+    pub fn lookup(&self, ip: IpAddr) -> Option<GeoInfo> {
+        let (city, _) = lookup::<geoip2::City>(&self.reader, "city", ip)?;
+
+        let subdivision = city.subdivisions.first();
+        let info = GeoInfo {
+            country_code: city.country.iso_code.map(str::to_string),
+            country: city.country.names.english.map(str::to_string),
+            region_code: subdivision.and_then(|s| s.iso_code).map(str::to_string),
+            region: subdivision
+                .and_then(|s| s.names.english)
+                .map(str::to_string),
+            city: city.city.names.english.map(str::to_string),
+            latitude: city.location.latitude,
+            longitude: city.location.longitude,
+            timezone: city.location.time_zone.map(str::to_string),
+        };
+        record("city", if info.is_empty() { "miss" } else { "hit" });
+        (!info.is_empty()).then_some(info)
+    }
+}
+
+pub struct AsnDb {
+    reader: Reader<Vec<u8>>,
+}
+
+impl AsnDb {
+    /// Load the ASN database at `path` into memory.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        load(path, Self::from_bytes)
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> anyhow::Result<Self> {
+        let reader = Reader::from_source(bytes)?;
+        Ok(Self { reader })
+    }
+
+    /// Look up `ip`. `None` if the database has no AS number for it.
+    pub fn lookup(&self, ip: IpAddr) -> Option<AsnInfo> {
+        let (asn, result) = lookup::<geoip2::Asn>(&self.reader, "asn", ip)?;
+        let Some(number) = asn.autonomous_system_number else {
+            record("asn", "miss");
+            return None;
+        };
+        record("asn", "hit");
+        Some(AsnInfo {
+            number,
+            organization: asn.autonomous_system_organization.map(str::to_string),
+            prefix: result.network().ok().map(|net| net.to_string()),
+        })
+    }
+}
+
+fn load<T>(path: &Path, parse: fn(Vec<u8>) -> anyhow::Result<T>) -> anyhow::Result<T> {
+    let bytes = std::fs::read(path)
+        .with_context(|| format!("failed to read GeoIP database {}", path.display()))?;
+    parse(bytes).with_context(|| format!("invalid GeoIP database {}", path.display()))
+}
+
+/// Look up and decode the record for `ip`, counting misses and errors.
+/// Hits are counted by the caller, which may still find the record empty.
+fn lookup<'a, T: serde::Deserialize<'a>>(
+    reader: &'a Reader<Vec<u8>>,
+    database: &'static str,
+    ip: IpAddr,
+) -> Option<(T, LookupResult<'a, Vec<u8>>)> {
+    match reader
+        .lookup(ip)
+        .and_then(|result| Ok((result.decode::<T>()?, result)))
+    {
+        Ok((Some(record), result)) => Some((record, result)),
+        Ok((None, _)) => {
+            self::record(database, "miss");
+            None
+        }
+        Err(e) => {
+            tracing::debug!(%ip, database, error = %e, "GeoIP lookup failed");
+            self::record(database, "error");
+            None
+        }
+    }
+}
+
+fn open_optional<T>(
+    path: Option<&Path>,
+    downloads: bool,
+    open: fn(&Path) -> anyhow::Result<T>,
+) -> anyhow::Result<Option<T>> {
+    match path {
+        Some(path) if downloads && !path.exists() => Ok(None),
+        Some(path) => {
+            let db = open(path)?;
+            tracing::info!(path = %path.display(), "GeoIP database loaded");
+            Ok(Some(db))
+        }
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mmdb_writer::{Value, Writer};
+
+    fn test_db() -> GeoIp {
+        let names = |en: &str| Value::map([("en", Value::from(en))]);
+        let mut writer = Writer::new("GeoIP2-City");
+        writer
+            .insert_value(
+                "203.0.113.0/24".parse::<ipnet::IpNet>().unwrap(),
+                Value::map([
+                    (
+                        "country",
+                        Value::map([("iso_code", Value::from("DE")), ("names", names("Germany"))]),
+                    ),
+                    (
+                        "subdivisions",
+                        Value::array([Value::map([
+                            ("iso_code", Value::from("BE")),
+                            ("names", names("Berlin")),
+                        ])]),
+                    ),
+                    ("city", Value::map([("names", names("Berlin"))])),
+                    (
+                        "location",
+                        Value::map([
+                            ("latitude", Value::from(52.52_f64)),
+                            ("longitude", Value::from(13.405_f64)),
+                            ("time_zone", Value::from("Europe/Berlin")),
+                        ]),
+                    ),
+                ]),
+            )
+            .unwrap();
+        writer
+            .insert_value(
+                "2001:db8::/32".parse::<ipnet::IpNet>().unwrap(),
+                Value::map([("country", Value::map([("iso_code", Value::from("NL"))]))]),
+            )
+            .unwrap();
+        GeoIp::from_bytes(writer.to_bytes().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn full_record() {
+        let info = test_db().lookup("203.0.113.7".parse().unwrap()).unwrap();
+        assert_eq!(
+            info,
+            GeoInfo {
+                country_code: Some("DE".into()),
+                country: Some("Germany".into()),
+                region_code: Some("BE".into()),
+                region: Some("Berlin".into()),
+                city: Some("Berlin".into()),
+                latitude: Some(52.52),
+                longitude: Some(13.405),
+                timezone: Some("Europe/Berlin".into()),
+            }
+        );
+    }
+
+    #[test]
+    fn partial_record() {
+        let info = test_db().lookup("2001:db8::1".parse().unwrap()).unwrap();
+        assert_eq!(info.country_code.as_deref(), Some("NL"));
+        assert_eq!(info.city, None);
+    }
+
+    #[test]
+    fn unknown_address() {
+        assert_eq!(test_db().lookup("198.51.100.1".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(GeoIp::from_bytes(b"not a database".to_vec()).is_err());
+        assert!(AsnDb::from_bytes(b"not a database".to_vec()).is_err());
+    }
+
+    fn test_asn_db() -> AsnDb {
+        let mut writer = Writer::new("GeoLite2-ASN");
+        writer
+            .insert_value(
+                "198.51.100.0/22".parse::<ipnet::IpNet>().unwrap(),
+                Value::map([
+                    ("autonomous_system_number", Value::from(64500_u32)),
+                    ("autonomous_system_organization", Value::from("Example Net")),
+                ]),
+            )
+            .unwrap();
+        writer
+            .insert_value(
+                "2001:db8::/32".parse::<ipnet::IpNet>().unwrap(),
+                Value::map([("autonomous_system_organization", Value::from("No Number"))]),
+            )
+            .unwrap();
+        AsnDb::from_bytes(writer.to_bytes().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn asn_record_with_prefix() {
+        let info = test_asn_db()
+            .lookup("198.51.101.9".parse().unwrap())
+            .unwrap();
+        assert_eq!(
+            info,
+            AsnInfo {
+                number: 64500,
+                organization: Some("Example Net".into()),
+                prefix: Some("198.51.100.0/22".into()),
+            }
+        );
+    }
+
+    #[test]
+    fn asn_requires_a_number() {
+        let db = test_asn_db();
+        assert_eq!(db.lookup("2001:db8::1".parse().unwrap()), None);
+        assert_eq!(db.lookup("203.0.113.1".parse().unwrap()), None);
+    }
+}
@@ -0,0 +1,193 @@
+//! Geo and network enrichment of the client address: location, and the
+//! autonomous system announcing it.
+//!
+//! The data source is pluggable via `GEOIP_BACKEND` — local MaxMind or
+//! IP2Location databases, or a remote HTTP API — behind [`IpEnricher`].
+//! Without one configured, the geo fields are simply `null` and their
+//! endpoints return 204. Names are reported in English. The backends need
+//! the `geoip` feature; without it, configuring one is a startup error.
+
+#[cfg(feature = "geoip")]
+pub mod api;
+#[cfg(feature = "geoip")]
+pub mod ip2location;
+#[cfg(feature = "geoip")]
+pub mod maxmind;
+#[cfg(feature = "geoip")]
+pub mod update;
+
+use std::future::Future;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+#[cfg(feature = "geoip")]
+use std::time::Duration;
+
+#[cfg(feature = "geoip")]
+use anyhow::Context;
+use arc_swap::ArcSwapOption;
+use serde::Serialize;
+use tokio::task::JoinHandle;
+
+use crate::config::{Config, GeoIpBackend};
+#[cfg(feature = "geoip")]
+use api::HttpApi;
+#[cfg(feature = "geoip")]
+use ip2location::Ip2Location;
+#[cfg(feature = "geoip")]
+use maxmind::MaxMind;
+#[cfg(feature = "geoip")]
+use update::Updater;
+
+/// Location of an IP as reported by the backend. Every field is optional:
+/// coverage varies a lot between addresses and data sources.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct GeoInfo {
+    /// ISO 3166-1 alpha-2 code, e.g. `DE`.
+    pub country_code: Option<String>,
+    pub country: Option<String>,
+    /// ISO 3166-2 subdivision code (without the country prefix), e.g. `BE`.
+    pub region_code: Option<String>,
+    pub region: Option<String>,
+    pub city: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// IANA time zone, e.g. `Europe/Berlin` (a UTC offset such as `+01:00`
+    /// with IP2Location).
+    pub timezone: Option<String>,
+}
+
+#[cfg(feature = "geoip")]
+impl GeoInfo {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Autonomous system announcing an IP.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AsnInfo {
+    pub number: u32,
+    /// Registered organization, usually the ISP or hosting company.
+    pub organization: Option<String>,
+    /// The network the database record covers, e.g. `203.0.113.0/24`.
+    pub prefix: Option<String>,
+}
+
+/// Everything a backend knows about an address.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Enrichment {
+    pub geo: Option<GeoInfo>,
+    pub asn: Option<AsnInfo>,
+}
+
+/// A source of [`Enrichment`]. Lookups never fail: a backend that can't
+/// answer (unknown address, decode error, API timeout) returns empty fields.
+pub trait IpEnricher: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn enrich<'a>(&'a self, ip: IpAddr) -> Pin<Box<dyn Future<Output = Enrichment> + Send + 'a>>;
+
+    /// Whether the backend's data is loaded, for `/readyz`. Remote APIs are
+    /// always ready.
+    fn is_ready(&self) -> bool {
+        true
+    }
+}
+
+/// The backend selected by `GEOIP_BACKEND`.
+pub struct Backend {
+    pub enricher: Arc<dyn IpEnricher>,
+    /// With a MaxMind license key, keeps its databases current.
+    #[cfg(feature = "geoip")]
+    pub updater: Option<Updater>,
+}
+
+impl Backend {
+    /// Bring downloadable databases up to date, then keep refreshing them
+    /// every `GEOIP_REFRESH_SECS` in the background. Returns the enricher
+    /// and the refresh task, if any.
+    #[cfg(feature = "geoip")]
+    pub async fn start(self, config: &Config) -> (Arc<dyn IpEnricher>, Option<JoinHandle<()>>) {
+        let task = match self.updater {
+            Some(updater) => {
+                updater.refresh().await;
+                let every = Duration::from_secs(config.geoip_refresh_secs);
+                Some(tokio::spawn(updater.run(every)))
+            }
+            None => None,
+        };
+        (self.enricher, task)
+    }
+
+    /// Without the `geoip` feature there are no databases to refresh.
+    #[cfg(not(feature = "geoip"))]
+    pub async fn start(self, _config: &Config) -> (Arc<dyn IpEnricher>, Option<JoinHandle<()>>) {
+        (self.enricher, None)
+    }
+}
+
+/// The backend in use, replaced when a reload changes the GeoIP settings.
+#[derive(Default)]
+pub struct SharedEnricher(ArcSwapOption<Arc<dyn IpEnricher>>);
+
+impl SharedEnricher {
+    pub fn new(enricher: Option<Arc<dyn IpEnricher>>) -> Self {
+        Self(ArcSwapOption::new(enricher.map(Arc::new)))
+    }
+
+    pub fn get(&self) -> Option<Arc<dyn IpEnricher>> {
+        self.0.load().as_deref().cloned()
+    }
+
+    pub fn set(&self, enricher: Option<Arc<dyn IpEnricher>>) {
+        self.0.store(enricher.map(Arc::new));
+    }
+}
+
+/// Set up the configured backend; `None` for MaxMind without any database.
+#[cfg(feature = "geoip")]
+pub fn from_config(config: &Config) -> anyhow::Result<Option<Backend>> {
+    let (enricher, updater): (Arc<dyn IpEnricher>, _) = match config.geoip_backend {
+        GeoIpBackend::MaxMind => {
+            if config.geoip_city_db.is_none() && config.geoip_asn_db.is_none() {
+                return Ok(None);
+            }
+            let db = Arc::new(MaxMind::open(
+                config.geoip_city_db.clone(),
+                config.geoip_asn_db.clone(),
+                config.geoip_license_key.is_some(),
+            )?);
+            let updater = Updater::from_config(config, db.clone());
+            (db, updater)
+        }
+        GeoIpBackend::Ip2Location => {
+            let path = config
+                .geoip_ip2location_db
+                .as_deref()
+                .context("GEOIP_IP2LOCATION_DB is not set")?;
+            (Arc::new(Ip2Location::open(path)?), None)
+        }
+        GeoIpBackend::IpInfo | GeoIpBackend::IpApi => {
+            (Arc::new(HttpApi::from_config(config)), None)
+        }
+    };
+    Ok(Some(Backend { enricher, updater }))
+}
+
+/// Built without the `geoip` feature there is no backend to set up, so
+/// configuring one is an error rather than silently ignored.
+#[cfg(not(feature = "geoip"))]
+pub fn from_config(config: &Config) -> anyhow::Result<Option<Backend>> {
+    let configured = match config.geoip_backend {
+        GeoIpBackend::MaxMind => config.geoip_city_db.is_some() || config.geoip_asn_db.is_some(),
+        _ => true,
+    };
+    anyhow::ensure!(!configured, "GeoIP needs a build with the geoip feature");
+    Ok(None)
+}
+
+#[cfg(feature = "geoip")]
+fn record(database: &'static str, result: &'static str) {
+    metrics::counter!("geoip_lookup_total", "database" => database, "result" => result)
+        .increment(1);
+}
@@ -0,0 +1,270 @@
+//! Automatic download and refresh of the MaxMind databases.
+//!
+//! With `GEOIP_LICENSE_KEY` set, each configured database is fetched from
+//! MaxMind's download service at startup and then every
+//! `GEOIP_REFRESH_SECS`. The edition is taken from the file name, so
+//! `GEOIP_CITY_DB=/var/lib/ipecho/GeoLite2-City.mmdb` downloads
+//! `GeoLite2-City`. Requests are conditional on the file's modification
+//! time, so an unchanged database only costs a 304.
+//!
+//! A download is validated before it replaces the file on disk and the
+//! reader in memory; on any failure the current database stays in service.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context;
+use axum::body::Bytes;
+use reqwest::StatusCode;
+use reqwest::header::IF_MODIFIED_SINCE;
+use tokio::time::MissedTickBehavior;
+
+use super::maxmind::{AsnDb, GeoIp, MaxMind};
+use crate::config::Config;
+
+const DOWNLOAD_URL: &str = "https://download.maxmind.com";
+
+pub struct Updater {
+    client: reqwest::Client,
+    base_url: String,
+    account_id: String,
+    license_key: String,
+    db: Arc<MaxMind>,
+}
+
+impl Updater {
+    /// `None` unless a license key (and account ID) is configured.
+    pub fn from_config(config: &Config, db: Arc<MaxMind>) -> Option<Self> {
+        Some(Self::new(
+            DOWNLOAD_URL,
+            config.geoip_account_id.clone()?,
+            config.geoip_license_key.clone()?,
+            db,
+        ))
+    }
+
+    pub fn new(base_url: &str, account_id: String, license_key: String, db: Arc<MaxMind>) -> Self {
+        // Full databases are tens of megabytes; allow for slow links.
+        let client = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(10))
+            .timeout(Duration::from_secs(300))
+            .build()
+            .unwrap_or_else(|_| reqwest::Client::new());
+        Self {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            account_id,
+            license_key,
+            db,
+        }
+    }
+
+    /// Refresh every database once, swapping in any that changed. Run it
+    /// before serving, so a fresh deployment starts with current data.
+    pub async fn refresh(&self) {
+        if let Some(path) = &self.db.city_path {
+            let loaded = self.db.city.read().await.is_some();
+            if let Some(db) = self
+                .refresh_one("city", path, loaded, GeoIp::from_bytes)
+                .await
+            {
+                *self.db.city.write().await = Some(db);
+            }
+        }
+        if let Some(path) = &self.db.asn_path {
+            let loaded = self.db.asn.read().await.is_some();
+            if let Some(db) = self
+                .refresh_one("asn", path, loaded, AsnDb::from_bytes)
+                .await
+            {
+                *self.db.asn.write().await = Some(db);
+            }
+        }
+    }
+
+    /// Refresh every `every` (`GEOIP_REFRESH_SECS`). The startup refresh is
+    /// the caller's job, so it can finish before the listener opens.
+    pub async fn run(self, every: Duration) {
+        let mut interval = tokio::time::interval(every);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        // The first tick completes immediately.
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+            self.refresh().await;
+        }
+    }
+
+    /// Download `edition`'s gzipped tarball, or `None` if it hasn't changed
+    /// since `since`.
+    async fn download(
+        &self,
+        edition: &str,
+        since: Option<SystemTime>,
+    ) -> anyhow::Result<Option<Bytes>> {
+        let url = format!(
+            "{}/geoip/databases/{edition}/download?suffix=tar.gz",
+            self.base_url
+        );
+        let mut request = self
+            .client
+            .get(&url)
+            .basic_auth(&self.account_id, Some(&self.license_key));
+        if let Some(since) = since {
+            request = request.header(IF_MODIFIED_SINCE, httpdate::fmt_http_date(since));
+        }
+        let response = request.send().await?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        Ok(Some(response.error_for_status()?.bytes().await?))
+    }
+
+    /// Refresh the database at `path`, returning the replacement if a new
+    /// one was installed. Unless `loaded`, the download is unconditional so
+    /// a file that failed to load is replaced even if it looks current.
+    async fn refresh_one<T: Send + 'static>(
+        &self,
+        database: &'static str,
+        path: &Path,
+        loaded: bool,
+        parse: fn(Vec<u8>) -> anyhow::Result<T>,
+    ) -> Option<T> {
+        let result = async {
+            let edition = edition(path)?;
+            let since = loaded
+                .then(|| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+                .flatten();
+            let Some(archive) = self.download(edition, since).await? else {
+                return Ok(None);
+            };
+            // Decompressing, writing and parsing a database of tens of
+            // megabytes would stall the runtime's worker threads.
+            let path = path.to_path_buf();
+            tokio::task::spawn_blocking(move || install(&path, extract_mmdb(&archive)?, parse))
+                .await?
+                .map(Some)
+        }
+        .await;
+
+        let outcome = match &result {
+            Ok(Some(_)) => {
+                tracing::info!(database, path = %path.display(), "GeoIP database updated");
+                "updated"
+            }
+            Ok(None) => {
+                tracing::debug!(database, "GeoIP database unchanged");
+                "unchanged"
+            }
+            Err(e) => {
+                tracing::error!(
+                    database,
+                    path = %path.display(),
+                    error = format!("{e:#}"),
+                    "GeoIP database update failed; keeping the current one"
+                );
+                "error"
+            }
+        };
+        metrics::counter!("geoip_update_total", "database" => database, "result" => outcome)
+            .increment(1);
+        result.ok().flatten()
+    }
+}
+
+/// MaxMind edition ID for a database path: its file name without extension.
+fn edition(path: &Path) -> anyhow::Result<&str> {
+    path.file_stem()
+        .and_then(|s| s.to_str())
+        .filter(|s| !s.is_empty())
+        .with_context(|| format!("no edition ID in file name {}", path.display()))
+}
+
+/// The first `.mmdb` file in a gzipped tarball.
+fn extract_mmdb(archive: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(archive));
+    for entry in archive.entries().context("invalid archive")? {
+        let mut entry = entry.context("invalid archive")?;
+        if entry.path()?.extension().is_some_and(|ext| ext == "mmdb") {
+            let mut bytes = Vec::new();
+            entry.read_to_end(&mut bytes)?;
+            return Ok(bytes);
+        }
+    }
+    anyhow::bail!("archive contains no .mmdb file")
+}
+
+/// Write `bytes` next to `path`, check they parse, then rename over `path`
+/// so a crash or a bad download never leaves a truncated database behind.
+fn install<T>(
+    path: &Path,
+    bytes: Vec<u8>,
+    parse: fn(Vec<u8>) -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    let tmp = tmp_path(path);
+    std::fs::write(&tmp, &bytes).with_context(|| format!("failed to write {}", tmp.display()))?;
+    match parse(bytes) {
+        Ok(db) => {
+            std::fs::rename(&tmp, path)
+                .with_context(|| format!("failed to replace {}", path.display()))?;
+            Ok(db)
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&tmp);
+            Err(e.context("downloaded database is invalid"))
+        }
+    }
+}
+
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tar_gz(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        let mut builder = tar::Builder::new(gz);
+        for (name, data) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, *data).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[test]
+    fn extracts_mmdb_from_archive() {
+        let archive = tar_gz(&[
+            ("GeoLite2-City_20260101/LICENSE.txt", b"license"),
+            ("GeoLite2-City_20260101/GeoLite2-City.mmdb", b"database"),
+        ]);
+        assert_eq!(extract_mmdb(&archive).unwrap(), b"database");
+    }
+
+    #[test]
+    fn rejects_archive_without_mmdb() {
+        let archive = tar_gz(&[("GeoLite2-City_20260101/README.txt", b"readme")]);
+        assert!(extract_mmdb(&archive).is_err());
+        assert!(extract_mmdb(b"not an archive").is_err());
+    }
+
+    #[test]
+    fn edition_from_file_name() {
+        let path = Path::new("/var/lib/ipecho/GeoLite2-ASN.mmdb");
+        assert_eq!(edition(path).unwrap(), "GeoLite2-ASN");
+        assert_eq!(
+            tmp_path(path),
+            Path::new("/var/lib/ipecho/GeoLite2-ASN.mmdb.tmp")
+        );
+    }
+}
@@ -0,0 +1,178 @@
+//! `/anything`: the whole request back as JSON, whatever the method, like
+//! httpbin's endpoint of the same name. Useful for seeing exactly what an
+//! HTTP client sends: method, path, query, headers and body. Bodies are
+//! capped at `MAX_BODY_BYTES`. `/get` is the GET-only subset, httpbin's
+//! `/get`: query, headers and origin. `/anything` needs the `anything`
+//! feature.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use axum::body::Body;
+#[cfg(feature = "anything")]
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::header::HeaderMap;
+#[cfg(feature = "anything")]
+use axum::http::{Method, header};
+use axum::http::{Response, Uri};
+#[cfg(feature = "anything")]
+use base64::Engine;
+#[cfg(feature = "anything")]
+use base64::engine::general_purpose::STANDARD;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use super::echo::{filter_headers, json_response};
+use crate::client_ip::ClientIp;
+use crate::errors::AppError;
+use crate::state::AppState;
+
+#[cfg(feature = "anything")]
+#[derive(Debug, Serialize)]
+pub struct AnythingResponse {
+    pub method: String,
+    pub path: String,
+    /// Query parameters; a repeated one becomes a list of its values.
+    pub args: Map<String, Value>,
+    pub headers: BTreeMap<String, String>,
+    /// Client IP, after trusted-proxy resolution.
+    pub origin: String,
+    /// The body as text, or as a base64 `data:` URL when it isn't UTF-8.
+    pub data: String,
+    /// Fields of an `application/x-www-form-urlencoded` body, like `args`.
+    pub form: Map<String, Value>,
+    /// A JSON body parsed, `null` for anything else.
+    pub json: Option<Value>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GetResponse {
+    /// Query parameters; a repeated one becomes a list of its values.
+    pub args: Map<String, Value>,
+    pub headers: BTreeMap<String, String>,
+    /// Client IP, after trusted-proxy resolution.
+    pub origin: String,
+}
+
+impl GetResponse {
+    pub(super) fn new(ip: IpAddr, state: &AppState, uri: &Uri, headers: &HeaderMap) -> Self {
+        Self {
+            args: query_args(uri),
+            headers: filter_headers(headers, &state.config.load().excluded_headers),
+            origin: ip.to_string(),
+        }
+    }
+}
+
+// GET /get — query parameters, headers and client IP
+pub async fn get_handler(
+    ClientIp(ip): ClientIp,
+    State(state): State<Arc<AppState>>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/get").increment(1);
+    json_response(&GetResponse::new(ip, &state, &uri, &headers))
+}
+
+// ANY /anything, /anything/{*path} — the request as the server saw it
+#[cfg(feature = "anything")]
+pub async fn anything_handler(
+    ClientIp(ip): ClientIp,
+    State(state): State<Arc<AppState>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/anything").increment(1);
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(|v| v.trim().to_ascii_lowercase())
+        .unwrap_or_default();
+    let form = if content_type == "application/x-www-form-urlencoded" {
+        multi_map(form_urlencoded::parse(&body))
+    } else {
+        Map::new()
+    };
+    let json = if content_type == "application/json" || content_type.ends_with("+json") {
+        serde_json::from_slice(&body).ok()
+    } else {
+        None
+    };
+
+    let response = AnythingResponse {
+        method: method.to_string(),
+        path: uri.path().to_string(),
+        args: query_args(&uri),
+        headers: filter_headers(&headers, &state.config.load().excluded_headers),
+        origin: ip.to_string(),
+        data: body_text(&body),
+        form,
+        json,
+    };
+    json_response(&response)
+}
+
+fn query_args(uri: &Uri) -> Map<String, Value> {
+    multi_map(form_urlencoded::parse(
+        uri.query().unwrap_or_default().as_bytes(),
+    ))
+}
+
+/// `pairs` by name, in order of first appearance; a name given more than
+/// once maps to the list of its values.
+fn multi_map<'a>(pairs: impl Iterator<Item = (Cow<'a, str>, Cow<'a, str>)>) -> Map<String, Value> {
+    let mut map = Map::new();
+    for (name, value) in pairs {
+        let value = Value::String(value.into_owned());
+        match map.get_mut(name.as_ref()) {
+            None => {
+                map.insert(name.into_owned(), value);
+            }
+            Some(Value::Array(values)) => values.push(value),
+            Some(first) => *first = Value::Array(vec![first.take(), value]),
+        }
+    }
+    map
+}
+
+#[cfg(feature = "anything")]
+fn body_text(body: &[u8]) -> String {
+    match std::str::from_utf8(body) {
+        Ok(text) => text.to_string(),
+        Err(_) => format!(
+            "data:application/octet-stream;base64,{}",
+            STANDARD.encode(body)
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_names_become_lists() {
+        let map = multi_map(form_urlencoded::parse(b"a=1&b=x+y&a=2&a=3"));
+        assert_eq!(
+            Value::Object(map),
+            serde_json::json!({"a": ["1", "2", "3"], "b": "x y"})
+        );
+    }
+
+    #[cfg(feature = "anything")]
+    #[test]
+    fn binary_bodies_are_base64() {
+        assert_eq!(body_text(b"hello"), "hello");
+        assert_eq!(
+            body_text(&[0xff, 0x00]),
+            "data:application/octet-stream;base64,/wA="
+        );
+    }
+}
@@ -0,0 +1,322 @@
+//! Authentication test endpoints, like httpbin's, for exercising an HTTP
+//! client's auth support. Each answers 401 with a `WWW-Authenticate`
+//! challenge until the request carries matching credentials, then returns
+//! `{"authenticated": true, ...}`:
+//!
+//! - `/basic-auth/{user}/{passwd}`: Basic auth with that user and password.
+//! - `/bearer`: any Bearer token.
+//! - `/digest-auth/{qop}/{user}/{passwd}[/{algorithm}]`: RFC 7616 Digest
+//!   auth, `qop` being `auth` or `auth-int` and `algorithm` `MD5` (the
+//!   default), `SHA-256` or `SHA-512`. Nonces aren't tracked, so any nonce
+//!   the client echoes back is accepted.
+
+use axum::body::{Body, Bytes};
+use axum::extract::Path;
+use axum::http::header::{self, HeaderMap};
+use axum::http::{Method, Response, StatusCode, Uri};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+
+use super::echo::json_response;
+use crate::errors::AppError;
+
+const REALM: &str = "echo";
+
+#[derive(Debug, Serialize)]
+pub struct AuthResponse {
+    pub authenticated: bool,
+    pub user: Option<String>,
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DigestPath {
+    pub qop: String,
+    pub user: String,
+    pub passwd: String,
+    pub algorithm: Option<String>,
+}
+
+// GET /basic-auth/{user}/{passwd} — succeed with matching Basic credentials
+pub async fn basic_auth_handler(
+    Path((user, passwd)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/basic-auth").increment(1);
+    let credentials = authorization(&headers, "basic")
+        .and_then(|encoded| STANDARD.decode(encoded).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok());
+    if credentials.as_deref() != Some(&format!("{user}:{passwd}")) {
+        return challenge(format!("Basic realm=\"{REALM}\""));
+    }
+    json_response(&AuthResponse {
+        authenticated: true,
+        user: Some(user),
+        token: None,
+    })
+}
+
+// GET /bearer — succeed with any Bearer token
+pub async fn bearer_handler(headers: HeaderMap) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/bearer").increment(1);
+    let Some(token) = authorization(&headers, "bearer").filter(|t| !t.is_empty()) else {
+        return challenge(format!("Bearer realm=\"{REALM}\""));
+    };
+    json_response(&AuthResponse {
+        authenticated: true,
+        user: None,
+        token: Some(token.to_string()),
+    })
+}
+
+// GET /digest-auth/{qop}/{user}/{passwd}[/{algorithm}] — succeed with a
+// matching Digest response
+pub async fn digest_auth_handler(
+    Path(path): Path<DigestPath>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/digest-auth").increment(1);
+    let algorithm = DigestAlgorithm::from_name(path.algorithm.as_deref().unwrap_or("MD5"))
+        .ok_or_else(|| {
+            AppError::BadRequest("algorithm must be MD5, SHA-256 or SHA-512".to_string())
+        })?;
+    if path.qop != "auth" && path.qop != "auth-int" {
+        return Err(AppError::BadRequest(
+            "qop must be auth or auth-int".to_string(),
+        ));
+    }
+
+    let request_uri = uri.path_and_query().map_or(uri.path(), |p| p.as_str());
+    let authenticated = authorization(&headers, "digest")
+        .map(parse_digest_params)
+        .is_some_and(|params| {
+            let param = |name: &str| {
+                params
+                    .iter()
+                    .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                    .map(|(_, v)| v.as_str())
+            };
+            let (Some(nonce), Some(nc), Some(cnonce), Some(response)) = (
+                param("nonce"),
+                param("nc"),
+                param("cnonce"),
+                param("response"),
+            ) else {
+                return false;
+            };
+            param("username") == Some(&path.user)
+                && param("realm") == Some(REALM)
+                && param("uri") == Some(request_uri)
+                && param("qop") == Some(&path.qop)
+                && param("algorithm").is_none_or(|a| a.eq_ignore_ascii_case(algorithm.name()))
+                && response.eq_ignore_ascii_case(&algorithm.response(&DigestInput {
+                    realm: REALM,
+                    user: &path.user,
+                    passwd: &path.passwd,
+                    method: method.as_str(),
+                    uri: request_uri,
+                    qop: &path.qop,
+                    nonce,
+                    nc,
+                    cnonce,
+                    body: &body,
+                }))
+        });
+    if !authenticated {
+        return challenge(format!(
+            "Digest realm=\"{REALM}\", qop=\"{}\", nonce=\"{}\", opaque=\"{}\", algorithm={}",
+            path.qop,
+            random_hex(),
+            random_hex(),
+            algorithm.name()
+        ));
+    }
+    json_response(&AuthResponse {
+        authenticated: true,
+        user: Some(path.user),
+        token: None,
+    })
+}
+
+/// The credentials of an `Authorization` header using `scheme`.
+fn authorization<'a>(headers: &'a HeaderMap, scheme: &str) -> Option<&'a str> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (name, credentials) = value.split_once(' ')?;
+    name.eq_ignore_ascii_case(scheme)
+        .then_some(credentials.trim())
+}
+
+fn challenge(www_authenticate: String) -> Result<Response<Body>, AppError> {
+    let body = serde_json::to_string_pretty(&AuthResponse {
+        authenticated: false,
+        user: None,
+        token: None,
+    })?;
+    Response::builder()
+        .status(StatusCode::UNAUTHORIZED)
+        .header(header::WWW_AUTHENTICATE, www_authenticate)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::from(body))
+        .map_err(|_| AppError::HttpBuilderError)
+}
+
+fn random_hex() -> String {
+    let mut bytes = [0u8; 16];
+    rand::rng().fill_bytes(&mut bytes);
+    hex(&bytes)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// `name=value` pairs of Digest credentials, with quoted values unquoted.
+/// Commas inside quotes don't split.
+fn parse_digest_params(credentials: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut rest = credentials.trim();
+    while !rest.is_empty() {
+        let Some((name, after)) = rest.split_once('=') else {
+            break;
+        };
+        let after = after.trim_start();
+        let (value, remaining) = if let Some(quoted) = after.strip_prefix('"') {
+            let mut value = String::new();
+            let mut chars = quoted.char_indices();
+            let mut end = quoted.len();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\\' => value.extend(chars.next().map(|(_, c)| c)),
+                    '"' => {
+                        end = i + 1;
+                        break;
+                    }
+                    c => value.push(c),
+                }
+            }
+            (value, &quoted[end..])
+        } else {
+            let end = after.find(',').unwrap_or(after.len());
+            (after[..end].trim().to_string(), &after[end..])
+        };
+        params.push((name.trim().to_string(), value));
+        rest = remaining.trim_start_matches([',', ' ', '\t']);
+    }
+    params
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DigestAlgorithm {
+    Md5,
+    Sha256,
+    Sha512,
+}
+
+struct DigestInput<'a> {
+    realm: &'a str,
+    user: &'a str,
+    passwd: &'a str,
+    method: &'a str,
+    uri: &'a str,
+    qop: &'a str,
+    nonce: &'a str,
+    nc: &'a str,
+    cnonce: &'a str,
+    body: &'a [u8],
+}
+
+impl DigestAlgorithm {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "MD5" => Some(Self::Md5),
+            "SHA-256" => Some(Self::Sha256),
+            "SHA-512" => Some(Self::Sha512),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Md5 => "MD5",
+            Self::Sha256 => "SHA-256",
+            Self::Sha512 => "SHA-512",
+        }
+    }
+
+    fn hash(self, data: &[u8]) -> String {
+        match self {
+            Self::Md5 => hex(&md5::Md5::digest(data)),
+            Self::Sha256 => hex(&sha2::Sha256::digest(data)),
+            Self::Sha512 => hex(&sha2::Sha512::digest(data)),
+        }
+    }
+
+    /// The expected `response` parameter (RFC 7616 §3.4.1).
+    fn response(self, input: &DigestInput) -> String {
+        let ha1 = self.hash(format!("{}:{}:{}", input.user, input.realm, input.passwd).as_bytes());
+        let ha2 = match input.qop {
+            "auth-int" => self.hash(
+                format!("{}:{}:{}", input.method, input.uri, self.hash(input.body)).as_bytes(),
+            ),
+            _ => self.hash(format!("{}:{}", input.method, input.uri).as_bytes()),
+        };
+        self.hash(
+            format!(
+                "{ha1}:{}:{}:{}:{}:{ha2}",
+                input.nonce, input.nc, input.cnonce, input.qop
+            )
+            .as_bytes(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_digest_params() {
+        let params = parse_digest_params(
+            r#"username="Mufasa", realm="a, b", nc=00000001, qop=auth, response="x\"y""#,
+        );
+        let expected = [
+            ("username", "Mufasa"),
+            ("realm", "a, b"),
+            ("nc", "00000001"),
+            ("qop", "auth"),
+            ("response", "x\"y"),
+        ];
+        assert_eq!(params.len(), expected.len());
+        for ((name, value), (want_name, want_value)) in params.iter().zip(expected) {
+            assert_eq!((name.as_str(), value.as_str()), (want_name, want_value));
+        }
+    }
+
+    #[test]
+    fn computes_rfc_2617_example_response() {
+        // RFC 2617 §3.5.
+        let input = DigestInput {
+            realm: "testrealm@host.com",
+            user: "Mufasa",
+            passwd: "Circle Of Life",
+            method: "GET",
+            uri: "/dir/index.html",
+            qop: "auth",
+            nonce: "dcd98b7102dd2f0e8b11d0f600bfb0c093",
+            nc: "00000001",
+            cnonce: "0a4f113b",
+            body: b"",
+        };
+        assert_eq!(
+            DigestAlgorithm::Md5.response(&input),
+            "6629fae49393a05397450978507c4ef1"
+        );
+    }
+}
@@ -0,0 +1,26 @@
+//! `/echo`: the request body sent straight back, for checking what an HTTP
+//! client really transmits once proxies have had their way with it. The
+//! body is streamed, never buffered, under the usual `MAX_BODY_BYTES` cap;
+//! a chunked body that runs over it is cut off mid-response.
+
+use axum::body::Body;
+use axum::http::header::{self, HeaderMap, HeaderValue};
+use axum::http::{Response, StatusCode};
+
+use crate::errors::AppError;
+
+// POST/PUT /echo — the request body back, with the request's Content-Type
+pub async fn echo_body_handler(headers: HeaderMap, body: Body) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/echo").increment(1);
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .cloned()
+        .unwrap_or(HeaderValue::from_static("application/octet-stream"));
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(body)
+        .map_err(|_| AppError::HttpBuilderError)
+}
@@ -0,0 +1,149 @@
+//! `/cache/{n}`: a cacheable response, for checking what a CDN or browser
+//! in front of the server does with one. It carries `Cache-Control:
+//! public, max-age=n` and validators that stay the same for each `n`-second
+//! window: `Last-Modified` is the start of the window and the `ETag` names
+//! it. `If-None-Match` and `If-Modified-Since` for the current window get a
+//! 304. The body is the `/get` response plus `generated_at`, so a cached
+//! copy is easy to tell from a fresh one.
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::body::Body;
+use axum::extract::{Path, State};
+use axum::http::header::{self, HeaderMap};
+use axum::http::{Response, StatusCode, Uri};
+use serde::Serialize;
+
+use super::anything::GetResponse;
+use crate::client_ip::ClientIp;
+use crate::errors::AppError;
+use crate::state::AppState;
+
+/// Longest `max-age` accepted: a year, the most caches honour.
+const MAX_AGE_SECS: u64 = 365 * 24 * 3600;
+
+#[derive(Serialize)]
+struct CacheResponse {
+    #[serde(flatten)]
+    request: GetResponse,
+    /// When this body was produced, as an HTTP date.
+    generated_at: String,
+}
+
+// GET /cache/{n} — a response cacheable for n seconds, with validators
+pub async fn cache_handler(
+    Path(max_age): Path<u64>,
+    ClientIp(ip): ClientIp,
+    State(state): State<Arc<AppState>>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/cache/{n}").increment(1);
+    if max_age > MAX_AGE_SECS {
+        return Err(AppError::BadRequest(format!(
+            "max-age must be at most {MAX_AGE_SECS} seconds"
+        )));
+    }
+    let now = SystemTime::now();
+    let window = window_start(now, max_age);
+    let secs = window
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let etag = format!("\"{max_age}-{secs}\"");
+
+    let response = Response::builder()
+        .header(header::CACHE_CONTROL, format!("public, max-age={max_age}"))
+        .header(header::ETAG, &etag)
+        .header(header::LAST_MODIFIED, httpdate::fmt_http_date(window));
+    if not_modified(&headers, &etag, window) {
+        return response
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Body::empty())
+            .map_err(|_| AppError::HttpBuilderError);
+    }
+
+    let body = serde_json::to_string_pretty(&CacheResponse {
+        request: GetResponse::new(ip, &state, &uri, &headers),
+        generated_at: httpdate::fmt_http_date(now),
+    })?;
+    response
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .map_err(|_| AppError::HttpBuilderError)
+}
+
+/// Start of the `max_age`-second window containing `now`, to the second;
+/// with 0, every second is its own window.
+fn window_start(now: SystemTime, max_age: u64) -> SystemTime {
+    let secs = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    UNIX_EPOCH + Duration::from_secs(secs - secs % max_age.max(1))
+}
+
+/// Whether the request's validators match (RFC 9110 §13.2.2):
+/// `If-None-Match` when present, otherwise `If-Modified-Since`.
+fn not_modified(headers: &HeaderMap, etag: &str, last_modified: SystemTime) -> bool {
+    if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH) {
+        let Ok(if_none_match) = if_none_match.to_str() else {
+            return false;
+        };
+        // Weak comparison: W/"x" matches "x".
+        return if_none_match
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag);
+    }
+    headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| httpdate::parse_http_date(v).ok())
+        .is_some_and(|since| last_modified <= since)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn headers(name: header::HeaderName, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn windows_are_aligned_to_max_age() {
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+        assert_eq!(window_start(at(1_000_059), 60), at(1_000_020));
+        assert_eq!(window_start(at(1_000_059), 0), at(1_000_059));
+    }
+
+    #[test]
+    fn validators_are_compared() {
+        let modified = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let etag = "\"60-1700000000\"";
+        let matches = |h: HeaderMap| not_modified(&h, etag, modified);
+
+        assert!(matches(headers(
+            header::IF_NONE_MATCH,
+            "\"x\", W/\"60-1700000000\""
+        )));
+        assert!(matches(headers(header::IF_NONE_MATCH, "*")));
+        assert!(!matches(headers(
+            header::IF_NONE_MATCH,
+            "\"60-1699999940\""
+        )));
+        assert!(matches(headers(
+            header::IF_MODIFIED_SINCE,
+            "Tue, 14 Nov 2023 22:13:20 GMT"
+        )));
+        assert!(!matches(headers(
+            header::IF_MODIFIED_SINCE,
+            "Tue, 14 Nov 2023 22:13:19 GMT"
+        )));
+        assert!(!matches(HeaderMap::new()));
+    }
+}
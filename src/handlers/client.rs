@@ -0,0 +1,313 @@
+//! `/client`: a guess at what software sent the request.
+//!
+//! The `User-Agent` names a product, but anyone can send any `User-Agent`,
+//! so the verdict's `confidence` also weighs whether the other headers fit
+//! it: browsers send `Accept-Language` and (all current ones) the
+//! `Sec-Fetch-*` headers, while curl and HTTP libraries send neither. Each
+//! piece of evidence is listed in `signals`. Bots are taken at their word;
+//! confirming one means checking its address (see `/host`).
+
+use axum::body::Body;
+use axum::http::Response;
+use axum::http::header::{self, HeaderMap};
+use serde::Serialize;
+
+use super::echo::json_response;
+use crate::errors::AppError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientKind {
+    Browser,
+    /// Command-line tools: curl, wget, HTTPie...
+    Cli,
+    /// HTTP libraries used by programs: python-requests, Go's net/http...
+    Library,
+    /// Crawlers and other automated agents that say so.
+    Bot,
+    Unknown,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClientVerdict {
+    pub kind: ClientKind,
+    /// Product name, `null` when unrecognised.
+    pub name: Option<&'static str>,
+    pub version: Option<String>,
+    /// 0 to 1.
+    pub confidence: f64,
+    /// The evidence behind the verdict, in plain words.
+    pub signals: Vec<String>,
+}
+
+/// A product recognised by a `User-Agent` token.
+struct Product {
+    /// Lowercase substring of the `User-Agent`; the version follows it.
+    token: &'static str,
+    name: &'static str,
+    kind: ClientKind,
+}
+
+const fn product(token: &'static str, name: &'static str, kind: ClientKind) -> Product {
+    Product { token, name, kind }
+}
+
+/// Checked in order, so more specific tokens (`edg/` before `chrome/`,
+/// `chrome/` before `safari/`) come first.
+const PRODUCTS: &[Product] = &[
+    product("googlebot/", "Googlebot", ClientKind::Bot),
+    product("bingbot/", "Bingbot", ClientKind::Bot),
+    product("duckduckbot", "DuckDuckBot", ClientKind::Bot),
+    product("yandexbot/", "YandexBot", ClientKind::Bot),
+    product("baiduspider", "Baiduspider", ClientKind::Bot),
+    product("applebot/", "Applebot", ClientKind::Bot),
+    product("gptbot/", "GPTBot", ClientKind::Bot),
+    product("facebookexternalhit/", "Facebook", ClientKind::Bot),
+    product("curl/", "curl", ClientKind::Cli),
+    product("wget/", "Wget", ClientKind::Cli),
+    product("httpie/", "HTTPie", ClientKind::Cli),
+    product("xh/", "xh", ClientKind::Cli),
+    product("aria2/", "aria2", ClientKind::Cli),
+    product("powershell/", "PowerShell", ClientKind::Cli),
+    product("python-requests/", "python-requests", ClientKind::Library),
+    product("python-httpx/", "HTTPX", ClientKind::Library),
+    product("python-urllib/", "urllib", ClientKind::Library),
+    product("aiohttp/", "aiohttp", ClientKind::Library),
+    product("go-http-client/", "Go net/http", ClientKind::Library),
+    product("okhttp/", "OkHttp", ClientKind::Library),
+    product("java-http-client/", "Java HttpClient", ClientKind::Library),
+    product(
+        "apache-httpclient/",
+        "Apache HttpClient",
+        ClientKind::Library,
+    ),
+    product("axios/", "axios", ClientKind::Library),
+    product("node-fetch", "node-fetch", ClientKind::Library),
+    product("undici", "undici", ClientKind::Library),
+    product("reqwest/", "reqwest", ClientKind::Library),
+    product("libwww-perl/", "libwww-perl", ClientKind::Library),
+    product("guzzlehttp/", "Guzzle", ClientKind::Library),
+    product("edg/", "Edge", ClientKind::Browser),
+    product("opr/", "Opera", ClientKind::Browser),
+    product("samsungbrowser/", "Samsung Internet", ClientKind::Browser),
+    product("firefox/", "Firefox", ClientKind::Browser),
+    product("chrome/", "Chrome", ClientKind::Browser),
+    product("crios/", "Chrome", ClientKind::Browser),
+    product("safari/", "Safari", ClientKind::Browser),
+];
+
+/// Generic words crawlers put in their `User-Agent`.
+const BOT_WORDS: &[&str] = &["bot", "spider", "crawler", "slurp"];
+
+// GET /client — what kind of software sent the request, with the evidence
+pub async fn client_handler(headers: HeaderMap) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/client").increment(1);
+    json_response(&classify(&headers))
+}
+
+/// Classify the sender of `headers`.
+pub fn classify(headers: &HeaderMap) -> ClientVerdict {
+    let ua = headers
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .trim();
+    let browser_headers = BrowserHeaders::of(headers);
+    let mut signals = Vec::new();
+
+    let lower = ua.to_ascii_lowercase();
+    let matched = PRODUCTS
+        .iter()
+        .find_map(|p| lower.find(p.token).map(|at| (p, at + p.token.len())));
+    let (kind, name, version) = match matched {
+        Some((p, end)) => {
+            signals.push(format!("User-Agent names {}", p.name));
+            // Safari's own version is in `Version/`, not `Safari/`.
+            let version = match p.name {
+                "Safari" => lower.find("version/").map(|at| at + "version/".len()),
+                _ => Some(end),
+            }
+            .and_then(|at| version_at(ua, at));
+            (p.kind, Some(p.name), version)
+        }
+        None if BOT_WORDS.iter().any(|w| lower.contains(w)) => {
+            signals.push("User-Agent says it's a crawler".to_string());
+            (ClientKind::Bot, None, None)
+        }
+        None if ua.is_empty() => {
+            signals.push("no User-Agent".to_string());
+            (ClientKind::Unknown, None, None)
+        }
+        None => {
+            signals.push("User-Agent not recognised".to_string());
+            (ClientKind::Unknown, None, None)
+        }
+    };
+
+    let confidence = match kind {
+        ClientKind::Browser => {
+            let mut confidence: f64 = 0.5;
+            for (present, what) in browser_headers.checks() {
+                if present {
+                    confidence += 0.15;
+                    signals.push(format!("sends {what}, as browsers do"));
+                } else {
+                    confidence -= 0.15;
+                    signals.push(format!("no {what}, which browsers send"));
+                }
+            }
+            confidence
+        }
+        ClientKind::Cli | ClientKind::Library => {
+            if browser_headers.count() == 0 {
+                signals.push("no browser-only headers".to_string());
+                0.9
+            } else {
+                signals.push("sends browser-only headers".to_string());
+                0.5
+            }
+        }
+        ClientKind::Bot => {
+            signals.push("bots are taken at their word".to_string());
+            if name.is_some() { 0.7 } else { 0.5 }
+        }
+        ClientKind::Unknown => 0.0,
+    };
+
+    ClientVerdict {
+        kind,
+        name,
+        version,
+        confidence: (confidence.clamp(0.0, 1.0) * 100.0).round() / 100.0,
+        signals,
+    }
+}
+
+/// The version number starting at byte `at` of `ua`: digits and dots.
+fn version_at(ua: &str, at: usize) -> Option<String> {
+    let version: String = ua
+        .get(at..)?
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    let version = version.trim_end_matches('.');
+    (!version.is_empty()).then(|| version.to_string())
+}
+
+/// Headers every current browser sends and HTTP tools normally don't.
+struct BrowserHeaders {
+    accept_language: bool,
+    sec_fetch: bool,
+    html_accept: bool,
+}
+
+impl BrowserHeaders {
+    fn of(headers: &HeaderMap) -> Self {
+        Self {
+            accept_language: headers.contains_key(header::ACCEPT_LANGUAGE),
+            sec_fetch: headers.contains_key("sec-fetch-mode"),
+            html_accept: headers
+                .get_all(header::ACCEPT)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .any(|v| v.contains("text/html")),
+        }
+    }
+
+    fn checks(&self) -> [(bool, &'static str); 3] {
+        [
+            (self.accept_language, "Accept-Language"),
+            (self.sec_fetch, "Sec-Fetch-* headers"),
+            (self.html_accept, "an Accept listing text/html"),
+        ]
+    }
+
+    fn count(&self) -> usize {
+        self.checks().iter().filter(|(present, _)| *present).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.parse().unwrap(), v.parse().unwrap()))
+            .collect()
+    }
+
+    const CHROME: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 \
+                          (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36";
+
+    #[test]
+    fn cli_tools() {
+        let verdict = classify(&headers(&[("user-agent", "curl/8.5.0"), ("accept", "*/*")]));
+        assert_eq!(verdict.kind, ClientKind::Cli);
+        assert_eq!(verdict.name, Some("curl"));
+        assert_eq!(verdict.version.as_deref(), Some("8.5.0"));
+        assert_eq!(verdict.confidence, 0.9);
+
+        let verdict = classify(&headers(&[("user-agent", "Go-http-client/2.0")]));
+        assert_eq!(verdict.kind, ClientKind::Library);
+        assert_eq!(verdict.name, Some("Go net/http"));
+        assert_eq!(verdict.version.as_deref(), Some("2.0"));
+    }
+
+    #[test]
+    fn browsers_are_trusted_by_their_headers() {
+        let full = classify(&headers(&[
+            ("user-agent", CHROME),
+            ("accept", "text/html,application/xhtml+xml,*/*;q=0.8"),
+            ("accept-language", "en-US,en;q=0.9"),
+            ("sec-fetch-mode", "navigate"),
+        ]));
+        assert_eq!(full.kind, ClientKind::Browser);
+        assert_eq!(full.name, Some("Chrome"));
+        assert_eq!(full.version.as_deref(), Some("126.0.0.0"));
+        assert_eq!(full.confidence, 0.95);
+
+        let spoofed = classify(&headers(&[("user-agent", CHROME)]));
+        assert_eq!(spoofed.kind, ClientKind::Browser);
+        assert_eq!(spoofed.confidence, 0.05);
+    }
+
+    #[test]
+    fn safari_version_and_edge_before_chrome() {
+        let safari = classify(&headers(&[(
+            "user-agent",
+            "Mozilla/5.0 (Macintosh; Intel Mac OS X 14_5) AppleWebKit/605.1.15 \
+             (KHTML, like Gecko) Version/17.5 Safari/605.1.15",
+        )]));
+        assert_eq!(safari.name, Some("Safari"));
+        assert_eq!(safari.version.as_deref(), Some("17.5"));
+
+        let edge = classify(&headers(&[(
+            "user-agent",
+            &format!("{CHROME} Edg/126.0.2592.87"),
+        )]));
+        assert_eq!(edge.name, Some("Edge"));
+        assert_eq!(edge.version.as_deref(), Some("126.0.2592.87"));
+    }
+
+    #[test]
+    fn bots_and_unknowns() {
+        let google = classify(&headers(&[(
+            "user-agent",
+            "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)",
+        )]));
+        assert_eq!(google.kind, ClientKind::Bot);
+        assert_eq!(google.name, Some("Googlebot"));
+        assert_eq!(google.version.as_deref(), Some("2.1"));
+
+        let generic = classify(&headers(&[("user-agent", "ExampleCrawler 1.0")]));
+        assert_eq!(generic.kind, ClientKind::Bot);
+        assert_eq!(generic.name, None);
+
+        let none = classify(&HeaderMap::new());
+        assert_eq!(none.kind, ClientKind::Unknown);
+        assert_eq!(none.confidence, 0.0);
+        assert_eq!(none.signals, ["no User-Agent"]);
+    }
+}
@@ -0,0 +1,160 @@
+//! `/gzip`, `/deflate` and `/brotli`: a body that is always compressed with
+//! the named codec and labelled with `Content-Encoding`, whatever the
+//! request's `Accept-Encoding` says, for checking that a client can decode
+//! it. The body is the `/get` response plus the `encoding` used.
+
+use std::io::Write;
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::header::{self, HeaderMap};
+use axum::http::{Response, StatusCode, Uri};
+use flate2::Compression;
+use flate2::write::{GzEncoder, ZlibEncoder};
+use serde::Serialize;
+
+use super::anything::GetResponse;
+use crate::client_ip::ClientIp;
+use crate::errors::AppError;
+use crate::state::AppState;
+
+/// A `Content-Encoding` the endpoints can produce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Codec {
+    Gzip,
+    /// zlib, which is what HTTP calls `deflate`.
+    Deflate,
+    Brotli,
+}
+
+impl Codec {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Deflate => "deflate",
+            Self::Brotli => "br",
+        }
+    }
+
+    fn compress(self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Self::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Self::Deflate => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Self::Brotli => {
+                let mut out = Vec::new();
+                {
+                    // Quality 5 and a 4 MiB window: quick, and ample here.
+                    let mut encoder = brotli::CompressorWriter::new(&mut out, 4096, 5, 22);
+                    encoder.write_all(data)?;
+                }
+                Ok(out)
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct CompressedResponse {
+    #[serde(flatten)]
+    request: GetResponse,
+    encoding: &'static str,
+}
+
+// GET /gzip — the /get response, gzip-compressed
+pub async fn gzip_handler(
+    ip: ClientIp,
+    state: State<Arc<AppState>>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/gzip").increment(1);
+    compressed_response(Codec::Gzip, ip, state, uri, headers)
+}
+
+// GET /deflate — the /get response, zlib-compressed
+pub async fn deflate_handler(
+    ip: ClientIp,
+    state: State<Arc<AppState>>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/deflate").increment(1);
+    compressed_response(Codec::Deflate, ip, state, uri, headers)
+}
+
+// GET /brotli — the /get response, brotli-compressed
+pub async fn brotli_handler(
+    ip: ClientIp,
+    state: State<Arc<AppState>>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/brotli").increment(1);
+    compressed_response(Codec::Brotli, ip, state, uri, headers)
+}
+
+fn compressed_response(
+    codec: Codec,
+    ClientIp(ip): ClientIp,
+    State(state): State<Arc<AppState>>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Result<Response<Body>, AppError> {
+    let body = serde_json::to_vec_pretty(&CompressedResponse {
+        request: GetResponse::new(ip, &state, &uri, &headers),
+        encoding: codec.name(),
+    })?;
+    let body = codec
+        .compress(&body)
+        .map_err(|_| AppError::HttpBuilderError)?;
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::CONTENT_ENCODING, codec.name())
+        .header(header::CACHE_CONTROL, "no-store, no-transform")
+        .body(Body::from(body))
+        .map_err(|_| AppError::HttpBuilderError)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::{GzDecoder, ZlibDecoder};
+
+    use super::*;
+
+    #[test]
+    fn compressed_bodies_round_trip() {
+        let data = b"{\"hello\": \"world\"}".repeat(10);
+        let mut out = Vec::new();
+
+        let gzip = Codec::Gzip.compress(&data).unwrap();
+        GzDecoder::new(&gzip[..]).read_to_end(&mut out).unwrap();
+        assert_eq!(out, data);
+
+        out.clear();
+        let deflate = Codec::Deflate.compress(&data).unwrap();
+        ZlibDecoder::new(&deflate[..])
+            .read_to_end(&mut out)
+            .unwrap();
+        assert_eq!(out, data);
+
+        out.clear();
+        let brotli = Codec::Brotli.compress(&data).unwrap();
+        brotli::Decompressor::new(&brotli[..], 4096)
+            .read_to_end(&mut out)
+            .unwrap();
+        assert_eq!(out, data);
+    }
+}
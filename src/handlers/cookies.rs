@@ -0,0 +1,148 @@
+//! Cookie endpoints, for testing how clients and intermediaries handle
+//! cookies:
+//!
+//! - `/cookies` returns the cookies the request carried as a JSON object.
+//! - `/cookies/set?name=value&...` sets each query parameter as a cookie.
+//! - `/cookies/delete?name&...` expires the named cookies.
+//!
+//! Both of the latter redirect to `/cookies`, so a client that follows the
+//! redirect with its cookie jar shows the result straight away. Cookies are
+//! set for `Path=/` with no other attributes. A `Cookie` header hidden by
+//! `EXCLUDED_HEADERS` reads as no cookies.
+
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::header::{self, HeaderMap};
+use axum::http::{Response, StatusCode, Uri};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use super::echo::json_response;
+use crate::errors::AppError;
+use crate::state::AppState;
+
+#[derive(Debug, Serialize)]
+pub struct CookiesResponse {
+    pub cookies: Map<String, Value>,
+}
+
+// GET /cookies — the request's cookies as JSON
+pub async fn cookies_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/cookies").increment(1);
+    let mut cookies = Map::new();
+    if !state.config.load().is_header_excluded("cookie") {
+        // HTTP/2 clients may split cookies over several headers.
+        for value in headers.get_all(header::COOKIE) {
+            let Ok(value) = value.to_str() else {
+                continue;
+            };
+            for (name, value) in parse_cookie_header(value) {
+                cookies.insert(name.to_string(), value.to_string().into());
+            }
+        }
+    }
+    json_response(&CookiesResponse { cookies })
+}
+
+// GET /cookies/set?name=value — set cookies, then redirect to /cookies
+pub async fn set_cookies_handler(uri: Uri) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/cookies/set").increment(1);
+    let mut set_cookies = Vec::new();
+    for (name, value) in query_pairs(&uri) {
+        if !is_cookie_name(&name) || !is_cookie_value(&value) {
+            return Err(AppError::BadRequest(format!("invalid cookie \"{name}\"")));
+        }
+        set_cookies.push(format!("{name}={value}; Path=/"));
+    }
+    redirect_to_cookies(set_cookies)
+}
+
+// GET /cookies/delete?name — expire cookies, then redirect to /cookies
+pub async fn delete_cookies_handler(uri: Uri) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/cookies/delete").increment(1);
+    let mut set_cookies = Vec::new();
+    for (name, _) in query_pairs(&uri) {
+        if !is_cookie_name(&name) {
+            return Err(AppError::BadRequest(format!("invalid cookie \"{name}\"")));
+        }
+        set_cookies.push(format!(
+            "{name}=; Path=/; Max-Age=0; Expires=Thu, 01 Jan 1970 00:00:00 GMT"
+        ));
+    }
+    redirect_to_cookies(set_cookies)
+}
+
+fn redirect_to_cookies(set_cookies: Vec<String>) -> Result<Response<Body>, AppError> {
+    let mut response = Response::builder()
+        .status(StatusCode::FOUND)
+        // Relative to /cookies/set and /cookies/delete, so it stays inside
+        // the mount when the router is nested under a prefix.
+        .header(header::LOCATION, "../cookies")
+        .header(header::CACHE_CONTROL, "no-store");
+    for set_cookie in set_cookies {
+        response = response.header(header::SET_COOKIE, set_cookie);
+    }
+    response
+        .body(Body::empty())
+        .map_err(|_| AppError::HttpBuilderError)
+}
+
+fn query_pairs(uri: &Uri) -> Vec<(String, String)> {
+    form_urlencoded::parse(uri.query().unwrap_or_default().as_bytes())
+        .into_owned()
+        .collect()
+}
+
+/// `name=value` pairs of a `Cookie` header (RFC 6265 §4.2), skipping
+/// malformed ones. Double quotes around a value are kept, as sent.
+fn parse_cookie_header(header: &str) -> impl Iterator<Item = (&str, &str)> {
+    header.split(';').filter_map(|pair| {
+        let (name, value) = pair.split_once('=')?;
+        let name = name.trim();
+        (!name.is_empty()).then(|| (name, value.trim()))
+    })
+}
+
+/// An RFC 6265 cookie name: an HTTP token.
+fn is_cookie_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+/// An RFC 6265 cookie value, unquoted: printable ASCII except space,
+/// `"`, `,`, `;` and `\`.
+fn is_cookie_value(value: &str) -> bool {
+    value
+        .bytes()
+        .all(|b| b.is_ascii_graphic() && !b"\",;\\".contains(&b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cookie_headers() {
+        let cookies: Vec<_> = parse_cookie_header("a=1; b = two;bad; c=\"q\"; =x").collect();
+        assert_eq!(cookies, [("a", "1"), ("b", "two"), ("c", "\"q\"")]);
+    }
+
+    #[test]
+    fn validates_names_and_values() {
+        assert!(is_cookie_name("session_id"));
+        assert!(!is_cookie_name("a b"));
+        assert!(!is_cookie_name("a=b"));
+        assert!(!is_cookie_name(""));
+        assert!(is_cookie_value("abc123-_."));
+        assert!(is_cookie_value(""));
+        assert!(!is_cookie_value("a;b"));
+        assert!(!is_cookie_value("a b"));
+    }
+}
@@ -0,0 +1,180 @@
+//! Test-data endpoints, for checking how clients handle downloads:
+//!
+//! - `/bytes/{n}` sends `n` random bytes in one response with a
+//!   `Content-Length`, at most `MAX_GENERATED_BYTES`. `?seed=` seeds the
+//!   generator, so the same seed always gives the same bytes.
+//! - `/pad/{n}` sends a body of exactly `n` bytes, refusing rather than
+//!   cutting counts over `MAX_GENERATED_BYTES`. The bytes are random, so
+//!   compressing them on the way gains nothing, and `no-transform` asks
+//!   proxies not to try. Bisecting `n` finds the largest response that
+//!   makes it through a path with a broken MTU.
+//! - `/stream/{n}` sends `n` lines of JSON, each the `/get` response with an
+//!   `id`, one chunk per line, so clients must handle chunked encoding.
+//! - `/drip` waits `?delay=` seconds, then trickles `?bytes=` asterisks
+//!   evenly over `?duration=` seconds, for read timeouts, progress bars and
+//!   proxy buffering. Both times are capped at `MAX_DELAY_SECS` and the
+//!   byte count at `MAX_GENERATED_BYTES`.
+//!
+//! `/bytes/{n}` and `/pad/{n}` need the `random-data` feature.
+
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::{Body, Bytes};
+use axum::extract::{Path, Query, State};
+use axum::http::header::{self, HeaderMap};
+use axum::http::{Response, StatusCode, Uri};
+use futures::stream::{self, StreamExt};
+#[cfg(feature = "random-data")]
+use rand::rngs::StdRng;
+#[cfg(feature = "random-data")]
+use rand::{RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+use super::anything::GetResponse;
+use crate::client_ip::ClientIp;
+use crate::errors::AppError;
+use crate::state::AppState;
+
+/// Most lines `/stream/{n}` will send.
+const MAX_STREAM_LINES: usize = 100;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct BytesQuery {
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DripQuery {
+    pub bytes: Option<usize>,
+    pub duration: Option<f64>,
+    pub delay: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct StreamLine<'a> {
+    id: usize,
+    #[serde(flatten)]
+    request: &'a GetResponse,
+}
+
+// GET /bytes/{n} — n random bytes (at most MAX_GENERATED_BYTES)
+#[cfg(feature = "random-data")]
+pub async fn bytes_handler(
+    Path(n): Path<usize>,
+    Query(query): Query<BytesQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/bytes/{n}").increment(1);
+    let mut bytes = vec![0; n.min(state.config.load().max_generated_bytes)];
+    match query.seed {
+        Some(seed) => StdRng::seed_from_u64(seed).fill_bytes(&mut bytes),
+        None => rand::rng().fill_bytes(&mut bytes),
+    }
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::from(bytes))
+        .map_err(|_| AppError::HttpBuilderError)
+}
+
+// GET /pad/{n} — a body of exactly n bytes, for path-MTU probing
+#[cfg(feature = "random-data")]
+pub async fn pad_handler(
+    Path(n): Path<usize>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/pad/{n}").increment(1);
+    let max = state.config.load().max_generated_bytes;
+    if n > max {
+        return Err(AppError::BadRequest(format!(
+            "{n} bytes is over MAX_GENERATED_BYTES ({max})"
+        )));
+    }
+    let mut bytes = vec![0; n];
+    rand::rng().fill_bytes(&mut bytes);
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .header(header::CACHE_CONTROL, "no-store, no-transform")
+        .body(Body::from(bytes))
+        .map_err(|_| AppError::HttpBuilderError)
+}
+
+// GET /stream/{n} — n JSON lines, one chunk each (at most 100)
+pub async fn stream_handler(
+    Path(n): Path<usize>,
+    ClientIp(ip): ClientIp,
+    State(state): State<Arc<AppState>>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/stream/{n}").increment(1);
+    let request = GetResponse::new(ip, &state, &uri, &headers);
+    let lines = (0..n.min(MAX_STREAM_LINES))
+        .map(|id| {
+            let mut line = serde_json::to_vec(&StreamLine {
+                id,
+                request: &request,
+            })?;
+            line.push(b'\n');
+            Ok(Bytes::from(line))
+        })
+        .collect::<Result<Vec<_>, serde_json::Error>>()?;
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/x-ndjson")
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::from_stream(stream::iter(
+            lines.into_iter().map(Ok::<_, Infallible>),
+        )))
+        .map_err(|_| AppError::HttpBuilderError)
+}
+
+// GET /drip?bytes=&duration=&delay= — bytes trickled out over duration
+// seconds, after delay seconds
+pub async fn drip_handler(
+    Query(query): Query<DripQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/drip").increment(1);
+    let config = state.config.load();
+    let max_secs = config.max_delay_secs as f64;
+    let bytes = query.bytes.unwrap_or(10).min(config.max_generated_bytes);
+    let duration = seconds("duration", query.duration.unwrap_or(2.0), max_secs)?;
+    let delay = seconds("delay", query.delay.unwrap_or(0.0), max_secs)?;
+
+    tokio::time::sleep(delay).await;
+    // Byte i is due at (i + 1) / bytes of the way through, so the timer's
+    // millisecond granularity can't stretch the total.
+    let start = Instant::now();
+    let drip = stream::iter(1..=bytes).then(move |i| async move {
+        let due = duration.mul_f64(i as f64 / bytes as f64);
+        tokio::time::sleep_until(start + due).await;
+        Ok::<_, Infallible>(Bytes::from_static(b"*"))
+    });
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .header(header::CONTENT_LENGTH, bytes)
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::from_stream(drip))
+        .map_err(|_| AppError::HttpBuilderError)
+}
+
+/// `?{name}=` as a duration, cut to `max` seconds.
+fn seconds(name: &str, secs: f64, max: f64) -> Result<Duration, AppError> {
+    if !secs.is_finite() || secs < 0.0 {
+        return Err(AppError::BadRequest(format!(
+            "{name} must be a number of seconds"
+        )));
+    }
+    Ok(Duration::from_secs_f64(secs.min(max)))
+}
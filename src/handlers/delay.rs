@@ -0,0 +1,36 @@
+//! `/delay/{n}`: the same JSON as `/`, sent after waiting `n` seconds, for
+//! testing client timeouts and how long proxies keep a quiet request open.
+//! The wait is capped at `MAX_DELAY_SECS`; a request waiting longer than
+//! `REQUEST_TIMEOUT_SECS` still gets a 408.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::extract::{ConnectInfo, Path, State};
+use axum::http::header::HeaderMap;
+use axum::http::{Response, Version};
+
+use super::echo::{build_echo_response, json_response};
+use crate::errors::AppError;
+use crate::listener::ConnectionDetails;
+use crate::state::AppState;
+
+// GET /delay/{n} — full client info as JSON, after n seconds (at most
+// MAX_DELAY_SECS)
+pub async fn delay_handler(
+    Path(secs): Path<u64>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    conn: ConnectionDetails,
+    State(state): State<Arc<AppState>>,
+    version: Version,
+    headers: HeaderMap,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/delay/{n}").increment(1);
+    let secs = secs.min(state.config.load().max_delay_secs);
+    tokio::time::sleep(Duration::from_secs(secs)).await;
+
+    let response = build_echo_response(&addr, conn, &state, version, &headers).await;
+    json_response(&response)
+}
@@ -0,0 +1,124 @@
+//! `/ds`: check that both IPv4 and IPv6 work, and compare the paths each
+//! leaves by.
+//!
+//! ```text
+//! token=$(curl -s https://host/ds/start | jq -r .token)
+//! curl -4 https://ipv4.host/ds/$token
+//! curl -6 https://ipv6.host/ds/$token
+//! curl https://host/ds/$token/result
+//! ```
+//!
+//! See [`crate::dual_stack`] for the flow; with `DUAL_STACK_ORIGIN` unset
+//! every `/ds` route is a 404.
+
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::{Path, State};
+use axum::http::Response;
+use serde::Serialize;
+
+use super::echo::{enrich, json_response, lookup_provider};
+use crate::client_ip::ClientIp;
+use crate::errors::AppError;
+use crate::geoip::AsnInfo;
+use crate::state::AppState;
+
+#[derive(Debug, Serialize)]
+pub struct DsStart {
+    pub token: String,
+    /// Fetch this over IPv4, from a name with only an A record.
+    pub ipv4_url: String,
+    /// Fetch this over IPv6, from a name with only an AAAA record.
+    pub ipv6_url: String,
+    pub result_url: String,
+    pub expires_in_secs: u64,
+}
+
+// GET /ds/start — a token for a dual-stack test
+pub async fn start_handler(State(state): State<Arc<AppState>>) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/ds/start").increment(1);
+    let ds = &state.dual_stack;
+    let token = ds.start()?;
+    json_response(&DsStart {
+        ipv4_url: ds.url("ipv4", &token)?,
+        ipv6_url: ds.url("ipv6", &token)?,
+        result_url: ds.result_url(&token)?,
+        expires_in_secs: ds.ttl().as_secs(),
+        token,
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct DsRecorded {
+    /// `ipv4` or `ipv6`.
+    pub family: &'static str,
+    pub ip: IpAddr,
+}
+
+// GET /ds/{token} — record the client's address under its family
+pub async fn record_handler(
+    State(state): State<Arc<AppState>>,
+    ClientIp(ip): ClientIp,
+    Path(token): Path<String>,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/ds/{token}").increment(1);
+    let ip = state.dual_stack.record(&token, ip)?;
+    json_response(&DsRecorded {
+        family: if ip.is_ipv4() { "ipv4" } else { "ipv6" },
+        ip,
+    })
+}
+
+/// Where one family's traffic came from.
+#[derive(Debug, Serialize)]
+pub struct Egress {
+    pub ip: IpAddr,
+    pub asn: Option<AsnInfo>,
+    /// Cloud provider, if the address is in a synced range.
+    pub provider: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DsResult {
+    pub token: String,
+    /// `null` until the IPv4 URL has been fetched.
+    pub ipv4: Option<Egress>,
+    pub ipv6: Option<Egress>,
+    /// Both families arrived.
+    pub dual_stack: bool,
+    /// Whether both left through the same autonomous system; `null` unless
+    /// both have ASN data.
+    pub same_asn: Option<bool>,
+}
+
+async fn egress(state: &AppState, ip: Option<IpAddr>) -> Option<Egress> {
+    let ip = ip?;
+    let (provider, _, _) = lookup_provider(state, ip).await;
+    Some(Egress {
+        ip,
+        asn: enrich(state, ip).await.asn,
+        provider,
+    })
+}
+
+// GET /ds/{token}/result — the addresses recorded for a token
+pub async fn result_handler(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/ds/{token}/result").increment(1);
+    let addresses = state.dual_stack.addresses(&token)?;
+    let ipv4 = egress(&state, addresses.ipv4).await;
+    let ipv6 = egress(&state, addresses.ipv6).await;
+    let asn = |e: &Option<Egress>| e.as_ref().and_then(|e| e.asn.as_ref()).map(|a| a.number);
+    let same_asn = asn(&ipv4).zip(asn(&ipv6)).map(|(a, b)| a == b);
+    json_response(&DsResult {
+        token,
+        dual_stack: ipv4.is_some() && ipv6.is_some(),
+        same_asn,
+        ipv4,
+        ipv6,
+    })
+}
@@ -2,26 +2,75 @@ use std::collections::BTreeMap;
 use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 
-use axum::extract::{ConnectInfo, Path, State};
+use axum::extract::{ConnectInfo, OriginalUri, Path, Query, State};
 use axum::http::header::HeaderMap;
-use axum::http::{header, Response, StatusCode};
+use axum::http::{header, Response, StatusCode, Version};
 use axum::body::Body;
-use serde::Serialize;
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
 
+use super::fetch_metadata::FetchMetadata;
+use super::hints;
+use super::privacy::PrivacySignals;
+
+use crate::addr::{AddressClass, CidrInfo, Ipv6Info};
+use crate::client_ip::{ClientIp, resolve_client_ip, resolve_client_port};
 use crate::errors::AppError;
+#[cfg(feature = "html")]
+use crate::format::render::Landing;
+use crate::format::render::{Jsonp, Renderer};
+use crate::format::{self, FormatQuery, JsonpQuery, ResponseFormat};
+use crate::forwarded::{self, ForwardedHop};
+use crate::geoip::{AsnInfo, Enrichment, GeoInfo};
+use crate::listener::client_hello::TlsFingerprint;
+use crate::listener::{ConnectionDetails, LocalAddr};
+use crate::providers;
 use crate::state::AppState;
+use crate::user_agent::{self, UserAgent};
 
 #[derive(Debug, Serialize)]
 pub struct EchoResponse {
     pub ip: String,
+    /// Whether `ip` is public, private, CGNAT, link-local, loopback or a bogon.
+    pub ip_class: AddressClass,
+    /// Socket address of the directly connected peer. Differs from `ip` when
+    /// the request came through a trusted proxy.
+    pub peer_addr: String,
+    /// Source port of the client. Behind a proxy only known if the
+    /// forwarding header carries it.
+    pub client_port: Option<u16>,
+    /// Port the connection was accepted on.
+    pub server_port: Option<u16>,
+    pub remote_host: Option<String>,
+    /// Protocol the request arrived over, e.g. `HTTP/1.1` or `HTTP/2`.
+    pub http_version: &'static str,
     pub provider: Option<String>,
     pub region: Option<String>,
     pub service: Option<String>,
+    /// Who runs the matched range, e.g. `Amazon Web Services`: a client
+    /// address here usually means a VPS, VPN or proxy egress.
+    pub hosting_provider: Option<String>,
+    /// GeoIP location, `null` without a database or for unknown addresses.
+    pub geo: Option<GeoInfo>,
+    /// Announcing autonomous system, `null` without an ASN database.
+    pub asn: Option<AsnInfo>,
+    /// Parsed RFC 7239 `Forwarded` chain, empty when the header is absent.
+    pub forwarded: Vec<ForwardedHop>,
+    /// JA3/JA4 of the TLS ClientHello; `null` over plain HTTP, including
+    /// when TLS ends at a proxy in front.
+    pub tls_fingerprint: Option<TlsFingerprint>,
+    /// Browser, OS and device type parsed from `User-Agent`; `null` when
+    /// absent, excluded or unrecognised.
+    pub user_agent: Option<UserAgent>,
+    /// `Sec-Fetch-*` headers; `null` when none were sent.
+    pub fetch_metadata: Option<FetchMetadata>,
+    /// Whether `DNT: 1` or `Sec-GPC: 1` was sent; details at `/privacy`.
+    pub privacy_opt_out: bool,
     pub headers: BTreeMap<String, String>,
 }
 
 struct EchoData {
-    ip: String,
+    ip: IpAddr,
     provider: Option<String>,
     region: Option<String>,
     service: Option<String>,
@@ -33,33 +82,11 @@ async fn build_echo_data(
     headers: &HeaderMap,
     state: &AppState,
 ) -> EchoData {
-    let client_ip = extract_client_ip(addr, headers, &state.config);
-
-    let header_map = filter_headers(headers, &state.config.excluded_headers);
-
-    let (provider, region, service) = {
-        let table = state.lookup_table.read().await;
-        match client_ip.parse::<IpAddr>() {
-            Ok(ip) => match table.lookup(ip) {
-                Some(entry) => {
-                    metrics::counter!("ip_lookup_total", "result" => "hit").increment(1);
-                    (
-                        Some(entry.provider.clone()),
-                        entry.region.clone(),
-                        entry.service.clone(),
-                    )
-                }
-                None => {
-                    metrics::counter!("ip_lookup_total", "result" => "miss").increment(1);
-                    (None, None, None)
-                }
-            },
-            Err(_) => {
-                metrics::counter!("ip_lookup_total", "result" => "miss").increment(1);
-                (None, None, None)
-            }
-        }
-    };
+    let client_ip = resolve_client_ip(addr.ip(), headers, &state.config.load());
+
+    let header_map = filter_headers(headers, &state.config.load().excluded_headers);
+
+    let (provider, region, service) = lookup_provider(state, client_ip).await;
 
     EchoData {
         ip: client_ip,
@@ -70,7 +97,36 @@ async fn build_echo_data(
     }
 }
 
-fn filter_headers(headers: &HeaderMap, excluded: &[String]) -> BTreeMap<String, String> {
+/// Provider, region and service for `ip`, if it is in a synced range.
+pub(super) async fn lookup_provider(
+    state: &AppState,
+    ip: IpAddr,
+) -> (Option<String>, Option<String>, Option<String>) {
+    let table = state.lookup_table.read().await;
+    match table.lookup(ip) {
+        Some(entry) => {
+            metrics::counter!("ip_lookup_total", "result" => "hit").increment(1);
+            (
+                Some(entry.provider.clone()),
+                entry.region.clone(),
+                entry.service.clone(),
+            )
+        }
+        None => {
+            metrics::counter!("ip_lookup_total", "result" => "miss").increment(1);
+            (None, None, None)
+        }
+    }
+}
+
+pub(super) async fn enrich(state: &AppState, ip: IpAddr) -> Enrichment {
+    match state.enricher.get() {
+        Some(enricher) => enricher.enrich(ip).await,
+        None => Enrichment::default(),
+    }
+}
+
+pub(super) fn filter_headers(headers: &HeaderMap, excluded: &[String]) -> BTreeMap<String, String> {
     let mut map = BTreeMap::new();
     for (name, value) in headers {
         let name_str = name.as_str();
@@ -84,7 +140,58 @@ fn filter_headers(headers: &HeaderMap, excluded: &[String]) -> BTreeMap<String,
     map
 }
 
-fn plain_text_response(body: String) -> Result<Response<Body>, AppError> {
+/// Request headers in order of first appearance, with repeated headers kept
+/// as separate values rather than collapsed.
+struct OrderedHeaders(Vec<(String, Vec<String>)>);
+
+impl OrderedHeaders {
+    fn from_request(headers: &HeaderMap, excluded: &[String]) -> Self {
+        let mut out = Vec::new();
+        for name in headers.keys() {
+            if excluded.iter().any(|e| e == name.as_str()) {
+                continue;
+            }
+            let values = headers
+                .get_all(name)
+                .iter()
+                .map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned())
+                .collect();
+            out.push((name.as_str().to_string(), values));
+        }
+        Self(out)
+    }
+
+    /// One `name: value` line per header value.
+    fn to_text(&self) -> String {
+        let mut out = String::new();
+        for (name, values) in &self.0 {
+            for value in values {
+                out.push_str(name);
+                out.push_str(": ");
+                out.push_str(value);
+                out.push('\n');
+            }
+        }
+        out
+    }
+}
+
+/// A JSON object in request order; a header sent more than once maps to an
+/// array of its values.
+impl Serialize for OrderedHeaders {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (name, values) in &self.0 {
+            match values.as_slice() {
+                [single] => map.serialize_entry(name, single)?,
+                _ => map.serialize_entry(name, values)?,
+            }
+        }
+        map.end()
+    }
+}
+
+pub(super) fn plain_text_response(body: String) -> Result<Response<Body>, AppError> {
     Response::builder()
         .status(StatusCode::OK)
         .header(header::CONTENT_TYPE, "text/plain")
@@ -93,6 +200,102 @@ fn plain_text_response(body: String) -> Result<Response<Body>, AppError> {
         .map_err(|_| AppError::HttpBuilderError)
 }
 
+pub(super) fn json_response<T: Serialize>(value: &T) -> Result<Response<Body>, AppError> {
+    let body = serde_json::to_string_pretty(value)?;
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::from(body))
+        .map_err(|_| AppError::HttpBuilderError)
+}
+
+/// Render `value` in `format` (see [`ResponseFormat::renderer`]).
+pub(super) fn negotiated_response<T: Serialize>(
+    format: ResponseFormat,
+    title: &str,
+    value: &T,
+    text: Option<String>,
+) -> Result<Response<Body>, AppError> {
+    rendered_response(format.renderer(), title, value, text)
+}
+
+/// Like [`negotiated_response`], for a renderer picked by the handler.
+fn rendered_response<T: Serialize>(
+    renderer: &dyn Renderer,
+    title: &str,
+    value: &T,
+    text: Option<String>,
+) -> Result<Response<Body>, AppError> {
+    let body = format::render_with(renderer, title, value, text)?;
+
+    let mut builder = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, renderer.content_type())
+        .header(header::CACHE_CONTROL, "no-store")
+        .header(header::VARY, "accept");
+    // Pages a browser opens ask for the full client hints for `/hints`.
+    if renderer.content_type().starts_with("text/html") {
+        builder = builder.header("accept-ch", hints::ACCEPT_CH);
+    }
+    builder
+        .body(Body::from(body))
+        .map_err(|_| AppError::HttpBuilderError)
+}
+
+/// The full echo response in `format`; HTML is the landing page rather
+/// than the generic table, if built with the `html` feature. Its links
+/// start with `base`, so they stay under the router's mount point.
+fn echo_format_response(
+    format: ResponseFormat,
+    response: &EchoResponse,
+    base: &str,
+) -> Result<Response<Body>, AppError> {
+    #[cfg(feature = "html")]
+    if format == ResponseFormat::Html {
+        return rendered_response(&Landing { base }, "ipecho", response, None);
+    }
+    #[cfg(not(feature = "html"))]
+    let _ = base;
+    negotiated_response(format, "ipecho", response, None)
+}
+
+/// `value` as JSONP, calling `callback` (validated here).
+pub(super) fn jsonp_response<T: Serialize>(callback: &str, value: &T) -> Result<Response<Body>, AppError> {
+    let renderer = Jsonp {
+        callback: format::jsonp_callback(callback)?,
+    };
+    let body = format::render_with(&renderer, "", value, None)?;
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, renderer.content_type())
+        .header(header::CACHE_CONTROL, "no-store")
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .body(Body::from(body))
+        .map_err(|_| AppError::HttpBuilderError)
+}
+
+/// Single-value endpoints: the bare value as plain text, or `{field: value}`
+/// in structured formats. 204 when the value is unknown.
+pub(super) fn field_response(
+    format: ResponseFormat,
+    field: &str,
+    value: Option<serde_json::Value>,
+) -> Result<Response<Body>, AppError> {
+    let Some(value) = value else {
+        return optional_plain_text_response(None);
+    };
+    let text = match &value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    let mut object = serde_json::Map::new();
+    object.insert(field.to_string(), value);
+    negotiated_response(format, field, &object, Some(text))
+}
+
 fn optional_plain_text_response(value: Option<String>) -> Result<Response<Body>, AppError> {
     match value {
         Some(v) => plain_text_response(v),
@@ -104,221 +307,268 @@ fn optional_plain_text_response(value: Option<String>) -> Result<Response<Body>,
     }
 }
 
-// GET / — full JSON response
-pub async fn echo_handler(
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
-    State(state): State<Arc<AppState>>,
-    headers: HeaderMap,
-) -> Result<Response<Body>, AppError> {
-    metrics::counter!("http_requests_total", "endpoint" => "/").increment(1);
-
-    let data = build_echo_data(&addr, &headers, &state).await;
+/// Display form of the negotiated HTTP version.
+pub(super) fn http_version_str(version: Version) -> &'static str {
+    match version {
+        Version::HTTP_09 => "HTTP/0.9",
+        Version::HTTP_10 => "HTTP/1.0",
+        Version::HTTP_11 => "HTTP/1.1",
+        Version::HTTP_2 => "HTTP/2",
+        Version::HTTP_3 => "HTTP/3",
+        _ => "unknown",
+    }
+}
 
-    let response = EchoResponse {
-        ip: data.ip,
+pub(super) async fn build_echo_response(
+    addr: &SocketAddr,
+    conn: ConnectionDetails,
+    state: &AppState,
+    version: Version,
+    headers: &HeaderMap,
+) -> EchoResponse {
+    let data = build_echo_data(addr, headers, state).await;
+    let remote_host = state.reverse_dns.lookup(data.ip).await;
+    let Enrichment { geo, asn } = enrich(state, data.ip).await;
+    let hosting_provider = data
+        .provider
+        .as_deref()
+        .map(|p| providers::hosting_provider(p).to_string());
+
+    EchoResponse {
+        ip: data.ip.to_string(),
+        ip_class: AddressClass::of(data.ip),
+        peer_addr: addr.to_string(),
+        client_port: resolve_client_port(*addr, headers, &state.config.load()),
+        server_port: conn.local.map(|LocalAddr(local)| local.port()),
+        remote_host,
+        http_version: http_version_str(version),
         provider: data.provider,
         region: data.region,
         service: data.service,
+        hosting_provider,
+        geo,
+        asn,
+        forwarded: forwarded_hops(headers, state),
+        tls_fingerprint: conn.tls,
+        user_agent: parsed_user_agent(headers, state),
+        fetch_metadata: Some(FetchMetadata::of(headers, &state.config.load()))
+            .filter(|m| !m.is_empty()),
+        privacy_opt_out: PrivacySignals::of(headers, &state.config.load()).opted_out,
         headers: data.headers,
-    };
-
-    let body = serde_json::to_string_pretty(&response)?;
-
-    Response::builder()
-        .status(StatusCode::OK)
-        .header(header::CONTENT_TYPE, "application/json")
-        .header(header::CACHE_CONTROL, "no-store")
-        .body(Body::from(body))
-        .map_err(|_| AppError::HttpBuilderError)
+    }
 }
 
-// GET /ip — plain text IP address
-pub async fn ip_handler(
+// GET / — full client info (JSON by default, the bare IP for command-line
+// clients; other formats via Accept or ?format=)
+pub async fn echo_handler(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    conn: ConnectionDetails,
     State(state): State<Arc<AppState>>,
+    Query(query): Query<FormatQuery>,
+    version: Version,
     headers: HeaderMap,
+    OriginalUri(original): OriginalUri,
 ) -> Result<Response<Body>, AppError> {
-    metrics::counter!("http_requests_total", "endpoint" => "/ip").increment(1);
-    let ip = extract_client_ip(&addr, &headers, &state.config);
-    plain_text_response(ip)
+    metrics::counter!("http_requests_total", "endpoint" => "/").increment(1);
+    let cli = format::is_cli_client(&headers);
+    let default = if cli {
+        ResponseFormat::Text
+    } else {
+        ResponseFormat::Json
+    };
+    let format = ResponseFormat::select(&query, &headers, default)?;
+
+    let response = build_echo_response(&addr, conn, &state, version, &headers).await;
+    let mut http_response = if cli && format == ResponseFormat::Text {
+        negotiated_response(format, "ipecho", &response, Some(response.ip.clone()))?
+    } else {
+        // This is the router's root, so the full path is where it's mounted.
+        let base = original.path().trim_end_matches('/');
+        echo_format_response(format, &response, base)?
+    };
+    http_response.headers_mut().insert(
+        header::VARY,
+        header::HeaderValue::from_static("accept, user-agent"),
+    );
+    Ok(http_response)
 }
 
-// GET /provider — plain text provider or 204
-pub async fn provider_handler(
+// GET /all.{format} — full client info in the format named by the extension
+// (e.g. /all.xml), for clients that can only vary the URL
+pub async fn echo_with_extension_handler(
+    Path(extension): Path<String>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    conn: ConnectionDetails,
     State(state): State<Arc<AppState>>,
+    Query(jsonp): Query<JsonpQuery>,
+    version: Version,
     headers: HeaderMap,
 ) -> Result<Response<Body>, AppError> {
-    metrics::counter!("http_requests_total", "endpoint" => "/provider").increment(1);
-    let data = build_echo_data(&addr, &headers, &state).await;
-    optional_plain_text_response(data.provider)
+    metrics::counter!("http_requests_total", "endpoint" => "/all.{format}").increment(1);
+    let format = ResponseFormat::from_name(&extension)
+        .ok_or_else(|| AppError::NotFound(format!("unknown format \"{extension}\"")))?;
+
+    let response = build_echo_response(&addr, conn, &state, version, &headers).await;
+    if format == ResponseFormat::Json
+        && let Some(callback) = jsonp.callback
+    {
+        return jsonp_response(&callback, &response);
+    }
+    // A sibling of the API routes, so relative links reach them.
+    echo_format_response(format, &response, ".")
 }
 
-// GET /region — plain text region or 204
-pub async fn region_handler(
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+// GET /geo — GeoIP location (JSON by default; other formats via Accept or
+// ?format=), or 204 without a database or match
+pub async fn geo_handler(
+    ClientIp(ip): ClientIp,
     State(state): State<Arc<AppState>>,
+    Query(query): Query<FormatQuery>,
     headers: HeaderMap,
 ) -> Result<Response<Body>, AppError> {
-    metrics::counter!("http_requests_total", "endpoint" => "/region").increment(1);
-    let data = build_echo_data(&addr, &headers, &state).await;
-    optional_plain_text_response(data.region)
+    metrics::counter!("http_requests_total", "endpoint" => "/geo").increment(1);
+    let format = ResponseFormat::select(&query, &headers, ResponseFormat::Json)?;
+    match enrich(&state, ip).await.geo {
+        Some(geo) => negotiated_response(format, "geo", &geo, None),
+        None => optional_plain_text_response(None),
+    }
 }
 
-// GET /service — plain text service or 204
-pub async fn service_handler(
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+// GET /asn — AS number, organization and announced prefix (JSON by default),
+// or 204 without a database or match
+pub async fn asn_handler(
+    ClientIp(ip): ClientIp,
     State(state): State<Arc<AppState>>,
+    Query(query): Query<FormatQuery>,
     headers: HeaderMap,
 ) -> Result<Response<Body>, AppError> {
-    metrics::counter!("http_requests_total", "endpoint" => "/service").increment(1);
-    let data = build_echo_data(&addr, &headers, &state).await;
-    optional_plain_text_response(data.service)
+    metrics::counter!("http_requests_total", "endpoint" => "/asn").increment(1);
+    let format = ResponseFormat::select(&query, &headers, ResponseFormat::Json)?;
+    match enrich(&state, ip).await.asn {
+        Some(asn) => negotiated_response(format, "asn", &asn, None),
+        None => optional_plain_text_response(None),
+    }
 }
 
-// GET /headers — pretty JSON of all headers
+// GET /ipv6/info — scope, interface ID kind and embedded IPv4 address of
+// an IPv6 client (JSON by default), or 204 for IPv4 clients
+pub async fn ipv6_info_handler(
+    ClientIp(ip): ClientIp,
+    Query(query): Query<FormatQuery>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/ipv6/info").increment(1);
+    let format = ResponseFormat::select(&query, &headers, ResponseFormat::Json)?;
+    match ip.to_canonical() {
+        IpAddr::V6(ip) => negotiated_response(format, "ipv6", &Ipv6Info::new(ip), None),
+        IpAddr::V4(_) => optional_plain_text_response(None),
+    }
+}
+
+// GET /cidr/{prefix} — network, broadcast, host range and size of a prefix
+// such as 203.0.113.5/28 (JSON by default), or 400 if it doesn't parse
+pub async fn cidr_handler(
+    Path(prefix): Path<String>,
+    Query(query): Query<FormatQuery>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/cidr/{prefix}").increment(1);
+    let format = ResponseFormat::select(&query, &headers, ResponseFormat::Json)?;
+    let info = CidrInfo::parse(&prefix)
+        .ok_or_else(|| AppError::BadRequest(format!("invalid CIDR prefix: {prefix}")))?;
+    negotiated_response(format, "cidr", &info, None)
+}
+
+// GET /headers — all headers as `name: value` lines, in request order
 pub async fn headers_handler(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
 ) -> Result<Response<Body>, AppError> {
     metrics::counter!("http_requests_total", "endpoint" => "/headers").increment(1);
 
-    let header_map = filter_headers(&headers, &state.config.excluded_headers);
-
-    let body = serde_json::to_string_pretty(&header_map)?;
+    let ordered = OrderedHeaders::from_request(&headers, &state.config.load().excluded_headers);
 
-    Response::builder()
-        .status(StatusCode::OK)
-        .header(header::CONTENT_TYPE, "application/json")
-        .header(header::CACHE_CONTROL, "no-store")
-        .body(Body::from(body))
-        .map_err(|_| AppError::HttpBuilderError)
+    plain_text_response(ordered.to_text())
 }
 
-// GET /headers/:name — single header value or 404
-pub async fn header_by_name_handler(
-    Path(name): Path<String>,
+// GET /headers.json — all headers as a JSON object, in request order
+pub async fn headers_json_handler(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
 ) -> Result<Response<Body>, AppError> {
-    metrics::counter!("http_requests_total", "endpoint" => "/headers/{name}").increment(1);
-
-    let name_lower = name.to_lowercase();
-
-    if state.config.is_header_excluded(&name_lower) {
-        return Err(AppError::NotFound("header not found".to_string()));
-    }
+    metrics::counter!("http_requests_total", "endpoint" => "/headers.json").increment(1);
 
-    for (key, value) in &headers {
-        if key.as_str() == name_lower {
-            if let Ok(v) = value.to_str() {
-                return plain_text_response(v.to_string());
-            }
-        }
-    }
+    let ordered = OrderedHeaders::from_request(&headers, &state.config.load().excluded_headers);
 
-    Err(AppError::NotFound("header not found".to_string()))
+    json_response(&ordered)
 }
 
-fn extract_client_ip(
-    addr: &SocketAddr,
-    headers: &HeaderMap,
-    config: &crate::config::Config,
-) -> String {
-    let peer_ip = addr.ip();
-
-    if config.is_trusted_proxy(&peer_ip) {
-        // Try X-Forwarded-For first (leftmost = original client)
-        if let Some(xff) = headers.get("x-forwarded-for").and_then(|v| v.to_str().ok()) {
-            if let Some(first_ip) = xff.split(',').next() {
-                let trimmed = first_ip.trim();
-                if !trimmed.is_empty() {
-                    return trimmed.to_string();
-                }
-            }
-        }
-
-        // Try X-Real-IP
-        if let Some(real_ip) = headers.get("x-real-ip").and_then(|v| v.to_str().ok()) {
-            let trimmed = real_ip.trim();
-            if !trimmed.is_empty() {
-                return trimmed.to_string();
-            }
-        }
+/// Parsed `User-Agent`, or none if the operator has hidden the header via
+/// `EXCLUDED_HEADERS`.
+fn parsed_user_agent(headers: &HeaderMap, state: &AppState) -> Option<UserAgent> {
+    if state.config.load().is_header_excluded("user-agent") {
+        return None;
     }
-
-    peer_ip.to_string()
+    user_agent::parse(headers.get(header::USER_AGENT)?.to_str().ok()?)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::config::Config;
-
-    fn test_config() -> Config {
-        Config {
-            port: 8083,
-            sync_interval_secs: 43200,
-            log_level: "info".to_string(),
-            trusted_proxies: vec!["10.0.0.0/8".parse().unwrap()],
-            rate_limit_per_second: 10,
-            rate_limit_burst: 20,
-            excluded_headers: vec![],
-        }
+/// Parsed `Forwarded` hops, or none if the operator has hidden the header
+/// via `EXCLUDED_HEADERS`.
+fn forwarded_hops(headers: &HeaderMap, state: &AppState) -> Vec<ForwardedHop> {
+    if state.config.load().is_header_excluded("forwarded") {
+        return Vec::new();
     }
+    forwarded::parse_headers(headers)
+}
 
-    #[test]
-    fn test_extract_ip_direct_connection() {
-        let config = test_config();
-        let addr: SocketAddr = "203.0.113.1:12345".parse().unwrap();
-        let headers = HeaderMap::new();
+// GET /forwarded — raw Forwarded header(s) or 204
+pub async fn forwarded_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/forwarded").increment(1);
 
-        let ip = extract_client_ip(&addr, &headers, &config);
-        assert_eq!(ip, "203.0.113.1");
+    if state.config.load().is_header_excluded("forwarded") {
+        return optional_plain_text_response(None);
     }
+    let values: Vec<&str> = headers
+        .get_all("forwarded")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .collect();
+    optional_plain_text_response((!values.is_empty()).then(|| values.join(", ")))
+}
 
-    #[test]
-    fn test_extract_ip_xff_from_trusted_proxy() {
-        let config = test_config();
-        let addr: SocketAddr = "10.0.0.1:12345".parse().unwrap();
-        let mut headers = HeaderMap::new();
-        headers.insert("x-forwarded-for", "203.0.113.50, 10.0.0.1".parse().unwrap());
+// GET /forwarded.json — parsed Forwarded hops as a JSON array
+pub async fn forwarded_json_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/forwarded.json").increment(1);
+    json_response(&forwarded_hops(&headers, &state))
+}
 
-        let ip = extract_client_ip(&addr, &headers, &config);
-        assert_eq!(ip, "203.0.113.50");
-    }
+// GET /headers/:name — single header value or 404
+pub async fn header_by_name_handler(
+    Path(name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/headers/{name}").increment(1);
 
-    #[test]
-    fn test_extract_ip_xff_from_untrusted_ignored() {
-        let config = test_config();
-        let addr: SocketAddr = "203.0.113.1:12345".parse().unwrap();
-        let mut headers = HeaderMap::new();
-        headers.insert("x-forwarded-for", "1.2.3.4".parse().unwrap());
+    let name_lower = name.to_lowercase();
 
-        let ip = extract_client_ip(&addr, &headers, &config);
-        assert_eq!(ip, "203.0.113.1");
+    if state.config.load().is_header_excluded(&name_lower) {
+        return Err(AppError::NotFound("header not found".to_string()));
     }
 
-    #[test]
-    fn test_extract_ip_x_real_ip_from_trusted() {
-        let config = test_config();
-        let addr: SocketAddr = "10.0.0.1:12345".parse().unwrap();
-        let mut headers = HeaderMap::new();
-        headers.insert("x-real-ip", "203.0.113.99".parse().unwrap());
-
-        let ip = extract_client_ip(&addr, &headers, &config);
-        assert_eq!(ip, "203.0.113.99");
+    for (key, value) in &headers {
+        if key.as_str() == name_lower
+            && let Ok(v) = value.to_str()
+        {
+            return plain_text_response(v.to_string());
+        }
     }
 
-    #[test]
-    fn test_xff_takes_priority_over_x_real_ip() {
-        let config = test_config();
-        let addr: SocketAddr = "10.0.0.1:12345".parse().unwrap();
-        let mut headers = HeaderMap::new();
-        headers.insert("x-forwarded-for", "1.1.1.1".parse().unwrap());
-        headers.insert("x-real-ip", "2.2.2.2".parse().unwrap());
-
-        let ip = extract_client_ip(&addr, &headers, &config);
-        assert_eq!(ip, "1.1.1.1");
-    }
+    Err(AppError::NotFound("header not found".to_string()))
 }
@@ -0,0 +1,121 @@
+//! Base64 helpers, for decoding a token or header value without leaving
+//! curl:
+//!
+//! - `/base64/encode/{value}` and `POST /base64/encode` encode the path
+//!   value or the request body.
+//! - `/base64/decode/{value}` and `POST /base64/decode` decode one.
+//!
+//! `?alphabet=url` switches from the standard alphabet to the URL-safe one
+//! (RFC 4648 §5). Encoding pads with `=`; decoding accepts input with or
+//! without padding. Decoded bytes come back as `text/plain` when they are
+//! UTF-8 and `application/octet-stream` otherwise.
+
+use axum::body::{Body, Bytes};
+use axum::extract::{Path, Query};
+use axum::http::header;
+use axum::http::{Response, StatusCode};
+use base64::Engine;
+use base64::engine::DecodePaddingMode;
+use base64::engine::general_purpose::{GeneralPurpose, GeneralPurposeConfig};
+use serde::Deserialize;
+
+use super::echo::plain_text_response;
+use crate::errors::AppError;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AlphabetQuery {
+    pub alphabet: Option<String>,
+}
+
+/// Pad when encoding, but accept input with or without padding.
+const LENIENT: GeneralPurposeConfig =
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+const STANDARD: GeneralPurpose = GeneralPurpose::new(&base64::alphabet::STANDARD, LENIENT);
+const URL_SAFE: GeneralPurpose = GeneralPurpose::new(&base64::alphabet::URL_SAFE, LENIENT);
+
+impl AlphabetQuery {
+    fn engine(&self) -> Result<&'static GeneralPurpose, AppError> {
+        match self.alphabet.as_deref() {
+            None | Some("standard") => Ok(&STANDARD),
+            Some("url") => Ok(&URL_SAFE),
+            Some(other) => Err(AppError::BadRequest(format!(
+                "unknown alphabet \"{other}\"; use standard or url"
+            ))),
+        }
+    }
+}
+
+// GET /base64/encode/{value} — the value, base64-encoded
+pub async fn encode_path_handler(
+    Path(value): Path<String>,
+    Query(query): Query<AlphabetQuery>,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/base64/encode").increment(1);
+    plain_text_response(query.engine()?.encode(value))
+}
+
+// POST /base64/encode — the request body, base64-encoded
+pub async fn encode_body_handler(
+    Query(query): Query<AlphabetQuery>,
+    body: Bytes,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/base64/encode").increment(1);
+    plain_text_response(query.engine()?.encode(body))
+}
+
+// GET /base64/decode/{value} — the value, base64-decoded
+pub async fn decode_path_handler(
+    Path(value): Path<String>,
+    Query(query): Query<AlphabetQuery>,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/base64/decode").increment(1);
+    decoded_response(query.engine()?, value.as_bytes())
+}
+
+// POST /base64/decode — the request body, base64-decoded
+pub async fn decode_body_handler(
+    Query(query): Query<AlphabetQuery>,
+    body: Bytes,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/base64/decode").increment(1);
+    decoded_response(query.engine()?, &body)
+}
+
+fn decoded_response(engine: &GeneralPurpose, encoded: &[u8]) -> Result<Response<Body>, AppError> {
+    // Tolerate the newline `echo` and most editors add.
+    let decoded = engine
+        .decode(encoded.trim_ascii())
+        .map_err(|e| AppError::BadRequest(format!("invalid base64: {e}")))?;
+    let content_type = if std::str::from_utf8(&decoded).is_ok() {
+        "text/plain"
+    } else {
+        "application/octet-stream"
+    };
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::from(decoded))
+        .map_err(|_| AppError::HttpBuilderError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alphabets_differ_in_two_characters() {
+        let data = [0xfb, 0xff, 0xbf];
+        assert_eq!(STANDARD.encode(data), "+/+/");
+        assert_eq!(URL_SAFE.encode(data), "-_-_");
+        assert_eq!(STANDARD.encode(b"hi"), "aGk=");
+    }
+
+    #[test]
+    fn padding_is_optional_when_decoding() {
+        assert_eq!(STANDARD.decode("aGk=").unwrap(), b"hi");
+        assert_eq!(STANDARD.decode("aGk").unwrap(), b"hi");
+        assert!(STANDARD.decode("-_-_").is_err());
+        assert_eq!(URL_SAFE.decode("-_-_").unwrap(), [0xfb, 0xff, 0xbf]);
+    }
+}
@@ -0,0 +1,109 @@
+//! `/fetch-metadata`: the Fetch Metadata request headers (`Sec-Fetch-*`).
+//!
+//! Browsers attach these to every request to say where it came from and
+//! what it's for: `site` is `same-origin`, `same-site`, `cross-site` or
+//! `none` (typed into the address bar, a bookmark), `mode` `navigate`,
+//! `cors`, `no-cors` or `websocket`, `dest` the kind of resource (`document`,
+//! `image`, `script`, `empty` for fetch()...), and `user` whether a user
+//! activation triggered a navigation. Loading this endpoint from a page,
+//! script or iframe on another origin shows how the browser classifies it,
+//! which is what a resource-isolation policy on that origin would see.
+
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::Response;
+use axum::http::header::HeaderMap;
+use serde::Serialize;
+
+use super::echo::json_response;
+use crate::config::Config;
+use crate::errors::AppError;
+use crate::state::AppState;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FetchMetadata {
+    /// `Sec-Fetch-Site`.
+    pub site: Option<String>,
+    /// `Sec-Fetch-Mode`.
+    pub mode: Option<String>,
+    /// `Sec-Fetch-Dest`.
+    pub dest: Option<String>,
+    /// `Sec-Fetch-User`; browsers only send it (as `?1`) for navigations
+    /// the user started.
+    pub user: Option<bool>,
+}
+
+impl FetchMetadata {
+    /// The `Sec-Fetch-*` headers of a request, leaving out any in
+    /// `EXCLUDED_HEADERS`.
+    pub fn of(headers: &HeaderMap, config: &Config) -> Self {
+        let get = |name: &str| {
+            if config.is_header_excluded(name) {
+                return None;
+            }
+            let value = headers.get(name)?.to_str().ok()?.trim();
+            Some(value.to_string())
+        };
+        Self {
+            site: get("sec-fetch-site"),
+            mode: get("sec-fetch-mode"),
+            dest: get("sec-fetch-dest"),
+            user: get("sec-fetch-user").map(|v| v == "?1"),
+        }
+    }
+
+    /// Whether the request carried none of the headers, as from anything
+    /// but a browser.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+// GET /fetch-metadata — how the browser classified this request
+pub async fn fetch_metadata_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/fetch-metadata").increment(1);
+    json_response(&FetchMetadata::of(&headers, &state.config.load()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.parse().unwrap(), v.parse().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn reads_and_excludes_headers() {
+        let navigation = headers(&[
+            ("sec-fetch-site", "none"),
+            ("sec-fetch-mode", "navigate"),
+            ("sec-fetch-dest", "document"),
+            ("sec-fetch-user", "?1"),
+        ]);
+        let mut config = Config::default();
+        assert_eq!(
+            FetchMetadata::of(&navigation, &config),
+            FetchMetadata {
+                site: Some("none".into()),
+                mode: Some("navigate".into()),
+                dest: Some("document".into()),
+                user: Some(true),
+            }
+        );
+
+        config.excluded_headers = vec!["sec-fetch-site".into()];
+        let metadata = FetchMetadata::of(&navigation, &config);
+        assert_eq!(metadata.site, None);
+        assert!(!metadata.is_empty());
+        assert!(FetchMetadata::of(&HeaderMap::new(), &config).is_empty());
+    }
+}
@@ -0,0 +1,337 @@
+//! Single-value endpoints (`/ip`, `/ua`, ...), generated from [`FIELDS`].
+//!
+//! Each field gets two routes: `/{path}`, which returns the bare value as
+//! plain text by default (other formats via `Accept` or `?format=`, 204
+//! when unknown), and `/{path}.json`, which always returns `{key: value}`
+//! (`null` when unknown) and supports JSONP via `?callback=`. Headers with
+//! structure (`/ua.json`, `/lang.json`, `/mime.json`) add their parsed
+//! parts as further keys. Adding a field means adding a descriptor here —
+//! no new handlers.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::Router;
+use axum::body::Body;
+use axum::extract::{ConnectInfo, Query, State};
+use axum::http::header::HeaderMap;
+use axum::http::{Response, Version, header};
+use axum::routing::get;
+use serde_json::Value;
+
+use super::echo::{
+    enrich, field_response, http_version_str, json_response, jsonp_response, lookup_provider,
+};
+use crate::accept_language;
+use crate::addr::{self, AddressClass};
+use crate::client_ip::{resolve_client_ip, resolve_client_port};
+use crate::config::Config;
+use crate::errors::AppError;
+use crate::format::{FormatQuery, JsonpQuery, ResponseFormat};
+use crate::media_type;
+use crate::providers;
+use crate::state::AppState;
+use crate::user_agent;
+
+/// Where a field's value comes from.
+enum Source {
+    /// The client IP, after trusted-proxy resolution.
+    Ip,
+    /// The client IP's [`AddressClass`].
+    IpClass,
+    /// The client IP in another notation, see [`addr`].
+    IpDecimal,
+    IpHex,
+    IpPtr,
+    IpExpanded,
+    /// The client's source port, see [`resolve_client_port`].
+    Port,
+    /// PTR record for the client IP.
+    Host,
+    HttpVersion,
+    Provider,
+    Region,
+    Service,
+    /// Display name of the provider, see [`providers::hosting_provider`].
+    Datacenter,
+    /// GeoIP country code and city name.
+    Country,
+    City,
+    /// Organization of the announcing AS.
+    Isp,
+    /// A request header, subject to `EXCLUDED_HEADERS`.
+    Header(header::HeaderName),
+    /// A header as above, whose `/{path}.json` also has the keys
+    /// [`Details`] derives from its value.
+    ParsedHeader(header::HeaderName, Details),
+}
+
+/// The parsed parts of a header value, as JSON object entries.
+type Details = fn(&str, &Config) -> serde_json::Map<String, Value>;
+
+fn language_details(raw: &str, config: &Config) -> serde_json::Map<String, Value> {
+    let ranges = accept_language::parse(raw);
+    let best = accept_language::best_match(&ranges, &config.supported_languages);
+    let mut details = serde_json::Map::new();
+    details.insert(
+        "languages".to_string(),
+        serde_json::to_value(&ranges).unwrap_or_default(),
+    );
+    details.insert("best_match".to_string(), best.into());
+    details
+}
+
+fn media_type_details(raw: &str, _config: &Config) -> serde_json::Map<String, Value> {
+    let mut details = serde_json::Map::new();
+    details.insert(
+        "media_types".to_string(),
+        serde_json::to_value(media_type::parse(raw)).unwrap_or_default(),
+    );
+    details
+}
+
+fn user_agent_details(raw: &str, _config: &Config) -> serde_json::Map<String, Value> {
+    match serde_json::to_value(user_agent::parse(raw).unwrap_or_default()) {
+        Ok(Value::Object(parsed)) => parsed,
+        _ => serde_json::Map::new(),
+    }
+}
+
+pub struct Field {
+    /// Route path without the leading slash.
+    pub path: &'static str,
+    /// Key in structured output, matching the field name in `/`.
+    pub key: &'static str,
+    source: Source,
+}
+
+pub const FIELDS: &[Field] = &[
+    Field {
+        path: "ip",
+        key: "ip",
+        source: Source::Ip,
+    },
+    Field {
+        path: "ip/class",
+        key: "ip_class",
+        source: Source::IpClass,
+    },
+    Field {
+        path: "ip/decimal",
+        key: "ip_decimal",
+        source: Source::IpDecimal,
+    },
+    Field {
+        path: "ip/hex",
+        key: "ip_hex",
+        source: Source::IpHex,
+    },
+    Field {
+        path: "ip/ptr",
+        key: "ip_ptr",
+        source: Source::IpPtr,
+    },
+    Field {
+        path: "ip/expanded",
+        key: "ip_expanded",
+        source: Source::IpExpanded,
+    },
+    Field {
+        path: "port",
+        key: "client_port",
+        source: Source::Port,
+    },
+    Field {
+        path: "host",
+        key: "remote_host",
+        source: Source::Host,
+    },
+    Field {
+        path: "proto",
+        key: "http_version",
+        source: Source::HttpVersion,
+    },
+    Field {
+        path: "provider",
+        key: "provider",
+        source: Source::Provider,
+    },
+    Field {
+        path: "region",
+        key: "region",
+        source: Source::Region,
+    },
+    Field {
+        path: "service",
+        key: "service",
+        source: Source::Service,
+    },
+    Field {
+        path: "datacenter",
+        key: "hosting_provider",
+        source: Source::Datacenter,
+    },
+    Field {
+        path: "country",
+        key: "country_code",
+        source: Source::Country,
+    },
+    Field {
+        path: "city",
+        key: "city",
+        source: Source::City,
+    },
+    Field {
+        path: "isp",
+        key: "isp",
+        source: Source::Isp,
+    },
+    Field {
+        path: "ua",
+        key: "user_agent",
+        source: Source::ParsedHeader(header::USER_AGENT, user_agent_details),
+    },
+    Field {
+        path: "lang",
+        key: "accept_language",
+        source: Source::ParsedHeader(header::ACCEPT_LANGUAGE, language_details),
+    },
+    Field {
+        path: "encoding",
+        key: "accept_encoding",
+        source: Source::Header(header::ACCEPT_ENCODING),
+    },
+    Field {
+        path: "mime",
+        key: "accept",
+        source: Source::ParsedHeader(header::ACCEPT, media_type_details),
+    },
+    Field {
+        path: "charset",
+        key: "accept_charset",
+        source: Source::Header(header::ACCEPT_CHARSET),
+    },
+];
+
+/// The request parts any field may need.
+struct FieldRequest {
+    addr: SocketAddr,
+    state: Arc<AppState>,
+    version: Version,
+    headers: HeaderMap,
+}
+
+impl Field {
+    async fn value(&self, req: &FieldRequest) -> Option<Value> {
+        let ip = || resolve_client_ip(req.addr.ip(), &req.headers, &req.state.config.load());
+        let value: Option<String> = match &self.source {
+            Source::Ip => Some(ip().to_string()),
+            Source::IpClass => Some(AddressClass::of(ip()).as_str().to_string()),
+            Source::IpDecimal => Some(addr::decimal(ip())),
+            Source::IpHex => Some(addr::hex(ip())),
+            Source::IpPtr => Some(addr::ptr_name(ip())),
+            Source::IpExpanded => addr::expanded(ip()),
+            Source::Port => {
+                return resolve_client_port(req.addr, &req.headers, &req.state.config.load())
+                    .map(Value::from);
+            }
+            Source::Host => req.state.reverse_dns.lookup(ip()).await,
+            Source::HttpVersion => Some(http_version_str(req.version).to_string()),
+            Source::Provider => lookup_provider(&req.state, ip()).await.0,
+            Source::Region => lookup_provider(&req.state, ip()).await.1,
+            Source::Service => lookup_provider(&req.state, ip()).await.2,
+            Source::Datacenter => lookup_provider(&req.state, ip())
+                .await
+                .0
+                .map(|p| providers::hosting_provider(&p).to_string()),
+            Source::Country => enrich(&req.state, ip()).await.geo?.country_code,
+            Source::City => enrich(&req.state, ip()).await.geo?.city,
+            Source::Isp => enrich(&req.state, ip()).await.asn?.organization,
+            Source::Header(name) | Source::ParsedHeader(name, _) => {
+                if req.state.config.load().is_header_excluded(name.as_str()) {
+                    return None;
+                }
+                req.headers
+                    .get(name)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_string)
+            }
+        };
+        value.map(Value::from)
+    }
+}
+
+/// `/{path}` and `/{path}.json` for every field in [`FIELDS`].
+pub fn routes() -> Router<Arc<AppState>> {
+    FIELDS.iter().fold(Router::new(), |router, field| {
+        router
+            .route(
+                &format!("/{}", field.path),
+                get(
+                    move |ConnectInfo(addr): ConnectInfo<SocketAddr>,
+                          State(state): State<Arc<AppState>>,
+                          Query(query): Query<FormatQuery>,
+                          version: Version,
+                          headers: HeaderMap| {
+                        let req = FieldRequest {
+                            addr,
+                            state,
+                            version,
+                            headers,
+                        };
+                        field_handler(field, req, query)
+                    },
+                ),
+            )
+            .route(
+                &format!("/{}.json", field.path),
+                get(
+                    move |ConnectInfo(addr): ConnectInfo<SocketAddr>,
+                          State(state): State<Arc<AppState>>,
+                          Query(jsonp): Query<JsonpQuery>,
+                          version: Version,
+                          headers: HeaderMap| {
+                        let req = FieldRequest {
+                            addr,
+                            state,
+                            version,
+                            headers,
+                        };
+                        field_json_handler(field, req, jsonp)
+                    },
+                ),
+            )
+    })
+}
+
+// GET /{field} — the field as plain text by default; other formats via
+// Accept or ?format=
+async fn field_handler(
+    field: &'static Field,
+    req: FieldRequest,
+    query: FormatQuery,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => format!("/{}", field.path)).increment(1);
+    let format = ResponseFormat::select(&query, &req.headers, ResponseFormat::Text)?;
+    field_response(format, field.key, field.value(&req).await)
+}
+
+// GET /{field}.json — the field as `{key: value}`, or JSONP with ?callback=
+async fn field_json_handler(
+    field: &'static Field,
+    req: FieldRequest,
+    jsonp: JsonpQuery,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => format!("/{}.json", field.path))
+        .increment(1);
+    let value = field.value(&req).await;
+    let mut body = serde_json::Map::new();
+    body.insert(field.key.to_string(), value.clone().into());
+    if let (Source::ParsedHeader(_, details), Some(Value::String(raw))) = (&field.source, &value) {
+        body.extend(details(raw, &req.state.config.load()));
+    }
+    match jsonp.callback {
+        Some(callback) => jsonp_response(&callback, &body),
+        None => json_response(&body),
+    }
+}
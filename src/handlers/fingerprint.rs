@@ -0,0 +1,114 @@
+//! How identifiable the client's stack is from what it sends besides the
+//! request itself.
+//!
+//! `/fingerprint/http`:
+//!
+//! - `header_order` lists header names in the order sent, with their
+//!   casing for HTTP/1 (HTTP/2 names are lowercase on the wire), and
+//!   `header_order_hash` is the first 12 hex digits of the SHA-256 of them
+//!   comma-joined. For HTTP/2 the order is as parsed, which groups repeated
+//!   names. Headers in `EXCLUDED_HEADERS` are left out.
+//! - For HTTP/2, `akamai` is Akamai's fingerprint of the connection's
+//!   opening frames (see [`crate::listener::http2_preface`]) and
+//!   `akamai_hash` its MD5, the form most fingerprint databases use.
+//!
+//! `/fingerprint/tls` gives the JA3 and JA4 fingerprints of the TLS
+//! ClientHello (see [`crate::listener::client_hello`]). They're only known
+//! when this server terminates TLS; anything else gets a 404.
+
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::{Extension, State};
+use axum::http::header::HeaderMap;
+use axum::http::{Response, Version};
+use md5::Digest;
+use serde::Serialize;
+
+use super::echo::{http_version_str, json_response};
+use crate::errors::AppError;
+use crate::listener::client_hello::TlsFingerprint;
+use crate::listener::http2_preface::Http2Preface;
+use crate::listener::raw_head::RawHead;
+use crate::state::AppState;
+
+#[derive(Debug, Serialize)]
+pub struct HttpFingerprint {
+    pub protocol: &'static str,
+    pub header_order: Vec<String>,
+    pub header_order_hash: String,
+    /// `null` for HTTP/1, or when the opening frames weren't captured.
+    pub akamai: Option<String>,
+    pub akamai_hash: Option<String>,
+}
+
+// GET /fingerprint/http — header order and casing, and the HTTP/2
+// fingerprint
+pub async fn http_fingerprint_handler(
+    raw_head: Option<Extension<RawHead>>,
+    http2: Option<Extension<Http2Preface>>,
+    State(state): State<Arc<AppState>>,
+    version: Version,
+    headers: HeaderMap,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/fingerprint/http").increment(1);
+    let excluded = &state.config.load().excluded_headers;
+    let names = match &raw_head {
+        Some(Extension(RawHead(head))) => head_names(head),
+        None => headers.keys().map(|name| name.to_string()).collect(),
+    };
+    let header_order: Vec<String> = names
+        .into_iter()
+        .filter(|name| !excluded.iter().any(|e| e.eq_ignore_ascii_case(name)))
+        .collect();
+    let header_order_hash = hex(&sha2::Sha256::digest(header_order.join(",")))[..12].to_string();
+    let akamai = http2.map(|Extension(preface)| preface.akamai());
+    let akamai_hash = akamai.as_ref().map(|a| hex(&md5::Md5::digest(a)));
+
+    json_response(&HttpFingerprint {
+        protocol: http_version_str(version),
+        header_order,
+        header_order_hash,
+        akamai,
+        akamai_hash,
+    })
+}
+
+// GET /fingerprint/tls — JA3 and JA4 of the TLS ClientHello
+pub async fn tls_fingerprint_handler(
+    fingerprint: Option<Extension<TlsFingerprint>>,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/fingerprint/tls").increment(1);
+    let Some(Extension(fingerprint)) = fingerprint else {
+        return Err(AppError::NotFound(
+            "no TLS ClientHello on this connection; connect over HTTPS to this server".to_string(),
+        ));
+    };
+    json_response(&fingerprint)
+}
+
+/// Header names of a raw HTTP/1 head, as sent.
+fn head_names(head: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(head)
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, _)| name.trim().to_string())
+        .collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_keep_order_and_casing() {
+        let head =
+            b"GET / HTTP/1.1\r\nHost: x\r\nuser-agent: a:b\r\nACCEPT: */*\r\nhost: y\r\n\r\n";
+        assert_eq!(head_names(head), ["Host", "user-agent", "ACCEPT", "host"]);
+    }
+}
@@ -0,0 +1,130 @@
+//! `POST /hash/{algo}`: a digest of the request body, for checking which
+//! bytes reached the server through an upload path. MD5, SHA-1, SHA-256
+//! and BLAKE3 are supported; the digest comes back in hex and base64 along
+//! with the body's length. JSON by default, the hex digest as plain text.
+
+use axum::body::{Body, Bytes};
+use axum::extract::{Path, Query};
+use axum::http::Response;
+use axum::http::header::HeaderMap;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::Serialize;
+use sha1::Digest;
+
+use super::echo::negotiated_response;
+use crate::errors::AppError;
+use crate::format::{FormatQuery, ResponseFormat};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Algorithm {
+    Md5,
+    Sha1,
+    Sha256,
+    Blake3,
+}
+
+impl Algorithm {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "md5" => Some(Self::Md5),
+            "sha1" => Some(Self::Sha1),
+            "sha256" => Some(Self::Sha256),
+            "blake3" => Some(Self::Blake3),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Md5 => "md5",
+            Self::Sha1 => "sha1",
+            Self::Sha256 => "sha256",
+            Self::Blake3 => "blake3",
+        }
+    }
+
+    fn digest(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Md5 => md5::Md5::digest(data).to_vec(),
+            Self::Sha1 => sha1::Sha1::digest(data).to_vec(),
+            Self::Sha256 => sha2::Sha256::digest(data).to_vec(),
+            Self::Blake3 => blake3::hash(data).as_bytes().to_vec(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct HashResponse {
+    pub algorithm: &'static str,
+    /// Length of the body that was hashed.
+    pub bytes: usize,
+    pub hex: String,
+    pub base64: String,
+}
+
+// POST /hash/{algo} — digest of the request body (JSON by default; other
+// formats via Accept or ?format=)
+pub async fn hash_handler(
+    Path(algo): Path<String>,
+    Query(query): Query<FormatQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/hash/{algo}").increment(1);
+    let format = ResponseFormat::select(&query, &headers, ResponseFormat::Json)?;
+    let algorithm = Algorithm::from_name(&algo).ok_or_else(|| {
+        AppError::BadRequest(format!(
+            "unknown algorithm \"{algo}\"; use md5, sha1, sha256 or blake3"
+        ))
+    })?;
+
+    let digest = algorithm.digest(&body);
+    let hex: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+    let response = HashResponse {
+        algorithm: algorithm.name(),
+        bytes: body.len(),
+        hex: hex.clone(),
+        base64: STANDARD.encode(&digest),
+    };
+    negotiated_response(format, "hash", &response, Some(hex))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(algorithm: Algorithm, data: &[u8]) -> String {
+        algorithm
+            .digest(data)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+
+    #[test]
+    fn known_digests_of_abc() {
+        assert_eq!(
+            hex(Algorithm::Md5, b"abc"),
+            "900150983cd24fb0d6963f7d28e17f72"
+        );
+        assert_eq!(
+            hex(Algorithm::Sha1, b"abc"),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            hex(Algorithm::Sha256, b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hex(Algorithm::Blake3, b"abc"),
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+    }
+
+    #[test]
+    fn names_are_case_insensitive() {
+        assert_eq!(Algorithm::from_name("SHA256"), Some(Algorithm::Sha256));
+        assert_eq!(Algorithm::from_name("sha512"), None);
+    }
+}
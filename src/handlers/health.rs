@@ -1,4 +1,5 @@
 use std::sync::Arc;
+use std::sync::atomic::Ordering;
 
 use axum::extract::State;
 use axum::http::{header, Response, StatusCode};
@@ -39,7 +40,8 @@ pub async fn health_handler(
         })
         .collect();
 
-    let degraded = table.is_empty();
+    // Without the range sync there's no table to wait for.
+    let degraded = cfg!(feature = "cloud-ranges") && table.is_empty();
     let response = HealthResponse {
         status: if degraded { "degraded" } else { "ok" },
         total_cidrs: table.len(),
@@ -62,3 +64,61 @@ pub async fn health_handler(
         .body(Body::from(body))
         .map_err(|_| AppError::HttpBuilderError)
 }
+
+#[derive(Serialize)]
+struct ReadyResponse {
+    status: &'static str,
+    checks: ReadyChecks,
+}
+
+#[derive(Serialize)]
+struct ReadyChecks {
+    /// The HTTP listener is bound.
+    listener: bool,
+    /// At least one provider's IP ranges are loaded; `null` when built
+    /// without the `cloud-ranges` feature.
+    ip_ranges: Option<bool>,
+    /// The GeoIP backend's databases are loaded; `null` without one.
+    geoip: Option<bool>,
+}
+
+// GET /healthz — liveness: the process is up and serving requests. Touches
+// no shared state, so it stays cheap under load.
+pub async fn healthz_handler() -> Result<Response<Body>, AppError> {
+    probe_response(StatusCode::OK, &serde_json::json!({ "status": "ok" }))
+}
+
+// GET /readyz — readiness: listener bound and data loaded, else 503. No
+// enrichment or rDNS for the caller's address.
+pub async fn readyz_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<Response<Body>, AppError> {
+    let checks = ReadyChecks {
+        listener: state.listener_bound.load(Ordering::Relaxed),
+        ip_ranges: if cfg!(feature = "cloud-ranges") {
+            Some(!state.lookup_table.read().await.is_empty())
+        } else {
+            None
+        },
+        geoip: state.enricher.get().map(|e| e.is_ready()),
+    };
+    let ready = checks.listener && checks.ip_ranges != Some(false) && checks.geoip != Some(false);
+    let (status, code) = if ready {
+        ("ready", StatusCode::OK)
+    } else {
+        ("not_ready", StatusCode::SERVICE_UNAVAILABLE)
+    };
+    probe_response(code, &ReadyResponse { status, checks })
+}
+
+fn probe_response<T: Serialize>(
+    status: StatusCode,
+    body: &T,
+) -> Result<Response<Body>, AppError> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::from(serde_json::to_string(body)?))
+        .map_err(|_| AppError::HttpBuilderError)
+}
@@ -0,0 +1,169 @@
+//! `/hints`: the User-Agent Client Hints (`Sec-CH-UA*`) the request carried.
+//!
+//! Chromium browsers send only the low-entropy hints (`Sec-CH-UA`,
+//! `-Mobile`, `-Platform`) unprompted. The rest are sent once a site asks
+//! for them with `Accept-CH`, which HTML responses here do (see
+//! [`ACCEPT_CH`]), so opening the landing page and then `/hints` shows the
+//! full set. Browsers only honour `Accept-CH` over HTTPS.
+//!
+//! Each hint is keyed by its header name without `sec-ch-ua-`, in snake
+//! case, with `Sec-CH-UA` itself as `brands`. Values are decoded from their
+//! structured-field form (RFC 8941): brand lists become `{brand, version}`
+//! objects, `?1`/`?0` booleans and quoted strings plain strings.
+
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::header::HeaderMap;
+use axum::http::{HeaderValue, Response};
+use serde_json::{Map, Value, json};
+
+use super::echo::json_response;
+use crate::errors::AppError;
+use crate::forwarded::{split_unquoted, unquote};
+use crate::state::AppState;
+
+/// `Accept-CH` sent with HTML responses: the high-entropy hints, which
+/// browsers send on later requests to this origin.
+pub const ACCEPT_CH: HeaderValue = HeaderValue::from_static(
+    "Sec-CH-UA-Platform-Version, Sec-CH-UA-Model, Sec-CH-UA-Arch, Sec-CH-UA-Bitness, \
+     Sec-CH-UA-Full-Version-List, Sec-CH-UA-WoW64, Sec-CH-UA-Form-Factors",
+);
+
+const PREFIX: &str = "sec-ch-ua";
+
+/// Hints whose value is a list even when it has one member.
+const LIST_HINTS: &[&str] = &[
+    "sec-ch-ua",
+    "sec-ch-ua-full-version-list",
+    "sec-ch-ua-form-factors",
+];
+
+// GET /hints — the Sec-CH-UA* headers, decoded
+pub async fn hints_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/hints").increment(1);
+    let config = state.config.load();
+    let mut hints = client_hints(&headers);
+    hints.retain(|key, _| !config.is_header_excluded(&header_name(key)));
+    json_response(&hints)
+}
+
+/// The `Sec-CH-UA*` headers in `headers`, decoded, in header order.
+pub fn client_hints(headers: &HeaderMap) -> Map<String, Value> {
+    let mut hints = Map::new();
+    for (name, value) in headers {
+        let name = name.as_str();
+        let Some(rest) = name.strip_prefix(PREFIX) else {
+            continue;
+        };
+        let key = match rest.strip_prefix('-') {
+            None if rest.is_empty() => "brands".to_string(),
+            Some(rest) if !rest.is_empty() => rest.replace('-', "_"),
+            _ => continue,
+        };
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+        let items: Vec<Value> = split_unquoted(value, ',')
+            .into_iter()
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(item)
+            .collect();
+        let value = if LIST_HINTS.contains(&name) {
+            Value::Array(items)
+        } else {
+            items.into_iter().next().unwrap_or(Value::Null)
+        };
+        hints.insert(key, value);
+    }
+    hints
+}
+
+/// The header a [`client_hints`] key came from.
+fn header_name(key: &str) -> String {
+    match key {
+        "brands" => PREFIX.to_string(),
+        key => format!("{PREFIX}-{}", key.replace('_', "-")),
+    }
+}
+
+/// One structured-field item: `"Chromium";v="126"` is a brand, `?1` a
+/// boolean, `"x86"` a string. Other parameters are dropped.
+fn item(raw: &str) -> Value {
+    let mut parts = split_unquoted(raw, ';').into_iter().map(str::trim);
+    let bare = parts.next().unwrap_or_default();
+    let version = parts
+        .find_map(|param| param.strip_prefix("v="))
+        .map(unquote);
+    match (bare, version) {
+        (bare, Some(version)) => json!({ "brand": unquote(bare), "version": version }),
+        ("?1", None) => Value::Bool(true),
+        ("?0", None) => Value::Bool(false),
+        (bare, None) => Value::String(unquote(bare)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.parse().unwrap(), v.parse().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn decodes_low_and_high_entropy_hints() {
+        let hints = client_hints(&headers(&[
+            (
+                "sec-ch-ua",
+                r#""Chromium";v="126", "Google Chrome";v="126", "Not-A.Brand";v="8""#,
+            ),
+            ("sec-ch-ua-mobile", "?0"),
+            ("sec-ch-ua-platform", r#""Windows""#),
+            ("sec-ch-ua-platform-version", r#""15.0.0""#),
+            ("sec-ch-ua-bitness", r#""64""#),
+            ("sec-ch-ua-wow64", "?0"),
+            ("sec-ch-ua-form-factors", r#""Desktop""#),
+            ("user-agent", "Mozilla/5.0"),
+        ]));
+        assert_eq!(
+            Value::Object(hints),
+            json!({
+                "brands": [
+                    { "brand": "Chromium", "version": "126" },
+                    { "brand": "Google Chrome", "version": "126" },
+                    { "brand": "Not-A.Brand", "version": "8" },
+                ],
+                "mobile": false,
+                "platform": "Windows",
+                "platform_version": "15.0.0",
+                "bitness": "64",
+                "wow64": false,
+                "form_factors": ["Desktop"],
+            })
+        );
+    }
+
+    #[test]
+    fn ignores_other_headers_and_empty_values() {
+        let hints = client_hints(&headers(&[
+            ("sec-ch-ua-model", r#""""#),
+            ("sec-ch-uax", "?1"),
+            ("sec-ch-prefers-color-scheme", "dark"),
+        ]));
+        assert_eq!(Value::Object(hints), json!({ "model": "" }));
+        assert_eq!(header_name("brands"), "sec-ch-ua");
+        assert_eq!(
+            header_name("full_version_list"),
+            "sec-ch-ua-full-version-list"
+        );
+    }
+}
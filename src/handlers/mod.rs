@@ -1,3 +1,47 @@
+pub mod anything;
+#[cfg(feature = "auth")]
+pub mod auth;
+pub mod body;
+pub mod cache;
+pub mod client;
+#[cfg(feature = "compressed")]
+pub mod compressed;
+pub mod cookies;
+pub mod data;
+pub mod delay;
+pub mod dual_stack;
 pub mod echo;
+#[cfg(feature = "encoding")]
+pub mod encoding;
+pub mod fetch_metadata;
+pub mod fields;
+#[cfg(feature = "fingerprint")]
+pub mod fingerprint;
+#[cfg(feature = "hash")]
+pub mod hash;
 pub mod health;
+pub mod hints;
+#[cfg(feature = "jwt")]
+pub mod jwt;
+#[cfg(feature = "metrics")]
 pub mod metrics;
+#[cfg(unix)]
+pub mod mtu;
+pub mod ping;
+pub mod portcheck;
+pub mod privacy;
+pub mod raw;
+pub mod redirect;
+pub mod resolver_check;
+#[cfg(unix)]
+pub mod rtt;
+#[cfg(feature = "speedtest")]
+pub mod speedtest;
+pub mod sse;
+pub mod time;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod traceroute;
+pub mod uuid;
+#[cfg(feature = "ws")]
+pub mod ws;
@@ -0,0 +1,120 @@
+//! `/mtu`: the segment size the kernel negotiated with the client, and the
+//! MTU that implies.
+//!
+//! A client's SYN advertises an MSS derived from its link MTU, and routers
+//! doing MSS clamping (PPPoE, VPNs) lower it on the way; the kernel then
+//! sends segments no larger than that or the path MTU it has learned. So
+//! `mss` plus the IP and TCP headers is the largest packet that made it
+//! through, and a value below 1500 usually names what's in the path. On
+//! Linux `TCP_INFO` adds the kernel's path MTU, whether TCP timestamps take
+//! 12 bytes of each segment, and the window scaling both ends agreed to.
+//!
+//! This is about the TCP connection to this server: behind a reverse proxy
+//! (`via_proxy`), that's the proxy's.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::{Extension, State};
+use axum::http::Response;
+use serde::Serialize;
+
+use super::echo::json_response;
+use crate::errors::AppError;
+use crate::listener::tcp_socket::{TcpSocket, WindowScale};
+use crate::state::AppState;
+
+/// IPv4 and TCP headers without options.
+const IPV4_OVERHEAD: u32 = 20 + 20;
+/// IPv6 and TCP headers without options or extension headers.
+const IPV6_OVERHEAD: u32 = 40 + 20;
+/// The TCP timestamps option, padded.
+const TIMESTAMPS_OVERHEAD: u32 = 12;
+
+/// MTUs that give away the link or tunnel.
+const KNOWN_MTUS: &[(u32, &str)] = &[
+    (65536, "loopback"),
+    (9000, "jumbo frames"),
+    (1500, "Ethernet"),
+    (1492, "PPPoE"),
+    (1480, "6in4 tunnel"),
+    (1476, "GRE tunnel"),
+    (1420, "WireGuard"),
+    (1280, "IPv6 minimum"),
+];
+
+#[derive(Debug, Serialize)]
+pub struct MtuReport {
+    /// The other end of the TCP connection.
+    pub peer: SocketAddr,
+    /// Whether `peer` is a trusted proxy, so this describes its connection
+    /// rather than the client's.
+    pub via_proxy: bool,
+    pub ip_version: u8,
+    /// `TCP_MAXSEG`.
+    pub mss: u32,
+    /// `mss` plus headers: the largest packet that fits the path.
+    pub mtu: u32,
+    /// What an MTU of exactly `mtu` usually means, e.g. `PPPoE`.
+    pub likely_link: Option<&'static str>,
+    /// The kernel's path MTU for the connection (Linux only).
+    pub path_mtu: Option<u32>,
+    /// Whether TCP timestamps are on (Linux only).
+    pub timestamps: Option<bool>,
+    /// Agreed window scale shifts, `null` without scaling or off Linux.
+    pub window_scale: Option<WindowScale>,
+}
+
+impl MtuReport {
+    fn of(socket: &TcpSocket, state: &AppState) -> std::io::Result<Self> {
+        let peer = socket.peer_addr()?;
+        let ipv4 = socket.local_addr()?.ip().to_canonical().is_ipv4();
+        let mss = socket.mss()?;
+        let info = socket.info()?;
+        let timestamps = info.map(|i| i.timestamps);
+
+        let overhead = if ipv4 { IPV4_OVERHEAD } else { IPV6_OVERHEAD };
+        let options = if timestamps == Some(true) {
+            TIMESTAMPS_OVERHEAD
+        } else {
+            0
+        };
+        let mtu = mss + overhead + options;
+        Ok(Self {
+            peer,
+            via_proxy: state
+                .config
+                .load()
+                .is_trusted_peer(&peer.ip().to_canonical()),
+            ip_version: if ipv4 { 4 } else { 6 },
+            mss,
+            mtu,
+            likely_link: KNOWN_MTUS
+                .iter()
+                .find(|(known, _)| *known == mtu)
+                .map(|(_, link)| *link),
+            path_mtu: info.map(|i| i.pmtu),
+            timestamps,
+            window_scale: info.and_then(|i| i.window_scale),
+        })
+    }
+}
+
+// GET /mtu — TCP MSS and the MTU it implies
+pub async fn mtu_handler(
+    State(state): State<Arc<AppState>>,
+    socket: Option<Extension<TcpSocket>>,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/mtu").increment(1);
+    let Some(Extension(socket)) = socket else {
+        return Err(AppError::NotFound(
+            "not a TCP connection, so there is no MSS to report".to_string(),
+        ));
+    };
+    let report = MtuReport::of(&socket, &state).map_err(|e| {
+        tracing::debug!(error = %e, "reading TCP socket options failed");
+        AppError::NotFound("the connection's TCP state is unavailable".to_string())
+    })?;
+    json_response(&report)
+}
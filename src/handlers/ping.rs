@@ -0,0 +1,63 @@
+//! Latency endpoints.
+//!
+//! - `/ping` answers a one-byte body as cheaply as the server can, for
+//!   scripted RTT measurements. It's routed outside all middleware, so no
+//!   logging, metrics, rate limiting or client IP lookup, and allocates
+//!   nothing unless `?ts=` asks for the client's timestamp back, with the
+//!   server's clock beside it for offset estimates.
+//! - `/ping-me`: ICMP echo requests from the server to the client, reporting
+//!   loss and round-trip times as seen from outside. See [`crate::ping`] for
+//!   what it needs and the safeguards; with `PING_ENABLED` off it's a 404.
+
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::body::{Body, Bytes};
+use axum::extract::State;
+use axum::http::header::{self, HeaderValue};
+use axum::http::{Response, Uri};
+
+use super::echo::json_response;
+use crate::client_ip::ClientIp;
+use crate::errors::AppError;
+use crate::state::AppState;
+
+/// Longest `?ts=` echoed back.
+const MAX_TS_LEN: usize = 64;
+
+// GET /ping — one byte, no middleware; ?ts= is echoed with the server's time
+pub async fn ping_handler(uri: Uri) -> Result<Response<Body>, AppError> {
+    let mut response = Response::new(Body::from(Bytes::from_static(b"1")));
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    let ts = uri
+        .query()
+        .and_then(|q| q.split('&').find_map(|pair| pair.strip_prefix("ts=")));
+    if let Some(ts) = ts {
+        let ts = HeaderValue::from_str(ts)
+            .ok()
+            .filter(|_| ts.len() <= MAX_TS_LEN)
+            .ok_or_else(|| {
+                AppError::BadRequest(format!(
+                    "ts must be at most {MAX_TS_LEN} printable characters"
+                ))
+            })?;
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        headers.insert("x-ping-ts", ts);
+        headers.insert("x-server-time-ms", HeaderValue::from(now_ms));
+    }
+    Ok(response)
+}
+
+// GET /ping-me — ping the client from the server
+pub async fn ping_me_handler(
+    State(state): State<Arc<AppState>>,
+    ClientIp(ip): ClientIp,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/ping-me").increment(1);
+    json_response(&state.pinger.ping(ip).await?)
+}
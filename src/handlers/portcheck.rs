@@ -0,0 +1,27 @@
+//! `/portcheck/{port}`: whether the client's public address accepts TCP
+//! connections on `port`, seen from outside. Useful for checking a port
+//! forward or a firewall rule without a second machine.
+//!
+//! See [`crate::portcheck`] for the safeguards; with `PORTCHECK_PORTS`
+//! unset the endpoint is a 404.
+
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::{Path, State};
+use axum::http::Response;
+
+use super::echo::json_response;
+use crate::client_ip::ClientIp;
+use crate::errors::AppError;
+use crate::state::AppState;
+
+// GET /portcheck/{port} — connect back to the client on an allowed port
+pub async fn portcheck_handler(
+    State(state): State<Arc<AppState>>,
+    ClientIp(ip): ClientIp,
+    Path(port): Path<u16>,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/portcheck/{port}").increment(1);
+    json_response(&state.port_checker.check(ip, port).await?)
+}
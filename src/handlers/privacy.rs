@@ -0,0 +1,93 @@
+//! `/privacy`: the opt-out signals the browser sends, `DNT` (Do Not Track)
+//! and `Sec-GPC` (Global Privacy Control).
+//!
+//! Both are set in browser or extension settings, and both can be stripped
+//! on the way by a proxy or privacy tool, so seeing them here confirms they
+//! reach servers. GPC is the one with legal weight in some jurisdictions
+//! (e.g. under the CCPA); DNT was never standardised and most sites ignore
+//! it.
+
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::Response;
+use axum::http::header::HeaderMap;
+use serde::Serialize;
+
+use super::echo::json_response;
+use crate::config::Config;
+use crate::errors::AppError;
+use crate::state::AppState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PrivacySignals {
+    /// `DNT: 1` is `true`, `DNT: 0` (tracking explicitly allowed) `false`,
+    /// `null` when not sent.
+    pub dnt: Option<bool>,
+    /// `Sec-GPC: 1`; the spec defines no other value.
+    pub gpc: bool,
+    /// Whether either signal asks not to be tracked or sold to.
+    pub opted_out: bool,
+}
+
+impl PrivacySignals {
+    /// The signals in `headers`, treating any in `EXCLUDED_HEADERS` as not
+    /// sent.
+    pub fn of(headers: &HeaderMap, config: &Config) -> Self {
+        let get = |name: &str| {
+            if config.is_header_excluded(name) {
+                return None;
+            }
+            headers.get(name)?.to_str().ok().map(str::trim)
+        };
+        let dnt = get("dnt").and_then(|v| match v {
+            "1" => Some(true),
+            "0" => Some(false),
+            _ => None,
+        });
+        let gpc = get("sec-gpc") == Some("1");
+        Self {
+            dnt,
+            gpc,
+            opted_out: gpc || dnt == Some(true),
+        }
+    }
+}
+
+// GET /privacy — whether DNT and GPC reach the server
+pub async fn privacy_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/privacy").increment(1);
+    json_response(&PrivacySignals::of(&headers, &state.config.load()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signals(pairs: &[(&str, &str)]) -> PrivacySignals {
+        let headers: HeaderMap = pairs
+            .iter()
+            .map(|(k, v)| (k.parse().unwrap(), v.parse().unwrap()))
+            .collect();
+        PrivacySignals::of(&headers, &Config::default())
+    }
+
+    #[test]
+    fn dnt_and_gpc() {
+        let none = signals(&[]);
+        assert_eq!((none.dnt, none.gpc, none.opted_out), (None, false, false));
+
+        let gpc = signals(&[("sec-gpc", "1"), ("dnt", "0")]);
+        assert_eq!((gpc.dnt, gpc.gpc, gpc.opted_out), (Some(false), true, true));
+
+        let dnt = signals(&[("dnt", "1")]);
+        assert_eq!((dnt.dnt, dnt.gpc, dnt.opted_out), (Some(true), false, true));
+
+        let garbage = signals(&[("dnt", "yes"), ("sec-gpc", "0")]);
+        assert_eq!((garbage.dnt, garbage.gpc), (None, false));
+    }
+}
@@ -0,0 +1,115 @@
+//! `/raw`: the request as text, for seeing exactly what proxies on the way
+//! injected or stripped. HTTP/1 requests come back verbatim, request line
+//! and headers with their original order and casing (see
+//! [`crate::listener::raw_head`]), followed by the body. HTTP/2 has no
+//! textual head, so one is rebuilt from the parsed request: lowercase
+//! names, in order of first appearance. Either way the body is as decoded
+//! (without chunked framing) and capped at `MAX_BODY_BYTES`, and header
+//! lines named in `EXCLUDED_HEADERS` are left out.
+
+use std::sync::Arc;
+
+use axum::body::{Body, Bytes};
+use axum::extract::{Extension, State};
+use axum::http::header::{self, HeaderMap};
+use axum::http::{Method, Response, StatusCode, Uri, Version};
+
+use super::echo::http_version_str;
+use crate::errors::AppError;
+use crate::listener::raw_head::RawHead;
+use crate::state::AppState;
+
+// ANY /raw — the request line, headers and body as received
+pub async fn raw_handler(
+    raw_head: Option<Extension<RawHead>>,
+    State(state): State<Arc<AppState>>,
+    method: Method,
+    uri: Uri,
+    version: Version,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/raw").increment(1);
+    let config = state.config.load();
+    let mut dump = match raw_head {
+        Some(Extension(RawHead(head))) => without_excluded(&head, &config.excluded_headers),
+        None => reconstruct(&method, &uri, version, &headers, &config.excluded_headers),
+    };
+    dump.extend_from_slice(&body);
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::from(dump))
+        .map_err(|_| AppError::HttpBuilderError)
+}
+
+/// `head` without the header lines whose (case-insensitive) name is in
+/// `excluded`.
+fn without_excluded(head: &[u8], excluded: &[String]) -> Vec<u8> {
+    head.split_inclusive(|&b| b == b'\n')
+        .filter(|line| {
+            let name = line.split(|&b| b == b':').next().unwrap_or_default();
+            !excluded
+                .iter()
+                .any(|e| e.as_bytes().eq_ignore_ascii_case(name.trim_ascii()))
+        })
+        .flatten()
+        .copied()
+        .collect()
+}
+
+/// An HTTP/1-style head for a request that arrived without one.
+fn reconstruct(
+    method: &Method,
+    uri: &Uri,
+    version: Version,
+    headers: &HeaderMap,
+    excluded: &[String],
+) -> Vec<u8> {
+    let target = uri.path_and_query().map_or("/", |p| p.as_str());
+    let mut head = format!("{method} {target} {}\r\n", http_version_str(version)).into_bytes();
+    for (name, value) in headers {
+        if excluded.iter().any(|e| e == name.as_str()) {
+            continue;
+        }
+        head.extend_from_slice(name.as_str().as_bytes());
+        head.extend_from_slice(b": ");
+        head.extend_from_slice(value.as_bytes());
+        head.extend_from_slice(b"\r\n");
+    }
+    head.extend_from_slice(b"\r\n");
+    head
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn drops_excluded_lines_whatever_their_case() {
+        let head = b"GET / HTTP/1.1\r\nHost: x\r\nX-Secret: s\r\nAccept: */*\r\n\r\n";
+        let excluded = ["x-secret".to_string()];
+        assert_eq!(
+            without_excluded(head, &excluded),
+            b"GET / HTTP/1.1\r\nHost: x\r\nAccept: */*\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn reconstructs_a_head() {
+        let mut headers = HeaderMap::new();
+        headers.insert("accept", HeaderValue::from_static("*/*"));
+        headers.append("x-a", HeaderValue::from_static("1"));
+        headers.append("x-a", HeaderValue::from_static("2"));
+        let uri: Uri = "https://example.com/p?q=1".parse().unwrap();
+        let head = reconstruct(&Method::PUT, &uri, Version::HTTP_2, &headers, &[]);
+        assert_eq!(
+            String::from_utf8(head).unwrap(),
+            "PUT /p?q=1 HTTP/2\r\naccept: */*\r\nx-a: 1\r\nx-a: 2\r\n\r\n"
+        );
+    }
+}
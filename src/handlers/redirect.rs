@@ -0,0 +1,129 @@
+//! Redirect endpoints, for checking how HTTP clients follow redirects:
+//!
+//! - `/redirect/{n}` redirects `n` times, through `/redirect/{n-1}` down to
+//!   `/get`. Its `Location`s are relative, so the chain stays inside the
+//!   mount when the router is nested under a prefix.
+//! - `/redirect-to?url=` redirects once to `url`.
+//!
+//! Both take `?status=` for the redirect code: 301, 302 (the default), 303,
+//! 307 or 308. `/redirect-to` only accepts paths on this server, so it
+//! can't be used as an open redirect to send people elsewhere.
+
+use axum::body::Body;
+use axum::extract::{Path, Query};
+use axum::http::header::{self, HeaderValue};
+use axum::http::{Response, StatusCode};
+use serde::Deserialize;
+
+use crate::errors::AppError;
+
+/// Longest chain `/redirect/{n}` will start.
+const MAX_REDIRECTS: u32 = 20;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RedirectQuery {
+    pub status: Option<u16>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RedirectToQuery {
+    pub url: Option<String>,
+    pub status: Option<u16>,
+}
+
+// GET /redirect/{n} — redirect n times, ending at /get
+pub async fn redirect_handler(
+    Path(n): Path<u32>,
+    Query(query): Query<RedirectQuery>,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/redirect/{n}").increment(1);
+    if !(1..=MAX_REDIRECTS).contains(&n) {
+        return Err(AppError::BadRequest(format!(
+            "redirect count must be between 1 and {MAX_REDIRECTS}"
+        )));
+    }
+    let status = redirect_status(query.status)?;
+    // Relative to /redirect/{n}: a sibling, or /get one level up.
+    let next = match n {
+        1 => "../get".to_string(),
+        n => (n - 1).to_string(),
+    };
+    // Carry a non-default status down the chain.
+    let location = match query.status {
+        Some(code) if n > 1 => format!("{next}?status={code}"),
+        _ => next,
+    };
+    redirect_response(status, &location)
+}
+
+// GET /redirect-to?url=&status= — redirect once to a path on this server
+pub async fn redirect_to_handler(
+    Query(query): Query<RedirectToQuery>,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/redirect-to").increment(1);
+    let status = redirect_status(query.status)?;
+    let url = query
+        .url
+        .ok_or_else(|| AppError::BadRequest("missing url".to_string()))?;
+    if !is_local_path(&url) {
+        return Err(AppError::BadRequest(
+            "url must be a path on this server, like /get".to_string(),
+        ));
+    }
+    redirect_response(status, &url)
+}
+
+/// The redirect status for `?status=`, 302 without one.
+fn redirect_status(code: Option<u16>) -> Result<StatusCode, AppError> {
+    match code.unwrap_or(302) {
+        code @ (301 | 302 | 303 | 307 | 308) => {
+            StatusCode::from_u16(code).map_err(|_| AppError::HttpBuilderError)
+        }
+        code => Err(AppError::BadRequest(format!(
+            "status {code} is not a redirect; use 301, 302, 303, 307 or 308"
+        ))),
+    }
+}
+
+/// Whether `url` is an absolute path, which browsers and clients resolve
+/// against this server. `//host` and `/\host` are taken as other hosts.
+fn is_local_path(url: &str) -> bool {
+    url.starts_with('/') && !url[1..].starts_with(['/', '\\'])
+}
+
+fn redirect_response(status: StatusCode, location: &str) -> Result<Response<Body>, AppError> {
+    let location = HeaderValue::from_str(location)
+        .map_err(|_| AppError::BadRequest("url is not a valid header value".to_string()))?;
+    Response::builder()
+        .status(status)
+        .header(header::LOCATION, location)
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::empty())
+        .map_err(|_| AppError::HttpBuilderError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_local_paths_are_allowed() {
+        assert!(is_local_path("/get"));
+        assert!(is_local_path("/anything?a=1"));
+        assert!(!is_local_path("https://example.com/"));
+        assert!(!is_local_path("//example.com/"));
+        assert!(!is_local_path("/\\example.com/"));
+        assert!(!is_local_path("get"));
+    }
+
+    #[test]
+    fn only_redirect_statuses_are_allowed() {
+        assert_eq!(redirect_status(None).unwrap(), StatusCode::FOUND);
+        assert_eq!(
+            redirect_status(Some(308)).unwrap(),
+            StatusCode::PERMANENT_REDIRECT
+        );
+        assert!(redirect_status(Some(200)).is_err());
+        assert!(redirect_status(Some(304)).is_err());
+    }
+}
@@ -0,0 +1,79 @@
+//! `/resolver-check`: which recursive resolver the client's lookups go
+//! through, and what it tells authoritative servers about the client.
+//!
+//! ```text
+//! token=$(curl -s https://host/resolver-check | jq -r .token)
+//! dig +short $token.whoami.example.com
+//! curl https://host/resolver-check/$token
+//! ```
+//!
+//! See [`crate::resolver_check`]; without `DNS_ADDR` both routes are a
+//! 404.
+
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::{OriginalUri, Path, State};
+use axum::http::Response;
+use serde::Serialize;
+
+use super::echo::json_response;
+use crate::errors::AppError;
+use crate::resolver_check::ResolverQuery;
+use crate::state::AppState;
+
+#[derive(Debug, Serialize)]
+pub struct CheckStart {
+    pub token: String,
+    /// Resolve this, with any record type, through the resolver to check.
+    pub hostname: String,
+    /// Where the queries for `hostname` are reported, on this server;
+    /// under the prefix the router is mounted at, if any.
+    pub result_path: String,
+    pub expires_in_secs: u64,
+}
+
+// GET /resolver-check — a fresh name to resolve
+pub async fn start_handler(
+    State(state): State<Arc<AppState>>,
+    OriginalUri(uri): OriginalUri,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/resolver-check").increment(1);
+    let check = &state.resolver_check;
+    let token = check.start()?;
+    json_response(&CheckStart {
+        hostname: check.hostname(&token)?,
+        result_path: format!("{}/{token}", uri.path()),
+        expires_in_secs: check.ttl().as_secs(),
+        token,
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct CheckResult {
+    pub token: String,
+    pub hostname: String,
+    /// The resolver behind the first query; `null` until `hostname` has
+    /// been looked up.
+    pub resolver: Option<IpAddr>,
+    /// Every query for `hostname`, oldest first. Several resolvers here
+    /// means a resolver pool, or a client using more than one.
+    pub queries: Vec<ResolverQuery>,
+}
+
+// GET /resolver-check/{token} — the resolvers that looked up a check's name
+pub async fn result_handler(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/resolver-check/{token}").increment(1);
+    let check = &state.resolver_check;
+    let queries = check.queries(&token)?;
+    json_response(&CheckResult {
+        hostname: check.hostname(&token)?,
+        resolver: queries.first().map(|query| query.resolver),
+        queries,
+        token,
+    })
+}
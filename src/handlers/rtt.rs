@@ -0,0 +1,95 @@
+//! `/rtt`: the kernel's view of the connection's quality, from Linux's
+//! `TCP_INFO`.
+//!
+//! `rtt_ms` is the smoothed round-trip time the kernel measures from ACKs,
+//! so it covers the network and the client's TCP stack but none of HTTP;
+//! `retransmits` and `lost` count segments that didn't make it the first
+//! time. A new connection has only the handshake and a few segments behind
+//! it, so ask over a connection that has carried some traffic (keep-alive,
+//! or after a `/bytes` download) for steadier numbers.
+//!
+//! As with `/mtu`, behind a reverse proxy (`via_proxy`) this measures the
+//! connection from the proxy.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::{Extension, State};
+use axum::http::Response;
+use serde::Serialize;
+
+use super::echo::json_response;
+use crate::errors::AppError;
+use crate::listener::tcp_socket::{TcpInfo, TcpSocket};
+use crate::state::AppState;
+
+#[derive(Debug, Serialize)]
+pub struct RttReport {
+    /// The other end of the TCP connection.
+    pub peer: SocketAddr,
+    /// Whether `peer` is a trusted proxy, so this describes its connection
+    /// rather than the client's.
+    pub via_proxy: bool,
+    /// Smoothed round-trip time.
+    pub rtt_ms: f64,
+    /// How much the round-trip time varies (`rttvar`).
+    pub rtt_var_ms: f64,
+    /// Current retransmission timeout.
+    pub rto_ms: f64,
+    /// Segments retransmitted so far on this connection.
+    pub retransmits: u32,
+    /// Segments currently presumed lost.
+    pub lost: u32,
+    /// Congestion window, in segments.
+    pub congestion_window: u32,
+    pub congestion_window_bytes: u64,
+    /// Slow-start threshold in segments; `null` while still in slow start.
+    pub slow_start_threshold: Option<u32>,
+}
+
+impl RttReport {
+    fn new(peer: SocketAddr, via_proxy: bool, info: &TcpInfo) -> Self {
+        let ms = |us: u32| f64::from(us) / 1000.0;
+        Self {
+            peer,
+            via_proxy,
+            rtt_ms: ms(info.rtt_us),
+            rtt_var_ms: ms(info.rttvar_us),
+            rto_ms: ms(info.rto_us),
+            retransmits: info.total_retrans,
+            lost: info.lost,
+            congestion_window: info.snd_cwnd,
+            congestion_window_bytes: u64::from(info.snd_cwnd) * u64::from(info.snd_mss),
+            slow_start_threshold: info.snd_ssthresh,
+        }
+    }
+}
+
+// GET /rtt — RTT, retransmits and congestion window from TCP_INFO
+pub async fn rtt_handler(
+    State(state): State<Arc<AppState>>,
+    socket: Option<Extension<TcpSocket>>,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/rtt").increment(1);
+    let Some(Extension(socket)) = socket else {
+        return Err(AppError::NotFound(
+            "not a TCP connection, so there is no RTT to report".to_string(),
+        ));
+    };
+    let unavailable = |e: std::io::Error| {
+        tracing::debug!(error = %e, "reading TCP_INFO failed");
+        AppError::NotFound("the connection's TCP state is unavailable".to_string())
+    };
+    let peer = socket.peer_addr().map_err(unavailable)?;
+    let Some(info) = socket.info().map_err(unavailable)? else {
+        return Err(AppError::NotFound(
+            "TCP_INFO is only read on Linux".to_string(),
+        ));
+    };
+    let via_proxy = state
+        .config
+        .load()
+        .is_trusted_peer(&peer.ip().to_canonical());
+    json_response(&RttReport::new(peer, via_proxy, &info))
+}
@@ -0,0 +1,190 @@
+//! Throughput tests that need nothing but curl:
+//!
+//! ```text
+//! curl -o /dev/null -w '%{speed_download}\n' https://host/speedtest/download/100M
+//! ```
+//!
+//! `/speedtest/download/{size}` streams `size` bytes of pseudo-random data,
+//! so compression anywhere on the path can't inflate the result, in 64 KiB
+//! chunks generated as they're sent. Sizes are bytes or take a `k`, `M` or
+//! `G` suffix (powers of 1000) or `Ki`, `Mi`, `Gi` (powers of 1024), with
+//! an optional `B`; the largest is `MAX_SPEEDTEST_BYTES`, and 0 turns the
+//! endpoints off.
+//!
+//! `/speedtest/upload` is the other direction: it reads and discards the
+//! request body, up to `MAX_SPEEDTEST_BYTES` in place of `MAX_BODY_BYTES`,
+//! and reports how fast it arrived:
+//!
+//! ```text
+//! head -c 100M /dev/urandom | curl -T - https://host/speedtest/upload
+//! ```
+
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use axum::body::{Body, Bytes};
+use axum::extract::{Path, State};
+use axum::http::header;
+use axum::http::{Response, StatusCode};
+use futures::{StreamExt, stream};
+use http_body_util::LengthLimitError;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use serde::Serialize;
+
+use super::echo::json_response;
+use crate::errors::AppError;
+use crate::state::AppState;
+
+const CHUNK_BYTES: usize = 64 * 1024;
+
+/// A size like `1500`, `10M`, `512KiB` or `1GB`.
+fn parse_size(raw: &str) -> Option<usize> {
+    let raw = raw.trim();
+    let digits = raw.find(|c: char| !c.is_ascii_digit()).unwrap_or(raw.len());
+    let (number, unit) = raw.split_at(digits);
+    let number: usize = number.parse().ok()?;
+    let unit = unit.strip_suffix(['B', 'b']).unwrap_or(unit);
+    let multiplier: usize = match unit.to_ascii_lowercase().as_str() {
+        "" => 1,
+        "k" => 1000,
+        "m" => 1000 * 1000,
+        "g" => 1000 * 1000 * 1000,
+        "ki" => 1 << 10,
+        "mi" => 1 << 20,
+        "gi" => 1 << 30,
+        _ => return None,
+    };
+    number.checked_mul(multiplier)
+}
+
+/// `MAX_SPEEDTEST_BYTES`, or 404 when it's 0.
+fn max_bytes(state: &AppState) -> Result<usize, AppError> {
+    match state.config.load().max_speedtest_bytes {
+        0 => Err(AppError::NotFound(
+            "speed tests are disabled (MAX_SPEEDTEST_BYTES)".to_string(),
+        )),
+        max => Ok(max),
+    }
+}
+
+// GET /speedtest/download/{size} — size bytes of incompressible data
+pub async fn download_handler(
+    Path(size): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/speedtest/download/{size}")
+        .increment(1);
+    let max = max_bytes(&state)?;
+    let n = parse_size(&size).ok_or_else(|| {
+        AppError::BadRequest(format!(
+            "invalid size {size:?}; expected bytes or a number with k, M, G, Ki, Mi or Gi"
+        ))
+    })?;
+    if n > max {
+        return Err(AppError::BadRequest(format!(
+            "{n} bytes is over MAX_SPEEDTEST_BYTES ({max})"
+        )));
+    }
+    metrics::counter!("speedtest_bytes_total", "direction" => "download").increment(n as u64);
+
+    let rng = StdRng::from_rng(&mut rand::rng());
+    let chunks = stream::unfold((rng, n), |(mut rng, remaining)| async move {
+        if remaining == 0 {
+            return None;
+        }
+        let mut chunk = vec![0; remaining.min(CHUNK_BYTES)];
+        rng.fill_bytes(&mut chunk);
+        let left = remaining - chunk.len();
+        Some((Ok::<_, Infallible>(Bytes::from(chunk)), (rng, left)))
+    });
+    let started_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .header(header::CONTENT_LENGTH, n)
+        .header(header::CACHE_CONTROL, "no-store, no-transform")
+        // When the server started sending, for timing against the client's
+        // clock; and let browsers' Resource Timing see cross-origin timings.
+        .header("x-speedtest-start-ms", started_ms.to_string())
+        .header("timing-allow-origin", "*")
+        .body(Body::from_stream(chunks))
+        .map_err(|_| AppError::HttpBuilderError)
+}
+
+#[derive(Debug, Serialize)]
+pub struct UploadReport {
+    pub bytes: u64,
+    /// From the request head to the end of the body.
+    pub elapsed_ms: f64,
+    pub bytes_per_second: f64,
+    /// Megabits (10^6 bits) per second.
+    pub mbps: f64,
+}
+
+impl UploadReport {
+    fn new(bytes: u64, elapsed: Duration) -> Self {
+        let secs = elapsed.as_secs_f64();
+        let bytes_per_second = if secs > 0.0 { bytes as f64 / secs } else { 0.0 };
+        Self {
+            bytes,
+            elapsed_ms: secs * 1000.0,
+            bytes_per_second,
+            mbps: bytes_per_second * 8.0 / 1e6,
+        }
+    }
+}
+
+// POST|PUT /speedtest/upload — discard the body, reporting its throughput
+pub async fn upload_handler(
+    State(state): State<Arc<AppState>>,
+    body: Body,
+) -> Result<Response<Body>, AppError> {
+    let started = Instant::now();
+    metrics::counter!("http_requests_total", "endpoint" => "/speedtest/upload").increment(1);
+    let max = max_bytes(&state)?;
+    let mut chunks = body.into_data_stream();
+    let mut bytes = 0u64;
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk.map_err(|e| match e.into_inner() {
+            e if e.is::<LengthLimitError>() => AppError::PayloadTooLarge(max),
+            e => AppError::BadRequest(format!("reading the body failed: {e}")),
+        })?;
+        bytes += chunk.len() as u64;
+    }
+    metrics::counter!("speedtest_bytes_total", "direction" => "upload").increment(bytes);
+    json_response(&UploadReport::new(bytes, started.elapsed()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("1500"), Some(1500));
+        assert_eq!(parse_size("10M"), Some(10_000_000));
+        assert_eq!(parse_size("10mb"), Some(10_000_000));
+        assert_eq!(parse_size("512KiB"), Some(512 * 1024));
+        assert_eq!(parse_size("1Gi"), Some(1 << 30));
+        assert_eq!(parse_size("0"), Some(0));
+        assert_eq!(parse_size("M"), None);
+        assert_eq!(parse_size("10X"), None);
+        assert_eq!(parse_size("-1"), None);
+        assert_eq!(parse_size("99999999999999999999G"), None);
+    }
+
+    #[test]
+    fn upload_rates() {
+        let report = UploadReport::new(2_500_000, Duration::from_secs(2));
+        assert_eq!(report.elapsed_ms, 2000.0);
+        assert_eq!(report.bytes_per_second, 1_250_000.0);
+        assert_eq!(report.mbps, 10.0);
+        assert_eq!(UploadReport::new(0, Duration::ZERO).mbps, 0.0);
+    }
+}
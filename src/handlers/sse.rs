@@ -0,0 +1,84 @@
+//! `/sse`: a Server-Sent Events stream, for checking whether proxies and
+//! CDNs pass streamed responses through as they are written or buffer
+//! them, and how long they let one run. The first event, `info`, is the
+//! same JSON as `/`; after that a `heartbeat` event with a sequence number
+//! and timestamp follows every `?interval=` seconds. The stream ends after
+//! `MAX_STREAM`, so a graceful shutdown never waits on it for longer.
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use axum::extract::{ConnectInfo, Query, State};
+use axum::http::Version;
+use axum::http::header::HeaderMap;
+use axum::response::sse::{Event, Sse};
+use futures::Stream;
+use futures::stream::{self, StreamExt};
+use serde::Deserialize;
+
+use super::echo::build_echo_response;
+use crate::datetime::UtcDateTime;
+use crate::errors::AppError;
+use crate::listener::ConnectionDetails;
+use crate::state::AppState;
+
+/// Heartbeat interval without `?interval=`.
+const DEFAULT_INTERVAL_SECS: u64 = 5;
+
+/// Longest `?interval=` accepted.
+const MAX_INTERVAL_SECS: u64 = 60;
+
+/// How long a stream runs before the server ends it.
+const MAX_STREAM: Duration = Duration::from_secs(600);
+
+/// The `?interval=` query parameter, in seconds.
+#[derive(Debug, Default, Deserialize)]
+pub struct SseQuery {
+    pub interval: Option<u64>,
+}
+
+// GET /sse — an event stream: the connection info, then a heartbeat every
+// ?interval= seconds (default 5)
+pub async fn sse_handler(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    conn: ConnectionDetails,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<SseQuery>,
+    version: Version,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/sse").increment(1);
+    let interval = query.interval.unwrap_or(DEFAULT_INTERVAL_SECS);
+    if !(1..=MAX_INTERVAL_SECS).contains(&interval) {
+        return Err(AppError::BadRequest(format!(
+            "interval must be between 1 and {MAX_INTERVAL_SECS} seconds"
+        )));
+    }
+    let interval = Duration::from_secs(interval);
+
+    let info = build_echo_response(&addr, conn, &state, version, &headers).await;
+    let info = Event::default()
+        .event("info")
+        .data(serde_json::to_string(&info)?);
+    let heartbeats = stream::unfold(1u64, move |seq| async move {
+        tokio::time::sleep(interval).await;
+        Some((heartbeat(seq), seq + 1))
+    })
+    .take((MAX_STREAM.as_secs() / interval.as_secs()) as usize);
+    let events = stream::once(async { info }).chain(heartbeats).map(Ok);
+    Ok(Sse::new(events))
+}
+
+/// Heartbeat number `seq`, with the time it was sent.
+fn heartbeat(seq: u64) -> Event {
+    metrics::counter!("sse_heartbeats_sent_total").increment(1);
+    Event::default()
+        .event("heartbeat")
+        .id(seq.to_string())
+        .data(format!(
+            r#"{{"seq":{seq},"timestamp":"{}"}}"#,
+            UtcDateTime::from_system_time(SystemTime::now()).rfc3339(false)
+        ))
+}
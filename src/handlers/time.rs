@@ -0,0 +1,231 @@
+//! `/time`: the server's clock, as `key: value` lines by default (other
+//! formats via `Accept` or `?format=`), in the forms scripts usually want.
+//!
+//! `/clock-skew` compares it with the client's: `?t=` (Unix seconds, with
+//! an optional fraction, or milliseconds) or the request's `Date` header.
+//! A skewed clock is behind most "invalid TOTP code" and "certificate not
+//! yet valid" reports. The offset also contains the request's one-way
+//! network delay, so anything within a round trip is noise.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::body::Body;
+use axum::extract::Query;
+use axum::http::Response;
+use axum::http::header::{self, HeaderMap};
+use serde::{Deserialize, Serialize};
+
+use super::echo::negotiated_response;
+use crate::datetime::UtcDateTime;
+use crate::errors::AppError;
+use crate::format::{FormatQuery, ResponseFormat};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ServerTime {
+    /// UTC, to the millisecond: `2026-10-15T05:04:06.123Z`.
+    pub rfc3339: String,
+    /// Seconds since the Unix epoch.
+    pub unix: u64,
+    /// Milliseconds since the Unix epoch.
+    pub unix_ms: u64,
+    /// The RFC 7231 `Date` header form: `Thu, 15 Oct 2026 05:04:06 GMT`.
+    pub http_date: String,
+}
+
+impl ServerTime {
+    /// `time` in each form; times before the epoch are clamped to it.
+    pub fn at(time: SystemTime) -> Self {
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        Self {
+            rfc3339: UtcDateTime::from_system_time(time).rfc3339(true),
+            unix: since_epoch.as_secs(),
+            unix_ms: since_epoch.as_millis() as u64,
+            http_date: httpdate::fmt_http_date(UNIX_EPOCH + since_epoch),
+        }
+    }
+}
+
+// GET /time — the server's current time (text by default; other formats via
+// Accept or ?format=)
+pub async fn time_handler(
+    Query(query): Query<FormatQuery>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/time").increment(1);
+    let format = ResponseFormat::select(&query, &headers, ResponseFormat::Text)?;
+    negotiated_response(format, "time", &ServerTime::at(SystemTime::now()), None)
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ClockSkewQuery {
+    pub t: Option<String>,
+    pub format: Option<String>,
+}
+
+/// `?t=` values above this are milliseconds: as seconds they'd be past the
+/// year 5000.
+const MAX_UNIX_SECS: f64 = 1e11;
+
+#[derive(Debug, Serialize)]
+pub struct ClockSkew {
+    /// Where the client's time came from: `query` (`?t=`) or `date_header`.
+    pub source: &'static str,
+    pub client_time: ServerTime,
+    /// When the server received the request.
+    pub server_time: ServerTime,
+    /// Client minus server; positive when the client's clock is ahead.
+    pub offset_ms: i64,
+    /// Resolution of the client's timestamp: 1000 for whole seconds.
+    pub precision_ms: u64,
+    /// The offset in plain words.
+    pub summary: String,
+}
+
+impl ClockSkew {
+    fn new(
+        source: &'static str,
+        client: SystemTime,
+        precision_ms: u64,
+        server: SystemTime,
+    ) -> Self {
+        let offset_ms = match client.duration_since(server) {
+            Ok(ahead) => ahead.as_millis() as i64,
+            Err(behind) => -(behind.duration().as_millis() as i64),
+        };
+        let summary = if offset_ms.unsigned_abs() < precision_ms {
+            "in sync".to_string()
+        } else {
+            let direction = if offset_ms > 0 { "ahead" } else { "behind" };
+            format!(
+                "client clock is {:.3}s {direction}",
+                offset_ms.abs() as f64 / 1000.0
+            )
+        };
+        Self {
+            source,
+            client_time: ServerTime::at(client),
+            server_time: ServerTime::at(server),
+            offset_ms,
+            precision_ms,
+            summary,
+        }
+    }
+}
+
+/// A `?t=` value as a time and its precision in milliseconds.
+fn parse_client_time(raw: &str) -> Result<(SystemTime, u64), AppError> {
+    let raw = raw.trim();
+    let invalid = || {
+        AppError::BadRequest(format!(
+            "invalid t {raw:?}; expected Unix seconds (e.g. 1792040646.5) or milliseconds"
+        ))
+    };
+    if let Ok(int) = raw.parse::<u64>() {
+        return Ok(if int as f64 > MAX_UNIX_SECS {
+            (UNIX_EPOCH + Duration::from_millis(int), 1)
+        } else {
+            (UNIX_EPOCH + Duration::from_secs(int), 1000)
+        });
+    }
+    let secs: f64 = raw.parse().map_err(|_| invalid())?;
+    if !(0.0..=MAX_UNIX_SECS).contains(&secs) {
+        return Err(invalid());
+    }
+    let decimals = raw.split_once('.').map_or(0, |(_, frac)| frac.len());
+    let precision_ms = 10u64.pow(3u32.saturating_sub(decimals as u32));
+    Ok((UNIX_EPOCH + Duration::from_secs_f64(secs), precision_ms))
+}
+
+// GET /clock-skew — offset between the client's clock (?t= or Date) and the
+// server's (text by default; other formats via Accept or ?format=)
+pub async fn clock_skew_handler(
+    Query(query): Query<ClockSkewQuery>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, AppError> {
+    let received = SystemTime::now();
+    metrics::counter!("http_requests_total", "endpoint" => "/clock-skew").increment(1);
+    let format = ResponseFormat::select(
+        &FormatQuery {
+            format: query.format,
+        },
+        &headers,
+        ResponseFormat::Text,
+    )?;
+    let skew = match (&query.t, headers.get(header::DATE)) {
+        (Some(t), _) => {
+            let (client, precision_ms) = parse_client_time(t)?;
+            ClockSkew::new("query", client, precision_ms, received)
+        }
+        (None, Some(date)) => {
+            let client = date
+                .to_str()
+                .ok()
+                .and_then(|v| httpdate::parse_http_date(v).ok())
+                .ok_or_else(|| AppError::BadRequest("invalid Date header".to_string()))?;
+            ClockSkew::new("date_header", client, 1000, received)
+        }
+        (None, None) => {
+            return Err(AppError::BadRequest(
+                "send the client's time as ?t=<unix seconds> or a Date header".to_string(),
+            ));
+        }
+    };
+    negotiated_response(format, "clock-skew", &skew, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn all_forms_agree() {
+        let time = ServerTime::at(UNIX_EPOCH + Duration::from_millis(1_792_040_646_123));
+        assert_eq!(
+            time,
+            ServerTime {
+                rfc3339: "2026-10-15T05:04:06.123Z".into(),
+                unix: 1_792_040_646,
+                unix_ms: 1_792_040_646_123,
+                http_date: "Thu, 15 Oct 2026 05:04:06 GMT".into(),
+            }
+        );
+        assert_eq!(ServerTime::at(UNIX_EPOCH - Duration::from_secs(1)).unix, 0);
+    }
+
+    #[test]
+    fn client_time_forms() {
+        let at = |secs: u64, ms: u64| UNIX_EPOCH + Duration::from_millis(secs * 1000 + ms);
+        assert_eq!(
+            parse_client_time("1792040646").unwrap(),
+            (at(1_792_040_646, 0), 1000)
+        );
+        assert_eq!(
+            parse_client_time("1792040646123").unwrap(),
+            (at(1_792_040_646, 123), 1)
+        );
+        let (time, precision) = parse_client_time("1792040646.5").unwrap();
+        assert_eq!((time, precision), (at(1_792_040_646, 500), 100));
+        assert!(parse_client_time("yesterday").is_err());
+        assert!(parse_client_time("-5").is_err());
+    }
+
+    #[test]
+    fn offset_sign_and_summary() {
+        let server = UNIX_EPOCH + Duration::from_secs(1_792_040_646);
+        let ahead = ClockSkew::new("query", server + Duration::from_millis(2500), 1, server);
+        assert_eq!(ahead.offset_ms, 2500);
+        assert_eq!(ahead.summary, "client clock is 2.500s ahead");
+
+        let behind = ClockSkew::new("query", server - Duration::from_secs(90), 1000, server);
+        assert_eq!(behind.offset_ms, -90_000);
+        assert_eq!(behind.summary, "client clock is 90.000s behind");
+
+        let close = ClockSkew::new(
+            "date_header",
+            server - Duration::from_millis(400),
+            1000,
+            server,
+        );
+        assert_eq!(close.summary, "in sync");
+    }
+}
@@ -0,0 +1,47 @@
+//! `/tls`: what the client's TLS connection to this server negotiated, for
+//! checking a TLS stack against a known-good server. Only known when this
+//! server terminates TLS; anything else gets a 404.
+//!
+//! `/cert`: the certificate the client presented, with `TLS_CLIENT_AUTH`
+//! (see [`crate::listener::tls`]); a 404 when there is none.
+
+use axum::body::Body;
+use axum::extract::Extension;
+use axum::http::Response;
+
+use super::echo::json_response;
+use crate::errors::AppError;
+use crate::listener::tls::{ClientCert, TlsSession};
+
+// GET /tls — version, cipher suite, ALPN, SNI and resumption of the
+// connection
+pub async fn tls_handler(
+    session: Option<Extension<TlsSession>>,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/tls").increment(1);
+    let Some(Extension(session)) = session else {
+        return Err(AppError::NotFound(
+            "not a TLS connection; connect over HTTPS to this server".to_string(),
+        ));
+    };
+    json_response(&session)
+}
+
+// GET /cert — subject, issuer, SANs, validity and fingerprint of the client
+// certificate
+pub async fn cert_handler(
+    session: Option<Extension<TlsSession>>,
+    cert: Option<Extension<ClientCert>>,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/cert").increment(1);
+    match (session, cert) {
+        (_, Some(Extension(cert))) => json_response(&cert),
+        (Some(_), None) => Err(AppError::NotFound(
+            "no client certificate presented (TLS_CLIENT_AUTH must be enabled to ask for one)"
+                .to_string(),
+        )),
+        (None, None) => Err(AppError::NotFound(
+            "not a TLS connection; connect over HTTPS to this server".to_string(),
+        )),
+    }
+}
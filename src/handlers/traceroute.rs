@@ -0,0 +1,36 @@
+//! `/trace-me`: a traceroute from the server to the client, streamed as
+//! newline-delimited JSON with one line per hop as each is probed.
+//!
+//! See [`crate::traceroute`] for what it needs and the safeguards; with
+//! `TRACE_ENABLED` off the endpoint is a 404.
+
+use std::sync::Arc;
+
+use axum::body::{Body, Bytes};
+use axum::extract::State;
+use axum::http::header;
+use axum::http::{Response, StatusCode};
+use futures::StreamExt;
+
+use crate::client_ip::ClientIp;
+use crate::errors::AppError;
+use crate::state::AppState;
+
+// GET /trace-me — traceroute to the client, one JSON line per hop
+pub async fn trace_me_handler(
+    State(state): State<Arc<AppState>>,
+    ClientIp(ip): ClientIp,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/trace-me").increment(1);
+    let hops = state.tracer.trace(ip)?.map(|hop| {
+        let mut line = serde_json::to_vec(&hop)?;
+        line.push(b'\n');
+        Ok::<_, serde_json::Error>(Bytes::from(line))
+    });
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/x-ndjson")
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::from_stream(hops))
+        .map_err(|_| AppError::HttpBuilderError)
+}
@@ -0,0 +1,65 @@
+//! `/uuid`: freshly generated UUIDs, one per line as plain text by default
+//! (other formats via `Accept` or `?format=`). `?count=` asks for up to 100
+//! at once and `?version=` picks random v4 (the default) or time-ordered
+//! v7.
+
+use axum::body::Body;
+use axum::extract::Query;
+use axum::http::Response;
+use axum::http::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::echo::negotiated_response;
+use crate::errors::AppError;
+use crate::format::{FormatQuery, ResponseFormat};
+
+/// Most UUIDs one request can ask for.
+const MAX_COUNT: usize = 100;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct UuidQuery {
+    pub count: Option<usize>,
+    pub version: Option<u8>,
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UuidResponse {
+    pub uuids: Vec<String>,
+}
+
+// GET /uuid — new UUIDs (text by default; other formats via Accept or
+// ?format=)
+pub async fn uuid_handler(
+    Query(query): Query<UuidQuery>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, AppError> {
+    metrics::counter!("http_requests_total", "endpoint" => "/uuid").increment(1);
+    let format = ResponseFormat::select(
+        &FormatQuery {
+            format: query.format,
+        },
+        &headers,
+        ResponseFormat::Text,
+    )?;
+    let count = query.count.unwrap_or(1);
+    if !(1..=MAX_COUNT).contains(&count) {
+        return Err(AppError::BadRequest(format!(
+            "count must be between 1 and {MAX_COUNT}"
+        )));
+    }
+    let generate = match query.version.unwrap_or(4) {
+        4 => Uuid::new_v4,
+        7 => Uuid::now_v7,
+        version => {
+            return Err(AppError::BadRequest(format!(
+                "unsupported UUID version {version}; use 4 or 7"
+            )));
+        }
+    };
+
+    let uuids: Vec<String> = (0..count).map(|_| generate().to_string()).collect();
+    let text = uuids.join("\n");
+    negotiated_response(format, "uuid", &UuidResponse { uuids }, Some(text))
+}
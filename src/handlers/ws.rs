@@ -0,0 +1,60 @@
+//! `/ws`: a WebSocket echo, for checking that proxies and CDNs in front of
+//! a service pass WebSockets through. The first message is the same JSON
+//! as `/`, describing the upgrade request as the server saw it; after that
+//! every text or binary frame is sent straight back. Messages are capped at
+//! `MAX_BODY_BYTES`.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{ConnectInfo, State};
+use axum::http::Version;
+use axum::http::header::HeaderMap;
+use axum::response::Response;
+
+use super::echo::{EchoResponse, build_echo_response};
+use crate::listener::ConnectionDetails;
+use crate::state::AppState;
+
+// GET /ws — upgrade to a WebSocket that sends the connection info, then
+// echoes every frame
+pub async fn ws_handler(
+    upgrade: WebSocketUpgrade,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    conn: ConnectionDetails,
+    State(state): State<Arc<AppState>>,
+    version: Version,
+    headers: HeaderMap,
+) -> Response {
+    metrics::counter!("http_requests_total", "endpoint" => "/ws").increment(1);
+    let info = build_echo_response(&addr, conn, &state, version, &headers).await;
+    let max = state.config.load().max_body_bytes;
+    upgrade
+        .max_message_size(max)
+        .max_frame_size(max)
+        .on_upgrade(move |socket| echo_frames(socket, info))
+}
+
+/// Send `info`, then echo until the client closes. Pings are answered by
+/// the WebSocket layer itself.
+async fn echo_frames(mut socket: WebSocket, info: EchoResponse) {
+    let Ok(info) = serde_json::to_string_pretty(&info) else {
+        return;
+    };
+    if socket.send(Message::Text(info.into())).await.is_err() {
+        return;
+    }
+    while let Some(Ok(message)) = socket.recv().await {
+        match message {
+            Message::Text(_) | Message::Binary(_) => {
+                metrics::counter!("websocket_messages_echoed_total").increment(1);
+                if socket.send(message).await.is_err() {
+                    return;
+                }
+            }
+            Message::Close(_) => return,
+            Message::Ping(_) | Message::Pong(_) => {}
+        }
+    }
+}
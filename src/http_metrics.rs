@@ -0,0 +1,102 @@
+//! Per-request Prometheus metrics: response counts by route and status,
+//! latency histograms and response body bytes.
+//!
+//! Routes are labelled with their pattern (`/headers/{name}`), never the raw
+//! path, so a client can't blow up label cardinality by requesting unique
+//! URLs. Requests that match no route share the `unmatched` label.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use axum::body::{Body, Bytes};
+use axum::extract::MatchedPath;
+use axum::http::{Request, Response};
+use axum::middleware::Next;
+use http_body::{Body as HttpBody, Frame, SizeHint};
+use metrics::Counter;
+#[cfg(feature = "metrics")]
+use metrics_exporter_prometheus::{BuildError, Matcher, PrometheusBuilder};
+
+/// Latency buckets in seconds. Most endpoints answer from memory in well
+/// under a millisecond; the upper buckets catch reverse DNS and remote
+/// GeoIP lookups.
+#[cfg(feature = "metrics")]
+const DURATION_BUCKETS: &[f64] = &[
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+/// A Prometheus builder with histogram buckets for the metrics recorded
+/// here. Without buckets the exporter renders histograms as summaries.
+#[cfg(feature = "metrics")]
+pub fn prometheus_builder() -> Result<PrometheusBuilder, BuildError> {
+    PrometheusBuilder::new().set_buckets_for_metric(
+        Matcher::Full("http_request_duration_seconds".to_string()),
+        DURATION_BUCKETS,
+    )
+}
+
+/// Record `http_responses_total`, `http_request_duration_seconds` and
+/// `http_response_body_bytes_total` for every request. Body bytes are
+/// counted as they're sent, so streamed responses are included.
+pub async fn http_metrics_middleware(request: Request<Body>, next: Next) -> Response<Body> {
+    let start = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let response = next.run(request).await;
+
+    let status = response.status().as_u16().to_string();
+    metrics::counter!(
+        "http_responses_total",
+        "method" => method.clone(),
+        "route" => route.clone(),
+        "status" => status
+    )
+    .increment(1);
+    metrics::histogram!(
+        "http_request_duration_seconds",
+        "method" => method,
+        "route" => route.clone()
+    )
+    .record(start.elapsed().as_secs_f64());
+
+    let bytes = metrics::counter!("http_response_body_bytes_total", "route" => route);
+    response.map(|body| Body::new(CountingBody { inner: body, bytes }))
+}
+
+/// Adds the size of every data frame to `bytes` as it's polled.
+struct CountingBody {
+    inner: Body,
+    bytes: Counter,
+}
+
+impl HttpBody for CountingBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &poll
+            && let Some(data) = frame.data_ref()
+        {
+            self.bytes.increment(data.len() as u64);
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
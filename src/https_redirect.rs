@@ -0,0 +1,92 @@
+//! Redirects from the plain-HTTP listener to HTTPS (`HTTP_PORT`,
+//! `HTTPS_REDIRECT`).
+//!
+//! Requests on a redirecting listener carry an [`HttpsRedirect`] extension
+//! and get a 301 to the same host and path over HTTPS. The paths in
+//! `HTTPS_REDIRECT_EXEMPT` (`/ip` by default) are answered as usual, so
+//! `curl http://host/ip` keeps working without `-L`.
+
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::uri::Authority;
+use axum::http::{HeaderValue, Request, Response, StatusCode, header};
+use axum::middleware::Next;
+
+/// Marks requests from a listener that redirects to HTTPS on `port`.
+#[derive(Debug, Clone, Copy)]
+pub struct HttpsRedirect {
+    pub port: u16,
+}
+
+pub async fn https_redirect_middleware(
+    State(exempt): State<Arc<[String]>>,
+    request: Request<Body>,
+    next: Next,
+) -> Response<Body> {
+    let Some(redirect) = request.extensions().get::<HttpsRedirect>().copied() else {
+        return next.run(request).await;
+    };
+    if exempt.iter().any(|path| path == request.uri().path()) {
+        return next.run(request).await;
+    }
+    // Without a usable host there is nowhere to send the client.
+    let Some(location) = location(&request, redirect.port) else {
+        return next.run(request).await;
+    };
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = StatusCode::MOVED_PERMANENTLY;
+    response.headers_mut().insert(header::LOCATION, location);
+    response
+}
+
+/// The HTTPS URL for `request`: its host (from the URI or `Host`), `port`
+/// unless it's 443, and the original path and query.
+fn location(request: &Request<Body>, port: u16) -> Option<HeaderValue> {
+    let authority = match request.uri().authority() {
+        Some(authority) => authority.clone(),
+        None => request
+            .headers()
+            .get(header::HOST)?
+            .to_str()
+            .ok()?
+            .parse::<Authority>()
+            .ok()?,
+    };
+    let port = if port == 443 {
+        String::new()
+    } else {
+        format!(":{port}")
+    };
+    let path = request.uri().path_and_query().map_or("/", |p| p.as_str());
+    HeaderValue::from_str(&format!("https://{}{port}{path}", authority.host())).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(uri: &str, host: &str) -> Request<Body> {
+        Request::builder()
+            .uri(uri)
+            .header(header::HOST, host)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn location_keeps_host_path_and_query() {
+        let to = location(&request("/headers?x=1", "echo.example.com:80"), 443).unwrap();
+        assert_eq!(to, "https://echo.example.com/headers?x=1");
+        let to = location(&request("/", "[2001:db8::1]"), 8443).unwrap();
+        assert_eq!(to, "https://[2001:db8::1]:8443/");
+    }
+
+    #[test]
+    fn no_location_without_a_valid_host() {
+        let no_host = Request::builder().uri("/").body(Body::empty()).unwrap();
+        assert!(location(&no_host, 443).is_none());
+        assert!(location(&request("/", "bad host"), 443).is_none());
+    }
+}
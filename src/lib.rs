@@ -1,10 +1,109 @@
+//! Echo client connection metadata over HTTP.
+//!
+//! The `ipecho` binary is a thin wrapper around [`server::run`]. Other axum
+//! applications can mount the same endpoints with [`router`], under a
+//! prefix if they like, or route individual handlers from [`handlers`]
+//! against a [`state::AppState`] of their own:
+//!
+//! ```no_run
+//! use std::net::SocketAddr;
+//!
+//! # async fn example() -> std::io::Result<()> {
+//! let echo = ipecho::router(ipecho::config::Config::default());
+//! let app = axum::Router::new().nest("/echo", echo);
+//! let listener = tokio::net::TcpListener::bind("0.0.0.0:8080").await?;
+//! axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await
+//! # }
+//! ```
+
+use axum::Router;
+
+pub use crate::client_ip::{ClientIp, ClientIpConfig};
+
+use crate::config::Config;
+use crate::ratelimit::RateLimitState;
+use crate::state::AppState;
+
+pub mod accept_language;
+pub mod access_log;
+#[cfg(feature = "tls")]
+pub mod acme;
+pub mod addr;
+pub mod cli;
+pub mod client_ip;
 pub mod config;
+#[cfg(feature = "compression")]
+pub mod compression;
+pub mod cors;
+pub mod datetime;
+#[cfg(feature = "dns")]
+pub mod dns_server;
+pub mod dual_stack;
 pub mod errors;
+pub mod format;
+pub mod forwarded;
+pub mod geoip;
 pub mod handlers;
+pub mod http_metrics;
+pub mod https_redirect;
+pub mod limits;
+pub mod listener;
+pub mod load_shed;
+#[cfg(feature = "log-shipping")]
+pub mod log_file;
+pub mod logging;
 pub mod lookup;
+pub mod media_type;
+pub mod outbound;
+pub mod ping;
+pub mod portcheck;
+#[cfg(unix)]
+pub mod privileges;
 pub mod providers;
 pub mod ratelimit;
+pub mod rdns;
+pub mod reload;
+pub mod resolver_check;
 pub mod request_id;
 pub mod routes;
+pub mod security_headers;
+pub mod server;
+pub mod session;
 pub mod state;
+#[cfg(feature = "metrics")]
+pub mod statsd;
+#[cfg(feature = "cloud-ranges")]
 pub mod sync;
+#[cfg(feature = "log-shipping")]
+pub mod syslog;
+pub mod tcp_echo;
+pub mod timeout;
+pub mod traceroute;
+pub mod ttl_map;
+pub mod udp_echo;
+pub mod user_agent;
+
+/// The echo endpoints and their middleware as a standalone [`Router`], for
+/// nesting in another application. It must be served with
+/// `into_make_service_with_connect_info::<SocketAddr>()`, since the handlers
+/// read the peer address.
+///
+/// Spawns the cloud IP range sync (with the `cloud-ranges` feature) and
+/// rate-limiter eviction tasks, so it must be called within a Tokio
+/// runtime. GeoIP enrichment, the access log and listener settings are left
+/// to [`server::run`]; build an [`AppState`] and call
+/// [`routes::create_router`] to configure those yourself. The router's own
+/// `/metrics` stays empty: installing a global metrics recorder is left to
+/// the application.
+pub fn router(config: Config) -> Router {
+    let rl_state = RateLimitState::new(config.rate_limit_per_second, config.rate_limit_burst);
+    let state = AppState::new(config);
+    // Ready as soon as the host application serves it.
+    state
+        .listener_bound
+        .store(true, std::sync::atomic::Ordering::Relaxed);
+    #[cfg(feature = "cloud-ranges")]
+    tokio::spawn(sync::scheduler::start_sync_loop(state.clone()));
+    tokio::spawn(rl_state.clone().evict_idle());
+    routes::create_router(state, rl_state)
+}
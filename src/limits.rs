@@ -0,0 +1,193 @@
+//! Request size limits (`MAX_HEADER_BYTES`, `MAX_BODY_BYTES`).
+//!
+//! Oversized requests are refused with a JSON 431 or 413 before reaching a
+//! handler. A declared `Content-Length` over the limit is rejected up front;
+//! a chunked body is cut off once it passes the limit, so a handler reading
+//! it gets an error rather than buffering without bound. The accept loop
+//! additionally caps hyper's read buffer, so a request head far beyond the
+//! limit is dropped before it's fully buffered.
+//!
+//! `/speedtest/upload` exists to receive large bodies, so it's held to
+//! `MAX_SPEEDTEST_BYTES` instead.
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{HeaderMap, Request, Response, header};
+use axum::middleware::Next;
+use axum::response::IntoResponse;
+use http_body_util::Limited;
+
+use crate::config::Config;
+use crate::errors::AppError;
+
+/// The one path with its own body limit.
+pub const UPLOAD_PATH: &str = "/speedtest/upload";
+
+/// Limits enforced by [`size_limit_middleware`].
+#[derive(Debug, Clone, Copy)]
+pub struct SizeLimits {
+    pub max_header_bytes: usize,
+    pub max_body_bytes: usize,
+    /// Body limit for [`UPLOAD_PATH`].
+    pub max_upload_bytes: usize,
+}
+
+impl SizeLimits {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_header_bytes: config.max_header_bytes,
+            max_body_bytes: config.max_body_bytes,
+            // With speed tests off, the upload path is like any other.
+            max_upload_bytes: match config.max_speedtest_bytes {
+                0 => config.max_body_bytes,
+                max => max,
+            },
+        }
+    }
+
+    fn body_limit(&self, path: &str) -> usize {
+        if path == UPLOAD_PATH {
+            self.max_upload_bytes
+        } else {
+            self.max_body_bytes
+        }
+    }
+}
+
+pub async fn size_limit_middleware(
+    State(limits): State<SizeLimits>,
+    request: Request<Body>,
+    next: Next,
+) -> Response<Body> {
+    if header_bytes(request.headers()) > limits.max_header_bytes {
+        metrics::counter!("http_request_too_large_total", "part" => "headers").increment(1);
+        return AppError::HeadersTooLarge(limits.max_header_bytes).into_response();
+    }
+    let max_body_bytes = limits.body_limit(request.uri().path());
+    let declared = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if declared.is_some_and(|len| len > max_body_bytes as u64) {
+        metrics::counter!("http_request_too_large_total", "part" => "body").increment(1);
+        return AppError::PayloadTooLarge(max_body_bytes).into_response();
+    }
+
+    let request = request.map(|body| Body::new(Limited::new(body, max_body_bytes)));
+    next.run(request).await
+}
+
+/// Size of the header section as sent over HTTP/1: `name: value\r\n` per
+/// field.
+fn header_bytes(headers: &HeaderMap) -> usize {
+    headers
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len() + 4)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::Router;
+    use axum::body::Bytes;
+    use axum::http::StatusCode;
+    use axum::routing::post;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn app() -> Router {
+        let count = post(|body: Body| async move {
+            match body.collect().await {
+                Ok(collected) => collected.to_bytes().len().to_string().into_response(),
+                Err(_) => AppError::PayloadTooLarge(16).into_response(),
+            }
+        });
+        Router::new()
+            .route("/", count.clone())
+            .route(UPLOAD_PATH, count)
+            .layer(axum::middleware::from_fn_with_state(
+                SizeLimits {
+                    max_header_bytes: 64,
+                    max_body_bytes: 16,
+                    max_upload_bytes: 32,
+                },
+                size_limit_middleware,
+            ))
+    }
+
+    async fn json_error(response: Response<Body>) -> serde_json::Value {
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn oversized_headers_get_431() {
+        let request = Request::post("/")
+            .header("x-padding", "a".repeat(64))
+            .body(Body::empty())
+            .unwrap();
+        let response = app().oneshot(request).await.unwrap();
+        assert_eq!(
+            response.status(),
+            StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE
+        );
+        assert_eq!(
+            json_error(response).await["error"],
+            "Request headers larger than 64 bytes"
+        );
+    }
+
+    #[tokio::test]
+    async fn declared_oversized_body_gets_413() {
+        let request = Request::post("/")
+            .header(header::CONTENT_LENGTH, "17")
+            .body(Body::from("x".repeat(17)))
+            .unwrap();
+        let response = app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(
+            json_error(response).await["error"],
+            "Request body larger than 16 bytes"
+        );
+    }
+
+    #[tokio::test]
+    async fn streamed_body_is_cut_off_at_the_limit() {
+        let chunks = ["0123456789", "0123456789"].map(|c| Ok::<_, std::io::Error>(Bytes::from(c)));
+        let request = Request::post("/")
+            .body(Body::from_stream(futures::stream::iter(chunks)))
+            .unwrap();
+        let response = app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn body_within_limit_passes() {
+        let request = Request::post("/").body(Body::from("hello")).unwrap();
+        let response = app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn upload_path_has_its_own_limit() {
+        let request = Request::post(UPLOAD_PATH)
+            .body(Body::from("x".repeat(20)))
+            .unwrap();
+        let response = app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = Request::post(UPLOAD_PATH)
+            .header(header::CONTENT_LENGTH, "33")
+            .body(Body::from("x".repeat(33)))
+            .unwrap();
+        let response = app().oneshot(request).await.unwrap();
+        assert_eq!(
+            json_error(response).await["error"],
+            "Request body larger than 32 bytes"
+        );
+    }
+}
@@ -0,0 +1,510 @@
+//! TLS ClientHello capture and JA3/JA4 fingerprints.
+//!
+//! rustls parses the ClientHello but doesn't expose what these
+//! fingerprints need (extension order, GREASE values, point formats), so
+//! [`peek`] reads the handshake's first records off the socket itself and
+//! [`Rewind`] replays them to rustls. The fingerprint is computed once per
+//! connection and attached to its requests as a [`TlsFingerprint`].
+//!
+//! - JA3 (Salesforce): `version,ciphers,extensions,groups,point_formats`
+//!   in decimal, GREASE removed, and its MD5.
+//! - JA4 (FoxIO, BSD-3-Clause): e.g. `t13d1516h2_8daaf6152771_e5627efa2ab1`,
+//!   with `ja4_r` the unhashed form.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+#[cfg(feature = "tls")]
+use md5::Digest;
+use serde::Serialize;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+
+const RECORD_HANDSHAKE: u8 = 0x16;
+const HANDSHAKE_CLIENT_HELLO: u8 = 0x01;
+
+/// Largest ClientHello read ahead of rustls; rustls rejects bigger ones
+/// anyway.
+const MAX_CLIENT_HELLO: usize = 64 * 1024;
+
+#[cfg(feature = "tls")]
+const EXT_SERVER_NAME: u16 = 0x0000;
+const EXT_SUPPORTED_GROUPS: u16 = 0x000a;
+const EXT_EC_POINT_FORMATS: u16 = 0x000b;
+const EXT_SIGNATURE_ALGORITHMS: u16 = 0x000d;
+const EXT_ALPN: u16 = 0x0010;
+const EXT_SUPPORTED_VERSIONS: u16 = 0x002b;
+
+/// A client's TLS fingerprints, a request extension on TLS connections.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TlsFingerprint {
+    pub ja3: String,
+    pub ja3_hash: String,
+    pub ja4: String,
+    pub ja4_r: String,
+}
+
+/// The ClientHello fields fingerprints are made of, GREASE included.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientHello {
+    pub legacy_version: u16,
+    pub cipher_suites: Vec<u16>,
+    /// Extension types in the order sent.
+    pub extensions: Vec<u16>,
+    pub supported_groups: Vec<u16>,
+    pub ec_point_formats: Vec<u8>,
+    pub signature_algorithms: Vec<u16>,
+    pub alpn: Vec<Vec<u8>>,
+    pub supported_versions: Vec<u16>,
+}
+
+/// Read the TLS records holding the ClientHello from `stream`, returning
+/// every byte read. Stops early, returning what it has, at anything that
+/// isn't a handshake record so rustls can report the error.
+pub async fn peek<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    // Handshake bytes gathered from the records so far.
+    let mut handshake = 0usize;
+    loop {
+        let mut header = [0u8; 5];
+        stream.read_exact(&mut header).await?;
+        bytes.extend(header);
+        let length = usize::from(u16::from_be_bytes([header[3], header[4]]));
+        if header[0] != RECORD_HANDSHAKE || bytes.len() + length > MAX_CLIENT_HELLO {
+            return Ok(bytes);
+        }
+        let start = bytes.len();
+        bytes.resize(start + length, 0);
+        stream.read_exact(&mut bytes[start..]).await?;
+        handshake += length;
+
+        // The handshake header is in the first record: type and a 24-bit
+        // length.
+        let body = handshake_fragments(&bytes);
+        if body.len() >= 4 && handshake >= 4 + u24(&body[1..4]) {
+            return Ok(bytes);
+        }
+    }
+}
+
+/// The handshake-layer bytes of the handshake records in `records`.
+fn handshake_fragments(mut records: &[u8]) -> Vec<u8> {
+    let mut fragments = Vec::new();
+    while records.len() >= 5 && records[0] == RECORD_HANDSHAKE {
+        let length = usize::from(u16::from_be_bytes([records[3], records[4]]));
+        let Some(fragment) = records.get(5..5 + length) else {
+            break;
+        };
+        fragments.extend_from_slice(fragment);
+        records = &records[5 + length..];
+    }
+    fragments
+}
+
+fn u24(bytes: &[u8]) -> usize {
+    usize::from(bytes[0]) << 16 | usize::from(bytes[1]) << 8 | usize::from(bytes[2])
+}
+
+impl ClientHello {
+    /// Parse the ClientHello from the records [`peek`] read.
+    pub fn parse(records: &[u8]) -> Option<Self> {
+        let handshake = handshake_fragments(records);
+        let (&kind, rest) = handshake.split_first()?;
+        if kind != HANDSHAKE_CLIENT_HELLO {
+            return None;
+        }
+        let length = u24(rest.get(..3)?);
+        let mut body = Reader(rest.get(3..3 + length)?);
+
+        let mut hello = Self {
+            legacy_version: body.u16()?,
+            ..Self::default()
+        };
+        body.skip(32)?; // random
+        let session_id = usize::from(body.u8()?);
+        body.skip(session_id)?;
+        let mut ciphers = body.vec16()?;
+        while !ciphers.0.is_empty() {
+            hello.cipher_suites.push(ciphers.u16()?);
+        }
+        let compression = usize::from(body.u8()?);
+        body.skip(compression)?;
+        if body.0.is_empty() {
+            return Some(hello);
+        }
+
+        let mut extensions = body.vec16()?;
+        while !extensions.0.is_empty() {
+            let kind = extensions.u16()?;
+            let mut data = extensions.vec16()?;
+            hello.extensions.push(kind);
+            match kind {
+                EXT_SUPPORTED_GROUPS => hello.supported_groups = data.vec16()?.u16s()?,
+                EXT_EC_POINT_FORMATS => hello.ec_point_formats = data.vec8()?.0.to_vec(),
+                EXT_SIGNATURE_ALGORITHMS => {
+                    hello.signature_algorithms = data.vec16()?.u16s()?;
+                }
+                EXT_ALPN => {
+                    let mut protocols = data.vec16()?;
+                    while !protocols.0.is_empty() {
+                        hello.alpn.push(protocols.vec8()?.0.to_vec());
+                    }
+                }
+                EXT_SUPPORTED_VERSIONS => hello.supported_versions = data.vec8()?.u16s()?,
+                _ => {}
+            }
+        }
+        Some(hello)
+    }
+
+    #[cfg(feature = "tls")]
+    pub fn fingerprint(&self) -> TlsFingerprint {
+        let ja3 = self.ja3();
+        let (ja4, ja4_r) = self.ja4();
+        TlsFingerprint {
+            ja3_hash: hex(&md5::Md5::digest(&ja3)),
+            ja3,
+            ja4,
+            ja4_r,
+        }
+    }
+
+    #[cfg(feature = "tls")]
+    fn ja3(&self) -> String {
+        fn join<T: ToString>(values: impl Iterator<Item = T>) -> String {
+            values.map(|v| v.to_string()).collect::<Vec<_>>().join("-")
+        }
+        format!(
+            "{},{},{},{},{}",
+            self.legacy_version,
+            join(self.cipher_suites.iter().filter(|&&v| !is_grease(v))),
+            join(self.extensions.iter().filter(|&&v| !is_grease(v))),
+            join(self.supported_groups.iter().filter(|&&v| !is_grease(v))),
+            join(self.ec_point_formats.iter()),
+        )
+    }
+
+    /// JA4 and its raw form (the JA4 specification, "JA4: TLS Client
+    /// Fingerprint").
+    #[cfg(feature = "tls")]
+    fn ja4(&self) -> (String, String) {
+        let ciphers: Vec<u16> = self
+            .cipher_suites
+            .iter()
+            .copied()
+            .filter(|&v| !is_grease(v))
+            .collect();
+        let extensions: Vec<u16> = self
+            .extensions
+            .iter()
+            .copied()
+            .filter(|&v| !is_grease(v))
+            .collect();
+        let version = self
+            .supported_versions
+            .iter()
+            .copied()
+            .filter(|&v| !is_grease(v))
+            .max()
+            .unwrap_or(self.legacy_version);
+        let version = match version {
+            0x0304 => "13",
+            0x0303 => "12",
+            0x0302 => "11",
+            0x0301 => "10",
+            0x0300 => "s3",
+            0x0002 => "s2",
+            _ => "00",
+        };
+        let sni = if extensions.contains(&EXT_SERVER_NAME) {
+            'd'
+        } else {
+            'i'
+        };
+        let alpn = match self.alpn.first().filter(|p| !p.is_empty()) {
+            None => "00".to_string(),
+            Some(p) => {
+                let (first, last) = (p[0], p[p.len() - 1]);
+                if first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric() {
+                    format!("{}{}", char::from(first), char::from(last))
+                } else {
+                    let hex = hex(p);
+                    format!("{}{}", &hex[..1], &hex[hex.len() - 1..])
+                }
+            }
+        };
+        let a = format!(
+            "t{version}{sni}{:02}{:02}{alpn}",
+            ciphers.len().min(99),
+            extensions.len().min(99)
+        );
+
+        let mut sorted_ciphers = ciphers.clone();
+        sorted_ciphers.sort_unstable();
+        let b = hex_list(&sorted_ciphers);
+        let mut sorted_extensions: Vec<u16> = extensions
+            .into_iter()
+            .filter(|&v| v != EXT_SERVER_NAME && v != EXT_ALPN)
+            .collect();
+        sorted_extensions.sort_unstable();
+        let mut c = hex_list(&sorted_extensions);
+        if !self.signature_algorithms.is_empty() {
+            c = format!("{c}_{}", hex_list(&self.signature_algorithms));
+        }
+
+        let ja4 = format!("{a}_{}_{}", truncated_sha256(&b), truncated_sha256(&c));
+        (ja4, format!("{a}_{b}_{c}"))
+    }
+}
+
+/// GREASE values (RFC 8701): `0x?a?a` with both bytes equal.
+#[cfg(feature = "tls")]
+fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+#[cfg(feature = "tls")]
+fn hex_list(values: &[u16]) -> String {
+    values
+        .iter()
+        .map(|v| format!("{v:04x}"))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// JA4's hash: the first 12 hex digits of SHA-256, or zeros for nothing.
+#[cfg(feature = "tls")]
+fn truncated_sha256(list: &str) -> String {
+    if list.is_empty() {
+        return "000000000000".to_string();
+    }
+    hex(&sha2::Sha256::digest(list))[..12].to_string()
+}
+
+#[cfg(feature = "tls")]
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Big-endian reads from a TLS structure.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let (head, tail) = (self.0.get(..n)?, self.0.get(n..)?);
+        self.0 = tail;
+        Some(head)
+    }
+
+    fn skip(&mut self, n: usize) -> Option<()> {
+        self.take(n).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let b = self.take(2)?;
+        Some(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    /// A vector with a one-byte length prefix.
+    fn vec8(&mut self) -> Option<Reader<'a>> {
+        let n = usize::from(self.u8()?);
+        self.take(n).map(Reader)
+    }
+
+    /// A vector with a two-byte length prefix.
+    fn vec16(&mut self) -> Option<Reader<'a>> {
+        let n = usize::from(self.u16()?);
+        self.take(n).map(Reader)
+    }
+
+    fn u16s(mut self) -> Option<Vec<u16>> {
+        let mut values = Vec::new();
+        while !self.0.is_empty() {
+            values.push(self.u16()?);
+        }
+        Some(values)
+    }
+}
+
+/// A stream that yields `prefix` before reading from `inner`: hands the
+/// bytes [`peek`] consumed back to the TLS acceptor.
+pub struct Rewind<S> {
+    prefix: Vec<u8>,
+    pos: usize,
+    inner: S,
+}
+
+impl<S> Rewind<S> {
+    pub fn new(prefix: Vec<u8>, inner: S) -> Self {
+        Self {
+            prefix,
+            pos: 0,
+            inner,
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Rewind<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.pos < self.prefix.len() {
+            let n = buf.remaining().min(self.prefix.len() - self.pos);
+            buf.put_slice(&self.prefix[self.pos..self.pos + n]);
+            self.pos += n;
+            if self.pos == self.prefix.len() {
+                self.prefix = Vec::new();
+                self.pos = 0;
+            }
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Rewind<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+
+    fn vec16(data: &[u8]) -> Vec<u8> {
+        [&(data.len() as u16).to_be_bytes()[..], data].concat()
+    }
+
+    fn u16s(values: &[u16]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_be_bytes()).collect()
+    }
+
+    /// A ClientHello with Chrome's ciphers and extensions, the example in
+    /// the JA4 specification, plus GREASE values.
+    fn chrome_hello() -> Vec<u8> {
+        let ciphers = [
+            0x0a0a, 0x1301, 0x1302, 0x1303, 0xc02b, 0xc02f, 0xc02c, 0xc030, 0xcca9, 0xcca8, 0xc013,
+            0xc014, 0x009c, 0x009d, 0x002f, 0x0035,
+        ];
+        let extension = |kind: u16, data: &[u8]| [&kind.to_be_bytes()[..], &vec16(data)].concat();
+        let mut extensions = Vec::new();
+        extensions.extend(extension(0x1a1a, &[]));
+        extensions.extend(extension(0x0000, &vec16(b"\0\0\x09localhost")));
+        extensions.extend(extension(0x0010, &vec16(b"\x02h2\x08http/1.1")));
+        extensions.extend(extension(
+            0x000d,
+            &vec16(&u16s(&[
+                0x0403, 0x0804, 0x0401, 0x0503, 0x0805, 0x0501, 0x0806, 0x0601,
+            ])),
+        ));
+        extensions.extend(extension(0x000a, &vec16(&u16s(&[0x2a2a, 29, 23, 24]))));
+        extensions.extend(extension(0x000b, &[1, 0]));
+        extensions.extend(extension(0x002b, &[6, 0x3a, 0x3a, 3, 4, 3, 3]));
+        for kind in [
+            0x0005, 0x0012, 0x0015, 0x0017, 0x001b, 0x0023, 0x002d, 0x0033, 0x4469, 0xff01,
+        ] {
+            extensions.extend(extension(kind, &[]));
+        }
+
+        let mut body = vec![3, 3];
+        body.extend([0u8; 32]);
+        body.push(0); // session id
+        body.extend(vec16(&u16s(&ciphers)));
+        body.extend([1, 0]); // null compression
+        body.extend(vec16(&extensions));
+        let mut handshake = vec![HANDSHAKE_CLIENT_HELLO];
+        handshake.extend(&(body.len() as u32).to_be_bytes()[1..]);
+        handshake.extend(body);
+        handshake
+    }
+
+    fn records(handshake: &[u8], split: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        for fragment in [&handshake[..split], &handshake[split..]] {
+            out.extend([RECORD_HANDSHAKE, 3, 1]);
+            out.extend(vec16(fragment));
+        }
+        out
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn fingerprints_chrome() {
+        let hello = ClientHello::parse(&records(&chrome_hello(), 100)).unwrap();
+        let fingerprint = hello.fingerprint();
+        assert_eq!(fingerprint.ja4, "t13d1516h2_8daaf6152771_e5627efa2ab1");
+        assert!(fingerprint.ja4_r.starts_with("t13d1516h2_002f,0035,009c,"));
+        assert_eq!(
+            fingerprint.ja3,
+            "771,4865-4866-4867-49195-49199-49196-49200-52393-52392-49171-49172-156-157-47-53,\
+             0-16-13-10-11-43-5-18-21-23-27-35-45-51-17513-65281,29-23-24,0"
+        );
+        assert_eq!(fingerprint.ja3_hash.len(), 32);
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn recognises_grease() {
+        assert!(is_grease(0x0a0a));
+        assert!(is_grease(0xfafa));
+        assert!(!is_grease(0x0a1a));
+        assert!(!is_grease(0x1301));
+    }
+
+    #[tokio::test]
+    async fn peeks_a_split_hello_and_replays_it() {
+        let wire = records(&chrome_hello(), 40);
+        let (mut client, mut server) = tokio::io::duplex(4096);
+        client.write_all(&wire).await.unwrap();
+        client.write_all(b"after").await.unwrap();
+
+        let peeked = peek(&mut server).await.unwrap();
+        assert_eq!(peeked, wire);
+
+        let mut rewound = Rewind::new(peeked, server);
+        let mut replayed = vec![0u8; wire.len() + 5];
+        rewound.read_exact(&mut replayed).await.unwrap();
+        assert_eq!(&replayed[..wire.len()], wire);
+        assert_eq!(&replayed[wire.len()..], b"after");
+    }
+
+    #[tokio::test]
+    async fn stops_at_non_handshake_records() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        client.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+        assert_eq!(peek(&mut server).await.unwrap(), b"GET /");
+    }
+}
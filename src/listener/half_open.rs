@@ -0,0 +1,177 @@
+//! Slowloris protection: connections that haven't sent a request yet.
+//!
+//! A connection is "half-open" from accept until its first request head has
+//! been parsed. Each source IP may hold at most `MAX_HALF_OPEN_PER_IP` of
+//! them, so one client can't tie up file descriptors by opening sockets and
+//! trickling bytes, and each must get its first request in within
+//! `FIRST_REQUEST_TIMEOUT_SECS` of being accepted, however slowly it
+//! progresses through the PROXY header, TLS handshake and request head.
+//! Trusted proxies are exempt from the per-IP cap, since every client
+//! behind them shares their address.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::Notify;
+
+/// Per-IP half-open connection counts.
+pub struct HalfOpenTracker {
+    /// 0 for no limit.
+    max_per_ip: usize,
+    counts: Mutex<HashMap<IpAddr, usize>>,
+}
+
+impl HalfOpenTracker {
+    pub fn new(max_per_ip: usize) -> Arc<Self> {
+        Arc::new(Self {
+            max_per_ip,
+            counts: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Count a new connection from `ip`, or `None` if it already has the
+    /// maximum half-open.
+    pub fn open(self: &Arc<Self>, ip: IpAddr) -> Option<HalfOpen> {
+        if self.max_per_ip == 0 {
+            return Some(HalfOpen::new(None));
+        }
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        let count = counts.entry(ip).or_default();
+        if *count >= self.max_per_ip {
+            return None;
+        }
+        *count += 1;
+        Some(HalfOpen::new(Some((self.clone(), ip))))
+    }
+
+    fn release(&self, ip: IpAddr) {
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(count) = counts.get_mut(&ip) {
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(&ip);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    fn count(&self, ip: IpAddr) -> usize {
+        let counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        counts.get(&ip).copied().unwrap_or(0)
+    }
+}
+
+/// One connection's half-open state. Released by [`HalfOpen::established`]
+/// or, failing that, when dropped.
+pub struct HalfOpen {
+    slot: Option<(Arc<HalfOpenTracker>, IpAddr)>,
+    established: AtomicBool,
+    notify: Notify,
+}
+
+impl HalfOpen {
+    fn new(slot: Option<(Arc<HalfOpenTracker>, IpAddr)>) -> Self {
+        Self {
+            slot,
+            established: AtomicBool::new(false),
+            notify: Notify::new(),
+        }
+    }
+
+    /// Not counted against any IP; still subject to the deadline.
+    pub fn untracked() -> Self {
+        Self::new(None)
+    }
+
+    /// Mark the connection as having sent a request. Called for every
+    /// request; only the first has any effect.
+    pub fn established(&self) {
+        if self.established.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        if let Some((tracker, ip)) = &self.slot {
+            tracker.release(*ip);
+        }
+        self.notify.notify_one();
+    }
+
+    /// Resolves if `timeout` passes before the first request; never resolves
+    /// otherwise, or when `timeout` is `None`.
+    pub async fn expired(&self, timeout: Option<Duration>) {
+        if let Some(timeout) = timeout
+            && tokio::time::timeout(timeout, self.notify.notified())
+                .await
+                .is_err()
+        {
+            return;
+        }
+        std::future::pending().await
+    }
+}
+
+impl Drop for HalfOpen {
+    fn drop(&mut self) {
+        if !*self.established.get_mut()
+            && let Some((tracker, ip)) = &self.slot
+        {
+            tracker.release(*ip);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IP: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(203, 0, 113, 9));
+
+    #[test]
+    fn caps_half_open_connections_per_ip() {
+        let tracker = HalfOpenTracker::new(2);
+        let first = tracker.open(IP).unwrap();
+        let _second = tracker.open(IP).unwrap();
+        assert!(tracker.open(IP).is_none());
+        assert!(tracker.open("203.0.113.10".parse().unwrap()).is_some());
+
+        // A request frees the slot; so does closing without one.
+        first.established();
+        first.established();
+        assert_eq!(tracker.count(IP), 1);
+        let third = tracker.open(IP).unwrap();
+        drop(third);
+        assert_eq!(tracker.count(IP), 1);
+        drop(first);
+        assert_eq!(tracker.count(IP), 1);
+    }
+
+    #[test]
+    fn zero_disables_the_cap() {
+        let tracker = HalfOpenTracker::new(0);
+        let open: Vec<_> = (0..100).map(|_| tracker.open(IP).unwrap()).collect();
+        assert_eq!(open.len(), 100);
+        assert_eq!(tracker.count(IP), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn deadline_fires_only_without_a_request() {
+        let idle = HalfOpen::untracked();
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            idle.expired(Some(Duration::from_secs(5))),
+        )
+        .await
+        .expect("deadline should fire");
+
+        let active = HalfOpen::untracked();
+        active.established();
+        let never = tokio::time::timeout(
+            Duration::from_secs(10),
+            active.expired(Some(Duration::from_secs(5))),
+        )
+        .await;
+        assert!(never.is_err());
+    }
+}
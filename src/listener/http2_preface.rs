@@ -0,0 +1,278 @@
+//! The opening frames of an HTTP/2 connection, as sent by the client.
+//!
+//! Clients differ in the SETTINGS they send, the connection window they
+//! ask for, PRIORITY frames and the order of the pseudo-headers in their
+//! first request; together these make Akamai's HTTP/2 fingerprint
+//! ("Passive Fingerprinting of HTTP/2 Clients", Black Hat EU 2017). The
+//! frames are parsed from the bytes [`super::raw_head::HeadRecorder`] kept
+//! when the connection's first request arrives.
+
+/// Sent by every HTTP/2 client before its first frame (RFC 9113 §3.4).
+const CONNECTION_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+const FRAME_HEADERS: u8 = 0x1;
+const FRAME_PRIORITY: u8 = 0x2;
+const FRAME_SETTINGS: u8 = 0x4;
+const FRAME_WINDOW_UPDATE: u8 = 0x8;
+
+const FLAG_ACK: u8 = 0x1;
+const FLAG_PADDED: u8 = 0x8;
+const FLAG_PRIORITY: u8 = 0x20;
+
+/// What a client sent before and with its first request.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Http2Preface {
+    /// SETTINGS parameters as `(identifier, value)`, in the order sent.
+    pub settings: Vec<(u16, u32)>,
+    /// Increment of a connection-level WINDOW_UPDATE, if one was sent.
+    pub window_update: Option<u32>,
+    pub priorities: Vec<Priority>,
+    /// Pseudo-headers of the first request, in order, as Akamai's letters:
+    /// `m`ethod, `a`uthority, `s`cheme and `p`ath.
+    pub pseudo_header_order: Vec<char>,
+}
+
+/// A PRIORITY frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Priority {
+    pub stream_id: u32,
+    pub exclusive: bool,
+    pub depends_on: u32,
+    /// As on the wire: one less than the effective weight.
+    pub weight: u8,
+}
+
+impl Http2Preface {
+    /// Parse the connection preface and the frames up to and including the
+    /// first HEADERS. `None` if `buf` doesn't start with the preface or
+    /// ends before the HEADERS frame.
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        let mut rest = buf.strip_prefix(CONNECTION_PREFACE)?;
+        let mut preface = Self::default();
+        loop {
+            let header = rest.get(..9)?;
+            let length = u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize;
+            let (kind, flags) = (header[3], header[4]);
+            let stream_id = read_u32(&header[5..]) & 0x7fff_ffff;
+            let payload = rest.get(9..9 + length)?;
+            rest = &rest[9 + length..];
+
+            match kind {
+                FRAME_SETTINGS if flags & FLAG_ACK == 0 => {
+                    preface.settings.extend(
+                        payload
+                            .chunks_exact(6)
+                            .map(|s| (u16::from_be_bytes([s[0], s[1]]), read_u32(&s[2..]))),
+                    );
+                }
+                FRAME_WINDOW_UPDATE if stream_id == 0 && payload.len() == 4 => {
+                    preface.window_update = Some(read_u32(payload) & 0x7fff_ffff);
+                }
+                FRAME_PRIORITY if payload.len() == 5 => {
+                    preface.priorities.push(Priority {
+                        stream_id,
+                        exclusive: payload[0] & 0x80 != 0,
+                        depends_on: read_u32(payload) & 0x7fff_ffff,
+                        weight: payload[4],
+                    });
+                }
+                FRAME_HEADERS => {
+                    let mut block = payload;
+                    if flags & FLAG_PADDED != 0 {
+                        let pad = usize::from(*block.first()?);
+                        block = block.get(1..block.len().checked_sub(pad)?)?;
+                    }
+                    if flags & FLAG_PRIORITY != 0 {
+                        block = block.get(5..)?;
+                    }
+                    preface.pseudo_header_order = pseudo_header_order(block);
+                    return Some(preface);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Akamai's fingerprint text: `settings|window_update|priorities|pseudo_headers`,
+    /// e.g. `1:65536;4:131072;5:16384|12517377|3:0:0:201|m,p,a,s`. Absent
+    /// parts are `00` (window update) and `0` (priorities).
+    pub fn akamai(&self) -> String {
+        let settings = self
+            .settings
+            .iter()
+            .map(|(id, value)| format!("{id}:{value}"))
+            .collect::<Vec<_>>()
+            .join(";");
+        let window_update = self
+            .window_update
+            .map_or_else(|| "00".to_string(), |w| w.to_string());
+        let priorities = if self.priorities.is_empty() {
+            "0".to_string()
+        } else {
+            self.priorities
+                .iter()
+                .map(|p| {
+                    format!(
+                        "{}:{}:{}:{}",
+                        p.stream_id,
+                        u8::from(p.exclusive),
+                        p.depends_on,
+                        u16::from(p.weight) + 1
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let pseudo_headers = self
+            .pseudo_header_order
+            .iter()
+            .map(char::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        format!("{settings}|{window_update}|{priorities}|{pseudo_headers}")
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// The leading pseudo-headers of an HPACK header block (RFC 7541). Only
+/// names are needed, and pseudo-headers come first in a request, so this
+/// stops at the first regular header or anything it can't name without
+/// decoding strings.
+fn pseudo_header_order(mut block: &[u8]) -> Vec<char> {
+    let mut order = Vec::new();
+    while let Some(&first) = block.first() {
+        let (index, literal) = match first {
+            b if b & 0x80 != 0 => (read_int(&mut block, 7), false),
+            b if b & 0xc0 == 0x40 => (read_int(&mut block, 6), true),
+            // Dynamic table size update; no header.
+            b if b & 0xe0 == 0x20 => {
+                if read_int(&mut block, 5).is_none() {
+                    break;
+                }
+                continue;
+            }
+            _ => (read_int(&mut block, 4), true),
+        };
+        // Static table names (RFC 7541 Appendix A). A literal name (index
+        // 0) or a dynamic-table entry ends the scan.
+        let letter = match index {
+            Some(1) => 'a',
+            Some(2 | 3) => 'm',
+            Some(4 | 5) => 'p',
+            Some(6 | 7) => 's',
+            _ => break,
+        };
+        order.push(letter);
+        if literal && skip_string(&mut block).is_none() {
+            break;
+        }
+    }
+    order
+}
+
+/// An HPACK integer with an `prefix`-bit prefix (RFC 7541 §5.1).
+fn read_int(block: &mut &[u8], prefix: u8) -> Option<u32> {
+    let (&first, mut rest) = block.split_first()?;
+    let max = (1u32 << prefix) - 1;
+    let mut value = u32::from(first) & max;
+    if value == max {
+        let mut shift = 0;
+        loop {
+            let (&byte, tail) = rest.split_first()?;
+            rest = tail;
+            value = value.checked_add(u32::from(byte & 0x7f).checked_shl(shift)?)?;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            if shift > 28 {
+                return None;
+            }
+        }
+    }
+    *block = rest;
+    Some(value)
+}
+
+/// Skip an HPACK string literal (RFC 7541 §5.2).
+fn skip_string(block: &mut &[u8]) -> Option<()> {
+    let length = read_int(block, 7)? as usize;
+    *block = block.get(length..)?;
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(kind: u8, flags: u8, stream_id: u32, payload: &[u8]) -> Vec<u8> {
+        let mut frame = (payload.len() as u32).to_be_bytes()[1..].to_vec();
+        frame.extend([kind, flags]);
+        frame.extend(stream_id.to_be_bytes());
+        frame.extend(payload);
+        frame
+    }
+
+    #[test]
+    fn parses_a_chrome_like_preface() {
+        let mut buf = CONNECTION_PREFACE.to_vec();
+        let mut settings = Vec::new();
+        for (id, value) in [(1u16, 65536u32), (2, 0), (4, 6291456), (6, 262144)] {
+            settings.extend(id.to_be_bytes());
+            settings.extend(value.to_be_bytes());
+        }
+        buf.extend(frame(FRAME_SETTINGS, 0, 0, &settings));
+        buf.extend(frame(FRAME_WINDOW_UPDATE, 0, 0, &15663105u32.to_be_bytes()));
+        // :method GET (indexed 2), :authority literal with name index 1,
+        // :scheme https (indexed 7), :path / (indexed 4), then
+        // user-agent (58) as a regular header.
+        let block = [
+            0x82, 0x41, 0x03, b'a', b'.', b'b', 0x87, 0x84, 0x7a, 0x01, b'x',
+        ];
+        buf.extend(frame(
+            FRAME_HEADERS,
+            FLAG_PRIORITY | 0x5,
+            1,
+            &[[0x80, 0, 0, 0, 255].as_slice(), &block].concat(),
+        ));
+
+        let preface = Http2Preface::parse(&buf).unwrap();
+        assert_eq!(preface.pseudo_header_order, ['m', 'a', 's', 'p']);
+        assert_eq!(
+            preface.akamai(),
+            "1:65536;2:0;4:6291456;6:262144|15663105|0|m,a,s,p"
+        );
+    }
+
+    #[test]
+    fn includes_priority_frames() {
+        let mut buf = CONNECTION_PREFACE.to_vec();
+        buf.extend(frame(FRAME_SETTINGS, 0, 0, &[0, 4, 0, 2, 0, 0]));
+        buf.extend(frame(FRAME_SETTINGS, FLAG_ACK, 0, &[]));
+        buf.extend(frame(FRAME_PRIORITY, 0, 3, &[0, 0, 0, 0, 200]));
+        buf.extend(frame(FRAME_PRIORITY, 0, 5, &[0x80, 0, 0, 3, 100]));
+        buf.extend(frame(FRAME_HEADERS, 0x4, 15, &[0x82, 0x84, 0x87, 0x81]));
+
+        let preface = Http2Preface::parse(&buf).unwrap();
+        assert_eq!(preface.akamai(), "4:131072|00|3:0:0:201,5:1:3:101|m,p,s,a");
+    }
+
+    #[test]
+    fn needs_the_preface_and_a_headers_frame() {
+        assert!(Http2Preface::parse(b"GET / HTTP/1.1\r\n\r\n").is_none());
+        let mut buf = CONNECTION_PREFACE.to_vec();
+        buf.extend(frame(FRAME_SETTINGS, 0, 0, &[]));
+        assert!(Http2Preface::parse(&buf).is_none());
+    }
+
+    #[test]
+    fn reads_multibyte_integers() {
+        // RFC 7541 C.1.2: 1337 with a 5-bit prefix.
+        let mut block: &[u8] = &[0x1f, 0x9a, 0x0a, 0xff];
+        assert_eq!(read_int(&mut block, 5), Some(1337));
+        assert_eq!(block, [0xff]);
+    }
+}
@@ -0,0 +1,168 @@
+//! Idle-connection timeout for accepted streams.
+//!
+//! hyper has no idle timeout of its own for HTTP/2, and an HTTP/1 client
+//! that stops reading its response is never timed out at all. Wrapping the
+//! stream covers both: once no byte has been read or written for the
+//! configured time, the pending read or write fails with `TimedOut` and
+//! hyper closes the connection.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{Instant, Sleep};
+
+/// A stream that errors after `timeout` without progress in either
+/// direction.
+pub struct IdleTimeout<S> {
+    inner: S,
+    timeout: Duration,
+    /// `None` when the timeout is disabled.
+    deadline: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S> IdleTimeout<S> {
+    /// `None` disables the timeout, leaving a plain passthrough.
+    pub fn new(inner: S, timeout: Option<Duration>) -> Self {
+        Self {
+            inner,
+            timeout: timeout.unwrap_or_default(),
+            deadline: timeout.map(|t| Box::pin(tokio::time::sleep(t))),
+        }
+    }
+
+    fn touch(&mut self) {
+        if let Some(deadline) = &mut self.deadline {
+            deadline.as_mut().reset(Instant::now() + self.timeout);
+        }
+    }
+
+    /// Called when the inner stream isn't ready: has the deadline passed?
+    fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<io::Error> {
+        let Some(deadline) = &mut self.deadline else {
+            return Poll::Pending;
+        };
+        deadline
+            .as_mut()
+            .poll(cx)
+            .map(|()| io::Error::new(io::ErrorKind::TimedOut, "connection idle"))
+    }
+
+    fn after_write(
+        &mut self,
+        cx: &mut Context<'_>,
+        poll: Poll<io::Result<usize>>,
+    ) -> Poll<io::Result<usize>> {
+        match poll {
+            Poll::Ready(Ok(n)) => {
+                if n > 0 {
+                    self.touch();
+                }
+                Poll::Ready(Ok(n))
+            }
+            Poll::Pending => self.poll_expired(cx).map(Err),
+            ready => ready,
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for IdleTimeout<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        match Pin::new(&mut self.inner).poll_read(cx, buf) {
+            Poll::Ready(Ok(())) => {
+                if buf.filled().len() > before {
+                    self.touch();
+                }
+                Poll::Ready(Ok(()))
+            }
+            Poll::Pending => self.poll_expired(cx).map(Err),
+            ready => ready,
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for IdleTimeout<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        self.after_write(cx, poll)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write_vectored(cx, bufs);
+        self.after_write(cx, poll)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn read_fails_after_idle_timeout() {
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut server = IdleTimeout::new(server, Some(Duration::from_secs(5)));
+
+        client.write_all(b"ping").await.unwrap();
+        let mut buf = [0u8; 4];
+        server.read_exact(&mut buf).await.unwrap();
+
+        // Activity pushed the deadline out; silence then trips it.
+        tokio::time::advance(Duration::from_secs(4)).await;
+        client.write_all(b"pong").await.unwrap();
+        server.read_exact(&mut buf).await.unwrap();
+        let err = server.read(&mut buf).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn write_fails_when_peer_stops_reading() {
+        let (_client, server) = tokio::io::duplex(8);
+        let mut server = IdleTimeout::new(server, Some(Duration::from_secs(5)));
+
+        let err = server.write_all(&[0u8; 64]).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn disabled_never_times_out() {
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut server = IdleTimeout::new(server, None);
+
+        let read = tokio::spawn(async move {
+            let mut buf = [0u8; 4];
+            server.read_exact(&mut buf).await.map(|_| buf)
+        });
+        tokio::time::advance(Duration::from_secs(3600)).await;
+        client.write_all(b"late").await.unwrap();
+        assert_eq!(&read.await.unwrap().unwrap(), b"late");
+    }
+}
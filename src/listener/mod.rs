@@ -0,0 +1,585 @@
+//! TCP and Unix socket accept loop.
+//!
+//! We drive hyper directly instead of using `axum::serve` so that
+//! per-connection work (reading a PROXY protocol header) happens in the
+//! connection's own task and can override the address handlers see through
+//! `ConnectInfo<SocketAddr>`. A slow or malicious client stalling mid-header
+//! therefore never blocks `accept()` for everyone else. The same goes for
+//! the TLS handshake when HTTPS is enabled.
+//!
+//! Connection timeouts are applied here too: hyper enforces
+//! `HEADER_READ_TIMEOUT_SECS` on HTTP/1 request heads, every stream is
+//! wrapped in [`idle::IdleTimeout`] for `IDLE_TIMEOUT_SECS`, and slow or
+//! piled-up connections that haven't sent a request are cut off by
+//! [`half_open`].
+//!
+//! Each stream is also wrapped in [`raw_head::HeadRecorder`], so handlers
+//! can see HTTP/1 request heads exactly as they arrived.
+//!
+//! With `BIND_ADDR=unix:/path` the same loop serves a Unix domain socket
+//! instead; see [`unix`] for how clients are identified there.
+
+pub mod client_hello;
+pub mod half_open;
+pub mod http2_preface;
+pub mod idle;
+pub mod proxy_protocol;
+pub mod raw_head;
+#[cfg(unix)]
+pub mod systemd;
+#[cfg(unix)]
+pub mod tcp_socket;
+#[cfg(feature = "tls")]
+pub mod tls;
+#[cfg(unix)]
+pub mod unix;
+
+use std::convert::Infallible;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use arc_swap::ArcSwap;
+use axum::Router;
+use axum::body::Body;
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum::http::request::Parts;
+use axum::http::{Extensions, Request};
+use hyper::body::Incoming;
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use hyper_util::server::conn::auto;
+use hyper_util::server::graceful::{GracefulShutdown, Watcher};
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tower::ServiceExt;
+
+use crate::config::{BindAddr, Config, ListenerConfig};
+use crate::listener::client_hello::TlsFingerprint;
+use crate::listener::half_open::{HalfOpen, HalfOpenTracker};
+use crate::listener::idle::IdleTimeout;
+use crate::listener::raw_head::{HeadLog, HeadRecorder};
+#[cfg(feature = "tls")]
+use crate::listener::tls::TlsConfig;
+
+/// How long a client has to deliver its PROXY protocol header before the
+/// connection is dropped.
+const PROXY_HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a client has to complete the TLS handshake.
+#[cfg(feature = "tls")]
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The local socket address a connection was accepted on, exposed to
+/// handlers as a request extension alongside `ConnectInfo`.
+#[derive(Debug, Clone, Copy)]
+pub struct LocalAddr(pub SocketAddr);
+
+/// Extractor for what the listener attached about the request's
+/// connection; every field is `None` when not applicable (or in tests that
+/// skip the listener).
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionDetails {
+    pub local: Option<LocalAddr>,
+    /// ClientHello fingerprint, when this server terminated TLS.
+    pub tls: Option<TlsFingerprint>,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ConnectionDetails {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self {
+            local: parts.extensions.get::<LocalAddr>().copied(),
+            tls: parts.extensions.get::<TlsFingerprint>().cloned(),
+        })
+    }
+}
+
+/// A bound HTTP listener: TCP, or a Unix socket.
+pub enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(unix::UnixSocket),
+}
+
+impl Listener {
+    /// Accept the next connection, with the peer address handlers should
+    /// see (before PROXY protocol).
+    async fn accept(&self) -> io::Result<(Connection, SocketAddr)> {
+        match self {
+            Self::Tcp(listener) => {
+                let (stream, peer) = listener.accept().await?;
+                #[cfg(unix)]
+                let socket = tcp_socket::TcpSocket::of(&stream);
+                Ok((
+                    Connection::Tcp(
+                        stream,
+                        #[cfg(unix)]
+                        socket,
+                    ),
+                    peer,
+                ))
+            }
+            #[cfg(unix)]
+            Self::Unix(listener) => {
+                Ok((Connection::Unix(listener.accept().await?), unix::UNIX_PEER))
+            }
+        }
+    }
+}
+
+impl From<TcpListener> for Listener {
+    fn from(listener: TcpListener) -> Self {
+        Self::Tcp(listener)
+    }
+}
+
+impl fmt::Display for Listener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(listener) => match listener.local_addr() {
+                Ok(addr) => write!(f, "{addr}"),
+                Err(_) => f.write_str("tcp"),
+            },
+            #[cfg(unix)]
+            Self::Unix(listener) => write!(f, "unix:{}", listener.path().display()),
+        }
+    }
+}
+
+/// An accepted connection on either kind of [`Listener`].
+enum Connection {
+    /// With the [`tcp_socket::TcpSocket`] handed to requests, closed when
+    /// the connection drops.
+    Tcp(TcpStream, #[cfg(unix)] tcp_socket::TcpSocket),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixStream),
+}
+
+impl Connection {
+    /// The local socket address; Unix sockets don't have one.
+    fn local_addr(&self) -> Option<SocketAddr> {
+        match self {
+            Self::Tcp(stream, ..) => stream.local_addr().ok(),
+            #[cfg(unix)]
+            Self::Unix(_) => None,
+        }
+    }
+
+    /// Request extensions describing the socket itself: the
+    /// [`tcp_socket::TcpSocket`] of a TCP connection.
+    fn socket_extensions(&self) -> Extensions {
+        let mut extensions = Extensions::new();
+        #[cfg(unix)]
+        if let Self::Tcp(_, socket) = self {
+            extensions.insert(socket.clone());
+        }
+        extensions
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // Before the stream, and with it the descriptor, is dropped.
+        #[cfg(unix)]
+        if let Self::Tcp(_, socket) = self {
+            socket.close();
+        }
+    }
+}
+
+impl AsyncRead for Connection {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream, ..) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Connection {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Tcp(stream, ..) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream, ..) => Pin::new(stream).poll_flush(cx),
+            #[cfg(unix)]
+            Self::Unix(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream, ..) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(unix)]
+            Self::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+/// Bind every listener `config` describes, each paired with its settings.
+/// When socket-activated, the sockets systemd passed are used instead, in
+/// order: with `[[listeners]]` there must be one per entry, whose settings
+/// apply except for the address; otherwise all share the top-level ones.
+pub fn bind_all(config: &Config) -> io::Result<Vec<(Listener, ListenerConfig)>> {
+    let settings = config.all_listeners();
+    #[cfg(unix)]
+    if let Some(activated) = systemd::listeners()? {
+        if !config.listeners.is_empty() && activated.len() != settings.len() {
+            return Err(io::Error::other(format!(
+                "socket activation passed {} sockets for {} [[listeners]]",
+                activated.len(),
+                settings.len()
+            )));
+        }
+        let shared = settings[0].clone();
+        let settings = settings.into_iter().chain(std::iter::repeat(shared));
+        return Ok(activated.into_iter().zip(settings).collect());
+    }
+    let mut listeners = Vec::new();
+    for settings in settings {
+        for _ in 0..settings.acceptors {
+            listeners.push((bind(&settings)?, settings.clone()));
+        }
+    }
+    Ok(listeners)
+}
+
+/// Bind one listener: a Unix socket or TCP.
+fn bind(config: &ListenerConfig) -> io::Result<Listener> {
+    let addr = match &config.bind {
+        BindAddr::Ip(ip) => SocketAddr::new(*ip, config.port),
+        #[cfg(unix)]
+        BindAddr::Unix(path) => {
+            let owner = config.socket_owner.as_deref();
+            return unix::UnixSocket::bind(path, config.socket_mode, owner).map(Listener::Unix);
+        }
+        #[cfg(not(unix))]
+        BindAddr::Unix(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Unix sockets are not supported on this platform",
+            ));
+        }
+    };
+    bind_tcp(addr, config).map(Listener::Tcp)
+}
+
+/// Bind TCP on `addr` with the backlog, `IPV6_V6ONLY` and, for several
+/// acceptors, `SO_REUSEPORT` from `config`. Done via socket2 because
+/// std/tokio expose none of these before `listen()`.
+fn bind_tcp(addr: SocketAddr, config: &ListenerConfig) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    if config.acceptors > 1 {
+        #[cfg(unix)]
+        socket.set_reuse_port(true)?;
+        #[cfg(not(unix))]
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "ACCEPTORS > 1 needs SO_REUSEPORT, which this platform lacks",
+        ));
+    }
+    if addr.is_ipv6() {
+        socket.set_only_v6(config.ipv6_only)?;
+    }
+    socket.bind(&addr.into())?;
+    socket.listen(config.backlog.min(i32::MAX as u32) as i32)?;
+    socket.set_nonblocking(true)?;
+    TcpListener::from_std(socket.into())
+}
+
+/// How connections on one listener are handled.
+#[derive(Clone)]
+pub struct ConnectionOptions {
+    /// Every connection is HTTPS.
+    #[cfg(feature = "tls")]
+    pub tls: Option<TlsConfig>,
+    /// Every connection starts with a PROXY protocol header.
+    pub proxy_protocol: bool,
+    /// Shared by every listener, so `MAX_HALF_OPEN_PER_IP` holds however
+    /// many sockets a client's connections are spread over.
+    pub half_open: Arc<HalfOpenTracker>,
+}
+
+/// Accept connections on `listener` and serve `app` on each until
+/// `shutdown` resolves, then wait for in-flight connections to finish.
+/// Each connection uses the config current when it was accepted.
+pub async fn serve<F>(
+    listener: Listener,
+    app: Router,
+    config: Arc<ArcSwap<Config>>,
+    options: ConnectionOptions,
+    shutdown: F,
+) where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let graceful = GracefulShutdown::new();
+    tokio::pin!(shutdown);
+
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(conn) => conn,
+                Err(e) => {
+                    handle_accept_error(e).await;
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+
+        let config = config.load_full();
+        let slot = if config.is_trusted_peer(&peer.ip().to_canonical()) {
+            HalfOpen::untracked()
+        } else if let Some(slot) = options.half_open.open(peer.ip().to_canonical()) {
+            slot
+        } else {
+            tracing::debug!(%peer, "too many half-open connections from peer");
+            metrics::counter!("http_connections_rejected_total", "reason" => "half_open_limit")
+                .increment(1);
+            continue;
+        };
+
+        let app = app.clone();
+        let options = options.clone();
+        let watcher = graceful.watcher();
+        tokio::spawn(async move {
+            let _active = ActiveConnection::new();
+            let slot = Arc::new(slot);
+            let deadline = secs(config.first_request_timeout_secs);
+            tokio::select! {
+                _ = handle_connection(stream, peer, app, &config, options, watcher, slot.clone()) => {}
+                _ = slot.expired(deadline) => {
+                    tracing::debug!(%peer, "no request before the first-request deadline");
+                    metrics::counter!(
+                        "http_connections_rejected_total",
+                        "reason" => "first_request_timeout"
+                    )
+                    .increment(1);
+                }
+            }
+        });
+    }
+
+    drop(listener);
+    graceful.shutdown().await;
+}
+
+/// Resolve the client address, complete the TLS handshake if configured,
+/// then serve HTTP on the connection.
+async fn handle_connection(
+    mut stream: Connection,
+    peer: SocketAddr,
+    app: Router,
+    config: &Config,
+    options: ConnectionOptions,
+    watcher: Watcher,
+    slot: Arc<HalfOpen>,
+) {
+    let Some(remote) = remote_addr(&mut stream, peer, options.proxy_protocol, config).await else {
+        return;
+    };
+    let local = stream.local_addr();
+    let extensions = stream.socket_extensions();
+
+    #[cfg(feature = "tls")]
+    if let Some(tls) = options.tls {
+        match tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, tls.accept(stream)).await {
+            Ok(Ok(Some((stream, tls_extensions)))) => {
+                let mut extensions = extensions;
+                extensions.extend(tls_extensions);
+                let info = ConnectionInfo {
+                    remote,
+                    local,
+                    extensions,
+                };
+                serve_connection(stream, info, app, config, watcher, slot).await
+            }
+            Ok(Ok(None)) => {}
+            Ok(Err(e)) => {
+                tracing::debug!(%remote, error = %e, "TLS handshake failed");
+                metrics::counter!("tls_handshake_failed_total", "reason" => "error").increment(1);
+            }
+            Err(_) => {
+                tracing::debug!(%remote, "TLS handshake timed out");
+                metrics::counter!("tls_handshake_failed_total", "reason" => "timeout").increment(1);
+            }
+        }
+        return;
+    }
+    let info = ConnectionInfo {
+        remote,
+        local,
+        extensions,
+    };
+    serve_connection(stream, info, app, config, watcher, slot).await
+}
+
+/// Counts a connection in `http_connections_active` for as long as it's
+/// alive, whichever way its task ends.
+struct ActiveConnection;
+
+impl ActiveConnection {
+    fn new() -> Self {
+        metrics::counter!("http_connections_total").increment(1);
+        metrics::gauge!("http_connections_active").increment(1);
+        Self
+    }
+}
+
+impl Drop for ActiveConnection {
+    fn drop(&mut self) {
+        metrics::gauge!("http_connections_active").decrement(1);
+    }
+}
+
+/// What handlers learn about the connection a request came in on.
+struct ConnectionInfo {
+    /// Exposed as `ConnectInfo<SocketAddr>`.
+    remote: SocketAddr,
+    /// Exposed as [`LocalAddr`].
+    local: Option<SocketAddr>,
+    /// Copied into every request, e.g. the TCP socket and the
+    /// [`tls::TlsSession`] and [`client_hello::TlsFingerprint`] of a TLS
+    /// connection.
+    extensions: Extensions,
+}
+
+/// Serve HTTP/1 or HTTP/2 on an established (plain or TLS) stream,
+/// exposing `info` to handlers, along with HTTP/1 heads as
+/// [`raw_head::RawHead`] and HTTP/2 opening frames as
+/// [`http2_preface::Http2Preface`].
+async fn serve_connection<S>(
+    stream: S,
+    info: ConnectionInfo,
+    app: Router,
+    config: &Config,
+    watcher: Watcher,
+    slot: Arc<HalfOpen>,
+) where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let remote = info.remote;
+    let head_log = HeadLog::new(head_limit(config.max_header_bytes));
+    let stream = HeadRecorder::new(stream, head_log.clone());
+    let service = hyper::service::service_fn(move |mut req: Request<Incoming>| {
+        slot.established();
+        req.extensions_mut().insert(ConnectInfo(info.remote));
+        if let Some(local) = info.local {
+            req.extensions_mut().insert(LocalAddr(local));
+        }
+        req.extensions_mut().extend(info.extensions.clone());
+        if let Some(head) = head_log.take(&req) {
+            req.extensions_mut().insert(head);
+        }
+        if let Some(preface) = head_log.http2_preface() {
+            req.extensions_mut().insert(preface);
+        }
+        app.clone().oneshot(req.map(Body::new))
+    });
+    let mut builder = auto::Builder::new(TokioExecutor::new());
+    builder
+        .http1()
+        .timer(TokioTimer::new())
+        .header_read_timeout(secs(config.header_read_timeout_secs))
+        .max_buf_size(head_limit(config.max_header_bytes));
+    builder.http2().max_header_list_size(
+        head_limit(config.max_header_bytes)
+            .try_into()
+            .unwrap_or(u32::MAX),
+    );
+    let stream = IdleTimeout::new(stream, secs(config.idle_timeout_secs));
+    let conn = builder.serve_connection_with_upgrades(TokioIo::new(stream), service);
+    if let Err(e) = watcher.watch(conn.into_owned()).await {
+        tracing::debug!(%remote, error = %e, "connection closed with error");
+    }
+}
+
+/// How much of a request head hyper buffers (HTTP/1) or accepts as a header
+/// list (HTTP/2) before giving up with a bare 431. Leaves room over
+/// `MAX_HEADER_BYTES` for the request line and HTTP/2's per-field overhead,
+/// so modestly oversized heads still get the JSON 431 from `limits`. hyper
+/// requires at least 8 KiB.
+fn head_limit(max_header_bytes: usize) -> usize {
+    max_header_bytes.saturating_mul(2).max(8192)
+}
+
+/// A configured timeout, where 0 means none.
+fn secs(secs: u64) -> Option<Duration> {
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+/// Determine the address handlers should see for this connection. Without
+/// PROXY protocol that's the socket peer. With it, the header is mandatory
+/// and only accepted from `TRUSTED_PROXIES`; otherwise any client could
+/// claim any source address. Returns `None` if the connection should be
+/// dropped.
+async fn remote_addr(
+    stream: &mut Connection,
+    peer: SocketAddr,
+    proxy_protocol: bool,
+    config: &Config,
+) -> Option<SocketAddr> {
+    if !proxy_protocol {
+        return Some(peer);
+    }
+
+    if !config.is_trusted_peer(&peer.ip().to_canonical()) {
+        tracing::warn!(%peer, "rejecting PROXY protocol connection from untrusted peer");
+        metrics::counter!("proxy_protocol_rejected_total", "reason" => "untrusted").increment(1);
+        return None;
+    }
+
+    match tokio::time::timeout(PROXY_HEADER_TIMEOUT, proxy_protocol::read_header(stream)).await {
+        Ok(Ok(addr)) => Some(addr.unwrap_or(peer)),
+        Ok(Err(e)) => {
+            tracing::warn!(%peer, error = %e, "invalid PROXY protocol header");
+            metrics::counter!("proxy_protocol_rejected_total", "reason" => "invalid").increment(1);
+            None
+        }
+        Err(_) => {
+            tracing::warn!(%peer, "timed out waiting for PROXY protocol header");
+            metrics::counter!("proxy_protocol_rejected_total", "reason" => "timeout").increment(1);
+            None
+        }
+    }
+}
+
+/// Per-connection errors (the client hung up before we accepted) are
+/// harmless. Anything else — usually fd exhaustion — gets logged and a short
+/// back-off so we don't spin.
+pub async fn handle_accept_error(e: io::Error) {
+    if matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+    ) {
+        return;
+    }
+    tracing::error!(error = %e, "accept error");
+    tokio::time::sleep(Duration::from_secs(1)).await;
+}
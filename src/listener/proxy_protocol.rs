@@ -0,0 +1,225 @@
+//! HAProxy PROXY protocol (v1 text and v2 binary) header parsing.
+//!
+//! Spec: <https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt>
+//!
+//! The header is read byte-exactly from the stream so that nothing past it
+//! is consumed; the stream can then be handed to hyper untouched.
+
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+const V1_PREFIX: &[u8] = b"PROXY ";
+/// Longest legal v1 header, including the trailing CRLF.
+const V1_MAX_LEN: usize = 107;
+const V2_SIGNATURE: &[u8; 12] = b"\r\n\r\n\0\r\nQUIT\n";
+
+/// Read a PROXY protocol header from the start of `stream`.
+///
+/// Returns `Ok(Some(addr))` with the original client address, or `Ok(None)`
+/// when the sender declined to provide one (v1 `UNKNOWN`, v2 `LOCAL`, or a
+/// non-IP address family) — typically a load-balancer health check, where
+/// the socket peer address should be used as-is.
+pub async fn read_header<R: AsyncRead + Unpin>(stream: &mut R) -> io::Result<Option<SocketAddr>> {
+    let mut prefix = [0u8; 6];
+    stream.read_exact(&mut prefix).await?;
+
+    if prefix == V1_PREFIX {
+        read_v1(stream).await
+    } else if prefix == V2_SIGNATURE[..6] {
+        read_v2(stream).await
+    } else {
+        Err(invalid("missing PROXY protocol header"))
+    }
+}
+
+async fn read_v1<R: AsyncRead + Unpin>(stream: &mut R) -> io::Result<Option<SocketAddr>> {
+    let mut line = Vec::with_capacity(V1_MAX_LEN);
+    line.extend_from_slice(V1_PREFIX);
+    loop {
+        let byte = stream.read_u8().await?;
+        line.push(byte);
+        if line.ends_with(b"\r\n") {
+            break;
+        }
+        if line.len() >= V1_MAX_LEN {
+            return Err(invalid("PROXY v1 header too long"));
+        }
+    }
+    let line = std::str::from_utf8(&line[..line.len() - 2])
+        .map_err(|_| invalid("PROXY v1 header is not ASCII"))?;
+    parse_v1(line)
+}
+
+/// Parse a v1 header line without its trailing CRLF, e.g.
+/// `PROXY TCP4 192.0.2.1 198.51.100.1 56324 443`.
+fn parse_v1(line: &str) -> io::Result<Option<SocketAddr>> {
+    let mut parts = line.split(' ');
+    if parts.next() != Some("PROXY") {
+        return Err(invalid("PROXY v1 header malformed"));
+    }
+    match parts.next() {
+        Some("UNKNOWN") => Ok(None),
+        Some(proto @ ("TCP4" | "TCP6")) => {
+            let fields: Vec<&str> = parts.collect();
+            let [src, _dst, src_port, _dst_port] = fields[..] else {
+                return Err(invalid("PROXY v1 header has wrong field count"));
+            };
+            let port: u16 = src_port
+                .parse()
+                .map_err(|_| invalid("PROXY v1 source port invalid"))?;
+            let ip = if proto == "TCP4" {
+                src.parse::<Ipv4Addr>().map(Into::into)
+            } else {
+                src.parse::<Ipv6Addr>().map(Into::into)
+            }
+            .map_err(|_| invalid("PROXY v1 source address invalid"))?;
+            Ok(Some(SocketAddr::new(ip, port)))
+        }
+        _ => Err(invalid("PROXY v1 protocol unsupported")),
+    }
+}
+
+async fn read_v2<R: AsyncRead + Unpin>(stream: &mut R) -> io::Result<Option<SocketAddr>> {
+    // Rest of the signature, then version/command, family, and length.
+    let mut head = [0u8; 10];
+    stream.read_exact(&mut head).await?;
+    if head[..6] != V2_SIGNATURE[6..] {
+        return Err(invalid("PROXY v2 signature mismatch"));
+    }
+    let len = u16::from_be_bytes([head[8], head[9]]) as usize;
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload).await?;
+    parse_v2(head[6], head[7], &payload)
+}
+
+fn parse_v2(ver_cmd: u8, family: u8, payload: &[u8]) -> io::Result<Option<SocketAddr>> {
+    if ver_cmd >> 4 != 2 {
+        return Err(invalid("PROXY v2 version unsupported"));
+    }
+    match ver_cmd & 0x0f {
+        // LOCAL: connection initiated by the proxy itself.
+        0x0 => return Ok(None),
+        0x1 => {}
+        _ => return Err(invalid("PROXY v2 command unsupported")),
+    }
+    // High nibble is the address family; the low nibble (transport) is
+    // irrelevant for reporting the source address.
+    match family >> 4 {
+        0x1 => {
+            let b: &[u8; 12] = payload
+                .get(..12)
+                .and_then(|s| s.try_into().ok())
+                .ok_or_else(|| invalid("PROXY v2 IPv4 payload truncated"))?;
+            let ip = Ipv4Addr::new(b[0], b[1], b[2], b[3]);
+            let port = u16::from_be_bytes([b[8], b[9]]);
+            Ok(Some(SocketAddr::new(ip.into(), port)))
+        }
+        0x2 => {
+            let b: &[u8; 36] = payload
+                .get(..36)
+                .and_then(|s| s.try_into().ok())
+                .ok_or_else(|| invalid("PROXY v2 IPv6 payload truncated"))?;
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&b[..16]);
+            let port = u16::from_be_bytes([b[32], b[33]]);
+            Ok(Some(SocketAddr::new(Ipv6Addr::from(octets).into(), port)))
+        }
+        // AF_UNSPEC or AF_UNIX: no usable IP address.
+        _ => Ok(None),
+    }
+}
+
+fn invalid(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read(bytes: &[u8]) -> io::Result<Option<SocketAddr>> {
+        let mut cursor = bytes;
+        read_header(&mut cursor).await
+    }
+
+    fn v2(ver_cmd: u8, family: u8, payload: &[u8]) -> Vec<u8> {
+        let mut buf = V2_SIGNATURE.to_vec();
+        buf.push(ver_cmd);
+        buf.push(family);
+        buf.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        buf.extend_from_slice(payload);
+        buf
+    }
+
+    #[tokio::test]
+    async fn v1_tcp4() {
+        let addr = read(b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\nGET /").await;
+        assert_eq!(addr.unwrap(), Some("192.0.2.1:56324".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn v1_tcp6() {
+        let addr = read(b"PROXY TCP6 2001:db8::1 2001:db8::2 4711 80\r\n").await;
+        assert_eq!(addr.unwrap(), Some("[2001:db8::1]:4711".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn v1_unknown_has_no_address() {
+        assert_eq!(read(b"PROXY UNKNOWN\r\n").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn v1_does_not_consume_past_crlf() {
+        let mut cursor: &[u8] = b"PROXY UNKNOWN\r\nGET / HTTP/1.1\r\n";
+        read_header(&mut cursor).await.unwrap();
+        assert_eq!(cursor, b"GET / HTTP/1.1\r\n");
+    }
+
+    #[tokio::test]
+    async fn v1_rejects_garbage() {
+        assert!(read(b"PROXY TCP4 nope 198.51.100.1 1 2\r\n").await.is_err());
+        assert!(read(b"PROXY TCP4 192.0.2.1\r\n").await.is_err());
+        assert!(
+            read(&[b"PROXY ".as_slice(), &[b'A'; 200]].concat())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_header_is_rejected() {
+        assert!(read(b"GET / HTTP/1.1\r\n\r\n").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn v2_ipv4() {
+        let payload = [192, 0, 2, 1, 198, 51, 100, 1, 0xdc, 0x04, 0x01, 0xbb];
+        let addr = read(&v2(0x21, 0x11, &payload)).await.unwrap();
+        assert_eq!(addr, Some("192.0.2.1:56324".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn v2_ipv6_with_trailing_tlvs() {
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&"2001:db8::1".parse::<Ipv6Addr>().unwrap().octets());
+        payload.extend_from_slice(&"2001:db8::2".parse::<Ipv6Addr>().unwrap().octets());
+        payload.extend_from_slice(&4711u16.to_be_bytes());
+        payload.extend_from_slice(&443u16.to_be_bytes());
+        payload.extend_from_slice(&[0x04, 0x00, 0x01, 0xff]); // TLV we ignore
+        let addr = read(&v2(0x21, 0x21, &payload)).await.unwrap();
+        assert_eq!(addr, Some("[2001:db8::1]:4711".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn v2_local_has_no_address() {
+        assert_eq!(read(&v2(0x20, 0x00, &[])).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn v2_rejects_truncated_payload_and_bad_version() {
+        assert!(read(&v2(0x21, 0x11, &[192, 0, 2, 1])).await.is_err());
+        assert!(read(&v2(0x11, 0x11, &[0; 12])).await.is_err());
+    }
+}
@@ -0,0 +1,210 @@
+//! Capture of HTTP/1 request heads as they arrived on the wire.
+//!
+//! hyper lowercases header names and groups repeated headers by name, so
+//! the parsed request can't show what a client or proxy actually sent.
+//! [`HeadRecorder`] wraps a connection's stream and keeps the most recent
+//! bytes read; when hyper hands over a request, [`HeadLog::take`] finds
+//! its head among them and the listener attaches it as a [`RawHead`]
+//! extension. HTTP/2 has no textual head; instead, the client's opening
+//! frames are parsed once into an [`Http2Preface`], attached to every
+//! request on the connection.
+
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use axum::body::Bytes;
+use axum::http::{Request, Version};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use super::http2_preface::Http2Preface;
+
+/// An HTTP/1 request head exactly as received: request line and header
+/// lines, through the blank line that ends them.
+#[derive(Debug, Clone)]
+pub struct RawHead(pub Bytes);
+
+/// Bytes read on a connection and not yet matched to a request.
+#[derive(Debug, Clone)]
+pub struct HeadLog {
+    inner: Arc<Mutex<Recorded>>,
+}
+
+#[derive(Debug)]
+struct Recorded {
+    buf: Vec<u8>,
+    /// Most bytes kept; older ones (usually bodies) are dropped first.
+    limit: usize,
+    /// Off once the connection turns out to be HTTP/2.
+    enabled: bool,
+    http2: Option<Http2Preface>,
+}
+
+impl HeadLog {
+    /// A log keeping at most `limit` bytes, which should be at least the
+    /// largest head hyper accepts.
+    pub fn new(limit: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Recorded {
+                buf: Vec::new(),
+                limit,
+                enabled: true,
+                http2: None,
+            })),
+        }
+    }
+
+    fn record(&self, bytes: &[u8]) {
+        let mut recorded = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if !recorded.enabled || bytes.is_empty() {
+            return;
+        }
+        recorded.buf.extend_from_slice(bytes);
+        let excess = recorded.buf.len().saturating_sub(recorded.limit);
+        recorded.buf.drain(..excess);
+    }
+
+    /// The head of `req`, removing it and everything before it from the
+    /// log. `None` for HTTP/2, where the first request instead parses the
+    /// connection's [`Http2Preface`] and stops further recording.
+    pub fn take<B>(&self, req: &Request<B>) -> Option<RawHead> {
+        let mut recorded = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if req.version() >= Version::HTTP_2 {
+            if recorded.enabled {
+                recorded.http2 = Http2Preface::parse(&recorded.buf);
+                recorded.enabled = false;
+                recorded.buf = Vec::new();
+            }
+            return None;
+        }
+        let request_line = format!("{} {} HTTP/1.", req.method(), req.uri());
+        let (start, end) = find_head(&recorded.buf, request_line.as_bytes())?;
+        let head = Bytes::copy_from_slice(&recorded.buf[start..end]);
+        recorded.buf.drain(..end);
+        Some(RawHead(head))
+    }
+
+    /// The opening frames of an HTTP/2 connection, once a request on it
+    /// has been through [`HeadLog::take`].
+    pub fn http2_preface(&self) -> Option<Http2Preface> {
+        let recorded = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        recorded.http2.clone()
+    }
+}
+
+/// Where the head starting with `request_line` begins and ends in `buf`.
+fn find_head(buf: &[u8], request_line: &[u8]) -> Option<(usize, usize)> {
+    let start = buf
+        .windows(request_line.len())
+        .position(|w| w == request_line)?;
+    let rest = &buf[start..];
+    let end = rest
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .map(|i| i + 4)
+        // Bare LF line endings, which hyper also accepts.
+        .or_else(|| rest.windows(2).position(|w| w == b"\n\n").map(|i| i + 2))?;
+    Some((start, start + end))
+}
+
+/// A stream whose reads are copied into a [`HeadLog`].
+pub struct HeadRecorder<S> {
+    inner: S,
+    log: HeadLog,
+}
+
+impl<S> HeadRecorder<S> {
+    pub fn new(inner: S, log: HeadLog) -> Self {
+        Self { inner, log }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for HeadRecorder<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = poll {
+            self.log.record(&buf.filled()[before..]);
+        }
+        poll
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for HeadRecorder<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, uri: &str) -> Request<()> {
+        Request::builder().method(method).uri(uri).body(()).unwrap()
+    }
+
+    #[test]
+    fn takes_pipelined_heads_in_turn() {
+        let log = HeadLog::new(1024);
+        log.record(b"tail of a body GET /a HTTP/1.1\r\nX-One: 1\r\n\r\n");
+        log.record(b"POST /b?q=1 HTTP/1.1\r\nHost: x\r\nContent-Length: 2\r\n\r\nhi");
+
+        let a = log.take(&request("GET", "/a")).unwrap();
+        assert_eq!(&a.0[..], b"GET /a HTTP/1.1\r\nX-One: 1\r\n\r\n");
+        let b = log.take(&request("POST", "/b?q=1")).unwrap();
+        assert_eq!(
+            &b.0[..],
+            b"POST /b?q=1 HTTP/1.1\r\nHost: x\r\nContent-Length: 2\r\n\r\n"
+        );
+        assert!(log.take(&request("GET", "/c")).is_none());
+    }
+
+    #[test]
+    fn keeps_only_the_latest_bytes() {
+        let log = HeadLog::new(32);
+        log.record(&[b'x'; 100]);
+        log.record(b"GET / HTTP/1.1\r\n\r\n");
+        assert!(log.take(&request("GET", "/")).is_some());
+        assert!(log.inner.lock().unwrap().buf.is_empty());
+    }
+
+    #[test]
+    fn stops_recording_for_http2() {
+        let log = HeadLog::new(1024);
+        let mut req = request("GET", "/");
+        *req.version_mut() = Version::HTTP_2;
+        assert!(log.take(&req).is_none());
+        log.record(b"GET / HTTP/1.1\r\n\r\n");
+        assert!(log.inner.lock().unwrap().buf.is_empty());
+    }
+}
@@ -0,0 +1,119 @@
+//! systemd socket activation.
+//!
+//! With a `.socket` unit, systemd binds the listening sockets itself (port
+//! 80 included, without giving the service any privileges) and starts the
+//! service with them as file descriptors 3 and up, announced through
+//! `LISTEN_FDS` and `LISTEN_PID` as in `sd_listen_fds(3)`. Each socket can
+//! be TCP or a Unix stream socket; addresses and socket options come from
+//! the unit, so `BIND_ADDR`, `PORT` and the other bind settings are
+//! ignored.
+
+use std::io;
+use std::os::fd::{FromRawFd, OwnedFd, RawFd};
+
+use socket2::{Socket, Type};
+use tokio::net::TcpListener;
+
+use super::Listener;
+use super::unix::UnixSocket;
+
+/// The first descriptor systemd passes; the rest follow consecutively.
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+/// The listening sockets systemd passed to this process, in order, or
+/// `None` if it wasn't socket-activated.
+pub fn listeners() -> io::Result<Option<Vec<Listener>>> {
+    let pid = std::env::var("LISTEN_PID").ok();
+    let fds = std::env::var("LISTEN_FDS").ok();
+    let Some(count) = listen_fds(pid.as_deref(), fds.as_deref(), std::process::id())? else {
+        return Ok(None);
+    };
+    tracing::info!(
+        count,
+        "using the sockets passed by systemd; bind settings are ignored"
+    );
+    (0..count as RawFd)
+        .map(|i| {
+            // SAFETY: LISTEN_PID names this process, so systemd handed us
+            // these descriptors and nothing else in the process owns them.
+            let fd = unsafe { OwnedFd::from_raw_fd(SD_LISTEN_FDS_START + i) };
+            from_fd(fd)
+        })
+        .collect::<io::Result<_>>()
+        .map(Some)
+}
+
+/// How many descriptors were passed, from the `LISTEN_PID` and
+/// `LISTEN_FDS` values. Variables meant for another process (a parent
+/// that didn't clear them) are ignored.
+fn listen_fds(pid: Option<&str>, fds: Option<&str>, own_pid: u32) -> io::Result<Option<u32>> {
+    let (Some(pid), Some(fds)) = (pid, fds) else {
+        return Ok(None);
+    };
+    if pid.trim().parse::<u32>().ok() != Some(own_pid) {
+        return Ok(None);
+    }
+    match fds.trim().parse::<u32>() {
+        Ok(0) => Ok(None),
+        Ok(n) => Ok(Some(n)),
+        Err(_) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid LISTEN_FDS {fds:?}"),
+        )),
+    }
+}
+
+fn from_fd(fd: OwnedFd) -> io::Result<Listener> {
+    let socket = Socket::from(fd);
+    if socket.r#type()? != Type::STREAM {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "socket activation passed a datagram socket; use ListenStream=",
+        ));
+    }
+    socket.set_nonblocking(true)?;
+    if socket.local_addr()?.is_unix() {
+        UnixSocket::from_std(socket.into()).map(Listener::Unix)
+    } else {
+        TcpListener::from_std(socket.into()).map(Listener::Tcp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_variables_for_this_process_count() {
+        assert_eq!(listen_fds(None, None, 42).unwrap(), None);
+        assert_eq!(listen_fds(Some("42"), Some("1"), 42).unwrap(), Some(1));
+        assert_eq!(listen_fds(Some("41"), Some("1"), 42).unwrap(), None);
+        assert_eq!(listen_fds(Some("42"), Some("0"), 42).unwrap(), None);
+        assert!(listen_fds(Some("42"), Some("one"), 42).is_err());
+    }
+
+    #[tokio::test]
+    async fn adopts_tcp_and_unix_sockets() {
+        let tcp = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = tcp.local_addr().unwrap();
+        let listener = from_fd(tcp.into()).unwrap();
+        assert_eq!(listener.to_string(), addr.to_string());
+
+        let path =
+            std::env::temp_dir().join(format!("ipecho-activated-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let unix = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        let listener = from_fd(unix.into()).unwrap();
+        assert_eq!(listener.to_string(), format!("unix:{}", path.display()));
+        // The socket belongs to systemd, so it outlives the listener.
+        drop(listener);
+        assert!(path.exists());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn datagram_sockets_are_rejected() {
+        let udp = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        assert!(from_fd(udp.into()).is_err());
+    }
+}
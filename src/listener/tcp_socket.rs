@@ -0,0 +1,196 @@
+//! The accepted TCP socket, for handlers that report what the kernel knows
+//! about the connection (`/mtu`, `/rtt`).
+//!
+//! [`TcpSocket`] borrows the connection's descriptor, only ever for
+//! `getsockopt`; reads and writes stay with hyper. It's attached to every
+//! request on a TCP connection, TLS or not, but costs no syscall until a
+//! handler reads it, and is closed before the connection's socket is, so a
+//! copy kept past the connection can't read a reused descriptor. Note that
+//! behind a reverse proxy or PROXY protocol the socket is the one to the
+//! proxy, not to the client.
+
+use std::io;
+use std::net::SocketAddr;
+use std::os::fd::{AsRawFd, BorrowedFd, RawFd};
+use std::sync::{Arc, RwLock};
+
+use serde::Serialize;
+use socket2::SockRef;
+
+/// A connection's TCP socket, as a request extension. `None` once the
+/// connection is closed.
+#[derive(Debug, Clone)]
+pub struct TcpSocket(Arc<RwLock<Option<RawFd>>>);
+
+impl TcpSocket {
+    /// Borrow `stream`'s descriptor; [`TcpSocket::close`] must be called
+    /// before `stream` is dropped.
+    pub(crate) fn of(stream: &impl AsRawFd) -> Self {
+        Self(Arc::new(RwLock::new(Some(stream.as_raw_fd()))))
+    }
+
+    /// Give the descriptor back, waiting out any reads in progress.
+    pub(crate) fn close(&self) {
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// Run `f` on the socket, if the connection is still open.
+    fn with<T>(&self, f: impl FnOnce(SockRef<'_>) -> io::Result<T>) -> io::Result<T> {
+        let fd = self.0.read().unwrap_or_else(|e| e.into_inner());
+        let fd = fd.ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?;
+        // SAFETY: the descriptor stays open while the read lock is held,
+        // as `close` runs before the stream owning it is dropped.
+        let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+        f(SockRef::from(&fd))
+    }
+
+    /// The address at the other end of the socket.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.with(|sock| sock.peer_addr())?
+            .as_socket()
+            .ok_or_else(|| io::Error::other("not an IP socket"))
+    }
+
+    /// The local address, to tell IPv4 from IPv6.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.with(|sock| sock.local_addr())?
+            .as_socket()
+            .ok_or_else(|| io::Error::other("not an IP socket"))
+    }
+
+    /// `TCP_MAXSEG`: the largest segment payload the kernel sends, after
+    /// the peer's advertised MSS, the path MTU and TCP options.
+    pub fn mss(&self) -> io::Result<u32> {
+        self.with(|sock| sock.tcp_mss())
+    }
+
+    /// `TCP_INFO`, the kernel's per-connection state; `None` off Linux.
+    #[cfg(not(target_os = "linux"))]
+    pub fn info(&self) -> io::Result<Option<TcpInfo>> {
+        Ok(None)
+    }
+
+    /// `TCP_INFO`, the kernel's per-connection state; `None` off Linux.
+    #[cfg(target_os = "linux")]
+    pub fn info(&self) -> io::Result<Option<TcpInfo>> {
+        self.with(|sock| {
+            // SAFETY: `tcp_info` is plain integers, so all-zero is a valid
+            // value, and getsockopt writes at most `len` bytes into it.
+            let mut raw: libc::tcp_info = unsafe { std::mem::zeroed() };
+            let mut len = std::mem::size_of::<libc::tcp_info>() as libc::socklen_t;
+            let rc = unsafe {
+                libc::getsockopt(
+                    sock.as_raw_fd(),
+                    libc::IPPROTO_TCP,
+                    libc::TCP_INFO,
+                    (&mut raw as *mut libc::tcp_info).cast(),
+                    &mut len,
+                )
+            };
+            if rc != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Some(TcpInfo::from(&raw)))
+        })
+    }
+}
+
+/// The parts of Linux's `struct tcp_info` that are reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TcpInfo {
+    /// Path MTU the kernel currently assumes for this connection.
+    pub pmtu: u32,
+    /// Whether the TCP timestamps option is in use (12 bytes per segment).
+    pub timestamps: bool,
+    /// Window scale shifts, when both ends agreed to scaling.
+    pub window_scale: Option<WindowScale>,
+    /// Smoothed round-trip time, in microseconds.
+    pub rtt_us: u32,
+    /// Round-trip time variation, in microseconds.
+    pub rttvar_us: u32,
+    /// Current retransmission timeout, in microseconds.
+    pub rto_us: u32,
+    /// Segments retransmitted over the connection's lifetime.
+    pub total_retrans: u32,
+    /// Segments currently presumed lost.
+    pub lost: u32,
+    /// Congestion window, in segments.
+    pub snd_cwnd: u32,
+    /// Slow-start threshold in segments; `None` while still in slow start.
+    pub snd_ssthresh: Option<u32>,
+    /// Sending MSS, to turn `snd_cwnd` into bytes.
+    pub snd_mss: u32,
+}
+
+/// RFC 7323 window scale shift counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct WindowScale {
+    /// Applied to windows the client advertises.
+    pub send: u8,
+    /// Applied to windows this server advertises.
+    pub receive: u8,
+}
+
+#[cfg(target_os = "linux")]
+impl From<&libc::tcp_info> for TcpInfo {
+    fn from(raw: &libc::tcp_info) -> Self {
+        // From linux/tcp.h.
+        const TCPI_OPT_TIMESTAMPS: u8 = 1;
+        const TCPI_OPT_WSCALE: u8 = 4;
+        const TCP_INFINITE_SSTHRESH: u32 = 0x7fff_ffff;
+        Self {
+            pmtu: raw.tcpi_pmtu,
+            timestamps: raw.tcpi_options & TCPI_OPT_TIMESTAMPS != 0,
+            window_scale: (raw.tcpi_options & TCPI_OPT_WSCALE != 0).then(|| {
+                // Two 4-bit fields, the send scale declared first.
+                let (first, second) = if cfg!(target_endian = "little") {
+                    (raw.tcpi_snd_rcv_wscale & 0x0f, raw.tcpi_snd_rcv_wscale >> 4)
+                } else {
+                    (raw.tcpi_snd_rcv_wscale >> 4, raw.tcpi_snd_rcv_wscale & 0x0f)
+                };
+                WindowScale {
+                    send: first,
+                    receive: second,
+                }
+            }),
+            rtt_us: raw.tcpi_rtt,
+            rttvar_us: raw.tcpi_rttvar,
+            rto_us: raw.tcpi_rto,
+            total_retrans: raw.tcpi_total_retrans,
+            lost: raw.tcpi_lost,
+            snd_cwnd: raw.tcpi_snd_cwnd,
+            snd_ssthresh: (raw.tcpi_snd_ssthresh < TCP_INFINITE_SSTHRESH)
+                .then_some(raw.tcpi_snd_ssthresh),
+            snd_mss: raw.tcpi_snd_mss,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reads_a_loopback_connection() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+
+        let socket = TcpSocket::of(&server);
+        assert_eq!(socket.peer_addr().unwrap(), client.local_addr().unwrap());
+        assert_eq!(socket.local_addr().unwrap(), addr);
+        assert!(socket.mss().unwrap() > 0);
+        #[cfg(target_os = "linux")]
+        {
+            let info = socket.info().unwrap().unwrap();
+            assert!(info.pmtu > 0);
+            assert!(info.snd_cwnd > 0);
+            assert!(info.rtt_us > 0);
+        }
+
+        socket.close();
+        let err = socket.mss().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotConnected);
+    }
+}
@@ -0,0 +1,417 @@
+//! TLS termination via rustls.
+//!
+//! The handshake runs inside the per-connection task after any PROXY
+//! protocol header has been consumed (the header precedes the TLS
+//! ClientHello on the wire), so `ConnectInfo` still carries the real client
+//! address.
+//!
+//! Certificates loaded from files can be replaced at runtime (SIGHUP, see
+//! [`crate::reload`]); connections already established keep the old one.
+//!
+//! Each connection's requests carry a [`TlsSession`] describing what was
+//! negotiated, and the ClientHello's [`client_hello::TlsFingerprint`].
+//!
+//! With `TLS_CLIENT_AUTH`, clients are asked for a certificate but may
+//! decline. Any certificate is accepted, whoever issued it, as long as the
+//! client proves it holds the key; it's attached to requests as a
+//! [`ClientCert`] for `/cert` to report. Nothing here authenticates anyone.
+
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+use axum::http::Extensions;
+use serde::Serialize;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio_rustls::LazyConfigAcceptor;
+use tokio_rustls::rustls::client::danger::HandshakeSignatureValid;
+use tokio_rustls::rustls::crypto::{
+    CryptoProvider, WebPkiSupportedAlgorithms, ring, verify_tls12_signature, verify_tls13_signature,
+};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, UnixTime};
+use tokio_rustls::rustls::server::danger::{ClientCertVerified, ClientCertVerifier};
+use tokio_rustls::rustls::server::{Acceptor, ResolvesServerCert, WantsServerCert};
+use tokio_rustls::rustls::{
+    ConfigBuilder, DigitallySignedStruct, DistinguishedName, Error, HandshakeKind, ServerConfig,
+    ServerConnection, SignatureScheme,
+};
+use tokio_rustls::server::TlsStream;
+use x509_parser::prelude::{FromDer, GeneralName, X509Certificate};
+
+use crate::datetime::UtcDateTime;
+
+use super::client_hello::{self, ClientHello, Rewind};
+
+/// ALPN protocols offered to clients, in preference order.
+const ALPN_PROTOCOLS: [&[u8]; 2] = [b"h2", b"http/1.1"];
+
+/// What a TLS connection negotiated, a request extension on TLS
+/// connections.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TlsSession {
+    /// e.g. `TLSv1.3`.
+    pub version: String,
+    /// rustls's name for it, e.g. `TLS13_AES_128_GCM_SHA256`.
+    pub cipher_suite: String,
+    /// `null` when the client offered no ALPN.
+    pub alpn: Option<String>,
+    /// Server name the client sent, `null` when it sent none (e.g. when
+    /// connecting to an IP address).
+    pub sni: Option<String>,
+    /// Whether the handshake resumed an earlier session.
+    pub resumed: bool,
+}
+
+impl TlsSession {
+    fn of(conn: &ServerConnection) -> Self {
+        Self {
+            version: conn
+                .protocol_version()
+                .map(|v| match v.as_str() {
+                    Some(name) => name.replace('_', "."),
+                    None => format!("{v:?}"),
+                })
+                .unwrap_or_default(),
+            cipher_suite: conn
+                .negotiated_cipher_suite()
+                .map(|s| match s.suite().as_str() {
+                    Some(name) => name.to_string(),
+                    None => format!("{:?}", s.suite()),
+                })
+                .unwrap_or_default(),
+            alpn: conn
+                .alpn_protocol()
+                .map(|p| String::from_utf8_lossy(p).into_owned()),
+            sni: conn.server_name().map(str::to_string),
+            resumed: conn.handshake_kind() == Some(HandshakeKind::Resumed),
+        }
+    }
+}
+
+/// The certificate a client presented, a request extension on TLS
+/// connections that sent one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ClientCert {
+    /// Distinguished names in RFC 4514 form, e.g. `CN=alice, O=Example`.
+    pub subject: String,
+    pub issuer: String,
+    /// Subject alternative names, e.g. `DNS:alice.example.com`,
+    /// `IP:10.0.0.1`, `email:alice@example.com`.
+    pub sans: Vec<String>,
+    /// Hex, as in the certificate.
+    pub serial: String,
+    /// RFC 3339, UTC.
+    pub not_before: String,
+    pub not_after: String,
+    /// SHA-256 of the DER encoding, hex.
+    pub sha256: String,
+    /// How many intermediate certificates came with it.
+    pub chain_length: usize,
+}
+
+impl ClientCert {
+    /// `None` when `cert` isn't valid X.509 (rustls has already checked the
+    /// client holds its key, not that it parses).
+    fn parse(cert: &CertificateDer<'_>, intermediates: usize) -> Option<Self> {
+        use sha2::Digest;
+
+        let (_, parsed) = X509Certificate::from_der(cert).ok()?;
+        let sans = parsed
+            .subject_alternative_name()
+            .ok()
+            .flatten()
+            .map(|ext| ext.value.general_names.iter().map(general_name).collect())
+            .unwrap_or_default();
+        let time = |t: x509_parser::time::ASN1Time| {
+            UtcDateTime::from_unix(t.timestamp().max(0) as u64).rfc3339(false)
+        };
+        Some(Self {
+            subject: parsed.subject().to_string(),
+            issuer: parsed.issuer().to_string(),
+            sans,
+            serial: parsed.raw_serial_as_string().replace(':', ""),
+            not_before: time(parsed.validity().not_before),
+            not_after: time(parsed.validity().not_after),
+            sha256: sha2::Sha256::digest(cert)
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect(),
+            chain_length: intermediates,
+        })
+    }
+}
+
+fn general_name(name: &GeneralName<'_>) -> String {
+    match name {
+        GeneralName::DNSName(dns) => format!("DNS:{dns}"),
+        GeneralName::RFC822Name(email) => format!("email:{email}"),
+        GeneralName::URI(uri) => format!("URI:{uri}"),
+        GeneralName::IPAddress(bytes) => match <[u8; 4]>::try_from(*bytes) {
+            Ok(v4) => format!("IP:{}", std::net::Ipv4Addr::from(v4)),
+            Err(_) => match <[u8; 16]>::try_from(*bytes) {
+                Ok(v6) => format!("IP:{}", std::net::Ipv6Addr::from(v6)),
+                Err(_) => format!("IP:{bytes:02x?}"),
+            },
+        },
+        other => other.to_string(),
+    }
+}
+
+/// Asks for a client certificate without requiring one, and accepts any:
+/// only the handshake signature is checked, so the client must hold the
+/// certificate's key.
+struct RequestClientCert {
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl fmt::Debug for RequestClientCert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestClientCert").finish_non_exhaustive()
+    }
+}
+
+impl ClientCertVerifier for RequestClientCert {
+    fn client_auth_mandatory(&self) -> bool {
+        false
+    }
+
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _now: UnixTime,
+    ) -> Result<ClientCertVerified, Error> {
+        Ok(ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
+
+/// Server-side TLS settings shared by every connection on a listener.
+#[derive(Clone)]
+pub struct TlsConfig {
+    /// Shared by every clone, so [`TlsConfig::reload_pem_files`] reaches
+    /// the accept loop.
+    server: Arc<ArcSwap<ServerConfig>>,
+    /// Answers ACME TLS-ALPN-01 validation handshakes, when ACME is enabled.
+    challenge: Option<Arc<ServerConfig>>,
+    /// Ask clients for a certificate (`TLS_CLIENT_AUTH`).
+    client_auth: bool,
+}
+
+impl TlsConfig {
+    /// Build from a PEM certificate chain and private key (PKCS#8, PKCS#1
+    /// or SEC1). With `client_auth`, clients are asked for a certificate.
+    pub fn from_pem_files(
+        cert_path: &Path,
+        key_path: &Path,
+        client_auth: bool,
+    ) -> io::Result<Self> {
+        let config = server_config(cert_path, key_path, client_auth)?;
+        Ok(Self {
+            server: Arc::new(ArcSwap::from_pointee(config)),
+            challenge: None,
+            client_auth,
+        })
+    }
+
+    /// Replace the certificate with the pair at `cert_path` and `key_path`
+    /// for new connections. On error, the current one stays in use.
+    pub fn reload_pem_files(&self, cert_path: &Path, key_path: &Path) -> io::Result<()> {
+        if self.challenge.is_some() {
+            return Err(invalid("certificates are managed by ACME".into()));
+        }
+        self.server.store(Arc::new(server_config(
+            cert_path,
+            key_path,
+            self.client_auth,
+        )?));
+        Ok(())
+    }
+
+    /// Serve whatever certificate `resolver` currently holds. Used by the
+    /// ACME subsystem, whose resolver is swapped on every renewal, so new
+    /// certificates apply without touching the listener.
+    pub fn from_acme(
+        resolver: Arc<dyn ResolvesServerCert>,
+        challenge: Arc<ServerConfig>,
+        client_auth: bool,
+    ) -> io::Result<Self> {
+        let mut config = builder(client_auth)?.with_cert_resolver(resolver);
+        config.alpn_protocols = ALPN_PROTOCOLS.iter().map(|p| p.to_vec()).collect();
+
+        Ok(Self {
+            server: Arc::new(ArcSwap::from_pointee(config)),
+            challenge: Some(challenge),
+            client_auth,
+        })
+    }
+
+    /// Perform the server handshake, fingerprinting the ClientHello on the
+    /// way (see [`client_hello`]). Returns the stream along with the
+    /// request extensions describing it ([`TlsSession`],
+    /// [`client_hello::TlsFingerprint`] and any [`ClientCert`]), or
+    /// `Ok(None)` for connections that were only an ACME validation probe
+    /// and have already been answered.
+    pub async fn accept<S>(
+        &self,
+        mut stream: S,
+    ) -> io::Result<Option<(TlsStream<Rewind<S>>, Extensions)>>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let hello = client_hello::peek(&mut stream).await?;
+        let fingerprint = ClientHello::parse(&hello).map(|hello| hello.fingerprint());
+        let stream = Rewind::new(hello, stream);
+        let start = LazyConfigAcceptor::new(Acceptor::default(), stream).await?;
+        if let Some(challenge) = &self.challenge
+            && rustls_acme::is_tls_alpn_challenge(&start.client_hello())
+        {
+            tracing::info!("answering ACME TLS-ALPN-01 validation request");
+            let mut tls = start.into_stream(challenge.clone()).await?;
+            tls.shutdown().await?;
+            return Ok(None);
+        }
+        let stream = start.into_stream(self.server.load_full()).await?;
+        let conn = stream.get_ref().1;
+        let mut extensions = Extensions::new();
+        extensions.insert(TlsSession::of(conn));
+        if let Some(fingerprint) = fingerprint {
+            extensions.insert(fingerprint);
+        }
+        if let Some((cert, intermediates)) = conn.peer_certificates().and_then(|c| c.split_first())
+            && let Some(cert) = ClientCert::parse(cert, intermediates.len())
+        {
+            extensions.insert(cert);
+        }
+        Ok(Some((stream, extensions)))
+    }
+}
+
+/// A server config for a PEM certificate chain and private key (PKCS#8,
+/// PKCS#1 or SEC1).
+fn server_config(cert_path: &Path, key_path: &Path, client_auth: bool) -> io::Result<ServerConfig> {
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|it| it.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| invalid(format!("{}: {e}", cert_path.display())))?;
+    if certs.is_empty() {
+        return Err(invalid(format!(
+            "{}: no certificates found",
+            cert_path.display()
+        )));
+    }
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|e| invalid(format!("{}: {e}", key_path.display())))?;
+
+    let mut config = builder(client_auth)?
+        .with_single_cert(certs, key)
+        .map_err(|e| invalid(format!("certificate/key mismatch: {e}")))?;
+    config.alpn_protocols = ALPN_PROTOCOLS.iter().map(|p| p.to_vec()).collect();
+    Ok(config)
+}
+
+/// A config builder with the default protocol versions and, with
+/// `client_auth`, [`RequestClientCert`].
+fn builder(client_auth: bool) -> io::Result<ConfigBuilder<ServerConfig, WantsServerCert>> {
+    let provider = provider();
+    let algorithms = provider.signature_verification_algorithms;
+    let builder = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| invalid(e.to_string()))?;
+    Ok(if client_auth {
+        builder.with_client_cert_verifier(Arc::new(RequestClientCert { algorithms }))
+    } else {
+        builder.with_no_client_auth()
+    })
+}
+
+/// Both ring and aws-lc-rs end up in the dependency graph, so rustls can't
+/// pick a process-wide default; name the provider explicitly.
+pub fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_pair(dir: &Path) -> (std::path::PathBuf, std::path::PathBuf) {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+        let cert_path = dir.join("cert.pem");
+        let key_path = dir.join("key.pem");
+        std::fs::write(&cert_path, cert.cert.pem()).unwrap();
+        std::fs::write(&key_path, cert.signing_key.serialize_pem()).unwrap();
+        (cert_path, key_path)
+    }
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("ipecho-tls-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn loads_valid_pair() {
+        let dir = temp_dir("valid");
+        let (cert, key) = write_pair(&dir);
+        let tls = TlsConfig::from_pem_files(&cert, &key, false).unwrap();
+        assert_eq!(tls.server.load().alpn_protocols[0], b"h2");
+        assert!(tls.challenge.is_none());
+    }
+
+    #[test]
+    fn reload_swaps_in_new_pair_or_keeps_old() {
+        let dir = temp_dir("reload");
+        let (cert, key) = write_pair(&dir);
+        let tls = TlsConfig::from_pem_files(&cert, &key, false).unwrap();
+        let accepting = tls.clone();
+        let before = accepting.server.load_full();
+
+        assert!(tls.reload_pem_files(&cert, &cert).is_err());
+        assert!(Arc::ptr_eq(&before, &accepting.server.load_full()));
+
+        write_pair(&dir);
+        tls.reload_pem_files(&cert, &key).unwrap();
+        assert!(!Arc::ptr_eq(&before, &accepting.server.load_full()));
+    }
+
+    #[test]
+    fn missing_or_swapped_files_are_errors() {
+        let dir = temp_dir("invalid");
+        let (cert, key) = write_pair(&dir);
+        assert!(TlsConfig::from_pem_files(&dir.join("nope.pem"), &key, false).is_err());
+        assert!(TlsConfig::from_pem_files(&key, &key, false).is_err());
+        assert!(TlsConfig::from_pem_files(&cert, &cert, false).is_err());
+    }
+}
@@ -0,0 +1,185 @@
+//! Unix domain socket listener, for running behind a reverse proxy on the
+//! same host (`BIND_ADDR=unix:/run/echo.sock`).
+//!
+//! A UDS peer has no IP address, so connections are reported to handlers
+//! as [`UNIX_PEER`]. That address is always a trusted peer, which means the
+//! client address comes entirely from the forwarding headers the proxy
+//! sets; who may connect at all is decided by the socket's permissions
+//! (`UNIX_SOCKET_MODE`, `UNIX_SOCKET_OWNER`).
+
+use std::fs::Permissions;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use nix::unistd::{Group, User};
+use tokio::net::{UnixListener, UnixStream};
+
+/// The address handlers see for a connection accepted on a Unix socket.
+pub const UNIX_PEER: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
+
+/// A bound Unix socket. A socket file we created is removed again on drop.
+pub struct UnixSocket {
+    listener: UnixListener,
+    path: PathBuf,
+    /// False for a socket someone else created, e.g. systemd.
+    owned: bool,
+}
+
+impl UnixSocket {
+    /// Bind `path`, replacing a stale socket left behind by a previous run,
+    /// then apply `mode` and, if given, the `user[:group]` owner.
+    pub fn bind(path: &Path, mode: u32, owner: Option<&str>) -> io::Result<Self> {
+        let owner = owner.map(parse_owner).transpose()?;
+        remove_stale(path)?;
+        let listener = UnixListener::bind(path)?;
+        // Owned from here on, so a failure below doesn't leave the file.
+        let socket = Self {
+            listener,
+            path: path.to_path_buf(),
+            owned: true,
+        };
+        std::fs::set_permissions(path, Permissions::from_mode(mode))?;
+        if let Some((uid, gid)) = owner {
+            std::os::unix::fs::chown(path, uid, gid)?;
+        }
+        Ok(socket)
+    }
+
+    /// Adopt an already listening socket, which is left in place on drop.
+    /// Must be in non-blocking mode.
+    pub fn from_std(listener: std::os::unix::net::UnixListener) -> io::Result<Self> {
+        let path = listener.local_addr()?.as_pathname().map(Path::to_path_buf);
+        Ok(Self {
+            listener: UnixListener::from_std(listener)?,
+            path: path.unwrap_or_default(),
+            owned: false,
+        })
+    }
+
+    pub async fn accept(&self) -> io::Result<UnixStream> {
+        self.listener.accept().await.map(|(stream, _)| stream)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        if self.owned {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Remove a socket file nobody is listening on. A live socket, or a path
+/// that isn't a socket at all, is left alone and reported as an error.
+fn remove_stale(path: &Path) -> io::Result<()> {
+    let meta = match std::fs::symlink_metadata(path) {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    if !meta.file_type().is_socket() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} exists and is not a socket", path.display()),
+        ));
+    }
+    if std::os::unix::net::UnixStream::connect(path).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("{} is in use by another process", path.display()),
+        ));
+    }
+    std::fs::remove_file(path)
+}
+
+/// Resolve `user[:group]`, each a name or a numeric ID, to the IDs to
+/// `chown` to. Without a group, the group is left unchanged.
+fn parse_owner(owner: &str) -> io::Result<(Option<u32>, Option<u32>)> {
+    let (user, group) = match owner.split_once(':') {
+        Some((user, group)) => (user, Some(group)),
+        None => (owner, None),
+    };
+    let uid = match user {
+        "" => None,
+        user => Some(resolve(user, "user", |name| {
+            User::from_name(name).map(|u| u.map(|u| u.uid.as_raw()))
+        })?),
+    };
+    let gid = match group {
+        None | Some("") => None,
+        Some(group) => Some(resolve(group, "group", |name| {
+            Group::from_name(name).map(|g| g.map(|g| g.gid.as_raw()))
+        })?),
+    };
+    Ok((uid, gid))
+}
+
+fn resolve(
+    name: &str,
+    kind: &str,
+    lookup: impl FnOnce(&str) -> nix::Result<Option<u32>>,
+) -> io::Result<u32> {
+    if let Ok(id) = name.parse() {
+        return Ok(id);
+    }
+    lookup(name)?.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("unknown {kind} {name:?} in UNIX_SOCKET_OWNER"),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ipecho-{name}-{}.sock", std::process::id()))
+    }
+
+    #[test]
+    fn owner_accepts_names_and_ids() {
+        assert_eq!(parse_owner("0").unwrap(), (Some(0), None));
+        assert_eq!(parse_owner("root:0").unwrap(), (Some(0), Some(0)));
+        assert_eq!(parse_owner(":1234").unwrap(), (None, Some(1234)));
+        assert!(parse_owner("no-such-user-ipecho").is_err());
+    }
+
+    #[tokio::test]
+    async fn applies_mode_and_removes_the_file_on_drop() {
+        let path = temp_path("mode");
+        let socket = UnixSocket::bind(&path, 0o600, None).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        drop(socket);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn replaces_stale_sockets_but_not_live_ones() {
+        let path = temp_path("stale");
+        let _ = std::fs::remove_file(&path);
+        // A std listener that is dropped leaves its file behind.
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        let live = UnixSocket::bind(&path, 0o660, None).unwrap();
+
+        let err = UnixSocket::bind(&path, 0o660, None).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+        drop(live);
+    }
+
+    #[test]
+    fn refuses_to_replace_other_files() {
+        let path = temp_path("file");
+        std::fs::write(&path, "").unwrap();
+        assert!(UnixSocket::bind(&path, 0o660, None).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
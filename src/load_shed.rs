@@ -0,0 +1,100 @@
+//! Concurrency cap for the public endpoints (`MAX_CONCURRENT_REQUESTS`).
+//!
+//! Once that many requests are in flight, new ones are refused immediately
+//! with a 503 and `Retry-After` instead of queueing, so a saturated instance
+//! stays responsive and a load balancer can send the client elsewhere.
+//! Shed requests are counted in `http_requests_shed_total`. Health and
+//! metrics endpoints aren't subject to the cap.
+
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{Request, Response, header};
+use axum::middleware::Next;
+use axum::response::IntoResponse;
+use tokio::sync::Semaphore;
+
+use crate::errors::AppError;
+
+/// Shared permit pool for [`load_shed_middleware`].
+#[derive(Clone)]
+pub struct LoadShed {
+    permits: Arc<Semaphore>,
+}
+
+impl LoadShed {
+    /// A cap of `max_concurrent` requests, or `None` for no limit (0).
+    pub fn new(max_concurrent: usize) -> Option<Self> {
+        (max_concurrent > 0).then(|| Self {
+            permits: Arc::new(Semaphore::new(max_concurrent)),
+        })
+    }
+}
+
+pub async fn load_shed_middleware(
+    State(shed): State<LoadShed>,
+    request: Request<Body>,
+    next: Next,
+) -> Response<Body> {
+    let Ok(_permit) = shed.permits.try_acquire() else {
+        metrics::counter!("http_requests_shed_total").increment(1);
+        return ([(header::RETRY_AFTER, "1")], AppError::Overloaded).into_response();
+    };
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::Router;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use tokio::sync::Notify;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn request() -> Request<Body> {
+        Request::builder().uri("/").body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn sheds_requests_beyond_the_cap() {
+        // The handler signals `entered`, then waits until the gate closes.
+        let entered = Arc::new(Notify::new());
+        let gate = Arc::new(Semaphore::new(0));
+        let (handler_entered, handler_gate) = (entered.clone(), gate.clone());
+        let app = Router::new()
+            .route(
+                "/",
+                get(move || {
+                    let (entered, gate) = (handler_entered.clone(), handler_gate.clone());
+                    async move {
+                        entered.notify_one();
+                        let _ = gate.acquire().await;
+                    }
+                }),
+            )
+            .layer(axum::middleware::from_fn_with_state(
+                LoadShed::new(1).unwrap(),
+                load_shed_middleware,
+            ));
+
+        let in_flight = tokio::spawn(app.clone().oneshot(request()));
+        entered.notified().await;
+
+        let shed = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(shed.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(shed.headers()[header::RETRY_AFTER], "1");
+
+        gate.close();
+        assert_eq!(in_flight.await.unwrap().unwrap().status(), StatusCode::OK);
+        let after = app.oneshot(request()).await.unwrap();
+        assert_eq!(after.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn zero_disables_the_cap() {
+        assert!(LoadShed::new(0).is_none());
+    }
+}
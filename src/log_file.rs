@@ -0,0 +1,248 @@
+//! Size- and time-rotated log file for `LOG_FILE`.
+//!
+//! The active file keeps its configured name, so a shipper (Promtail,
+//! Filebeat, Vector) can tail a fixed path. On rotation it's renamed to
+//! `<name>.<YYYYMMDDTHHMMSS>` (UTC), which sorts chronologically, and the
+//! oldest rotated files beyond `LOG_FILE_MAX_FILES` are deleted.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::config::{Config, LogRotation};
+use crate::datetime::UtcDateTime;
+
+/// When to rotate and how many old files to keep.
+#[derive(Debug, Clone, Copy)]
+pub struct RotationPolicy {
+    /// Rotate before a write would take the file past this many bytes.
+    pub max_bytes: Option<u64>,
+    pub rotation: LogRotation,
+    /// Rotated files to keep; `None` keeps them all.
+    pub max_files: Option<usize>,
+}
+
+impl RotationPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_bytes: match config.log_file_max_size_mb {
+                0 => None,
+                mb => Some(mb * 1024 * 1024),
+            },
+            rotation: config.log_file_rotation,
+            max_files: match config.log_file_max_files {
+                0 => None,
+                n => Some(n),
+            },
+        }
+    }
+
+    /// Index of the rotation period `time` falls in. The file is rotated
+    /// when this changes.
+    fn period(&self, time: SystemTime) -> u64 {
+        let secs = time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        match self.rotation {
+            LogRotation::Never => 0,
+            LogRotation::Hourly => secs / 3600,
+            LogRotation::Daily => secs / 86_400,
+        }
+    }
+}
+
+/// An append-only log file that rotates itself on write.
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    period: u64,
+    policy: RotationPolicy,
+}
+
+impl RotatingFile {
+    /// Open `path` for appending. An existing file last written in an
+    /// earlier period is rotated on the first write.
+    pub fn open(path: &Path, policy: RotationPolicy) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let metadata = file.metadata()?;
+        let last_write = metadata.modified().unwrap_or_else(|_| SystemTime::now());
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            size: metadata.len(),
+            period: policy.period(last_write),
+            policy,
+        })
+    }
+
+    fn needs_rotation(&self, incoming: usize, now: SystemTime) -> bool {
+        if self.size == 0 {
+            return false;
+        }
+        let too_big = self
+            .policy
+            .max_bytes
+            .is_some_and(|max| self.size + incoming as u64 > max);
+        too_big || self.policy.period(now) != self.period
+    }
+
+    fn rotate(&mut self, now: SystemTime) -> io::Result<()> {
+        self.file.flush()?;
+        fs::rename(&self.path, self.archive_path(now))?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        if let Some(keep) = self.policy.max_files {
+            self.prune(keep)?;
+        }
+        Ok(())
+    }
+
+    /// `<path>.<timestamp>`, with a `-N` suffix if several rotations land
+    /// in the same second.
+    fn archive_path(&self, now: SystemTime) -> PathBuf {
+        let t = UtcDateTime::from_system_time(now);
+        let stamp = format!(
+            "{:04}{:02}{:02}T{:02}{:02}{:02}",
+            t.year, t.month, t.day, t.hour, t.minute, t.second
+        );
+        let base = format!("{}.{stamp}", self.path.display());
+        let mut candidate = PathBuf::from(&base);
+        let mut n = 1;
+        while candidate.exists() {
+            candidate = PathBuf::from(format!("{base}-{n}"));
+            n += 1;
+        }
+        candidate
+    }
+
+    /// Delete the oldest rotated files so at most `keep` remain.
+    fn prune(&self, keep: usize) -> io::Result<()> {
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let Some(name) = self.path.file_name().and_then(|n| n.to_str()) else {
+            return Ok(());
+        };
+        let prefix = format!("{name}.");
+        let mut rotated: Vec<_> = fs::read_dir(dir)?
+            .filter_map(Result::ok)
+            .filter_map(|e| {
+                let file_name = e.file_name().into_string().ok()?;
+                let rest = file_name.strip_prefix(&prefix)?;
+                rest.starts_with(|c: char| c.is_ascii_digit())
+                    .then(|| (file_name.clone(), e.path()))
+            })
+            .collect();
+        if rotated.len() <= keep {
+            return Ok(());
+        }
+        rotated.sort();
+        for (_, path) in &rotated[..rotated.len() - keep] {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let now = SystemTime::now();
+        if self.needs_rotation(buf.len(), now) {
+            self.rotate(now)?;
+        }
+        self.period = self.policy.period(now);
+        self.file.write_all(buf)?;
+        self.size += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("ipecho-logfile-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn rotated(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .filter(|n| n != "ipecho.log")
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn rotates_by_size_and_keeps_max_files() {
+        let dir = temp_dir("size");
+        let path = dir.join("ipecho.log");
+        let policy = RotationPolicy {
+            max_bytes: Some(10),
+            rotation: LogRotation::Never,
+            max_files: Some(2),
+        };
+        let mut file = RotatingFile::open(&path, policy).unwrap();
+        for line in ["aaaaaaa\n", "bbbbbbb\n", "ccccccc\n", "ddddddd\n"] {
+            file.write_all(line.as_bytes()).unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "ddddddd\n");
+        let old = rotated(&dir);
+        assert_eq!(old.len(), 2, "{old:?}");
+        assert_eq!(fs::read_to_string(dir.join(&old[0])).unwrap(), "bbbbbbb\n");
+        assert_eq!(fs::read_to_string(dir.join(&old[1])).unwrap(), "ccccccc\n");
+        assert!(old[0].starts_with("ipecho.log.20"));
+    }
+
+    #[test]
+    fn rotates_when_the_period_changes() {
+        let dir = temp_dir("period");
+        let path = dir.join("ipecho.log");
+        let policy = RotationPolicy {
+            max_bytes: None,
+            rotation: LogRotation::Daily,
+            max_files: None,
+        };
+        let mut file = RotatingFile::open(&path, policy).unwrap();
+        file.write_all(b"today\n").unwrap();
+        assert!(!file.needs_rotation(1, SystemTime::now()));
+        let tomorrow = SystemTime::now() + Duration::from_secs(86_400);
+        assert!(file.needs_rotation(1, tomorrow));
+
+        file.rotate(tomorrow).unwrap();
+        assert_eq!(rotated(&dir).len(), 1);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+    }
+
+    #[test]
+    fn empty_file_is_never_rotated() {
+        let dir = temp_dir("empty");
+        let policy = RotationPolicy {
+            max_bytes: Some(1),
+            rotation: LogRotation::Hourly,
+            max_files: None,
+        };
+        let file = RotatingFile::open(&dir.join("ipecho.log"), policy).unwrap();
+        let later = SystemTime::now() + Duration::from_secs(7200);
+        assert!(!file.needs_rotation(100, later));
+    }
+}
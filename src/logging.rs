@@ -0,0 +1,441 @@
+//! Log output, trace export and the per-request span.
+//!
+//! Every request runs inside an `http` span carrying the method, path and
+//! resolved client IP; when the response is ready, its status and duration
+//! are recorded on the span and a `request completed` event is logged.
+//! With `LOG_FORMAT=json`, each line is a JSON object including the fields
+//! of the enclosing spans, ready for a log shipper.
+//!
+//! `LOG_FILE` additionally writes JSON lines to a rotated file (see
+//! `log_file`), whatever the stdout format. `SYSLOG_ADDR` sends each event
+//! to a syslog collector as an RFC 5424 message (see `syslog`). Both need
+//! the `log-shipping` feature.
+//!
+//! With `OTLP_ENDPOINT` set, the request span is also exported over OTLP/HTTP
+//! as a server span with OpenTelemetry semantic-convention attributes, as a
+//! child of any W3C `traceparent` the client sent. That needs the `otlp`
+//! feature.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(feature = "log-shipping")]
+use anyhow::Context;
+use arc_swap::ArcSwap;
+use axum::extract::ConnectInfo;
+#[cfg(feature = "otlp")]
+use axum::extract::MatchedPath;
+#[cfg(feature = "otlp")]
+use axum::http::header;
+use axum::http::{Request, Response};
+#[cfg(feature = "otlp")]
+use opentelemetry::trace::TracerProvider;
+#[cfg(feature = "otlp")]
+use opentelemetry_http::HeaderExtractor;
+#[cfg(feature = "otlp")]
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+#[cfg(feature = "otlp")]
+use opentelemetry_sdk::Resource;
+#[cfg(feature = "otlp")]
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+#[cfg(feature = "otlp")]
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tower_http::trace::MakeSpan;
+use tracing::Span;
+use tracing::field::Empty;
+#[cfg(feature = "log-shipping")]
+use tracing_appender::non_blocking::WorkerGuard;
+#[cfg(feature = "otlp")]
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+#[cfg(not(all(feature = "log-shipping", feature = "otlp")))]
+use tracing_subscriber::layer::Identity;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+use crate::client_ip::resolve_client_ip;
+use crate::config::{Config, LogFormat};
+#[cfg(feature = "log-shipping")]
+use crate::log_file::{RotatingFile, RotationPolicy};
+#[cfg(feature = "log-shipping")]
+use crate::syslog::SyslogWriter;
+
+/// Keeps log and trace output alive; call [`Telemetry::shutdown`] before
+/// exiting so buffered file lines and spans aren't lost.
+pub struct Telemetry {
+    #[cfg(feature = "otlp")]
+    tracer_provider: Option<SdkTracerProvider>,
+    #[cfg(feature = "log-shipping")]
+    _log_file: Option<WorkerGuard>,
+    #[cfg(feature = "log-shipping")]
+    _syslog: Option<WorkerGuard>,
+}
+
+impl Telemetry {
+    /// Flush exported spans. The log file and syslog queue are flushed when
+    /// `self` drops.
+    pub fn shutdown(self) {
+        #[cfg(feature = "otlp")]
+        if let Some(provider) = &self.tracer_provider
+            && let Err(e) = provider.shutdown()
+        {
+            tracing::warn!(error = %e, "failed to flush trace spans");
+        }
+    }
+}
+
+/// Install the global subscriber. `RUST_LOG`, if set, takes precedence over
+/// the configured level.
+pub fn init(config: &Config) -> anyhow::Result<Telemetry> {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&config.log_level));
+    let fmt = match config.log_format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_current_span(false)
+            .boxed(),
+    };
+
+    // LOG_FILE is always JSON, for shipping; stdout keeps LOG_FORMAT.
+    #[cfg(feature = "log-shipping")]
+    let (file, log_file) = match &config.log_file {
+        Some(path) => {
+            let writer = RotatingFile::open(path, RotationPolicy::from_config(config))
+                .with_context(|| format!("failed to open LOG_FILE {}", path.display()))?;
+            let (writer, guard) = tracing_appender::non_blocking(writer);
+            let layer = tracing_subscriber::fmt::layer()
+                .json()
+                .with_current_span(false)
+                .with_ansi(false)
+                .with_writer(writer);
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
+
+    // Syslog adds its own timestamp and severity to each message.
+    #[cfg(feature = "log-shipping")]
+    let (syslog, syslog_guard) = match &config.syslog_addr {
+        Some(target) => {
+            let (writer, guard) = SyslogWriter::open(target, config.syslog_facility)
+                .with_context(|| format!("failed to open SYSLOG_ADDR {target}"))?;
+            let layer = tracing_subscriber::fmt::layer()
+                .without_time()
+                .with_level(false)
+                .with_ansi(false)
+                .with_writer(writer);
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
+
+    // Server::run refuses LOG_FILE and SYSLOG_ADDR without the feature.
+    #[cfg(not(feature = "log-shipping"))]
+    let (file, syslog) = (None::<Identity>, None::<Identity>);
+
+    #[cfg(feature = "otlp")]
+    let tracer_provider = config
+        .otlp_endpoint
+        .as_deref()
+        .map(tracer_provider)
+        .transpose()?;
+    #[cfg(feature = "otlp")]
+    let otel = tracer_provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer().with_tracer(provider.tracer(env!("CARGO_PKG_NAME")))
+    });
+    #[cfg(not(feature = "otlp"))]
+    let otel = None::<Identity>;
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt)
+        .with(file)
+        .with(syslog)
+        .with(otel)
+        .init();
+    Ok(Telemetry {
+        #[cfg(feature = "otlp")]
+        tracer_provider,
+        #[cfg(feature = "log-shipping")]
+        _log_file: log_file,
+        #[cfg(feature = "log-shipping")]
+        _syslog: syslog_guard,
+    })
+}
+
+/// A batching OTLP/HTTP exporter for `{endpoint}/v1/traces`. The service
+/// name defaults to `ipecho`; `OTEL_SERVICE_NAME` and
+/// `OTEL_RESOURCE_ATTRIBUTES` are honored.
+#[cfg(feature = "otlp")]
+fn tracer_provider(endpoint: &str) -> anyhow::Result<SdkTracerProvider> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{endpoint}/v1/traces"))
+        .build()?;
+    let mut resource = Resource::builder();
+    if std::env::var_os("OTEL_SERVICE_NAME").is_none() {
+        resource = resource.with_service_name(env!("CARGO_PKG_NAME"));
+    }
+    opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource.build())
+        .build())
+}
+
+/// Opens the `http` span for each request. When OTLP export is enabled the
+/// span also carries the attributes collectors expect of an HTTP server
+/// span; they're left off otherwise to keep log lines short.
+#[derive(Clone)]
+pub struct RequestSpan {
+    config: Arc<ArcSwap<Config>>,
+}
+
+impl RequestSpan {
+    pub fn new(config: Arc<ArcSwap<Config>>) -> Self {
+        Self { config }
+    }
+}
+
+impl<B> MakeSpan<B> for RequestSpan {
+    fn make_span(&mut self, request: &Request<B>) -> Span {
+        let peer = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| *addr);
+        let config = self.config.load();
+        let client_ip = peer.map(|addr| resolve_client_ip(addr.ip(), request.headers(), &config));
+
+        #[cfg(feature = "otlp")]
+        if config.otlp_endpoint.is_some() {
+            return otel_span(request, peer, client_ip);
+        }
+        tracing::info_span!(
+            "http",
+            method = %request.method(),
+            path = request.uri().path(),
+            client_ip = client_ip.map(tracing::field::display),
+            status = Empty,
+            duration_ms = Empty,
+        )
+    }
+}
+
+/// The request span with the OpenTelemetry HTTP server attributes, parented
+/// to the client's `traceparent`.
+#[cfg(feature = "otlp")]
+fn otel_span<B>(
+    request: &Request<B>,
+    peer: Option<SocketAddr>,
+    client_ip: Option<std::net::IpAddr>,
+) -> Span {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(MatchedPath::as_str);
+    let name = match route {
+        Some(route) => format!("{} {route}", request.method()),
+        None => request.method().to_string(),
+    };
+    let span = tracing::info_span!(
+        "http",
+        method = %request.method(),
+        path = request.uri().path(),
+        client_ip = client_ip.map(tracing::field::display),
+        status = Empty,
+        duration_ms = Empty,
+        otel.name = name,
+        otel.kind = "server",
+        otel.status_code = Empty,
+        http.request.method = %request.method(),
+        http.route = route,
+        http.response.status_code = Empty,
+        url.path = request.uri().path(),
+        url.query = request.uri().query(),
+        client.address = client_ip.map(tracing::field::display),
+        network.peer.address = peer.map(|addr| tracing::field::display(addr.ip())),
+        network.peer.port = peer.map(|addr| i64::from(addr.port())),
+        network.protocol.version = protocol_version(request),
+        user_agent.original = request
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok()),
+    );
+    let parent = opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(request.headers()))
+    });
+    // Only fails if no OpenTelemetry layer is installed (e.g. tests).
+    let _ = span.set_parent(parent);
+    span
+}
+
+/// `network.protocol.version`: "1.1", "2" and so on.
+#[cfg(feature = "otlp")]
+fn protocol_version<B>(request: &Request<B>) -> Option<&'static str> {
+    use axum::http::Version;
+    match request.version() {
+        Version::HTTP_09 => Some("0.9"),
+        Version::HTTP_10 => Some("1.0"),
+        Version::HTTP_11 => Some("1.1"),
+        Version::HTTP_2 => Some("2"),
+        Version::HTTP_3 => Some("3"),
+        _ => None,
+    }
+}
+
+/// Record the status and duration on the request span and log completion.
+/// Server errors mark an exported span as failed; 4xx responses don't, per
+/// the OpenTelemetry HTTP conventions.
+pub fn on_response<B>(response: &Response<B>, latency: Duration, span: &Span) {
+    let status = response.status().as_u16();
+    let duration_ms = latency.as_secs_f64() * 1000.0;
+    span.record("status", status);
+    span.record("duration_ms", duration_ms);
+    span.record("http.response.status_code", i64::from(status));
+    if response.status().is_server_error() {
+        span.record("otel.status_code", "ERROR");
+    }
+    tracing::info!(status, duration_ms, "request completed");
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::Mutex;
+
+    use axum::http::{HeaderValue, StatusCode};
+    use tracing_subscriber::fmt::MakeWriter;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Capture {
+        type Writer = Self;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn completion_event_carries_request_fields() {
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::fmt()
+            .json()
+            .with_current_span(false)
+            .with_writer(capture.clone())
+            .finish();
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/ip?format=json")
+            .header("x-forwarded-for", HeaderValue::from_static("203.0.113.9"))
+            .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 40000))))
+            .body(())
+            .unwrap();
+        let response = Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(())
+            .unwrap();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = RequestSpan::new(Arc::new(ArcSwap::from_pointee(Config::default())))
+                .make_span(&request);
+            let _guard = span.enter();
+            on_response(&response, Duration::from_millis(12), &span);
+        });
+
+        let output = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+        let line: serde_json::Value = serde_json::from_str(output.lines().next().unwrap()).unwrap();
+        assert_eq!(line["fields"]["message"], "request completed");
+        assert_eq!(line["fields"]["status"], 404);
+        let span = &line["spans"][0];
+        assert_eq!(span["name"], "http");
+        assert_eq!(span["method"], "POST");
+        assert_eq!(span["path"], "/ip");
+        // The peer is a trusted proxy, so the forwarded address is logged.
+        assert_eq!(span["client_ip"], "203.0.113.9");
+        assert_eq!(span["status"], 404);
+    }
+
+    #[cfg(feature = "otlp")]
+    #[test]
+    fn exported_span_has_server_attributes_and_remote_parent() {
+        use opentelemetry::trace::{SpanKind, Status};
+        use opentelemetry::{Key, Value};
+        use opentelemetry_sdk::trace::InMemorySpanExporter;
+        use tracing_subscriber::Registry;
+
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = Registry::default()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+
+        let config = Config {
+            otlp_endpoint: Some("http://127.0.0.1:4318".to_string()),
+            ..Config::default()
+        };
+        let request = Request::builder()
+            .uri("/ip?format=json")
+            .header(
+                "traceparent",
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            )
+            .header("user-agent", "curl/8.5.0")
+            .extension(ConnectInfo(SocketAddr::from(([198, 51, 100, 7], 40000))))
+            .body(())
+            .unwrap();
+        let response = Response::builder()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .body(())
+            .unwrap();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span =
+                RequestSpan::new(Arc::new(ArcSwap::from_pointee(config))).make_span(&request);
+            on_response(&response, Duration::from_millis(3), &span);
+        });
+
+        let spans = exporter.get_finished_spans().unwrap();
+        assert_eq!(spans.len(), 1);
+        let span = &spans[0];
+        assert_eq!(span.name, "GET");
+        assert_eq!(span.span_kind, SpanKind::Server);
+        assert!(matches!(span.status, Status::Error { .. }));
+        assert_eq!(
+            span.span_context.trace_id().to_string(),
+            "4bf92f3577b34da6a3ce929d0e0e4736"
+        );
+        assert_eq!(span.parent_span_id.to_string(), "00f067aa0ba902b7");
+        let attr = |key: &'static str| {
+            span.attributes
+                .iter()
+                .find(|kv| kv.key == Key::from_static_str(key))
+                .map(|kv| kv.value.clone())
+        };
+        assert_eq!(attr("http.request.method"), Some(Value::from("GET")));
+        assert_eq!(attr("url.path"), Some(Value::from("/ip")));
+        assert_eq!(attr("client.address"), Some(Value::from("198.51.100.7")));
+        assert_eq!(attr("network.peer.port"), Some(Value::I64(40000)));
+        assert_eq!(attr("user_agent.original"), Some(Value::from("curl/8.5.0")));
+        assert_eq!(attr("http.response.status_code"), Some(Value::I64(503)));
+    }
+}
@@ -39,7 +39,7 @@ impl IpLookupTable {
             .collect();
 
         // Sort by prefix length descending for longest-prefix match
-        entries.sort_by(|a, b| b.network.prefix_len().cmp(&a.network.prefix_len()));
+        entries.sort_by_key(|e| std::cmp::Reverse(e.network.prefix_len()));
 
         Self { entries }
     }
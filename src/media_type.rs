@@ -0,0 +1,119 @@
+//! Structured `Accept` header parsing (RFC 9110 §12.5.1), for showing a
+//! client's content negotiation rather than acting on it; response format
+//! selection is [`crate::format::ResponseFormat::negotiate`].
+
+use serde::Serialize;
+
+use crate::format::parse_qvalue;
+use crate::forwarded::{split_unquoted, unquote};
+
+/// One media range of an `Accept` header.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MediaRange {
+    /// `type/subtype` as sent, e.g. `text/html`, `image/*` or `*/*`.
+    pub media_type: String,
+    /// Parameters before `q`, names lowercased and quotes removed, in the
+    /// order sent. Extension parameters after `q` are dropped.
+    pub params: serde_json::Map<String, serde_json::Value>,
+    /// 0 to 1; 0 means "not acceptable".
+    pub q: f64,
+}
+
+impl MediaRange {
+    /// 3 for a type with parameters, 2 exact, 1 `type/*`, 0 `*/*`.
+    fn specificity(&self) -> u8 {
+        match self.media_type.split_once('/') {
+            Some(("*", _)) => 0,
+            Some((_, "*")) => 1,
+            _ if self.params.is_empty() => 2,
+            _ => 3,
+        }
+    }
+}
+
+/// The media ranges of `raw` in precedence order: highest q first, then
+/// the more specific range, then the client's order. Malformed ranges are
+/// skipped.
+pub fn parse(raw: &str) -> Vec<MediaRange> {
+    let mut ranges: Vec<MediaRange> = split_unquoted(raw, ',')
+        .into_iter()
+        .filter_map(parse_range)
+        .collect();
+    ranges.sort_by(|a, b| {
+        b.q.total_cmp(&a.q)
+            .then(b.specificity().cmp(&a.specificity()))
+    });
+    ranges
+}
+
+fn parse_range(raw: &str) -> Option<MediaRange> {
+    let mut parts = split_unquoted(raw, ';').into_iter();
+    let media_type = parts.next()?;
+    let (kind, subtype) = media_type.split_once('/')?;
+    let token = |s: &str| !s.is_empty() && !s.contains(char::is_whitespace);
+    if !token(kind) || !token(subtype) || (kind == "*" && subtype != "*") {
+        return None;
+    }
+
+    let mut params = serde_json::Map::new();
+    let mut q = 1000;
+    for param in parts {
+        let (name, value) = param.split_once('=')?;
+        let name = name.trim().to_ascii_lowercase();
+        if name == "q" {
+            q = parse_qvalue(value.trim())?;
+            break;
+        }
+        params.insert(name, unquote(value.trim()).into());
+    }
+    Some(MediaRange {
+        media_type: media_type.to_string(),
+        params,
+        q: f64::from(q) / 1000.0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn types(raw: &str) -> Vec<(String, f64)> {
+        parse(raw)
+            .into_iter()
+            .map(|r| (r.media_type, r.q))
+            .collect()
+    }
+
+    #[test]
+    fn orders_by_q_then_specificity() {
+        assert_eq!(
+            types("*/*;q=0.8, text/*, application/json;q=0.9, text/html"),
+            [
+                ("text/html".to_string(), 1.0),
+                ("text/*".to_string(), 1.0),
+                ("application/json".to_string(), 0.9),
+                ("*/*".to_string(), 0.8),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_parameters_before_q() {
+        let ranges = parse(r#"text/plain; charset="utf-8, really"; format=flowed; q=0.5; ext=1"#);
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0].media_type, "text/plain");
+        assert_eq!(ranges[0].q, 0.5);
+        assert_eq!(
+            serde_json::Value::Object(ranges[0].params.clone()),
+            serde_json::json!({"charset": "utf-8, really", "format": "flowed"})
+        );
+    }
+
+    #[test]
+    fn skips_malformed_ranges() {
+        assert_eq!(
+            types("html, */json, text/html;q=2, image/png;level, image/webp"),
+            [("image/webp".to_string(), 1.0)]
+        );
+    }
+}
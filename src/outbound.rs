@@ -0,0 +1,110 @@
+//! Guards shared by the endpoints that send traffic from the server back to
+//! the client (`/portcheck`, `/ping-me`, `/trace-me`).
+//!
+//! They only ever target the address the request came from, and only when
+//! it's public, so they can't be aimed at a third party or at the server's
+//! own network. `OUTBOUND_ALLOW_PRIVATE` relaxes that for servers that only
+//! face a LAN, but bogons (unspecified, multicast, reserved) stay off
+//! limits. Each also has a per-client quota counted in minutes rather
+//! than the request rate limit's seconds, since one request can cost
+//! several seconds of probing.
+
+use std::net::IpAddr;
+use std::num::NonZeroU32;
+
+use governor::clock::{Clock, DefaultClock};
+use governor::state::keyed::DashMapStateStore;
+use governor::{Quota, RateLimiter};
+
+use crate::addr::AddressClass;
+use crate::errors::AppError;
+use crate::ratelimit::retry_after_secs;
+
+/// Client IPs tracked before idle ones are swept out.
+const MAX_TRACKED_IPS: usize = 10_000;
+
+type Limiter = RateLimiter<IpAddr, DashMapStateStore<IpAddr>, DefaultClock>;
+
+/// `ip` in canonical form, or 403 unless it's a public address or, with
+/// `allow_private`, a private, CGNAT, link-local or loopback one.
+pub fn client_target(ip: IpAddr, allow_private: bool) -> Result<IpAddr, AppError> {
+    let ip = ip.to_canonical();
+    match AddressClass::of(ip) {
+        AddressClass::Public => Ok(ip),
+        AddressClass::Bogon => Err(AppError::Forbidden(format!(
+            "{ip} is a bogon address; it can't be probed"
+        ))),
+        _ if allow_private => Ok(ip),
+        class => Err(AppError::Forbidden(format!(
+            "{ip} is not a public address ({class:?}); only public clients can be probed"
+        ))),
+    }
+}
+
+/// A per-client-IP allowance of requests per minute.
+pub struct ClientQuota {
+    limiter: Limiter,
+}
+
+impl ClientQuota {
+    /// `per_minute` of 0 is treated as 1.
+    pub fn per_minute(per_minute: u32) -> Self {
+        let per_minute = NonZeroU32::new(per_minute).unwrap_or(NonZeroU32::MIN);
+        Self {
+            limiter: RateLimiter::keyed(Quota::per_minute(per_minute)),
+        }
+    }
+
+    /// Take one request from `ip`'s allowance, or 429 with how long to wait.
+    pub fn check(&self, ip: IpAddr) -> Result<(), AppError> {
+        let result = self.limiter.check_key(&ip).map_err(|not_until| {
+            let wait = not_until.wait_time_from(DefaultClock::default().now());
+            AppError::RateLimited {
+                retry_after_secs: retry_after_secs(wait),
+            }
+        });
+        if self.limiter.len() > MAX_TRACKED_IPS {
+            self.limiter.retain_recent();
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_public_clients() {
+        assert!(client_target("8.8.8.8".parse().unwrap(), false).is_ok());
+        let mapped = client_target("::ffff:8.8.8.8".parse().unwrap(), false).unwrap();
+        assert_eq!(mapped, "8.8.8.8".parse::<IpAddr>().unwrap());
+        for ip in ["127.0.0.1", "10.1.2.3", "fe80::1", "::ffff:192.168.0.1"] {
+            let err = client_target(ip.parse().unwrap(), false).unwrap_err();
+            assert!(matches!(err, AppError::Forbidden(_)), "{ip}");
+        }
+    }
+
+    #[test]
+    fn private_clients_when_allowed() {
+        for ip in ["127.0.0.1", "10.1.2.3", "fe80::1", "::ffff:192.168.0.1"] {
+            assert!(client_target(ip.parse().unwrap(), true).is_ok(), "{ip}");
+        }
+        for ip in ["0.0.0.0", "224.0.0.1", "::"] {
+            let err = client_target(ip.parse().unwrap(), true).unwrap_err();
+            assert!(matches!(err, AppError::Forbidden(_)), "{ip}");
+        }
+    }
+
+    #[test]
+    fn quota_is_per_client() {
+        let quota = ClientQuota::per_minute(1);
+        let a: IpAddr = "8.8.8.8".parse().unwrap();
+        assert!(quota.check(a).is_ok());
+        assert!(matches!(
+            quota.check(a),
+            Err(AppError::RateLimited { retry_after_secs }) if retry_after_secs > 0
+        ));
+        assert!(quota.check("8.8.4.4".parse().unwrap()).is_ok());
+    }
+}
@@ -0,0 +1,290 @@
+//! ICMP echo from the server to the client (`/ping-me`): latency and loss
+//! measured from the outside in, the reverse of running `ping` yourself.
+//!
+//! Sending ICMP takes either an unprivileged ICMP socket (Linux, when the
+//! server's group is within `net.ipv4.ping_group_range`) or a raw socket
+//! (`CAP_NET_RAW`); whichever opens first is used. It's off unless
+//! `PING_ENABLED`, and has the [`crate::outbound`] guards: public client
+//! addresses only (unless `OUTBOUND_ALLOW_PRIVATE`), and `PING_PER_MINUTE`
+//! runs per client.
+
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use serde::Serialize;
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::UdpSocket;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::errors::AppError;
+use crate::outbound::{ClientQuota, client_target};
+
+/// Least time between echo requests, like `ping -i 0.2`.
+const INTERVAL: Duration = Duration::from_millis(200);
+
+pub(crate) const ICMPV4_ECHO_REQUEST: u8 = 8;
+pub(crate) const ICMPV4_ECHO_REPLY: u8 = 0;
+pub(crate) const ICMPV6_ECHO_REQUEST: u8 = 128;
+pub(crate) const ICMPV6_ECHO_REPLY: u8 = 129;
+
+#[derive(Debug, Serialize)]
+pub struct PingReport {
+    pub ip: IpAddr,
+    /// `unprivileged` (an ICMP datagram socket) or `raw`.
+    pub socket: &'static str,
+    pub sent: u8,
+    pub received: u8,
+    pub loss_percent: f64,
+    /// Round-trip time of each echo request, `null` where no reply came in
+    /// time.
+    pub rtt_ms: Vec<Option<f64>>,
+    pub min_ms: Option<f64>,
+    pub avg_ms: Option<f64>,
+    pub max_ms: Option<f64>,
+}
+
+impl PingReport {
+    fn new(ip: IpAddr, socket: &'static str, rtts: Vec<Option<Duration>>) -> Self {
+        let rtt_ms: Vec<Option<f64>> = rtts
+            .iter()
+            .map(|rtt| rtt.map(|d| d.as_secs_f64() * 1000.0))
+            .collect();
+        let replies: Vec<f64> = rtt_ms.iter().flatten().copied().collect();
+        let sent = rtt_ms.len() as u8;
+        let received = replies.len() as u8;
+        Self {
+            ip,
+            socket,
+            sent,
+            received,
+            loss_percent: f64::from(sent - received) * 100.0 / f64::from(sent.max(1)),
+            min_ms: replies.iter().copied().reduce(f64::min),
+            avg_ms: (!replies.is_empty())
+                .then(|| replies.iter().sum::<f64>() / f64::from(received)),
+            max_ms: replies.iter().copied().reduce(f64::max),
+            rtt_ms,
+        }
+    }
+}
+
+pub struct Pinger {
+    enabled: bool,
+    allow_private: bool,
+    count: u8,
+    timeout: Duration,
+    quota: ClientQuota,
+}
+
+impl Pinger {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            enabled: config.ping_enabled,
+            allow_private: config.outbound_allow_private,
+            count: config.ping_count,
+            timeout: Duration::from_millis(config.ping_timeout_ms),
+            quota: ClientQuota::per_minute(config.ping_per_minute),
+        }
+    }
+
+    /// Ping `ip`, the requesting client, if config and quota allow.
+    pub async fn ping(&self, ip: IpAddr) -> Result<PingReport, AppError> {
+        if !self.enabled {
+            return Err(AppError::NotFound(
+                "pinging clients is disabled (PING_ENABLED)".to_string(),
+            ));
+        }
+        let ip = client_target(ip, self.allow_private)?;
+        self.quota.check(ip)?;
+        let socket = IcmpSocket::connect(ip).map_err(|e| {
+            tracing::warn!(error = %e, "opening an ICMP socket failed");
+            AppError::NotFound(
+                "this server can't send ICMP (needs CAP_NET_RAW or net.ipv4.ping_group_range)"
+                    .to_string(),
+            )
+        })?;
+        let rtts = socket.ping(self.count, self.timeout).await;
+        Ok(PingReport::new(ip, socket.kind, rtts))
+    }
+}
+
+/// An ICMP socket connected to one address, so it only sees that address's
+/// packets.
+struct IcmpSocket {
+    socket: UdpSocket,
+    /// `unprivileged` or `raw`.
+    kind: &'static str,
+    ipv4: bool,
+}
+
+impl IcmpSocket {
+    fn connect(ip: IpAddr) -> io::Result<Self> {
+        let (domain, protocol) = match ip {
+            IpAddr::V4(_) => (Domain::IPV4, Protocol::ICMPV4),
+            IpAddr::V6(_) => (Domain::IPV6, Protocol::ICMPV6),
+        };
+        let (socket, kind) = match Socket::new(domain, Type::DGRAM, Some(protocol)) {
+            Ok(socket) => (socket, "unprivileged"),
+            Err(_) => (Socket::new(domain, Type::RAW, Some(protocol))?, "raw"),
+        };
+        socket.set_nonblocking(true)?;
+        socket.connect(&SocketAddr::new(ip, 0).into())?;
+        Ok(Self {
+            // Send and receive on an ICMP socket are plain datagram calls.
+            socket: UdpSocket::from_std(std::net::UdpSocket::from(socket))?,
+            kind,
+            ipv4: ip.is_ipv4(),
+        })
+    }
+
+    /// Send `count` echo requests, one after another, returning each one's
+    /// round-trip time or `None` if no reply came within `timeout`.
+    async fn ping(&self, count: u8, timeout: Duration) -> Vec<Option<Duration>> {
+        // Identifies our replies on a raw socket, which sees all of them.
+        // An unprivileged socket rewrites the echo ID, but the payload
+        // comes back unchanged.
+        let token = Uuid::new_v4().as_u64_pair().0.to_be_bytes();
+        let id = u16::from_be_bytes([token[0], token[1]]);
+        let mut buf = [0u8; 1500];
+        let mut rtts = Vec::with_capacity(usize::from(count));
+        for seq in 0..u16::from(count) {
+            let sent_at = Instant::now();
+            let request = echo_request(self.ipv4, id, seq, &token);
+            if let Err(e) = self.socket.send(&request).await {
+                tracing::debug!(error = %e, "sending an ICMP echo request failed");
+                rtts.push(None);
+                continue;
+            }
+            let deadline = sent_at + timeout;
+            let rtt = loop {
+                match tokio::time::timeout_at(deadline, self.socket.recv(&mut buf)).await {
+                    Ok(Ok(n)) => {
+                        if parse_reply(&buf[..n], self.ipv4)
+                            .is_some_and(|(s, payload)| s == seq && payload == token)
+                        {
+                            break Some(sent_at.elapsed());
+                        }
+                    }
+                    // An ICMP error for the connected address, or a timeout.
+                    Ok(Err(_)) | Err(_) => break None,
+                }
+            };
+            rtts.push(rtt);
+            tokio::time::sleep_until(sent_at + INTERVAL).await;
+        }
+        rtts
+    }
+}
+
+/// An echo request carrying `payload`. The ICMPv6 checksum covers a
+/// pseudo-header, so the kernel fills that one in.
+pub(crate) fn echo_request(ipv4: bool, id: u16, seq: u16, payload: &[u8]) -> Vec<u8> {
+    let kind = if ipv4 {
+        ICMPV4_ECHO_REQUEST
+    } else {
+        ICMPV6_ECHO_REQUEST
+    };
+    let mut packet = vec![kind, 0, 0, 0];
+    packet.extend_from_slice(&id.to_be_bytes());
+    packet.extend_from_slice(&seq.to_be_bytes());
+    packet.extend_from_slice(payload);
+    if ipv4 {
+        let sum = checksum(&packet);
+        packet[2..4].copy_from_slice(&sum.to_be_bytes());
+    }
+    packet
+}
+
+/// The ICMP message in `packet`. Raw IPv4 sockets (and unprivileged ones
+/// outside Linux) deliver the IP header too.
+pub(crate) fn icmp_message(packet: &[u8], ipv4: bool) -> Option<&[u8]> {
+    match packet.first() {
+        Some(b) if ipv4 && b >> 4 == 4 => packet.get(usize::from(b & 0x0f) * 4..),
+        _ => Some(packet),
+    }
+}
+
+/// The sequence number and payload of an echo reply.
+pub(crate) fn parse_reply(packet: &[u8], ipv4: bool) -> Option<(u16, &[u8])> {
+    let icmp = icmp_message(packet, ipv4)?;
+    let reply = if ipv4 {
+        ICMPV4_ECHO_REPLY
+    } else {
+        ICMPV6_ECHO_REPLY
+    };
+    if icmp.len() < 8 || icmp[0] != reply || icmp[1] != 0 {
+        return None;
+    }
+    Some((u16::from_be_bytes([icmp[6], icmp[7]]), &icmp[8..]))
+}
+
+/// The RFC 1071 Internet checksum.
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|pair| u32::from(u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)])))
+        .sum();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn echo_request_round_trips() {
+        let request = echo_request(true, 0x1234, 7, b"token!");
+        assert_eq!(&request[..2], [ICMPV4_ECHO_REQUEST, 0]);
+        // A correct checksum sums to zero.
+        assert_eq!(checksum(&request), 0);
+
+        let mut reply = request.clone();
+        reply[0] = ICMPV4_ECHO_REPLY;
+        assert_eq!(parse_reply(&reply, true), Some((7, &b"token!"[..])));
+        // Behind a 20-byte IPv4 header, as a raw socket delivers it.
+        let mut with_header = vec![0x45];
+        with_header.resize(20, 0);
+        with_header.extend_from_slice(&reply);
+        assert_eq!(parse_reply(&with_header, true), Some((7, &b"token!"[..])));
+        // The request itself isn't a reply.
+        assert_eq!(parse_reply(&request, true), None);
+
+        let mut v6 = echo_request(false, 1, 2, b"x");
+        v6[0] = ICMPV6_ECHO_REPLY;
+        assert_eq!(parse_reply(&v6, false), Some((2, &b"x"[..])));
+    }
+
+    #[test]
+    fn report_summarizes_replies() {
+        let ms = |n| Some(Duration::from_millis(n));
+        let ip = "203.0.113.1".parse().unwrap();
+        let report = PingReport::new(ip, "raw", vec![ms(10), None, ms(30), ms(20)]);
+        assert_eq!((report.sent, report.received), (4, 3));
+        assert_eq!(report.loss_percent, 25.0);
+        assert_eq!(report.rtt_ms[1], None);
+        assert_eq!(
+            (report.min_ms, report.avg_ms, report.max_ms),
+            (Some(10.0), Some(20.0), Some(30.0))
+        );
+
+        let lost = PingReport::new(ip, "raw", vec![None, None]);
+        assert_eq!(lost.loss_percent, 100.0);
+        assert_eq!(lost.avg_ms, None);
+    }
+
+    #[tokio::test]
+    async fn pings_loopback() {
+        // Needs CAP_NET_RAW or ping_group_range, which CI may not grant.
+        let Ok(socket) = IcmpSocket::connect("127.0.0.1".parse().unwrap()) else {
+            return;
+        };
+        let rtts = socket.ping(2, Duration::from_secs(1)).await;
+        assert_eq!(rtts.len(), 2);
+        assert!(rtts.iter().all(Option::is_some), "{rtts:?}");
+    }
+}
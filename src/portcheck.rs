@@ -0,0 +1,147 @@
+//! Reverse port reachability: connect back to the client on a port it
+//! names and report whether anything answered (`/portcheck/{port}`).
+//!
+//! Making outbound connections on a client's behalf is easy to abuse, so
+//! the check is off until `PORTCHECK_PORTS` allows some ports, and it has
+//! the [`crate::outbound`] guards: public client addresses only (unless
+//! `OUTBOUND_ALLOW_PRIVATE`), and `PORTCHECK_PER_MINUTE` checks per
+//! client. Each connect gives up after `PORTCHECK_TIMEOUT_MS`.
+
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tokio::net::TcpStream;
+
+use crate::config::Config;
+use crate::errors::AppError;
+use crate::outbound::{ClientQuota, client_target};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PortState {
+    /// The connection was accepted.
+    Open,
+    /// The host answered with a reset: reachable, but nothing listening.
+    Closed,
+    /// No answer before the timeout, or an ICMP unreachable: a firewall or
+    /// NAT dropped the attempt.
+    Filtered,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PortCheck {
+    pub ip: IpAddr,
+    pub port: u16,
+    pub state: PortState,
+    /// Time to the answer, or to giving up.
+    pub elapsed_ms: u64,
+}
+
+pub struct PortChecker {
+    ports: Vec<u16>,
+    allow_private: bool,
+    timeout: Duration,
+    quota: ClientQuota,
+}
+
+impl PortChecker {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            ports: config.portcheck_ports.clone(),
+            allow_private: config.outbound_allow_private,
+            timeout: Duration::from_millis(config.portcheck_timeout_ms),
+            quota: ClientQuota::per_minute(config.portcheck_per_minute),
+        }
+    }
+
+    /// Check `port` on `ip`, the requesting client, if config and quota
+    /// allow.
+    pub async fn check(&self, ip: IpAddr, port: u16) -> Result<PortCheck, AppError> {
+        if self.ports.is_empty() {
+            return Err(AppError::NotFound(
+                "port checks are disabled (PORTCHECK_PORTS)".to_string(),
+            ));
+        }
+        if !self.ports.contains(&port) {
+            return Err(AppError::Forbidden(format!(
+                "port {port} is not in PORTCHECK_PORTS"
+            )));
+        }
+        let ip = client_target(ip, self.allow_private)?;
+        if let Err(e) = self.quota.check(ip) {
+            metrics::counter!("portcheck_total", "result" => "rate_limited").increment(1);
+            return Err(e);
+        }
+
+        let started = Instant::now();
+        let state = probe(SocketAddr::new(ip, port), self.timeout).await;
+        let label = match state {
+            PortState::Open => "open",
+            PortState::Closed => "closed",
+            PortState::Filtered => "filtered",
+        };
+        metrics::counter!("portcheck_total", "result" => label).increment(1);
+        Ok(PortCheck {
+            ip,
+            port,
+            state,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+        })
+    }
+}
+
+/// Try a TCP connect to `addr`, classifying the outcome.
+async fn probe(addr: SocketAddr, timeout: Duration) -> PortState {
+    match tokio::time::timeout(timeout, TcpStream::connect(addr)).await {
+        Ok(Ok(_)) => PortState::Open,
+        Ok(Err(e)) if e.kind() == io::ErrorKind::ConnectionRefused => PortState::Closed,
+        Ok(Err(e)) => {
+            tracing::debug!(%addr, error = %e, "port check connect failed");
+            PortState::Filtered
+        }
+        Err(_) => PortState::Filtered,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checker(ports: &[u16]) -> PortChecker {
+        PortChecker::new(&Config {
+            portcheck_ports: ports.to_vec(),
+            portcheck_per_minute: 1,
+            ..Config::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn probe_tells_open_from_closed() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let open = listener.local_addr().unwrap();
+        assert_eq!(probe(open, Duration::from_secs(1)).await, PortState::Open);
+        drop(listener);
+        assert_eq!(probe(open, Duration::from_secs(1)).await, PortState::Closed);
+    }
+
+    #[tokio::test]
+    async fn refuses_what_config_and_address_dont_allow() {
+        let public: IpAddr = "203.0.113.9".parse().unwrap();
+        let err = checker(&[]).check(public, 22).await.unwrap_err();
+        assert!(matches!(err, AppError::NotFound(_)));
+        let err = checker(&[443]).check(public, 22).await.unwrap_err();
+        assert!(matches!(err, AppError::Forbidden(_)));
+        let err = checker(&[22])
+            .check("10.1.2.3".parse().unwrap(), 22)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::Forbidden(_)));
+        let err = checker(&[22])
+            .check("::ffff:127.0.0.1".parse().unwrap(), 22)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::Forbidden(_)));
+    }
+}
@@ -0,0 +1,132 @@
+//! Dropping root privileges after binding (`--user`, `--group`).
+//!
+//! Binding ports below 1024 needs root (or `CAP_NET_BIND_SERVICE`); nothing
+//! after that does. With `--user`, the process binds its sockets as root,
+//! then permanently switches to the account's group, supplementary groups
+//! and user before accepting a connection. Anything opened later must be
+//! accessible to that account: rotated log files, the ACME cache,
+//! downloaded GeoIP databases, and the config and certificates re-read on
+//! SIGHUP.
+
+use std::ffi::CString;
+
+use nix::unistd::{self, Gid, Group, Uid, User};
+
+/// The account to switch to. Resolved at startup, before anything is
+/// bound, so a typo fails fast.
+#[derive(Debug)]
+pub struct Account {
+    /// `None` with only `--group`: keep the user, change the group.
+    uid: Option<Uid>,
+    gid: Gid,
+    /// For looking up supplementary groups; `None` for a bare user ID.
+    name: Option<CString>,
+}
+
+impl Account {
+    /// Resolve `--user` and `--group`, each a name or numeric ID. The group
+    /// defaults to the user's primary group.
+    pub fn resolve(user: Option<&str>, group: Option<&str>) -> Result<Self, String> {
+        let user = user.map(lookup_user).transpose()?;
+        let gid = match (group, &user) {
+            (Some(group), _) => lookup_group(group)?,
+            (None, Some((_, Some(entry)))) => entry.gid,
+            (None, Some((uid, None))) => {
+                return Err(format!(
+                    "user ID {uid} has no passwd entry; give --group too"
+                ));
+            }
+            (None, None) => return Err("nothing to switch to".into()),
+        };
+        let (uid, name) = match user {
+            Some((uid, entry)) => (Some(uid), entry.and_then(|e| CString::new(e.name).ok())),
+            None => (None, None),
+        };
+        Ok(Self { uid, gid, name })
+    }
+
+    /// Switch to the account for the rest of the process's life. A no-op if
+    /// already running as it; otherwise the process must be root.
+    pub fn switch(&self) -> Result<(), String> {
+        let current = (Uid::effective(), Gid::effective());
+        if current == (self.uid.unwrap_or(current.0), self.gid) {
+            return Ok(());
+        }
+        if !current.0.is_root() {
+            return Err("switching user or group requires starting as root".into());
+        }
+        let groups = match &self.name {
+            Some(name) => unistd::initgroups(name, self.gid),
+            None => unistd::setgroups(&[self.gid]),
+        };
+        groups.map_err(|e| format!("failed to set supplementary groups: {e}"))?;
+        unistd::setgid(self.gid).map_err(|e| format!("setgid({}) failed: {e}", self.gid))?;
+        if let Some(uid) = self.uid {
+            unistd::setuid(uid).map_err(|e| format!("setuid({uid}) failed: {e}"))?;
+        }
+        tracing::info!(
+            uid = %Uid::effective(),
+            gid = %Gid::effective(),
+            "dropped root privileges"
+        );
+        Ok(())
+    }
+}
+
+/// A user by name or ID, with its passwd entry if there is one.
+fn lookup_user(user: &str) -> Result<(Uid, Option<User>), String> {
+    let failed = |e| format!("failed to look up user {user:?}: {e}");
+    if let Ok(id) = user.parse() {
+        let uid = Uid::from_raw(id);
+        return Ok((uid, User::from_uid(uid).map_err(failed)?));
+    }
+    match User::from_name(user).map_err(failed)? {
+        Some(entry) => Ok((entry.uid, Some(entry))),
+        None => Err(format!("unknown user {user:?}")),
+    }
+}
+
+fn lookup_group(group: &str) -> Result<Gid, String> {
+    if let Ok(id) = group.parse() {
+        return Ok(Gid::from_raw(id));
+    }
+    match Group::from_name(group) {
+        Ok(Some(entry)) => Ok(entry.gid),
+        Ok(None) => Err(format!("unknown group {group:?}")),
+        Err(e) => Err(format!("failed to look up group {group:?}: {e}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn group_defaults_to_the_users_primary_group() {
+        let root = Account::resolve(Some("root"), None).unwrap();
+        assert_eq!(root.uid, Some(Uid::from_raw(0)));
+        assert_eq!(root.gid, Gid::from_raw(0));
+        assert!(root.name.is_some());
+
+        let group_only = Account::resolve(None, Some("1234")).unwrap();
+        assert_eq!(group_only.uid, None);
+        assert_eq!(group_only.gid, Gid::from_raw(1234));
+    }
+
+    #[test]
+    fn unknown_names_are_errors() {
+        assert!(Account::resolve(Some("no-such-user-ipecho"), None).is_err());
+        assert!(Account::resolve(Some("root"), Some("no-such-group-ipecho")).is_err());
+        assert!(Account::resolve(None, None).is_err());
+    }
+
+    #[test]
+    fn switching_to_the_current_account_is_a_no_op() {
+        let me = Account {
+            uid: Some(Uid::effective()),
+            gid: Gid::effective(),
+            name: None,
+        };
+        assert!(me.switch().is_ok());
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::HashSet;
+use std::future::Future;
+use std::pin::Pin;
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use super::{IpRangeProvider, ProviderRecord};
+
+/// Microsoft publishes the Azure service tags as a weekly file with the date
+/// in its name, so the current URL is read off the download page.
+const AZURE_DOWNLOAD_PAGE: &str = "https://www.microsoft.com/en-us/download/details.aspx?id=56519";
+
+#[derive(Debug, Deserialize)]
+struct AzureResponse {
+    values: Vec<AzureServiceTag>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AzureServiceTag {
+    properties: AzureProperties,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AzureProperties {
+    #[serde(default)]
+    region: String,
+    #[serde(default)]
+    system_service: String,
+    address_prefixes: Vec<String>,
+}
+
+pub struct AzureProvider;
+
+impl IpRangeProvider for AzureProvider {
+    fn name(&self) -> &'static str {
+        "azure"
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        client: &'a reqwest::Client,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<Vec<ProviderRecord>>> + Send + 'a>> {
+        Box::pin(self.fetch_inner(client))
+    }
+}
+
+impl AzureProvider {
+    async fn fetch_inner(&self, client: &reqwest::Client) -> anyhow::Result<Vec<ProviderRecord>> {
+        let page = client
+            .get(AZURE_DOWNLOAD_PAGE)
+            .send()
+            .await
+            .context("failed to fetch Azure service tags download page")?
+            .text()
+            .await
+            .context("failed to read Azure service tags download page")?;
+        let url = service_tags_url(&page)
+            .context("Azure service tags download link not found")?
+            .to_string();
+
+        let resp: AzureResponse = client
+            .get(&url)
+            .send()
+            .await
+            .context("failed to fetch Azure IP ranges")?
+            .json()
+            .await
+            .context("failed to parse Azure IP ranges")?;
+
+        Ok(flatten(resp))
+    }
+}
+
+/// The `ServiceTags_Public_YYYYMMDD.json` link on the download page.
+fn service_tags_url(page: &str) -> Option<&str> {
+    let name = page.find("ServiceTags_Public_")?;
+    let start = page[..name].rfind("https://")?;
+    let end = name + page[name..].find(".json")? + ".json".len();
+    let url = &page[start..end];
+    (!url.contains(['"', '\'', '<', '>', ' '])).then_some(url)
+}
+
+/// The same prefix appears under the global `AzureCloud` tag, its regional
+/// tag and any service tags; keep one record per prefix, preferring tags
+/// that name a region.
+fn flatten(resp: AzureResponse) -> Vec<ProviderRecord> {
+    let mut tags = resp.values;
+    tags.sort_by_key(|t| t.properties.region.is_empty());
+
+    let mut seen = HashSet::new();
+    let mut records = Vec::new();
+    for tag in tags {
+        let p = tag.properties;
+        let region = Some(p.region).filter(|r| !r.is_empty());
+        let service = Some(p.system_service).filter(|s| !s.is_empty());
+        for cidr in p.address_prefixes {
+            if seen.insert(cidr.clone()) {
+                records.push(ProviderRecord {
+                    provider: "azure".to_string(),
+                    cidr,
+                    region: region.clone(),
+                    service: service.clone(),
+                });
+            }
+        }
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_azure_response() {
+        let json = r#"{
+            "changeNumber": 300,
+            "cloud": "Public",
+            "values": [
+                {
+                    "name": "AzureCloud",
+                    "id": "AzureCloud",
+                    "properties": {
+                        "changeNumber": 250,
+                        "region": "",
+                        "regionId": 0,
+                        "platform": "Azure",
+                        "systemService": "",
+                        "addressPrefixes": ["13.64.0.0/16", "20.38.0.0/20", "2603:1000::/40"],
+                        "networkFeatures": ["API", "NSG"]
+                    }
+                },
+                {
+                    "name": "Storage.WestUS",
+                    "id": "Storage.WestUS",
+                    "properties": {
+                        "changeNumber": 12,
+                        "region": "westus",
+                        "regionId": 4,
+                        "platform": "Azure",
+                        "systemService": "AzureStorage",
+                        "addressPrefixes": ["13.64.0.0/16"],
+                        "networkFeatures": ["API"]
+                    }
+                }
+            ]
+        }"#;
+
+        let resp: AzureResponse = serde_json::from_str(json).unwrap();
+        let records = flatten(resp);
+
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].cidr, "13.64.0.0/16");
+        assert_eq!(records[0].region.as_deref(), Some("westus"));
+        assert_eq!(records[0].service.as_deref(), Some("AzureStorage"));
+        assert_eq!(records[1].cidr, "20.38.0.0/20");
+        assert_eq!(records[1].region, None);
+        assert_eq!(records[1].service, None);
+        assert_eq!(records[2].cidr, "2603:1000::/40");
+        assert!(records.iter().all(|r| r.provider == "azure"));
+    }
+
+    #[test]
+    fn test_service_tags_url() {
+        let page = r#"<a href="https://www.microsoft.com/en-us/">Home</a>
+            <a href="https://download.microsoft.com/download/7/1/D/71D86715-5596-4529-9B13-DA13A5DE5B63/ServiceTags_Public_20240101.json" class="mscom-link">"#;
+        assert_eq!(
+            service_tags_url(page),
+            Some(
+                "https://download.microsoft.com/download/7/1/D/71D86715-5596-4529-9B13-DA13A5DE5B63/ServiceTags_Public_20240101.json"
+            )
+        );
+        assert_eq!(service_tags_url("<html>no link</html>"), None);
+    }
+}
@@ -1,9 +1,17 @@
+#[cfg(feature = "cloud-ranges")]
 pub mod aws;
+#[cfg(feature = "cloud-ranges")]
+pub mod azure;
+#[cfg(feature = "cloud-ranges")]
 pub mod cloudflare;
+#[cfg(feature = "cloud-ranges")]
 pub mod gcp;
+#[cfg(feature = "cloud-ranges")]
 pub mod oracle;
 
+#[cfg(feature = "cloud-ranges")]
 use std::future::Future;
+#[cfg(feature = "cloud-ranges")]
 use std::pin::Pin;
 
 #[derive(Debug, Clone)]
@@ -14,6 +22,7 @@ pub struct ProviderRecord {
     pub service: Option<String>,
 }
 
+#[cfg(feature = "cloud-ranges")]
 pub trait IpRangeProvider: Send + Sync {
     fn name(&self) -> &'static str;
     fn fetch<'a>(
@@ -21,3 +30,16 @@ pub trait IpRangeProvider: Send + Sync {
         client: &'a reqwest::Client,
     ) -> Pin<Box<dyn Future<Output = anyhow::Result<Vec<ProviderRecord>>> + Send + 'a>>;
 }
+
+/// Display name of the company behind a provider, reported as
+/// `hosting_provider`.
+pub fn hosting_provider(provider: &str) -> &str {
+    match provider {
+        "aws" => "Amazon Web Services",
+        "azure" => "Microsoft Azure",
+        "cloudflare" => "Cloudflare",
+        "gcp" => "Google Cloud",
+        "oracle" => "Oracle Cloud",
+        other => other,
+    }
+}
@@ -1,47 +1,129 @@
+//! Per-client token-bucket rate limiting for the public endpoints.
+//!
+//! Buckets are keyed by the client IP after `TRUSTED_PROXIES` resolution,
+//! so clients behind a shared reverse proxy each get their own quota.
+//! Rejected requests get a 429 whose `Retry-After` says when the next
+//! request would be allowed. The quota can be changed at runtime with
+//! [`RateLimitState::set_quota`], which starts every client afresh.
+
 use std::net::IpAddr;
 use std::num::NonZeroU32;
 use std::sync::Arc;
+use std::time::Duration;
 
+use arc_swap::ArcSwap;
 use axum::body::Body;
-use axum::extract::ConnectInfo;
+use axum::extract::{ConnectInfo, State};
 use axum::http::{Request, Response, StatusCode};
 use axum::middleware::Next;
-use governor::clock::DefaultClock;
+use governor::clock::{Clock, DefaultClock};
 use governor::state::keyed::DashMapStateStore;
 use governor::{Quota, RateLimiter};
 
+use crate::client_ip::resolve_client_ip;
 use crate::errors::AppError;
+use crate::state::AppState;
+
+/// How often [`RateLimitState::evict_idle`] sweeps idle IPs out of the
+/// DashMap. 60s is a balance between memory pressure (many short-lived
+/// clients) and doing unnecessary work under steady traffic.
+const EVICTION_INTERVAL: Duration = Duration::from_secs(60);
 
 type Limiter = RateLimiter<IpAddr, DashMapStateStore<IpAddr>, DefaultClock>;
 
 #[derive(Clone)]
 pub struct RateLimitState {
-    limiter: Arc<Limiter>,
+    /// Shared by every clone, so a new quota reaches the middleware and the
+    /// eviction task alike.
+    limiter: Arc<ArcSwap<Limiter>>,
 }
 
 impl RateLimitState {
     pub fn new(per_second: u64, burst: u32) -> Self {
-        let quota = Quota::per_second(NonZeroU32::new(per_second as u32).unwrap_or(NonZeroU32::MIN))
-            .allow_burst(NonZeroU32::new(burst).unwrap_or(NonZeroU32::MIN));
-        let limiter = Arc::new(RateLimiter::keyed(quota));
+        let limiter = Arc::new(ArcSwap::from_pointee(limiter(per_second, burst)));
         Self { limiter }
     }
 
+    /// Replace the quota. Governor can't change the quota of a live
+    /// limiter, so this swaps in a fresh one and existing buckets are
+    /// forgotten.
+    pub fn set_quota(&self, per_second: u64, burst: u32) {
+        self.limiter.store(Arc::new(limiter(per_second, burst)));
+    }
+
+    /// Take a token from `ip`'s bucket, for the listeners that aren't
+    /// behind [`rate_limit_middleware`]. False when it's over quota.
+    pub fn check(&self, ip: IpAddr) -> bool {
+        self.limiter.load().check_key(&ip).is_ok()
+    }
+
     /// Evict keys whose rate-limit state has fully replenished. Without this,
     /// the DashMap grows by one entry per unique client IP and never shrinks,
     /// so long-running instances slowly leak memory.
     pub fn retain_recent(&self) {
-        self.limiter.retain_recent();
-        self.limiter.shrink_to_fit();
+        let limiter = self.limiter.load();
+        limiter.retain_recent();
+        limiter.shrink_to_fit();
+    }
+
+    /// Call [`retain_recent`](Self::retain_recent) every minute, forever,
+    /// and report the number of tracked IPs. Meant to be spawned.
+    pub async fn evict_idle(self) {
+        let mut interval = tokio::time::interval(EVICTION_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.retain_recent();
+            metrics::gauge!("rate_limit_tracked_ips").set(self.tracked_ip_count() as f64);
+        }
     }
 
     /// Current number of tracked IPs. Used for observability of the eviction
     /// loop; governor may return an estimate depending on the store.
     pub fn tracked_ip_count(&self) -> usize {
-        self.limiter.len()
+        self.limiter.load().len()
+    }
+}
+
+fn limiter(per_second: u64, burst: u32) -> Limiter {
+    let quota = Quota::per_second(NonZeroU32::new(per_second as u32).unwrap_or(NonZeroU32::MIN))
+        .allow_burst(NonZeroU32::new(burst).unwrap_or(NonZeroU32::MIN));
+    RateLimiter::keyed(quota)
+}
+
+pub async fn rate_limit_middleware(
+    ConnectInfo(addr): ConnectInfo<std::net::SocketAddr>,
+    State((rl, state)): State<(RateLimitState, Arc<AppState>)>,
+    request: Request<Body>,
+    next: Next,
+) -> Result<Response<Body>, AppError> {
+    let ip = resolve_client_ip(addr.ip(), request.headers(), &state.config.load());
+
+    match rl.limiter.load().check_key(&ip) {
+        Ok(_) => Ok(next.run(request).await),
+        Err(not_until) => {
+            metrics::counter!("rate_limit_rejected_total").increment(1);
+            let wait = not_until.wait_time_from(DefaultClock::default().now());
+            let body = serde_json::json!({
+                "error": "Too Many Requests",
+                "message": "Rate limit exceeded. Please try again later."
+            });
+            Response::builder()
+                .status(StatusCode::TOO_MANY_REQUESTS)
+                .header("content-type", "application/json")
+                .header("retry-after", retry_after_secs(wait))
+                .body(Body::from(serde_json::to_string_pretty(&body)?))
+                .map_err(|_| AppError::HttpBuilderError)
+        }
     }
 }
 
+/// Whole seconds until a request would be allowed, rounded up so a client
+/// honoring it isn't rejected again. `Retry-After: 0` would invite an
+/// immediate retry, so the minimum is 1.
+pub(crate) fn retry_after_secs(wait: Duration) -> u64 {
+    (wait.as_secs() + u64::from(wait.subsec_nanos() > 0)).max(1)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -54,9 +136,9 @@ mod tests {
 
         let a: IpAddr = "10.0.0.1".parse().unwrap();
         let b: IpAddr = "10.0.0.2".parse().unwrap();
-        let _ = rl.limiter.check_key(&a);
-        let _ = rl.limiter.check_key(&b);
-        let _ = rl.limiter.check_key(&a); // same key again — still one tracked entry
+        let _ = rl.limiter.load().check_key(&a);
+        let _ = rl.limiter.load().check_key(&b);
+        let _ = rl.limiter.load().check_key(&a); // same key again — still one tracked entry
 
         assert_eq!(rl.tracked_ip_count(), 2);
     }
@@ -68,7 +150,7 @@ mod tests {
         // and retain_recent() considers it indistinguishable from fresh.
         let rl = RateLimitState::new(1000, 1);
         let ip: IpAddr = "203.0.113.7".parse().unwrap();
-        let _ = rl.limiter.check_key(&ip);
+        let _ = rl.limiter.load().check_key(&ip);
         assert_eq!(rl.tracked_ip_count(), 1);
 
         std::thread::sleep(Duration::from_millis(100));
@@ -80,30 +162,27 @@ mod tests {
             "key with fully-replenished quota should be evicted"
         );
     }
-}
 
-pub async fn rate_limit_middleware(
-    ConnectInfo(addr): ConnectInfo<std::net::SocketAddr>,
-    axum::extract::State(rl): axum::extract::State<RateLimitState>,
-    request: Request<Body>,
-    next: Next,
-) -> Result<Response<Body>, AppError> {
-    let ip = addr.ip();
-
-    match rl.limiter.check_key(&ip) {
-        Ok(_) => Ok(next.run(request).await),
-        Err(_) => {
-            metrics::counter!("rate_limit_rejected_total").increment(1);
-            let body = serde_json::json!({
-                "error": "Too Many Requests",
-                "message": "Rate limit exceeded. Please try again later."
-            });
-            Response::builder()
-                .status(StatusCode::TOO_MANY_REQUESTS)
-                .header("content-type", "application/json")
-                .header("retry-after", "1")
-                .body(Body::from(serde_json::to_string_pretty(&body)?))
-                .map_err(|_| AppError::HttpBuilderError)
+    #[test]
+    fn set_quota_applies_to_existing_clones() {
+        let rl = RateLimitState::new(1, 1);
+        let middleware = rl.clone();
+        let ip: IpAddr = "203.0.113.8".parse().unwrap();
+        assert!(middleware.limiter.load().check_key(&ip).is_ok());
+        assert!(middleware.limiter.load().check_key(&ip).is_err());
+
+        rl.set_quota(1, 3);
+        for _ in 0..3 {
+            assert!(middleware.limiter.load().check_key(&ip).is_ok());
         }
+        assert!(middleware.limiter.load().check_key(&ip).is_err());
+    }
+
+    #[test]
+    fn retry_after_rounds_up_to_whole_seconds() {
+        assert_eq!(retry_after_secs(Duration::ZERO), 1);
+        assert_eq!(retry_after_secs(Duration::from_millis(100)), 1);
+        assert_eq!(retry_after_secs(Duration::from_secs(2)), 2);
+        assert_eq!(retry_after_secs(Duration::from_millis(2001)), 3);
     }
 }
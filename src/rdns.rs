@@ -0,0 +1,191 @@
+//! Reverse DNS (PTR) lookups for the client address.
+//!
+//! Lookups go through an async hickory resolver configured from the system
+//! resolv.conf, bounded by `RDNS_TIMEOUT_MS`. Results — including negative
+//! ones — are cached in-process for `RDNS_CACHE_TTL_SECS` so repeat visitors
+//! don't pay a DNS round trip on every request. Built without the `dns`
+//! feature, there is no resolver and every lookup comes back empty.
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+#[cfg(feature = "dns")]
+use hickory_resolver::{ResolveError, TokioResolver};
+
+use crate::config::Config;
+use crate::ttl_map::TtlMap;
+
+/// Stands in for the resolver without the `dns` feature; never constructed.
+#[cfg(not(feature = "dns"))]
+enum TokioResolver {}
+
+pub struct ReverseDns {
+    /// `None` when rDNS is disabled by config or the system resolver
+    /// configuration could not be loaded. Always `None` without the `dns`
+    /// feature.
+    resolver: Option<TokioResolver>,
+    timeout: Duration,
+    cache: TtlMap<IpAddr, Option<String>>,
+}
+
+impl ReverseDns {
+    pub fn new(config: &Config) -> Self {
+        let resolver = if config.rdns_enabled {
+            system_resolver(config)
+        } else {
+            None
+        };
+
+        Self {
+            resolver,
+            timeout: Duration::from_millis(config.rdns_timeout_ms),
+            cache: TtlMap::new(
+                Duration::from_secs(config.rdns_cache_ttl_secs),
+                config.rdns_cache_capacity,
+            ),
+        }
+    }
+
+    /// Resolve the PTR record for `ip`. Returns `None` when rDNS is disabled,
+    /// no record exists, or the lookup times out.
+    pub async fn lookup(&self, ip: IpAddr) -> Option<String> {
+        let resolver = self.resolver.as_ref()?;
+        let ip = ip.to_canonical();
+
+        if let Some(host) = self.cached(ip) {
+            metrics::counter!("rdns_lookup_total", "result" => "cache_hit").increment(1);
+            return host;
+        }
+
+        let host = match tokio::time::timeout(self.timeout, reverse_lookup(resolver, ip)).await {
+            Ok(Ok(host)) => {
+                metrics::counter!("rdns_lookup_total", "result" => "resolved").increment(1);
+                host
+            }
+            Ok(Err(e)) => {
+                tracing::debug!(%ip, error = %e, "reverse lookup failed");
+                metrics::counter!("rdns_lookup_total", "result" => "not_found").increment(1);
+                None
+            }
+            Err(_) => {
+                metrics::counter!("rdns_lookup_total", "result" => "timeout").increment(1);
+                // Don't cache timeouts: the resolver may just be slow right now.
+                return None;
+            }
+        };
+
+        self.insert(ip, host.clone());
+        host
+    }
+
+    /// Returns `Some(entry)` on a fresh cache hit, where the entry itself may
+    /// be a cached negative result.
+    fn cached(&self, ip: IpAddr) -> Option<Option<String>> {
+        self.cache.get(&ip)
+    }
+
+    fn insert(&self, ip: IpAddr, host: Option<String>) {
+        if !self.cache.ttl().is_zero() {
+            self.cache.insert(ip, host);
+        }
+    }
+}
+
+/// A resolver configured from the system resolv.conf, if it can be loaded.
+#[cfg(feature = "dns")]
+fn system_resolver(config: &Config) -> Option<TokioResolver> {
+    match TokioResolver::builder_tokio() {
+        Ok(mut builder) => {
+            let opts = builder.options_mut();
+            opts.timeout = Duration::from_millis(config.rdns_timeout_ms);
+            opts.attempts = 1;
+            Some(builder.build())
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "failed to load system resolver config; rDNS disabled");
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "dns"))]
+fn system_resolver(_config: &Config) -> Option<TokioResolver> {
+    tracing::debug!("built without the dns feature; rDNS disabled");
+    None
+}
+
+/// The first PTR name for `ip`, without the trailing dot.
+#[cfg(feature = "dns")]
+async fn reverse_lookup(
+    resolver: &TokioResolver,
+    ip: IpAddr,
+) -> Result<Option<String>, ResolveError> {
+    let lookup = resolver.reverse_lookup(ip).await?;
+    Ok(lookup
+        .iter()
+        .next()
+        .map(|ptr| ptr.0.to_utf8().trim_end_matches('.').to_string()))
+}
+
+#[cfg(not(feature = "dns"))]
+async fn reverse_lookup(
+    resolver: &TokioResolver,
+    _ip: IpAddr,
+) -> Result<Option<String>, std::convert::Infallible> {
+    match *resolver {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache_only(ttl: Duration, capacity: usize) -> ReverseDns {
+        ReverseDns {
+            resolver: None,
+            timeout: Duration::from_millis(100),
+            cache: TtlMap::new(ttl, capacity),
+        }
+    }
+
+    #[tokio::test]
+    async fn disabled_resolver_returns_none() {
+        let config = Config {
+            rdns_enabled: false,
+            ..Config::default()
+        };
+        let rdns = ReverseDns::new(&config);
+        assert!(rdns.lookup("127.0.0.1".parse().unwrap()).await.is_none());
+    }
+
+    #[test]
+    fn cache_returns_fresh_entries_including_negative() {
+        let rdns = cache_only(Duration::from_secs(60), 10);
+        let a: IpAddr = "192.0.2.1".parse().unwrap();
+        let b: IpAddr = "192.0.2.2".parse().unwrap();
+
+        rdns.insert(a, Some("host.example.com".to_string()));
+        rdns.insert(b, None);
+
+        assert_eq!(rdns.cached(a), Some(Some("host.example.com".to_string())));
+        assert_eq!(rdns.cached(b), Some(None));
+        assert_eq!(rdns.cached("192.0.2.3".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn cache_respects_capacity() {
+        let rdns = cache_only(Duration::from_secs(60), 2);
+        for i in 1..=5u8 {
+            rdns.insert(IpAddr::from([192, 0, 2, i]), None);
+        }
+        assert_eq!(rdns.cache.len(), 2);
+    }
+
+    #[test]
+    fn expired_entries_are_ignored() {
+        let rdns = cache_only(Duration::from_millis(1), 10);
+        let ip: IpAddr = "192.0.2.1".parse().unwrap();
+        rdns.insert(ip, Some("stale.example.com".to_string()));
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(rdns.cached(ip), None);
+    }
+}
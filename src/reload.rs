@@ -0,0 +1,235 @@
+//! Live configuration reload on SIGHUP.
+//!
+//! The config file, environment and flags are read again exactly as at
+//! startup. If the result is valid, the settings that don't need the
+//! listening socket rebuilt take effect for new requests and connections:
+//!
+//! - `TRUSTED_PROXIES`
+//! - `RATE_LIMIT_PER_SECOND` and `RATE_LIMIT_BURST`; every client's bucket
+//!   starts over
+//! - `GEOIP_CITY_DB`, `GEOIP_ASN_DB` and `GEOIP_IP2LOCATION_DB`; the backend
+//!   is reopened when they change
+//! - `TLS_CERT` and `TLS_KEY`; the files are read again even when the paths
+//!   are unchanged, so renewed certificates are picked up
+//!
+//! Everything else needs a restart. If the new config is invalid, or a
+//! database or certificate fails to load, nothing changes.
+
+use std::sync::Arc;
+
+#[cfg(feature = "tls")]
+use anyhow::Context;
+use arc_swap::ArcSwap;
+use tokio::task::JoinHandle;
+
+use crate::cli::Cli;
+use crate::config::Config;
+use crate::geoip::{self, SharedEnricher};
+#[cfg(feature = "tls")]
+use crate::listener::tls::TlsConfig;
+use crate::ratelimit::RateLimitState;
+use crate::state::AppState;
+
+pub struct Reloader {
+    cli: Cli,
+    config: Arc<ArcSwap<Config>>,
+    rate_limit: RateLimitState,
+    enricher: Arc<SharedEnricher>,
+    /// Refreshes the current MaxMind databases; replaced with the backend.
+    geoip_updater: Option<JoinHandle<()>>,
+    /// Only for certificates from `TLS_CERT`/`TLS_KEY`; ACME renews its own.
+    #[cfg(feature = "tls")]
+    tls: Option<TlsConfig>,
+}
+
+impl Reloader {
+    pub fn new(
+        cli: Cli,
+        state: &AppState,
+        rate_limit: RateLimitState,
+        geoip_updater: Option<JoinHandle<()>>,
+    ) -> Self {
+        Self {
+            cli,
+            config: state.config.clone(),
+            rate_limit,
+            enricher: state.enricher.clone(),
+            geoip_updater,
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+
+    /// Reload these certificates along with the config.
+    #[cfg(feature = "tls")]
+    pub fn with_tls(mut self, tls: Option<TlsConfig>) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Reload on every SIGHUP.
+    #[cfg(unix)]
+    pub async fn run(mut self) {
+        let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+            .expect("failed to install SIGHUP handler");
+        while hangup.recv().await.is_some() {
+            tracing::info!("reload: SIGHUP received");
+            self.reload().await;
+        }
+    }
+
+    /// There is no SIGHUP outside Unix; the config is fixed at startup.
+    #[cfg(not(unix))]
+    pub async fn run(self) {}
+
+    async fn reload(&mut self) {
+        let result = match self.cli.load_config() {
+            Ok(new) => self.apply(&new).await,
+            Err(e) => Err(anyhow::anyhow!("invalid configuration: {e}")),
+        };
+        match result {
+            Ok(()) => {
+                tracing::info!("configuration reloaded");
+                metrics::counter!("config_reloads_total", "result" => "success").increment(1);
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "reload failed, keeping the running configuration");
+                metrics::counter!("config_reloads_total", "result" => "error").increment(1);
+            }
+        }
+    }
+
+    /// Switch to the live settings of `new`. Everything that can fail is
+    /// done before anything is swapped in.
+    async fn apply(&mut self, new: &Config) -> anyhow::Result<()> {
+        let running = self.config.load_full();
+        let next = running.reloaded(new);
+
+        let geoip_changed = next.geoip_city_db != running.geoip_city_db
+            || next.geoip_asn_db != running.geoip_asn_db
+            || next.geoip_ip2location_db != running.geoip_ip2location_db;
+        let backend = if geoip_changed {
+            Some(geoip::from_config(&next)?)
+        } else {
+            None
+        };
+
+        #[cfg(feature = "tls")]
+        match (&self.tls, &next.tls_cert, &next.tls_key) {
+            (Some(tls), Some(cert), Some(key)) => tls
+                .reload_pem_files(cert, key)
+                .context("failed to load TLS certificate")?,
+            (Some(_), _, _) => anyhow::bail!("turning TLS off needs a restart"),
+            (None, cert, _) if *cert != running.tls_cert => {
+                anyhow::bail!("turning TLS on needs a restart")
+            }
+            (None, _, _) => {}
+        }
+        #[cfg(not(feature = "tls"))]
+        anyhow::ensure!(
+            next.tls_cert.is_none(),
+            "TLS_CERT needs a build with the tls feature"
+        );
+
+        if let Some(backend) = backend {
+            if let Some(task) = self.geoip_updater.take() {
+                task.abort();
+            }
+            let enricher = match backend {
+                Some(backend) => {
+                    let (enricher, task) = backend.start(&next).await;
+                    self.geoip_updater = task;
+                    Some(enricher)
+                }
+                None => None,
+            };
+            tracing::info!(
+                backend = enricher.as_ref().map(|e| e.name()),
+                "GeoIP backend reopened"
+            );
+            self.enricher.set(enricher);
+        }
+        if (next.rate_limit_per_second, next.rate_limit_burst)
+            != (running.rate_limit_per_second, running.rate_limit_burst)
+        {
+            self.rate_limit
+                .set_quota(next.rate_limit_per_second, next.rate_limit_burst);
+        }
+        self.config.store(Arc::new(next));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    fn reloader(config: Config) -> Reloader {
+        let rate_limit = RateLimitState::new(config.rate_limit_per_second, config.rate_limit_burst);
+        let state = AppState::new(config);
+        Reloader::new(Cli::parse_from(["ipecho"]), &state, rate_limit, None)
+    }
+
+    #[cfg(feature = "tls")]
+    fn write_pair(dir: &std::path::Path) -> (std::path::PathBuf, std::path::PathBuf) {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+        std::fs::create_dir_all(dir).unwrap();
+        let (cert_path, key_path) = (dir.join("cert.pem"), dir.join("key.pem"));
+        std::fs::write(&cert_path, cert.cert.pem()).unwrap();
+        std::fs::write(&key_path, cert.signing_key.serialize_pem()).unwrap();
+        (cert_path, key_path)
+    }
+
+    #[tokio::test]
+    async fn applies_live_settings_and_keeps_the_rest() {
+        let mut reloader = reloader(Config::default());
+        let new = Config {
+            port: 9999,
+            trusted_proxies: vec!["192.0.2.0/24".parse().unwrap()],
+            rate_limit_burst: 3,
+            ..Config::default()
+        };
+        reloader.apply(&new).await.unwrap();
+
+        let config = reloader.config.load();
+        assert_eq!(config.trusted_proxies, new.trusted_proxies);
+        assert_eq!(config.rate_limit_burst, 3);
+        assert_eq!(config.port, Config::default().port);
+    }
+
+    #[tokio::test]
+    async fn failed_geoip_load_changes_nothing() {
+        let mut reloader = reloader(Config::default());
+        let new = Config {
+            trusted_proxies: vec!["192.0.2.0/24".parse().unwrap()],
+            geoip_city_db: Some("/nonexistent/GeoLite2-City.mmdb".into()),
+            ..Config::default()
+        };
+        assert!(reloader.apply(&new).await.is_err());
+        assert_eq!(
+            reloader.config.load().trusted_proxies,
+            Config::default().trusted_proxies
+        );
+        assert!(reloader.enricher.get().is_none());
+    }
+
+    #[cfg(feature = "tls")]
+    #[tokio::test]
+    async fn tls_certificates_reload_but_cannot_be_removed() {
+        let dir = std::env::temp_dir().join(format!("ipecho-reload-{}", std::process::id()));
+        let (cert, key) = write_pair(&dir);
+        let running = Config {
+            tls_cert: Some(cert.clone()),
+            tls_key: Some(key.clone()),
+            ..Config::default()
+        };
+        let tls = TlsConfig::from_pem_files(&cert, &key, false).unwrap();
+        let mut reloader = reloader(running.clone()).with_tls(Some(tls));
+
+        write_pair(&dir);
+        reloader.apply(&running).await.unwrap();
+        assert!(reloader.apply(&Config::default()).await.is_err());
+    }
+}
@@ -6,7 +6,7 @@ use uuid::Uuid;
 
 /// Header name for request correlation. Lowercase per HTTP/2 rules; axum
 /// normalizes regardless, but keeping it lowercase avoids allocations.
-const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
 
 /// Reuse any inbound `x-request-id` header so callers (e.g. an ingress or
 /// upstream proxy) can correlate their traces with ours. Otherwise generate a
@@ -0,0 +1,156 @@
+//! Resolver detection across HTTP and DNS (`/resolver-check`).
+//!
+//! `/resolver-check` hands the client a fresh name, `<token>.<DNS_ZONE>`.
+//! Resolving it sends the client's recursive resolver to the `DNS_ADDR`
+//! server, which records where each query came from and what it carried:
+//! the EDNS Client Subnet the resolver passed on, and whether it set the
+//! DNSSEC OK bit. `/resolver-check/{token}` then reports those queries.
+//! Only the lookup matters, so the client needn't connect to the address
+//! it gets back.
+//!
+//! Needs `DNS_ADDR`; names are valid for five minutes (see
+//! [`crate::session`]).
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::errors::AppError;
+use crate::session::SessionStore;
+
+const TTL: Duration = Duration::from_secs(300);
+
+/// Most checks in flight at once.
+const CAPACITY: usize = 10_000;
+
+/// Queries kept per name. Resolvers ask for several record types, and a
+/// pool may send one query per member.
+const MAX_QUERIES: usize = 16;
+
+/// One query for a check's name, as the DNS server saw it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResolverQuery {
+    /// The address the query came from: the resolver's egress address.
+    pub resolver: IpAddr,
+    /// EDNS Client Subnet the resolver sent on the client's behalf, e.g.
+    /// `203.0.113.0/24`; `null` when it sent none.
+    pub ecs: Option<String>,
+    /// The DO bit: the resolver asked for DNSSEC records, as a validating
+    /// resolver does.
+    pub dnssec_ok: bool,
+    /// `udp` or `tcp`.
+    pub transport: &'static str,
+    /// The record type asked for, e.g. `A`.
+    #[serde(rename = "type")]
+    pub query_type: String,
+}
+
+pub struct ResolverCheck {
+    /// `DNS_ZONE`, when `DNS_ADDR` serves it; `None` disables the check.
+    zone: Option<String>,
+    sessions: SessionStore<Vec<ResolverQuery>>,
+}
+
+impl ResolverCheck {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            zone: config.dns_addr.and(config.dns_zone.clone()),
+            sessions: SessionStore::new(TTL, CAPACITY),
+        }
+    }
+
+    fn zone(&self) -> Result<&str, AppError> {
+        self.zone.as_deref().ok_or_else(|| {
+            AppError::NotFound("resolver checks need the DNS server (DNS_ADDR)".to_string())
+        })
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.sessions.ttl()
+    }
+
+    /// Start a check, returning its token.
+    pub fn start(&self) -> Result<String, AppError> {
+        self.zone()?;
+        Ok(self.sessions.start())
+    }
+
+    /// The name to resolve for `token`.
+    pub fn hostname(&self, token: &str) -> Result<String, AppError> {
+        Ok(format!("{token}.{}", self.zone()?))
+    }
+
+    /// Record a query for `token`'s name. Returns whether the token is
+    /// live, so the DNS server can answer NXDOMAIN for the rest.
+    pub fn record(&self, token: &str, query: ResolverQuery) -> bool {
+        self.sessions
+            .update(token, |queries| {
+                if queries.len() < MAX_QUERIES {
+                    queries.push(query);
+                }
+            })
+            .is_some()
+    }
+
+    /// The queries seen for `token` so far, oldest first.
+    pub fn queries(&self, token: &str) -> Result<Vec<ResolverQuery>, AppError> {
+        self.zone()?;
+        self.sessions.get(token).ok_or_else(|| {
+            AppError::NotFound("unknown or expired resolver check token".to_string())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(resolver: &str) -> ResolverQuery {
+        ResolverQuery {
+            resolver: resolver.parse().unwrap(),
+            ecs: None,
+            dnssec_ok: true,
+            transport: "udp",
+            query_type: "A".to_string(),
+        }
+    }
+
+    #[test]
+    fn needs_the_dns_server() {
+        let check = ResolverCheck::new(&Config {
+            dns_zone: Some("whoami.example.com".to_string()),
+            ..Config::default()
+        });
+        assert!(matches!(check.start(), Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn records_queries_for_live_tokens() {
+        let check = ResolverCheck::new(&Config {
+            dns_addr: Some("127.0.0.1:5353".parse().unwrap()),
+            dns_zone: Some("whoami.example.com".to_string()),
+            ..Config::default()
+        });
+        let token = check.start().unwrap();
+        assert_eq!(
+            check.hostname(&token).unwrap(),
+            format!("{token}.whoami.example.com")
+        );
+        assert_eq!(check.queries(&token).unwrap(), []);
+
+        for _ in 0..MAX_QUERIES + 1 {
+            assert!(check.record(&token, query("198.51.100.53")));
+        }
+        let queries = check.queries(&token).unwrap();
+        assert_eq!(queries.len(), MAX_QUERIES);
+        assert_eq!(queries[0], query("198.51.100.53"));
+
+        assert!(!check.record("unknown", query("198.51.100.53")));
+        assert!(matches!(
+            check.queries("unknown"),
+            Err(AppError::NotFound(_))
+        ));
+    }
+}
@@ -1,42 +1,255 @@
 use std::sync::Arc;
+use std::time::Duration;
 
-use axum::routing::get;
+use axum::extract::DefaultBodyLimit;
+use axum::routing::{any, get, post};
 use axum::Router;
 use tower_http::trace::TraceLayer;
 
-use crate::handlers::{echo, health, metrics};
+use crate::access_log::access_log_middleware;
+#[cfg(feature = "compression")]
+use crate::compression::compression_layer;
+use crate::cors::cors_layer;
+use crate::handlers::{
+    anything, body, cache, client, cookies, data, delay, dual_stack, echo, fetch_metadata, fields,
+    health, hints, ping, portcheck, privacy, raw, redirect, resolver_check, sse, time, traceroute,
+    uuid,
+};
+#[cfg(feature = "auth")]
+use crate::handlers::auth;
+#[cfg(feature = "compressed")]
+use crate::handlers::compressed;
+#[cfg(feature = "encoding")]
+use crate::handlers::encoding;
+#[cfg(feature = "fingerprint")]
+use crate::handlers::fingerprint;
+#[cfg(feature = "hash")]
+use crate::handlers::hash;
+#[cfg(feature = "jwt")]
+use crate::handlers::jwt;
+#[cfg(feature = "metrics")]
+use crate::handlers::metrics;
+#[cfg(unix)]
+use crate::handlers::{mtu, rtt};
+#[cfg(feature = "speedtest")]
+use crate::handlers::speedtest;
+#[cfg(feature = "tls")]
+use crate::handlers::tls;
+#[cfg(feature = "ws")]
+use crate::handlers::ws;
+use crate::http_metrics::http_metrics_middleware;
+use crate::https_redirect::https_redirect_middleware;
+#[cfg(feature = "speedtest")]
+use crate::limits::UPLOAD_PATH;
+use crate::limits::{SizeLimits, size_limit_middleware};
+use crate::load_shed::{LoadShed, load_shed_middleware};
+use crate::logging::{RequestSpan, on_response};
 use crate::ratelimit::{RateLimitState, rate_limit_middleware};
 use crate::request_id::request_id_middleware;
+use crate::security_headers::{SecurityHeaders, security_headers_middleware};
 use crate::state::AppState;
+use crate::timeout::request_timeout_middleware;
 
 /// Wire up all routes and middleware. `rl_state` is owned by the caller so
 /// a background task can periodically evict idle entries (see `main.rs`).
 pub fn create_router(state: AppState, rl_state: RateLimitState) -> Router {
     let shared_state = Arc::new(state.clone());
+    // Settings that only apply at startup; see `reload` for the rest.
+    let config = state.config.load_full();
+    let trace = TraceLayer::new_for_http()
+        .make_span_with(RequestSpan::new(state.config.clone()))
+        .on_response(on_response);
 
     // Rate-limited routes (public echo endpoints)
     let rate_limited = Router::new()
         .route("/", get(echo::echo_handler))
-        .route("/ip", get(echo::ip_handler))
-        .route("/provider", get(echo::provider_handler))
-        .route("/region", get(echo::region_handler))
-        .route("/service", get(echo::service_handler))
+        .route("/all.{format}", get(echo::echo_with_extension_handler))
         .route("/headers", get(echo::headers_handler))
+        .route("/headers.json", get(echo::headers_json_handler))
         .route("/headers/{name}", get(echo::header_by_name_handler))
+        .route("/forwarded", get(echo::forwarded_handler))
+        .route("/forwarded.json", get(echo::forwarded_json_handler))
+        .route("/geo", get(echo::geo_handler))
+        .route("/asn", get(echo::asn_handler))
+        .route("/ipv6/info", get(echo::ipv6_info_handler))
+        .route("/cidr/{*prefix}", get(echo::cidr_handler))
+        .route("/raw", any(raw::raw_handler))
+        .route("/client", get(client::client_handler))
+        .route("/hints", get(hints::hints_handler))
+        .route("/fetch-metadata", get(fetch_metadata::fetch_metadata_handler))
+        .route("/privacy", get(privacy::privacy_handler))
+        .route("/portcheck/{port}", get(portcheck::portcheck_handler))
+        .route("/ping-me", get(ping::ping_me_handler))
+        .route("/trace-me", get(traceroute::trace_me_handler))
+        .route("/ds/start", get(dual_stack::start_handler))
+        .route("/ds/{token}", get(dual_stack::record_handler))
+        .route("/ds/{token}/result", get(dual_stack::result_handler))
+        .route("/resolver-check", get(resolver_check::start_handler))
+        .route("/resolver-check/{token}", get(resolver_check::result_handler))
+        .route("/stream/{n}", get(data::stream_handler))
+        .route("/drip", get(data::drip_handler))
+        .route("/cache/{n}", get(cache::cache_handler))
+        .route("/cookies", get(cookies::cookies_handler))
+        .route("/cookies/set", get(cookies::set_cookies_handler))
+        .route("/cookies/delete", get(cookies::delete_cookies_handler))
+        .route("/delay/{n}", get(delay::delay_handler))
+        .route("/get", get(anything::get_handler))
+        .route("/echo", post(body::echo_body_handler).put(body::echo_body_handler))
+        .route("/redirect/{n}", get(redirect::redirect_handler))
+        .route("/redirect-to", get(redirect::redirect_to_handler))
+        .route("/sse", get(sse::sse_handler))
+        .route("/uuid", get(uuid::uuid_handler))
+        .route("/time", get(time::time_handler))
+        .route("/clock-skew", get(time::clock_skew_handler))
+        .merge(fields::routes());
+    #[cfg(feature = "anything")]
+    let rate_limited = rate_limited
+        .route("/anything", any(anything::anything_handler))
+        .route("/anything/{*path}", any(anything::anything_handler));
+    #[cfg(feature = "auth")]
+    let rate_limited = rate_limited
+        .route("/basic-auth/{user}/{passwd}", get(auth::basic_auth_handler))
+        .route("/bearer", get(auth::bearer_handler))
+        .route(
+            "/digest-auth/{qop}/{user}/{passwd}",
+            get(auth::digest_auth_handler),
+        )
+        .route(
+            "/digest-auth/{qop}/{user}/{passwd}/{algorithm}",
+            get(auth::digest_auth_handler),
+        );
+    #[cfg(feature = "compressed")]
+    let rate_limited = rate_limited
+        .route("/gzip", get(compressed::gzip_handler))
+        .route("/deflate", get(compressed::deflate_handler))
+        .route("/brotli", get(compressed::brotli_handler));
+    #[cfg(feature = "encoding")]
+    let rate_limited = rate_limited
+        .route("/base64/encode", post(encoding::encode_body_handler))
+        .route("/base64/encode/{*value}", get(encoding::encode_path_handler))
+        .route("/base64/decode", post(encoding::decode_body_handler))
+        .route("/base64/decode/{*value}", get(encoding::decode_path_handler));
+    #[cfg(feature = "fingerprint")]
+    let rate_limited = rate_limited
+        .route(
+            "/fingerprint/http",
+            get(fingerprint::http_fingerprint_handler),
+        )
+        .route(
+            "/fingerprint/tls",
+            get(fingerprint::tls_fingerprint_handler),
+        );
+    #[cfg(feature = "hash")]
+    let rate_limited = rate_limited.route("/hash/{algo}", post(hash::hash_handler));
+    #[cfg(feature = "jwt")]
+    let rate_limited = rate_limited.route("/jwt", get(jwt::jwt_handler).post(jwt::jwt_handler));
+    #[cfg(feature = "random-data")]
+    let rate_limited = rate_limited
+        .route("/bytes/{n}", get(data::bytes_handler))
+        .route("/pad/{n}", get(data::pad_handler));
+    #[cfg(feature = "speedtest")]
+    let rate_limited = rate_limited
+        .route(
+            "/speedtest/download/{size}",
+            get(speedtest::download_handler),
+        )
+        .route(
+            UPLOAD_PATH,
+            post(speedtest::upload_handler).put(speedtest::upload_handler),
+        );
+    #[cfg(feature = "ws")]
+    let rate_limited = rate_limited.route("/ws", get(ws::ws_handler));
+    #[cfg(feature = "tls")]
+    let rate_limited = rate_limited
+        .route("/tls", get(tls::tls_handler))
+        .route("/cert", get(tls::cert_handler));
+    #[cfg(unix)]
+    let rate_limited = rate_limited
+        .route("/mtu", get(mtu::mtu_handler))
+        .route("/rtt", get(rtt::rtt_handler));
+    let mut rate_limited = rate_limited
         .route_layer(axum::middleware::from_fn_with_state(
-            rl_state,
+            (rl_state, shared_state.clone()),
             rate_limit_middleware,
-        ))
-        .with_state(shared_state.clone());
+        ));
+    // Shed excess load before doing any per-request work.
+    if let Some(shed) = LoadShed::new(config.max_concurrent_requests) {
+        rate_limited = rate_limited.route_layer(axum::middleware::from_fn_with_state(
+            shed,
+            load_shed_middleware,
+        ));
+    }
+    let rate_limited = rate_limited.with_state(shared_state.clone());
 
-    // Non-rate-limited routes (health, metrics)
+    // Non-rate-limited routes (health, metrics). With a separate admin
+    // listener, /metrics is only served there.
     let internal = Router::new()
         .route("/health", get(health::health_handler))
-        .route("/metrics", get(metrics::metrics_handler))
-        .with_state(shared_state);
+        .route("/healthz", get(health::healthz_handler))
+        .route("/readyz", get(health::readyz_handler));
+    #[cfg(feature = "metrics")]
+    let internal = match config.metrics_addr {
+        None => internal.route("/metrics", get(metrics::metrics_handler)),
+        Some(_) => internal,
+    };
+    let internal = internal.with_state(shared_state.clone());
+
+    let mut router = rate_limited.merge(internal);
+    if state.access_log.is_some() {
+        router = router.layer(axum::middleware::from_fn_with_state(
+            shared_state,
+            access_log_middleware,
+        ));
+    }
+    if config.request_timeout_secs > 0 {
+        router = router.layer(axum::middleware::from_fn_with_state(
+            Duration::from_secs(config.request_timeout_secs),
+            request_timeout_middleware,
+        ));
+    }
+    let mut router = router
+        .layer(DefaultBodyLimit::max(config.max_body_bytes))
+        .layer(axum::middleware::from_fn_with_state(
+            SizeLimits::from_config(&config),
+            size_limit_middleware,
+        ));
+    if config.all_listeners().iter().any(|l| l.redirect_https.is_some()) {
+        router = router.layer(axum::middleware::from_fn_with_state(
+            config.https_redirect_exempt.clone().into(),
+            https_redirect_middleware,
+        ));
+    }
+    // Inside the metrics so response sizes are counted as sent.
+    #[cfg(feature = "compression")]
+    if let Some(compression) = compression_layer(&config) {
+        router = router.layer(compression);
+    }
+    let mut router = router.layer(axum::middleware::from_fn(http_metrics_middleware));
+    // Outside the limits so error responses get the headers too.
+    if let Some(security) = SecurityHeaders::from_config(&config) {
+        router = router.layer(axum::middleware::from_fn_with_state(
+            security,
+            security_headers_middleware,
+        ));
+    }
+    let router = router.layer(axum::middleware::from_fn(request_id_middleware));
+    // Outside everything else so preflights skip rate limiting and error
+    // responses carry CORS headers too.
+    let router = match cors_layer(&config) {
+        Some(cors) => router.layer(cors),
+        None => router,
+    };
+    // Added after every layer, so none of them run for it.
+    router
+        .layer(trace)
+        .route("/ping", get(ping::ping_handler))
+}
 
-    rate_limited
-        .merge(internal)
-        .layer(axum::middleware::from_fn(request_id_middleware))
-        .layer(TraceLayer::new_for_http())
+/// Routes for the admin listener on `METRICS_ADDR`: `/metrics` only, without
+/// rate limiting or request metrics of its own.
+#[cfg(feature = "metrics")]
+pub fn admin_router(state: AppState) -> Router {
+    Router::new()
+        .route("/metrics", get(metrics::metrics_handler))
+        .with_state(Arc::new(state))
 }
@@ -0,0 +1,147 @@
+//! Browser hardening headers (`SECURITY_HEADERS`).
+//!
+//! Off by default, since a proxy in front often sets its own. When on, every
+//! response gets `X-Content-Type-Options: nosniff`, `Referrer-Policy` and,
+//! unless `HSTS_MAX_AGE_SECS` is 0, `Strict-Transport-Security`. HTML
+//! responses (the landing page) also get `Content-Security-Policy`; the
+//! data endpoints are never rendered as documents, so it would only be
+//! noise there. Headers a handler already set are left alone.
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{HeaderValue, Request, Response, header};
+use axum::middleware::Next;
+
+use crate::config::Config;
+
+/// Header values sent by [`security_headers_middleware`].
+#[derive(Debug, Clone)]
+pub struct SecurityHeaders {
+    hsts: Option<HeaderValue>,
+    referrer_policy: HeaderValue,
+    content_security_policy: Option<HeaderValue>,
+}
+
+impl SecurityHeaders {
+    /// `None` unless `SECURITY_HEADERS` is enabled. Values were validated
+    /// when the config was loaded.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        if !config.security_headers {
+            return None;
+        }
+        Some(Self {
+            hsts: (config.hsts_max_age_secs > 0).then(|| {
+                HeaderValue::from_str(&format!("max-age={}", config.hsts_max_age_secs))
+                    .expect("digits are a valid header value")
+            }),
+            referrer_policy: HeaderValue::from_str(&config.referrer_policy).ok()?,
+            content_security_policy: Some(&config.content_security_policy)
+                .filter(|csp| !csp.is_empty())
+                .and_then(|csp| HeaderValue::from_str(csp).ok()),
+        })
+    }
+}
+
+pub async fn security_headers_middleware(
+    State(security): State<SecurityHeaders>,
+    request: Request<Body>,
+    next: Next,
+) -> Response<Body> {
+    let mut response = next.run(request).await;
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/html"));
+    let headers = response.headers_mut();
+    headers
+        .entry(header::X_CONTENT_TYPE_OPTIONS)
+        .or_insert(HeaderValue::from_static("nosniff"));
+    headers
+        .entry(header::REFERRER_POLICY)
+        .or_insert(security.referrer_policy);
+    if let Some(hsts) = security.hsts {
+        headers
+            .entry(header::STRICT_TRANSPORT_SECURITY)
+            .or_insert(hsts);
+    }
+    if is_html && let Some(csp) = security.content_security_policy {
+        headers
+            .entry(header::CONTENT_SECURITY_POLICY)
+            .or_insert(csp);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::Router;
+    use axum::response::Html;
+    use axum::routing::get;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn app(config: &Config) -> Router {
+        Router::new()
+            .route("/", get(|| async { Html("<p>hi</p>") }))
+            .route("/ip", get(|| async { "203.0.113.5" }))
+            .layer(axum::middleware::from_fn_with_state(
+                SecurityHeaders::from_config(config).unwrap(),
+                security_headers_middleware,
+            ))
+    }
+
+    async fn get_headers(config: &Config, uri: &str) -> axum::http::HeaderMap {
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        app(config)
+            .oneshot(request)
+            .await
+            .unwrap()
+            .headers()
+            .clone()
+    }
+
+    fn enabled() -> Config {
+        Config {
+            security_headers: true,
+            ..Config::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn html_gets_csp_and_everything_gets_the_rest() {
+        let html = get_headers(&enabled(), "/").await;
+        assert_eq!(html[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert_eq!(html[header::REFERRER_POLICY], "no-referrer");
+        assert_eq!(html[header::STRICT_TRANSPORT_SECURITY], "max-age=31536000");
+        assert!(
+            html[header::CONTENT_SECURITY_POLICY]
+                .to_str()
+                .unwrap()
+                .starts_with("default-src 'none'")
+        );
+
+        let text = get_headers(&enabled(), "/ip").await;
+        assert_eq!(text[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert!(!text.contains_key(header::CONTENT_SECURITY_POLICY));
+    }
+
+    #[tokio::test]
+    async fn zero_max_age_and_empty_csp_omit_headers() {
+        let config = Config {
+            hsts_max_age_secs: 0,
+            content_security_policy: String::new(),
+            ..enabled()
+        };
+        let headers = get_headers(&config, "/").await;
+        assert!(!headers.contains_key(header::STRICT_TRANSPORT_SECURITY));
+        assert!(!headers.contains_key(header::CONTENT_SECURITY_POLICY));
+        assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+    }
+
+    #[test]
+    fn disabled_by_default() {
+        assert!(SecurityHeaders::from_config(&Config::default()).is_none());
+    }
+}
@@ -0,0 +1,311 @@
+//! The `ipecho` binary: everything between parsing the flags and shutting
+//! down. To mount the endpoints in another axum application instead, see
+//! [`router`](crate::router).
+
+use std::sync::atomic::Ordering;
+#[cfg(feature = "metrics")]
+use std::time::Duration;
+
+#[cfg(feature = "tls")]
+use crate::acme;
+#[cfg(feature = "dns")]
+use crate::dns_server;
+#[cfg(unix)]
+use crate::privileges;
+#[cfg(feature = "cloud-ranges")]
+use crate::sync;
+use crate::{
+    access_log, cli, geoip, https_redirect, listener, logging, ratelimit, reload, routes, state,
+    tcp_echo, udp_echo,
+};
+#[cfg(feature = "metrics")]
+use crate::{http_metrics, statsd};
+
+/// How often the Prometheus recorder drains buffered histogram samples, the
+/// same as its own `install_recorder` default.
+#[cfg(feature = "metrics")]
+const METRICS_UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Load the configuration, bind the listeners and serve until SIGINT or
+/// SIGTERM.
+pub async fn run(cli: cli::Cli) -> anyhow::Result<()> {
+    let config = cli
+        .load_config()
+        .map_err(|e| anyhow::anyhow!("invalid configuration: {e}"))?;
+    // Resolved now so a typo fails before anything is bound.
+    #[cfg(unix)]
+    let account = match (&cli.user, &cli.group) {
+        (None, None) => None,
+        (user, group) => Some(
+            privileges::Account::resolve(user.as_deref(), group.as_deref())
+                .map_err(|e| anyhow::anyhow!("invalid --user/--group: {e}"))?,
+        ),
+    };
+    #[cfg(not(unix))]
+    if cli.user.is_some() || cli.group.is_some() {
+        anyhow::bail!("--user and --group are only supported on Unix");
+    }
+
+    #[cfg(not(feature = "metrics"))]
+    if config.metrics_addr.is_some() || config.statsd_addr.is_some() {
+        anyhow::bail!("METRICS_ADDR and STATSD_ADDR need a build with the metrics feature");
+    }
+    #[cfg(not(feature = "dns"))]
+    if config.dns_addr.is_some() {
+        anyhow::bail!("DNS_ADDR needs a build with the dns feature");
+    }
+    #[cfg(not(feature = "tls"))]
+    if config.tls_cert.is_some() || !config.acme_domains.is_empty() {
+        anyhow::bail!("TLS_CERT and ACME_DOMAINS need a build with the tls feature");
+    }
+
+    #[cfg(not(feature = "compression"))]
+    if config.compression {
+        anyhow::bail!("COMPRESSION needs a build with the compression feature");
+    }
+    #[cfg(not(feature = "log-shipping"))]
+    if config.log_file.is_some() || config.syslog_addr.is_some() {
+        anyhow::bail!("LOG_FILE and SYSLOG_ADDR need a build with the log-shipping feature");
+    }
+    #[cfg(not(feature = "otlp"))]
+    if config.otlp_endpoint.is_some() {
+        anyhow::bail!("OTLP_ENDPOINT needs a build with the otlp feature");
+    }
+
+    let telemetry = logging::init(&config)?;
+
+    #[cfg(feature = "metrics")]
+    let metrics_handle = install_metrics(&config).await?;
+
+    let mut enricher = None;
+    let mut geoip_updater = None;
+    if let Some(backend) = geoip::from_config(&config)? {
+        tracing::info!(backend = backend.enricher.name(), "IP enrichment enabled");
+        // Refreshes before serving, so a fresh deployment starts with
+        // current data.
+        let (backend, updater) = backend.start(&config).await;
+        enricher = Some(backend);
+        geoip_updater = updater;
+    }
+
+    let access_log = config
+        .access_log
+        .as_deref()
+        .map(|path| {
+            access_log::AccessLog::open(path)
+                .map_err(|e| anyhow::anyhow!("failed to open ACCESS_LOG {}: {e}", path.display()))
+        })
+        .transpose()?;
+
+    let state = state::AppState::new(config.clone());
+    #[cfg(feature = "metrics")]
+    let state = state.with_metrics_handle(metrics_handle);
+    let state = state.with_enricher(enricher).with_access_log(access_log);
+
+    #[cfg(feature = "cloud-ranges")]
+    tokio::spawn(sync::scheduler::start_sync_loop(state.clone()));
+
+    let rl_state =
+        ratelimit::RateLimitState::new(config.rate_limit_per_second, config.rate_limit_burst);
+
+    tokio::spawn(rl_state.clone().evict_idle());
+
+    let listener_bound = state.listener_bound.clone();
+    #[cfg(feature = "metrics")]
+    if let Some(addr) = config.metrics_addr {
+        let admin = tokio::net::TcpListener::bind(addr)
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to bind METRICS_ADDR {addr}: {e}"))?;
+        tracing::info!("serving /metrics on {addr}");
+        let router = routes::admin_router(state.clone());
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(admin, router).await {
+                tracing::error!(error = %e, "metrics listener failed");
+            }
+        });
+    }
+
+    let mut tcp_echo_listener = None;
+    if let Some(addr) = config.tcp_echo_addr {
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to bind TCP_ECHO_ADDR {addr}: {e}"))?;
+        tracing::info!("TCP echo on {addr}");
+        tcp_echo_listener = Some(listener);
+    }
+    let mut udp_echo_socket = None;
+    if let Some(addr) = config.udp_echo_addr {
+        let socket = tokio::net::UdpSocket::bind(addr)
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to bind UDP_ECHO_ADDR {addr}: {e}"))?;
+        tracing::info!("UDP echo on {addr}");
+        udp_echo_socket = Some(socket);
+    }
+    #[cfg(feature = "dns")]
+    let mut dns = None;
+    #[cfg(feature = "dns")]
+    if let (Some(addr), Some(zone)) = (config.dns_addr, &config.dns_zone) {
+        let rate_limit = ratelimit::RateLimitState::new(
+            config.dns_rate_limit.into(),
+            config.dns_rate_limit,
+        );
+        let server = dns_server::DnsServer::new(zone, rate_limit, state.resolver_check.clone())?;
+        let socket = tokio::net::UdpSocket::bind(addr)
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to bind DNS_ADDR {addr} (UDP): {e}"))?;
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to bind DNS_ADDR {addr} (TCP): {e}"))?;
+        tracing::info!("DNS for {zone} on {addr}");
+        dns = Some((server, socket, listener));
+    }
+
+    #[cfg(feature = "tls")]
+    let tls = match (&config.tls_cert, &config.tls_key) {
+        (Some(cert), Some(key)) => Some(
+            listener::tls::TlsConfig::from_pem_files(cert, key, config.tls_client_auth)
+                .map_err(|e| anyhow::anyhow!("invalid TLS configuration: {e}"))?,
+        ),
+        _ if !config.acme_domains.is_empty() => {
+            tracing::info!(domains = ?config.acme_domains, "ACME enabled");
+            Some(acme::start(&config)?)
+        }
+        _ => None,
+    };
+
+    let reloader = reload::Reloader::new(cli, &state, rl_state.clone(), geoip_updater);
+    #[cfg(feature = "tls")]
+    let reloader = reloader.with_tls(tls.clone().filter(|_| config.tls_cert.is_some()));
+    tokio::spawn(reloader.run());
+
+    let live_config = state.config.clone();
+    let app = routes::create_router(state, rl_state.clone());
+
+    let listeners = listener::bind_all(&config)?;
+    #[cfg(unix)]
+    if let Some(account) = account {
+        account
+            .switch()
+            .map_err(|e| anyhow::anyhow!("failed to drop privileges: {e}"))?;
+    }
+    listener_bound.store(true, Ordering::Relaxed);
+    let half_open = listener::half_open::HalfOpenTracker::new(config.max_half_open_per_ip);
+    let mut servers = tokio::task::JoinSet::new();
+    if let Some(listener) = tcp_echo_listener {
+        servers.spawn(tcp_echo::serve(
+            listener,
+            rl_state.clone(),
+            shutdown_signal(),
+        ));
+    }
+    if let Some(socket) = udp_echo_socket {
+        servers.spawn(udp_echo::serve(socket, rl_state.clone(), shutdown_signal()));
+    }
+    #[cfg(feature = "dns")]
+    if let Some((server, socket, listener)) = dns {
+        servers.spawn(server.clone().serve_udp(socket, shutdown_signal()));
+        servers.spawn(server.serve_tcp(listener, shutdown_signal()));
+    }
+    for (listener, settings) in listeners {
+        let app = match settings.redirect_https {
+            Some(port) => app
+                .clone()
+                .layer(axum::Extension(https_redirect::HttpsRedirect { port })),
+            None => app.clone(),
+        };
+        let options = listener::ConnectionOptions {
+            #[cfg(feature = "tls")]
+            tls: tls.clone().filter(|_| settings.tls),
+            proxy_protocol: settings.proxy_protocol,
+            half_open: half_open.clone(),
+        };
+        tracing::info!(
+            tls = settings.tls,
+            proxy_protocol = options.proxy_protocol,
+            ipv6_only = settings.ipv6_only,
+            redirect_https = settings.redirect_https,
+            "listening on {listener}"
+        );
+        servers.spawn(listener::serve(
+            listener,
+            app,
+            live_config.clone(),
+            options,
+            shutdown_signal(),
+        ));
+    }
+    servers.join_all().await;
+
+    tracing::info!("shutdown complete");
+    telemetry.shutdown();
+    Ok(())
+}
+
+/// Install the global metrics recorder: Prometheus, plus StatsD with
+/// `STATSD_ADDR`. Returns the handle `/metrics` renders.
+#[cfg(feature = "metrics")]
+async fn install_metrics(
+    config: &crate::config::Config,
+) -> anyhow::Result<metrics_exporter_prometheus::PrometheusHandle> {
+    let prometheus = http_metrics::prometheus_builder()
+        .expect("invalid histogram buckets")
+        .build_recorder();
+    let metrics_handle = prometheus.handle();
+    match &config.statsd_addr {
+        Some(addr) => {
+            let target = tokio::net::lookup_host(addr)
+                .await
+                .ok()
+                .and_then(|mut addrs| addrs.next())
+                .ok_or_else(|| anyhow::anyhow!("failed to resolve STATSD_ADDR {addr}"))?;
+            let statsd =
+                statsd::StatsdRecorder::new(target, &config.statsd_prefix, &config.statsd_tags)?;
+            tracing::info!("sending metrics to StatsD at {target}");
+            let fanout = metrics_util::layers::FanoutBuilder::default()
+                .add_recorder(prometheus)
+                .add_recorder(statsd)
+                .build();
+            metrics::set_global_recorder(fanout).map_err(|e| e.to_string())
+        }
+        None => metrics::set_global_recorder(prometheus).map_err(|e| e.to_string()),
+    }
+    .expect("failed to install metrics recorder");
+
+    // install_recorder() would do this for us; drains histogram samples.
+    let upkeep_handle = metrics_handle.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(METRICS_UPKEEP_INTERVAL).await;
+            upkeep_handle.run_upkeep();
+        }
+    });
+
+    Ok(metrics_handle)
+}
+
+/// Wait for SIGINT or SIGTERM, then return so axum's graceful shutdown
+/// drains in-flight requests. On non-Unix platforms, only Ctrl-C is
+/// honored (tokio does not expose SIGTERM elsewhere).
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl-C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => tracing::info!("shutdown: SIGINT received"),
+        _ = terminate => tracing::info!("shutdown: SIGTERM received"),
+    }
+}
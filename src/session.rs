@@ -0,0 +1,91 @@
+//! Short-lived server-side state for flows that span several requests,
+//! like `/ds`, keyed by a random token the client carries between them.
+//!
+//! Sessions live in memory only, so they don't survive a restart and aren't
+//! shared between instances behind a load balancer. Each expires `ttl` after
+//! it was started; at capacity, expired sessions are dropped first, then an
+//! arbitrary live one, as in any [`TtlMap`].
+
+use std::time::Duration;
+
+use uuid::Uuid;
+
+use crate::ttl_map::TtlMap;
+
+pub struct SessionStore<T> {
+    sessions: TtlMap<String, T>,
+}
+
+impl<T: Clone + Default> SessionStore<T> {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            sessions: TtlMap::new(ttl, capacity),
+        }
+    }
+
+    /// How long a session lasts from [`start`](Self::start).
+    pub fn ttl(&self) -> Duration {
+        self.sessions.ttl()
+    }
+
+    /// Start a session holding `T::default()`, returning its token: 32
+    /// lowercase hex digits, usable in a path or as a DNS label.
+    pub fn start(&self) -> String {
+        let token = Uuid::new_v4().simple().to_string();
+        self.sessions.insert(token.clone(), T::default());
+        token
+    }
+
+    /// A copy of the session's value, or `None` if the token is unknown or
+    /// expired.
+    pub fn get(&self, token: &str) -> Option<T> {
+        self.sessions.get(token)
+    }
+
+    /// Apply `f` to the session's value, returning its result, or `None`
+    /// if the token is unknown or expired.
+    pub fn update<R>(&self, token: &str, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        self.sessions.update(token, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_find_their_sessions() {
+        let store = SessionStore::<u32>::new(Duration::from_secs(60), 10);
+        let a = store.start();
+        let b = store.start();
+        assert_ne!(a, b);
+        assert_eq!(a.len(), 32);
+        assert!(
+            a.bytes()
+                .all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase())
+        );
+
+        assert_eq!(store.update(&a, |n| *n += 5), Some(()));
+        assert_eq!(store.get(&a), Some(5));
+        assert_eq!(store.get(&b), Some(0));
+        assert_eq!(store.get("nope"), None);
+        assert_eq!(store.update("nope", |n| *n += 1), None);
+    }
+
+    #[test]
+    fn sessions_expire() {
+        let store = SessionStore::<u32>::new(Duration::ZERO, 10);
+        let token = store.start();
+        assert_eq!(store.get(&token), None);
+    }
+
+    #[test]
+    fn capacity_is_bounded() {
+        let store = SessionStore::<u32>::new(Duration::from_secs(60), 2);
+        let tokens: Vec<_> = (0..3).map(|_| store.start()).collect();
+        let live = tokens.iter().filter(|t| store.get(t).is_some()).count();
+        assert_eq!(live, 2);
+        // The newest session always survives.
+        assert!(store.get(&tokens[2]).is_some());
+    }
+}
@@ -1,12 +1,23 @@
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 
-use metrics_exporter_prometheus::PrometheusHandle;
+use arc_swap::ArcSwap;
+#[cfg(feature = "metrics")]
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
 use tokio::sync::RwLock;
 
+use crate::access_log::AccessLog;
 use crate::config::Config;
+use crate::dual_stack::DualStack;
+use crate::geoip::{IpEnricher, SharedEnricher};
 use crate::lookup::IpLookupTable;
+use crate::ping::Pinger;
+use crate::portcheck::PortChecker;
 use crate::providers::ProviderRecord;
+use crate::rdns::ReverseDns;
+use crate::resolver_check::ResolverCheck;
+use crate::traceroute::Tracer;
 
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct SyncStatus {
@@ -23,18 +34,66 @@ pub struct AppState {
     /// Last-known-good records per provider name. On a fetch failure, the
     /// previous records are reused so partial sync failures don't drop data.
     pub provider_records: Arc<RwLock<HashMap<String, Vec<ProviderRecord>>>>,
-    pub config: Arc<Config>,
+    /// Swapped on SIGHUP; see [`crate::reload`] for what takes effect.
+    pub config: Arc<ArcSwap<Config>>,
+    /// Rendered by `/metrics`.
+    #[cfg(feature = "metrics")]
     pub metrics_handle: PrometheusHandle,
+    pub reverse_dns: Arc<ReverseDns>,
+    /// Quota and allowlist for `/portcheck`.
+    pub port_checker: Arc<PortChecker>,
+    /// Quota and settings for `/ping-me`.
+    pub pinger: Arc<Pinger>,
+    /// Quota and settings for `/trace-me`.
+    pub tracer: Arc<Tracer>,
+    /// Sessions for `/ds`.
+    pub dual_stack: Arc<DualStack>,
+    /// Checks for `/resolver-check`, shared with the DNS server.
+    pub resolver_check: Arc<ResolverCheck>,
+    /// Geo/ASN data source selected by `GEOIP_BACKEND`; empty when it has
+    /// nothing configured.
+    pub enricher: Arc<SharedEnricher>,
+    /// Set once the HTTP listener is bound, for `/readyz`.
+    pub listener_bound: Arc<AtomicBool>,
+    /// Writer for `ACCESS_LOG`, when set.
+    pub access_log: Option<Arc<AccessLog>>,
 }
 
 impl AppState {
-    pub fn new(config: Config, metrics_handle: PrometheusHandle) -> Self {
+    pub fn new(config: Config) -> Self {
         Self {
             lookup_table: Arc::new(RwLock::new(IpLookupTable::empty())),
             sync_status: Arc::new(RwLock::new(Vec::new())),
             provider_records: Arc::new(RwLock::new(HashMap::new())),
-            config: Arc::new(config),
-            metrics_handle,
+            reverse_dns: Arc::new(ReverseDns::new(&config)),
+            port_checker: Arc::new(PortChecker::new(&config)),
+            pinger: Arc::new(Pinger::new(&config)),
+            tracer: Arc::new(Tracer::new(&config)),
+            dual_stack: Arc::new(DualStack::new(&config)),
+            resolver_check: Arc::new(ResolverCheck::new(&config)),
+            config: Arc::new(ArcSwap::from_pointee(config)),
+            // Renders nothing until replaced by `with_metrics_handle`.
+            #[cfg(feature = "metrics")]
+            metrics_handle: PrometheusBuilder::new().build_recorder().handle(),
+            enricher: Arc::default(),
+            listener_bound: Arc::new(AtomicBool::new(false)),
+            access_log: None,
         }
     }
+
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics_handle(mut self, metrics_handle: PrometheusHandle) -> Self {
+        self.metrics_handle = metrics_handle;
+        self
+    }
+
+    pub fn with_enricher(mut self, enricher: Option<Arc<dyn IpEnricher>>) -> Self {
+        self.enricher = Arc::new(SharedEnricher::new(enricher));
+        self
+    }
+
+    pub fn with_access_log(mut self, access_log: Option<AccessLog>) -> Self {
+        self.access_log = access_log.map(Arc::new);
+        self
+    }
 }
@@ -0,0 +1,231 @@
+//! StatsD metrics sink, for deployments that don't scrape Prometheus.
+//!
+//! Every `metrics::counter!`, `gauge!` and `histogram!` call is also sent as
+//! a DogStatsD line over UDP: counters as `|c`, gauges as `|g` and
+//! histograms as `|h`, with labels as `|#key:value` tags. Plain StatsD
+//! servers ignore the tags section and treat `|h` as a timer. Sends are
+//! fire-and-forget on a non-blocking socket, so a slow or missing agent
+//! never holds up a request.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use metrics::{
+    Counter, CounterFn, Gauge, GaugeFn, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+    SharedString, Unit,
+};
+
+/// Sends metrics to a StatsD agent.
+pub struct StatsdRecorder {
+    socket: Arc<UdpSocket>,
+    prefix: String,
+    /// Rendered `,key:value` pairs appended to every metric's tags.
+    default_tags: String,
+    /// Gauges are sent as absolute values, so increments need the current
+    /// value. Keyed by metric key since `gauge!` re-registers on every call.
+    gauges: Mutex<HashMap<Key, Arc<AtomicU64>>>,
+}
+
+impl StatsdRecorder {
+    /// Send to `addr`, prefixing every metric name with `prefix.` (unless
+    /// empty) and tagging every line with `tags` (`key:value` or bare).
+    pub fn new(addr: SocketAddr, prefix: &str, tags: &[String]) -> io::Result<Self> {
+        let bind: SocketAddr = match addr {
+            SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+            SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+        };
+        let socket = UdpSocket::bind(bind)?;
+        socket.connect(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket: Arc::new(socket),
+            prefix: match prefix {
+                "" => String::new(),
+                p => format!("{p}."),
+            },
+            default_tags: tags.iter().map(|t| format!(",{t}")).collect(),
+            gauges: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn handle(&self, key: &Key, kind: &'static str) -> Handle {
+        let tags: String = key
+            .labels()
+            .map(|l| format!(",{}:{}", sanitize(l.key()), sanitize(l.value())))
+            .chain(std::iter::once(self.default_tags.clone()))
+            .collect();
+        let tags = match tags.strip_prefix(',') {
+            Some(tags) => format!("|#{tags}"),
+            None => String::new(),
+        };
+        Handle {
+            socket: self.socket.clone(),
+            name: format!("{}{}", self.prefix, key.name()),
+            kind,
+            tags,
+            value: Arc::new(AtomicU64::new(0)),
+        }
+    }
+}
+
+/// `,` separates tags and `|`/`#` delimit sections, so they can't appear in
+/// a tag. Label values here are route patterns and fixed strings, but be
+/// safe.
+fn sanitize(s: &str) -> String {
+    s.replace([',', '|', '#'], "_")
+}
+
+impl Recorder for StatsdRecorder {
+    fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+        Counter::from_arc(Arc::new(self.handle(key, "c")))
+    }
+
+    fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+        let mut handle = self.handle(key, "g");
+        let mut gauges = self.gauges.lock().unwrap_or_else(|e| e.into_inner());
+        handle.value = gauges.entry(key.clone()).or_default().clone();
+        Gauge::from_arc(Arc::new(handle))
+    }
+
+    fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+        Histogram::from_arc(Arc::new(self.handle(key, "h")))
+    }
+}
+
+struct Handle {
+    socket: Arc<UdpSocket>,
+    name: String,
+    /// StatsD type: `c`, `g` or `h`.
+    kind: &'static str,
+    /// `|#...` or empty.
+    tags: String,
+    /// Current gauge value as `f64` bits; unused for other kinds.
+    value: Arc<AtomicU64>,
+}
+
+impl Handle {
+    fn send(&self, value: impl std::fmt::Display) {
+        let line = format!("{}:{value}|{}{}", self.name, self.kind, self.tags);
+        // Dropped if the socket buffer is full or nothing is listening.
+        let _ = self.socket.send(line.as_bytes());
+    }
+
+    fn update_gauge(&self, f: impl Fn(f64) -> f64) {
+        let mut new = 0.0;
+        let _ = self
+            .value
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                new = f(f64::from_bits(bits));
+                Some(new.to_bits())
+            });
+        self.send(new);
+    }
+}
+
+impl CounterFn for Handle {
+    fn increment(&self, value: u64) {
+        self.send(value);
+    }
+
+    /// StatsD counters are deltas; there's no way to report a total.
+    fn absolute(&self, _value: u64) {}
+}
+
+impl GaugeFn for Handle {
+    fn increment(&self, value: f64) {
+        self.update_gauge(|v| v + value);
+    }
+
+    fn decrement(&self, value: f64) {
+        self.update_gauge(|v| v - value);
+    }
+
+    fn set(&self, value: f64) {
+        self.update_gauge(|_| value);
+    }
+}
+
+impl HistogramFn for Handle {
+    fn record(&self, value: f64) {
+        self.send(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn agent() -> (UdpSocket, SocketAddr) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let addr = socket.local_addr().unwrap();
+        (socket, addr)
+    }
+
+    fn recv(socket: &UdpSocket) -> String {
+        let mut buf = [0u8; 512];
+        let n = socket.recv(&mut buf).unwrap();
+        String::from_utf8_lossy(&buf[..n]).into_owned()
+    }
+
+    #[test]
+    fn sends_prefixed_lines_with_label_and_default_tags() {
+        let (agent, addr) = agent();
+        let recorder = StatsdRecorder::new(addr, "ipecho", &["env:prod".to_string()]).unwrap();
+
+        metrics::with_local_recorder(&recorder, || {
+            metrics::counter!("rdns_lookup_total", "result" => "cache_hit").increment(2);
+            metrics::histogram!("http_request_duration_seconds", "route" => "/ip").record(0.25);
+        });
+
+        assert_eq!(
+            recv(&agent),
+            "ipecho.rdns_lookup_total:2|c|#result:cache_hit,env:prod"
+        );
+        assert_eq!(
+            recv(&agent),
+            "ipecho.http_request_duration_seconds:0.25|h|#route:/ip,env:prod"
+        );
+    }
+
+    #[test]
+    fn gauges_are_sent_as_absolute_values() {
+        let (agent, addr) = agent();
+        let recorder = StatsdRecorder::new(addr, "", &[]).unwrap();
+
+        metrics::with_local_recorder(&recorder, || {
+            metrics::gauge!("http_connections_active").increment(1);
+            metrics::gauge!("http_connections_active").increment(1);
+            metrics::gauge!("http_connections_active").decrement(1);
+        });
+
+        assert_eq!(recv(&agent), "http_connections_active:1|g");
+        assert_eq!(recv(&agent), "http_connections_active:2|g");
+        assert_eq!(recv(&agent), "http_connections_active:1|g");
+    }
+
+    #[test]
+    fn delimiters_in_labels_are_replaced() {
+        let (agent, addr) = agent();
+        let recorder = StatsdRecorder::new(addr, "", &[]).unwrap();
+
+        metrics::with_local_recorder(&recorder, || {
+            metrics::counter!("requests", "route" => "/a,b|c#d").increment(1);
+        });
+
+        assert_eq!(recv(&agent), "requests:1|c|#route:/a_b_c_d");
+    }
+}
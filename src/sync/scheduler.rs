@@ -4,6 +4,7 @@ use tokio::time::MissedTickBehavior;
 
 use crate::lookup::IpLookupTable;
 use crate::providers::aws::AwsProvider;
+use crate::providers::azure::AzureProvider;
 use crate::providers::cloudflare::CloudflareProvider;
 use crate::providers::gcp::GcpProvider;
 use crate::providers::oracle::OracleProvider;
@@ -13,6 +14,7 @@ use crate::state::{AppState, SyncStatus};
 pub async fn start_sync_loop(state: AppState) {
     let providers: Vec<Box<dyn IpRangeProvider>> = vec![
         Box::new(AwsProvider),
+        Box::new(AzureProvider),
         Box::new(CloudflareProvider),
         Box::new(GcpProvider),
         Box::new(OracleProvider),
@@ -22,7 +24,7 @@ pub async fn start_sync_loop(state: AppState) {
     run_sync(&providers, &state).await;
 
     let mut interval =
-        tokio::time::interval(Duration::from_secs(state.config.sync_interval_secs));
+        tokio::time::interval(Duration::from_secs(state.config.load().sync_interval_secs));
     interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
 
     loop {
@@ -128,6 +130,7 @@ mod tests {
     use std::pin::Pin;
     use std::sync::Mutex;
 
+    #[cfg(feature = "metrics")]
     use metrics_exporter_prometheus::PrometheusBuilder;
 
     use super::*;
@@ -164,12 +167,11 @@ mod tests {
     fn test_config() -> Config {
         Config {
             port: 0,
-            sync_interval_secs: 43200,
-            log_level: "info".to_string(),
             trusted_proxies: vec![],
             rate_limit_per_second: 10,
             rate_limit_burst: 10,
-            excluded_headers: vec![],
+            rdns_enabled: false,
+            ..Config::default()
         }
     }
 
@@ -186,9 +188,10 @@ mod tests {
     async fn partial_failure_retains_last_known_good() {
         // Install a throwaway Prometheus recorder so metrics::counter! calls
         // have somewhere to go even if another test already installed one.
+        #[cfg(feature = "metrics")]
         let _ = PrometheusBuilder::new().install_recorder();
 
-        let state = AppState::new(test_config(), PrometheusBuilder::new().build_recorder().handle());
+        let state = AppState::new(test_config());
 
         let providers: Vec<Box<dyn IpRangeProvider>> = vec![
             Box::new(StubProvider::new(
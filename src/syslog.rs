@@ -0,0 +1,281 @@
+//! Syslog output for `SYSLOG_ADDR`.
+//!
+//! Each log event becomes one RFC 5424 message:
+//!
+//! ```text
+//! <30>1 2026-10-15T05:04:06.123Z host ipecho 4242 - - ipecho: listening on 0.0.0.0:8083
+//! ```
+//!
+//! The priority combines `SYSLOG_FACILITY` with a severity mapped from the
+//! event level. Messages are handed to a background thread, so a slow or
+//! unreachable collector can't block requests; like UDP syslog itself,
+//! delivery is best effort.
+
+use std::io::{self, Write};
+use std::net::{TcpStream, UdpSocket};
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+use std::time::SystemTime;
+
+use tracing::{Level, Metadata};
+use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::config::{SyslogFacility, SyslogTarget};
+use crate::datetime::UtcDateTime;
+
+/// Builds one [`SyslogEvent`] writer per log event.
+pub struct SyslogWriter {
+    out: NonBlocking,
+    facility: SyslogFacility,
+    /// `HOSTNAME APP-NAME PROCID`, fixed for the process lifetime.
+    origin: String,
+}
+
+impl SyslogWriter {
+    /// Connect to `target`. The returned guard flushes queued messages when
+    /// dropped.
+    pub fn open(
+        target: &SyslogTarget,
+        facility: SyslogFacility,
+    ) -> io::Result<(Self, WorkerGuard)> {
+        let (out, guard) = tracing_appender::non_blocking(Transport::connect(target)?);
+        let origin = format!(
+            "{} {} {}",
+            hostname(),
+            env!("CARGO_PKG_NAME"),
+            std::process::id()
+        );
+        Ok((
+            Self {
+                out,
+                facility,
+                origin,
+            },
+            guard,
+        ))
+    }
+
+    fn event(&self, level: &Level) -> SyslogEvent {
+        SyslogEvent {
+            out: self.out.clone(),
+            priority: self.facility.0 * 8 + severity(level),
+            origin: self.origin.clone(),
+            buf: Vec::new(),
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for SyslogWriter {
+    type Writer = SyslogEvent;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.event(&Level::INFO)
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        self.event(meta.level())
+    }
+}
+
+/// RFC 5424 severity for a tracing level.
+fn severity(level: &Level) -> u8 {
+    match *level {
+        Level::ERROR => 3,
+        Level::WARN => 4,
+        Level::INFO => 6,
+        Level::DEBUG | Level::TRACE => 7,
+    }
+}
+
+/// Collects one formatted event and sends it as a single message when
+/// dropped, since the formatter may write it in several pieces.
+pub struct SyslogEvent {
+    out: NonBlocking,
+    priority: u8,
+    origin: String,
+    buf: Vec<u8>,
+}
+
+impl Write for SyslogEvent {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for SyslogEvent {
+    fn drop(&mut self) {
+        let msg = String::from_utf8_lossy(&self.buf);
+        let msg = msg.trim_end();
+        if msg.is_empty() {
+            return;
+        }
+        let line = format!(
+            "<{}>1 {} {} - - {msg}",
+            self.priority,
+            UtcDateTime::from_system_time(SystemTime::now()).rfc3339(true),
+            self.origin
+        );
+        let _ = self.out.write_all(line.as_bytes());
+    }
+}
+
+/// Our hostname for the HOSTNAME field, or `-` (the RFC 5424 nil value).
+fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/proc/sys/kernel/hostname").ok())
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty() && !h.contains(' '))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// Sends each `write` as one syslog message.
+enum Transport {
+    #[cfg(unix)]
+    Unix(UnixDatagram),
+    Udp(UdpSocket),
+    /// Reconnected on the next message after a write error.
+    Tcp {
+        addr: String,
+        stream: Option<TcpStream>,
+    },
+}
+
+impl Transport {
+    fn connect(target: &SyslogTarget) -> io::Result<Self> {
+        match target {
+            #[cfg(unix)]
+            SyslogTarget::Unix(path) => {
+                let socket = UnixDatagram::unbound()?;
+                socket.connect(path)?;
+                Ok(Self::Unix(socket))
+            }
+            #[cfg(not(unix))]
+            SyslogTarget::Unix(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "unix syslog sockets are only supported on Unix",
+            )),
+            SyslogTarget::Udp(addr) => {
+                let socket = UdpSocket::bind(if addr.starts_with('[') {
+                    "[::]:0"
+                } else {
+                    "0.0.0.0:0"
+                })?;
+                socket.connect(addr.as_str())?;
+                Ok(Self::Udp(socket))
+            }
+            SyslogTarget::Tcp(addr) => Ok(Self::Tcp {
+                stream: Some(TcpStream::connect(addr.as_str())?),
+                addr: addr.clone(),
+            }),
+        }
+    }
+}
+
+impl Write for Transport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(socket) => socket.send(buf).map(drop)?,
+            Self::Udp(socket) => socket.send(buf).map(drop)?,
+            Self::Tcp { addr, stream } => {
+                // RFC 6587 octet counting: "<len> <msg>".
+                let mut frame = format!("{} ", buf.len()).into_bytes();
+                frame.extend_from_slice(buf);
+                let conn = match stream {
+                    Some(conn) => conn,
+                    None => stream.insert(TcpStream::connect(addr.as_str())?),
+                };
+                if let Err(e) = conn.write_all(&frame) {
+                    *stream = None;
+                    return Err(e);
+                }
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Tcp {
+                stream: Some(conn), ..
+            } => conn.flush(),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    use super::*;
+
+    fn log_one(writer: SyslogWriter, guard: WorkerGuard) {
+        let subscriber = tracing_subscriber::fmt()
+            .without_time()
+            .with_ansi(false)
+            .with_level(false)
+            .with_writer(writer)
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(peer = "203.0.113.9", "handshake failed");
+        });
+        drop(guard);
+    }
+
+    #[test]
+    fn udp_message_is_rfc5424() {
+        let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+        collector
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let target = SyslogTarget::Udp(collector.local_addr().unwrap().to_string());
+        let (writer, guard) = SyslogWriter::open(&target, SyslogFacility(16)).unwrap();
+        log_one(writer, guard);
+
+        let mut buf = [0u8; 1024];
+        let n = collector.recv(&mut buf).unwrap();
+        let msg = std::str::from_utf8(&buf[..n]).unwrap();
+        // local0 (16) * 8 + warning (4)
+        assert!(msg.starts_with("<132>1 20"), "got: {msg}");
+        let pid = std::process::id().to_string();
+        assert!(msg.contains(&format!(" ipecho {pid} - - ")), "got: {msg}");
+        assert!(
+            msg.ends_with("handshake failed peer=\"203.0.113.9\""),
+            "got: {msg}"
+        );
+    }
+
+    #[test]
+    fn tcp_messages_are_octet_counted() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = SyslogTarget::Tcp(listener.local_addr().unwrap().to_string());
+        let (writer, guard) = SyslogWriter::open(&target, SyslogFacility::DAEMON).unwrap();
+        let (mut conn, _) = listener.accept().unwrap();
+        log_one(writer, guard);
+
+        let mut received = String::new();
+        conn.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        conn.read_to_string(&mut received).unwrap();
+        let (len, msg) = received.split_once(' ').unwrap();
+        assert_eq!(len.parse::<usize>().unwrap(), msg.len());
+        // daemon (3) * 8 + warning (4)
+        assert!(msg.starts_with("<28>1 "), "got: {msg}");
+    }
+
+    #[test]
+    fn severities() {
+        assert_eq!(severity(&Level::ERROR), 3);
+        assert_eq!(severity(&Level::INFO), 6);
+        assert_eq!(severity(&Level::TRACE), 7);
+    }
+}
@@ -0,0 +1,124 @@
+//! Raw TCP echo on `TCP_ECHO_ADDR`, for clients that don't speak HTTP.
+//!
+//! Every accepted connection is sent the client's own `ip:port` as a single
+//! line and closed, so `nc echo.example.com 2323` is all it takes. Nothing
+//! is read from the client. The address is the socket peer: there are no
+//! forwarding headers to trust, so put this listener directly on the
+//! network rather than behind a proxy. Connections share the HTTP rate
+//! limit; over quota, they are closed without an answer.
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::listener::handle_accept_error;
+use crate::ratelimit::RateLimitState;
+
+/// How long a client has to take its line before the connection is dropped.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Answer connections on `listener` until `shutdown` resolves.
+pub async fn serve<F>(listener: TcpListener, rate_limit: RateLimitState, shutdown: F)
+where
+    F: Future<Output = ()>,
+{
+    tokio::pin!(shutdown);
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(conn) => conn,
+                Err(e) => {
+                    handle_accept_error(e).await;
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+        let peer = SocketAddr::new(peer.ip().to_canonical(), peer.port());
+        if !rate_limit.check(peer.ip()) {
+            metrics::counter!("tcp_echo_connections_total", "result" => "rate_limited")
+                .increment(1);
+            continue;
+        }
+        metrics::counter!("tcp_echo_connections_total", "result" => "answered").increment(1);
+        tokio::spawn(answer(stream, peer));
+    }
+}
+
+/// Write `peer` to `stream` and close it.
+async fn answer(mut stream: TcpStream, peer: SocketAddr) {
+    let write = async {
+        stream.write_all(line(peer).as_bytes()).await?;
+        stream.shutdown().await
+    };
+    match tokio::time::timeout(WRITE_TIMEOUT, write).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => tracing::debug!(%peer, error = %e, "TCP echo write failed"),
+        Err(_) => tracing::debug!(%peer, "TCP echo write timed out"),
+    }
+}
+
+/// The answer for `peer`: `203.0.113.7:51234`, or `[2001:db8::1]:51234`.
+fn line(peer: SocketAddr) -> String {
+    format!("{peer}\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    #[test]
+    fn formats_both_families() {
+        assert_eq!(
+            line("203.0.113.7:51234".parse().unwrap()),
+            "203.0.113.7:51234\n"
+        );
+        assert_eq!(
+            line("[2001:db8::1]:51234".parse().unwrap()),
+            "[2001:db8::1]:51234\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn writes_the_peer_and_closes() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(
+            listener,
+            RateLimitState::new(100, 100),
+            std::future::pending(),
+        ));
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let local = client.local_addr().unwrap();
+        let mut answer = String::new();
+        client.read_to_string(&mut answer).await.unwrap();
+        assert_eq!(answer, format!("{local}\n"));
+    }
+
+    #[tokio::test]
+    async fn over_quota_connections_get_no_answer() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(
+            listener,
+            RateLimitState::new(1, 1),
+            std::future::pending(),
+        ));
+
+        let mut answers = Vec::new();
+        for _ in 0..2 {
+            let mut client = TcpStream::connect(addr).await.unwrap();
+            let mut answer = String::new();
+            let _ = client.read_to_string(&mut answer).await;
+            answers.push(answer);
+        }
+        assert!(!answers[0].is_empty());
+        assert!(answers[1].is_empty());
+    }
+}
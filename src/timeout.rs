@@ -0,0 +1,73 @@
+//! Per-request deadline (`REQUEST_TIMEOUT_SECS`).
+//!
+//! A request that hasn't produced a response in time, whether because the
+//! client is trickling its body or a lookup is stuck, is answered with a
+//! JSON 408 and counted in `http_requests_timed_out_total`. The deadline
+//! covers the time to the response head; streamed bodies are bounded by the
+//! connection's idle timeout instead.
+
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{Request, Response};
+use axum::middleware::Next;
+use axum::response::IntoResponse;
+
+use crate::errors::AppError;
+
+pub async fn request_timeout_middleware(
+    State(timeout): State<Duration>,
+    request: Request<Body>,
+    next: Next,
+) -> Response<Body> {
+    match tokio::time::timeout(timeout, next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => {
+            metrics::counter!("http_requests_timed_out_total").increment(1);
+            AppError::Timeout.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::Router;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn app() -> Router {
+        Router::new()
+            .route("/fast", get(|| async { "ok" }))
+            .route(
+                "/slow",
+                get(|| async { tokio::time::sleep(Duration::from_secs(60)).await }),
+            )
+            .layer(axum::middleware::from_fn_with_state(
+                Duration::from_secs(5),
+                request_timeout_middleware,
+            ))
+    }
+
+    fn request(uri: &str) -> Request<Body> {
+        Request::builder().uri(uri).body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn slow_request_gets_json_408() {
+        let response = app().oneshot(request("/slow")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], br#"{"error":"Request timed out"}"#);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fast_request_is_untouched() {
+        let response = app().oneshot(request("/fast")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
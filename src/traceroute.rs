@@ -0,0 +1,388 @@
+//! A traceroute from the server to the client (`/trace-me`), for
+//! complaints about asymmetric routing: the client's own traceroute shows
+//! the path in, this shows the path back.
+//!
+//! Each hop is one ICMP echo request with the TTL raised by one, answered
+//! by the router where it ran out (Time Exceeded) or, at the end, by the
+//! client. Reading routers' answers takes a raw socket, so this needs
+//! `CAP_NET_RAW`. It's off unless `TRACE_ENABLED`, has the
+//! [`crate::outbound`] guards (`OUTBOUND_ALLOW_PRIVATE` included) with a
+//! tighter default quota than `/ping-me` (`TRACE_PER_MINUTE`), and stops at
+//! `TRACE_MAX_HOPS` or after [`MAX_SILENT_HOPS`] hops in a row that didn't
+//! answer.
+//!
+//! Every raw ICMP socket sees every ICMP packet the host receives, so on
+//! top of the per-client quota at most [`MAX_CONCURRENT_TRACES`] run at
+//! once across all clients; beyond that the request is refused with a 503.
+
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::Stream;
+use futures::stream;
+use serde::Serialize;
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
+use tokio::net::UdpSocket;
+use tokio::sync::Semaphore;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::errors::AppError;
+use crate::outbound::{ClientQuota, client_target};
+use crate::ping::{
+    ICMPV4_ECHO_REQUEST, ICMPV6_ECHO_REQUEST, echo_request, icmp_message, parse_reply,
+};
+
+/// Consecutive silent hops after which the rest of the path is assumed to
+/// be dropping probes.
+pub const MAX_SILENT_HOPS: u8 = 5;
+
+/// Traces allowed in flight at once, each holding a raw socket.
+pub const MAX_CONCURRENT_TRACES: usize = 8;
+
+const ICMPV4_TIME_EXCEEDED: u8 = 11;
+const ICMPV4_UNREACHABLE: u8 = 3;
+const ICMPV6_TIME_EXCEEDED: u8 = 3;
+const ICMPV6_UNREACHABLE: u8 = 1;
+
+/// What answered a probe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reply {
+    /// A router on the way: the probe's TTL ran out there.
+    TimeExceeded,
+    /// The client itself; the trace is complete.
+    EchoReply,
+    /// A router or the client reported the client unreachable; the trace
+    /// ends there.
+    Unreachable,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Hop {
+    /// The probe's TTL.
+    pub hop: u8,
+    /// Who answered, `null` if nobody did within the timeout.
+    pub ip: Option<IpAddr>,
+    pub rtt_ms: Option<f64>,
+    pub reply: Option<Reply>,
+}
+
+pub struct Tracer {
+    enabled: bool,
+    allow_private: bool,
+    max_hops: u8,
+    timeout: Duration,
+    quota: ClientQuota,
+    traces: Arc<Semaphore>,
+}
+
+impl Tracer {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            enabled: config.trace_enabled,
+            allow_private: config.outbound_allow_private,
+            max_hops: config.trace_max_hops,
+            timeout: Duration::from_millis(config.trace_timeout_ms),
+            quota: ClientQuota::per_minute(config.trace_per_minute),
+            traces: Arc::new(Semaphore::new(MAX_CONCURRENT_TRACES)),
+        }
+    }
+
+    /// Start tracing the path to `ip`, the requesting client, if config and
+    /// quota allow. Hops are probed one at a time as the stream is polled.
+    pub fn trace(&self, ip: IpAddr) -> Result<impl Stream<Item = Hop> + Send + 'static, AppError> {
+        if !self.enabled {
+            return Err(AppError::NotFound(
+                "tracing clients is disabled (TRACE_ENABLED)".to_string(),
+            ));
+        }
+        let ip = client_target(ip, self.allow_private)?;
+        // Before the quota, so a refused trace doesn't use up the client's.
+        let permit = self
+            .traces
+            .clone()
+            .try_acquire_owned()
+            .map_err(|_| AppError::Overloaded)?;
+        self.quota.check(ip)?;
+        let trace = Trace::start(ip, self.max_hops, self.timeout).map_err(|e| {
+            tracing::warn!(error = %e, "opening a raw ICMP socket failed");
+            AppError::NotFound("this server can't trace routes (needs CAP_NET_RAW)".to_string())
+        })?;
+        // The permit goes with the socket, until the stream is dropped.
+        Ok(stream::unfold(
+            (trace, permit),
+            |(mut trace, permit)| async move {
+                let hop = trace.next_hop().await?;
+                Some((hop, (trace, permit)))
+            },
+        ))
+    }
+}
+
+/// One trace in progress.
+struct Trace {
+    socket: UdpSocket,
+    target: SocketAddr,
+    /// Our echo ID, to pick out answers to our probes from everything else
+    /// a raw socket sees.
+    id: u16,
+    /// Echoed in the client's replies.
+    token: [u8; 8],
+    ttl: u8,
+    max_hops: u8,
+    timeout: Duration,
+    silent: u8,
+    done: bool,
+}
+
+impl Trace {
+    fn start(ip: IpAddr, max_hops: u8, timeout: Duration) -> io::Result<Self> {
+        let (domain, protocol) = match ip {
+            IpAddr::V4(_) => (Domain::IPV4, Protocol::ICMPV4),
+            IpAddr::V6(_) => (Domain::IPV6, Protocol::ICMPV6),
+        };
+        // Unlike `/ping-me`, no unprivileged fallback: those sockets don't
+        // receive other hosts' Time Exceeded messages.
+        let socket = Socket::new(domain, Type::RAW, Some(protocol))?;
+        socket.set_nonblocking(true)?;
+        let token = Uuid::new_v4().as_u64_pair().0.to_be_bytes();
+        Ok(Self {
+            socket: UdpSocket::from_std(std::net::UdpSocket::from(socket))?,
+            target: SocketAddr::new(ip, 0),
+            id: u16::from_be_bytes([token[0], token[1]]),
+            token,
+            ttl: 0,
+            max_hops,
+            timeout,
+            silent: 0,
+            done: false,
+        })
+    }
+
+    fn ipv4(&self) -> bool {
+        self.target.is_ipv4()
+    }
+
+    /// Probe the next hop, or `None` once the trace is over.
+    async fn next_hop(&mut self) -> Option<Hop> {
+        if self.done || self.ttl >= self.max_hops || self.silent >= MAX_SILENT_HOPS {
+            return None;
+        }
+        self.ttl += 1;
+        let hop = match self.probe().await {
+            Ok(hop) => hop,
+            Err(e) => {
+                tracing::debug!(error = %e, ttl = self.ttl, "traceroute probe failed");
+                self.done = true;
+                return None;
+            }
+        };
+        match hop.reply {
+            None => self.silent += 1,
+            Some(Reply::TimeExceeded) => self.silent = 0,
+            Some(Reply::EchoReply | Reply::Unreachable) => self.done = true,
+        }
+        Some(hop)
+    }
+
+    async fn probe(&self) -> io::Result<Hop> {
+        let ttl = u32::from(self.ttl);
+        let sock = SockRef::from(&self.socket);
+        if self.ipv4() {
+            sock.set_ttl_v4(ttl)?;
+        } else {
+            sock.set_unicast_hops_v6(ttl)?;
+        }
+        let seq = u16::from(self.ttl);
+        let sent_at = Instant::now();
+        let request = echo_request(self.ipv4(), self.id, seq, &self.token);
+        self.socket.send_to(&request, self.target).await?;
+
+        let deadline = sent_at + self.timeout;
+        let mut buf = [0u8; 1500];
+        loop {
+            let Ok(received) =
+                tokio::time::timeout_at(deadline, self.socket.recv_from(&mut buf)).await
+            else {
+                return Ok(Hop {
+                    hop: self.ttl,
+                    ip: None,
+                    rtt_ms: None,
+                    reply: None,
+                });
+            };
+            let (n, from) = received?;
+            if let Some(reply) = self.classify(&buf[..n], from.ip(), seq) {
+                return Ok(Hop {
+                    hop: self.ttl,
+                    ip: Some(from.ip().to_canonical()),
+                    rtt_ms: Some(sent_at.elapsed().as_secs_f64() * 1000.0),
+                    reply: Some(reply),
+                });
+            }
+        }
+    }
+
+    /// Whether `packet` from `from` answers our probe `seq`, and how.
+    fn classify(&self, packet: &[u8], from: IpAddr, seq: u16) -> Option<Reply> {
+        if from == self.target.ip()
+            && parse_reply(packet, self.ipv4())
+                .is_some_and(|(s, payload)| s == seq && payload == self.token)
+        {
+            return Some(Reply::EchoReply);
+        }
+        let icmp = icmp_message(packet, self.ipv4())?;
+        let (time_exceeded, unreachable) = if self.ipv4() {
+            (ICMPV4_TIME_EXCEEDED, ICMPV4_UNREACHABLE)
+        } else {
+            (ICMPV6_TIME_EXCEEDED, ICMPV6_UNREACHABLE)
+        };
+        let reply = match *icmp.first()? {
+            t if t == time_exceeded => Reply::TimeExceeded,
+            t if t == unreachable => Reply::Unreachable,
+            _ => return None,
+        };
+        quotes_probe(icmp.get(8..)?, self.ipv4(), self.id, seq).then_some(reply)
+    }
+}
+
+/// Whether `quoted`, the original packet an ICMP error carries, is our echo
+/// request `id`/`seq`. Only its first 8 bytes are guaranteed to be there,
+/// which is enough for the ID and sequence number.
+fn quotes_probe(quoted: &[u8], ipv4: bool, id: u16, seq: u16) -> bool {
+    let (header_len, request) = if ipv4 {
+        let ihl = quoted.first().map_or(0, |b| usize::from(b & 0x0f) * 4);
+        (ihl, ICMPV4_ECHO_REQUEST)
+    } else {
+        (40, ICMPV6_ECHO_REQUEST)
+    };
+    quoted.get(header_len..header_len + 8).is_some_and(|inner| {
+        inner[0] == request
+            && u16::from_be_bytes([inner[4], inner[5]]) == id
+            && u16::from_be_bytes([inner[6], inner[7]]) == seq
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trace(target: &str) -> Trace {
+        let ip: IpAddr = target.parse().unwrap();
+        // Never sent on: the tests only classify packets.
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_nonblocking(true).unwrap();
+        Trace {
+            socket: UdpSocket::from_std(socket).unwrap(),
+            target: SocketAddr::new(ip, 0),
+            id: 0x1234,
+            token: *b"tracetok",
+            ttl: 3,
+            max_hops: 30,
+            timeout: Duration::from_secs(1),
+            silent: 0,
+            done: false,
+        }
+    }
+
+    /// An ICMPv4 error of `kind` quoting a 20-byte IP header and `probe`.
+    fn icmp_error(kind: u8, probe: &[u8]) -> Vec<u8> {
+        let mut packet = vec![kind, 0, 0, 0, 0, 0, 0, 0, 0x45];
+        packet.resize(8 + 20, 0);
+        packet.extend_from_slice(&probe[..8]);
+        packet
+    }
+
+    #[tokio::test]
+    async fn classifies_answers_to_our_probes() {
+        let trace = trace("203.0.113.7");
+        let router: IpAddr = "198.51.100.1".parse().unwrap();
+        let probe = echo_request(true, 0x1234, 3, b"tracetok");
+
+        let exceeded = icmp_error(ICMPV4_TIME_EXCEEDED, &probe);
+        assert_eq!(
+            trace.classify(&exceeded, router, 3),
+            Some(Reply::TimeExceeded)
+        );
+        // An answer to an earlier probe, or someone else's.
+        assert_eq!(trace.classify(&exceeded, router, 4), None);
+        let other = echo_request(true, 0x9999, 3, b"tracetok");
+        assert_eq!(
+            trace.classify(&icmp_error(ICMPV4_TIME_EXCEEDED, &other), router, 3),
+            None
+        );
+
+        let unreachable = icmp_error(ICMPV4_UNREACHABLE, &probe);
+        assert_eq!(
+            trace.classify(&unreachable, router, 3),
+            Some(Reply::Unreachable)
+        );
+
+        let mut reply = probe.clone();
+        reply[0] = crate::ping::ICMPV4_ECHO_REPLY;
+        let client = trace.target.ip();
+        assert_eq!(trace.classify(&reply, client, 3), Some(Reply::EchoReply));
+        // Only the client's own reply ends the trace.
+        assert_eq!(trace.classify(&reply, router, 3), None);
+    }
+
+    #[test]
+    fn quotes_ipv6_probes() {
+        let probe = echo_request(false, 7, 2, b"x");
+        let mut quoted = vec![0x60];
+        quoted.resize(40, 0);
+        quoted.extend_from_slice(&probe);
+        assert!(quotes_probe(&quoted, false, 7, 2));
+        assert!(!quotes_probe(&quoted[..44], false, 7, 2));
+    }
+
+    #[tokio::test]
+    async fn stops_when_the_client_answers_or_the_path_goes_quiet() {
+        let mut trace = trace("203.0.113.7");
+        trace.done = true;
+        assert!(trace.next_hop().await.is_none());
+
+        let mut trace = self::trace("203.0.113.7");
+        trace.silent = MAX_SILENT_HOPS;
+        assert!(trace.next_hop().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn refuses_traces_beyond_the_cap() {
+        let config = Config {
+            trace_enabled: true,
+            trace_per_minute: 1,
+            ..Config::default()
+        };
+        let mut tracer = Tracer::new(&config);
+        tracer.traces = Arc::new(Semaphore::new(0));
+        assert!(matches!(
+            tracer.trace("8.8.8.8".parse().unwrap()),
+            Err(AppError::Overloaded)
+        ));
+        // The refused request didn't count against the client's quota.
+        tracer.traces = Arc::new(Semaphore::new(1));
+        assert!(!matches!(
+            tracer.trace("8.8.8.8".parse().unwrap()),
+            Err(AppError::RateLimited { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn traces_loopback() {
+        // Needs CAP_NET_RAW, which CI may not grant.
+        let Ok(mut trace) = Trace::start("127.0.0.1".parse().unwrap(), 4, Duration::from_secs(1))
+        else {
+            return;
+        };
+        let hop = trace.next_hop().await.unwrap();
+        assert_eq!(hop.hop, 1);
+        assert_eq!(hop.reply, Some(Reply::EchoReply));
+        assert_eq!(hop.ip, Some("127.0.0.1".parse().unwrap()));
+        assert!(trace.next_hop().await.is_none());
+    }
+}
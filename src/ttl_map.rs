@@ -0,0 +1,135 @@
+//! A bounded in-memory map whose entries expire a fixed time after they
+//! were inserted, behind [`crate::session`], the reverse DNS cache and the
+//! IP lookup API cache.
+//!
+//! At capacity, inserting a new key first drops expired entries, then an
+//! arbitrary live one if that didn't make room.
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct Entry<V> {
+    value: V,
+    expires_at: Instant,
+}
+
+pub struct TtlMap<K, V> {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<HashMap<K, Entry<V>>>,
+}
+
+impl<K: Hash + Eq + Clone, V> TtlMap<K, V> {
+    /// A capacity of 0 holds nothing.
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// How long an entry lasts from [`insert`](Self::insert).
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Insert or replace `key`'s value, expiring `ttl` from now.
+    pub fn insert(&self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            entries.retain(|_, e| e.expires_at > now);
+            if entries.len() >= self.capacity
+                && let Some(victim) = entries.keys().next().cloned()
+            {
+                entries.remove(&victim);
+            }
+        }
+        entries.insert(
+            key,
+            Entry {
+                value,
+                expires_at: now + self.ttl,
+            },
+        );
+    }
+
+    /// A copy of `key`'s value, or `None` if it's absent or expired.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.update(key, |value| value.clone())
+    }
+
+    /// Apply `f` to `key`'s value, returning its result, or `None` if it's
+    /// absent or expired.
+    pub fn update<Q, R>(&self, key: &Q, f: impl FnOnce(&mut V) -> R) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = entries.get_mut(key)?;
+        if entry.expires_at <= Instant::now() {
+            entries.remove(key);
+            return None;
+        }
+        Some(f(&mut entry.value))
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn returns_fresh_entries() {
+        let map = TtlMap::new(Duration::from_secs(60), 10);
+        map.insert("a".to_string(), 1);
+        assert_eq!(map.get("a"), Some(1));
+        assert_eq!(map.update("a", |n| *n += 1), Some(()));
+        assert_eq!(map.get("a"), Some(2));
+        assert_eq!(map.get("b"), None);
+    }
+
+    #[test]
+    fn entries_expire() {
+        let map = TtlMap::new(Duration::ZERO, 10);
+        map.insert(1, "stale");
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.len(), 0);
+    }
+
+    #[test]
+    fn capacity_is_bounded() {
+        let map = TtlMap::new(Duration::from_secs(60), 2);
+        for i in 0..5 {
+            map.insert(i, ());
+        }
+        assert_eq!(map.len(), 2);
+        // The newest entry always survives, and replacing a key doesn't
+        // evict anything.
+        assert_eq!(map.get(&4), Some(()));
+        map.insert(4, ());
+        assert_eq!(map.len(), 2);
+
+        let empty = TtlMap::new(Duration::from_secs(60), 0);
+        empty.insert(1, ());
+        assert_eq!(empty.get(&1), None);
+    }
+}
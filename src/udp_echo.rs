@@ -0,0 +1,91 @@
+//! UDP "what is my IP" on `UDP_ECHO_ADDR`, for scripts and embedded devices
+//! that can send a single packet but not hold a TCP connection.
+//!
+//! Any datagram, whatever it contains, is answered with the sender's
+//! `ip:port` as one line, e.g. `echo | nc -u -w1 echo.example.com 2323`.
+//! The answer is at most a few dozen bytes, which makes the socket a poor
+//! amplifier for spoofed requests; senders also share the HTTP rate limit,
+//! and over quota their datagrams are dropped.
+
+use std::future::Future;
+use std::net::SocketAddr;
+
+use tokio::net::UdpSocket;
+
+use crate::ratelimit::RateLimitState;
+
+/// Answer datagrams on `socket` until `shutdown` resolves.
+pub async fn serve<F>(socket: UdpSocket, rate_limit: RateLimitState, shutdown: F)
+where
+    F: Future<Output = ()>,
+{
+    tokio::pin!(shutdown);
+    // The content is ignored; anything longer is truncated.
+    let mut buf = [0u8; 64];
+    loop {
+        let peer = tokio::select! {
+            received = socket.recv_from(&mut buf) => match received {
+                Ok((_, peer)) => peer,
+                // ICMP errors for earlier answers surface here on some
+                // platforms; they don't affect the socket.
+                Err(e) => {
+                    tracing::debug!(error = %e, "UDP echo receive failed");
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+        let client = SocketAddr::new(peer.ip().to_canonical(), peer.port());
+        if !rate_limit.check(client.ip()) {
+            metrics::counter!("udp_echo_datagrams_total", "result" => "rate_limited").increment(1);
+            continue;
+        }
+        metrics::counter!("udp_echo_datagrams_total", "result" => "answered").increment(1);
+        // Sent to `peer` as received: a dual-stack socket can't address
+        // the IPv4 form of a mapped address.
+        if let Err(e) = socket.send_to(format!("{client}\n").as_bytes(), peer).await {
+            tracing::debug!(%peer, error = %e, "UDP echo send failed");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    async fn server(rate_limit: RateLimitState) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(serve(socket, rate_limit, std::future::pending()));
+        addr
+    }
+
+    #[tokio::test]
+    async fn answers_with_the_sender() {
+        let addr = server(RateLimitState::new(100, 100)).await;
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.send_to(b"", addr).await.unwrap();
+
+        let mut buf = [0u8; 64];
+        let (len, from) = client.recv_from(&mut buf).await.unwrap();
+        assert_eq!(from, addr);
+        let expected = format!("{}\n", client.local_addr().unwrap());
+        assert_eq!(&buf[..len], expected.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn over_quota_datagrams_are_dropped() {
+        let addr = server(RateLimitState::new(1, 1)).await;
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut buf = [0u8; 64];
+
+        client.send_to(b"one", addr).await.unwrap();
+        client.recv_from(&mut buf).await.unwrap();
+        client.send_to(b"two", addr).await.unwrap();
+        let second =
+            tokio::time::timeout(Duration::from_millis(200), client.recv_from(&mut buf)).await;
+        assert!(second.is_err());
+    }
+}
@@ -0,0 +1,110 @@
+//! `User-Agent` parsing into browser, OS and device type, using woothee's
+//! dataset (the one behind Fluentd's and Norikra's UA parsers). Needs the
+//! `user-agent` feature; without it, [`parse`] recognises nothing.
+
+use serde::Serialize;
+
+/// Woothee's placeholder for fields it couldn't determine.
+#[cfg(feature = "user-agent")]
+const UNKNOWN: &str = "UNKNOWN";
+
+/// Woothee's name for curl, Wget and language HTTP libraries, which it
+/// tells apart by `version` (`curl`, `wget`, `python`...).
+#[cfg(feature = "user-agent")]
+const HTTP_LIBRARY: &str = "HTTP Library";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct UserAgent {
+    /// Browser or crawler name, e.g. `Chrome`, `Googlebot`.
+    pub browser: Option<String>,
+    pub browser_version: Option<String>,
+    /// e.g. `Windows 10`, `Mac OSX`, `iPhone`, `Android`.
+    pub os: Option<String>,
+    pub os_version: Option<String>,
+    /// `desktop`, `mobile`, `feature_phone`, `bot`, `appliance` (game
+    /// consoles, TVs) or `other`.
+    pub device: Option<&'static str>,
+    pub vendor: Option<String>,
+}
+
+/// Parse `raw`; `None` when nothing about it is recognised.
+#[cfg(feature = "user-agent")]
+pub fn parse(raw: &str) -> Option<UserAgent> {
+    let parsed = woothee::parser::Parser::new().parse(raw.trim())?;
+    let known = |v: &str| (!v.is_empty() && v != UNKNOWN).then(|| v.to_string());
+    let device = match parsed.category {
+        "pc" => Some("desktop"),
+        "smartphone" => Some("mobile"),
+        "mobilephone" => Some("feature_phone"),
+        "crawler" => Some("bot"),
+        "appliance" => Some("appliance"),
+        "misc" => Some("other"),
+        _ => None,
+    };
+    let (browser, browser_version) = match parsed.name {
+        HTTP_LIBRARY => (known(parsed.version), None),
+        name => (known(name), known(parsed.version)),
+    };
+    let agent = UserAgent {
+        browser,
+        browser_version,
+        os: known(parsed.os),
+        os_version: known(&parsed.os_version),
+        device,
+        vendor: known(parsed.vendor),
+    };
+    (agent.browser.is_some() || agent.os.is_some() || agent.device.is_some()).then_some(agent)
+}
+
+/// Built without the `user-agent` feature there is no dataset to match.
+#[cfg(not(feature = "user-agent"))]
+pub fn parse(_raw: &str) -> Option<UserAgent> {
+    None
+}
+
+#[cfg(all(test, feature = "user-agent"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn desktop_browser() {
+        let ua = parse(
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 \
+             (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36",
+        )
+        .unwrap();
+        assert_eq!(ua.browser.as_deref(), Some("Chrome"));
+        assert_eq!(ua.browser_version.as_deref(), Some("126.0.0.0"));
+        assert_eq!(ua.os.as_deref(), Some("Windows 10"));
+        assert_eq!(ua.device, Some("desktop"));
+        assert_eq!(ua.vendor.as_deref(), Some("Google"));
+    }
+
+    #[test]
+    fn mobile_bots_and_tools() {
+        let iphone = parse(
+            "Mozilla/5.0 (iPhone; CPU iPhone OS 17_5 like Mac OS X) AppleWebKit/605.1.15 \
+             (KHTML, like Gecko) Version/17.5 Mobile/15E148 Safari/604.1",
+        )
+        .unwrap();
+        assert_eq!(iphone.browser.as_deref(), Some("Safari"));
+        assert_eq!(iphone.os.as_deref(), Some("iPhone"));
+        assert_eq!(iphone.os_version.as_deref(), Some("17.5"));
+        assert_eq!(iphone.device, Some("mobile"));
+
+        let bot = parse("Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)")
+            .unwrap();
+        assert_eq!(bot.browser.as_deref(), Some("Googlebot"));
+        assert_eq!(bot.device, Some("bot"));
+
+        let curl = parse("curl/8.7.1").unwrap();
+        assert_eq!(curl.browser.as_deref(), Some("curl"));
+        assert_eq!(curl.browser_version, None);
+    }
+
+    #[test]
+    fn unrecognised_is_none() {
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("definitely not a browser"), None);
+    }
+}
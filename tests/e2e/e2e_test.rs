@@ -1,29 +1,66 @@
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 
-use tokio::net::TcpListener;
+use arc_swap::ArcSwap;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::RwLock;
 
 use ipecho::config::Config;
+use ipecho::dual_stack::DualStack;
+use ipecho::listener;
+use ipecho::listener::half_open::HalfOpenTracker;
+use ipecho::listener::tls::TlsConfig;
 use ipecho::lookup::IpLookupTable;
+use ipecho::ping::Pinger;
+use ipecho::portcheck::PortChecker;
 use ipecho::providers::ProviderRecord;
 use ipecho::ratelimit::RateLimitState;
+use ipecho::rdns::ReverseDns;
+use ipecho::resolver_check::ResolverCheck;
 use ipecho::routes::create_router;
 use ipecho::state::{AppState, SyncStatus};
+use ipecho::traceroute::Tracer;
 
 fn test_config() -> Config {
     Config {
         port: 0,
-        sync_interval_secs: 43200,
-        log_level: "info".to_string(),
         trusted_proxies: vec!["127.0.0.1/32".parse().unwrap()],
         rate_limit_per_second: 100,
         rate_limit_burst: 100,
-        excluded_headers: vec![],
+        rdns_enabled: false,
+        ..Config::default()
     }
 }
 
 async fn start_test_server() -> (String, tokio::task::JoinHandle<()>) {
+    start_test_server_with_config(test_config()).await
+}
+
+/// Serve through the production accept loop (`listener::serve`) so e2e tests
+/// cover connection-level behavior like PROXY protocol handling.
+async fn start_test_server_with_config(config: Config) -> (String, tokio::task::JoinHandle<()>) {
+    start_test_server_with(config, None).await
+}
+
+async fn start_test_server_with(
+    config: Config,
+    tls: Option<TlsConfig>,
+) -> (String, tokio::task::JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    let base_url = format!("{}://{}", scheme, addr);
+
+    (base_url, serve_test_app(listener.into(), config, tls))
+}
+
+fn serve_test_app(
+    listener: listener::Listener,
+    config: Config,
+    tls: Option<TlsConfig>,
+) -> tokio::task::JoinHandle<()> {
     let table = IpLookupTable::from_records(vec![ProviderRecord {
         provider: "aws".to_string(),
         cidr: "127.0.0.0/8".to_string(),
@@ -44,29 +81,33 @@ async fn start_test_server() -> (String, tokio::task::JoinHandle<()>) {
             last_error: None,
         }])),
         provider_records: Arc::new(RwLock::new(std::collections::HashMap::new())),
-        config: Arc::new(test_config()),
+        reverse_dns: Arc::new(ReverseDns::new(&config)),
+        port_checker: Arc::new(PortChecker::new(&config)),
+        pinger: Arc::new(Pinger::new(&config)),
+        tracer: Arc::new(Tracer::new(&config)),
+        dual_stack: Arc::new(DualStack::new(&config)),
+        resolver_check: Arc::new(ResolverCheck::new(&config)),
+        config: Arc::new(ArcSwap::from_pointee(config)),
         metrics_handle: handle,
+        enricher: Arc::default(),
+        listener_bound: Arc::new(AtomicBool::new(true)),
+        access_log: None,
     };
 
+    let config = state.config.clone();
+    let options = listener::ConnectionOptions {
+        tls,
+        proxy_protocol: config.load().proxy_protocol,
+        half_open: HalfOpenTracker::new(config.load().max_half_open_per_ip),
+    };
     let rl_state = RateLimitState::new(
-        state.config.rate_limit_per_second,
-        state.config.rate_limit_burst,
+        config.load().rate_limit_per_second,
+        config.load().rate_limit_burst,
     );
     let app = create_router(state, rl_state);
-    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
-    let addr = listener.local_addr().unwrap();
-    let base_url = format!("http://{}", addr);
-
-    let join_handle = tokio::spawn(async move {
-        axum::serve(
-            listener,
-            app.into_make_service_with_connect_info::<SocketAddr>(),
-        )
-        .await
-        .unwrap();
-    });
-
-    (base_url, join_handle)
+    tokio::spawn(async move {
+        listener::serve(listener, app, config, options, std::future::pending()).await;
+    })
 }
 
 #[tokio::test]
@@ -103,6 +144,158 @@ async fn test_e2e_echo_endpoint() {
     assert!(json["headers"].is_object());
 }
 
+#[tokio::test]
+async fn test_e2e_client_and_server_ports() {
+    let (base_url, _handle) = start_test_server().await;
+    let server_port: u16 = base_url.rsplit(':').next().unwrap().parse().unwrap();
+
+    let json: serde_json::Value = reqwest::get(format!("{base_url}/all.json"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(json["server_port"], server_port);
+    let client_port = json["client_port"].as_u64().unwrap();
+    assert!(client_port > 0);
+    assert_eq!(json["peer_addr"], format!("127.0.0.1:{client_port}"));
+
+    let port = reqwest::get(format!("{base_url}/port")).await.unwrap();
+    assert_eq!(port.status(), 200);
+    let port: u16 = port.text().await.unwrap().parse().unwrap();
+    assert!(port > 0);
+}
+
+#[tokio::test]
+async fn test_e2e_mtu_reads_the_tcp_socket() {
+    let (base_url, _handle) = start_test_server().await;
+    let json: serde_json::Value = reqwest::get(format!("{base_url}/mtu"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let mss = json["mss"].as_u64().unwrap();
+    assert!(mss > 0);
+    assert!(json["mtu"].as_u64().unwrap() > mss);
+    assert_eq!(json["ip_version"], 4);
+    assert!(json["peer"].as_str().unwrap().starts_with("127.0.0.1:"));
+    // 127.0.0.1 is a trusted proxy in `test_config`.
+    assert_eq!(json["via_proxy"], true);
+    #[cfg(target_os = "linux")]
+    assert!(json["path_mtu"].as_u64().unwrap() > 1500);
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_e2e_rtt_reads_tcp_info() {
+    let (base_url, _handle) = start_test_server().await;
+    let json: serde_json::Value = reqwest::get(format!("{base_url}/rtt"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(json["rtt_ms"].as_f64().unwrap() > 0.0);
+    assert!(json["rto_ms"].as_f64().unwrap() > 0.0);
+    assert_eq!(json["retransmits"], 0);
+    let cwnd = json["congestion_window"].as_u64().unwrap();
+    assert!(cwnd > 0);
+    assert!(json["congestion_window_bytes"].as_u64().unwrap() > cwnd);
+}
+
+#[tokio::test]
+async fn test_e2e_websocket_sends_info_then_echoes() {
+    use futures::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message;
+
+    let (base_url, _handle) = start_test_server().await;
+    let url = format!("{}/ws", base_url.replacen("http://", "ws://", 1));
+    let (mut socket, response) = tokio_tungstenite::connect_async(url).await.unwrap();
+    assert_eq!(response.status(), 101);
+
+    let Some(Ok(Message::Text(info))) = socket.next().await else {
+        panic!("expected the connection info first");
+    };
+    let json: serde_json::Value = serde_json::from_str(&info).unwrap();
+    assert_eq!(json["ip"], "127.0.0.1");
+    assert_eq!(json["headers"]["upgrade"], "websocket");
+
+    socket.send(Message::text("hello")).await.unwrap();
+    assert_eq!(socket.next().await.unwrap().unwrap(), Message::text("hello"));
+    socket.send(Message::binary(vec![0u8, 1, 2])).await.unwrap();
+    assert_eq!(
+        socket.next().await.unwrap().unwrap(),
+        Message::binary(vec![0u8, 1, 2])
+    );
+
+    // Without the upgrade headers, it's a plain (failed) request.
+    let resp = reqwest::get(format!("{base_url}/ws")).await.unwrap();
+    assert!(resp.status().is_client_error());
+}
+
+#[tokio::test]
+async fn test_e2e_delay_is_capped_by_config() {
+    let (base_url, _handle) = start_test_server_with_config(Config {
+        max_delay_secs: 1,
+        ..test_config()
+    })
+    .await;
+
+    let started = std::time::Instant::now();
+    let resp = reqwest::get(format!("{base_url}/delay/60")).await.unwrap();
+    let elapsed = started.elapsed();
+    assert_eq!(resp.status(), 200);
+    assert!(elapsed >= std::time::Duration::from_secs(1), "{elapsed:?}");
+    assert!(elapsed < std::time::Duration::from_secs(10), "{elapsed:?}");
+    let json: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(json["ip"], "127.0.0.1");
+
+    let resp = reqwest::get(format!("{base_url}/delay/soon")).await.unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_e2e_speedtest_download_has_a_content_length() {
+    let (base_url, _handle) = start_test_server().await;
+
+    let resp = reqwest::get(format!("{base_url}/speedtest/download/1Mi"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    // Streamed, but not chunked: clients can show progress.
+    assert_eq!(resp.content_length(), Some(1 << 20));
+    assert!(resp.headers().get("transfer-encoding").is_none());
+    assert_eq!(resp.bytes().await.unwrap().len(), 1 << 20);
+}
+
+#[tokio::test]
+async fn test_e2e_sse_streams_info_then_heartbeats() {
+    let (base_url, _handle) = start_test_server().await;
+
+    let mut resp = reqwest::get(format!("{base_url}/sse?interval=1")).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()["content-type"], "text/event-stream");
+    // Read events as they arrive; the stream itself doesn't end.
+    let mut body = String::new();
+    while !body.contains("event: heartbeat") {
+        let chunk = resp.chunk().await.unwrap().expect("stream ended early");
+        body.push_str(std::str::from_utf8(&chunk).unwrap());
+    }
+    let info = body
+        .split("\n\n")
+        .next()
+        .and_then(|event| event.strip_prefix("event: info\ndata: "))
+        .expect("expected the connection info first");
+    let json: serde_json::Value = serde_json::from_str(info).unwrap();
+    assert_eq!(json["ip"], "127.0.0.1");
+    assert!(body.contains("id: 1\n"));
+    assert!(body.contains(r#"data: {"seq":1,"timestamp":""#));
+
+    let resp = reqwest::get(format!("{base_url}/sse?interval=0")).await.unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
 #[tokio::test]
 async fn test_e2e_health_endpoint() {
     let (base_url, _handle) = start_test_server().await;
@@ -200,6 +393,16 @@ async fn test_e2e_headers_endpoint() {
         .await
         .unwrap();
 
+    assert_eq!(resp.status(), 200);
+    assert!(resp.text().await.unwrap().contains("x-test: e2e-value\n"));
+
+    let resp = client
+        .get(format!("{}/headers.json", base_url))
+        .header("x-test", "e2e-value")
+        .send()
+        .await
+        .unwrap();
+
     assert_eq!(resp.status(), 200);
     let json: serde_json::Value = resp.json().await.unwrap();
     assert_eq!(json["x-test"], "e2e-value");
@@ -307,3 +510,418 @@ async fn test_e2e_unknown_path_returns_404() {
 
     assert_eq!(resp.status(), 404);
 }
+
+/// Send a raw HTTP/1.1 request, optionally preceded by `prefix`, and return
+/// the full response text. Used where reqwest can't express the wire format.
+async fn raw_request(base_url: &str, prefix: &[u8], path: &str) -> String {
+    let addr: SocketAddr = base_url.trim_start_matches("http://").parse().unwrap();
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(prefix).await.unwrap();
+    let request = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response).await;
+    response
+}
+
+#[tokio::test]
+async fn test_e2e_proto_reports_wire_version() {
+    let (base_url, _handle) = start_test_server().await;
+
+    let resp = reqwest::get(format!("{}/proto", base_url)).await.unwrap();
+    assert_eq!(resp.text().await.unwrap(), "HTTP/1.1");
+
+    let addr: SocketAddr = base_url.trim_start_matches("http://").parse().unwrap();
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(b"GET /proto HTTP/1.0\r\n\r\n").await.unwrap();
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response).await;
+    assert!(response.ends_with("HTTP/1.0"), "got: {response}");
+}
+
+#[tokio::test]
+async fn test_e2e_raw_returns_heads_verbatim() {
+    let (base_url, _handle) = start_test_server().await;
+    let addr: SocketAddr = base_url.trim_start_matches("http://").parse().unwrap();
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    // Two pipelined requests: casing and interleaved repeats must survive.
+    let first = "POST /raw?a=1 HTTP/1.1\r\nHOST: localhost\r\nX-Dup: 1\r\n\
+                 content-length: 5\r\nX-Other: o\r\nx-dup: 2\r\n\r\n";
+    let second = "GET /raw HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+    stream
+        .write_all(format!("{first}hello{second}").as_bytes())
+        .await
+        .unwrap();
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response).await;
+
+    assert!(
+        response.contains(&format!("\r\n\r\n{first}hello")),
+        "got: {response}"
+    );
+    assert!(
+        response.ends_with(&format!("\r\n\r\n{second}")),
+        "got: {response}"
+    );
+}
+
+#[tokio::test]
+async fn test_e2e_http1_fingerprint_keeps_header_casing() {
+    let (base_url, _handle) = start_test_server().await;
+    let addr: SocketAddr = base_url.trim_start_matches("http://").parse().unwrap();
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(
+            b"GET /fingerprint/http HTTP/1.1\r\nhost: localhost\r\nUser-Agent: t\r\n\
+              ACCEPT: */*\r\nConnection: close\r\n\r\n",
+        )
+        .await
+        .unwrap();
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response).await;
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    let json: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert_eq!(
+        json["header_order"],
+        serde_json::json!(["host", "User-Agent", "ACCEPT", "Connection"])
+    );
+    assert_eq!(json["akamai"], serde_json::Value::Null);
+}
+
+/// An HTTP/2 frame: 24-bit length, type, flags and stream id, then payload.
+fn h2_frame(kind: u8, flags: u8, stream_id: u32, payload: &[u8]) -> Vec<u8> {
+    let mut frame = (payload.len() as u32).to_be_bytes()[1..].to_vec();
+    frame.extend([kind, flags]);
+    frame.extend(stream_id.to_be_bytes());
+    frame.extend(payload);
+    frame
+}
+
+#[tokio::test]
+async fn test_e2e_http2_akamai_fingerprint() {
+    let (base_url, _handle) = start_test_server().await;
+    let addr: SocketAddr = base_url.trim_start_matches("http://").parse().unwrap();
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    let mut request = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n".to_vec();
+    // SETTINGS_INITIAL_WINDOW_SIZE = 131072, then a window update.
+    request.extend(h2_frame(0x4, 0, 0, &[0, 4, 0, 2, 0, 0]));
+    request.extend(h2_frame(0x8, 0, 0, &12517377u32.to_be_bytes()));
+    // HPACK: :method GET, :path (literal), :authority (literal), :scheme http.
+    let mut block = vec![0x82, 0x44, 17];
+    block.extend(b"/fingerprint/http");
+    block.extend([0x41, 9]);
+    block.extend(b"localhost");
+    block.push(0x86);
+    request.extend(h2_frame(0x1, 0x5, 1, &block));
+    stream.write_all(&request).await.unwrap();
+
+    // Collect DATA frames on stream 1 until END_STREAM.
+    let mut buf = Vec::new();
+    let mut body = Vec::new();
+    'read: loop {
+        let mut chunk = [0u8; 4096];
+        let n = stream.read(&mut chunk).await.unwrap();
+        assert!(n > 0, "connection closed before the response ended");
+        buf.extend(&chunk[..n]);
+        while buf.len() >= 9 {
+            let length = u32::from_be_bytes([0, buf[0], buf[1], buf[2]]) as usize;
+            if buf.len() < 9 + length {
+                break;
+            }
+            let (kind, flags) = (buf[3], buf[4]);
+            let stream_id = u32::from_be_bytes([buf[5], buf[6], buf[7], buf[8]]);
+            if kind == 0x0 && stream_id == 1 {
+                body.extend(&buf[9..9 + length]);
+                if flags & 0x1 != 0 {
+                    break 'read;
+                }
+            }
+            buf.drain(..9 + length);
+        }
+    }
+
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["protocol"], "HTTP/2");
+    assert_eq!(json["akamai"], "4:131072|12517377|0|m,p,a,s");
+    assert_eq!(json["akamai_hash"].as_str().unwrap().len(), 32);
+}
+
+#[tokio::test]
+async fn test_e2e_proxy_protocol_v1_overrides_client_ip() {
+    let mut config = test_config();
+    config.proxy_protocol = true;
+    let (base_url, _handle) = start_test_server_with_config(config).await;
+
+    let response = raw_request(
+        &base_url,
+        b"PROXY TCP4 198.51.100.7 127.0.0.1 40000 8083\r\n",
+        "/ip",
+    )
+    .await;
+
+    assert!(response.starts_with("HTTP/1.1 200"), "got: {response}");
+    assert!(response.ends_with("198.51.100.7"), "got: {response}");
+}
+
+#[tokio::test]
+async fn test_e2e_unix_socket_takes_client_ip_from_forwarded_headers() {
+    let path = std::env::temp_dir().join(format!("ipecho-e2e-{}.sock", std::process::id()));
+    let config = Config {
+        unix_socket: Some(path.clone()),
+        // Nothing is trusted over TCP; the socket peer is regardless.
+        trusted_proxies: vec![],
+        ..test_config()
+    };
+    let (listener, _) = listener::bind_all(&config).unwrap().remove(0);
+    let _handle = serve_test_app(listener, config, None);
+
+    let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+    stream
+        .write_all(
+            b"GET /ip HTTP/1.1\r\nHost: localhost\r\n\
+              X-Forwarded-For: 203.0.113.9\r\nConnection: close\r\n\r\n",
+        )
+        .await
+        .unwrap();
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response).await;
+    assert!(response.ends_with("\r\n\r\n203.0.113.9"), "got: {response}");
+}
+
+#[tokio::test]
+async fn test_e2e_acceptors_share_one_port() {
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let config = Config {
+        bind_addr: "127.0.0.1".parse().unwrap(),
+        port,
+        acceptors: 3,
+        ..test_config()
+    };
+    let listeners = listener::bind_all(&config).unwrap();
+    assert_eq!(listeners.len(), 3);
+    for (listener, _) in listeners {
+        assert_eq!(listener.to_string(), format!("127.0.0.1:{port}"));
+        serve_test_app(listener, config.clone(), None);
+    }
+
+    for _ in 0..6 {
+        let resp = reqwest::get(format!("http://127.0.0.1:{port}/ip")).await.unwrap();
+        assert_eq!(resp.text().await.unwrap(), "127.0.0.1");
+    }
+}
+
+#[tokio::test]
+async fn test_e2e_proxy_protocol_required_when_enabled() {
+    let mut config = test_config();
+    config.proxy_protocol = true;
+    let (base_url, _handle) = start_test_server_with_config(config).await;
+
+    let response = raw_request(&base_url, b"", "/ip").await;
+    assert!(response.is_empty(), "connection without header should be dropped, got: {response}");
+}
+
+#[tokio::test]
+async fn test_e2e_slow_request_head_is_dropped() {
+    let mut config = test_config();
+    config.header_read_timeout_secs = 1;
+    let (base_url, _handle) = start_test_server_with_config(config).await;
+
+    let addr: SocketAddr = base_url.trim_start_matches("http://").parse().unwrap();
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(b"GET /ip HTTP/1.1\r\nHost: loc").await.unwrap();
+    let mut response = Vec::new();
+    let closed = tokio::time::timeout(
+        std::time::Duration::from_secs(5),
+        stream.read_to_end(&mut response),
+    )
+    .await;
+    assert!(closed.is_ok(), "connection with a stalled request head should be closed");
+}
+
+#[tokio::test]
+async fn test_e2e_half_open_connections_are_capped_per_ip() {
+    let mut config = test_config();
+    // Loopback must not be a trusted proxy, or it would be exempt.
+    config.trusted_proxies = vec!["10.0.0.0/8".parse().unwrap()];
+    config.max_half_open_per_ip = 1;
+    let (base_url, _handle) = start_test_server_with_config(config).await;
+    let addr: SocketAddr = base_url.trim_start_matches("http://").parse().unwrap();
+
+    let mut idle = TcpStream::connect(addr).await.unwrap();
+    let mut rejected = TcpStream::connect(addr).await.unwrap();
+    let mut buf = [0u8; 1];
+    let closed = tokio::time::timeout(std::time::Duration::from_secs(5), rejected.read(&mut buf))
+        .await
+        .expect("second half-open connection should be closed");
+    assert!(matches!(closed, Ok(0) | Err(_)));
+
+    // Once the first connection sends a request, its slot is free again.
+    idle.write_all(b"GET /ip HTTP/1.1\r\nHost: localhost\r\n\r\n").await.unwrap();
+    let mut head = [0u8; 12];
+    idle.read_exact(&mut head).await.unwrap();
+    assert_eq!(&head, b"HTTP/1.1 200");
+    let response = raw_request(&base_url, b"", "/ip").await;
+    assert!(response.starts_with("HTTP/1.1 200"), "got: {response}");
+}
+
+#[tokio::test]
+async fn test_e2e_tls_preserves_client_ip() {
+    let dir = std::env::temp_dir().join(format!("ipecho-e2e-tls-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+    std::fs::write(dir.join("cert.pem"), cert.cert.pem()).unwrap();
+    std::fs::write(dir.join("key.pem"), cert.signing_key.serialize_pem()).unwrap();
+    let tls =
+        TlsConfig::from_pem_files(&dir.join("cert.pem"), &dir.join("key.pem"), false).unwrap();
+
+    let (base_url, _handle) = start_test_server_with(test_config(), Some(tls)).await;
+    assert!(base_url.starts_with("https://"));
+
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap();
+    let resp = client.get(format!("{}/ip", base_url)).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.text().await.unwrap().trim(), "127.0.0.1");
+
+    // Plain HTTP against the TLS port fails the handshake.
+    let plain = base_url.replacen("https://", "http://", 1);
+    assert!(reqwest::get(format!("{}/ip", plain)).await.is_err());
+}
+
+#[tokio::test]
+async fn test_e2e_tls_fingerprint() {
+    let dir = std::env::temp_dir().join(format!("ipecho-e2e-ja4-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+    std::fs::write(dir.join("cert.pem"), cert.cert.pem()).unwrap();
+    std::fs::write(dir.join("key.pem"), cert.signing_key.serialize_pem()).unwrap();
+    let tls = TlsConfig::from_pem_files(&dir.join("cert.pem"), &dir.join("key.pem"), false).unwrap();
+
+    let (base_url, _handle) = start_test_server_with(test_config(), Some(tls)).await;
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap();
+    let resp = client
+        .get(format!("{}/fingerprint/tls", base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let fingerprint: serde_json::Value = resp.json().await.unwrap();
+    // rustls offers TLS 1.3 and sends no SNI for an IP address.
+    assert!(
+        fingerprint["ja4"].as_str().unwrap().starts_with("t13i"),
+        "got: {fingerprint}"
+    );
+    assert!(fingerprint["ja3"].as_str().unwrap().starts_with("771,"));
+    assert_eq!(fingerprint["ja3_hash"].as_str().unwrap().len(), 32);
+
+    let all: serde_json::Value = client
+        .get(format!("{}/all.json", base_url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(all["tls_fingerprint"], fingerprint);
+
+    let (plain_url, _plain) = start_test_server().await;
+    let resp = reqwest::get(format!("{}/fingerprint/tls", plain_url))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn test_e2e_tls_session() {
+    let dir = std::env::temp_dir().join(format!("ipecho-e2e-session-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+    std::fs::write(dir.join("cert.pem"), cert.cert.pem()).unwrap();
+    std::fs::write(dir.join("key.pem"), cert.signing_key.serialize_pem()).unwrap();
+    let tls = TlsConfig::from_pem_files(&dir.join("cert.pem"), &dir.join("key.pem"), false).unwrap();
+
+    let (base_url, _handle) = start_test_server_with(test_config(), Some(tls)).await;
+    // A fresh connection per request, so the second one can resume.
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .pool_max_idle_per_host(0)
+        .build()
+        .unwrap();
+    let get = || async {
+        let resp = client.get(format!("{}/tls", base_url)).send().await.unwrap();
+        assert_eq!(resp.status(), 200);
+        resp.json::<serde_json::Value>().await.unwrap()
+    };
+    let first = get().await;
+    assert_eq!(first["version"], "TLSv1.3");
+    assert!(first["cipher_suite"].as_str().unwrap().starts_with("TLS13_"));
+    assert_eq!(first["alpn"], "http/1.1");
+    assert_eq!(first["sni"], serde_json::Value::Null);
+    assert_eq!(first["resumed"], false);
+    assert_eq!(get().await["resumed"], true);
+
+    let (plain_url, _plain) = start_test_server().await;
+    let resp = reqwest::get(format!("{}/tls", plain_url)).await.unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn test_e2e_tls_client_cert() {
+    let dir = std::env::temp_dir().join(format!("ipecho-e2e-mtls-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+    std::fs::write(dir.join("cert.pem"), cert.cert.pem()).unwrap();
+    std::fs::write(dir.join("key.pem"), cert.signing_key.serialize_pem()).unwrap();
+    let tls =
+        TlsConfig::from_pem_files(&dir.join("cert.pem"), &dir.join("key.pem"), true).unwrap();
+    let (base_url, _handle) = start_test_server_with(test_config(), Some(tls)).await;
+
+    let mut params = rcgen::CertificateParams::new(vec!["alice.example.com".into()]).unwrap();
+    params
+        .distinguished_name
+        .push(rcgen::DnType::CommonName, "alice");
+    let key = rcgen::KeyPair::generate().unwrap();
+    let client_cert = params.self_signed(&key).unwrap();
+    let pem = format!("{}{}", client_cert.pem(), key.serialize_pem());
+    let identity = reqwest::Identity::from_pem(pem.as_bytes()).unwrap();
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .identity(identity)
+        .build()
+        .unwrap();
+    let resp = client
+        .get(format!("{}/cert", base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["subject"], "CN=alice");
+    assert_eq!(body["issuer"], "CN=alice");
+    assert_eq!(body["sans"], serde_json::json!(["DNS:alice.example.com"]));
+    assert_eq!(body["chain_length"], 0);
+    assert_eq!(body["sha256"].as_str().unwrap().len(), 64);
+    assert!(body["not_after"].as_str().unwrap().ends_with('Z'));
+
+    // The certificate is optional.
+    let anonymous = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap();
+    let resp = anonymous
+        .get(format!("{}/cert", base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+}
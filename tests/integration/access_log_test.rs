@@ -0,0 +1,108 @@
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+use ipecho::access_log::AccessLog;
+use ipecho::lookup::IpLookupTable;
+
+use super::common::{build_router, test_config, test_state, throwaway_metrics_handle};
+
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Buffer {
+    /// Wait for the writer thread to flush at least one line.
+    async fn lines(&self) -> Vec<String> {
+        for _ in 0..100 {
+            let contents = String::from_utf8(self.0.lock().unwrap().clone()).unwrap();
+            if !contents.is_empty() {
+                return contents.lines().map(str::to_string).collect();
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("nothing was written to the access log");
+    }
+}
+
+#[tokio::test]
+async fn test_combined_log_line_uses_forwarded_client_ip() {
+    let buffer = Buffer::default();
+    let state = test_state(
+        test_config(),
+        throwaway_metrics_handle(),
+        IpLookupTable::empty(),
+    )
+    .with_access_log(Some(AccessLog::from_writer(buffer.clone())));
+    let app = build_router(state);
+
+    let req = Request::builder()
+        .uri("/ip/hex?x=1")
+        .header("x-forwarded-for", "203.0.113.9")
+        .header("referer", "https://example.com/")
+        .header("user-agent", "curl/8.5.0")
+        .extension(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(&body[..], b"0xcb007109");
+
+    let lines = buffer.lines().await;
+    assert_eq!(lines.len(), 1);
+    let line = &lines[0];
+    assert!(line.starts_with("203.0.113.9 - - ["), "got: {line}");
+    assert!(
+        line.ends_with(
+            "] \"GET /ip/hex?x=1 HTTP/1.1\" 200 10 \"https://example.com/\" \"curl/8.5.0\""
+        ),
+        "got: {line}"
+    );
+}
+
+#[tokio::test]
+async fn test_unmatched_routes_are_logged() {
+    let buffer = Buffer::default();
+    let state = test_state(
+        test_config(),
+        throwaway_metrics_handle(),
+        IpLookupTable::empty(),
+    )
+    .with_access_log(Some(AccessLog::from_writer(buffer.clone())));
+    let app = build_router(state);
+
+    let req = Request::builder()
+        .uri("/no-such-route")
+        .extension(ConnectInfo(SocketAddr::from(([192, 0, 2, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    drop(response);
+
+    let lines = buffer.lines().await;
+    assert!(lines[0].starts_with("192.0.2.1 - - ["), "got: {}", lines[0]);
+    assert!(
+        lines[0].contains("\"GET /no-such-route HTTP/1.1\" 404 "),
+        "got: {}",
+        lines[0]
+    );
+}
@@ -0,0 +1,81 @@
+use std::net::SocketAddr;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+use ipecho::lookup::IpLookupTable;
+
+use super::common::{build_router, test_state_with_table};
+
+async fn send(req: Request<Body>) -> (StatusCode, serde_json::Value) {
+    let app = build_router(test_state_with_table(IpLookupTable::empty()));
+    let response = app.oneshot(req).await.unwrap();
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap_or_default())
+}
+
+#[tokio::test]
+async fn test_anything_echoes_the_request() {
+    let req = Request::builder()
+        .method("PATCH")
+        .uri("/anything/some/path?a=1&a=2&b=x")
+        .header("content-type", "application/json; charset=utf-8")
+        .header("x-test", "yes")
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::from(r#"{"hello":"world"}"#))
+        .unwrap();
+    let (status, json) = send(req).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["method"], "PATCH");
+    assert_eq!(json["path"], "/anything/some/path");
+    assert_eq!(json["args"], serde_json::json!({"a": ["1", "2"], "b": "x"}));
+    assert_eq!(json["headers"]["x-test"], "yes");
+    assert_eq!(json["origin"], "127.0.0.1");
+    assert_eq!(json["data"], r#"{"hello":"world"}"#);
+    assert_eq!(json["json"]["hello"], "world");
+    assert_eq!(json["form"], serde_json::json!({}));
+}
+
+#[tokio::test]
+async fn test_anything_parses_form_bodies() {
+    let req = Request::builder()
+        .method("POST")
+        .uri("/anything")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::from("name=ip+echo&tag=a&tag=b"))
+        .unwrap();
+    let (status, json) = send(req).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        json["form"],
+        serde_json::json!({"name": "ip echo", "tag": ["a", "b"]})
+    );
+    assert!(json["json"].is_null());
+}
+
+#[tokio::test]
+async fn test_get_echoes_query_and_headers() {
+    let req = Request::builder()
+        .uri("/get?q=rust&page=2&q=axum")
+        .header("x-test", "yes")
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+    let (status, json) = send(req).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        json["args"],
+        serde_json::json!({"q": ["rust", "axum"], "page": "2"})
+    );
+    assert_eq!(json["headers"]["x-test"], "yes");
+    assert_eq!(json["origin"], "127.0.0.1");
+    assert!(json.get("data").is_none());
+}
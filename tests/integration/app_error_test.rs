@@ -81,3 +81,21 @@ async fn test_metrics_endpoint_returns_ok() {
         .unwrap()
         .contains("text/plain"));
 }
+
+#[tokio::test]
+async fn test_oversized_headers_return_431_json() {
+    let state = test_state_with_table(IpLookupTable::empty());
+    let max = state.config.load().max_header_bytes;
+    let app = build_router(state);
+
+    let req = Request::builder()
+        .uri("/")
+        .header("x-padding", "a".repeat(max))
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE);
+    assert_eq!(response.headers()["content-type"], "application/json");
+}
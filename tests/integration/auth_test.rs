@@ -0,0 +1,115 @@
+use std::net::SocketAddr;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode, header};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+use ipecho::lookup::IpLookupTable;
+
+use super::common::{build_router, test_state_with_table};
+
+async fn get(uri: &str, authorization: Option<&str>) -> axum::response::Response {
+    let app = build_router(test_state_with_table(IpLookupTable::empty()));
+    let mut req = Request::builder()
+        .uri(uri)
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))));
+    if let Some(authorization) = authorization {
+        req = req.header(header::AUTHORIZATION, authorization);
+    }
+    app.oneshot(req.body(Body::empty()).unwrap()).await.unwrap()
+}
+
+async fn json(response: axum::response::Response) -> serde_json::Value {
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    serde_json::from_slice(&body).unwrap()
+}
+
+fn md5_hex(data: &str) -> String {
+    use md5::Digest;
+    md5::Md5::digest(data.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_basic_auth() {
+    let response = get("/basic-auth/alice/secret", None).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        response.headers()[header::WWW_AUTHENTICATE],
+        "Basic realm=\"echo\""
+    );
+
+    // alice:wrong
+    let response = get("/basic-auth/alice/secret", Some("Basic YWxpY2U6d3Jvbmc=")).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // alice:secret
+    let response = get("/basic-auth/alice/secret", Some("Basic YWxpY2U6c2VjcmV0")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let json = json(response).await;
+    assert_eq!(json["authenticated"], true);
+    assert_eq!(json["user"], "alice");
+}
+
+#[tokio::test]
+async fn test_bearer() {
+    let response = get("/bearer", None).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert!(
+        response.headers()[header::WWW_AUTHENTICATE]
+            .to_str()
+            .unwrap()
+            .starts_with("Bearer")
+    );
+
+    let response = get("/bearer", Some("Bearer abc123")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(json(response).await["token"], "abc123");
+}
+
+#[tokio::test]
+async fn test_digest_auth() {
+    let uri = "/digest-auth/auth/alice/secret";
+    let response = get(uri, None).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let challenge = response.headers()[header::WWW_AUTHENTICATE]
+        .to_str()
+        .unwrap()
+        .to_string();
+    assert!(challenge.starts_with("Digest realm=\"echo\", qop=\"auth\""));
+    let nonce = challenge
+        .split("nonce=\"")
+        .nth(1)
+        .and_then(|rest| rest.split('"').next())
+        .unwrap();
+
+    let ha1 = md5_hex("alice:echo:secret");
+    let ha2 = md5_hex(&format!("GET:{uri}"));
+    let answer = |ha1: &str| md5_hex(&format!("{ha1}:{nonce}:00000001:c0ffee:auth:{ha2}"));
+    let authorization = |response: &str| {
+        format!(
+            "Digest username=\"alice\", realm=\"echo\", nonce=\"{nonce}\", uri=\"{uri}\", \
+             qop=auth, nc=00000001, cnonce=\"c0ffee\", response=\"{response}\""
+        )
+    };
+
+    let response = get(uri, Some(&authorization(&answer(&ha1)))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(json(response).await["user"], "alice");
+
+    let wrong = md5_hex("alice:echo:wrong");
+    let response = get(uri, Some(&authorization(&answer(&wrong)))).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_digest_auth_rejects_unknown_algorithm() {
+    let response = get("/digest-auth/auth/alice/secret/SHA-1", None).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = get("/digest-auth/other/alice/secret", None).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
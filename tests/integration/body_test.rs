@@ -0,0 +1,75 @@
+use std::net::SocketAddr;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+use ipecho::config::Config;
+use ipecho::lookup::IpLookupTable;
+
+use super::common::{build_router, test_config, test_state, throwaway_metrics_handle};
+
+fn request(method: &str, content_type: Option<&str>, body: &'static [u8]) -> Request<Body> {
+    let mut req = Request::builder()
+        .method(method)
+        .uri("/echo")
+        .header("content-length", body.len());
+    if let Some(content_type) = content_type {
+        req = req.header("content-type", content_type);
+    }
+    req.extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::from(body))
+        .unwrap()
+}
+
+fn app(config: Config) -> axum::Router {
+    build_router(test_state(
+        config,
+        throwaway_metrics_handle(),
+        IpLookupTable::empty(),
+    ))
+}
+
+#[tokio::test]
+async fn test_echo_returns_the_body_and_content_type() {
+    for method in ["POST", "PUT"] {
+        let response = app(test_config())
+            .oneshot(request(method, Some("text/csv"), b"a,b\n1,2\n"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "text/csv");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"a,b\n1,2\n");
+    }
+
+    let response = app(test_config())
+        .oneshot(request("POST", None, &[0, 1, 2]))
+        .await
+        .unwrap();
+    assert_eq!(
+        response.headers()["content-type"],
+        "application/octet-stream"
+    );
+}
+
+#[tokio::test]
+async fn test_echo_rejects_other_methods_and_large_bodies() {
+    let response = app(test_config())
+        .oneshot(request("GET", None, b""))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+    let config = Config {
+        max_body_bytes: 4,
+        ..test_config()
+    };
+    let response = app(config)
+        .oneshot(request("POST", None, b"too long"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
@@ -0,0 +1,48 @@
+use std::net::SocketAddr;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+use ipecho::lookup::IpLookupTable;
+
+use super::common::{build_router, test_state_with_table};
+
+async fn get(headers: &[(&str, &str)]) -> (StatusCode, serde_json::Value) {
+    let app = build_router(test_state_with_table(IpLookupTable::empty()));
+    let mut req = Request::builder()
+        .uri("/client")
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))));
+    for (name, value) in headers {
+        req = req.header(*name, *value);
+    }
+    let response = app.oneshot(req.body(Body::empty()).unwrap()).await.unwrap();
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+async fn test_client_classifies_curl() {
+    let (status, body) = get(&[("user-agent", "curl/8.5.0"), ("accept", "*/*")]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["kind"], "cli");
+    assert_eq!(body["name"], "curl");
+    assert_eq!(body["version"], "8.5.0");
+    assert_eq!(body["confidence"], 0.9);
+    assert!(!body["signals"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_client_doubts_a_bare_browser_user_agent() {
+    let (_, body) = get(&[(
+        "user-agent",
+        "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0",
+    )])
+    .await;
+    assert_eq!(body["kind"], "browser");
+    assert_eq!(body["name"], "Firefox");
+    assert!(body["confidence"].as_f64().unwrap() < 0.5);
+}
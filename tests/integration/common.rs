@@ -7,30 +7,43 @@
 #![allow(dead_code)]
 
 use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, OnceLock};
 
+use arc_swap::ArcSwap;
 use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
 use tokio::sync::RwLock;
 
 use axum::Router;
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, Response, StatusCode};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
 
 use ipecho::config::Config;
+use ipecho::dual_stack::DualStack;
 use ipecho::lookup::IpLookupTable;
+use ipecho::ping::Pinger;
+use ipecho::portcheck::PortChecker;
 use ipecho::ratelimit::RateLimitState;
+use ipecho::rdns::ReverseDns;
+use ipecho::resolver_check::ResolverCheck;
 use ipecho::routes::create_router;
 use ipecho::state::AppState;
+use ipecho::traceroute::Tracer;
 
 /// Permissive defaults suitable for most tests. Individual tests can mutate
 /// the returned Config (e.g. shrinking rate limits to exercise rejection).
 pub fn test_config() -> Config {
     Config {
-        port: 8083,
-        sync_interval_secs: 43200,
-        log_level: "info".to_string(),
         trusted_proxies: vec!["10.0.0.0/8".parse().unwrap()],
         rate_limit_per_second: 100,
         rate_limit_burst: 100,
-        excluded_headers: vec![],
+        // Keep tests hermetic: no real DNS traffic.
+        rdns_enabled: false,
+        ..Config::default()
     }
 }
 
@@ -49,7 +62,8 @@ pub fn global_metrics_handle() -> PrometheusHandle {
     static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
     HANDLE
         .get_or_init(|| {
-            PrometheusBuilder::new()
+            ipecho::http_metrics::prometheus_builder()
+                .unwrap()
                 .install_recorder()
                 .expect("failed to install global Prometheus recorder for tests")
         })
@@ -72,8 +86,17 @@ pub fn test_state(
         lookup_table: Arc::new(RwLock::new(table)),
         sync_status: Arc::new(RwLock::new(vec![])),
         provider_records: Arc::new(RwLock::new(HashMap::new())),
-        config: Arc::new(config),
+        reverse_dns: Arc::new(ReverseDns::new(&config)),
+        port_checker: Arc::new(PortChecker::new(&config)),
+        pinger: Arc::new(Pinger::new(&config)),
+        tracer: Arc::new(Tracer::new(&config)),
+        dual_stack: Arc::new(DualStack::new(&config)),
+        resolver_check: Arc::new(ResolverCheck::new(&config)),
+        config: Arc::new(ArcSwap::from_pointee(config)),
         metrics_handle,
+        enricher: Arc::default(),
+        listener_bound: Arc::new(AtomicBool::new(true)),
+        access_log: None,
     }
 }
 
@@ -81,9 +104,34 @@ pub fn test_state(
 /// the state's config. Hides the two-argument router constructor from test
 /// call sites that don't care about driving eviction.
 pub fn build_router(state: AppState) -> Router {
-    let rl_state = RateLimitState::new(
-        state.config.rate_limit_per_second,
-        state.config.rate_limit_burst,
-    );
+    let config = state.config.load_full();
+    let rl_state = RateLimitState::new(config.rate_limit_per_second, config.rate_limit_burst);
     create_router(state, rl_state)
 }
+
+/// GET `uri` from `peer` on a fresh router built from `config`.
+pub async fn get_from(config: Config, uri: &str, peer: [u8; 4]) -> Response<Body> {
+    let app = build_router(test_state(
+        config,
+        throwaway_metrics_handle(),
+        IpLookupTable::empty(),
+    ));
+    let req = Request::builder()
+        .uri(uri)
+        .extension(ConnectInfo(SocketAddr::from((peer, 12345))))
+        .body(Body::empty())
+        .unwrap();
+    app.oneshot(req).await.unwrap()
+}
+
+/// Like [`get_from`], returning the status and the body parsed as JSON.
+pub async fn get_json_from(
+    config: Config,
+    uri: &str,
+    peer: [u8; 4],
+) -> (StatusCode, serde_json::Value) {
+    let response = get_from(config, uri, peer).await;
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap())
+}
@@ -0,0 +1,67 @@
+use std::net::SocketAddr;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+use ipecho::lookup::IpLookupTable;
+
+use super::common::{build_router, test_state_with_table};
+
+fn request(uri: &str, cookie: Option<&str>) -> Request<Body> {
+    let mut req = Request::builder().uri(uri);
+    if let Some(cookie) = cookie {
+        req = req.header("cookie", cookie);
+    }
+    req.extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_cookies_are_echoed() {
+    let app = build_router(test_state_with_table(IpLookupTable::empty()));
+    let response = app
+        .oneshot(request("/cookies", Some("session=abc; theme=dark")))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({"cookies": {"session": "abc", "theme": "dark"}})
+    );
+}
+
+#[tokio::test]
+async fn test_cookies_set_and_delete_redirect() {
+    let app = build_router(test_state_with_table(IpLookupTable::empty()));
+    let response = app
+        .clone()
+        .oneshot(request("/cookies/set?session=abc&theme=dark", None))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FOUND);
+    assert_eq!(response.headers()["location"], "../cookies");
+    let set: Vec<_> = response.headers().get_all("set-cookie").iter().collect();
+    assert_eq!(set, ["session=abc; Path=/", "theme=dark; Path=/"]);
+
+    let response = app
+        .clone()
+        .oneshot(request("/cookies/delete?session", None))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FOUND);
+    let set = response.headers()["set-cookie"].to_str().unwrap();
+    assert!(set.starts_with("session=; Path=/; Max-Age=0"), "{set}");
+
+    let response = app
+        .oneshot(request("/cookies/set?bad=a%3Bb", None))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
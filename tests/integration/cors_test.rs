@@ -0,0 +1,104 @@
+use std::net::SocketAddr;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Method, Request, StatusCode, header};
+use tower::ServiceExt;
+
+use ipecho::lookup::IpLookupTable;
+
+use super::common::{build_router, test_config, test_state, throwaway_metrics_handle};
+
+const ORIGIN: &str = "https://dash.example.com";
+
+fn cors_router(limit: u32) -> axum::Router {
+    let mut config = test_config();
+    config.cors_allowed_origins = vec![ORIGIN.into()];
+    config.rate_limit_per_second = limit.into();
+    config.rate_limit_burst = limit;
+    build_router(test_state(
+        config,
+        throwaway_metrics_handle(),
+        IpLookupTable::empty(),
+    ))
+}
+
+fn request(method: Method, uri: &str) -> Request<Body> {
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::ORIGIN, ORIGIN)
+        .header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+        .extension(ConnectInfo(SocketAddr::from(([203, 0, 113, 5], 4000))))
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_allowed_origin_can_read_json() {
+    let response = cors_router(100)
+        .oneshot(request(Method::GET, "/all.json"))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+        ORIGIN
+    );
+    assert_eq!(
+        response.headers()[header::ACCESS_CONTROL_EXPOSE_HEADERS],
+        "x-request-id"
+    );
+}
+
+#[tokio::test]
+async fn test_preflight_bypasses_rate_limit_and_errors_carry_cors() {
+    let app = cors_router(1);
+
+    let response = app
+        .clone()
+        .oneshot(request(Method::GET, "/all.json"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Preflights are answered before the rate limiter sees them.
+    for _ in 0..3 {
+        let response = app
+            .clone()
+            .oneshot(request(Method::OPTIONS, "/all.json"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    // A rate-limited response is still readable cross-origin.
+    let response = app
+        .oneshot(request(Method::GET, "/all.json"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(
+        response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+        ORIGIN
+    );
+}
+
+#[tokio::test]
+async fn test_cors_disabled_by_default() {
+    let app = build_router(test_state(
+        test_config(),
+        throwaway_metrics_handle(),
+        IpLookupTable::empty(),
+    ));
+    let response = app
+        .oneshot(request(Method::GET, "/all.json"))
+        .await
+        .unwrap();
+    assert!(
+        !response
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+    );
+}
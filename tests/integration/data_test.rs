@@ -0,0 +1,113 @@
+use std::net::SocketAddr;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+use ipecho::config::Config;
+use ipecho::lookup::IpLookupTable;
+
+use super::common::{build_router, test_config, test_state, throwaway_metrics_handle};
+
+async fn get(config: Config, uri: &str) -> (StatusCode, String, Vec<u8>) {
+    let app = build_router(test_state(
+        config,
+        throwaway_metrics_handle(),
+        IpLookupTable::empty(),
+    ));
+    let req = Request::builder()
+        .uri(uri)
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(req).await.unwrap();
+    let status = response.status();
+    let content_type = response.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, content_type, body.to_vec())
+}
+
+#[tokio::test]
+async fn test_bytes_are_capped_and_seedable() {
+    let (status, content_type, body) = get(test_config(), "/bytes/64").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "application/octet-stream");
+    assert_eq!(body.len(), 64);
+
+    let (_, _, first) = get(test_config(), "/bytes/32?seed=7").await;
+    let (_, _, second) = get(test_config(), "/bytes/32?seed=7").await;
+    assert_eq!(first, second);
+
+    let config = Config {
+        max_generated_bytes: 10,
+        ..test_config()
+    };
+    let (_, _, body) = get(config, "/bytes/1000").await;
+    assert_eq!(body.len(), 10);
+}
+
+#[tokio::test]
+async fn test_pad_is_exact_or_refused() {
+    for n in [0, 1, 1452, 1500] {
+        let (status, content_type, body) = get(test_config(), &format!("/pad/{n}")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type, "application/octet-stream");
+        assert_eq!(body.len(), n);
+    }
+
+    let config = Config {
+        max_generated_bytes: 10,
+        ..test_config()
+    };
+    let (status, _, _) = get(config, "/pad/11").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_stream_sends_json_lines() {
+    let (status, content_type, body) = get(test_config(), "/stream/3?x=1").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "application/x-ndjson");
+
+    let lines: Vec<serde_json::Value> = String::from_utf8(body)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 3);
+    for (id, line) in lines.iter().enumerate() {
+        assert_eq!(line["id"], id);
+        assert_eq!(line["origin"], "127.0.0.1");
+        assert_eq!(line["args"]["x"], "1");
+    }
+
+    let (_, _, body) = get(test_config(), "/stream/1000").await;
+    assert_eq!(String::from_utf8(body).unwrap().lines().count(), 100);
+}
+
+#[tokio::test]
+async fn test_drip_trickles_the_requested_bytes() {
+    let started = std::time::Instant::now();
+    let (status, _, body) = get(test_config(), "/drip?bytes=5&duration=0.5&delay=0.1").await;
+    let elapsed = started.elapsed();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, b"*****");
+    assert!(elapsed >= std::time::Duration::from_millis(600), "{elapsed:?}");
+
+    let config = Config {
+        max_delay_secs: 0,
+        ..test_config()
+    };
+    let started = std::time::Instant::now();
+    let (_, _, body) = get(config, "/drip?bytes=3&duration=60&delay=60").await;
+    assert_eq!(body, b"***");
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+
+    let (status, _, _) = get(test_config(), "/drip?duration=-1").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
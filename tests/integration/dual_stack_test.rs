@@ -0,0 +1,125 @@
+use std::net::SocketAddr;
+
+use axum::Router;
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+use ipecho::config::Config;
+use ipecho::lookup::IpLookupTable;
+
+use super::common::{build_router, test_config, test_state, throwaway_metrics_handle};
+
+fn app(origin: Option<&str>) -> Router {
+    let mut config = test_config();
+    config.dual_stack_origin = origin.map(String::from);
+    app_with(config)
+}
+
+fn app_with(config: Config) -> Router {
+    build_router(test_state(
+        config,
+        throwaway_metrics_handle(),
+        IpLookupTable::empty(),
+    ))
+}
+
+/// GET `uri` from 10.0.0.1, a trusted proxy, forwarding for `client`.
+async fn get(app: &Router, uri: &str, client: &str) -> (StatusCode, serde_json::Value) {
+    let req = Request::builder()
+        .uri(uri)
+        .header("x-forwarded-for", client)
+        .extension(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(req).await.unwrap();
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap_or_default())
+}
+
+#[tokio::test]
+async fn test_disabled_without_an_origin() {
+    let (status, body) = get(&app(None), "/ds/start", "203.0.113.5").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(
+        body["error"]
+            .as_str()
+            .unwrap()
+            .contains("DUAL_STACK_ORIGIN")
+    );
+}
+
+#[tokio::test]
+async fn test_correlates_both_families() {
+    let app = app(Some("https://example.com"));
+    let (status, start) = get(&app, "/ds/start", "203.0.113.5").await;
+    assert_eq!(status, StatusCode::OK);
+    let token = start["token"].as_str().unwrap();
+    assert_eq!(
+        start["ipv4_url"],
+        format!("https://ipv4.example.com/ds/{token}")
+    );
+    assert_eq!(
+        start["ipv6_url"],
+        format!("https://ipv6.example.com/ds/{token}")
+    );
+    assert_eq!(
+        start["result_url"],
+        format!("https://example.com/ds/{token}/result")
+    );
+    assert_eq!(start["expires_in_secs"], 300);
+
+    let result_uri = format!("/ds/{token}/result");
+    let (_, result) = get(&app, &result_uri, "203.0.113.5").await;
+    assert_eq!(result["ipv4"], serde_json::Value::Null);
+    assert_eq!(result["dual_stack"], false);
+
+    let (status, recorded) = get(&app, &format!("/ds/{token}"), "203.0.113.5").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(recorded["family"], "ipv4");
+    assert_eq!(recorded["ip"], "203.0.113.5");
+    let (_, recorded) = get(&app, &format!("/ds/{token}"), "2001:db8::5").await;
+    assert_eq!(recorded["family"], "ipv6");
+
+    let (status, result) = get(&app, &result_uri, "198.51.100.1").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(result["token"], token);
+    assert_eq!(result["ipv4"]["ip"], "203.0.113.5");
+    assert_eq!(result["ipv6"]["ip"], "2001:db8::5");
+    assert_eq!(result["dual_stack"], true);
+    // No ASN data without a GeoIP backend.
+    assert_eq!(result["same_asn"], serde_json::Value::Null);
+}
+
+#[tokio::test]
+async fn test_unknown_tokens_are_not_found() {
+    let app = app(Some("https://example.com"));
+    let (status, _) = get(&app, "/ds/0123456789abcdef", "203.0.113.5").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = get(&app, "/ds/0123456789abcdef/result", "203.0.113.5").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_expired_tokens_are_rejected() {
+    let mut config = test_config();
+    config.dual_stack_origin = Some("https://example.com".to_string());
+    // Every token is expired as soon as it's handed out.
+    config.dual_stack_ttl_secs = 0;
+    let app = app_with(config);
+    let (status, start) = get(&app, "/ds/start", "203.0.113.5").await;
+    assert_eq!(status, StatusCode::OK);
+    let token = start["token"].as_str().unwrap();
+
+    let (status, body) = get(&app, &format!("/ds/{token}"), "203.0.113.5").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(
+        body["error"],
+        "Not found: unknown or expired dual-stack token"
+    );
+    let (status, _) = get(&app, &format!("/ds/{token}/result"), "203.0.113.5").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
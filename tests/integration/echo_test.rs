@@ -1,9 +1,10 @@
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::sync::atomic::Ordering;
 
 use axum::body::Body;
 use axum::extract::ConnectInfo;
-use axum::http::{Request, StatusCode};
+use axum::http::{Request, StatusCode, Version};
 use http_body_util::BodyExt;
 use tokio::sync::RwLock;
 use tower::ServiceExt;
@@ -76,6 +77,7 @@ async fn test_echo_returns_json_with_headers() {
     assert_eq!(json["ip"], "127.0.0.1");
     assert!(json["provider"].is_null());
     assert!(json["region"].is_null());
+    assert!(json["hosting_provider"].is_null());
     assert!(json["headers"].is_object());
     // user_agent and host are available inside headers, not as top-level fields
     assert_eq!(json["headers"]["user-agent"], "test-agent/1.0");
@@ -100,6 +102,28 @@ async fn test_echo_with_provider_match() {
     assert_eq!(json["provider"], "aws");
     assert_eq!(json["region"], "us-east-1");
     assert_eq!(json["service"], "AMAZON");
+    assert_eq!(json["hosting_provider"], "Amazon Web Services");
+}
+
+#[tokio::test]
+async fn test_echo_behind_trusted_proxy_reports_client_and_peer() {
+    let state = test_state_with_table(seeded_lookup_table());
+    let app = build_router(state);
+
+    let req = Request::builder()
+        .uri("/")
+        .header("x-forwarded-for", "6.6.6.6, 3.0.0.1")
+        .extension(ConnectInfo(SocketAddr::from(([10, 0, 0, 5], 40000))))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(req).await.unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(json["ip"], "3.0.0.1", "rightmost untrusted hop wins");
+    assert_eq!(json["peer_addr"], "10.0.0.5:40000");
+    assert_eq!(json["provider"], "aws");
 }
 
 #[tokio::test]
@@ -164,6 +188,46 @@ async fn test_health_degraded_when_empty() {
     assert_eq!(json["status"], "degraded");
 }
 
+#[tokio::test]
+async fn test_healthz_is_always_ok() {
+    let state = test_state_with_table(IpLookupTable::empty());
+    let peer = SocketAddr::from(([127, 0, 0, 1], 12345));
+    let (status, body) = get_field_from(state, "/healthz", &[], peer).await;
+    assert_eq!(status, StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json, serde_json::json!({"status": "ok"}));
+}
+
+#[tokio::test]
+async fn test_readyz_reports_checks() {
+    let peer = SocketAddr::from(([127, 0, 0, 1], 12345));
+    let state = test_state_with_table(seeded_lookup_table());
+    let (status, body) = get_field_from(state, "/readyz", &[], peer).await;
+    assert_eq!(status, StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "status": "ready",
+            "checks": {"listener": true, "ip_ranges": true, "geoip": null},
+        })
+    );
+
+    let state = test_state_with_table(IpLookupTable::empty());
+    let (status, body) = get_field_from(state, "/readyz", &[], peer).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["status"], "not_ready");
+    assert_eq!(json["checks"]["ip_ranges"], false);
+
+    let state = test_state_with_table(seeded_lookup_table());
+    state.listener_bound.store(false, Ordering::Relaxed);
+    let (status, body) = get_field_from(state, "/readyz", &[], peer).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["checks"]["listener"], false);
+}
+
 // --- Per-field endpoint tests ---
 
 #[tokio::test]
@@ -219,6 +283,170 @@ async fn test_provider_endpoint_returns_204_when_unknown() {
     assert_eq!(response.status(), StatusCode::NO_CONTENT);
 }
 
+#[tokio::test]
+async fn test_ip_notation_endpoints() {
+    let peer = SocketAddr::from(([203, 0, 113, 1], 12345));
+    for (uri, expected) in [
+        ("/ip/decimal", "3405803777"),
+        ("/ip/hex", "0xcb007101"),
+        ("/ip/ptr", "1.113.0.203.in-addr.arpa"),
+    ] {
+        let state = test_state_with_table(IpLookupTable::empty());
+        let (status, body) = get_field_from(state, uri, &[], peer).await;
+        assert_eq!(status, StatusCode::OK, "{uri}");
+        assert_eq!(body, expected, "{uri}");
+    }
+
+    let state = test_state_with_table(IpLookupTable::empty());
+    let (status, _) = get_field_from(state, "/ip/expanded", &[], peer).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let state = test_state_with_table(IpLookupTable::empty());
+    let peer: SocketAddr = "[2001:db8::1]:12345".parse().unwrap();
+    let (_, body) = get_field_from(state, "/ip/expanded.json", &[], peer).await;
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({"ip_expanded": "2001:0db8:0000:0000:0000:0000:0000:0001"})
+    );
+}
+
+#[tokio::test]
+async fn test_ipv6_info_endpoint() {
+    let state = test_state_with_table(IpLookupTable::empty());
+    let peer: SocketAddr = "[2002:cb00:7101::211:22ff:fe33:4455]:12345".parse().unwrap();
+    let (status, body) = get_field_from(state, "/ipv6/info", &[], peer).await;
+    assert_eq!(status, StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "address": "2002:cb00:7101:0:211:22ff:fe33:4455",
+            "expanded": "2002:cb00:7101:0000:0211:22ff:fe33:4455",
+            "scope": "global",
+            "interface_id": "eui-64",
+            "mac": "00:11:22:33:44:55",
+            "embedded_ipv4": {"mechanism": "6to4", "address": "203.0.113.1"},
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_ipv6_info_is_no_content_for_ipv4_clients() {
+    let state = test_state_with_table(IpLookupTable::empty());
+    let peer: SocketAddr = "[::ffff:203.0.113.1]:12345".parse().unwrap();
+    let (status, body) = get_field_from(state, "/ipv6/info", &[], peer).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert!(body.is_empty());
+}
+
+#[tokio::test]
+async fn test_cidr_endpoint() {
+    let state = test_state_with_table(IpLookupTable::empty());
+    let peer = SocketAddr::from(([127, 0, 0, 1], 12345));
+    let (status, body) = get_field_from(state, "/cidr/203.0.113.5/28", &[], peer).await;
+    assert_eq!(status, StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["cidr"], "203.0.113.0/28");
+    assert_eq!(json["broadcast"], "203.0.113.15");
+    assert_eq!(json["first_host"], "203.0.113.1");
+    assert_eq!(json["last_host"], "203.0.113.14");
+    assert_eq!(json["usable_hosts"], "14");
+
+    let state = test_state_with_table(IpLookupTable::empty());
+    let (status, body) =
+        get_field_from(state, "/cidr/2001:db8::/126?format=text", &[], peer).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("network: 2001:db8::\n"), "{body}");
+    assert!(body.contains("addresses: 4\n"), "{body}");
+}
+
+#[tokio::test]
+async fn test_cidr_endpoint_rejects_invalid_prefix() {
+    let state = test_state_with_table(IpLookupTable::empty());
+    let peer = SocketAddr::from(([127, 0, 0, 1], 12345));
+    let (status, body) = get_field_from(state, "/cidr/10.0.0.0/33", &[], peer).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("invalid CIDR prefix"), "{body}");
+}
+
+#[tokio::test]
+async fn test_ip_class_endpoint() {
+    for (peer, expected) in [
+        (SocketAddr::from(([100, 72, 1, 2], 12345)), "cgnat"),
+        (SocketAddr::from(([192, 168, 1, 1], 12345)), "private"),
+        (SocketAddr::from(([127, 0, 0, 1], 12345)), "loopback"),
+        (SocketAddr::from(([8, 8, 8, 8], 12345)), "public"),
+    ] {
+        let state = test_state_with_table(IpLookupTable::empty());
+        let (status, body) = get_field_from(state, "/ip/class", &[], peer).await;
+        assert_eq!(status, StatusCode::OK, "{peer}");
+        assert_eq!(body, expected, "{peer}");
+    }
+}
+
+#[tokio::test]
+async fn test_ip_class_in_echo_response() {
+    let state = test_state_with_table(IpLookupTable::empty());
+    let (_, body) = get_field_from(
+        state,
+        "/ip/class.json",
+        &[("x-forwarded-for", "100.64.0.9")],
+        SocketAddr::from(([10, 0, 0, 2], 12345)),
+    )
+    .await;
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json, serde_json::json!({"ip_class": "cgnat"}));
+
+    let state = test_state_with_table(IpLookupTable::empty());
+    let (_, body) = get_field_from(
+        state,
+        "/all.json",
+        &[],
+        SocketAddr::from(([169, 254, 0, 1], 12345)),
+    )
+    .await;
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["ip_class"], "link-local");
+}
+
+#[tokio::test]
+async fn test_datacenter_endpoint_names_hosting_provider() {
+    let state = test_state_with_table(seeded_lookup_table());
+    let (status, body) = get_field_from(
+        state,
+        "/datacenter",
+        &[],
+        SocketAddr::from(([34, 0, 0, 1], 12345)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "Google Cloud");
+}
+
+#[tokio::test]
+async fn test_datacenter_endpoint_for_residential_ip() {
+    let state = test_state_with_table(seeded_lookup_table());
+    let (status, _) = get_field_from(
+        state.clone(),
+        "/datacenter",
+        &[],
+        SocketAddr::from(([192, 168, 1, 1], 12345)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (_, body) = get_field_from(
+        state,
+        "/datacenter.json",
+        &[],
+        SocketAddr::from(([192, 168, 1, 1], 12345)),
+    )
+    .await;
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json, serde_json::json!({"hosting_provider": null}));
+}
+
 #[tokio::test]
 async fn test_region_endpoint_with_match() {
     let state = test_state_with_table(seeded_lookup_table());
@@ -237,6 +465,40 @@ async fn test_region_endpoint_with_match() {
     assert_eq!(String::from_utf8(body.to_vec()).unwrap(), "us-central1");
 }
 
+#[tokio::test]
+async fn test_host_endpoint_returns_204_when_rdns_disabled() {
+    let state = test_state_with_table(IpLookupTable::empty());
+    let app = build_router(state);
+
+    let req = Request::builder()
+        .uri("/host")
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn test_echo_includes_null_remote_host_when_rdns_disabled() {
+    let state = test_state_with_table(IpLookupTable::empty());
+    let app = build_router(state);
+
+    let req = Request::builder()
+        .uri("/")
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(req).await.unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert!(json.get("remote_host").is_some());
+    assert!(json["remote_host"].is_null());
+}
+
 #[tokio::test]
 async fn test_service_endpoint_returns_204_when_unknown() {
     let state = test_state_with_table(IpLookupTable::empty());
@@ -258,7 +520,7 @@ async fn test_headers_endpoint_returns_json() {
     let app = build_router(state);
 
     let req = Request::builder()
-        .uri("/headers")
+        .uri("/headers.json")
         .header("x-custom", "hello")
         .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
         .body(Body::empty())
@@ -276,6 +538,54 @@ async fn test_headers_endpoint_returns_json() {
     assert_eq!(json["x-custom"], "hello");
 }
 
+#[tokio::test]
+async fn test_headers_text_preserves_order_and_duplicates() {
+    let state = test_state_with_table(IpLookupTable::empty());
+    let app = build_router(state);
+
+    let req = Request::builder()
+        .uri("/headers")
+        .header("x-zeta", "1")
+        .header("x-alpha", "2")
+        .header("x-zeta", "3")
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get("content-type").unwrap(), "text/plain");
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    // x-request-id is appended by the request ID middleware.
+    let text = std::str::from_utf8(&body).unwrap();
+    assert!(text.starts_with("x-zeta: 1\nx-zeta: 3\nx-alpha: 2\n"), "got: {text}");
+}
+
+#[tokio::test]
+async fn test_headers_json_keeps_order_and_repeated_values() {
+    let state = test_state_with_table(IpLookupTable::empty());
+    let app = build_router(state);
+
+    let req = Request::builder()
+        .uri("/headers.json")
+        .header("x-zeta", "1")
+        .header("x-alpha", "2")
+        .header("x-zeta", "3")
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(req).await.unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let text = std::str::from_utf8(&body).unwrap();
+    assert!(text.find("x-zeta").unwrap() < text.find("x-alpha").unwrap());
+
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["x-zeta"], serde_json::json!(["1", "3"]));
+    assert_eq!(json["x-alpha"], "2");
+}
+
 #[tokio::test]
 async fn test_header_by_name_returns_value() {
     let state = test_state_with_table(IpLookupTable::empty());
@@ -311,6 +621,70 @@ async fn test_header_by_name_returns_404_for_missing() {
     assert_eq!(response.status(), StatusCode::NOT_FOUND);
 }
 
+#[tokio::test]
+async fn test_forwarded_endpoint_returns_raw_header() {
+    let state = test_state_with_table(IpLookupTable::empty());
+    let app = build_router(state);
+
+    let req = Request::builder()
+        .uri("/forwarded")
+        .header("forwarded", "for=192.0.2.60;proto=https")
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(
+        String::from_utf8(body.to_vec()).unwrap(),
+        "for=192.0.2.60;proto=https"
+    );
+}
+
+#[tokio::test]
+async fn test_forwarded_json_returns_parsed_hops() {
+    let state = test_state_with_table(IpLookupTable::empty());
+    let app = build_router(state);
+
+    let req = Request::builder()
+        .uri("/forwarded.json")
+        .header("forwarded", r#"for="[2001:db8::17]:4711";proto=https, for=198.51.100.1;by=10.0.0.1"#)
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(json[0]["for"], "[2001:db8::17]:4711");
+    assert_eq!(json[0]["proto"], "https");
+    assert_eq!(json[1]["for"], "198.51.100.1");
+    assert_eq!(json[1]["by"], "10.0.0.1");
+}
+
+#[tokio::test]
+async fn test_echo_includes_forwarded_chain() {
+    let state = test_state_with_table(IpLookupTable::empty());
+    let app = build_router(state);
+
+    let req = Request::builder()
+        .uri("/")
+        .header("forwarded", "for=192.0.2.60;host=example.com")
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(req).await.unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(json["forwarded"][0]["for"], "192.0.2.60");
+    assert_eq!(json["forwarded"][0]["host"], "example.com");
+}
+
 // --- Header exclusion tests ---
 
 fn test_config_with_excluded_headers() -> Config {
@@ -360,7 +734,7 @@ async fn test_excluded_headers_omitted_from_headers_endpoint() {
     let app = build_router(state);
 
     let req = Request::builder()
-        .uri("/headers")
+        .uri("/headers.json")
         .header("accept", "*/*")
         .header("x-forwarded-host", "example.com")
         .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
@@ -411,3 +785,629 @@ async fn test_metrics_endpoint() {
         .unwrap()
         .contains("text/plain"));
 }
+
+#[tokio::test]
+async fn test_proto_reports_request_version() {
+    for (version, expected) in [
+        (Version::HTTP_10, "HTTP/1.0"),
+        (Version::HTTP_11, "HTTP/1.1"),
+        (Version::HTTP_2, "HTTP/2"),
+    ] {
+        let app = build_router(test_state_with_table(IpLookupTable::empty()));
+        let req = Request::builder()
+            .uri("/proto")
+            .version(version)
+            .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, expected);
+    }
+}
+
+#[tokio::test]
+async fn test_echo_includes_http_version() {
+    let app = build_router(test_state_with_table(IpLookupTable::empty()));
+    let req = Request::builder()
+        .uri("/")
+        .version(Version::HTTP_2)
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(req).await.unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["http_version"], "HTTP/2");
+}
+
+async fn get_with_accept(uri: &str, accept: &str) -> (String, String, String) {
+    let app = build_router(test_state_with_table(IpLookupTable::empty()));
+    let req = Request::builder()
+        .uri(uri)
+        .header("accept", accept)
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let header = |name: &str| {
+        response.headers().get(name).unwrap().to_str().unwrap().to_string()
+    };
+    let (content_type, vary) = (header("content-type"), header("vary"));
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (content_type, vary, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn test_echo_negotiates_html_for_browsers() {
+    let (content_type, vary, body) = get_with_accept(
+        "/",
+        "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+    )
+    .await;
+    assert_eq!(content_type, "text/html; charset=utf-8");
+    assert_eq!(vary, "accept, user-agent");
+    assert!(body.contains("<td>127.0.0.1</td>"));
+}
+
+#[tokio::test]
+async fn test_landing_page_links_api_and_copies_ip() {
+    for (uri, link) in [
+        ("/", "/ip.json"),
+        ("/all.html", "./ip.json"),
+        ("/?format=html", "/ip.json"),
+    ] {
+        let (content_type, _, body) = get_with_accept(uri, "text/html").await;
+        assert_eq!(content_type, "text/html; charset=utf-8");
+        assert!(body.contains("<code id=\"ip\">127.0.0.1</code>"), "{uri}");
+        assert!(body.contains("<th>http_version</th><td>HTTP/1.1</td>"), "{uri}");
+        assert!(body.contains("navigator.clipboard.writeText"));
+        assert!(body.contains(&format!("<a href=\"{link}\">")), "{uri}");
+    }
+}
+
+async fn get_with_user_agent(uri: &str, user_agent: &str, accept: &str) -> (String, String) {
+    let app = build_router(test_state_with_table(IpLookupTable::empty()));
+    let req = Request::builder()
+        .uri(uri)
+        .header("user-agent", user_agent)
+        .header("accept", accept)
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let content_type = response
+        .headers()
+        .get("content-type")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (content_type, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn test_cli_clients_get_bare_ip_at_root() {
+    for ua in ["curl/8.7.1", "Wget/1.21.4", "HTTPie/3.2.2"] {
+        let (content_type, body) = get_with_user_agent("/", ua, "*/*").await;
+        assert_eq!(content_type, "text/plain", "{ua}");
+        assert_eq!(body, "127.0.0.1", "{ua}");
+    }
+}
+
+#[tokio::test]
+async fn test_cli_clients_can_still_ask_for_json() {
+    let (content_type, body) = get_with_user_agent("/", "curl/8.7.1", "application/json").await;
+    assert_eq!(content_type, "application/json");
+    assert!(body.contains("\"peer_addr\""));
+
+    let (content_type, _) = get_with_user_agent("/?format=json", "curl/8.7.1", "*/*").await;
+    assert_eq!(content_type, "application/json");
+
+    let (content_type, _) = get_with_user_agent("/all.json", "curl/8.7.1", "*/*").await;
+    assert_eq!(content_type, "application/json");
+}
+
+#[tokio::test]
+async fn test_non_cli_clients_keep_json_default() {
+    let (content_type, _) = get_with_user_agent("/", "python-requests/2.32", "*/*").await;
+    assert_eq!(content_type, "application/json");
+}
+
+#[tokio::test]
+async fn test_echo_negotiates_plain_text() {
+    let (content_type, _, body) = get_with_accept("/", "text/plain").await;
+    assert_eq!(content_type, "text/plain");
+    assert!(body.starts_with("ip: 127.0.0.1\n"), "got: {body}");
+}
+
+#[tokio::test]
+async fn test_ip_negotiates_json() {
+    let (content_type, _, body) = get_with_accept("/ip", "application/json").await;
+    assert_eq!(content_type, "application/json");
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["ip"], "127.0.0.1");
+
+    let (content_type, _, body) = get_with_accept("/ip", "*/*").await;
+    assert_eq!(content_type, "text/plain");
+    assert_eq!(body, "127.0.0.1");
+}
+
+#[tokio::test]
+async fn test_format_query_selects_output() {
+    let (content_type, _, body) = get_with_accept("/?format=xml", "application/json").await;
+    assert_eq!(content_type, "application/xml");
+    assert!(body.contains("<ipecho><ip>127.0.0.1</ip>"), "got: {body}");
+
+    let (content_type, _, body) = get_with_accept("/?format=yaml", "*/*").await;
+    assert_eq!(content_type, "application/yaml");
+    assert!(body.starts_with("ip: 127.0.0.1\n"), "got: {body}");
+
+    let (content_type, _, body) = get_with_accept("/ip?format=csv", "*/*").await;
+    assert_eq!(content_type, "text/csv");
+    assert_eq!(body, "ip\r\n127.0.0.1\r\n");
+
+    let (content_type, _, body) = get_with_accept("/proto?format=json", "*/*").await;
+    assert_eq!(content_type, "application/json");
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["http_version"], "HTTP/1.1");
+}
+
+#[tokio::test]
+async fn test_format_query_on_field_endpoints() {
+    let state = test_state_with_table(seeded_lookup_table());
+    let app = build_router(state);
+
+    let req = Request::builder()
+        .uri("/region?format=json")
+        .extension(ConnectInfo(SocketAddr::from(([3, 1, 2, 3], 12345))))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["region"], "us-east-1");
+
+    // Unknown values are still 204 regardless of format.
+    let req = Request::builder()
+        .uri("/region?format=json")
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn test_unknown_format_is_bad_request() {
+    let app = build_router(test_state_with_table(IpLookupTable::empty()));
+    let req = Request::builder()
+        .uri("/?format=bogus")
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_all_xml_extension() {
+    let (content_type, _, body) = get_with_accept("/all.xml", "application/json").await;
+    assert_eq!(content_type, "application/xml");
+    assert!(body.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<ipecho>"));
+    assert!(body.contains("<http_version>HTTP/1.1</http_version>"), "got: {body}");
+    assert!(body.contains("<region/>"), "got: {body}");
+}
+
+#[tokio::test]
+async fn test_all_yaml_extension() {
+    for uri in ["/all.yaml", "/all.yml"] {
+        let (content_type, _, body) = get_with_accept(uri, "*/*").await;
+        assert_eq!(content_type, "application/yaml");
+        assert!(body.starts_with("ip: 127.0.0.1\n"), "got: {body}");
+        assert!(body.contains("\nhttp_version: HTTP/1.1\n"), "got: {body}");
+        assert!(body.contains("\nregion: null\n"), "got: {body}");
+    }
+}
+
+#[tokio::test]
+async fn test_all_unknown_extension_is_not_found() {
+    let app = build_router(test_state_with_table(IpLookupTable::empty()));
+    let req = Request::builder()
+        .uri("/all.exe")
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+async fn get_jsonp(uri: &str) -> (StatusCode, String, String) {
+    let app = build_router(test_state_with_table(IpLookupTable::empty()));
+    let req = Request::builder()
+        .uri(uri)
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(req).await.unwrap();
+    let status = response.status();
+    let content_type = response
+        .headers()
+        .get("content-type")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, content_type, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn test_ip_json_without_callback() {
+    let (status, content_type, body) = get_jsonp("/ip.json").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "application/json");
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json, serde_json::json!({"ip": "127.0.0.1"}));
+}
+
+#[tokio::test]
+async fn test_ip_json_with_callback() {
+    let (status, content_type, body) = get_jsonp("/ip.json?callback=showIp").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "application/javascript");
+    assert_eq!(body, "/**/showIp({\"ip\":\"127.0.0.1\"});");
+}
+
+#[tokio::test]
+async fn test_all_json_with_callback() {
+    let (status, content_type, body) = get_jsonp("/all.json?callback=app.onEcho").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "application/javascript");
+    let json = body
+        .strip_prefix("/**/app.onEcho(")
+        .and_then(|rest| rest.strip_suffix(");"))
+        .unwrap_or_else(|| panic!("got: {body}"));
+    let json: serde_json::Value = serde_json::from_str(json).unwrap();
+    assert_eq!(json["ip"], "127.0.0.1");
+}
+
+#[tokio::test]
+async fn test_callback_ignored_for_non_json_extension() {
+    let (status, content_type, _) = get_jsonp("/all.xml?callback=cb").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "application/xml");
+}
+
+#[tokio::test]
+async fn test_invalid_callback_is_bad_request() {
+    let (status, content_type, _) = get_jsonp("/ip.json?callback=alert(1)").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(content_type, "application/json");
+}
+
+async fn get_bytes(uri: &str, accept: &str) -> (String, Vec<u8>) {
+    let app = build_router(test_state_with_table(IpLookupTable::empty()));
+    let req = Request::builder()
+        .uri(uri)
+        .header("accept", accept)
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let content_type = response
+        .headers()
+        .get("content-type")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (content_type, body.to_vec())
+}
+
+#[tokio::test]
+async fn test_all_cbor() {
+    for (uri, accept) in [
+        ("/all.cbor", "*/*"),
+        ("/?format=cbor", "*/*"),
+        ("/", "application/cbor"),
+    ] {
+        let (content_type, body) = get_bytes(uri, accept).await;
+        assert_eq!(content_type, "application/cbor", "{uri}");
+        let json: serde_json::Value = ciborium::from_reader(body.as_slice()).unwrap();
+        assert_eq!(json["ip"], "127.0.0.1");
+        assert_eq!(json["http_version"], "HTTP/1.1");
+    }
+}
+
+#[tokio::test]
+async fn test_all_msgpack() {
+    for (uri, accept) in [
+        ("/all.msgpack", "*/*"),
+        ("/?format=msgpack", "*/*"),
+        ("/", "application/msgpack"),
+    ] {
+        let (content_type, body) = get_bytes(uri, accept).await;
+        assert_eq!(content_type, "application/msgpack", "{uri}");
+        let json: serde_json::Value = rmp_serde::from_slice(&body).unwrap();
+        assert_eq!(json["ip"], "127.0.0.1");
+        assert_eq!(json["http_version"], "HTTP/1.1");
+    }
+}
+
+#[tokio::test]
+async fn test_ip_cbor_is_smaller_than_json() {
+    let (_, cbor) = get_bytes("/ip?format=cbor", "*/*").await;
+    let (_, json) = get_bytes("/ip?format=json", "*/*").await;
+    assert!(cbor.len() < json.len());
+    let decoded: serde_json::Value = ciborium::from_reader(cbor.as_slice()).unwrap();
+    assert_eq!(decoded, serde_json::json!({"ip": "127.0.0.1"}));
+}
+
+async fn get_field(state: AppState, uri: &str, headers: &[(&str, &str)]) -> (StatusCode, String) {
+    get_field_from(state, uri, headers, SocketAddr::from(([127, 0, 0, 1], 12345))).await
+}
+
+async fn get_field_from(
+    state: AppState,
+    uri: &str,
+    headers: &[(&str, &str)],
+    peer: SocketAddr,
+) -> (StatusCode, String) {
+    let app = build_router(state);
+    let mut req = Request::builder().uri(uri);
+    for (name, value) in headers {
+        req = req.header(*name, *value);
+    }
+    let req = req.extension(ConnectInfo(peer)).body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(req).await.unwrap();
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn test_header_field_endpoints() {
+    let headers = [
+        ("user-agent", "test-agent/3.0"),
+        ("accept-language", "en-GB,en;q=0.8"),
+        ("accept-encoding", "gzip, br"),
+        ("accept", "*/*"),
+        ("accept-charset", "utf-8"),
+    ];
+    for (uri, expected) in [
+        ("/ua", "test-agent/3.0"),
+        ("/lang", "en-GB,en;q=0.8"),
+        ("/encoding", "gzip, br"),
+        ("/mime", "*/*"),
+        ("/charset", "utf-8"),
+    ] {
+        let state = test_state_with_table(IpLookupTable::empty());
+        let (status, body) = get_field(state, uri, &headers).await;
+        assert_eq!(status, StatusCode::OK, "{uri}");
+        assert_eq!(body, expected, "{uri}");
+    }
+}
+
+#[tokio::test]
+async fn test_field_json_endpoints() {
+    let headers = [("user-agent", "test-agent/3.0"), ("accept", "text/plain")];
+    for (uri, expected) in [
+        ("/ip.json", serde_json::json!({"ip": "127.0.0.1"})),
+        ("/proto.json", serde_json::json!({"http_version": "HTTP/1.1"})),
+        (
+            "/ua.json",
+            serde_json::json!({
+                "user_agent": "test-agent/3.0",
+                "browser": null,
+                "browser_version": null,
+                "os": null,
+                "os_version": null,
+                "device": null,
+                "vendor": null,
+            }),
+        ),
+        ("/provider.json", serde_json::json!({"provider": null})),
+        ("/lang.json", serde_json::json!({"accept_language": null})),
+    ] {
+        let state = test_state_with_table(IpLookupTable::empty());
+        let (status, body) = get_field(state, uri, &headers).await;
+        assert_eq!(status, StatusCode::OK, "{uri}");
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json, expected, "{uri}");
+    }
+}
+
+#[tokio::test]
+async fn test_field_json_with_provider_match() {
+    let state = test_state_with_table(seeded_lookup_table());
+    let (_, body) = get_field_from(
+        state,
+        "/region.json?callback=cb",
+        &[("x-forwarded-for", "3.5.140.1")],
+        SocketAddr::from(([10, 0, 0, 2], 12345)),
+    )
+    .await;
+    assert_eq!(body, "/**/cb({\"region\":\"us-east-1\"});");
+}
+
+#[tokio::test]
+async fn test_missing_header_field_is_no_content() {
+    let state = test_state_with_table(IpLookupTable::empty());
+    let (status, body) = get_field(state, "/lang", &[]).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert!(body.is_empty());
+}
+
+#[tokio::test]
+async fn test_excluded_header_field_is_hidden() {
+    let mut config = test_config();
+    config.excluded_headers = vec!["user-agent".to_string()];
+    let state = test_state(config, throwaway_metrics_handle(), IpLookupTable::empty());
+    let (status, body) = get_field(state, "/ua.json", &[("user-agent", "secret/1.0")]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.replace(char::is_whitespace, ""), "{\"user_agent\":null}");
+}
+
+#[tokio::test]
+async fn test_user_agent_is_parsed() {
+    let ua = [(
+        "user-agent",
+        "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0",
+    )];
+    let state = test_state_with_table(IpLookupTable::empty());
+    let (_, body) = get_field(state, "/ua.json", &ua).await;
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["user_agent"], ua[0].1);
+    assert_eq!(json["browser"], "Firefox");
+    assert_eq!(json["browser_version"], "128.0");
+    assert_eq!(json["os"], "Linux");
+    assert_eq!(json["device"], "desktop");
+
+    let state = test_state_with_table(IpLookupTable::empty());
+    let (_, body) = get_field(state, "/all.json", &ua).await;
+    let all: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(all["user_agent"]["browser"], "Firefox");
+    assert_eq!(all["headers"]["user-agent"], ua[0].1);
+}
+
+#[tokio::test]
+async fn test_accept_language_is_parsed_and_matched() {
+    let mut config = test_config();
+    config.supported_languages = vec!["en".to_string(), "de-DE".to_string()];
+    let state = test_state(config, throwaway_metrics_handle(), IpLookupTable::empty());
+    let (status, body) = get_field(
+        state,
+        "/lang.json",
+        &[("accept-language", "fr-CH, fr;q=0.9, de;q=0.7, *;q=0.5")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["accept_language"], "fr-CH, fr;q=0.9, de;q=0.7, *;q=0.5");
+    assert_eq!(
+        json["languages"],
+        serde_json::json!([
+            {"tag": "fr-CH", "q": 1.0},
+            {"tag": "fr", "q": 0.9},
+            {"tag": "de", "q": 0.7},
+            {"tag": "*", "q": 0.5},
+        ])
+    );
+    assert_eq!(json["best_match"], "de-DE");
+}
+
+#[tokio::test]
+async fn test_accept_is_parsed() {
+    let state = test_state_with_table(IpLookupTable::empty());
+    let accept = "text/html,application/xml;q=0.9,text/plain;charset=utf-8,*/*;q=0.8";
+    let (_, body) = get_field(state, "/mime.json", &[("accept", accept)]).await;
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["accept"], accept);
+    assert_eq!(
+        json["media_types"],
+        serde_json::json!([
+            {"media_type": "text/plain", "params": {"charset": "utf-8"}, "q": 1.0},
+            {"media_type": "text/html", "params": {}, "q": 1.0},
+            {"media_type": "application/xml", "params": {}, "q": 0.9},
+            {"media_type": "*/*", "params": {}, "q": 0.8},
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_port_endpoints() {
+    let state = test_state_with_table(IpLookupTable::empty());
+    let (status, body) = get_field(state, "/port", &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "12345");
+
+    let state = test_state_with_table(IpLookupTable::empty());
+    let (_, body) = get_field(state, "/port.json", &[]).await;
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json, serde_json::json!({"client_port": 12345}));
+
+    let state = test_state_with_table(IpLookupTable::empty());
+    let (_, body) = get_field(state, "/all.json", &[]).await;
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["client_port"], 12345);
+    assert!(json["server_port"].is_null());
+}
+
+#[tokio::test]
+async fn test_port_behind_proxy_without_port_is_no_content() {
+    let state = test_state_with_table(IpLookupTable::empty());
+    let (status, _) = get_field_from(
+        state,
+        "/port",
+        &[("x-forwarded-for", "203.0.113.7")],
+        SocketAddr::from(([10, 0, 0, 2], 443)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn test_fetch_metadata() {
+    let sec_fetch = [
+        ("sec-fetch-site", "cross-site"),
+        ("sec-fetch-mode", "cors"),
+        ("sec-fetch-dest", "empty"),
+    ];
+    let state = test_state_with_table(IpLookupTable::empty());
+    let (status, body) = get_field(state, "/fetch-metadata", &sec_fetch).await;
+    assert_eq!(status, StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({"site": "cross-site", "mode": "cors", "dest": "empty", "user": null})
+    );
+
+    let state = test_state_with_table(IpLookupTable::empty());
+    let (_, body) = get_field(state, "/all.json", &sec_fetch).await;
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["fetch_metadata"]["site"], "cross-site");
+
+    let state = test_state_with_table(IpLookupTable::empty());
+    let (_, body) = get_field(state, "/all.json", &[]).await;
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert!(json["fetch_metadata"].is_null());
+}
+
+#[tokio::test]
+async fn test_privacy_signals() {
+    let state = test_state_with_table(IpLookupTable::empty());
+    let (status, body) = get_field(state, "/privacy", &[("sec-gpc", "1")]).await;
+    assert_eq!(status, StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json, serde_json::json!({"dnt": null, "gpc": true, "opted_out": true}));
+
+    let state = test_state_with_table(IpLookupTable::empty());
+    let (_, body) = get_field(state, "/all.json", &[("dnt", "1")]).await;
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["privacy_opt_out"], true);
+
+    let mut config = test_config();
+    config.excluded_headers = vec!["dnt".to_string()];
+    let state = test_state(config, throwaway_metrics_handle(), IpLookupTable::empty());
+    let (_, body) = get_field(state, "/all.json", &[("dnt", "1")]).await;
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["privacy_opt_out"], false);
+}
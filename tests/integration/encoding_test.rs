@@ -0,0 +1,57 @@
+use std::net::SocketAddr;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+use ipecho::lookup::IpLookupTable;
+
+use super::common::{build_router, test_state_with_table};
+
+async fn send(method: &str, uri: &str, body: &'static str) -> (StatusCode, String, Vec<u8>) {
+    let app = build_router(test_state_with_table(IpLookupTable::empty()));
+    let req = Request::builder()
+        .method(method)
+        .uri(uri)
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::from(body))
+        .unwrap();
+    let response = app.oneshot(req).await.unwrap();
+    let status = response.status();
+    let content_type = response
+        .headers()
+        .get("content-type")
+        .map(|v| v.to_str().unwrap().to_string())
+        .unwrap_or_default();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, content_type, body.to_vec())
+}
+
+#[tokio::test]
+async fn test_base64_encode_path_and_body() {
+    let (status, _, body) = send("GET", "/base64/encode/hello%20world", "").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, b"aGVsbG8gd29ybGQ=");
+
+    let (_, _, body) = send("POST", "/base64/encode?alphabet=url", "??>>").await;
+    assert_eq!(body, b"Pz8-Pg==");
+}
+
+#[tokio::test]
+async fn test_base64_decode_path_and_body() {
+    let (status, content_type, body) = send("GET", "/base64/decode/aGVsbG8gd29ybGQ", "").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "text/plain");
+    assert_eq!(body, b"hello world");
+
+    let (_, content_type, body) = send("POST", "/base64/decode", "//8=\n").await;
+    assert_eq!(content_type, "application/octet-stream");
+    assert_eq!(body, [0xff, 0xff]);
+
+    let (status, _, _) = send("GET", "/base64/decode/not*base64", "").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _, _) = send("GET", "/base64/decode/aGk?alphabet=morse", "").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,375 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode};
+use http_body_util::BodyExt;
+use mmdb_writer::{Value, Writer};
+use tower::ServiceExt;
+use wiremock::matchers::{header, header_exists, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use ipecho::geoip::IpEnricher;
+use ipecho::geoip::api::{Api, HttpApi};
+use ipecho::geoip::ip2location::Ip2Location;
+use ipecho::geoip::maxmind::{AsnDb, GeoIp, MaxMind};
+use ipecho::geoip::update::Updater;
+use ipecho::lookup::IpLookupTable;
+use ipecho::state::AppState;
+
+use super::common::{build_router, test_state_with_table};
+
+/// A City database covering 203.0.113.0/24 (Berlin) only.
+fn city_db_bytes() -> Vec<u8> {
+    let names = |en: &str| Value::map([("en", Value::from(en))]);
+    let mut writer = Writer::new("GeoIP2-City");
+    writer
+        .insert_value(
+            "203.0.113.0/24".parse::<ipnet::IpNet>().unwrap(),
+            Value::map([
+                (
+                    "country",
+                    Value::map([("iso_code", Value::from("DE")), ("names", names("Germany"))]),
+                ),
+                ("city", Value::map([("names", names("Berlin"))])),
+                (
+                    "location",
+                    Value::map([
+                        ("latitude", Value::from(52.52_f64)),
+                        ("longitude", Value::from(13.405_f64)),
+                        ("time_zone", Value::from("Europe/Berlin")),
+                    ]),
+                ),
+            ]),
+        )
+        .unwrap();
+    writer.to_bytes().unwrap()
+}
+
+fn test_geoip() -> GeoIp {
+    GeoIp::from_bytes(city_db_bytes()).unwrap()
+}
+
+/// An ASN database covering 203.0.113.0/24 only.
+fn test_asn_db() -> AsnDb {
+    let mut writer = Writer::new("GeoLite2-ASN");
+    writer
+        .insert_value(
+            "203.0.113.0/24".parse::<ipnet::IpNet>().unwrap(),
+            Value::map([
+                ("autonomous_system_number", Value::from(64500_u32)),
+                ("autonomous_system_organization", Value::from("Example Net")),
+            ]),
+        )
+        .unwrap();
+    AsnDb::from_bytes(writer.to_bytes().unwrap()).unwrap()
+}
+
+fn test_state_with(enricher: Arc<dyn IpEnricher>) -> AppState {
+    test_state_with_table(IpLookupTable::empty()).with_enricher(Some(enricher))
+}
+
+fn test_state_with_geoip() -> AppState {
+    test_state_with(Arc::new(MaxMind::new(
+        Some(test_geoip()),
+        Some(test_asn_db()),
+    )))
+}
+
+/// GET `uri` as a client at `ip`, seen through a trusted proxy.
+async fn get_as(state: AppState, uri: &str, ip: &str) -> (StatusCode, String) {
+    let app = build_router(state);
+    let req = Request::builder()
+        .uri(uri)
+        .header("x-forwarded-for", ip)
+        .extension(ConnectInfo(SocketAddr::from(([10, 0, 0, 2], 12345))))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(req).await.unwrap();
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn test_geo_endpoint_returns_location() {
+    let (status, body) = get_as(test_state_with_geoip(), "/geo", "203.0.113.7").await;
+    assert_eq!(status, StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "country_code": "DE",
+            "country": "Germany",
+            "region_code": null,
+            "region": null,
+            "city": "Berlin",
+            "latitude": 52.52,
+            "longitude": 13.405,
+            "timezone": "Europe/Berlin",
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_geo_endpoint_negotiates_format() {
+    let (status, body) = get_as(test_state_with_geoip(), "/geo?format=text", "203.0.113.7").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("Berlin"), "{body}");
+}
+
+#[tokio::test]
+async fn test_country_and_city_endpoints() {
+    let (status, body) = get_as(test_state_with_geoip(), "/country", "203.0.113.7").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "DE");
+
+    let (status, body) = get_as(test_state_with_geoip(), "/city", "203.0.113.7").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "Berlin");
+
+    let (_, body) = get_as(test_state_with_geoip(), "/country.json", "203.0.113.7").await;
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json, serde_json::json!({"country_code": "DE"}));
+}
+
+#[tokio::test]
+async fn test_geo_endpoints_no_content_for_unknown_address() {
+    for uri in ["/geo", "/country", "/city", "/asn", "/isp"] {
+        let (status, body) = get_as(test_state_with_geoip(), uri, "198.51.100.1").await;
+        assert_eq!(status, StatusCode::NO_CONTENT, "{uri}");
+        assert!(body.is_empty(), "{uri}");
+    }
+}
+
+#[tokio::test]
+async fn test_geo_endpoints_no_content_without_database() {
+    for uri in ["/geo", "/country", "/city", "/asn", "/isp"] {
+        let state = test_state_with_table(IpLookupTable::empty());
+        let (status, _) = get_as(state, uri, "203.0.113.7").await;
+        assert_eq!(status, StatusCode::NO_CONTENT, "{uri}");
+    }
+}
+
+#[tokio::test]
+async fn test_echo_includes_geo() {
+    let (_, body) = get_as(test_state_with_geoip(), "/all.json", "203.0.113.7").await;
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["geo"]["country_code"], "DE");
+    assert_eq!(json["geo"]["timezone"], "Europe/Berlin");
+    assert_eq!(json["asn"]["number"], 64500);
+
+    let state = test_state_with_table(IpLookupTable::empty());
+    let (_, body) = get_as(state, "/all.json", "203.0.113.7").await;
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert!(json["geo"].is_null());
+    assert!(json["asn"].is_null());
+}
+
+#[tokio::test]
+async fn test_asn_endpoint_returns_number_org_and_prefix() {
+    let (status, body) = get_as(test_state_with_geoip(), "/asn", "203.0.113.7").await;
+    assert_eq!(status, StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "number": 64500,
+            "organization": "Example Net",
+            "prefix": "203.0.113.0/24",
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_isp_endpoint() {
+    let (status, body) = get_as(test_state_with_geoip(), "/isp", "203.0.113.7").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "Example Net");
+
+    let (_, body) = get_as(test_state_with_geoip(), "/isp.json", "203.0.113.7").await;
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json, serde_json::json!({"isp": "Example Net"}));
+}
+
+/// The download service's archive layout: a dated directory holding the
+/// database next to license files.
+fn tar_gz(mmdb: &[u8]) -> Vec<u8> {
+    let gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+    let mut builder = tar::Builder::new(gz);
+    for (name, data) in [
+        ("GeoLite2-City_20260101/COPYRIGHT.txt", &b"copyright"[..]),
+        ("GeoLite2-City_20260101/GeoLite2-City.mmdb", mmdb),
+    ] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, name, data).unwrap();
+    }
+    builder.into_inner().unwrap().finish().unwrap()
+}
+
+/// A `GeoLite2-City.mmdb` path in a fresh temp dir.
+fn city_db_path(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("ipecho-geoip-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join("GeoLite2-City.mmdb")
+}
+
+fn updater(server: &MockServer, db: Arc<MaxMind>) -> Updater {
+    Updater::new(&server.uri(), "12345".into(), "secret".into(), db)
+}
+
+const CITY_DOWNLOAD: &str = "/geoip/databases/GeoLite2-City/download";
+
+#[tokio::test]
+async fn test_update_downloads_missing_database() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(CITY_DOWNLOAD))
+        // Basic auth for 12345:secret.
+        .and(header("authorization", "Basic MTIzNDU6c2VjcmV0"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(tar_gz(&city_db_bytes())))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let db_path = city_db_path("download");
+    let db = Arc::new(MaxMind::open(Some(db_path.clone()), None, true).unwrap());
+    assert!(!db.is_ready(), "not ready until the first download");
+    updater(&server, db.clone()).refresh().await;
+    assert!(db.is_ready());
+
+    assert_eq!(std::fs::read(&db_path).unwrap(), city_db_bytes());
+    let (status, body) = get_as(test_state_with(db), "/country", "203.0.113.7").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "DE");
+}
+
+#[tokio::test]
+async fn test_update_is_conditional_once_loaded() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(CITY_DOWNLOAD))
+        .and(header_exists("if-modified-since"))
+        .respond_with(ResponseTemplate::new(304))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let db_path = city_db_path("unchanged");
+    std::fs::write(&db_path, city_db_bytes()).unwrap();
+    let db = Arc::new(MaxMind::open(Some(db_path), None, true).unwrap());
+
+    updater(&server, db.clone()).refresh().await;
+    let (_, body) = get_as(test_state_with(db), "/country", "203.0.113.7").await;
+    assert_eq!(body, "DE");
+}
+
+#[tokio::test]
+async fn test_update_keeps_current_database_on_bad_download() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(CITY_DOWNLOAD))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(tar_gz(b"not a database")))
+        .mount(&server)
+        .await;
+
+    let db_path = city_db_path("invalid");
+    std::fs::write(&db_path, city_db_bytes()).unwrap();
+    let db = Arc::new(MaxMind::open(Some(db_path.clone()), None, true).unwrap());
+    updater(&server, db.clone()).refresh().await;
+
+    assert_eq!(std::fs::read(&db_path).unwrap(), city_db_bytes());
+    assert!(!db_path.with_extension("mmdb.tmp").exists());
+    let (_, body) = get_as(test_state_with(db), "/country", "203.0.113.7").await;
+    assert_eq!(body, "DE");
+}
+
+#[tokio::test]
+async fn test_ip2location_backend() {
+    // 3405803776 is 203.0.113.0.
+    let csv = r#""3405803776","3405804031","AU","Australia","Queensland","Brisbane","-27.467940","153.028090","4000","+10:00""#;
+    let db = Ip2Location::from_reader(csv.as_bytes()).unwrap();
+    let state = test_state_with(Arc::new(db));
+
+    let (_, body) = get_as(state.clone(), "/country", "203.0.113.7").await;
+    assert_eq!(body, "AU");
+    let (_, body) = get_as(state.clone(), "/all.json", "203.0.113.7").await;
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["geo"]["city"], "Brisbane");
+    assert_eq!(json["geo"]["timezone"], "+10:00");
+    assert!(json["asn"].is_null());
+    let (status, _) = get_as(state, "/isp", "203.0.113.7").await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn test_ipinfo_backend_is_cached() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/8.8.8.8/json"))
+        .and(header("authorization", "Bearer token"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(
+            r#"{"ip":"8.8.8.8","city":"Mountain View","region":"California","country":"US",
+                "loc":"37.4056,-122.0775","org":"AS15169 Google LLC","timezone":"America/Los_Angeles"}"#,
+        ))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let api = HttpApi::new(Api::IpInfo, &server.uri(), Some("token".into()));
+    let state = test_state_with(Arc::new(api));
+    let (_, body) = get_as(state.clone(), "/country", "8.8.8.8").await;
+    assert_eq!(body, "US");
+    let (_, body) = get_as(state, "/isp", "8.8.8.8").await;
+    assert_eq!(body, "Google LLC");
+}
+
+#[tokio::test]
+async fn test_ipapi_backend_errors_are_not_cached() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/8.8.8.8/json/"))
+        .and(query_param("key", "token"))
+        .respond_with(ResponseTemplate::new(429))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/8.8.8.8/json/"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(
+            r#"{"ip":"8.8.8.8","country_code":"US","country_name":"United States","asn":"AS15169","org":"GOOGLE"}"#,
+        ))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let api = HttpApi::new(Api::IpApi, &server.uri(), Some("token".into()));
+    let state = test_state_with(Arc::new(api));
+    let (status, _) = get_as(state.clone(), "/country", "8.8.8.8").await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (_, body) = get_as(state, "/asn", "8.8.8.8").await;
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["number"], 15169);
+    assert_eq!(json["organization"], "GOOGLE");
+}
+
+#[tokio::test]
+async fn test_api_backend_skips_private_addresses() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let api = HttpApi::new(Api::IpInfo, &server.uri(), None);
+    let (status, _) = get_as(test_state_with(Arc::new(api)), "/geo", "192.168.1.10").await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+}
@@ -0,0 +1,55 @@
+use std::net::SocketAddr;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+use ipecho::lookup::IpLookupTable;
+
+use super::common::{build_router, test_state_with_table};
+
+async fn post(uri: &str, body: &'static [u8]) -> (StatusCode, String) {
+    let app = build_router(test_state_with_table(IpLookupTable::empty()));
+    let req = Request::builder()
+        .method("POST")
+        .uri(uri)
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::from(body))
+        .unwrap();
+    let response = app.oneshot(req).await.unwrap();
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn test_hash_json() {
+    let (status, body) = post("/hash/sha256", b"abc").await;
+    assert_eq!(status, StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["algorithm"], "sha256");
+    assert_eq!(json["bytes"], 3);
+    assert_eq!(
+        json["hex"],
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    assert_eq!(
+        json["base64"],
+        "ungWv48Bz+pBQUDeXa4iI7ADYaOWF3qctBD/YfIAFa0="
+    );
+}
+
+#[tokio::test]
+async fn test_hash_text() {
+    let (status, body) = post("/hash/md5?format=text", b"").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.trim(), "d41d8cd98f00b204e9800998ecf8427e");
+}
+
+#[tokio::test]
+async fn test_hash_unknown_algorithm() {
+    let (status, _) = post("/hash/sha512", b"abc").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,74 @@
+use std::net::SocketAddr;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{HeaderMap, Request, StatusCode};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+use ipecho::lookup::IpLookupTable;
+
+use super::common::{build_router, test_state_with_table};
+
+async fn get(uri: &str, headers: &[(&str, &str)]) -> (StatusCode, HeaderMap, Vec<u8>) {
+    let app = build_router(test_state_with_table(IpLookupTable::empty()));
+    let mut req = Request::builder()
+        .uri(uri)
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))));
+    for (name, value) in headers {
+        req = req.header(*name, *value);
+    }
+    let response = app.oneshot(req.body(Body::empty()).unwrap()).await.unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, headers, body.to_vec())
+}
+
+#[tokio::test]
+async fn test_hints_decodes_sec_ch_ua_headers() {
+    let (status, _, body) = get(
+        "/hints",
+        &[
+            ("sec-ch-ua", r#""Chromium";v="126", "Not-A.Brand";v="8""#),
+            ("sec-ch-ua-mobile", "?1"),
+            ("sec-ch-ua-platform", r#""Android""#),
+            ("sec-ch-ua-model", r#""Pixel 8""#),
+            ("user-agent", "Mozilla/5.0"),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "brands": [
+                { "brand": "Chromium", "version": "126" },
+                { "brand": "Not-A.Brand", "version": "8" },
+            ],
+            "mobile": true,
+            "platform": "Android",
+            "model": "Pixel 8",
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_hints_is_empty_without_client_hints() {
+    let (status, _, body) = get("/hints", &[("user-agent", "curl/8.5.0")]).await;
+    assert_eq!(status, StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body, serde_json::json!({}));
+}
+
+#[tokio::test]
+async fn test_html_responses_request_high_entropy_hints() {
+    let (_, headers, _) = get("/", &[("accept", "text/html")]).await;
+    let accept_ch = headers["accept-ch"].to_str().unwrap();
+    assert!(accept_ch.contains("Sec-CH-UA-Model"));
+    assert!(accept_ch.contains("Sec-CH-UA-Full-Version-List"));
+
+    let (_, headers, _) = get("/", &[("accept", "application/json")]).await;
+    assert!(!headers.contains_key("accept-ch"));
+}
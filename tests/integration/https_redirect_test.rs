@@ -0,0 +1,52 @@
+use std::net::SocketAddr;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode, header};
+use tower::ServiceExt;
+
+use ipecho::https_redirect::HttpsRedirect;
+use ipecho::lookup::IpLookupTable;
+
+use super::common::{build_router, test_config, test_state, throwaway_metrics_handle};
+
+fn request(uri: &str, redirect: bool) -> Request<Body> {
+    let mut req = Request::builder()
+        .uri(uri)
+        .header(header::HOST, "echo.example.com")
+        .extension(ConnectInfo(SocketAddr::from(([203, 0, 113, 5], 4000))));
+    if redirect {
+        req = req.extension(HttpsRedirect { port: 443 });
+    }
+    req.body(Body::empty()).unwrap()
+}
+
+#[tokio::test]
+async fn test_plain_http_redirects_except_exempt_paths() {
+    let mut config = test_config();
+    config.tls_cert = Some("cert.pem".into());
+    config.tls_key = Some("key.pem".into());
+    let app = build_router(test_state(
+        config,
+        throwaway_metrics_handle(),
+        IpLookupTable::empty(),
+    ));
+
+    let response = app
+        .clone()
+        .oneshot(request("/all.json?x=1", true))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(
+        response.headers()[header::LOCATION],
+        "https://echo.example.com/all.json?x=1"
+    );
+
+    let response = app.clone().oneshot(request("/ip", true)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The HTTPS listener's requests carry no marker and are served.
+    let response = app.oneshot(request("/all.json", false)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
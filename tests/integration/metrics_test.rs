@@ -3,10 +3,12 @@ use std::net::SocketAddr;
 use axum::body::Body;
 use axum::extract::ConnectInfo;
 use axum::http::{Request, StatusCode};
+use http_body_util::BodyExt;
 use tower::ServiceExt;
 
 use ipecho::lookup::IpLookupTable;
 use ipecho::providers::ProviderRecord;
+use ipecho::routes::admin_router;
 use ipecho::state::AppState;
 
 use super::common::{build_router, global_metrics_handle, test_config, test_state};
@@ -113,3 +115,76 @@ async fn test_rate_limit_rejected_counter_incremented() {
     let metrics_output = state.metrics_handle.render();
     assert!(metrics_output.contains("rate_limit_rejected_total"), "Expected rate_limit_rejected_total in metrics, got: {}", metrics_output);
 }
+
+#[tokio::test]
+async fn test_responses_counted_by_route_pattern_and_status() {
+    let state = test_state_with_table(IpLookupTable::empty());
+    let app = build_router(state.clone());
+
+    let req = Request::builder()
+        .uri("/headers/x-metrics-test-missing")
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let metrics_output = state.metrics_handle.render();
+    assert!(
+        metrics_output.contains(
+            r#"http_responses_total{method="GET",route="/headers/{name}",status="404"}"#
+        ),
+        "Expected labelled http_responses_total in metrics, got: {}",
+        metrics_output
+    );
+    assert!(
+        metrics_output.contains(
+            r#"http_request_duration_seconds_bucket{method="GET",route="/headers/{name}",le="#
+        ),
+        "Expected http_request_duration_seconds histogram in metrics, got: {}",
+        metrics_output
+    );
+}
+
+#[tokio::test]
+async fn test_response_body_bytes_counted() {
+    let state = test_state_with_table(IpLookupTable::empty());
+    let app = build_router(state.clone());
+
+    let req = Request::builder()
+        .uri("/ip/hex")
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.clone().oneshot(req).await.unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(&body[..], b"0x7f000001");
+
+    let metrics_output = state.metrics_handle.render();
+    assert!(
+        metrics_output.contains(r#"http_response_body_bytes_total{route="/ip/hex"} 10"#),
+        "Expected http_response_body_bytes_total in metrics, got: {}",
+        metrics_output
+    );
+}
+
+#[tokio::test]
+async fn test_metrics_moves_to_admin_router_with_metrics_addr() {
+    let mut config = test_config();
+    config.metrics_addr = Some("127.0.0.1:9090".parse().unwrap());
+    let state = test_state(config, global_metrics_handle(), IpLookupTable::empty());
+
+    let req = Request::builder()
+        .uri("/metrics")
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+    let response = build_router(state.clone()).oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let req = Request::builder().uri("/metrics").body(Body::empty()).unwrap();
+    let response = admin_router(state).oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
@@ -1,7 +1,34 @@
 mod common;
 
+mod access_log_test;
+mod anything_test;
 mod app_error_test;
+mod auth_test;
+mod body_test;
+mod client_test;
+mod cookies_test;
+mod cors_test;
+mod data_test;
+mod dual_stack_test;
 mod echo_test;
+mod encoding_test;
+mod geoip_test;
+mod hash_test;
+mod hints_test;
+mod https_redirect_test;
+mod jwt_test;
 mod metrics_test;
+mod ping_route_test;
+mod ping_test;
+mod portcheck_test;
 mod provider_test;
 mod ratelimit_test;
+mod raw_test;
+mod redirect_test;
+mod resolver_check_test;
+mod router_test;
+mod security_headers_test;
+mod speedtest_test;
+mod time_test;
+mod traceroute_test;
+mod uuid_test;
@@ -0,0 +1,67 @@
+use std::net::SocketAddr;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode, header};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+use ipecho::lookup::IpLookupTable;
+
+use super::common::{build_router, test_config, test_state, throwaway_metrics_handle};
+
+#[tokio::test]
+async fn test_ping_is_one_byte_and_skips_rate_limiting() {
+    let mut config = test_config();
+    config.rate_limit_per_second = 1;
+    config.rate_limit_burst = 1;
+    let app = build_router(test_state(
+        config,
+        throwaway_metrics_handle(),
+        IpLookupTable::empty(),
+    ));
+    for _ in 0..3 {
+        let req = Request::builder()
+            .uri("/ping")
+            .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        // Outside the middleware, so no request ID either.
+        assert!(!response.headers().contains_key("x-request-id"));
+        assert!(!response.headers().contains_key("x-server-time-ms"));
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"1");
+    }
+}
+
+#[tokio::test]
+async fn test_ping_echoes_ts() {
+    let app = build_router(test_state(
+        test_config(),
+        throwaway_metrics_handle(),
+        IpLookupTable::empty(),
+    ));
+    let req = Request::builder()
+        .uri("/ping?ts=1792040646123")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["x-ping-ts"], "1792040646123");
+    let server_ms: u64 = response.headers()["x-server-time-ms"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(server_ms > 1_700_000_000_000);
+    assert_eq!(response.headers()[header::CONTENT_LENGTH], "1");
+
+    let req = Request::builder()
+        .uri(format!("/ping?ts={}", "9".repeat(65)))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
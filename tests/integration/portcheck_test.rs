@@ -0,0 +1,65 @@
+use axum::http::StatusCode;
+use tokio::net::TcpListener;
+
+use super::common::{get_from, get_json_from, test_config};
+
+async fn portcheck(ports: &[u16], port: u16, peer: [u8; 4]) -> (StatusCode, serde_json::Value) {
+    let mut config = test_config();
+    config.portcheck_ports = ports.to_vec();
+    get_json_from(config, &format!("/portcheck/{port}"), peer).await
+}
+
+#[tokio::test]
+async fn test_portcheck_disabled_without_an_allowlist() {
+    let (status, body) = portcheck(&[], 22, [127, 0, 0, 1]).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(body["error"].as_str().unwrap().contains("PORTCHECK_PORTS"));
+}
+
+#[tokio::test]
+async fn test_portcheck_refuses_ports_outside_the_allowlist() {
+    let (status, body) = portcheck(&[80, 443], 22, [127, 0, 0, 1]).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(body["error"].as_str().unwrap().contains("port 22"));
+}
+
+#[tokio::test]
+async fn test_portcheck_refuses_non_public_clients() {
+    let (status, body) = portcheck(&[22], 22, [127, 0, 0, 1]).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(body["error"].as_str().unwrap().contains("127.0.0.1"));
+
+    let (status, _) = portcheck(&[22], 22, [192, 168, 1, 20]).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_portcheck_rejects_invalid_ports() {
+    let response = get_from(test_config(), "/portcheck/70000", [127, 0, 0, 1]).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_portcheck_reports_open_and_closed_ports() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let mut config = test_config();
+    config.portcheck_ports = vec![port];
+    config.outbound_allow_private = true;
+
+    let (status, body) = get_json_from(
+        config.clone(),
+        &format!("/portcheck/{port}"),
+        [127, 0, 0, 1],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["ip"], "127.0.0.1");
+    assert_eq!(body["port"], port);
+    assert_eq!(body["state"], "open");
+
+    drop(listener);
+    let (status, body) = get_json_from(config, &format!("/portcheck/{port}"), [127, 0, 0, 1]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["state"], "closed");
+}
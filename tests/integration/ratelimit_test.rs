@@ -110,3 +110,73 @@ async fn test_rate_limit_returns_json_error() {
     let body_str = String::from_utf8(body.to_vec()).unwrap();
     assert!(body_str.contains("Too Many Requests"));
 }
+
+#[tokio::test]
+async fn test_rate_limit_sets_retry_after() {
+    let state = strict_rate_limit_state(IpLookupTable::empty());
+    let app = build_router(state);
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], 12345));
+    let request = || {
+        Request::builder()
+            .uri("/")
+            .extension(ConnectInfo(addr))
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = app.clone().oneshot(request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // 1 rps: the next token is at most a second away.
+    assert_eq!(response.headers()["retry-after"], "1");
+}
+
+#[tokio::test]
+async fn test_rate_limit_keys_on_forwarded_client_behind_trusted_proxy() {
+    let state = strict_rate_limit_state(IpLookupTable::empty());
+    let app = build_router(state);
+
+    // 10.0.0.0/8 is trusted in test_config, so each X-Forwarded-For client
+    // gets its own bucket even though they share a peer address.
+    let proxy = SocketAddr::from(([10, 0, 0, 1], 12345));
+    let request = |client: &str| {
+        Request::builder()
+            .uri("/")
+            .header("x-forwarded-for", client)
+            .extension(ConnectInfo(proxy))
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(request("203.0.113.1")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = app.clone().oneshot(request("203.0.113.2")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = app.clone().oneshot(request("203.0.113.1")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn test_rate_limit_ignores_forwarded_header_from_untrusted_peer() {
+    let state = strict_rate_limit_state(IpLookupTable::empty());
+    let app = build_router(state);
+
+    // A direct client can't dodge its limit by rotating X-Forwarded-For.
+    let addr = SocketAddr::from(([198, 51, 100, 7], 12345));
+    let request = |client: &str| {
+        Request::builder()
+            .uri("/")
+            .header("x-forwarded-for", client)
+            .extension(ConnectInfo(addr))
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(request("203.0.113.1")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = app.clone().oneshot(request("203.0.113.2")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+}
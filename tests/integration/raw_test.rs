@@ -0,0 +1,34 @@
+use std::net::SocketAddr;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+use ipecho::lookup::IpLookupTable;
+
+use super::common::{build_router, test_state_with_table};
+
+#[tokio::test]
+async fn test_raw_reconstructs_without_a_captured_head() {
+    let app = build_router(test_state_with_table(IpLookupTable::empty()));
+    let req = Request::builder()
+        .method("PUT")
+        .uri("/raw?x=1")
+        .header("X-Custom", "a")
+        .header("Accept", "*/*")
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::from("payload"))
+        .unwrap();
+    let response = app.oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/plain");
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let dump = String::from_utf8(body.to_vec()).unwrap();
+    assert!(
+        dump.starts_with("PUT /raw?x=1 HTTP/1.1\r\nx-custom: a\r\naccept: */*\r\n"),
+        "got: {dump}"
+    );
+    assert!(dump.ends_with("\r\n\r\npayload"), "got: {dump}");
+}
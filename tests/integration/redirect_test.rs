@@ -0,0 +1,66 @@
+use std::net::SocketAddr;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode};
+use tower::ServiceExt;
+
+use ipecho::lookup::IpLookupTable;
+
+use super::common::{build_router, test_state_with_table};
+
+async fn get(uri: &str) -> (StatusCode, Option<String>) {
+    let app = build_router(test_state_with_table(IpLookupTable::empty()));
+    let req = Request::builder()
+        .uri(uri)
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(req).await.unwrap();
+    let location = response
+        .headers()
+        .get("location")
+        .map(|v| v.to_str().unwrap().to_string());
+    (response.status(), location)
+}
+
+#[tokio::test]
+async fn test_redirect_chain_ends_at_get() {
+    assert_eq!(
+        get("/redirect/3").await,
+        (StatusCode::FOUND, Some("2".to_string()))
+    );
+    assert_eq!(
+        get("/redirect/2?status=307").await,
+        (
+            StatusCode::TEMPORARY_REDIRECT,
+            Some("1?status=307".to_string())
+        )
+    );
+    assert_eq!(
+        get("/redirect/1?status=307").await,
+        (StatusCode::TEMPORARY_REDIRECT, Some("../get".to_string()))
+    );
+    assert_eq!(get("/redirect/0").await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(get("/redirect/100").await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        get("/redirect/1?status=200").await.0,
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[tokio::test]
+async fn test_redirect_to_stays_on_this_server() {
+    assert_eq!(
+        get("/redirect-to?url=%2Fanything%3Fa%3D1&status=301").await,
+        (
+            StatusCode::MOVED_PERMANENTLY,
+            Some("/anything?a=1".to_string())
+        )
+    );
+    assert_eq!(
+        get("/redirect-to?url=https%3A%2F%2Fexample.com").await.0,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(get("/redirect-to").await.0, StatusCode::BAD_REQUEST);
+}
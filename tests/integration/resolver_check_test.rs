@@ -0,0 +1,89 @@
+use std::net::SocketAddr;
+
+use axum::Router;
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+use ipecho::lookup::IpLookupTable;
+use ipecho::resolver_check::ResolverQuery;
+use ipecho::state::AppState;
+
+use super::common::{build_router, test_config, test_state, throwaway_metrics_handle};
+
+fn state(dns: bool) -> AppState {
+    let mut config = test_config();
+    config.dns_zone = Some("whoami.example.com".to_string());
+    if dns {
+        config.dns_addr = Some("127.0.0.1:5353".parse().unwrap());
+    }
+    test_state(config, throwaway_metrics_handle(), IpLookupTable::empty())
+}
+
+async fn get(app: &Router, uri: &str) -> (StatusCode, serde_json::Value) {
+    let req = Request::builder()
+        .uri(uri)
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(req).await.unwrap();
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap_or_default())
+}
+
+#[tokio::test]
+async fn test_disabled_without_the_dns_server() {
+    let (status, body) = get(&build_router(state(false)), "/resolver-check").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(body["error"].as_str().unwrap().contains("DNS_ADDR"));
+}
+
+#[tokio::test]
+async fn test_reports_the_queries_the_dns_server_saw() {
+    let state = state(true);
+    let check = state.resolver_check.clone();
+    let app = build_router(state);
+
+    let (status, start) = get(&app, "/resolver-check").await;
+    assert_eq!(status, StatusCode::OK);
+    let token = start["token"].as_str().unwrap();
+    assert_eq!(start["hostname"], format!("{token}.whoami.example.com"));
+    assert_eq!(start["result_path"], format!("/resolver-check/{token}"));
+    assert_eq!(start["expires_in_secs"], 300);
+
+    let result_path = format!("/resolver-check/{token}");
+    let (status, result) = get(&app, &result_path).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(result["resolver"], serde_json::Value::Null);
+    assert_eq!(result["queries"], serde_json::json!([]));
+
+    // As the DNS server records a lookup of the name.
+    check.record(
+        token,
+        ResolverQuery {
+            resolver: "198.51.100.53".parse().unwrap(),
+            ecs: Some("203.0.113.0/24".to_string()),
+            dnssec_ok: true,
+            transport: "udp",
+            query_type: "A".to_string(),
+        },
+    );
+    let (_, result) = get(&app, &result_path).await;
+    assert_eq!(result["resolver"], "198.51.100.53");
+    assert_eq!(
+        result["queries"],
+        serde_json::json!([{
+            "resolver": "198.51.100.53",
+            "ecs": "203.0.113.0/24",
+            "dnssec_ok": true,
+            "transport": "udp",
+            "type": "A",
+        }])
+    );
+
+    let (status, _) = get(&app, "/resolver-check/0123456789abcdef").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,156 @@
+use std::net::SocketAddr;
+
+use axum::Router;
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+use ipecho::{ClientIp, ClientIpConfig};
+
+use super::common::test_config;
+
+fn request(uri: &str) -> Request<Body> {
+    Request::builder()
+        .uri(uri)
+        .extension(ConnectInfo(SocketAddr::from(([203, 0, 113, 5], 4000))))
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_router_mounts_under_a_prefix() {
+    let app = Router::new()
+        .route("/", axum::routing::get(|| async { "host app" }))
+        .nest("/echo", ipecho::router(test_config()));
+
+    let response = app.clone().oneshot(request("/echo/ip")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(&body[..], b"203.0.113.5");
+
+    let response = app.clone().oneshot(request("/echo/healthz")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app.oneshot(request("/")).await.unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(&body[..], b"host app");
+}
+
+/// `/echo` nesting the echo router, as a host app would mount it.
+fn nested() -> Router {
+    Router::new().nest("/echo", ipecho::router(test_config()))
+}
+
+/// Follow redirects from `uri`, resolving each `Location` as a client
+/// would, and return the path and query that finally answered.
+async fn follow(app: &Router, uri: &str) -> String {
+    let mut url = url::Url::parse(&format!("http://localhost{uri}")).unwrap();
+    loop {
+        let target = &url[url::Position::BeforePath..];
+        let response = app.clone().oneshot(request(target)).await.unwrap();
+        if !response.status().is_redirection() {
+            assert_eq!(response.status(), StatusCode::OK, "{target}");
+            return target.to_string();
+        }
+        let location = response.headers()["location"].to_str().unwrap();
+        url = url.join(location).unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_redirects_stay_under_a_prefix() {
+    let app = nested();
+    assert_eq!(follow(&app, "/echo/redirect/3").await, "/echo/get");
+    assert_eq!(
+        follow(&app, "/echo/redirect/2?status=307").await,
+        "/echo/get"
+    );
+}
+
+#[tokio::test]
+async fn test_cookie_redirects_stay_under_a_prefix() {
+    let app = nested();
+    assert_eq!(
+        follow(&app, "/echo/cookies/set?theme=dark").await,
+        "/echo/cookies"
+    );
+    assert_eq!(
+        follow(&app, "/echo/cookies/delete?theme").await,
+        "/echo/cookies"
+    );
+}
+
+#[tokio::test]
+async fn test_resolver_check_result_path_is_under_a_prefix() {
+    let mut config = test_config();
+    config.dns_addr = Some("127.0.0.1:5353".parse().unwrap());
+    config.dns_zone = Some("whoami.example.com".to_string());
+    let app = Router::new().nest("/echo", ipecho::router(config));
+
+    let response = app
+        .clone()
+        .oneshot(request("/echo/resolver-check"))
+        .await
+        .unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let start: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let token = start["token"].as_str().unwrap();
+    let result_path = start["result_path"].as_str().unwrap();
+    assert_eq!(result_path, format!("/echo/resolver-check/{token}"));
+
+    let response = app.oneshot(request(result_path)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[cfg(feature = "html")]
+#[tokio::test]
+async fn test_landing_page_links_stay_under_a_prefix() {
+    for uri in ["/echo", "/echo/all.html"] {
+        let mut request = request(uri);
+        request
+            .headers_mut()
+            .insert("accept", "text/html".parse().unwrap());
+        let response = nested().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let html = std::str::from_utf8(&body).unwrap();
+        let link = html.split(r#"<a href=""#).nth(1).unwrap();
+        let link = &link[..link.find('"').unwrap()];
+        let resolved = url::Url::parse(&format!("http://localhost{uri}"))
+            .unwrap()
+            .join(link)
+            .unwrap();
+        assert_eq!(resolved.path(), "/echo/ip", "{uri}");
+    }
+}
+
+#[tokio::test]
+async fn test_client_ip_extractor_uses_trusted_proxies() {
+    let app = Router::new()
+        .route(
+            "/",
+            axum::routing::get(|ClientIp(ip): ClientIp| async move { ip.to_string() }),
+        )
+        .with_state(ClientIpConfig::new(test_config()));
+
+    for (peer, expected) in [
+        ([10, 0, 0, 1], "198.51.100.7"),
+        ([203, 0, 113, 5], "203.0.113.5"),
+    ] {
+        let request = Request::builder()
+            .uri("/")
+            .header("x-forwarded-for", "198.51.100.7")
+            .extension(ConnectInfo(SocketAddr::from((peer, 4000))))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], expected.as_bytes());
+    }
+
+    // Without ConnectInfo there is no peer to start from.
+    let response = app.oneshot(Request::new(Body::empty())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+}
@@ -0,0 +1,52 @@
+use std::net::SocketAddr;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode, header};
+use tower::ServiceExt;
+
+use ipecho::lookup::IpLookupTable;
+
+use super::common::{build_router, test_config, test_state, throwaway_metrics_handle};
+
+#[tokio::test]
+async fn test_landing_page_gets_csp_and_json_does_not() {
+    let mut config = test_config();
+    config.security_headers = true;
+    let app = build_router(test_state(
+        config,
+        throwaway_metrics_handle(),
+        IpLookupTable::empty(),
+    ));
+    let addr = SocketAddr::from(([203, 0, 113, 5], 4000));
+
+    let req = Request::builder()
+        .uri("/")
+        .header(header::ACCEPT, "text/html")
+        .extension(ConnectInfo(addr))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let headers = response.headers();
+    assert!(headers.contains_key(header::CONTENT_SECURITY_POLICY));
+    assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+    assert_eq!(headers[header::REFERRER_POLICY], "no-referrer");
+    assert!(headers.contains_key(header::STRICT_TRANSPORT_SECURITY));
+
+    let req = Request::builder()
+        .uri("/all.json")
+        .extension(ConnectInfo(addr))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(req).await.unwrap();
+    assert!(
+        !response
+            .headers()
+            .contains_key(header::CONTENT_SECURITY_POLICY)
+    );
+    assert_eq!(
+        response.headers()[header::X_CONTENT_TYPE_OPTIONS],
+        "nosniff"
+    );
+}
@@ -0,0 +1,116 @@
+use std::net::SocketAddr;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, Response, StatusCode, header};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+use ipecho::config::Config;
+use ipecho::lookup::IpLookupTable;
+
+use super::common::{build_router, test_config, test_state, throwaway_metrics_handle};
+
+async fn send(config: Config, req: Request<Body>) -> Response<Body> {
+    let app = build_router(test_state(
+        config,
+        throwaway_metrics_handle(),
+        IpLookupTable::empty(),
+    ));
+    let mut req = req;
+    req.extensions_mut()
+        .insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))));
+    app.oneshot(req).await.unwrap()
+}
+
+fn get(uri: &str) -> Request<Body> {
+    Request::builder().uri(uri).body(Body::empty()).unwrap()
+}
+
+#[tokio::test]
+async fn test_download_streams_exactly_the_requested_size() {
+    let response = send(test_config(), get("/speedtest/download/200KiB")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_LENGTH], "204800");
+    assert!(response.headers().contains_key("x-speedtest-start-ms"));
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(body.len(), 200 * 1024);
+    // Random, so nothing on the path can compress it.
+    assert!(body.iter().any(|&b| b != body[0]));
+}
+
+#[tokio::test]
+async fn test_download_size_is_bounded() {
+    let mut config = test_config();
+    config.max_speedtest_bytes = 1000;
+    let response = send(config, get("/speedtest/download/1k")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let mut config = test_config();
+    config.max_speedtest_bytes = 1000;
+    let response = send(config, get("/speedtest/download/1001")).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = send(test_config(), get("/speedtest/download/lots")).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_disabled_with_a_zero_limit() {
+    let mut config = test_config();
+    config.max_speedtest_bytes = 0;
+    let response = send(config, get("/speedtest/download/1k")).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+fn upload(body: Body) -> Request<Body> {
+    Request::post("/speedtest/upload").body(body).unwrap()
+}
+
+#[tokio::test]
+async fn test_upload_counts_the_body() {
+    // Over MAX_BODY_BYTES, which the upload sink isn't held to.
+    let mut config = test_config();
+    config.max_body_bytes = 1024;
+    let response = send(config, upload(Body::from(vec![7u8; 300_000]))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["bytes"], 300_000);
+    assert!(json["elapsed_ms"].as_f64().unwrap() >= 0.0);
+    assert!(json["mbps"].is_number());
+}
+
+#[tokio::test]
+async fn test_upload_is_bounded() {
+    let mut config = test_config();
+    config.max_speedtest_bytes = 1000;
+    let response = send(config.clone(), upload(Body::from(vec![0u8; 1001]))).await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    // Without a Content-Length, cut off as it streams.
+    let chunks = [Ok::<_, std::io::Error>(vec![0u8; 600]), Ok(vec![0u8; 600])];
+    let response = send(
+        config,
+        upload(Body::from_stream(futures::stream::iter(chunks))),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn test_upload_over_the_limit_gets_a_json_413() {
+    let mut config = test_config();
+    config.max_speedtest_bytes = 1000;
+    let chunks = [Ok::<_, std::io::Error>(vec![0u8; 600]), Ok(vec![0u8; 600])];
+    let response = send(
+        config,
+        upload(Body::from_stream(futures::stream::iter(chunks))),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"], "Request body larger than 1000 bytes");
+}
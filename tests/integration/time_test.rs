@@ -0,0 +1,90 @@
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+use ipecho::lookup::IpLookupTable;
+
+use super::common::{build_router, test_state_with_table};
+
+async fn get(uri: &str) -> (StatusCode, String) {
+    get_with(uri, &[]).await
+}
+
+async fn get_with(uri: &str, headers: &[(&str, &str)]) -> (StatusCode, String) {
+    let app = build_router(test_state_with_table(IpLookupTable::empty()));
+    let mut req = Request::builder().uri(uri);
+    for (name, value) in headers {
+        req = req.header(*name, *value);
+    }
+    let req = req
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(req).await.unwrap();
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+#[tokio::test]
+async fn test_time_text_and_json() {
+    let (status, body) = get("/time").await;
+    assert_eq!(status, StatusCode::OK);
+    let keys: Vec<&str> = body
+        .lines()
+        .filter_map(|l| l.split_once(": "))
+        .map(|(k, _)| k)
+        .collect();
+    assert_eq!(keys, ["rfc3339", "unix", "unix_ms", "http_date"]);
+
+    let before = now_ms();
+    let (status, body) = get("/time?format=json").await;
+    assert_eq!(status, StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let unix_ms = json["unix_ms"].as_u64().unwrap();
+    assert!((before..=now_ms()).contains(&unix_ms));
+    assert_eq!(json["unix"].as_u64().unwrap(), unix_ms / 1000);
+    assert!(json["rfc3339"].as_str().unwrap().ends_with('Z'));
+    assert!(json["http_date"].as_str().unwrap().ends_with(" GMT"));
+}
+
+#[tokio::test]
+async fn test_clock_skew_from_query_and_date() {
+    let ahead = now_ms() + 60_000;
+    let (status, body) = get(&format!("/clock-skew?t={ahead}&format=json")).await;
+    assert_eq!(status, StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["source"], "query");
+    assert_eq!(json["precision_ms"], 1);
+    let offset = json["offset_ms"].as_i64().unwrap();
+    assert!((59_000..=60_000).contains(&offset), "{offset}");
+
+    let behind = httpdate::fmt_http_date(SystemTime::now() - std::time::Duration::from_secs(3600));
+    let (status, body) = get_with("/clock-skew?format=json", &[("date", &behind)]).await;
+    assert_eq!(status, StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["source"], "date_header");
+    let offset = json["offset_ms"].as_i64().unwrap();
+    assert!((-3_602_000..=-3_599_000).contains(&offset), "{offset}");
+    assert!(json["summary"].as_str().unwrap().ends_with("behind"));
+}
+
+#[tokio::test]
+async fn test_clock_skew_needs_a_client_time() {
+    assert_eq!(get("/clock-skew").await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(get("/clock-skew?t=soon").await.0, StatusCode::BAD_REQUEST);
+    let (status, _) = get_with("/clock-skew", &[("date", "not a date")]).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
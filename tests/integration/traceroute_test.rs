@@ -0,0 +1,57 @@
+use axum::http::{StatusCode, header};
+use http_body_util::BodyExt;
+
+use ipecho::config::Config;
+
+use super::common::{get_from, get_json_from, test_config};
+
+fn enabled() -> Config {
+    let mut config = test_config();
+    config.trace_enabled = true;
+    config
+}
+
+#[tokio::test]
+async fn test_trace_me_disabled_by_default() {
+    let (status, body) = get_json_from(test_config(), "/trace-me", [127, 0, 0, 1]).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(body["error"].as_str().unwrap().contains("TRACE_ENABLED"));
+}
+
+#[tokio::test]
+async fn test_trace_me_refuses_non_public_clients() {
+    let (status, body) = get_json_from(enabled(), "/trace-me", [10, 0, 0, 9]).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(body["error"].as_str().unwrap().contains("10.0.0.9"));
+}
+
+#[tokio::test]
+async fn test_trace_me_streams_hops_to_loopback() {
+    let mut config = enabled();
+    config.outbound_allow_private = true;
+    let response = get_from(config, "/trace-me", [127, 0, 0, 1]).await;
+    let status = response.status();
+    let content_type = response.headers()[header::CONTENT_TYPE].clone();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    // Without CAP_NET_RAW, as CI may run, no raw socket opens and that's a
+    // 404 naming what's missing.
+    if status == StatusCode::NOT_FOUND {
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(body["error"].as_str().unwrap().contains("CAP_NET_RAW"));
+        return;
+    }
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "application/x-ndjson");
+
+    let hops: Vec<serde_json::Value> = std::str::from_utf8(&body)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    // Loopback is one hop away.
+    assert_eq!(hops.len(), 1);
+    assert_eq!(hops[0]["hop"], 1);
+    assert_eq!(hops[0]["ip"], "127.0.0.1");
+    assert_eq!(hops[0]["reply"], "echo_reply");
+    assert!(hops[0]["rtt_ms"].as_f64().unwrap() >= 0.0);
+}
@@ -0,0 +1,49 @@
+use std::net::SocketAddr;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+use ipecho::lookup::IpLookupTable;
+
+use super::common::{build_router, test_state_with_table};
+
+async fn get(uri: &str) -> (StatusCode, String) {
+    let app = build_router(test_state_with_table(IpLookupTable::empty()));
+    let req = Request::builder()
+        .uri(uri)
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 12345))))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(req).await.unwrap();
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn test_uuid_text_and_json() {
+    let (status, body) = get("/uuid").await;
+    assert_eq!(status, StatusCode::OK);
+    let uuid = uuid::Uuid::parse_str(body.trim()).unwrap();
+    assert_eq!(uuid.get_version_num(), 4);
+
+    let (status, body) = get("/uuid?count=3&version=7&format=json").await;
+    assert_eq!(status, StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let uuids = json["uuids"].as_array().unwrap();
+    assert_eq!(uuids.len(), 3);
+    for uuid in uuids {
+        let uuid = uuid::Uuid::parse_str(uuid.as_str().unwrap()).unwrap();
+        assert_eq!(uuid.get_version_num(), 7);
+    }
+}
+
+#[tokio::test]
+async fn test_uuid_rejects_bad_parameters() {
+    assert_eq!(get("/uuid?count=0").await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(get("/uuid?count=101").await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(get("/uuid?version=1").await.0, StatusCode::BAD_REQUEST);
+}